@@ -0,0 +1,21 @@
+-- Create export_jobs table for asynchronous, content-addressed export artifacts
+CREATE TABLE IF NOT EXISTS export_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    params_hash VARCHAR(64) NOT NULL,         -- sha256 of the normalized filter parameters
+    params JSONB NOT NULL,
+    format VARCHAR(10) NOT NULL,              -- 'csv' or 'ndjson'
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    storage_key TEXT,                         -- key of the artifact in the storage backend
+    content_hash VARCHAR(64),                 -- sha256 of the artifact bytes, used as strong ETag
+    size_bytes BIGINT,
+    row_count BIGINT,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- Lookup of a reusable artifact for identical parameters
+CREATE INDEX idx_export_jobs_params_hash ON export_jobs(params_hash, created_at DESC);
+-- Sweeper scans
+CREATE INDEX idx_export_jobs_expires_at ON export_jobs(expires_at);
@@ -0,0 +1,12 @@
+-- Track issuer home-domain verification for registry assets
+ALTER TABLE assets
+    ADD COLUMN IF NOT EXISTS verification_status VARCHAR(20) NOT NULL DEFAULT 'unverified', -- 'verified' or 'unverified'
+    ADD COLUMN IF NOT EXISTS verification_error TEXT,
+    ADD COLUMN IF NOT EXISTS verified_at TIMESTAMPTZ;
+
+-- Re-verification task scans by last verification time
+CREATE INDEX IF NOT EXISTS idx_assets_verified_at ON assets(verified_at);
+
+INSERT INTO feature_flags (name, enabled, description) VALUES
+    ('refuse_unverified_asset_deposits', false, 'Reject deposit callbacks for assets whose issuer home domain could not be verified')
+ON CONFLICT (name) DO NOTHING;
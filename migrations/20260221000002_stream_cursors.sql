@@ -0,0 +1,16 @@
+-- Persisted Horizon streaming cursors, one row per listener
+CREATE TABLE IF NOT EXISTS stream_cursors (
+    name VARCHAR(100) PRIMARY KEY,
+    cursor TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Effects already handled, so replays after a cursor reset are no-ops
+CREATE TABLE IF NOT EXISTS processed_effects (
+    effect_id TEXT PRIMARY KEY,
+    processed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO feature_flags (name, enabled, description) VALUES
+    ('trustline_effects_listener', false, 'Watch Horizon effects for removed or deauthorized trustlines on our assets')
+ON CONFLICT (name) DO NOTHING;
@@ -0,0 +1,5 @@
+-- Hash of the Stellar payment that paid the transaction out
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS payout_tx_hash VARCHAR(64);
+
+CREATE INDEX IF NOT EXISTS idx_transactions_payout_tx_hash ON transactions(payout_tx_hash);
+CREATE INDEX IF NOT EXISTS idx_transactions_anchor_transaction_id ON transactions(anchor_transaction_id);
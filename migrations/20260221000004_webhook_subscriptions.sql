@@ -0,0 +1,31 @@
+-- Outbound webhook subscriptions and their delivery queue
+CREATE TABLE IF NOT EXISTS webhook_subscriptions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    url TEXT NOT NULL,
+    event_types TEXT[] NOT NULL DEFAULT '{}',   -- empty means all events
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    max_in_flight INTEGER,                      -- overrides WEBHOOK_MAX_IN_FLIGHT when set
+    cooldown_until TIMESTAMPTZ,                 -- no deliveries are claimed before this, on any instance
+    cooldown_reason VARCHAR(20),                -- 'saturated' or 'failure_rate'
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    subscription_id UUID NOT NULL REFERENCES webhook_subscriptions(id) ON DELETE CASCADE,
+    event_type VARCHAR(100) NOT NULL,
+    payload JSONB NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',  -- pending, delivering, delivered, failed
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    locked_until TIMESTAMPTZ,                       -- claim lease; expired leases are reclaimed
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMPTZ
+);
+
+-- Claim scans per subscription
+CREATE INDEX idx_webhook_deliveries_claim
+    ON webhook_deliveries(subscription_id, next_attempt_at)
+    WHERE status IN ('pending', 'delivering');
@@ -0,0 +1,11 @@
+-- Verbatim callback bodies, kept for debugging anchor payload issues.
+-- Captured best-effort: a failed capture never blocks the transaction insert.
+CREATE TABLE IF NOT EXISTS raw_callbacks (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    transaction_id UUID NOT NULL,
+    schema_version VARCHAR(10) NOT NULL,
+    body TEXT NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_raw_callbacks_transaction_id ON raw_callbacks(transaction_id);
@@ -0,0 +1,25 @@
+-- Monotonic generation for feature flag mutations, so cache writers can tell
+-- which of two racing reads is newer.
+CREATE TABLE IF NOT EXISTS flag_generations (
+    id BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+    generation BIGINT NOT NULL
+);
+
+INSERT INTO flag_generations (id, generation) VALUES (true, 0)
+ON CONFLICT (id) DO NOTHING;
+
+ALTER TABLE feature_flags ADD COLUMN IF NOT EXISTS generation BIGINT NOT NULL DEFAULT 0;
+
+-- Every insert or update takes the next generation. The row lock on
+-- flag_generations is held until commit, so generations follow commit order.
+CREATE OR REPLACE FUNCTION bump_flag_generation() RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE flag_generations SET generation = generation + 1
+    RETURNING generation INTO NEW.generation;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER feature_flags_generation
+    BEFORE INSERT OR UPDATE ON feature_flags
+    FOR EACH ROW EXECUTE FUNCTION bump_flag_generation();
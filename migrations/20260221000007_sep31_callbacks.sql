@@ -0,0 +1,10 @@
+-- SEP-31 statuses are longer than the Anchor Platform ones
+-- (pending_transaction_info_update is 31 characters)
+ALTER TABLE transactions ALTER COLUMN callback_status TYPE VARCHAR(64);
+
+-- Transactions that need an operator to look at them, e.g. after a callback
+-- with a status we do not know how to map
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS needs_review BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS review_reason TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_transactions_needs_review ON transactions(created_at) WHERE needs_review;
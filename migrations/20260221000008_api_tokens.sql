@@ -0,0 +1,12 @@
+-- Scoped, expiring API tokens for third-party read access
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(100) NOT NULL,
+    token_prefix VARCHAR(16) NOT NULL,       -- first characters of the secret, for identification only
+    token_hash CHAR(64) NOT NULL UNIQUE,     -- sha256 of the secret; the secret itself is never stored
+    scope JSONB NOT NULL,                    -- allowed routes and injected filters
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    created_by VARCHAR(100) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
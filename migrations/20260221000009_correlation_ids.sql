@@ -0,0 +1,8 @@
+-- Correlation id shared by everything that touches a transaction's lifecycle
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS correlation_id VARCHAR(128);
+CREATE INDEX IF NOT EXISTS idx_transactions_correlation_id ON transactions(correlation_id);
+
+ALTER TABLE webhook_deliveries ADD COLUMN IF NOT EXISTS correlation_id VARCHAR(128);
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_correlation_id
+    ON webhook_deliveries(correlation_id)
+    WHERE correlation_id IS NOT NULL;
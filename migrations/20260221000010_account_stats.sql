@@ -0,0 +1,30 @@
+-- Per-account, per-asset deposit aggregates, maintained alongside transaction
+-- writes so lookups never scan history
+CREATE TABLE IF NOT EXISTS account_stats (
+    stellar_account VARCHAR(56) NOT NULL,
+    asset_code VARCHAR(12) NOT NULL,
+    total_completed NUMERIC NOT NULL DEFAULT 0,
+    total_pending NUMERIC NOT NULL DEFAULT 0,   -- pending, processing and pending_trustline
+    first_deposit_at TIMESTAMPTZ,
+    last_deposit_at TIMESTAMPTZ,
+    deposit_count BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (stellar_account, asset_code)
+);
+
+-- Backfill from existing history
+INSERT INTO account_stats (
+    stellar_account, asset_code, total_completed, total_pending,
+    first_deposit_at, last_deposit_at, deposit_count
+)
+SELECT
+    stellar_account,
+    asset_code,
+    COALESCE(SUM(amount) FILTER (WHERE status = 'completed'), 0),
+    COALESCE(SUM(amount) FILTER (WHERE status IN ('pending', 'processing', 'pending_trustline')), 0),
+    MIN(created_at),
+    MAX(created_at),
+    COUNT(*)
+FROM transactions
+GROUP BY stellar_account, asset_code
+ON CONFLICT (stellar_account, asset_code) DO NOTHING;
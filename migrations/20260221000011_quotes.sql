@@ -0,0 +1,22 @@
+-- Firm SEP-38 quotes accepted from the Anchor Platform, and the withdrawal
+-- transaction that used each one
+CREATE TABLE IF NOT EXISTS quotes (
+    id VARCHAR(64) PRIMARY KEY,              -- quote id assigned by the Anchor Platform
+    sell_asset VARCHAR(100) NOT NULL,        -- SEP-38 asset identifiers, e.g. stellar:USDC:G...
+    buy_asset VARCHAR(100) NOT NULL,
+    sell_amount NUMERIC NOT NULL,
+    buy_amount NUMERIC NOT NULL,
+    rate NUMERIC NOT NULL,                   -- SEP-38 price: sell units per buy unit, before fees
+    total_price NUMERIC NOT NULL,            -- including fees
+    fee_total NUMERIC NOT NULL,
+    fee_asset VARCHAR(100) NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    transaction_id UUID UNIQUE,              -- set once, when a withdrawal callback uses the quote
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_quotes_unused_expires_at
+    ON quotes(expires_at)
+    WHERE transaction_id IS NULL;
+
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS quote_id VARCHAR(64);
@@ -0,0 +1,4 @@
+-- Keep the status snapshot's queue depth count cheap as export_jobs grows
+CREATE INDEX IF NOT EXISTS idx_export_jobs_queued
+    ON export_jobs(status)
+    WHERE status IN ('pending', 'running');
@@ -0,0 +1,23 @@
+-- Callbacks acknowledged asynchronously, waiting to be stored as transactions
+CREATE TABLE IF NOT EXISTS ingestion_outbox (
+    id UUID PRIMARY KEY,                        -- provisional id; becomes transactions.id
+    anchor_transaction_id VARCHAR(255),
+    payload JSONB NOT NULL,                     -- the transaction to insert
+    schema_version VARCHAR(8),                  -- raw callback capture, if any
+    raw_body TEXT,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',  -- pending, failed
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- A replayed callback finds the queued entry instead of queueing another
+CREATE UNIQUE INDEX IF NOT EXISTS idx_ingestion_outbox_anchor_transaction_id
+    ON ingestion_outbox(anchor_transaction_id)
+    WHERE anchor_transaction_id IS NOT NULL;
+
+-- Drainer claims
+CREATE INDEX IF NOT EXISTS idx_ingestion_outbox_claim
+    ON ingestion_outbox(next_attempt_at)
+    WHERE status = 'pending';
@@ -0,0 +1,17 @@
+-- Localized, human-readable summaries attached to outbound webhook events
+ALTER TABLE webhook_subscriptions
+    ADD COLUMN IF NOT EXISTS locale VARCHAR(35) NOT NULL DEFAULT 'en';  -- BCP 47 tag used to pick templates
+
+CREATE TABLE IF NOT EXISTS notification_templates (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    event_type VARCHAR(100) NOT NULL,
+    locale VARCHAR(35) NOT NULL,
+    template TEXT NOT NULL,  -- Fluent pattern; variables are validated per event type
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (event_type, locale)
+);
+
+INSERT INTO notification_templates (event_type, locale, template) VALUES
+    ('transaction.created', 'en', E'{ $callback_type ->\n    [deposit] Your deposit of { $amount } { $asset_code } is being processed\n    [withdrawal] Your withdrawal of { $amount } { $asset_code } is being processed\n   *[other] Your transaction of { $amount } { $asset_code } is being processed\n}')
+ON CONFLICT (event_type, locale) DO NOTHING;
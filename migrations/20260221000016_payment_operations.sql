@@ -0,0 +1,21 @@
+-- Horizon operations already handled by the payment listener, so replays
+-- after a cursor reset are no-ops. `transaction_id` is the matched
+-- transaction, if any.
+CREATE TABLE IF NOT EXISTS processed_operations (
+    operation_id TEXT PRIMARY KEY,
+    operation_type VARCHAR(64) NOT NULL,
+    transaction_id UUID,
+    processed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Reconciliation details recorded when a transaction is matched, such as
+-- both legs of a cross-asset path payment
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS metadata JSONB;
+
+CREATE INDEX IF NOT EXISTS idx_transactions_pending_anchor_id
+    ON transactions(anchor_transaction_id)
+    WHERE status = 'pending';
+
+INSERT INTO feature_flags (name, enabled, description) VALUES
+    ('payment_operations_listener', false, 'Match incoming payments, path payments and account merges to pending transactions')
+ON CONFLICT (name) DO NOTHING;
@@ -0,0 +1,28 @@
+-- Account erasure runs. A completed run that was not a dry run is the
+-- erasure certificate. The address is only kept while the run is pending
+-- or running; the certificate identifies the account by its sha256.
+CREATE TABLE IF NOT EXISTS erasure_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    account_hash VARCHAR(64) NOT NULL,
+    stellar_account VARCHAR(56),
+    dry_run BOOLEAN NOT NULL,
+    block_callbacks BOOLEAN NOT NULL DEFAULT FALSE,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',  -- pending, running, completed, failed
+    policy JSONB NOT NULL,                          -- action per category at request time
+    report JSONB,                                   -- rows affected per category
+    error TEXT,
+    requested_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMPTZ,
+    completed_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_erasure_jobs_account_hash ON erasure_jobs(account_hash, created_at DESC);
+
+-- Accounts whose callbacks are rejected, by sha256 of the address
+CREATE TABLE IF NOT EXISTS account_watchlist (
+    account_hash VARCHAR(64) PRIMARY KEY,
+    reason VARCHAR(50) NOT NULL,
+    erasure_id UUID REFERENCES erasure_jobs(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
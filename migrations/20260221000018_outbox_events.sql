@@ -0,0 +1,26 @@
+-- Every outbound event, in commit order of its sequence number, for the
+-- admin event stream and its Last-Event-ID replay. Webhook deliveries fan
+-- out from the same write.
+CREATE TABLE IF NOT EXISTS outbox_events (
+    seq BIGSERIAL PRIMARY KEY,
+    event_type VARCHAR(100) NOT NULL,
+    asset_code VARCHAR(12),                     -- copied from the payload for stream filters
+    payload JSONB NOT NULL,
+    correlation_id VARCHAR(128),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_outbox_events_created_at ON outbox_events(created_at);
+
+-- Wakes the in-process relays; delivered only when the writing
+-- transaction commits
+CREATE OR REPLACE FUNCTION notify_outbox_event() RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM pg_notify('outbox_events', NEW.seq::text);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER outbox_events_notify
+    AFTER INSERT ON outbox_events
+    FOR EACH ROW EXECUTE FUNCTION notify_outbox_event();
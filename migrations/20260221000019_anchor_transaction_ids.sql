@@ -0,0 +1,18 @@
+-- One row per anchor transaction id, claimed in the same database
+-- transaction that stores the callback's transaction. `transactions` is
+-- partitioned by created_at, so a unique index there would have to include
+-- it and could not stop a replay arriving a second later.
+CREATE TABLE IF NOT EXISTS anchor_transaction_ids (
+    anchor_transaction_id VARCHAR(255) PRIMARY KEY,
+    transaction_id UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Existing rows are claimed by the oldest transaction per id; replays that
+-- were stored before anyway keep their rows
+INSERT INTO anchor_transaction_ids (anchor_transaction_id, transaction_id, created_at)
+SELECT DISTINCT ON (anchor_transaction_id) anchor_transaction_id, id, created_at
+FROM transactions
+WHERE anchor_transaction_id IS NOT NULL
+ORDER BY anchor_transaction_id, created_at, id
+ON CONFLICT (anchor_transaction_id) DO NOTHING;
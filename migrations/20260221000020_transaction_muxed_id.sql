@@ -0,0 +1,4 @@
+-- SEP-23 id of the muxed (M...) account a callback named. stellar_account
+-- holds the underlying G... account. NUMERIC because the id is an unsigned
+-- 64-bit integer.
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS muxed_id NUMERIC(20, 0);
@@ -0,0 +1,7 @@
+-- Earliest time the reconciliation worker may check a pending transaction
+-- again. A pass sets it when it claims the transaction, so concurrent
+-- workers and the next pass skip it until then.
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS reconcile_after TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_transactions_pending_created_at
+    ON transactions(created_at) WHERE status = 'pending';
@@ -0,0 +1,6 @@
+-- Gradual rollouts. While a flag is disabled it is still on for the
+-- accounts in allowed_accounts, and for rollout_percentage percent of all
+-- other subjects, bucketed by a hash of the flag name and the subject.
+ALTER TABLE feature_flags ADD COLUMN IF NOT EXISTS rollout_percentage SMALLINT NOT NULL DEFAULT 0
+    CHECK (rollout_percentage BETWEEN 0 AND 100);
+ALTER TABLE feature_flags ADD COLUMN IF NOT EXISTS allowed_accounts TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,9 @@
+-- Retention is applied by PartitionManager from PARTITION_RETENTION_MONTHS
+-- and PARTITION_RETENTION_MODE, so maintenance here only creates partitions.
+-- detach_old_partitions() is kept for manual use.
+CREATE OR REPLACE FUNCTION maintain_partitions()
+RETURNS void AS $$
+BEGIN
+    PERFORM create_monthly_partition();
+END;
+$$ LANGUAGE plpgsql;
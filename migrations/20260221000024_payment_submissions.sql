@@ -0,0 +1,13 @@
+-- The payment the payment processor last signed for a deposit. While its
+-- outcome is unknown the same envelope is sent again; once it is past
+-- payout_valid_until it can no longer land and a new one is signed.
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS payout_envelope TEXT;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS payout_valid_until TIMESTAMPTZ;
+
+-- Earliest time the payment processor may pick the transaction up again. A
+-- pass sets it when it claims the transaction, so concurrent processors and
+-- the next pass skip it until then.
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS payout_after TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_transactions_submitted_payout_after
+    ON transactions(payout_after) WHERE status = 'submitted';
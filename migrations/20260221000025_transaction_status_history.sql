@@ -0,0 +1,15 @@
+-- Every status change of a transaction, with why and by whom. Rows are only
+-- ever inserted. transactions is partitioned, so transaction_id can't be a
+-- foreign key.
+CREATE TABLE IF NOT EXISTS transaction_status_history (
+    id BIGSERIAL PRIMARY KEY,
+    transaction_id UUID NOT NULL,
+    old_status TEXT NOT NULL,
+    new_status TEXT NOT NULL,
+    reason TEXT,
+    actor VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_transaction_status_history_transaction
+    ON transaction_status_history(transaction_id, created_at);
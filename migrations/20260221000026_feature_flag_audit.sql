@@ -0,0 +1,17 @@
+-- Targeting rules. While a flag is disabled it is also on for subjects whose
+-- attributes match a rule, e.g. [{"attribute": "asset_code", "values": ["USDC"]}].
+ALTER TABLE feature_flags ADD COLUMN IF NOT EXISTS targeting JSONB NOT NULL DEFAULT '[]'
+    CHECK (jsonb_typeof(targeting) = 'array');
+
+-- Every change to a flag, with the whole flag before and after it
+CREATE TABLE IF NOT EXISTS feature_flag_audit (
+    id BIGSERIAL PRIMARY KEY,
+    flag_name VARCHAR(100) NOT NULL,
+    actor VARCHAR(255) NOT NULL,
+    before JSONB NOT NULL,
+    after JSONB NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_feature_flag_audit_flag
+    ON feature_flag_audit (flag_name, changed_at DESC, id DESC);
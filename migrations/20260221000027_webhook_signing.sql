@@ -0,0 +1,14 @@
+-- Outbound webhooks are signed with a per-subscription secret. Existing
+-- subscriptions get one too; their owners rotate it to learn it.
+ALTER TABLE webhook_subscriptions ADD COLUMN IF NOT EXISTS secret VARCHAR(72) NOT NULL
+    DEFAULT 'whsec_' || replace(gen_random_uuid()::text, '-', '') || replace(gen_random_uuid()::text, '-', '');
+
+-- Deliveries that used up their attempts ('failed') are the dead-letter
+-- queue, listed newest first by the admin API
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_dead_letters
+    ON webhook_deliveries(created_at DESC)
+    WHERE status = 'failed';
+
+INSERT INTO notification_templates (event_type, locale, template) VALUES
+    ('transaction.status_changed', 'en', E'Your transaction of { $amount } { $asset_code } is now { $status }')
+ON CONFLICT (event_type, locale) DO NOTHING;
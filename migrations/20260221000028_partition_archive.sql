@@ -0,0 +1,57 @@
+-- Cold storage for transactions partitions past retention, with
+-- PARTITION_RETENTION_MODE=archive. Not partitioned and only indexed for
+-- lookups by id and date.
+CREATE TABLE IF NOT EXISTS transactions_archive (
+    LIKE transactions INCLUDING DEFAULTS,
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_transactions_archive_id ON transactions_archive (id);
+CREATE INDEX IF NOT EXISTS idx_transactions_archive_created_at
+    ON transactions_archive (created_at);
+
+-- Move a partition's rows into transactions_archive and drop it, detaching it
+-- first if it is still attached. Columns added to transactions since the
+-- archive was created are added to the archive first. Returns the rows moved.
+CREATE OR REPLACE FUNCTION archive_transactions_partition(part_name TEXT)
+RETURNS BIGINT AS $$
+DECLARE
+    part REGCLASS := format('%I', part_name)::regclass;
+    missing RECORD;
+    columns TEXT;
+    moved BIGINT;
+BEGIN
+    FOR missing IN
+        SELECT a.attname, format_type(a.atttypid, a.atttypmod) AS type
+        FROM pg_attribute a
+        WHERE a.attrelid = 'transactions'::regclass AND a.attnum > 0 AND NOT a.attisdropped
+          AND NOT EXISTS (
+              SELECT 1 FROM pg_attribute b
+              WHERE b.attrelid = 'transactions_archive'::regclass
+                AND b.attname = a.attname AND NOT b.attisdropped
+          )
+        ORDER BY a.attnum
+    LOOP
+        EXECUTE format('ALTER TABLE transactions_archive ADD COLUMN %I %s', missing.attname, missing.type);
+    END LOOP;
+
+    IF EXISTS (
+        SELECT 1 FROM pg_inherits WHERE inhrelid = part AND inhparent = 'transactions'::regclass
+    ) THEN
+        EXECUTE format('ALTER TABLE transactions DETACH PARTITION %I', part_name);
+    END IF;
+
+    SELECT string_agg(quote_ident(attname), ', ' ORDER BY attnum) INTO columns
+    FROM pg_attribute
+    WHERE attrelid = part AND attnum > 0 AND NOT attisdropped;
+
+    EXECUTE format(
+        'INSERT INTO transactions_archive (%s) SELECT %s FROM %I',
+        columns, columns, part_name
+    );
+    GET DIAGNOSTICS moved = ROW_COUNT;
+
+    EXECUTE format('DROP TABLE %I', part_name);
+    RETURN moved;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,27 @@
+-- Deleting a flag takes the next generation too, recorded in a tombstone, so
+-- incremental refreshes see the deletion like any other change.
+CREATE TABLE IF NOT EXISTS feature_flag_tombstones (
+    name VARCHAR(100) PRIMARY KEY,
+    generation BIGINT NOT NULL,
+    deleted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_feature_flag_tombstones_generation
+    ON feature_flag_tombstones (generation);
+
+CREATE OR REPLACE FUNCTION record_flag_tombstone() RETURNS TRIGGER AS $$
+DECLARE
+    next_generation BIGINT;
+BEGIN
+    UPDATE flag_generations SET generation = generation + 1
+    RETURNING generation INTO next_generation;
+    INSERT INTO feature_flag_tombstones (name, generation) VALUES (OLD.name, next_generation)
+    ON CONFLICT (name) DO UPDATE
+        SET generation = EXCLUDED.generation, deleted_at = NOW();
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER feature_flags_tombstone
+    AFTER DELETE ON feature_flags
+    FOR EACH ROW EXECUTE FUNCTION record_flag_tombstone();
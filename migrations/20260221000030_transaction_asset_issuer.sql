@@ -0,0 +1,15 @@
+-- Issuer of the transaction's asset, so two issuers of the same code don't
+-- collide. NULL for lumens and for assets without one in the registry.
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS asset_issuer VARCHAR(56);
+
+-- Existing rows take the registry issuer where the code has only one
+UPDATE transactions t
+SET asset_issuer = a.asset_issuer
+FROM (
+    SELECT asset_code, MIN(asset_issuer) AS asset_issuer
+    FROM assets
+    WHERE asset_issuer IS NOT NULL
+    GROUP BY asset_code
+    HAVING COUNT(*) = 1
+) a
+WHERE t.asset_code = a.asset_code AND t.asset_issuer IS NULL;
@@ -1,76 +1,122 @@
 //! Postgres implementation of TransactionRepository.
 
 use async_trait::async_trait;
-use sqlx::PgPool;
+use std::time::Instant;
 use uuid::Uuid;
 
-use crate::domain::Transaction;
+use crate::db::query_plan::{self, PlanParam, QueryMeta, QueryPlanCapture, SENSITIVE_COLUMNS};
+use crate::db::uow::DbConn;
+use crate::domain::{Transaction, TransactionStatus};
 use crate::ports::{RepositoryError, RepositoryResult, TransactionRepository};
 
+const INSERT: QueryMeta = QueryMeta {
+    name: "transactions.insert",
+    sql: r#"
+        INSERT INTO transactions (
+            id, stellar_account, amount, asset_code, status,
+            created_at, updated_at, anchor_transaction_id, callback_type, callback_status
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING id, stellar_account, amount, asset_code, status,
+            created_at, updated_at, anchor_transaction_id, callback_type, callback_status
+        "#,
+    bound_columns: &[
+        "id", "stellar_account", "amount", "asset_code", "status",
+        "created_at", "updated_at", "anchor_transaction_id", "callback_type", "callback_status",
+    ],
+};
+
+const GET_BY_ID: QueryMeta = QueryMeta {
+    name: "transactions.get_by_id",
+    sql: "SELECT * FROM transactions WHERE id = $1",
+    bound_columns: &["id"],
+};
+
+const LIST: QueryMeta = QueryMeta {
+    name: "transactions.list",
+    sql: "SELECT * FROM transactions ORDER BY created_at DESC LIMIT $1 OFFSET $2",
+    bound_columns: &[],
+};
+
 /// Postgres-backed transaction repository.
-#[derive(Clone)]
+///
+/// Stateless apart from plan capture; the connection is passed per call.
+#[derive(Clone, Default)]
 pub struct PostgresTransactionRepository {
-    pool: PgPool,
+    plan_capture: Option<QueryPlanCapture>,
 }
 
 impl PostgresTransactionRepository {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Capture EXPLAIN plans of slow queries (debug environments only).
+    pub fn with_plan_capture(mut self, capture: QueryPlanCapture) -> Self {
+        self.plan_capture = Some(capture);
+        self
+    }
+
+    fn observe(&self, meta: &'static QueryMeta, started: Instant, params: impl FnOnce() -> Vec<PlanParam>) {
+        match &self.plan_capture {
+            Some(capture) => capture.observe(meta, started, params()),
+            None => query_plan::observe(meta, started, params),
+        }
     }
 }
 
 #[async_trait]
 impl TransactionRepository for PostgresTransactionRepository {
-    async fn insert(&self, tx: &Transaction) -> RepositoryResult<Transaction> {
-        let row = sqlx::query_as::<_, TransactionRow>(
-            r#"
-            INSERT INTO transactions (
-                id, stellar_account, amount, asset_code, status,
-                created_at, updated_at, anchor_transaction_id, callback_type, callback_status
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-            RETURNING id, stellar_account, amount, asset_code, status,
-                created_at, updated_at, anchor_transaction_id, callback_type, callback_status
-            "#,
-        )
-        .bind(tx.id)
-        .bind(&tx.stellar_account)
-        .bind(&tx.amount)
-        .bind(&tx.asset_code)
-        .bind(&tx.status)
-        .bind(tx.created_at)
-        .bind(tx.updated_at)
-        .bind(&tx.anchor_transaction_id)
-        .bind(&tx.callback_type)
-        .bind(&tx.callback_status)
-        .fetch_one(&self.pool)
-        .await
+    async fn insert(&self, conn: DbConn<'_>, tx: &Transaction) -> RepositoryResult<Transaction> {
+        let started = Instant::now();
+        let query = sqlx::query_as::<_, TransactionRow>(INSERT.sql)
+            .bind(tx.id)
+            .bind(&tx.stellar_account)
+            .bind(&tx.amount)
+            .bind(&tx.asset_code)
+            .bind(&tx.status)
+            .bind(tx.created_at)
+            .bind(tx.updated_at)
+            .bind(&tx.anchor_transaction_id)
+            .bind(&tx.callback_type)
+            .bind(&tx.callback_status);
+        let row = match conn {
+            DbConn::Pool(pool) => query.fetch_one(pool).await,
+            DbConn::Conn(conn) => query.fetch_one(conn).await,
+        }
         .map_err(RepositoryError::from)?;
+        // Binds customer data, so the capture always declines it
+        self.observe(&INSERT, started, Vec::new);
 
-        Ok(row.into_domain())
+        row.into_domain()
     }
 
-    async fn get_by_id(&self, id: Uuid) -> RepositoryResult<Transaction> {
-        let row = sqlx::query_as::<_, TransactionRow>("SELECT * FROM transactions WHERE id = $1")
-            .bind(id)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(RepositoryError::from)?;
+    async fn get_by_id(&self, conn: DbConn<'_>, id: Uuid) -> RepositoryResult<Transaction> {
+        let started = Instant::now();
+        let query = sqlx::query_as::<_, TransactionRow>(GET_BY_ID.sql).bind(id);
+        let row = match conn {
+            DbConn::Pool(pool) => query.fetch_optional(pool).await,
+            DbConn::Conn(conn) => query.fetch_optional(conn).await,
+        }
+        .map_err(RepositoryError::from)?;
+        self.observe(&GET_BY_ID, started, || vec![PlanParam::Uuid(Some(id))]);
 
-        row.map(|r| r.into_domain())
-            .ok_or_else(|| RepositoryError::NotFound(id.to_string()))
+        row.ok_or_else(|| RepositoryError::NotFound(id.to_string()))?
+            .into_domain()
     }
 
-    async fn list(&self, limit: i64, offset: i64) -> RepositoryResult<Vec<Transaction>> {
-        let rows = sqlx::query_as::<_, TransactionRow>(
-            "SELECT * FROM transactions ORDER BY created_at DESC LIMIT $1 OFFSET $2",
-        )
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(&self.pool)
-        .await
+    async fn list(&self, conn: DbConn<'_>, limit: i64, offset: i64) -> RepositoryResult<Vec<Transaction>> {
+        let started = Instant::now();
+        let query = sqlx::query_as::<_, TransactionRow>(LIST.sql)
+            .bind(limit)
+            .bind(offset);
+        let rows = match conn {
+            DbConn::Pool(pool) => query.fetch_all(pool).await,
+            DbConn::Conn(conn) => query.fetch_all(conn).await,
+        }
         .map_err(RepositoryError::from)?;
+        self.observe(&LIST, started, || vec![PlanParam::Int(limit), PlanParam::Int(offset)]);
 
-        Ok(rows.into_iter().map(|r| r.into_domain()).collect())
+        rows.into_iter().map(|r| r.into_domain()).collect()
     }
 }
 
@@ -90,18 +136,72 @@ struct TransactionRow {
 }
 
 impl TransactionRow {
-    fn into_domain(self) -> Transaction {
-        Transaction {
+    /// Legacy status spellings are read as their canonical value, so rows
+    /// not yet rewritten by `migrate-statuses` look the same as the rest.
+    fn into_domain(self) -> RepositoryResult<Transaction> {
+        let status = TransactionStatus::from_legacy(&self.status)
+            .map_err(|status| RepositoryError::UnknownStatus { id: self.id, status })?;
+        Ok(Transaction {
             id: self.id,
             stellar_account: self.stellar_account,
             amount: self.amount,
             asset_code: self.asset_code,
-            status: self.status,
+            status: status.as_str().to_string(),
             created_at: self.created_at,
             updated_at: self.updated_at,
             anchor_transaction_id: self.anchor_transaction_id,
             callback_type: self.callback_type,
             callback_status: self.callback_status,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_plan_is_never_captured() {
+        assert!(INSERT.touches_sensitive(SENSITIVE_COLUMNS));
+    }
+
+    fn row(status: &str) -> TransactionRow {
+        TransactionRow {
+            id: Uuid::new_v4(),
+            stellar_account: "GABC".to_string(),
+            amount: sqlx::types::BigDecimal::from(10),
+            asset_code: "USDC".to_string(),
+            status: status.to_string(),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+            anchor_transaction_id: None,
+            callback_type: None,
+            callback_status: None,
+        }
+    }
+
+    #[test]
+    fn test_legacy_status_is_read_as_canonical() {
+        assert_eq!(row("PENDING_anchor").into_domain().unwrap().status, "pending");
+        assert_eq!(row("Completed").into_domain().unwrap().status, "completed");
+    }
+
+    #[test]
+    fn test_unknown_status_is_a_distinct_error() {
+        let row = row("on_hold");
+        let id = row.id;
+        match row.into_domain() {
+            Err(RepositoryError::UnknownStatus { id: got, status }) => {
+                assert_eq!(got, id);
+                assert_eq!(status.0, "on_hold");
+            }
+            other => panic!("expected UnknownStatus, got {:?}", other),
         }
     }
+
+    #[test]
+    fn test_read_plans_are_capturable() {
+        assert!(!GET_BY_ID.touches_sensitive(SENSITIVE_COLUMNS));
+        assert!(!LIST.touches_sensitive(SENSITIVE_COLUMNS));
+    }
 }
@@ -2,6 +2,11 @@ use clap::{Parser, Subcommand};
 use sqlx::PgPool;
 use uuid::Uuid;
 use crate::config::Config;
+use crate::db::uow;
+use crate::domain::TransactionStatus;
+use crate::error::AppError;
+use crate::loadgen::LoadgenArgs;
+use crate::services::transaction;
 
 #[derive(Parser)]
 #[command(name = "synapse-core")]
@@ -26,6 +31,20 @@ pub enum Commands {
     
     /// Configuration validation
     Config,
+
+    /// Send synthetic signed callbacks at a target for capacity testing
+    Loadgen(LoadgenArgs),
+
+    /// Rewrite legacy transaction status spellings to canonical statuses
+    MigrateStatuses {
+        /// Only report what would be rewritten
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Rows rewritten per database transaction
+        #[arg(long, default_value_t = 1000)]
+        batch_size: i64,
+    },
 }
 
 #[derive(Subcommand)]
@@ -45,23 +64,31 @@ pub enum DbCommands {
 }
 
 pub async fn handle_tx_force_complete(pool: &PgPool, tx_id: Uuid) -> anyhow::Result<()> {
-    let result = sqlx::query(
-        "UPDATE transactions SET status = 'completed', updated_at = NOW() WHERE id = $1 RETURNING id"
-    )
-    .bind(tx_id)
-    .fetch_optional(pool)
-    .await?;
+    // Through the checked status change so the audit log, history and
+    // account stats follow, and a failed transaction can't be completed
+    let result = uow::run(pool, |uow| Box::pin(async move {
+        transaction::transition(
+            uow.conn(),
+            tx_id,
+            TransactionStatus::Completed,
+            Some("forced complete"),
+            "cli",
+        )
+        .await
+    }))
+    .await;
 
     match result {
-        Some(_) => {
+        Ok(_) => {
             tracing::info!("Transaction {} marked as completed", tx_id);
             println!("✓ Transaction {} marked as completed", tx_id);
             Ok(())
         }
-        None => {
+        Err(AppError::NotFound(_)) => {
             tracing::warn!("Transaction {} not found", tx_id);
             anyhow::bail!("Transaction {} not found", tx_id)
         }
+        Err(e) => anyhow::bail!("{}", e),
     }
 }
 
@@ -81,13 +108,46 @@ pub async fn handle_db_migrate(config: &Config) -> anyhow::Result<()> {
     Ok(())
 }
 
+pub async fn handle_migrate_statuses(pool: &PgPool, dry_run: bool, batch_size: i64) -> anyhow::Result<()> {
+    if batch_size < 1 {
+        anyhow::bail!("--batch-size must be at least 1");
+    }
+
+    let report = crate::db::legacy_statuses::migrate(pool, batch_size, dry_run, |raw, status, done, total| {
+        println!("  {:?} -> {}: {}/{}", raw, status, done, total);
+    })
+    .await?;
+
+    for (raw, status, rows) in &report.legacy {
+        let verb = if dry_run { "Would rewrite" } else { "Rewrote" };
+        println!("{} {} rows from {:?} to {}", verb, rows, raw, status);
+    }
+    for (raw, rows) in &report.unknown {
+        println!("! {} rows hold the unknown status {:?}; fix them by hand", rows, raw);
+    }
+
+    if report.legacy.is_empty() && report.unknown.is_empty() {
+        println!("✓ Every transaction status is canonical");
+    } else if dry_run {
+        println!("Dry run: nothing was changed");
+    } else {
+        tracing::info!(rows = report.legacy_rows(), "Legacy transaction statuses migrated");
+        println!("✓ Migrated {} rows", report.legacy_rows());
+    }
+    Ok(())
+}
+
 pub fn handle_config_validate(config: &Config) -> anyhow::Result<()> {
     tracing::info!("Validating configuration...");
     
     println!("Configuration:");
     println!("  Server Port: {}", config.server_port);
     println!("  Database URL: {}", mask_password(&config.database_url));
-    println!("  Stellar Horizon URL: {}", config.stellar_horizon_url);
+    println!("  Stellar Network: {}", config.stellar.kind.as_str());
+    println!("  Stellar Horizon URL: {}", config.stellar.horizon_url);
+    if let Some(url) = &config.stellar.soroban_rpc_url {
+        println!("  Soroban RPC URL: {}", url);
+    }
     
     tracing::info!("Configuration is valid");
     println!("✓ Configuration is valid");
@@ -1,15 +1,583 @@
 use anyhow::Result;
 use dotenvy::dotenv;
+use ipnet::IpNet;
 use serde::Deserialize;
+use std::collections::HashSet;
 use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+use sqlx::types::BigDecimal;
+
+use crate::deprecation;
+use crate::middleware::auth::AdminRole;
+use crate::middleware::idempotency::DEFAULT_CALLBACK_TTL;
+use crate::services::erasure::{parse_erasure_policy, ErasurePolicy};
+use crate::services::feature_flags::{DEFAULT_FULL_REFRESH_EVERY, DEFAULT_INVALIDATION_CHANNEL};
+use crate::services::redis_health::{parse_required_features, RedisFeature};
+use crate::stellar::client::HorizonRoot;
+use crate::stellar::transaction::{BASE_FEE, PUBLIC_NETWORK_PASSPHRASE, TESTNET_PASSPHRASE};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub server_port: u16,
     pub database_url: String,
     pub database_replica_url: Option<String>,
-    pub stellar_horizon_url: String,
+    pub stellar: StellarNetwork,
+    pub horizon: HorizonConfig,
+    /// Account whose incoming payments are matched to pending transactions
+    pub payment_listener_account: Option<String>,
+    /// Payments paging token the payment listener starts from on a fresh
+    /// database; unset, it reads the account's payments from the oldest
+    pub payment_listener_start_cursor: Option<String>,
+    /// Effects paging token the trustline listener starts from on a fresh
+    /// database; unset, it starts after the newest effect
+    pub trustline_listener_start_cursor: Option<String>,
     pub anchor_webhook_secret: String,
+    /// `G...` account whose ed25519 key signs Anchor Platform callbacks;
+    /// unset outside production, callbacks are not verified
+    pub anchor_signing_key: Option<String>,
+    pub export_storage: ExportStorageConfig,
+    pub export_retention_hours: i64,
+    pub export_freshness_minutes: i64,
+    pub duplicate_callback_response: DuplicateCallbackResponse,
+    pub app_env: String,
+    pub capture_query_plans: bool,
+    pub slow_query_threshold_ms: u64,
+    pub redis_url: String,
+    /// Redis-backed features that fail closed instead of degrading
+    pub redis_required_features: HashSet<RedisFeature>,
+    pub webhook_dispatch: WebhookDispatchConfig,
+    pub server_limits: ServerLimits,
+    pub quotes: QuoteConfig,
+    /// Reported by the status snapshot; defaults to the crate version
+    pub deploy_version: String,
+    pub ingestion: IngestionConfig,
+    pub buffered_writes: BufferedWriteConfig,
+    /// Fake Horizon settings, used only when `APP_ENV=sandbox`
+    pub sandbox: SandboxConfig,
+    /// Action per data category when an account is erased
+    pub erasure_policy: ErasurePolicy,
+    /// Deprecation and sunset dates overriding `deprecation::catalog`
+    pub deprecation_schedule: deprecation::Schedule,
+    /// Time between scheduled feature flag cache refreshes
+    pub flag_refresh_interval: Duration,
+    /// Every Nth scheduled refresh reloads every flag instead of only the
+    /// changed ones, dropping deleted flags from the cache
+    pub flag_full_refresh_every: u64,
+    /// Redis channel flag changes are announced on, so every instance
+    /// refreshes at once; `None` leaves only the scheduled refreshes
+    pub flag_invalidation_channel: Option<String>,
+    /// Clients allowed on the legacy callback route and every `/admin` route
+    pub allowed_ips: AllowedIps,
+    /// Take the client address from the proxy header instead of the peer
+    pub trust_proxy_headers: bool,
+    /// The header the trusted proxies write; only read with
+    /// `trust_proxy_headers`
+    pub trusted_proxy_header: ProxyHeader,
+    /// Proxies appending to that header in front of the one that connects;
+    /// only read with `trust_proxy_headers`
+    pub trusted_proxy_depth: usize,
+    /// How long Redis remembers the first response to each callback
+    pub callback_idempotency_ttl: Duration,
+    pub reconciliation: ReconciliationConfig,
+    /// `S...` secret of the account deposits are paid from; without it the
+    /// payment processor doesn't run
+    pub payment_signing_secret: Option<String>,
+    pub payment_processor: PaymentProcessorConfig,
+    pub partition_retention: PartitionRetentionConfig,
+    /// Span of each new `transactions` partition
+    pub partition_interval: PartitionInterval,
+    /// Hours between partition maintenance runs
+    pub partition_maintenance_hours: u64,
+    /// Record metrics and serve them on `/metrics`
+    pub metrics_enabled: bool,
+    /// Credentials accepted on `/admin` routes
+    pub admin_auth: AdminAuthConfig,
+    pub telemetry: TelemetryConfig,
+}
+
+impl Config {
+    /// `APP_ENV=sandbox`: fake Horizon, seeded data, unrestricted admin resets
+    pub fn is_sandbox(&self) -> bool {
+        is_sandbox_env(&self.app_env)
+    }
+
+    pub fn deployment(&self) -> Deployment {
+        Deployment {
+            version: self.deploy_version.clone(),
+            environment: self.app_env.clone(),
+        }
+    }
+}
+
+/// `ALLOWED_IPS`: `*`, or a comma-separated list of CIDRs. A bare address
+/// is read as a single-host range.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub enum AllowedIps {
+    Any,
+    Cidrs(Vec<IpNet>),
+}
+
+/// `TRUSTED_PROXY_HEADER`: where a trusted proxy records the client. Only
+/// the configured header is read, so a client can't put an address in the
+/// other one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProxyHeader {
+    #[default]
+    XForwardedFor,
+    /// RFC 7239 `Forwarded: for=...`
+    Forwarded,
+}
+
+fn is_sandbox_env(app_env: &str) -> bool {
+    app_env.trim().eq_ignore_ascii_case("sandbox")
+}
+
+/// What is running where, reported by `/version`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Deployment {
+    pub version: String,
+    pub environment: String,
+}
+
+impl Deployment {
+    pub fn is_sandbox(&self) -> bool {
+        is_sandbox_env(&self.environment)
+    }
+}
+
+/// The deterministic fake Horizon of sandbox mode.
+#[derive(Debug, Deserialize, Clone)]
+pub struct SandboxConfig {
+    /// How long a submission takes before it succeeds
+    pub submission_delay: Duration,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            submission_delay: Duration::from_millis(2000),
+        }
+    }
+}
+
+/// One static admin API key. `Debug` leaves the key out.
+#[derive(Deserialize, Clone, PartialEq, Eq)]
+pub struct AdminApiKey {
+    /// Who the key belongs to, recorded as the actor of their changes
+    pub name: String,
+    pub role: AdminRole,
+    pub key: String,
+}
+
+impl std::fmt::Debug for AdminApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AdminApiKey")
+            .field("name", &self.name)
+            .field("role", &self.role)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Admin credentials. `Debug` leaves the secrets out.
+#[derive(Deserialize, Clone)]
+pub struct AdminAuthConfig {
+    pub api_keys: Vec<AdminApiKey>,
+    /// HS256 secret admin JWTs are signed with; without it JWTs are rejected
+    pub jwt_secret: Option<String>,
+    /// `iss` every admin JWT must carry, when set
+    pub jwt_issuer: Option<String>,
+}
+
+impl std::fmt::Debug for AdminAuthConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AdminAuthConfig")
+            .field("api_keys", &self.api_keys)
+            .field("jwt_secret", &self.jwt_secret.as_ref().map(|_| "<set>"))
+            .field("jwt_issuer", &self.jwt_issuer)
+            .finish()
+    }
+}
+
+/// Outside production, an unconfigured service accepts this operator key
+pub const DEVELOPMENT_ADMIN_API_KEY: &str = "admin-secret-key";
+
+impl Default for AdminAuthConfig {
+    fn default() -> Self {
+        Self {
+            api_keys: vec![AdminApiKey {
+                name: "admin".to_string(),
+                role: AdminRole::Operator,
+                key: DEVELOPMENT_ADMIN_API_KEY.to_string(),
+            }],
+            jwt_secret: None,
+            jwt_issuer: None,
+        }
+    }
+}
+
+/// SEP-38 quoting for withdrawals
+#[derive(Debug, Deserialize, Clone)]
+pub struct QuoteConfig {
+    /// Anchor Platform SEP-38 base URL; firm quotes can't be requested without it
+    pub sep38_url: Option<String>,
+    /// SEP-10 JWT sent with quote requests
+    pub sep38_auth_token: Option<String>,
+    /// Relative difference allowed between a withdrawal and its quoted amount
+    pub amount_tolerance: BigDecimal,
+    /// Unused quotes are purged this long after they expire
+    pub retention_hours: i64,
+}
+
+/// HTTP server connection hygiene. `None` means unlimited, which is what every
+/// knob is when its variable is unset.
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServerLimits {
+    /// Time a client gets to send complete request headers
+    pub header_read_timeout: Option<Duration>,
+    /// Keep-alive connections with no traffic for this long are closed
+    pub idle_timeout: Option<Duration>,
+    /// Connections beyond this wait in the listen backlog until one closes
+    pub max_connections: Option<usize>,
+    /// The response to this request carries `Connection: close`
+    pub max_requests_per_connection: Option<u64>,
+}
+
+impl ServerLimits {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Limits of the outbound webhook dispatcher.
+#[derive(Debug, Deserialize, Clone)]
+pub struct WebhookDispatchConfig {
+    /// Concurrent deliveries per subscription unless the subscription overrides it
+    pub max_in_flight: usize,
+    /// Deliveries claimed per poll across all subscriptions
+    pub claim_batch_size: i64,
+    /// Failure rate over the recent window that triggers a cool-down
+    pub failure_rate_threshold: f64,
+    /// Number of recent attempts the failure rate is computed over
+    pub failure_window: usize,
+    pub cooldown_secs: u64,
+    pub delivery_timeout_secs: u64,
+    pub max_attempts: i32,
+}
+
+impl Default for WebhookDispatchConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 4,
+            claim_batch_size: 100,
+            failure_rate_threshold: 0.5,
+            failure_window: 20,
+            cooldown_secs: 60,
+            delivery_timeout_secs: 10,
+            max_attempts: 8,
+        }
+    }
+}
+
+/// Batching of buffered audit and usage writes.
+#[derive(Debug, Deserialize, Clone)]
+pub struct BufferedWriteConfig {
+    /// Entries the channel holds before audit writes turn synchronous and
+    /// usage counts are dropped
+    pub capacity: usize,
+    /// A batch is written once it has this many entries...
+    pub batch_size: usize,
+    /// ...or when this much time has passed since the last write
+    pub flush_interval: Duration,
+    /// Hard cap on the shutdown flush; what is left is printed to stderr
+    pub shutdown_timeout: Duration,
+}
+
+impl Default for BufferedWriteConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000,
+            batch_size: 500,
+            flush_interval: Duration::from_millis(250),
+            shutdown_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Checking pending deposits against the anchor account's Horizon payments.
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReconciliationConfig {
+    /// Time between reconciliation passes
+    pub poll_interval: Duration,
+    /// Transactions claimed per pass
+    pub batch_size: i64,
+    /// A transaction is first checked once it has been pending this long...
+    pub min_age: Duration,
+    /// ...and fails once it has been pending this long without a payment
+    pub max_age: Duration,
+}
+
+impl Default for ReconciliationConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(30),
+            batch_size: 50,
+            min_age: Duration::from_secs(60),
+            max_age: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl ReconciliationConfig {
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.max_age <= self.min_age {
+            anyhow::bail!(
+                "RECONCILIATION_MAX_AGE_SECS must be greater than RECONCILIATION_MIN_AGE_SECS"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Stellar network presets for `STELLAR_NETWORK`.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkKind {
+    Testnet,
+    Pubnet,
+    /// Any other network; its passphrase and Horizon must both be set
+    Custom,
+}
+
+impl NetworkKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NetworkKind::Testnet => "testnet",
+            NetworkKind::Pubnet => "pubnet",
+            NetworkKind::Custom => "custom",
+        }
+    }
+
+    /// Passphrase and SDF Horizon of a preset
+    fn preset(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            NetworkKind::Testnet => {
+                Some((TESTNET_PASSPHRASE, "https://horizon-testnet.stellar.org"))
+            }
+            NetworkKind::Pubnet => Some((PUBLIC_NETWORK_PASSPHRASE, "https://horizon.stellar.org")),
+            NetworkKind::Custom => None,
+        }
+    }
+}
+
+/// The Stellar network a deployment runs on, and its endpoints.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct StellarNetwork {
+    pub kind: NetworkKind,
+    /// Payments are signed for it, and Horizon must report it at startup
+    pub network_passphrase: String,
+    pub horizon_url: String,
+    /// Soroban RPC of the same network; unset when no contract calls are made
+    pub soroban_rpc_url: Option<String>,
+}
+
+impl StellarNetwork {
+    /// Refuse a Horizon that serves another network
+    pub fn check_horizon(&self, root: &HorizonRoot) -> anyhow::Result<()> {
+        if root.network_passphrase != self.network_passphrase {
+            anyhow::bail!(
+                "Horizon at {} serves the network \"{}\", but STELLAR_NETWORK={} expects \"{}\"",
+                self.horizon_url,
+                root.network_passphrase,
+                self.kind.as_str(),
+                self.network_passphrase
+            );
+        }
+        Ok(())
+    }
+}
+
+/// How the Horizon client copes with a slow or failing Horizon.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct HorizonConfig {
+    /// Limit on a single request, connecting included
+    pub request_timeout: Duration,
+    /// Retries of a GET that timed out, failed to connect, or was answered
+    /// with `429` or a `5xx`; `0` disables retrying
+    pub max_retries: u32,
+    /// Delay before the first retry; it doubles for each one after
+    pub retry_base_delay: Duration,
+    /// Cap on a retry delay, `Retry-After` included
+    pub retry_max_delay: Duration,
+    /// Consecutive failed calls that open the circuit breaker
+    pub circuit_failure_threshold: u32,
+    /// How long the breaker stays open before letting a call through again;
+    /// jittered up to twice as long
+    pub circuit_reset_timeout: Duration,
+}
+
+impl Default for HorizonConfig {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(30),
+            max_retries: 3,
+            retry_base_delay: Duration::from_millis(500),
+            retry_max_delay: Duration::from_secs(10),
+            circuit_failure_threshold: 3,
+            circuit_reset_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl HorizonConfig {
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.retry_max_delay < self.retry_base_delay {
+            anyhow::bail!(
+                "HORIZON_RETRY_MAX_DELAY_MS must not be less than HORIZON_RETRY_BASE_DELAY_MS"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Signing and submitting the Stellar payments of pending deposits.
+#[derive(Debug, Deserialize, Clone)]
+pub struct PaymentProcessorConfig {
+    /// Time between passes, and how long a claim lasts
+    pub poll_interval: Duration,
+    /// Transactions claimed per pass
+    pub batch_size: i64,
+    /// Fee per payment, in stroops
+    pub base_fee: u32,
+    /// How long a signed payment may take to land before a new one is signed
+    pub submission_timeout: Duration,
+    /// Network the payments are signed for
+    pub network_passphrase: String,
+}
+
+impl Default for PaymentProcessorConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(10),
+            batch_size: 20,
+            base_fee: BASE_FEE,
+            submission_timeout: Duration::from_secs(120),
+            network_passphrase: TESTNET_PASSPHRASE.to_string(),
+        }
+    }
+}
+
+/// What happens to a `transactions` partition once it is past retention.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionRetentionMode {
+    /// Kept as a standalone table, for archiving
+    Detach,
+    /// Dropped with its rows
+    Drop,
+    /// Rows moved to `transactions_archive`, then dropped
+    Archive,
+}
+
+/// Span of each `transactions` partition created ahead of time.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionInterval {
+    Daily,
+    /// Monday to Monday, UTC
+    Weekly,
+    Monthly,
+}
+
+/// Span export to an OpenTelemetry collector.
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+pub struct TelemetryConfig {
+    /// `OTEL_EXPORTER_OTLP_ENDPOINT`, the collector's OTLP/gRPC address;
+    /// `None` keeps spans in the logs only
+    pub otlp_endpoint: Option<String>,
+    /// `service.name` on every exported span
+    pub service_name: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: "synapse-core".to_string(),
+        }
+    }
+}
+
+/// How long `transactions` partitions are kept.
+#[derive(Debug, Deserialize, Clone)]
+pub struct PartitionRetentionConfig {
+    /// A partition is removed once its upper bound is this many months ago
+    pub months: u32,
+    pub mode: PartitionRetentionMode,
+}
+
+impl Default for PartitionRetentionConfig {
+    fn default() -> Self {
+        Self {
+            months: 12,
+            mode: PartitionRetentionMode::Detach,
+        }
+    }
+}
+
+/// How a new callback is acknowledged.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum IngestionMode {
+    /// 201 once the transaction is stored
+    Sync,
+    /// 202 once the callback is queued in the ingestion outbox
+    Async,
+    /// `Sync` until the database is slow, then `Async` until it recovers
+    Auto,
+}
+
+/// Callback acknowledgment mode and the thresholds `Auto` switches on.
+#[derive(Debug, Deserialize, Clone)]
+pub struct IngestionConfig {
+    pub mode: IngestionMode,
+    /// Smoothed insert latency above which `Auto` acknowledges asynchronously
+    pub latency_threshold: Duration,
+    /// Smoothed connection pool wait above which `Auto` acknowledges asynchronously
+    pub pool_wait_threshold: Duration,
+    /// How long `Auto` stays asynchronous after the last slow sample
+    pub cooldown: Duration,
+}
+
+impl Default for IngestionConfig {
+    fn default() -> Self {
+        Self {
+            mode: IngestionMode::Sync,
+            latency_threshold: Duration::from_millis(250),
+            pool_wait_threshold: Duration::from_millis(100),
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// How a replayed callback for an already known transaction is answered.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateCallbackResponse {
+    /// 200 with the stored transaction's current id and status
+    Echo,
+    /// 200 with a minimal `{"duplicate": true}` body
+    Ack,
+}
+
+/// Where asynchronously generated export artifacts are written.
+#[derive(Debug, Deserialize, Clone)]
+pub enum ExportStorageConfig {
+    Local {
+        path: String,
+    },
+    S3 {
+        bucket: String,
+        region: String,
+        endpoint: Option<String>,
+        access_key: Option<String>,
+        secret_key: Option<String>,
+    },
 }
 
 pub mod assets;
@@ -17,21 +585,200 @@ impl Config {
     pub fn from_env() -> anyhow::Result<Self> {
         dotenv().ok(); // Load .env file if present
 
-        let _allowed_ips =
+        let allowed_ips =
             parse_allowed_ips(&env::var("ALLOWED_IPS").unwrap_or_else(|_| "*".to_string()))?;
+        let trust_proxy_headers: bool = env::var("TRUST_PROXY_HEADERS")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()?;
+        let metrics_enabled: bool = env::var("METRICS_ENABLED")
+            .unwrap_or_else(|_| "true".to_string())
+            .parse()?;
+        let trusted_proxy_depth: usize = env::var("TRUSTED_PROXY_DEPTH")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()?;
+        let trusted_proxy_header = parse_proxy_header(
+            &env::var("TRUSTED_PROXY_HEADER").unwrap_or_else(|_| "x-forwarded-for".to_string()),
+        )?;
 
         let log_format = parse_log_format(
             &env::var("LOG_FORMAT").unwrap_or_else(|_| "text".to_string()),
         )?;
 
+        let export_storage = parse_export_storage(
+            &env::var("EXPORT_STORAGE").unwrap_or_else(|_| "local".to_string()),
+        )?;
+
+        let duplicate_callback_response = parse_duplicate_callback_response(
+            &env::var("DUPLICATE_CALLBACK_RESPONSE").unwrap_or_else(|_| "echo".to_string()),
+        )?;
+
+        let app_env = env::var("APP_ENV").unwrap_or_else(|_| "development".to_string());
+        let capture_query_plans: bool = env::var("CAPTURE_QUERY_PLANS")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()?;
+        if capture_query_plans && app_env.eq_ignore_ascii_case("production") {
+            anyhow::bail!("CAPTURE_QUERY_PLANS must not be enabled when APP_ENV=production");
+        }
+
+        let redis_required_features = parse_required_features(
+            &env::var("REDIS_REQUIRED_FEATURES").unwrap_or_default(),
+        )?;
+
+        let webhook_dispatch = parse_webhook_dispatch()?;
+        let ingestion = parse_ingestion()?;
+        let buffered_writes = parse_buffered_writes()?;
+        let erasure_policy = parse_erasure_policy(&env::var("ERASURE_POLICY").unwrap_or_default())?;
+        let deprecation_schedule = deprecation::Schedule::parse(
+            &env::var("DEPRECATION_DATES").unwrap_or_default(),
+            &env::var("DEPRECATION_SUNSETS").unwrap_or_default(),
+        )?;
+        let flag_refresh_interval = Duration::from_secs(
+            env::var("FEATURE_FLAG_REFRESH_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()?,
+        );
+        let flag_full_refresh_every: u64 = env::var("FEATURE_FLAG_FULL_REFRESH_EVERY")
+            .unwrap_or_else(|_| DEFAULT_FULL_REFRESH_EVERY.to_string())
+            .parse()?;
+        if flag_refresh_interval.is_zero() || flag_full_refresh_every == 0 {
+            anyhow::bail!("FEATURE_FLAG_REFRESH_SECS and FEATURE_FLAG_FULL_REFRESH_EVERY must be at least 1");
+        }
+        let flag_invalidation_channel =
+            parse_invalidation_channel(env::var("FEATURE_FLAG_INVALIDATION_CHANNEL").ok());
+        let callback_idempotency_ttl = Duration::from_secs(
+            env::var("CALLBACK_IDEMPOTENCY_TTL_SECS")
+                .unwrap_or_else(|_| DEFAULT_CALLBACK_TTL.as_secs().to_string())
+                .parse()?,
+        );
+        if callback_idempotency_ttl.is_zero() {
+            anyhow::bail!("CALLBACK_IDEMPOTENCY_TTL_SECS must be at least 1");
+        }
+        let reconciliation = parse_reconciliation()?;
+        let horizon = parse_horizon()?;
+        let payment_signing_secret =
+            parse_payment_signing_secret(env::var("PAYMENT_SIGNING_SECRET").ok())?;
+        let stellar = parse_stellar_network(
+            env::var("STELLAR_NETWORK").ok(),
+            env::var("STELLAR_NETWORK_PASSPHRASE").ok(),
+            env::var("STELLAR_HORIZON_URL").ok(),
+            env::var("SOROBAN_RPC_URL").ok(),
+            app_env.eq_ignore_ascii_case("production"),
+            is_sandbox_env(&app_env),
+        )?;
+        let payment_processor = parse_payment_processor(&stellar.network_passphrase)?;
+        let anchor_signing_key = parse_anchor_signing_key(
+            env::var("ANCHOR_SIGNING_KEY").ok(),
+            app_env.eq_ignore_ascii_case("production"),
+        )?;
+        let admin_auth = parse_admin_auth(
+            env::var("ADMIN_API_KEYS").ok(),
+            env::var("ADMIN_API_KEY").ok(),
+            env::var("ADMIN_JWT_SECRET").ok(),
+            env::var("ADMIN_JWT_ISSUER").ok(),
+            app_env.eq_ignore_ascii_case("production"),
+        )?;
+        let telemetry = parse_telemetry(
+            env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok(),
+            env::var("OTEL_SERVICE_NAME").ok(),
+        )?;
+        let partition_retention = PartitionRetentionConfig {
+            months: parse_positive(
+                "PARTITION_RETENTION_MONTHS",
+                env::var("PARTITION_RETENTION_MONTHS").ok(),
+                PartitionRetentionConfig::default().months as usize,
+            )? as u32,
+            mode: parse_partition_retention_mode(
+                &env::var("PARTITION_RETENTION_MODE").unwrap_or_else(|_| "detach".to_string()),
+            )?,
+        };
+        let partition_interval = parse_partition_interval(
+            &env::var("PARTITION_INTERVAL").unwrap_or_else(|_| "monthly".to_string()),
+        )?;
+        let partition_maintenance_hours = parse_positive(
+            "PARTITION_MAINTENANCE_INTERVAL_HOURS",
+            env::var("PARTITION_MAINTENANCE_INTERVAL_HOURS").ok(),
+            24,
+        )? as u64;
+        let sandbox = SandboxConfig {
+            submission_delay: Duration::from_millis(
+                env::var("SANDBOX_SUBMISSION_DELAY_MS")
+                    .unwrap_or_else(|_| "2000".to_string())
+                    .parse()?,
+            ),
+        };
+        let server_limits = parse_server_limits()?;
+        let quotes = QuoteConfig {
+            sep38_url: env::var("SEP38_URL").ok(),
+            sep38_auth_token: env::var("SEP38_AUTH_TOKEN").ok(),
+            amount_tolerance: parse_quote_tolerance(
+                &env::var("QUOTE_AMOUNT_TOLERANCE").unwrap_or_else(|_| "0.01".to_string()),
+            )?,
+            retention_hours: env::var("QUOTE_RETENTION_HOURS")
+                .unwrap_or_else(|_| "24".to_string())
+                .parse()?,
+        };
+
         Ok(Config {
             server_port: env::var("SERVER_PORT")
                 .unwrap_or_else(|_| "3000".to_string())
                 .parse()?,
             database_url: env::var("DATABASE_URL")?,
             database_replica_url: env::var("DATABASE_REPLICA_URL").ok(),
-            stellar_horizon_url: env::var("STELLAR_HORIZON_URL")?,
+            stellar,
+            payment_listener_account: env::var("PAYMENT_LISTENER_ACCOUNT")
+                .ok()
+                .filter(|account| !account.trim().is_empty()),
+            payment_listener_start_cursor: env::var("PAYMENT_LISTENER_START_CURSOR")
+                .ok()
+                .filter(|cursor| !cursor.trim().is_empty()),
+            trustline_listener_start_cursor: env::var("TRUSTLINE_LISTENER_START_CURSOR")
+                .ok()
+                .filter(|cursor| !cursor.trim().is_empty()),
             anchor_webhook_secret: env::var("ANCHOR_WEBHOOK_SECRET")?,
+            anchor_signing_key,
+            export_storage,
+            export_retention_hours: env::var("EXPORT_RETENTION_HOURS")
+                .unwrap_or_else(|_| "72".to_string())
+                .parse()?,
+            export_freshness_minutes: env::var("EXPORT_FRESHNESS_MINUTES")
+                .unwrap_or_else(|_| "15".to_string())
+                .parse()?,
+            duplicate_callback_response,
+            app_env,
+            capture_query_plans,
+            slow_query_threshold_ms: env::var("SLOW_QUERY_THRESHOLD_MS")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse()?,
+            redis_url: env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string()),
+            redis_required_features,
+            webhook_dispatch,
+            server_limits,
+            quotes,
+            deploy_version: env::var("DEPLOY_VERSION")
+                .unwrap_or_else(|_| env!("CARGO_PKG_VERSION").to_string()),
+            ingestion,
+            buffered_writes,
+            sandbox,
+            erasure_policy,
+            deprecation_schedule,
+            flag_refresh_interval,
+            flag_invalidation_channel,
+            flag_full_refresh_every,
+            allowed_ips,
+            trust_proxy_headers,
+            trusted_proxy_header,
+            trusted_proxy_depth,
+            callback_idempotency_ttl,
+            reconciliation,
+            horizon,
+            payment_signing_secret,
+            payment_processor,
+            partition_retention,
+            partition_interval,
+            partition_maintenance_hours,
+            metrics_enabled,
+            admin_auth,
+            telemetry,
         })
     }
 }
@@ -46,7 +793,7 @@ fn parse_allowed_ips(raw: &str) -> anyhow::Result<AllowedIps> {
         .split(',')
         .map(str::trim)
         .filter(|entry| !entry.is_empty())
-        .map(str::parse::<IpNet>)
+        .map(parse_cidr)
         .collect::<Result<Vec<_>, _>>()?;
 
     if cidrs.is_empty() {
@@ -56,6 +803,168 @@ fn parse_allowed_ips(raw: &str) -> anyhow::Result<AllowedIps> {
     Ok(AllowedIps::Cidrs(cidrs))
 }
 
+fn parse_proxy_header(raw: &str) -> anyhow::Result<ProxyHeader> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "x-forwarded-for" => Ok(ProxyHeader::XForwardedFor),
+        "forwarded" => Ok(ProxyHeader::Forwarded),
+        _ => anyhow::bail!("TRUSTED_PROXY_HEADER must be 'x-forwarded-for' or 'forwarded'"),
+    }
+}
+
+/// The default channel when unset; an empty value turns invalidation off
+fn parse_invalidation_channel(raw: Option<String>) -> Option<String> {
+    match raw {
+        None => Some(DEFAULT_INVALIDATION_CHANNEL.to_string()),
+        Some(channel) => Some(channel.trim().to_string()).filter(|c| !c.is_empty()),
+    }
+}
+
+/// A CIDR, or a bare address as its single-host range
+fn parse_cidr(entry: &str) -> anyhow::Result<IpNet> {
+    if let Ok(ip) = entry.parse::<std::net::IpAddr>() {
+        return Ok(IpNet::from(ip));
+    }
+    entry
+        .parse::<IpNet>()
+        .map_err(|_| anyhow::anyhow!("ALLOWED_IPS entry '{}' is not an address or CIDR", entry))
+}
+
+/// A set key must be a valid `G` account; production requires one
+fn parse_anchor_signing_key(
+    raw: Option<String>,
+    production: bool,
+) -> anyhow::Result<Option<String>> {
+    match raw.map(|key| key.trim().to_string()).filter(|key| !key.is_empty()) {
+        Some(key) => {
+            crate::middleware::anchor_signature::parse_signing_key(&key)?;
+            Ok(Some(key))
+        }
+        None if production => anyhow::bail!("ANCHOR_SIGNING_KEY is required in production"),
+        None => Ok(None),
+    }
+}
+
+/// The secret is checked but never echoed in the error
+fn parse_payment_signing_secret(raw: Option<String>) -> anyhow::Result<Option<String>> {
+    match raw.map(|secret| secret.trim().to_string()).filter(|secret| !secret.is_empty()) {
+        Some(secret) => {
+            if let Err(e) = crate::stellar::strkey::decode_secret_seed(&secret) {
+                anyhow::bail!("PAYMENT_SIGNING_SECRET is not a valid secret seed: {}", e);
+            }
+            Ok(Some(secret))
+        }
+        None => Ok(None),
+    }
+}
+
+/// `ADMIN_API_KEYS` is a comma-separated list of `name:role:key`, with
+/// role `read_only` or `operator`; the legacy `ADMIN_API_KEY` is an operator
+/// key named `admin`. Production has no default key and needs at least one
+/// key or a JWT secret. Errors name the entry, never the key.
+fn parse_admin_auth(
+    keys: Option<String>,
+    legacy_key: Option<String>,
+    jwt_secret: Option<String>,
+    jwt_issuer: Option<String>,
+    production: bool,
+) -> anyhow::Result<AdminAuthConfig> {
+    let non_empty = |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+
+    let mut api_keys: Vec<AdminApiKey> = Vec::new();
+    for (position, entry) in non_empty(keys).unwrap_or_default().split(',').enumerate() {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        let mut parts = entry.splitn(3, ':');
+        let (Some(name), Some(role), Some(key)) = (parts.next(), parts.next(), parts.next()) else {
+            anyhow::bail!("ADMIN_API_KEYS entry {} must be name:role:key", position + 1);
+        };
+        let (name, key) = (name.trim(), key.trim());
+        let role = AdminRole::parse(role).ok_or_else(|| {
+            anyhow::anyhow!(
+                "ADMIN_API_KEYS entry '{}': role must be read_only or operator",
+                name
+            )
+        })?;
+        if name.is_empty() || key.is_empty() {
+            anyhow::bail!("ADMIN_API_KEYS entry {} has an empty name or key", position + 1);
+        }
+        if api_keys.iter().any(|existing| existing.name == name || existing.key == key) {
+            anyhow::bail!("ADMIN_API_KEYS entry '{}' repeats a name or key", name);
+        }
+        api_keys.push(AdminApiKey {
+            name: name.to_string(),
+            role,
+            key: key.to_string(),
+        });
+    }
+    if let Some(key) = non_empty(legacy_key) {
+        if api_keys.iter().any(|existing| existing.name == "admin" || existing.key == key) {
+            anyhow::bail!("ADMIN_API_KEY repeats a name or key of ADMIN_API_KEYS");
+        }
+        api_keys.push(AdminApiKey {
+            name: "admin".to_string(),
+            role: AdminRole::Operator,
+            key,
+        });
+    }
+
+    let jwt_secret = non_empty(jwt_secret);
+    if api_keys.is_empty() && jwt_secret.is_none() {
+        if production {
+            anyhow::bail!("ADMIN_API_KEYS, ADMIN_API_KEY or ADMIN_JWT_SECRET is required in production");
+        }
+        return Ok(AdminAuthConfig {
+            jwt_issuer: non_empty(jwt_issuer),
+            ..Default::default()
+        });
+    }
+    Ok(AdminAuthConfig {
+        api_keys,
+        jwt_secret,
+        jwt_issuer: non_empty(jwt_issuer),
+    })
+}
+
+fn parse_partition_retention_mode(raw: &str) -> anyhow::Result<PartitionRetentionMode> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "detach" => Ok(PartitionRetentionMode::Detach),
+        "drop" => Ok(PartitionRetentionMode::Drop),
+        "archive" => Ok(PartitionRetentionMode::Archive),
+        _ => anyhow::bail!("PARTITION_RETENTION_MODE must be 'detach', 'drop' or 'archive'"),
+    }
+}
+
+fn parse_partition_interval(raw: &str) -> anyhow::Result<PartitionInterval> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "daily" => Ok(PartitionInterval::Daily),
+        "weekly" => Ok(PartitionInterval::Weekly),
+        "monthly" => Ok(PartitionInterval::Monthly),
+        _ => anyhow::bail!("PARTITION_INTERVAL must be 'daily', 'weekly' or 'monthly'"),
+    }
+}
+
+/// An endpoint must be an `http(s)` URL; blank values count as unset
+fn parse_telemetry(
+    endpoint: Option<String>,
+    service_name: Option<String>,
+) -> anyhow::Result<TelemetryConfig> {
+    let blank_to_none =
+        |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let otlp_endpoint = blank_to_none(endpoint);
+    if let Some(endpoint) = &otlp_endpoint {
+        if !(endpoint.starts_with("http://") || endpoint.starts_with("https://")) {
+            anyhow::bail!("OTEL_EXPORTER_OTLP_ENDPOINT must be an http:// or https:// URL");
+        }
+    }
+    Ok(TelemetryConfig {
+        otlp_endpoint,
+        service_name: blank_to_none(service_name)
+            .unwrap_or_else(|| TelemetryConfig::default().service_name),
+    })
+}
+
 fn parse_log_format(raw: &str) -> anyhow::Result<LogFormat> {
     match raw.trim().to_ascii_lowercase().as_str() {
         "text" => Ok(LogFormat::Text),
@@ -63,3 +972,637 @@ fn parse_log_format(raw: &str) -> anyhow::Result<LogFormat> {
         _ => anyhow::bail!("LOG_FORMAT must be 'text' or 'json'"),
     }
 }
+
+fn parse_export_storage(raw: &str) -> anyhow::Result<ExportStorageConfig> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "local" => Ok(ExportStorageConfig::Local {
+            path: env::var("EXPORT_LOCAL_PATH").unwrap_or_else(|_| "./exports".to_string()),
+        }),
+        "s3" => Ok(ExportStorageConfig::S3 {
+            bucket: env::var("EXPORT_S3_BUCKET")
+                .map_err(|_| anyhow::anyhow!("EXPORT_S3_BUCKET is required when EXPORT_STORAGE=s3"))?,
+            region: env::var("EXPORT_S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            endpoint: env::var("EXPORT_S3_ENDPOINT").ok(),
+            access_key: env::var("EXPORT_S3_ACCESS_KEY").ok(),
+            secret_key: env::var("EXPORT_S3_SECRET_KEY").ok(),
+        }),
+        _ => anyhow::bail!("EXPORT_STORAGE must be 'local' or 's3'"),
+    }
+}
+
+fn parse_duplicate_callback_response(raw: &str) -> anyhow::Result<DuplicateCallbackResponse> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "echo" => Ok(DuplicateCallbackResponse::Echo),
+        "ack" => Ok(DuplicateCallbackResponse::Ack),
+        _ => anyhow::bail!("DUPLICATE_CALLBACK_RESPONSE must be 'echo' or 'ack'"),
+    }
+}
+
+fn parse_ingestion_mode(raw: &str) -> anyhow::Result<IngestionMode> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "sync" => Ok(IngestionMode::Sync),
+        "async" => Ok(IngestionMode::Async),
+        "auto" => Ok(IngestionMode::Auto),
+        _ => anyhow::bail!("INGESTION_MODE must be 'sync', 'async' or 'auto'"),
+    }
+}
+
+fn parse_ingestion() -> anyhow::Result<IngestionConfig> {
+    let millis = |name: &str, default: &str| -> anyhow::Result<Duration> {
+        Ok(Duration::from_millis(env::var(name).unwrap_or_else(|_| default.to_string()).parse()?))
+    };
+
+    Ok(IngestionConfig {
+        mode: parse_ingestion_mode(
+            &env::var("INGESTION_MODE").unwrap_or_else(|_| "sync".to_string()),
+        )?,
+        latency_threshold: millis("INGESTION_AUTO_LATENCY_MS", "250")?,
+        pool_wait_threshold: millis("INGESTION_AUTO_POOL_WAIT_MS", "100")?,
+        cooldown: Duration::from_secs(
+            env::var("INGESTION_AUTO_COOLDOWN_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()?,
+        ),
+    })
+}
+
+fn parse_positive(name: &str, raw: Option<String>, default: usize) -> anyhow::Result<usize> {
+    let value = match raw {
+        Some(raw) => raw.trim().parse()?,
+        None => default,
+    };
+    if value == 0 {
+        anyhow::bail!("{} must be greater than 0", name);
+    }
+    Ok(value)
+}
+
+fn parse_buffered_writes() -> anyhow::Result<BufferedWriteConfig> {
+    let millis = |name: &str, default: &str| -> anyhow::Result<Duration> {
+        Ok(Duration::from_millis(env::var(name).unwrap_or_else(|_| default.to_string()).parse()?))
+    };
+
+    Ok(BufferedWriteConfig {
+        capacity: parse_positive(
+            "BUFFERED_WRITE_CAPACITY",
+            env::var("BUFFERED_WRITE_CAPACITY").ok(),
+            10_000,
+        )?,
+        batch_size: parse_positive(
+            "BUFFERED_WRITE_BATCH_SIZE",
+            env::var("BUFFERED_WRITE_BATCH_SIZE").ok(),
+            500,
+        )?,
+        flush_interval: millis("BUFFERED_WRITE_INTERVAL_MS", "250")?,
+        shutdown_timeout: millis("BUFFERED_WRITE_SHUTDOWN_TIMEOUT_MS", "5000")?,
+    })
+}
+
+fn parse_reconciliation() -> anyhow::Result<ReconciliationConfig> {
+    let defaults = ReconciliationConfig::default();
+    let secs = |name: &str, default: Duration| -> anyhow::Result<Duration> {
+        let secs = parse_positive(name, env::var(name).ok(), default.as_secs() as usize)?;
+        Ok(Duration::from_secs(secs as u64))
+    };
+
+    let config = ReconciliationConfig {
+        poll_interval: secs("RECONCILIATION_POLL_INTERVAL_SECS", defaults.poll_interval)?,
+        batch_size: parse_positive(
+            "RECONCILIATION_BATCH_SIZE",
+            env::var("RECONCILIATION_BATCH_SIZE").ok(),
+            defaults.batch_size as usize,
+        )? as i64,
+        min_age: secs("RECONCILIATION_MIN_AGE_SECS", defaults.min_age)?,
+        max_age: secs("RECONCILIATION_MAX_AGE_SECS", defaults.max_age)?,
+    };
+    config.validate()?;
+    Ok(config)
+}
+
+fn parse_horizon() -> anyhow::Result<HorizonConfig> {
+    let defaults = HorizonConfig::default();
+    let positive = |name: &str, default: u64| -> anyhow::Result<u64> {
+        Ok(parse_positive(name, env::var(name).ok(), default as usize)? as u64)
+    };
+
+    let config = HorizonConfig {
+        request_timeout: Duration::from_secs(positive(
+            "HORIZON_TIMEOUT_SECS",
+            defaults.request_timeout.as_secs(),
+        )?),
+        max_retries: match env::var("HORIZON_MAX_RETRIES") {
+            Ok(raw) => raw.trim().parse()?,
+            Err(_) => defaults.max_retries,
+        },
+        retry_base_delay: Duration::from_millis(positive(
+            "HORIZON_RETRY_BASE_DELAY_MS",
+            defaults.retry_base_delay.as_millis() as u64,
+        )?),
+        retry_max_delay: Duration::from_millis(positive(
+            "HORIZON_RETRY_MAX_DELAY_MS",
+            defaults.retry_max_delay.as_millis() as u64,
+        )?),
+        circuit_failure_threshold: positive(
+            "HORIZON_CIRCUIT_FAILURE_THRESHOLD",
+            defaults.circuit_failure_threshold as u64,
+        )? as u32,
+        circuit_reset_timeout: Duration::from_secs(positive(
+            "HORIZON_CIRCUIT_RESET_SECS",
+            defaults.circuit_reset_timeout.as_secs(),
+        )?),
+    };
+    config.validate()?;
+    Ok(config)
+}
+
+/// `STELLAR_NETWORK` is `testnet` or `pubnet`, by default pubnet in
+/// production and testnet elsewhere, or `custom`. Presets use SDF's Horizon
+/// unless `STELLAR_HORIZON_URL` names another; `custom` needs it and
+/// `STELLAR_NETWORK_PASSPHRASE`. A passphrase set for a preset must be the
+/// preset's. Without `STELLAR_NETWORK`, the passphrase picks the network.
+fn parse_stellar_network(
+    network: Option<String>,
+    passphrase: Option<String>,
+    horizon_url: Option<String>,
+    soroban_rpc_url: Option<String>,
+    production: bool,
+    sandbox: bool,
+) -> anyhow::Result<StellarNetwork> {
+    let set = |raw: Option<String>| raw.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let passphrase = set(passphrase);
+    let kind = match set(network) {
+        Some(raw) => match raw.to_ascii_lowercase().as_str() {
+            "testnet" => NetworkKind::Testnet,
+            "pubnet" | "public" | "mainnet" => NetworkKind::Pubnet,
+            "custom" => NetworkKind::Custom,
+            other => anyhow::bail!(
+                "Invalid STELLAR_NETWORK '{}'; expected testnet, pubnet or custom",
+                other
+            ),
+        },
+        None => match passphrase.as_deref() {
+            Some(TESTNET_PASSPHRASE) => NetworkKind::Testnet,
+            Some(PUBLIC_NETWORK_PASSPHRASE) => NetworkKind::Pubnet,
+            Some(_) => NetworkKind::Custom,
+            None if production => NetworkKind::Pubnet,
+            None => NetworkKind::Testnet,
+        },
+    };
+
+    let (network_passphrase, sdf_horizon) = match (kind.preset(), passphrase) {
+        (Some((preset, _)), Some(passphrase)) if passphrase != preset => anyhow::bail!(
+            "STELLAR_NETWORK_PASSPHRASE is not the {} passphrase",
+            kind.as_str()
+        ),
+        (Some((preset, horizon)), _) => (preset.to_string(), Some(horizon)),
+        (None, Some(passphrase)) => (passphrase, None),
+        (None, None) => anyhow::bail!("STELLAR_NETWORK=custom needs STELLAR_NETWORK_PASSPHRASE"),
+    };
+    let horizon_url = match (set(horizon_url), sdf_horizon) {
+        (Some(url), _) => url,
+        // The sandbox never calls Horizon
+        (None, _) if sandbox => "sandbox://horizon".to_string(),
+        (None, Some(url)) => url.to_string(),
+        (None, None) => anyhow::bail!("STELLAR_NETWORK=custom needs STELLAR_HORIZON_URL"),
+    };
+    reqwest::Url::parse(&horizon_url)
+        .map_err(|e| anyhow::anyhow!("STELLAR_HORIZON_URL is not a valid URL: {}", e))?;
+    let soroban_rpc_url = set(soroban_rpc_url);
+    if let Some(url) = &soroban_rpc_url {
+        reqwest::Url::parse(url)
+            .map_err(|e| anyhow::anyhow!("SOROBAN_RPC_URL is not a valid URL: {}", e))?;
+    }
+
+    Ok(StellarNetwork {
+        kind,
+        network_passphrase,
+        horizon_url,
+        soroban_rpc_url,
+    })
+}
+
+/// Payments are signed for the configured [`StellarNetwork`]
+fn parse_payment_processor(network_passphrase: &str) -> anyhow::Result<PaymentProcessorConfig> {
+    let defaults = PaymentProcessorConfig::default();
+    let secs = |name: &str, default: Duration| -> anyhow::Result<Duration> {
+        let secs = parse_positive(name, env::var(name).ok(), default.as_secs() as usize)?;
+        Ok(Duration::from_secs(secs as u64))
+    };
+
+    let base_fee = parse_positive(
+        "PAYMENT_BASE_FEE",
+        env::var("PAYMENT_BASE_FEE").ok(),
+        defaults.base_fee as usize,
+    )?;
+    if base_fee < BASE_FEE as usize || base_fee > u32::MAX as usize {
+        anyhow::bail!("PAYMENT_BASE_FEE must be at least {} stroops", BASE_FEE);
+    }
+
+    Ok(PaymentProcessorConfig {
+        poll_interval: secs("PAYMENT_PROCESSOR_POLL_INTERVAL_SECS", defaults.poll_interval)?,
+        batch_size: parse_positive(
+            "PAYMENT_PROCESSOR_BATCH_SIZE",
+            env::var("PAYMENT_PROCESSOR_BATCH_SIZE").ok(),
+            defaults.batch_size as usize,
+        )? as i64,
+        base_fee: base_fee as u32,
+        submission_timeout: secs("PAYMENT_SUBMISSION_TIMEOUT_SECS", defaults.submission_timeout)?,
+        network_passphrase: network_passphrase.to_string(),
+    })
+}
+
+fn parse_quote_tolerance(raw: &str) -> anyhow::Result<BigDecimal> {
+    let tolerance: BigDecimal = raw
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("QUOTE_AMOUNT_TOLERANCE must be a decimal"))?;
+    if tolerance < BigDecimal::from(0) || tolerance >= BigDecimal::from(1) {
+        anyhow::bail!("QUOTE_AMOUNT_TOLERANCE must be in [0, 1)");
+    }
+    Ok(tolerance)
+}
+
+fn parse_webhook_dispatch() -> anyhow::Result<WebhookDispatchConfig> {
+    let config = WebhookDispatchConfig {
+        max_in_flight: env::var("WEBHOOK_MAX_IN_FLIGHT")
+            .unwrap_or_else(|_| "4".to_string())
+            .parse()?,
+        claim_batch_size: env::var("WEBHOOK_CLAIM_BATCH_SIZE")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse()?,
+        failure_rate_threshold: env::var("WEBHOOK_FAILURE_RATE_THRESHOLD")
+            .unwrap_or_else(|_| "0.5".to_string())
+            .parse()?,
+        failure_window: env::var("WEBHOOK_FAILURE_WINDOW")
+            .unwrap_or_else(|_| "20".to_string())
+            .parse()?,
+        cooldown_secs: env::var("WEBHOOK_COOLDOWN_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()?,
+        delivery_timeout_secs: env::var("WEBHOOK_DELIVERY_TIMEOUT_SECS")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse()?,
+        max_attempts: env::var("WEBHOOK_MAX_ATTEMPTS")
+            .unwrap_or_else(|_| "8".to_string())
+            .parse()?,
+    };
+
+    if config.max_in_flight == 0 || config.claim_batch_size <= 0 {
+        anyhow::bail!("WEBHOOK_MAX_IN_FLIGHT and WEBHOOK_CLAIM_BATCH_SIZE must be at least 1");
+    }
+    if !(config.failure_rate_threshold > 0.0 && config.failure_rate_threshold <= 1.0) {
+        anyhow::bail!("WEBHOOK_FAILURE_RATE_THRESHOLD must be in (0, 1]");
+    }
+    Ok(config)
+}
+
+/// Read a server limit: an explicit value wins, `0` disables the limit, and an
+/// unset variable falls back to the production default only in production.
+fn parse_limit<T: FromStr + PartialEq + Default>(
+    name: &str,
+    raw: Option<String>,
+) -> anyhow::Result<Option<T>>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match raw {
+        Some(raw) => {
+            let value: T = raw
+                .trim()
+                .parse()
+                .map_err(|e| anyhow::anyhow!("{} must be a non-negative integer: {}", name, e))?;
+            Ok((value != T::default()).then_some(value))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Unset limits stay `None`, so the server only runs its own accept loop
+/// when a deployment asks for a limit
+fn parse_server_limits() -> anyhow::Result<ServerLimits> {
+    let limit = |name: &str| parse_limit(name, env::var(name).ok());
+    let secs = |name: &str| -> anyhow::Result<Option<Duration>> {
+        Ok(limit(name)?.map(Duration::from_secs))
+    };
+
+    Ok(ServerLimits {
+        header_read_timeout: secs("SERVER_HEADER_READ_TIMEOUT_SECS")?,
+        idle_timeout: secs("SERVER_IDLE_TIMEOUT_SECS")?,
+        max_connections: parse_limit(
+            "SERVER_MAX_CONNECTIONS",
+            env::var("SERVER_MAX_CONNECTIONS").ok(),
+        )?,
+        max_requests_per_connection: limit("SERVER_MAX_REQUESTS_PER_CONNECTION")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duplicate_callback_response() {
+        assert_eq!(
+            parse_duplicate_callback_response("echo").unwrap(),
+            DuplicateCallbackResponse::Echo
+        );
+        assert_eq!(
+            parse_duplicate_callback_response(" ACK ").unwrap(),
+            DuplicateCallbackResponse::Ack
+        );
+        assert!(parse_duplicate_callback_response("silent").is_err());
+    }
+
+    #[test]
+    fn test_parse_ingestion_mode() {
+        assert_eq!(parse_ingestion_mode("sync").unwrap(), IngestionMode::Sync);
+        assert_eq!(parse_ingestion_mode(" Async ").unwrap(), IngestionMode::Async);
+        assert_eq!(parse_ingestion_mode("AUTO").unwrap(), IngestionMode::Auto);
+        assert!(parse_ingestion_mode("eventually").is_err());
+    }
+
+    #[test]
+    fn test_parse_allowed_ips() {
+        assert_eq!(parse_allowed_ips(" * ").unwrap(), AllowedIps::Any);
+        assert_eq!(
+            parse_allowed_ips("10.0.0.0/8, 203.0.113.7,2001:db8::/32").unwrap(),
+            AllowedIps::Cidrs(vec![
+                "10.0.0.0/8".parse().unwrap(),
+                "203.0.113.7/32".parse().unwrap(),
+                "2001:db8::/32".parse().unwrap(),
+            ])
+        );
+        assert!(parse_allowed_ips("").is_err());
+        assert!(parse_allowed_ips("10.0.0.0/8,office").is_err());
+    }
+
+    #[test]
+    fn test_parse_telemetry() {
+        assert_eq!(parse_telemetry(None, None).unwrap(), TelemetryConfig::default());
+        assert_eq!(
+            parse_telemetry(Some(" ".to_string()), Some("".to_string())).unwrap(),
+            TelemetryConfig::default()
+        );
+
+        let config = parse_telemetry(
+            Some("http://otel-collector:4317".to_string()),
+            Some("synapse-staging".to_string()),
+        )
+        .unwrap();
+        assert_eq!(config.otlp_endpoint.as_deref(), Some("http://otel-collector:4317"));
+        assert_eq!(config.service_name, "synapse-staging");
+
+        assert!(parse_telemetry(Some("otel-collector:4317".to_string()), None).is_err());
+    }
+
+    #[test]
+    fn test_parse_proxy_header() {
+        assert_eq!(parse_proxy_header("X-Forwarded-For").unwrap(), ProxyHeader::XForwardedFor);
+        assert_eq!(parse_proxy_header(" forwarded ").unwrap(), ProxyHeader::Forwarded);
+        assert!(parse_proxy_header("x-real-ip").is_err());
+    }
+
+    #[test]
+    fn test_parse_invalidation_channel() {
+        assert_eq!(
+            parse_invalidation_channel(None).as_deref(),
+            Some(DEFAULT_INVALIDATION_CHANNEL)
+        );
+        assert_eq!(
+            parse_invalidation_channel(Some(" staging:flags ".to_string())).as_deref(),
+            Some("staging:flags")
+        );
+        assert_eq!(parse_invalidation_channel(Some(" ".to_string())), None);
+    }
+
+    #[test]
+    fn test_sandbox_environment() {
+        let deployment = |environment: &str| Deployment {
+            version: "1.0.0".to_string(),
+            environment: environment.to_string(),
+        };
+        assert!(deployment("sandbox").is_sandbox());
+        assert!(deployment(" Sandbox ").is_sandbox());
+        assert!(!deployment("production").is_sandbox());
+        assert!(!Deployment::default().is_sandbox());
+    }
+
+    #[test]
+    fn test_parse_positive() {
+        assert_eq!(parse_positive("X", None, 500).unwrap(), 500);
+        assert_eq!(parse_positive("X", Some(" 20 ".to_string()), 500).unwrap(), 20);
+        assert!(parse_positive("X", Some("0".to_string()), 500).is_err());
+        assert!(parse_positive("X", Some("lots".to_string()), 500).is_err());
+    }
+
+    #[test]
+    fn test_reconciliation_max_age_must_exceed_min_age() {
+        assert!(ReconciliationConfig::default().validate().is_ok());
+        let config = ReconciliationConfig {
+            min_age: Duration::from_secs(600),
+            max_age: Duration::from_secs(600),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_horizon_retry_delay_cap_covers_base_delay() {
+        assert!(HorizonConfig::default().validate().is_ok());
+        let config = HorizonConfig {
+            retry_base_delay: Duration::from_secs(5),
+            retry_max_delay: Duration::from_secs(1),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_anchor_signing_key() {
+        // The public key of the ed25519 seed [7; 32]
+        const KEY: &str = "GDVEU3DD4KOFECV66VIHWEZOYX4ZKR3WV27L464SIIPOU2IUI3JCZA57";
+        assert_eq!(
+            parse_anchor_signing_key(Some(format!(" {} ", KEY)), true).unwrap(),
+            Some(KEY.to_string())
+        );
+        assert_eq!(parse_anchor_signing_key(None, false).unwrap(), None);
+        assert_eq!(parse_anchor_signing_key(Some(" ".to_string()), false).unwrap(), None);
+        assert!(parse_anchor_signing_key(None, true).is_err());
+        assert!(parse_anchor_signing_key(Some("GABC".to_string()), false).is_err());
+    }
+
+    #[test]
+    fn test_parse_payment_signing_secret() {
+        const SECRET: &str = "SA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJUWVG";
+        assert_eq!(
+            parse_payment_signing_secret(Some(format!(" {} ", SECRET))).unwrap(),
+            Some(SECRET.to_string())
+        );
+        assert_eq!(parse_payment_signing_secret(None).unwrap(), None);
+        assert_eq!(parse_payment_signing_secret(Some(" ".to_string())).unwrap(), None);
+
+        let account = "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ";
+        let error = parse_payment_signing_secret(Some(account.to_string())).unwrap_err();
+        assert!(!error.to_string().contains(account));
+    }
+
+    #[test]
+    fn test_parse_partition_retention_mode() {
+        assert_eq!(
+            parse_partition_retention_mode("detach").unwrap(),
+            PartitionRetentionMode::Detach
+        );
+        assert_eq!(parse_partition_retention_mode(" DROP ").unwrap(), PartitionRetentionMode::Drop);
+        assert_eq!(
+            parse_partition_retention_mode("archive").unwrap(),
+            PartitionRetentionMode::Archive
+        );
+        assert!(parse_partition_retention_mode("truncate").is_err());
+    }
+
+    #[test]
+    fn test_parse_partition_interval() {
+        assert_eq!(parse_partition_interval("daily").unwrap(), PartitionInterval::Daily);
+        assert_eq!(parse_partition_interval(" Weekly ").unwrap(), PartitionInterval::Weekly);
+        assert_eq!(parse_partition_interval("monthly").unwrap(), PartitionInterval::Monthly);
+        assert!(parse_partition_interval("hourly").is_err());
+    }
+
+    /// Outside production and the sandbox, without a Soroban RPC
+    fn stellar_network(
+        network: Option<&str>,
+        passphrase: Option<&str>,
+        horizon_url: Option<&str>,
+    ) -> anyhow::Result<StellarNetwork> {
+        parse_stellar_network(
+            network.map(str::to_string),
+            passphrase.map(str::to_string),
+            horizon_url.map(str::to_string),
+            None,
+            false,
+            false,
+        )
+    }
+
+    #[test]
+    fn test_stellar_network_presets() {
+        let testnet = stellar_network(None, None, None).unwrap();
+        assert_eq!(testnet.kind, NetworkKind::Testnet);
+        assert_eq!(testnet.horizon_url, "https://horizon-testnet.stellar.org");
+
+        let pubnet = parse_stellar_network(None, None, None, None, true, false).unwrap();
+        assert_eq!(pubnet.network_passphrase, PUBLIC_NETWORK_PASSPHRASE);
+        assert_eq!(pubnet.horizon_url, "https://horizon.stellar.org");
+
+        // Own Horizon for a preset, picked by its passphrase
+        let own =
+            stellar_network(None, Some(PUBLIC_NETWORK_PASSPHRASE), Some("http://horizon:8000"))
+                .unwrap();
+        assert_eq!(own.kind, NetworkKind::Pubnet);
+        assert_eq!(own.horizon_url, "http://horizon:8000");
+
+        let soroban = Some("https://soroban.internal".to_string());
+        let with_rpc = parse_stellar_network(None, None, None, soroban, false, false).unwrap();
+        assert_eq!(with_rpc.soroban_rpc_url.as_deref(), Some("https://soroban.internal"));
+
+        let sandbox = parse_stellar_network(None, None, None, None, false, true).unwrap();
+        assert_eq!(sandbox.horizon_url, "sandbox://horizon");
+    }
+
+    #[test]
+    fn test_stellar_network_rejects_contradictions() {
+        assert!(stellar_network(Some("pubnet"), Some(TESTNET_PASSPHRASE), None).is_err());
+        assert!(stellar_network(Some("futurenet"), None, None).is_err());
+        assert!(stellar_network(None, None, Some("not a url")).is_err());
+
+        // A custom network has no defaults
+        let standalone = "Standalone Network ; February 2017";
+        let local = "http://localhost:8000";
+        assert!(stellar_network(Some("custom"), None, Some(local)).is_err());
+        assert!(stellar_network(Some("custom"), Some(standalone), None).is_err());
+        let custom = stellar_network(None, Some(standalone), Some(local)).unwrap();
+        assert_eq!(custom.kind, NetworkKind::Custom);
+    }
+
+    #[test]
+    fn test_horizon_must_serve_the_configured_network() {
+        let network = parse_stellar_network(None, None, None, None, true, false).unwrap();
+        let root = |passphrase: &str| HorizonRoot {
+            horizon_version: "2.30.0".to_string(),
+            history_latest_ledger: 100,
+            core_latest_ledger: 100,
+            network_passphrase: passphrase.to_string(),
+        };
+        assert!(network.check_horizon(&root(PUBLIC_NETWORK_PASSPHRASE)).is_ok());
+
+        let error = network.check_horizon(&root(TESTNET_PASSPHRASE)).unwrap_err().to_string();
+        assert!(error.contains("Test SDF Network"), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_quote_tolerance() {
+        assert_eq!(parse_quote_tolerance(" 0.01 ").unwrap(), "0.01".parse::<BigDecimal>().unwrap());
+        assert_eq!(parse_quote_tolerance("0").unwrap(), BigDecimal::from(0));
+        assert!(parse_quote_tolerance("1").is_err());
+        assert!(parse_quote_tolerance("-0.1").is_err());
+        assert!(parse_quote_tolerance("one percent").is_err());
+    }
+
+    #[test]
+    fn test_server_limit_unset_is_unlimited() {
+        assert_eq!(parse_limit::<u64>("X", None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_server_limit_zero_disables_and_explicit_value_wins() {
+        assert_eq!(parse_limit::<u64>("X", Some("0".to_string())).unwrap(), None);
+        assert_eq!(parse_limit::<u64>("X", Some("5".to_string())).unwrap(), Some(5));
+        assert!(parse_limit::<u64>("X", Some("soon".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_parse_admin_auth_keys() {
+        let config = parse_admin_auth(
+            Some("dashboard:read_only:read-key, alice:operator:op:key:with:colons".to_string()),
+            Some("legacy-key".to_string()),
+            None,
+            None,
+            true,
+        )
+        .unwrap();
+        let keys: Vec<_> = config
+            .api_keys
+            .iter()
+            .map(|key| (key.name.as_str(), key.role, key.key.as_str()))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("dashboard", AdminRole::ReadOnly, "read-key"),
+                ("alice", AdminRole::Operator, "op:key:with:colons"),
+                ("admin", AdminRole::Operator, "legacy-key"),
+            ]
+        );
+        // Keys never show up in logs
+        assert!(!format!("{:?}", config).contains("read-key"));
+    }
+
+    #[test]
+    fn test_parse_admin_auth_rejects_bad_entries() {
+        for raw in ["dashboard:read_only", "dashboard:root:key", ":operator:key", "a:operator:k,b:operator:k"] {
+            let error = parse_admin_auth(Some(raw.to_string()), None, None, None, false).unwrap_err();
+            assert!(!error.to_string().contains(":k"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_parse_admin_auth_defaults() {
+        let development = parse_admin_auth(None, None, None, None, false).unwrap();
+        assert_eq!(development.api_keys, AdminAuthConfig::default().api_keys);
+
+        assert!(parse_admin_auth(None, None, None, None, true).is_err());
+        let jwt_only = parse_admin_auth(None, None, Some("secret".to_string()), None, true).unwrap();
+        assert!(jwt_only.api_keys.is_empty());
+        assert_eq!(jwt_only.jwt_secret.as_deref(), Some("secret"));
+    }
+}
@@ -1,14 +1,27 @@
-use sqlx::{Postgres, Transaction as SqlxTransaction};
+use sqlx::PgConnection;
 use uuid::Uuid;
 use serde_json::{json, Value as JsonValue};
 use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::utils::diff::Diff;
 
 /// Entity type constants for audit logs
 pub const ENTITY_TRANSACTION: &str = "transaction";
 pub const ENTITY_SETTLEMENT: &str = "settlement";
+pub const ENTITY_FEATURE_FLAG: &str = "feature_flag";
+pub const ENTITY_ASSET: &str = "asset";
+pub const ENTITY_API_TOKEN: &str = "api_token";
+pub const ENTITY_SANDBOX: &str = "sandbox";
+pub const ENTITY_ERASURE: &str = "erasure";
+
+/// Stable audit entity id for entities keyed by name rather than UUID
+pub fn named_entity_id(entity_type: &str, name: &str) -> Uuid {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, format!("{}:{}", entity_type, name).as_bytes())
+}
 
 /// Represents an audit log entry
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct AuditLog {
     pub entity_id: Uuid,
     pub entity_type: String,
@@ -42,7 +55,7 @@ impl AuditLog {
 
     /// Log an action with explicit old and new values
     pub async fn log(
-        tx: &mut SqlxTransaction<'_, Postgres>,
+        conn: &mut PgConnection,
         entity_id: Uuid,
         entity_type: &str,
         action: &str,
@@ -62,7 +75,7 @@ impl AuditLog {
         .bind(old_val)
         .bind(new_val)
         .bind(actor)
-        .execute(&mut **tx)
+        .execute(&mut *conn)
         .await?;
 
         Ok(())
@@ -70,7 +83,7 @@ impl AuditLog {
 
     /// Log a status change
     pub async fn log_status_change(
-        tx: &mut SqlxTransaction<'_, Postgres>,
+        conn: &mut PgConnection,
         entity_id: Uuid,
         entity_type: &str,
         old_status: &str,
@@ -78,7 +91,7 @@ impl AuditLog {
         actor: &str,
     ) -> sqlx::Result<()> {
         Self::log(
-            tx,
+            conn,
             entity_id,
             entity_type,
             "status_update",
@@ -91,7 +104,7 @@ impl AuditLog {
 
     /// Log a field update
     pub async fn log_field_update(
-        tx: &mut SqlxTransaction<'_, Postgres>,
+        conn: &mut PgConnection,
         entity_id: Uuid,
         entity_type: &str,
         field_name: &str,
@@ -100,7 +113,7 @@ impl AuditLog {
         actor: &str,
     ) -> sqlx::Result<()> {
         Self::log(
-            tx,
+            conn,
             entity_id,
             entity_type,
             &format!("{}_update", field_name),
@@ -111,16 +124,36 @@ impl AuditLog {
         .await
     }
 
+    /// Log an update as a field-level diff of the changed fields
+    pub async fn log_diff(
+        conn: &mut PgConnection,
+        entity_id: Uuid,
+        entity_type: &str,
+        diff: &Diff,
+        actor: &str,
+    ) -> sqlx::Result<()> {
+        Self::log(
+            conn,
+            entity_id,
+            entity_type,
+            "updated",
+            None,
+            Some(json!({ "diff": diff })),
+            actor,
+        )
+        .await
+    }
+
     /// Log a creation event
     pub async fn log_creation(
-        tx: &mut SqlxTransaction<'_, Postgres>,
+        conn: &mut PgConnection,
         entity_id: Uuid,
         entity_type: &str,
         created_data: JsonValue,
         actor: &str,
     ) -> sqlx::Result<()> {
         Self::log(
-            tx,
+            conn,
             entity_id,
             entity_type,
             "created",
@@ -133,14 +166,14 @@ impl AuditLog {
 
     /// Log a deletion event
     pub async fn log_deletion(
-        tx: &mut SqlxTransaction<'_, Postgres>,
+        conn: &mut PgConnection,
         entity_id: Uuid,
         entity_type: &str,
         deleted_data: JsonValue,
         actor: &str,
     ) -> sqlx::Result<()> {
         Self::log(
-            tx,
+            conn,
             entity_id,
             entity_type,
             "deleted",
@@ -178,4 +211,16 @@ mod tests {
         assert_eq!(log.new_val, new_val);
         assert_eq!(log.actor, "system");
     }
+
+    #[test]
+    fn test_named_entity_id_is_stable() {
+        assert_eq!(
+            named_entity_id(ENTITY_FEATURE_FLAG, "new_asset_support"),
+            named_entity_id(ENTITY_FEATURE_FLAG, "new_asset_support")
+        );
+        assert_ne!(
+            named_entity_id(ENTITY_FEATURE_FLAG, "new_asset_support"),
+            named_entity_id(ENTITY_ASSET, "new_asset_support")
+        );
+    }
 }
@@ -0,0 +1,137 @@
+//! Transaction statuses written before statuses were canonical.
+//!
+//! Early rows hold free-form spellings such as `Pending` or `PENDING_anchor`.
+//! Reads map them on the fly through [`TransactionStatus::from_legacy`]; the
+//! `migrate-statuses` command rewrites them for good. Statuses that aren't a
+//! known alias are only ever reported, never rewritten.
+
+use sqlx::PgPool;
+
+use crate::db::{queries, uow};
+use crate::domain::TransactionStatus;
+
+/// Non-canonical statuses found in `transactions`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LegacyStatusReport {
+    /// Known legacy spellings, the status each maps to, and their row count
+    pub legacy: Vec<(String, TransactionStatus, i64)>,
+    /// Statuses that map to nothing, and their row count
+    pub unknown: Vec<(String, i64)>,
+}
+
+impl LegacyStatusReport {
+    fn from_counts(counts: Vec<(String, i64)>) -> Self {
+        let mut report = Self::default();
+        for (raw, rows) in counts {
+            match TransactionStatus::from_legacy(&raw) {
+                Ok(status) => report.legacy.push((raw, status, rows)),
+                Err(_) => report.unknown.push((raw, rows)),
+            }
+        }
+        report
+    }
+
+    pub fn legacy_rows(&self) -> i64 {
+        self.legacy.iter().map(|(_, _, rows)| rows).sum()
+    }
+
+    pub fn unknown_rows(&self) -> i64 {
+        self.unknown.iter().map(|(_, rows)| rows).sum()
+    }
+
+    /// Startup summary: a warning while anything is left to migrate or fix
+    pub fn log(&self) {
+        if self.unknown_rows() > 0 {
+            tracing::warn!(
+                rows = self.unknown_rows(),
+                statuses = ?self.unknown,
+                "Transactions hold statuses that match no known value; reads of them fail until fixed by hand"
+            );
+        }
+        if self.legacy_rows() > 0 {
+            tracing::warn!(
+                rows = self.legacy_rows(),
+                "Transactions hold legacy status spellings; run `synapse-core migrate-statuses`"
+            );
+        }
+    }
+}
+
+/// Count the rows holding each non-canonical status
+pub async fn scan(pool: &PgPool) -> sqlx::Result<LegacyStatusReport> {
+    Ok(LegacyStatusReport::from_counts(
+        queries::count_noncanonical_statuses(pool).await?,
+    ))
+}
+
+/// Rewrite every legacy spelling to its canonical status, `batch_size` rows
+/// per database transaction. Account stats of the rewritten rows' accounts
+/// are recomputed in the same transaction. `progress` is called after each
+/// batch with the spelling, its status, rows done and rows in total.
+///
+/// Returns the scan taken before rewriting; with `dry_run` nothing is
+/// rewritten and only the scan is returned.
+pub async fn migrate(
+    pool: &PgPool,
+    batch_size: i64,
+    dry_run: bool,
+    mut progress: impl FnMut(&str, TransactionStatus, i64, i64),
+) -> sqlx::Result<LegacyStatusReport> {
+    let report = scan(pool).await?;
+    if dry_run {
+        return Ok(report);
+    }
+
+    for (raw, status, total) in &report.legacy {
+        let mut done = 0;
+        loop {
+            let batch_raw = raw.clone();
+            let status = *status;
+            let rows = uow::run(pool, |uow| {
+                Box::pin(async move {
+                    let mut accounts =
+                        queries::rewrite_legacy_status_batch(uow.conn(), &batch_raw, status, batch_size)
+                            .await?;
+                    let rows = accounts.len() as i64;
+                    accounts.sort();
+                    accounts.dedup();
+                    for account in &accounts {
+                        queries::recompute_account_stats(uow.conn(), account).await?;
+                    }
+                    Ok::<_, sqlx::Error>(rows)
+                })
+            })
+            .await?;
+            if rows == 0 {
+                break;
+            }
+            done += rows;
+            progress(raw, status, done, *total);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_splits_legacy_from_unknown_statuses() {
+        let report = LegacyStatusReport::from_counts(vec![
+            ("Pending".to_string(), 3),
+            ("PENDING_anchor".to_string(), 2),
+            ("on_hold".to_string(), 1),
+        ]);
+        assert_eq!(
+            report.legacy,
+            vec![
+                ("Pending".to_string(), TransactionStatus::Pending, 3),
+                ("PENDING_anchor".to_string(), TransactionStatus::Pending, 2),
+            ]
+        );
+        assert_eq!(report.unknown, vec![("on_hold".to_string(), 1)]);
+        assert_eq!(report.legacy_rows(), 5);
+        assert_eq!(report.unknown_rows(), 1);
+    }
+}
@@ -1,11 +1,17 @@
 use crate::config::Config;
 use sqlx::postgres::{PgPool, PgPoolOptions};
 
+pub mod audit;
+pub mod legacy_statuses;
 pub mod models;
 pub mod partition;
 pub mod pool_manager;
 pub mod queries;
+pub mod query_plan;
+pub mod seed;
+pub mod transactions;
 pub mod cron;
+pub mod uow;
 
 pub async fn create_pool(config: &Config) -> Result<PgPool, sqlx::Error> {
     PgPoolOptions::new()
@@ -5,12 +5,18 @@ use sqlx::types::BigDecimal;
 use uuid::Uuid;
 use utoipa::ToSchema;
 
+pub use crate::domain::TransactionStatus;
+use crate::domain::UnknownStatus;
+
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Transaction {
     pub id: Uuid,
     pub stellar_account: String,
     pub amount: BigDecimal,
     pub asset_code: String,
+    /// Issuer of `asset_code`; `None` for lumens and assets the registry
+    /// has no issuer for
+    pub asset_issuer: Option<String>,
     pub status: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -18,6 +24,13 @@ pub struct Transaction {
     pub callback_type: Option<String>,
     pub callback_status: Option<String>,
     pub settlement_id: Option<Uuid>,
+    /// Shared by the callback, worker runs, Horizon calls and webhooks
+    pub correlation_id: Option<String>,
+    /// SEP-38 quote a withdrawal was priced with
+    pub quote_id: Option<String>,
+    /// Id of the muxed account the callback named; `stellar_account` is its
+    /// base account
+    pub muxed_id: Option<BigDecimal>,
 }
 
 impl Transaction {
@@ -34,6 +47,7 @@ impl Transaction {
             stellar_account,
             amount,
             asset_code,
+            asset_issuer: None,
             status: "pending".to_string(),
             created_at: Utc::now(),
             updated_at: Utc::now(),
@@ -41,8 +55,21 @@ impl Transaction {
             callback_type,
             callback_status,
             settlement_id: None,
+            correlation_id: None,
+            quote_id: None,
+            muxed_id: None,
         }
     }
+
+    /// The stored status, read like any legacy spelling of it
+    pub fn current_status(&self) -> Result<TransactionStatus, UnknownStatus> {
+        TransactionStatus::from_legacy(&self.status)
+    }
+
+    pub fn with_correlation(mut self, ctx: &crate::utils::correlation::CorrelationContext) -> Self {
+        self.correlation_id = Some(ctx.as_str().to_string());
+        self
+    }
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
@@ -75,6 +102,324 @@ pub struct TransactionDlq {
     pub moved_to_dlq_at: DateTime<Utc>,
     pub last_retry_at: Option<DateTime<Utc>>,
 }
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ExportJob {
+    pub id: Uuid,
+    pub params_hash: String,
+    #[serde(serialize_with = "crate::utils::json::serialize_sorted")]
+    pub params: serde_json::Value,
+    pub format: String,
+    pub status: String,
+    pub storage_key: Option<String>,
+    pub content_hash: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub row_count: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// An account erasure run; once completed outside dry-run mode, its certificate
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ErasureJob {
+    pub id: Uuid,
+    pub account_hash: String,
+    /// Cleared when the run finishes
+    #[serde(skip_serializing)]
+    pub stellar_account: Option<String>,
+    pub dry_run: bool,
+    pub block_callbacks: bool,
+    pub status: String,
+    pub policy: serde_json::Value,
+    pub report: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub requested_by: String,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// Outbound webhook subscription
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct WebhookSubscription {
+    pub id: Uuid,
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub enabled: bool,
+    pub max_in_flight: Option<i32>,
+    pub cooldown_until: Option<DateTime<Utc>>,
+    pub cooldown_reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// BCP 47 tag choosing the `display_message` template
+    pub locale: String,
+    /// Signs every delivery; only returned when created or rotated
+    #[serde(skip_serializing, default)]
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub correlation_id: Option<String>,
+}
+
+/// An outbound event, numbered in write order
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct OutboxEvent {
+    pub seq: i64,
+    pub event_type: String,
+    pub asset_code: Option<String>,
+    pub payload: serde_json::Value,
+    pub correlation_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Localized `display_message` template for one event type
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct NotificationTemplate {
+    pub id: Uuid,
+    pub event_type: String,
+    pub locale: String,
+    pub template: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Stored audit log row
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    pub id: Uuid,
+    pub entity_id: Uuid,
+    pub entity_type: String,
+    pub action: String,
+    pub old_val: Option<serde_json::Value>,
+    pub new_val: Option<serde_json::Value>,
+    pub actor: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// One status change of a transaction, in `transaction_status_history`
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct StatusHistoryEntry {
+    pub id: i64,
+    pub transaction_id: Uuid,
+    pub old_status: String,
+    pub new_status: String,
+    pub reason: Option<String>,
+    pub actor: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A transaction with the columns only operators see
+#[derive(Debug, FromRow, Serialize)]
+pub struct TransactionDetail {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub transaction: Transaction,
+    pub needs_review: bool,
+    /// Every reason it was flagged for, newest last
+    pub review_reason: Option<String>,
+    pub payout_tx_hash: Option<String>,
+    #[serde(serialize_with = "crate::utils::json::serialize_sorted_opt")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// Scoped API token. Only the sha256 of the secret is stored.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    pub scope: serde_json::Value,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Requests made with one scoped token on one route and day
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize, Deserialize)]
+pub struct ApiTokenUsage {
+    pub token_id: Uuid,
+    pub method: String,
+    pub route: String,
+    pub day: chrono::NaiveDate,
+    pub requests: i64,
+}
+
+/// Materialized deposit aggregates for one account and asset
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AccountStats {
+    pub stellar_account: String,
+    pub asset_code: String,
+    pub total_completed: BigDecimal,
+    /// Sum over pending, processing and pending_trustline transactions
+    pub total_pending: BigDecimal,
+    pub first_deposit_at: Option<DateTime<Utc>>,
+    pub last_deposit_at: Option<DateTime<Utc>>,
+    pub deposit_count: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl AccountStats {
+    /// What a transaction of `amount` in `status` adds to
+    /// `(total_completed, total_pending)`
+    pub fn contribution(status: &str, amount: &BigDecimal) -> (BigDecimal, BigDecimal) {
+        let zero = BigDecimal::from(0);
+        match crate::domain::TransactionStatus::parse(status) {
+            Some(crate::domain::TransactionStatus::Completed) => (amount.clone(), zero),
+            Some(status) if status.is_in_flight() => (zero, amount.clone()),
+            _ => (zero.clone(), zero),
+        }
+    }
+
+    /// Equal aggregates, ignoring when the row was last written
+    pub fn same_totals(&self, other: &AccountStats) -> bool {
+        self.stellar_account == other.stellar_account
+            && self.asset_code == other.asset_code
+            && self.total_completed == other.total_completed
+            && self.total_pending == other.total_pending
+            && self.first_deposit_at == other.first_deposit_at
+            && self.last_deposit_at == other.last_deposit_at
+            && self.deposit_count == other.deposit_count
+    }
+}
+
+/// Firm SEP-38 quote accepted from the Anchor Platform
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Quote {
+    pub id: String,
+    pub sell_asset: String,
+    pub buy_asset: String,
+    pub sell_amount: BigDecimal,
+    pub buy_amount: BigDecimal,
+    pub rate: BigDecimal,
+    pub total_price: BigDecimal,
+    pub fee_total: BigDecimal,
+    pub fee_asset: String,
+    pub expires_at: DateTime<Utc>,
+    /// Withdrawal that used the quote; a quote is used at most once
+    pub transaction_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<crate::stellar::quotes::FirmQuote> for Quote {
+    fn from(quote: crate::stellar::quotes::FirmQuote) -> Self {
+        Self {
+            id: quote.id,
+            sell_asset: quote.sell_asset,
+            buy_asset: quote.buy_asset,
+            sell_amount: quote.sell_amount,
+            buy_amount: quote.buy_amount,
+            rate: quote.price,
+            total_price: quote.total_price,
+            fee_total: quote.fee.total,
+            fee_asset: quote.fee.asset,
+            expires_at: quote.expires_at,
+            transaction_id: None,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Callback acknowledged asynchronously and not yet stored as a transaction
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct IngestionOutboxEntry {
+    /// Provisional id returned to the anchor; the stored transaction keeps it
+    pub id: Uuid,
+    pub anchor_transaction_id: Option<String>,
+    /// The [`Transaction`] to insert
+    pub payload: serde_json::Value,
+    pub schema_version: Option<String>,
+    pub raw_body: Option<String>,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// In-flight transaction counts for the status snapshot
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct TransactionBacklog {
+    pub pending: i64,
+    /// Processing, or paid out and waiting for the network (`submitted`)
+    pub processing: i64,
+    /// Waiting on the customer's trustline (`pending_trustline`)
+    pub on_hold: i64,
+    pub oldest_pending_at: Option<DateTime<Utc>>,
+}
+
+/// A deposit claimed by the payment processor, with the payment it last
+/// signed for it
+#[derive(Debug, FromRow)]
+pub struct Payout {
+    #[sqlx(flatten)]
+    pub transaction: Transaction,
+    pub payout_tx_hash: Option<String>,
+    /// Base64 XDR envelope; `None` until one is signed, and after a
+    /// rejection until the next one is
+    pub payout_envelope: Option<String>,
+    pub payout_valid_until: Option<DateTime<Utc>>,
+}
+
+/// Transaction fields exposed to the anchor through the SEP-24 status endpoint
+#[derive(Debug, Clone, FromRow)]
+pub struct TransactionStatusView {
+    pub id: Uuid,
+    pub amount: BigDecimal,
+    pub asset_code: String,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub anchor_transaction_id: Option<String>,
+    pub callback_type: Option<String>,
+    pub payout_tx_hash: Option<String>,
+}
+
+/// Asset registry entry
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Asset {
+    pub id: Uuid,
+    pub asset_code: String,
+    pub asset_issuer: Option<String>,
+    #[serde(serialize_with = "crate::utils::json::serialize_sorted_opt")]
+    pub metadata: Option<serde_json::Value>,
+    pub enabled: Option<bool>,
+    /// Issuer home-domain verification result: `verified` or `unverified`
+    pub verification_status: String,
+    pub verification_error: Option<String>,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Asset {
+    pub async fn fetch_all(pool: &sqlx::PgPool) -> sqlx::Result<Vec<Asset>> {
+        sqlx::query_as::<_, Asset>("SELECT * FROM assets WHERE enabled IS DISTINCT FROM FALSE")
+            .fetch_all(pool)
+            .await
+    }
+
+    pub fn is_verified(&self) -> bool {
+        self.verification_status == "verified"
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -219,4 +564,18 @@ mod tests {
             .unwrap();
         assert_eq!(transactions.len(), 5);
     }
+
+    #[test]
+    fn test_account_stats_contribution_by_status() {
+        let amount = "25.50".parse::<BigDecimal>().unwrap();
+        let zero = BigDecimal::from(0);
+
+        assert_eq!(AccountStats::contribution("completed", &amount), (amount.clone(), zero.clone()));
+        for status in ["pending", "processing", "submitted", "pending_trustline"] {
+            assert_eq!(AccountStats::contribution(status, &amount), (zero.clone(), amount.clone()));
+        }
+        for status in ["failed", "dlq", "unknown"] {
+            assert_eq!(AccountStats::contribution(status, &amount), (zero.clone(), zero.clone()));
+        }
+    }
 }
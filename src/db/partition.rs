@@ -1,12 +1,191 @@
-use sqlx::PgPool;
+use crate::config::{PartitionInterval, PartitionRetentionConfig, PartitionRetentionMode};
+use crate::metrics;
+use crate::utils::clock::{self, SharedClock, Ticker};
+use crate::utils::shutdown::Shutdown;
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, PgExecutor, PgPool};
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::time;
+use tokio::sync::Mutex;
 use tracing::{error, info};
 
+/// A partition of `transactions`, as Postgres describes it
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PartitionInfo {
+    pub name: String,
+    /// `FOR VALUES FROM (...) TO (...)`, or `DEFAULT`
+    pub bound: String,
+    pub is_default: bool,
+    /// `None` for the default partition and `MINVALUE`/`MAXVALUE` bounds
+    pub lower_bound: Option<DateTime<Utc>>,
+    pub upper_bound: Option<DateTime<Utc>>,
+    /// Planner estimate; `0` until the partition has been analyzed
+    pub row_estimate: i64,
+    /// Including indexes and TOAST
+    pub size_bytes: i64,
+}
+
+/// Partitions removed by one retention pass, and the ones that could not be
+#[derive(Debug, Default, Serialize)]
+pub struct RetentionReport {
+    pub removed: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+/// Partitions created ahead of time by one pass, and the ones that could not be
+#[derive(Debug, Default, Serialize)]
+pub struct CreationReport {
+    pub created: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+/// What one maintenance pass did
+#[derive(Debug, Serialize)]
+pub struct MaintenanceReport {
+    pub ran_at: DateTime<Utc>,
+    pub creation: CreationReport,
+    pub retention: RetentionReport,
+}
+
+/// A partition to create: `FOR VALUES FROM (from) TO (to)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedPartition {
+    pub name: String,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).expect("midnight exists").and_utc()
+}
+
+/// The partition of `interval` that holds `at`. Monthly partitions keep the
+/// `transactions_y2026m03` names of the original migration.
+pub fn partition_for(interval: PartitionInterval, at: DateTime<Utc>) -> PlannedPartition {
+    let day = at.date_naive();
+    let (from, to, name) = match interval {
+        PartitionInterval::Daily => (
+            day,
+            day + Days::new(1),
+            format!("transactions_y{}m{:02}d{:02}", day.year(), day.month(), day.day()),
+        ),
+        PartitionInterval::Weekly => {
+            let monday = day - Days::new(day.weekday().num_days_from_monday() as u64);
+            let week = monday.iso_week();
+            (
+                monday,
+                monday + Days::new(7),
+                format!("transactions_y{}w{:02}", week.year(), week.week()),
+            )
+        }
+        PartitionInterval::Monthly => {
+            let first = day.with_day(1).expect("every month has a first day");
+            (
+                first,
+                first + Months::new(1),
+                format!("transactions_y{}m{:02}", first.year(), first.month()),
+            )
+        }
+    };
+    PlannedPartition {
+        name,
+        from: midnight(from),
+        to: midnight(to),
+    }
+}
+
+/// Partitions created beyond the current one. With daily maintenance this
+/// leaves at least a week of room whatever the interval.
+pub fn partitions_ahead(interval: PartitionInterval) -> usize {
+    match interval {
+        PartitionInterval::Daily => 7,
+        PartitionInterval::Weekly => 4,
+        PartitionInterval::Monthly => 2,
+    }
+}
+
+/// The partition holding `now` and the next [`partitions_ahead`]
+pub fn upcoming_partitions(
+    interval: PartitionInterval,
+    now: DateTime<Utc>,
+) -> Vec<PlannedPartition> {
+    let mut planned = vec![partition_for(interval, now)];
+    for _ in 0..partitions_ahead(interval) {
+        let next = partition_for(interval, planned.last().expect("never empty").to);
+        planned.push(next);
+    }
+    planned
+}
+
+/// Whether an attached partition already covers part of `planned`'s range,
+/// such as a monthly one after a switch to daily partitions
+pub fn is_covered(partitions: &[PartitionInfo], planned: &PlannedPartition) -> bool {
+    // A missing bound is MINVALUE or MAXVALUE
+    partitions.iter().filter(|p| !p.is_default).any(|p| {
+        !matches!(p.lower_bound, Some(lower) if lower >= planned.to)
+            && !matches!(p.upper_bound, Some(upper) if upper <= planned.from)
+    })
+}
+
+/// Every partition attached to `transactions`, by name
+pub async fn list_partitions<'e, E: PgExecutor<'e>>(
+    executor: E,
+) -> Result<Vec<PartitionInfo>, sqlx::Error> {
+    sqlx::query_as::<_, PartitionInfo>(
+        r#"
+        SELECT name, bound, bound = 'DEFAULT' AS is_default,
+               (regexp_match(bound, 'FROM \(''([^'']+)''\)'))[1]::timestamptz AS lower_bound,
+               (regexp_match(bound, 'TO \(''([^'']+)''\)'))[1]::timestamptz AS upper_bound,
+               row_estimate, size_bytes
+        FROM (
+            SELECT c.relname::text AS name,
+                   pg_get_expr(c.relpartbound, c.oid) AS bound,
+                   GREATEST(c.reltuples, 0)::bigint AS row_estimate,
+                   pg_total_relation_size(c.oid) AS size_bytes
+            FROM pg_inherits i
+            JOIN pg_class c ON c.oid = i.inhrelid
+            WHERE i.inhparent = 'transactions'::regclass
+        ) partitions
+        ORDER BY name
+        "#,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Partitions whose whole range ends at or before `cutoff`. The default
+/// partition, and any partition that still reaches past the cutoff, are kept.
+pub fn past_retention(
+    partitions: &[PartitionInfo],
+    cutoff: DateTime<Utc>,
+) -> Vec<&PartitionInfo> {
+    partitions
+        .iter()
+        .filter(|p| !p.is_default && p.upper_bound.is_some_and(|upper| upper <= cutoff))
+        .collect()
+}
+
+/// `months` calendar months before `now`
+pub fn retention_cutoff(now: DateTime<Utc>, months: u32) -> DateTime<Utc> {
+    now.checked_sub_months(Months::new(months)).unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
 /// Partition manager that runs maintenance tasks periodically
+#[derive(Clone)]
 pub struct PartitionManager {
     pool: PgPool,
     interval: Duration,
+    partition_interval: PartitionInterval,
+    retention: PartitionRetentionConfig,
+    clock: SharedClock,
+    shutdown: Shutdown,
+    /// Held for a whole pass, so a manual run never overlaps the timer's
+    running: Arc<Mutex<()>>,
 }
 
 impl PartitionManager {
@@ -14,39 +193,173 @@ impl PartitionManager {
         Self {
             pool,
             interval: Duration::from_secs(interval_hours * 3600),
+            partition_interval: PartitionInterval::Monthly,
+            retention: PartitionRetentionConfig::default(),
+            clock: clock::system(),
+            shutdown: Shutdown::new(),
+            running: Arc::new(Mutex::new(())),
         }
     }
 
+    /// Span of the partitions created from now on; existing ones are kept
+    pub fn with_partition_interval(mut self, interval: PartitionInterval) -> Self {
+        self.partition_interval = interval;
+        self
+    }
+
+    pub fn with_retention(mut self, retention: PartitionRetentionConfig) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Stop between maintenance runs once `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
     /// Start the partition maintenance background task
     pub fn start(self) {
-        tokio::spawn(async move {
-            let mut interval = time::interval(self.interval);
-            interval.tick().await; // Skip first immediate tick
+        let shutdown = self.shutdown.clone();
+        shutdown.spawn(async move {
+            let mut ticker = Ticker::new(self.clock.clone(), self.interval);
+            ticker.tick().await; // Skip first immediate tick
 
             loop {
-                interval.tick().await;
-                if let Err(e) = self.maintain_partitions().await {
+                tokio::select! {
+                    _ = self.shutdown.cancelled() => break,
+                    _ = ticker.tick() => {}
+                }
+                let result = self.run().await;
+                metrics::record_task_heartbeat("partition_manager", result.is_ok());
+                if let Err(e) = result {
                     error!("Partition maintenance failed: {}", e);
                 } else {
                     info!("Partition maintenance completed successfully");
                 }
             }
+            info!("Partition manager stopped");
         });
     }
 
-    /// Run partition maintenance (create new partitions, detach old ones)
-    async fn maintain_partitions(&self) -> Result<(), sqlx::Error> {
-        sqlx::query("SELECT maintain_partitions()")
-            .execute(&self.pool)
-            .await?;
+    /// Attached partitions of `transactions`
+    pub async fn partitions(&self) -> Result<Vec<PartitionInfo>, sqlx::Error> {
+        list_partitions(&self.pool).await
+    }
+
+    /// Run partition maintenance now: create upcoming partitions, then remove
+    /// the ones past retention. Waits for a pass already running.
+    pub async fn run(&self) -> Result<MaintenanceReport, sqlx::Error> {
+        let _running = self.running.lock().await;
+        let now = self.clock.now();
+
+        let creation = self.create_partitions(now).await?;
+        if !creation.failed.is_empty() {
+            error!(failed = ?creation.failed, "Some upcoming partitions were not created");
+        }
+        let retention = self.apply_retention(now).await?;
+        if !retention.failed.is_empty() {
+            error!(failed = ?retention.failed, "Some partitions past retention were not removed");
+        }
+
+        Ok(MaintenanceReport {
+            ran_at: now,
+            creation,
+            retention,
+        })
+    }
+
+    /// Create the partition holding `now` and the upcoming ones, skipping
+    /// ranges an attached partition already covers. A partition that fails,
+    /// e.g. because the default partition holds rows in its range, is logged
+    /// and skipped.
+    pub async fn create_partitions(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<CreationReport, sqlx::Error> {
+        let partitions = list_partitions(&self.pool).await?;
+        let mut report = CreationReport::default();
+
+        for planned in upcoming_partitions(self.partition_interval, now) {
+            if is_covered(&partitions, &planned) {
+                continue;
+            }
+            let statement = format!(
+                "CREATE TABLE {} PARTITION OF transactions FOR VALUES FROM ('{}') TO ('{}')",
+                quote_ident(&planned.name),
+                planned.from.to_rfc3339(),
+                planned.to.to_rfc3339()
+            );
+            match sqlx::query(&statement).execute(&self.pool).await {
+                Ok(_) => {
+                    info!(partition = %planned.name, "Created partition");
+                    report.created.push(planned.name);
+                }
+                Err(e) => {
+                    error!(partition = %planned.name, "Failed to create partition: {}", e);
+                    report.failed.push(planned.name);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Detach, drop or archive every partition that ended `retention.months` before
+    /// `now`. A partition that fails is logged and skipped; the rest still run.
+    pub async fn apply_retention(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<RetentionReport, sqlx::Error> {
+        let cutoff = retention_cutoff(now, self.retention.months);
+        let partitions = list_partitions(&self.pool).await?;
+        let mut report = RetentionReport::default();
+
+        for partition in past_retention(&partitions, cutoff) {
+            match self.remove_partition(&partition.name).await {
+                Ok(()) => {
+                    info!(
+                        partition = %partition.name,
+                        mode = ?self.retention.mode,
+                        "Removed partition past retention"
+                    );
+                    report.removed.push(partition.name.clone());
+                }
+                Err(e) => {
+                    error!(partition = %partition.name, "Failed to remove partition: {}", e);
+                    report.failed.push(partition.name.clone());
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    async fn remove_partition(&self, name: &str) -> Result<(), sqlx::Error> {
+        let statement = match self.retention.mode {
+            PartitionRetentionMode::Detach => {
+                format!("ALTER TABLE transactions DETACH PARTITION {}", quote_ident(name))
+            }
+            PartitionRetentionMode::Drop => format!("DROP TABLE {}", quote_ident(name)),
+            PartitionRetentionMode::Archive => {
+                let rows: i64 = sqlx::query_scalar("SELECT archive_transactions_partition($1)")
+                    .bind(name)
+                    .fetch_one(&self.pool)
+                    .await?;
+                info!(partition = %name, rows, "Archived partition to transactions_archive");
+                return Ok(());
+            }
+        };
+        sqlx::query(&statement).execute(&self.pool).await?;
         Ok(())
     }
 
     /// Manually trigger partition creation
     pub async fn create_partition(&self) -> Result<(), sqlx::Error> {
-        sqlx::query("SELECT create_monthly_partition()")
-            .execute(&self.pool)
-            .await?;
+        self.create_partitions(self.clock.now()).await?;
         Ok(())
     }
 
@@ -74,5 +387,112 @@ mod tests {
         let manager = PartitionManager::new(pool, 24);
 
         assert_eq!(manager.interval, Duration::from_secs(24 * 3600));
+        assert_eq!(manager.retention.mode, PartitionRetentionMode::Detach);
+    }
+
+    fn at(raw: &str) -> DateTime<Utc> {
+        raw.parse().unwrap()
+    }
+
+    fn partition(name: &str, range: Option<(&str, &str)>) -> PartitionInfo {
+        PartitionInfo {
+            name: name.to_string(),
+            bound: String::new(),
+            is_default: range.is_none(),
+            lower_bound: range.map(|(from, _)| at(from)),
+            upper_bound: range.map(|(_, to)| at(to)),
+            row_estimate: 0,
+            size_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn test_retention_cutoff_is_calendar_months() {
+        assert_eq!(retention_cutoff(at("2026-03-31T12:00:00Z"), 1), at("2026-02-28T12:00:00Z"));
+        assert_eq!(retention_cutoff(at("2026-03-15T00:00:00Z"), 12), at("2025-03-15T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_only_partitions_wholly_before_the_cutoff_are_past_retention() {
+        let partitions = vec![
+            partition("transactions_default", None),
+            partition(
+                "transactions_y2025m01",
+                Some(("2025-01-01T00:00:00Z", "2025-02-01T00:00:00Z")),
+            ),
+            partition(
+                "transactions_y2025m02",
+                Some(("2025-02-01T00:00:00Z", "2025-03-01T00:00:00Z")),
+            ),
+            // Straddles the cutoff
+            partition(
+                "transactions_y2025m03",
+                Some(("2025-03-01T00:00:00Z", "2025-04-01T00:00:00Z")),
+            ),
+        ];
+
+        let expired = past_retention(&partitions, at("2025-03-01T00:00:00Z"));
+        let names: Vec<&str> = expired.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["transactions_y2025m01", "transactions_y2025m02"]);
+
+        assert!(past_retention(&partitions, at("2024-12-31T00:00:00Z")).is_empty());
+    }
+
+    #[test]
+    fn test_partition_for_each_interval() {
+        let now = at("2026-03-04T15:30:00Z");
+
+        let daily = partition_for(PartitionInterval::Daily, now);
+        assert_eq!(daily.name, "transactions_y2026m03d04");
+        assert_eq!(daily.from, at("2026-03-04T00:00:00Z"));
+        assert_eq!(daily.to, at("2026-03-05T00:00:00Z"));
+
+        // 2026-03-04 is a Wednesday of ISO week 10
+        let weekly = partition_for(PartitionInterval::Weekly, now);
+        assert_eq!(weekly.name, "transactions_y2026w10");
+        assert_eq!(weekly.from, at("2026-03-02T00:00:00Z"));
+        assert_eq!(weekly.to, at("2026-03-09T00:00:00Z"));
+
+        let monthly = partition_for(PartitionInterval::Monthly, now);
+        assert_eq!(monthly.name, "transactions_y2026m03");
+        assert_eq!(monthly.from, at("2026-03-01T00:00:00Z"));
+        assert_eq!(monthly.to, at("2026-04-01T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_upcoming_partitions_are_contiguous() {
+        let planned = upcoming_partitions(PartitionInterval::Monthly, at("2026-12-10T00:00:00Z"));
+        let names: Vec<&str> = planned.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["transactions_y2026m12", "transactions_y2027m01", "transactions_y2027m02"]
+        );
+
+        let daily = upcoming_partitions(PartitionInterval::Daily, at("2026-12-30T23:59:00Z"));
+        assert_eq!(daily.len(), 1 + partitions_ahead(PartitionInterval::Daily));
+        assert!(daily.windows(2).all(|pair| pair[0].to == pair[1].from));
+        assert_eq!(daily[2].name, "transactions_y2027m01d01");
+    }
+
+    #[test]
+    fn test_ranges_covered_by_attached_partitions_are_skipped() {
+        let partitions = vec![
+            partition("transactions_default", None),
+            partition(
+                "transactions_y2026m03",
+                Some(("2026-03-01T00:00:00Z", "2026-04-01T00:00:00Z")),
+            ),
+        ];
+        let day = |raw: &str| partition_for(PartitionInterval::Daily, at(raw));
+
+        assert!(is_covered(&partitions, &day("2026-03-31T12:00:00Z")));
+        assert!(!is_covered(&partitions, &day("2026-04-01T00:00:00Z")));
+        assert!(!is_covered(&partitions, &day("2026-02-28T12:00:00Z")));
+    }
+
+    #[test]
+    fn test_quote_ident() {
+        assert_eq!(quote_ident("transactions_y2025m01"), "\"transactions_y2025m01\"");
+        assert_eq!(quote_ident("odd\"name"), "\"odd\"\"name\"");
     }
 }
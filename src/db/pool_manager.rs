@@ -70,6 +70,15 @@ impl PoolManager {
         self.replica.as_ref().map(|r| r.as_ref())
     }
 
+    /// Close the primary and replica pools, waiting for checked-out
+    /// connections to be returned
+    pub async fn close(&self) {
+        self.primary.close().await;
+        if let Some(replica) = &self.replica {
+            replica.close().await;
+        }
+    }
+
     pub async fn health_check(&self) -> HealthCheckResult {
         let primary_healthy = sqlx::query("SELECT 1")
             .execute(self.primary.as_ref())
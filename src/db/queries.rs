@@ -21,25 +21,51 @@ pub async fn insert_transaction(pool: &PgPool, tx: &Transaction) -> Result<Trans
         tx.callback_type,
         tx.callback_status
 
-use sqlx::{PgPool, Result, Postgres, Transaction as SqlxTransaction};
-use crate::db::models::{Transaction, Settlement, TransactionDlq};
+use sqlx::{PgConnection, PgExecutor, PgPool, Result, Postgres, Transaction as SqlxTransaction};
+use crate::db::models::{AccountStats, ApiToken, ApiTokenUsage, Asset, AuditLogEntry, ErasureJob, ExportJob, IngestionOutboxEntry, NotificationTemplate, OutboxEvent, Payout, Quote, StatusHistoryEntry, Transaction, TransactionBacklog, Settlement, TransactionDlq, TransactionStatusView, WebhookDelivery, WebhookSubscription};
 use crate::db::audit::{AuditLog, ENTITY_TRANSACTION, ENTITY_SETTLEMENT};
+use crate::db::query_plan::{self, PlanParam, QueryMeta};
+use crate::db::uow;
+use crate::domain::TransactionStatus;
+use std::time::Instant;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use serde_json::json;
+use sqlx::types::BigDecimal;
 
 // --- Transaction Queries ---
 
-pub async fn insert_transaction(pool: &PgPool, tx: &Transaction) -> Result<Transaction> {
-    let mut transaction = pool.begin().await?;
-    
-    let result = sqlx::query_as::<_, Transaction>(
+/// Accepts the pool or a unit of work connection (`uow.conn()`). The
+/// account's `account_stats` row is incremented in the same statement.
+pub async fn insert_transaction<'e, E>(executor: E, tx: &Transaction) -> Result<Transaction>
+where
+    E: PgExecutor<'e>,
+{
+    let (completed, pending) = AccountStats::contribution(&tx.status, &tx.amount);
+    sqlx::query_as::<_, Transaction>(
         r#"
-        INSERT INTO transactions (
-            id, stellar_account, amount, asset_code, status,
-            created_at, updated_at, anchor_transaction_id, callback_type, callback_status, settlement_id
-        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
-        RETURNING *
+        WITH inserted AS (
+            INSERT INTO transactions (
+                id, stellar_account, amount, asset_code, status,
+                created_at, updated_at, anchor_transaction_id, callback_type, callback_status, settlement_id,
+                correlation_id, quote_id, muxed_id, asset_issuer
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $17)
+            RETURNING *
+        ), stats AS (
+            INSERT INTO account_stats (
+                stellar_account, asset_code, total_completed, total_pending,
+                first_deposit_at, last_deposit_at, deposit_count
+            )
+            SELECT stellar_account, asset_code, $15, $16, created_at, created_at, 1 FROM inserted
+            ON CONFLICT (stellar_account, asset_code) DO UPDATE SET
+                total_completed = account_stats.total_completed + EXCLUDED.total_completed,
+                total_pending = account_stats.total_pending + EXCLUDED.total_pending,
+                first_deposit_at = LEAST(account_stats.first_deposit_at, EXCLUDED.first_deposit_at),
+                last_deposit_at = GREATEST(account_stats.last_deposit_at, EXCLUDED.last_deposit_at),
+                deposit_count = account_stats.deposit_count + 1,
+                updated_at = NOW()
+        )
+        SELECT * FROM inserted
         "#
     )
     .bind(tx.id)
@@ -53,28 +79,390 @@ pub async fn insert_transaction(pool: &PgPool, tx: &Transaction) -> Result<Trans
     .bind(&tx.callback_type)
     .bind(&tx.callback_status)
     .bind(tx.settlement_id)
-    .fetch_one(pool)
+    .bind(&tx.correlation_id)
+    .bind(&tx.quote_id)
+    .bind(&tx.muxed_id)
+    .bind(completed)
+    .bind(pending)
+    .bind(&tx.asset_issuer)
+    .fetch_one(executor)
+    .await
+}
+
+/// Store the verbatim body of a callback
+pub async fn insert_raw_callback<'e, E>(
+    executor: E,
+    transaction_id: Uuid,
+    schema_version: &str,
+    body: &str,
+) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query(
+        "INSERT INTO raw_callbacks (transaction_id, schema_version, body) VALUES ($1, $2, $3)"
+    )
+    .bind(transaction_id)
+    .bind(schema_version)
+    .bind(body)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// [`insert_transaction`] for many rows in one statement. Each account's
+/// `account_stats` row is incremented once with the sum of its rows.
+pub async fn insert_transactions<'e, E>(
+    executor: E,
+    txs: &[Transaction],
+) -> Result<Vec<Transaction>>
+where
+    E: PgExecutor<'e>,
+{
+    let (completed, pending): (Vec<_>, Vec<_>) = txs
+        .iter()
+        .map(|tx| AccountStats::contribution(&tx.status, &tx.amount))
+        .unzip();
+    sqlx::query_as::<_, Transaction>(
+        r#"
+        WITH rows AS (
+            SELECT * FROM UNNEST(
+                $1::uuid[], $2::text[], $3::numeric[], $4::text[], $5::text[],
+                $6::timestamptz[], $7::timestamptz[], $8::text[], $9::text[], $10::text[],
+                $11::uuid[], $12::text[], $13::text[], $14::numeric[], $15::numeric[],
+                $16::numeric[], $17::text[]
+            ) AS r(
+                id, stellar_account, amount, asset_code, status,
+                created_at, updated_at, anchor_transaction_id, callback_type, callback_status,
+                settlement_id, correlation_id, quote_id, muxed_id, completed, pending, asset_issuer
+            )
+        ), inserted AS (
+            INSERT INTO transactions (
+                id, stellar_account, amount, asset_code, status,
+                created_at, updated_at, anchor_transaction_id, callback_type, callback_status, settlement_id,
+                correlation_id, quote_id, muxed_id, asset_issuer
+            )
+            SELECT id, stellar_account, amount, asset_code, status,
+                   created_at, updated_at, anchor_transaction_id, callback_type, callback_status, settlement_id,
+                   correlation_id, quote_id, muxed_id, asset_issuer
+            FROM rows
+            RETURNING *
+        ), stats AS (
+            INSERT INTO account_stats (
+                stellar_account, asset_code, total_completed, total_pending,
+                first_deposit_at, last_deposit_at, deposit_count
+            )
+            SELECT stellar_account, asset_code, SUM(completed), SUM(pending),
+                   MIN(created_at), MAX(created_at), COUNT(*)
+            FROM rows
+            GROUP BY stellar_account, asset_code
+            ON CONFLICT (stellar_account, asset_code) DO UPDATE SET
+                total_completed = account_stats.total_completed + EXCLUDED.total_completed,
+                total_pending = account_stats.total_pending + EXCLUDED.total_pending,
+                first_deposit_at = LEAST(account_stats.first_deposit_at, EXCLUDED.first_deposit_at),
+                last_deposit_at = GREATEST(account_stats.last_deposit_at, EXCLUDED.last_deposit_at),
+                deposit_count = account_stats.deposit_count + EXCLUDED.deposit_count,
+                updated_at = NOW()
+        )
+        SELECT * FROM inserted
+        "#
+    )
+    .bind(txs.iter().map(|tx| tx.id).collect::<Vec<_>>())
+    .bind(txs.iter().map(|tx| tx.stellar_account.clone()).collect::<Vec<_>>())
+    .bind(txs.iter().map(|tx| tx.amount.clone()).collect::<Vec<_>>())
+    .bind(txs.iter().map(|tx| tx.asset_code.clone()).collect::<Vec<_>>())
+    .bind(txs.iter().map(|tx| tx.status.clone()).collect::<Vec<_>>())
+    .bind(txs.iter().map(|tx| tx.created_at).collect::<Vec<_>>())
+    .bind(txs.iter().map(|tx| tx.updated_at).collect::<Vec<_>>())
+    .bind(txs.iter().map(|tx| tx.anchor_transaction_id.clone()).collect::<Vec<_>>())
+    .bind(txs.iter().map(|tx| tx.callback_type.clone()).collect::<Vec<_>>())
+    .bind(txs.iter().map(|tx| tx.callback_status.clone()).collect::<Vec<_>>())
+    .bind(txs.iter().map(|tx| tx.settlement_id).collect::<Vec<_>>())
+    .bind(txs.iter().map(|tx| tx.correlation_id.clone()).collect::<Vec<_>>())
+    .bind(txs.iter().map(|tx| tx.quote_id.clone()).collect::<Vec<_>>())
+    .bind(txs.iter().map(|tx| tx.muxed_id.clone()).collect::<Vec<_>>())
+    .bind(completed)
+    .bind(pending)
+    .bind(txs.iter().map(|tx| tx.asset_issuer.clone()).collect::<Vec<_>>())
+    .fetch_all(executor)
+    .await
+}
+
+/// [`claim_anchor_transaction_id`] for many ids in one statement. Returns the
+/// anchor transaction ids that were claimed; the others were already taken.
+pub async fn claim_anchor_transaction_ids(
+    conn: &mut PgConnection,
+    claims: &[(String, Uuid)],
+) -> Result<Vec<String>> {
+    let (anchor_ids, transaction_ids): (Vec<_>, Vec<_>) = claims.iter().cloned().unzip();
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO anchor_transaction_ids (anchor_transaction_id, transaction_id)
+        SELECT * FROM UNNEST($1::text[], $2::uuid[])
+        ON CONFLICT (anchor_transaction_id) DO NOTHING
+        RETURNING anchor_transaction_id
+        "#,
+    )
+    .bind(anchor_ids)
+    .bind(transaction_ids)
+    .fetch_all(conn)
+    .await
+}
+
+/// Give up claims made by [`claim_anchor_transaction_ids`] in the same
+/// database transaction, for transactions that won't be stored after all
+pub async fn release_anchor_transaction_ids(
+    conn: &mut PgConnection,
+    anchor_transaction_ids: &[String],
+) -> Result<u64> {
+    let result =
+        sqlx::query("DELETE FROM anchor_transaction_ids WHERE anchor_transaction_id = ANY($1)")
+            .bind(anchor_transaction_ids)
+            .execute(conn)
+            .await?;
+    Ok(result.rows_affected())
+}
+
+/// The transaction holding each of `anchor_transaction_ids` that is claimed
+pub async fn find_anchor_transaction_holders<'e, E>(
+    executor: E,
+    anchor_transaction_ids: &[String],
+) -> Result<Vec<(String, Transaction)>>
+where
+    E: PgExecutor<'e>,
+{
+    let holders: Vec<Transaction> = sqlx::query_as(
+        r#"
+        SELECT t.* FROM transactions t
+        JOIN anchor_transaction_ids a ON a.transaction_id = t.id
+        WHERE a.anchor_transaction_id = ANY($1)
+        "#,
+    )
+    .bind(anchor_transaction_ids)
+    .fetch_all(executor)
+    .await?;
+    Ok(holders
+        .into_iter()
+        .filter_map(|tx| Some((tx.anchor_transaction_id.clone()?, tx)))
+        .collect())
+}
+
+/// [`insert_raw_callback`] for many bodies of one schema version
+pub async fn insert_raw_callbacks<'e, E>(
+    executor: E,
+    schema_version: &str,
+    bodies: &[(Uuid, String)],
+) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    let (transaction_ids, bodies): (Vec<_>, Vec<_>) = bodies.iter().cloned().unzip();
+    sqlx::query(
+        r#"
+        INSERT INTO raw_callbacks (transaction_id, schema_version, body)
+        SELECT transaction_id, $1, body FROM UNNEST($2::uuid[], $3::text[]) AS r(transaction_id, body)
+        "#,
+    )
+    .bind(schema_version)
+    .bind(transaction_ids)
+    .bind(bodies)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Move a transaction to `new_status` if it is currently in one of
+/// `from_statuses` (any status if empty), writing the audit entry, status
+/// history and a [`EVENT_TRANSACTION_STATUS_CHANGED`] event on the same
+/// connection and moving the amount between the account's stats buckets. Returns the previous and updated rows, or `None`
+/// if the transaction was not in an allowed state.
+///
+/// Whether the move is legal is the caller's to check; see
+/// [`crate::services::transaction::transition`].
+pub async fn update_transaction_status(
+    conn: &mut PgConnection,
+    id: Uuid,
+    from_statuses: &[&str],
+    new_status: &str,
+    actor: &str,
+) -> Result<Option<(Transaction, Transaction)>> {
+    update_transaction_status_with_reason(conn, id, from_statuses, new_status, None, actor).await
+}
+
+/// [`update_transaction_status`], recording `reason` in the status history
+pub async fn update_transaction_status_with_reason(
+    conn: &mut PgConnection,
+    id: Uuid,
+    from_statuses: &[&str],
+    new_status: &str,
+    reason: Option<&str>,
+    actor: &str,
+) -> Result<Option<(Transaction, Transaction)>> {
+    let statuses: Vec<String> = from_statuses.iter().map(|s| s.to_string()).collect();
+    let Some(previous) = sqlx::query_as::<_, Transaction>(
+        "SELECT * FROM transactions WHERE id = $1 AND (cardinality($2::text[]) = 0 OR status = ANY($2)) FOR UPDATE"
+    )
+    .bind(id)
+    .bind(&statuses)
+    .fetch_optional(&mut *conn)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let updated = sqlx::query_as::<_, Transaction>(
+        "UPDATE transactions SET status = $2, updated_at = NOW() WHERE id = $1 RETURNING *"
+    )
+    .bind(id)
+    .bind(new_status)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    apply_account_stats_transition(&mut *conn, &previous, new_status).await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO transaction_status_history (transaction_id, old_status, new_status, reason, actor)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(id)
+    .bind(&previous.status)
+    .bind(new_status)
+    .bind(reason)
+    .bind(actor)
+    .execute(&mut *conn)
+    .await?;
+
+    AuditLog::log_field_update(
+        &mut *conn,
+        id,
+        ENTITY_TRANSACTION,
+        "status",
+        json!(previous.status),
+        json!(new_status),
+        actor,
+    )
     .await?;
 
-    Ok(Transaction {
-        id: row.get("id"),
-        stellar_account: row.get("stellar_account"),
-        amount: row.get("amount"),
-        asset_code: row.get("asset_code"),
-        status: row.get("status"),
-        created_at: row.get("created_at"),
-        updated_at: row.get("updated_at"),
-        anchor_transaction_id: row.get("anchor_transaction_id"),
-        callback_type: row.get("callback_type"),
-        callback_status: row.get("callback_status"),
-    })
+    publish_event(
+        conn,
+        EVENT_TRANSACTION_STATUS_CHANGED,
+        &json!({
+            "transaction_id": id,
+            "status": updated.status,
+            "previous_status": previous.status,
+            "amount": updated.amount.to_string(),
+            "asset_code": updated.asset_code,
+            "asset_issuer": updated.asset_issuer,
+            "callback_type": updated.callback_type,
+            "reason": reason,
+            "correlation_id": updated.correlation_id,
+        }),
+        updated.correlation_id.as_deref(),
+    )
+    .await?;
+
+    Ok(Some((previous, updated)))
+}
+
+/// Lock a transaction, whatever its status, for the rest of the unit of work
+pub async fn lock_transaction(conn: &mut PgConnection, id: Uuid) -> Result<Option<Transaction>> {
+    sqlx::query_as::<_, Transaction>("SELECT * FROM transactions WHERE id = $1 FOR UPDATE")
+        .bind(id)
+        .fetch_optional(conn)
+        .await
+}
+
+const GET_STATUS_HISTORY: QueryMeta = QueryMeta {
+    name: "transaction_status_history.list",
+    sql: r#"
+        SELECT * FROM transaction_status_history
+        WHERE transaction_id = $1
+        ORDER BY created_at, id
+        "#,
+    bound_columns: &["transaction_id"],
+};
+
+/// A transaction's status changes, oldest first
+pub async fn get_status_history<'e, E>(executor: E, id: Uuid) -> Result<Vec<StatusHistoryEntry>>
+where
+    E: PgExecutor<'e>,
+{
+    let started = Instant::now();
+    let history = sqlx::query_as::<_, StatusHistoryEntry>(GET_STATUS_HISTORY.sql)
+        .bind(id)
+        .fetch_all(executor)
+        .await;
+    query_plan::observe(&GET_STATUS_HISTORY, started, || vec![PlanParam::Uuid(Some(id))]);
+    history
 }
 
+const GET_TRANSACTION: QueryMeta = QueryMeta {
+    name: "transactions.get",
+    sql: "SELECT * FROM transactions WHERE id = $1",
+    bound_columns: &["id"],
+};
+
 pub async fn get_transaction(pool: &PgPool, id: Uuid) -> Result<Transaction> {
-    sqlx::query_as::<_, Transaction>("SELECT * FROM transactions WHERE id = $1")
+    let started = Instant::now();
+    let tx = sqlx::query_as::<_, Transaction>(GET_TRANSACTION.sql)
         .bind(id)
         .fetch_one(pool)
-        .await
+        .await;
+    query_plan::observe(&GET_TRANSACTION, started, || vec![PlanParam::Uuid(Some(id))]);
+    tx
+}
+
+pub async fn get_transaction_by_anchor_id<'e, E>(
+    executor: E,
+    anchor_transaction_id: &str,
+) -> Result<Option<Transaction>>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, Transaction>(
+        "SELECT * FROM transactions WHERE anchor_transaction_id = $1 ORDER BY created_at LIMIT 1"
+    )
+    .bind(anchor_transaction_id)
+    .fetch_optional(executor)
+    .await
+}
+
+/// [`get_transaction_by_anchor_id`], locked for update
+pub async fn lock_transaction_by_anchor_id(
+    conn: &mut PgConnection,
+    anchor_transaction_id: &str,
+) -> Result<Option<Transaction>> {
+    sqlx::query_as::<_, Transaction>(
+        "SELECT * FROM transactions WHERE anchor_transaction_id = $1 \
+         ORDER BY created_at LIMIT 1 FOR UPDATE"
+    )
+    .bind(anchor_transaction_id)
+    .fetch_optional(conn)
+    .await
+}
+
+/// Claim an anchor transaction id for `transaction_id`. False when another
+/// transaction holds it. A claim racing an uncommitted one waits for it, so
+/// of two concurrent replays exactly one claims the id.
+pub async fn claim_anchor_transaction_id(
+    conn: &mut PgConnection,
+    anchor_transaction_id: &str,
+    transaction_id: Uuid,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO anchor_transaction_ids (anchor_transaction_id, transaction_id)
+        VALUES ($1, $2)
+        ON CONFLICT (anchor_transaction_id) DO NOTHING
+        "#,
+    )
+    .bind(anchor_transaction_id)
+    .bind(transaction_id)
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected() == 1)
 }
 
 pub async fn list_transactions(
@@ -137,6 +525,140 @@ pub async fn list_transactions(
     }
 }
 
+/// Filters for [`list_transactions_page`]. `None` leaves that column
+/// unrestricted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransactionListFilter<'a> {
+    pub stellar_account: Option<&'a str>,
+    pub status: Option<&'a str>,
+    pub asset_code: Option<&'a str>,
+    /// Inclusive
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive
+    pub until: Option<DateTime<Utc>>,
+}
+
+const LIST_PAGE_FORWARD_SQL: &str = r#"
+        SELECT * FROM transactions
+        WHERE ($1::timestamptz IS NULL OR (created_at, id) < ($1, $2))
+        AND ($3::varchar IS NULL OR stellar_account = $3)
+        AND ($4::varchar IS NULL OR status = $4)
+        AND ($5::varchar IS NULL OR asset_code = $5)
+        AND ($6::timestamptz IS NULL OR created_at >= $6)
+        AND ($7::timestamptz IS NULL OR created_at < $7)
+        ORDER BY created_at DESC, id DESC
+        LIMIT $8
+        "#;
+
+const LIST_PAGE_BACKWARD_SQL: &str = r#"
+        SELECT * FROM transactions
+        WHERE ($1::timestamptz IS NULL OR (created_at, id) > ($1, $2))
+        AND ($3::varchar IS NULL OR stellar_account = $3)
+        AND ($4::varchar IS NULL OR status = $4)
+        AND ($5::varchar IS NULL OR asset_code = $5)
+        AND ($6::timestamptz IS NULL OR created_at >= $6)
+        AND ($7::timestamptz IS NULL OR created_at < $7)
+        ORDER BY created_at ASC, id ASC
+        LIMIT $8
+        "#;
+
+const LIST_PAGE_COLUMNS: &[&str] = &["created_at", "id", "status", "asset_code"];
+const LIST_PAGE_BY_ACCOUNT_COLUMNS: &[&str] =
+    &["created_at", "id", "stellar_account", "status", "asset_code"];
+
+const LIST_PAGE_FORWARD: QueryMeta = QueryMeta {
+    name: "transactions.list_page",
+    sql: LIST_PAGE_FORWARD_SQL,
+    bound_columns: LIST_PAGE_COLUMNS,
+};
+
+const LIST_PAGE_BACKWARD: QueryMeta = QueryMeta {
+    name: "transactions.list_page_backward",
+    sql: LIST_PAGE_BACKWARD_SQL,
+    bound_columns: LIST_PAGE_COLUMNS,
+};
+
+// With the account filter set the account is a bind parameter, so these
+// plans are never captured.
+const LIST_PAGE_FORWARD_BY_ACCOUNT: QueryMeta = QueryMeta {
+    name: "transactions.list_page_by_account",
+    sql: LIST_PAGE_FORWARD_SQL,
+    bound_columns: LIST_PAGE_BY_ACCOUNT_COLUMNS,
+};
+
+const LIST_PAGE_BACKWARD_BY_ACCOUNT: QueryMeta = QueryMeta {
+    name: "transactions.list_page_backward_by_account",
+    sql: LIST_PAGE_BACKWARD_SQL,
+    bound_columns: LIST_PAGE_BY_ACCOUNT_COLUMNS,
+};
+
+/// Up to `limit` filtered transactions starting after `cursor`, the
+/// `(created_at, id)` of the last row of the previous page. Forward pages
+/// are older rows, newest first; backward pages are newer rows, oldest
+/// first, nearest the cursor. Keyed on the partition column, so pages
+/// neither skip nor repeat rows across partitions, and partitions outside
+/// the window are pruned.
+pub async fn list_transactions_page(
+    pool: &PgPool,
+    filter: &TransactionListFilter<'_>,
+    cursor: Option<(DateTime<Utc>, Uuid)>,
+    backward: bool,
+    limit: i64,
+) -> Result<Vec<Transaction>> {
+    let (cursor_ts, cursor_id) = cursor.unzip();
+    let meta = match (backward, filter.stellar_account.is_some()) {
+        (false, false) => &LIST_PAGE_FORWARD,
+        (true, false) => &LIST_PAGE_BACKWARD,
+        (false, true) => &LIST_PAGE_FORWARD_BY_ACCOUNT,
+        (true, true) => &LIST_PAGE_BACKWARD_BY_ACCOUNT,
+    };
+    let started = Instant::now();
+    let page = sqlx::query_as::<_, Transaction>(meta.sql)
+    .bind(cursor_ts)
+    .bind(cursor_id)
+    .bind(filter.stellar_account)
+    .bind(filter.status)
+    .bind(filter.asset_code)
+    .bind(filter.from)
+    .bind(filter.until)
+    .bind(limit)
+    .fetch_all(pool)
+    .await;
+    query_plan::observe(meta, started, || {
+        vec![
+            PlanParam::Timestamp(cursor_ts),
+            PlanParam::Uuid(cursor_id),
+            PlanParam::Text(filter.stellar_account.map(str::to_owned)),
+            PlanParam::Text(filter.status.map(str::to_owned)),
+            PlanParam::Text(filter.asset_code.map(str::to_owned)),
+            PlanParam::Timestamp(filter.from),
+            PlanParam::Timestamp(filter.until),
+            PlanParam::Int(limit),
+        ]
+    });
+    page
+}
+
+/// Audit trail of one entity, oldest first
+pub async fn list_audit_logs_for_entity(
+    pool: &PgPool,
+    entity_type: &str,
+    entity_id: Uuid,
+) -> Result<Vec<AuditLogEntry>> {
+    sqlx::query_as::<_, AuditLogEntry>(
+        r#"
+        SELECT id, entity_id, entity_type, action, old_val, new_val, actor, timestamp
+        FROM audit_logs
+        WHERE entity_type = $1 AND entity_id = $2
+        ORDER BY timestamp, created_at
+        "#
+    )
+    .bind(entity_type)
+    .bind(entity_id)
+    .fetch_all(pool)
+    .await
+}
+
 pub async fn get_unsettled_transactions(
     executor: &mut SqlxTransaction<'_, Postgres>,
     asset_code: &str,
@@ -247,3 +769,1886 @@ pub async fn get_unique_assets_to_settle(pool: &PgPool) -> Result<Vec<String>> {
         r.get:: <String, _>("asset_code")
     }).collect())
 }
+
+// --- Export Job Queries ---
+
+/// Find the newest export job for the given parameter hash that is still usable:
+/// either not yet finished, or ready and created within the freshness window.
+pub async fn find_reusable_export_job(
+    pool: &PgPool,
+    params_hash: &str,
+    fresh_after: DateTime<Utc>,
+) -> Result<Option<ExportJob>> {
+    sqlx::query_as::<_, ExportJob>(
+        r#"
+        SELECT * FROM export_jobs
+        WHERE params_hash = $1
+        AND expires_at > NOW()
+        AND (status IN ('pending', 'running') OR (status = 'ready' AND created_at >= $2))
+        ORDER BY created_at DESC
+        LIMIT 1
+        "#
+    )
+    .bind(params_hash)
+    .bind(fresh_after)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn insert_export_job(pool: &PgPool, job: &ExportJob) -> Result<ExportJob> {
+    sqlx::query_as::<_, ExportJob>(
+        r#"
+        INSERT INTO export_jobs (id, params_hash, params, format, status, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+        "#
+    )
+    .bind(job.id)
+    .bind(&job.params_hash)
+    .bind(&job.params)
+    .bind(&job.format)
+    .bind(&job.status)
+    .bind(job.created_at)
+    .bind(job.expires_at)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn get_export_job(pool: &PgPool, id: Uuid) -> Result<ExportJob> {
+    sqlx::query_as::<_, ExportJob>("SELECT * FROM export_jobs WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn mark_export_job_running(pool: &PgPool, id: Uuid) -> Result<()> {
+    sqlx::query("UPDATE export_jobs SET status = 'running' WHERE id = $1 AND status = 'pending'")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn complete_export_job(
+    pool: &PgPool,
+    id: Uuid,
+    storage_key: &str,
+    content_hash: &str,
+    size_bytes: i64,
+    row_count: i64,
+) -> Result<ExportJob> {
+    sqlx::query_as::<_, ExportJob>(
+        r#"
+        UPDATE export_jobs
+        SET status = 'ready', storage_key = $2, content_hash = $3, size_bytes = $4,
+            row_count = $5, completed_at = NOW()
+        WHERE id = $1
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(storage_key)
+    .bind(content_hash)
+    .bind(size_bytes)
+    .bind(row_count)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn fail_export_job(pool: &PgPool, id: Uuid, error: &str) -> Result<()> {
+    sqlx::query("UPDATE export_jobs SET status = 'failed', error = $2, completed_at = NOW() WHERE id = $1")
+        .bind(id)
+        .bind(error)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Mark jobs expired by `now` and return them so their artifacts can be removed.
+pub async fn expire_export_jobs(pool: &PgPool, now: DateTime<Utc>) -> Result<Vec<ExportJob>> {
+    sqlx::query_as::<_, ExportJob>(
+        r#"
+        UPDATE export_jobs SET status = 'expired'
+        WHERE expires_at <= $1 AND status <> 'expired'
+        RETURNING *
+        "#
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await
+}
+
+/// Whether any job that has not expired still references the given artifact.
+pub async fn export_artifact_in_use(pool: &PgPool, storage_key: &str) -> Result<bool> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM export_jobs WHERE storage_key = $1 AND status = 'ready')"
+    )
+    .bind(storage_key)
+    .fetch_one(pool)
+    .await
+}
+
+// --- Asset Queries ---
+
+pub async fn insert_asset<'e, E>(
+    executor: E,
+    asset_code: &str,
+    asset_issuer: Option<&str>,
+    metadata: &serde_json::Value,
+    enabled: bool,
+) -> Result<Asset>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, Asset>(
+        r#"
+        INSERT INTO assets (asset_code, asset_issuer, metadata, enabled)
+        VALUES ($1, $2, $3, $4)
+        RETURNING *
+        "#
+    )
+    .bind(asset_code)
+    .bind(asset_issuer)
+    .bind(metadata)
+    .bind(enabled)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn update_asset<'e, E>(
+    executor: E,
+    id: Uuid,
+    asset_issuer: Option<&str>,
+    metadata: &serde_json::Value,
+    enabled: bool,
+) -> Result<Asset>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, Asset>(
+        r#"
+        UPDATE assets
+        SET asset_issuer = $2, metadata = $3, enabled = $4, updated_at = NOW()
+        WHERE id = $1
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(asset_issuer)
+    .bind(metadata)
+    .bind(enabled)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn get_asset(pool: &PgPool, id: Uuid) -> Result<Asset> {
+    sqlx::query_as::<_, Asset>("SELECT * FROM assets WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+}
+
+/// The registry asset with this code and issuer; `None` as the issuer finds
+/// an asset registered by code alone
+pub async fn get_registry_asset(
+    pool: &PgPool,
+    asset_code: &str,
+    asset_issuer: Option<&str>,
+) -> Result<Option<Asset>> {
+    sqlx::query_as::<_, Asset>(
+        "SELECT * FROM assets WHERE asset_code = $1 AND asset_issuer IS NOT DISTINCT FROM $2",
+    )
+    .bind(asset_code)
+    .bind(asset_issuer)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Issuers the registry has for a code, oldest first
+pub async fn list_asset_issuers(pool: &PgPool, asset_code: &str) -> Result<Vec<String>> {
+    sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT asset_issuer FROM assets
+        WHERE asset_code = $1 AND asset_issuer IS NOT NULL
+        ORDER BY created_at
+        "#,
+    )
+    .bind(asset_code)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_asset_by_code_and_issuer(
+    pool: &PgPool,
+    asset_code: &str,
+    asset_issuer: &str,
+) -> Result<Option<Asset>> {
+    sqlx::query_as::<_, Asset>("SELECT * FROM assets WHERE asset_code = $1 AND asset_issuer = $2")
+        .bind(asset_code)
+        .bind(asset_issuer)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Store the outcome of an issuer home-domain verification.
+pub async fn set_asset_verification<'e, E>(
+    executor: E,
+    id: Uuid,
+    status: &str,
+    error: Option<&str>,
+) -> Result<Asset>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, Asset>(
+        r#"
+        UPDATE assets
+        SET verification_status = $2, verification_error = $3, verified_at = NOW()
+        WHERE id = $1
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(status)
+    .bind(error)
+    .fetch_one(executor)
+    .await
+}
+
+/// Assets never verified or last verified before `verified_before`.
+pub async fn list_assets_due_for_verification(
+    pool: &PgPool,
+    verified_before: DateTime<Utc>,
+) -> Result<Vec<Asset>> {
+    sqlx::query_as::<_, Asset>(
+        r#"
+        SELECT * FROM assets
+        WHERE verified_at IS NULL OR verified_at < $1
+        ORDER BY verified_at NULLS FIRST
+        "#
+    )
+    .bind(verified_before)
+    .fetch_all(pool)
+    .await
+}
+
+// --- Stream Cursor Queries ---
+
+pub async fn get_stream_cursor(pool: &PgPool, name: &str) -> Result<Option<String>> {
+    sqlx::query_scalar::<_, String>("SELECT cursor FROM stream_cursors WHERE name = $1")
+        .bind(name)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn save_stream_cursor(pool: &PgPool, name: &str, cursor: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO stream_cursors (name, cursor, updated_at)
+        VALUES ($1, $2, NOW())
+        ON CONFLICT (name) DO UPDATE SET cursor = EXCLUDED.cursor, updated_at = NOW()
+        "#
+    )
+    .bind(name)
+    .bind(cursor)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Record an effect as processed. Returns false if it was already recorded.
+pub async fn mark_effect_processed(pool: &PgPool, effect_id: &str) -> Result<bool> {
+    let result = sqlx::query(
+        "INSERT INTO processed_effects (effect_id) VALUES ($1) ON CONFLICT (effect_id) DO NOTHING"
+    )
+    .bind(effect_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+// --- Trustline Queries ---
+
+/// Move an account's transactions for an asset from a pre-payout state to
+/// `pending_trustline`, returning the transactions that changed.
+pub async fn move_to_pending_trustline(
+    pool: &PgPool,
+    stellar_account: &str,
+    asset_code: &str,
+    asset_issuer: &str,
+    pre_payout_statuses: &[&str],
+) -> Result<Vec<Transaction>> {
+    let statuses: Vec<String> = pre_payout_statuses.iter().map(|s| s.to_string()).collect();
+
+    uow::run(pool, |uow| Box::pin(async move {
+        let ids = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT id FROM transactions
+            WHERE stellar_account = $1 AND asset_code = $2 AND asset_issuer = $3
+              AND status = ANY($4)
+            "#,
+        )
+        .bind(stellar_account)
+        .bind(asset_code)
+        .bind(asset_issuer)
+        .bind(&statuses)
+        .fetch_all(uow.conn())
+        .await?;
+
+        let mut moved = Vec::with_capacity(ids.len());
+        for id in ids {
+            // Re-checked under the row lock; a concurrent change wins
+            if let Some((_, updated)) =
+                update_transaction_status(uow.conn(), id, pre_payout_statuses, "pending_trustline", "system")
+                    .await?
+            {
+                moved.push(updated);
+            }
+        }
+        Ok(moved)
+    }))
+    .await
+}
+
+// --- Payment Operation Queries ---
+
+/// Record a Horizon operation as processed. Returns false if it was already recorded.
+pub async fn mark_operation_processed<'e, E>(executor: E, operation_id: &str, operation_type: &str) -> Result<bool>
+where
+    E: PgExecutor<'e>,
+{
+    let result = sqlx::query(
+        r#"
+        INSERT INTO processed_operations (operation_id, operation_type)
+        VALUES ($1, $2)
+        ON CONFLICT (operation_id) DO NOTHING
+        "#
+    )
+    .bind(operation_id)
+    .bind(operation_type)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Link a processed operation to the transaction it matched
+pub async fn set_operation_transaction<'e, E>(executor: E, operation_id: &str, transaction_id: Uuid) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query("UPDATE processed_operations SET transaction_id = $2 WHERE operation_id = $1")
+        .bind(operation_id)
+        .bind(transaction_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// The oldest pending transaction a payment with this memo, asset and amount pays for
+pub async fn find_pending_payment_match<'e, E>(
+    executor: E,
+    memo: &str,
+    asset_code: &str,
+    asset_issuer: Option<&str>,
+    amount: &BigDecimal,
+) -> Result<Option<Uuid>>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT id FROM transactions
+        WHERE anchor_transaction_id = $1 AND asset_code = $2
+          AND asset_issuer IS NOT DISTINCT FROM $3 AND amount = $4 AND status = 'pending'
+        ORDER BY created_at
+        LIMIT 1
+        "#
+    )
+    .bind(memo)
+    .bind(asset_code)
+    .bind(asset_issuer)
+    .bind(amount)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Claim up to `limit` pending deposits created at or before `created_before`
+/// for a reconciliation pass. Deposits the payment processor has claimed are
+/// its to finish. A claimed transaction is not claimed again until
+/// `lease_until`, and rows another worker has locked are skipped.
+pub async fn claim_transactions_for_reconciliation(
+    pool: &PgPool,
+    created_before: DateTime<Utc>,
+    now: DateTime<Utc>,
+    lease_until: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<Transaction>> {
+    sqlx::query_as::<_, Transaction>(
+        r#"
+        UPDATE transactions SET reconcile_after = $3
+        WHERE (id, created_at) IN (
+            SELECT id, created_at FROM transactions
+            WHERE status = 'pending'
+            AND callback_type IS DISTINCT FROM 'withdrawal'
+            AND payout_after IS NULL
+            AND created_at <= $1
+            AND (reconcile_after IS NULL OR reconcile_after <= $2)
+            ORDER BY reconcile_after NULLS FIRST, created_at
+            LIMIT $4
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING *
+        "#,
+    )
+    .bind(created_before)
+    .bind(now)
+    .bind(lease_until)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Lock a transaction that is still pending and unclaimed by the payment
+/// processor, for the rest of the unit of work
+pub async fn lock_reconcilable_transaction(
+    conn: &mut PgConnection,
+    id: Uuid,
+) -> Result<Option<Transaction>> {
+    sqlx::query_as::<_, Transaction>(
+        "SELECT * FROM transactions \
+         WHERE id = $1 AND status = 'pending' AND payout_after IS NULL FOR UPDATE",
+    )
+    .bind(id)
+    .fetch_optional(conn)
+    .await
+}
+
+/// Set `key` in a transaction's metadata, keeping the other keys
+pub async fn set_transaction_metadata<'e, E>(
+    executor: E,
+    id: Uuid,
+    key: &str,
+    value: serde_json::Value,
+) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query(
+        r#"
+        UPDATE transactions
+        SET metadata = COALESCE(metadata, '{}'::jsonb) || jsonb_build_object($2::text, $3::jsonb)
+        WHERE id = $1
+        "#
+    )
+    .bind(id)
+    .bind(key)
+    .bind(value)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Reconciliation metadata of a transaction, `None` if nothing was recorded
+pub async fn get_transaction_metadata<'e, E>(executor: E, id: Uuid) -> Result<Option<serde_json::Value>>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_scalar::<_, Option<serde_json::Value>>("SELECT metadata FROM transactions WHERE id = $1")
+        .bind(id)
+        .fetch_one(executor)
+        .await
+}
+
+// --- Payment Processor Queries ---
+
+/// Claim up to `limit` deposits for a payment processor pass: pending ones,
+/// and submitted ones due for another look. Withdrawals and SEP-31 receiving
+/// transactions are not ours to pay out on Stellar. A claimed transaction is not
+/// claimed again until `lease_until`, and rows another worker has locked are
+/// skipped.
+pub async fn claim_transactions_for_payout(
+    pool: &PgPool,
+    now: DateTime<Utc>,
+    lease_until: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<Payout>> {
+    sqlx::query_as::<_, Payout>(
+        r#"
+        UPDATE transactions SET payout_after = $2
+        WHERE (id, created_at) IN (
+            SELECT id, created_at FROM transactions
+            WHERE status IN ('pending', 'submitted')
+            AND (callback_type IS NULL OR callback_type NOT IN ('withdrawal', 'sep31'))
+            AND (payout_after IS NULL OR payout_after <= $1)
+            ORDER BY payout_after NULLS FIRST, created_at
+            LIMIT $3
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING *
+        "#,
+    )
+    .bind(now)
+    .bind(lease_until)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Lock a transaction that is still in `status`, for the rest of the unit of work
+pub async fn lock_transaction_with_status(
+    conn: &mut PgConnection,
+    id: Uuid,
+    status: &str,
+) -> Result<Option<Transaction>> {
+    sqlx::query_as::<_, Transaction>(
+        "SELECT * FROM transactions WHERE id = $1 AND status = $2 FOR UPDATE",
+    )
+    .bind(id)
+    .bind(status)
+    .fetch_optional(conn)
+    .await
+}
+
+/// Record the payment signed for a transaction, before it is submitted
+pub async fn set_payout_envelope<'e, E>(
+    executor: E,
+    id: Uuid,
+    hash: &str,
+    envelope: &str,
+    valid_until: DateTime<Utc>,
+) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query(
+        r#"
+        UPDATE transactions
+        SET payout_tx_hash = $2, payout_envelope = $3, payout_valid_until = $4, updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(hash)
+    .bind(envelope)
+    .bind(valid_until)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Forget a payment that was rejected or can no longer land
+pub async fn clear_payout_envelope<'e, E>(executor: E, id: Uuid) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query(
+        r#"
+        UPDATE transactions
+        SET payout_tx_hash = NULL, payout_envelope = NULL, payout_valid_until = NULL,
+            updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Set the hash Horizon reported for a landed payment
+pub async fn set_payout_tx_hash<'e, E>(executor: E, id: Uuid, hash: &str) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query("UPDATE transactions SET payout_tx_hash = $2 WHERE id = $1")
+        .bind(id)
+        .bind(hash)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Lock the transaction paid out in Stellar transaction `hash`, for the rest
+/// of the unit of work
+pub async fn lock_transaction_by_payout_hash(
+    conn: &mut PgConnection,
+    hash: &str,
+) -> Result<Option<Transaction>> {
+    sqlx::query_as::<_, Transaction>(
+        "SELECT * FROM transactions WHERE payout_tx_hash = $1 LIMIT 1 FOR UPDATE",
+    )
+    .bind(hash)
+    .fetch_optional(conn)
+    .await
+}
+
+// --- Erasure Queries ---
+
+pub async fn insert_erasure_job(pool: &PgPool, job: &ErasureJob) -> Result<ErasureJob> {
+    sqlx::query_as::<_, ErasureJob>(
+        r#"
+        INSERT INTO erasure_jobs (
+            id, account_hash, stellar_account, dry_run, block_callbacks,
+            status, policy, requested_by, created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING *
+        "#
+    )
+    .bind(job.id)
+    .bind(&job.account_hash)
+    .bind(&job.stellar_account)
+    .bind(job.dry_run)
+    .bind(job.block_callbacks)
+    .bind(&job.status)
+    .bind(&job.policy)
+    .bind(&job.requested_by)
+    .bind(job.created_at)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn get_erasure_job(pool: &PgPool, id: Uuid) -> Result<ErasureJob> {
+    sqlx::query_as::<_, ErasureJob>("SELECT * FROM erasure_jobs WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn list_erasure_jobs_by_account_hash(pool: &PgPool, account_hash: &str) -> Result<Vec<ErasureJob>> {
+    sqlx::query_as::<_, ErasureJob>(
+        "SELECT * FROM erasure_jobs WHERE account_hash = $1 ORDER BY created_at DESC"
+    )
+    .bind(account_hash)
+    .fetch_all(pool)
+    .await
+}
+
+/// Claim a pending erasure. Returns `None` if it is not pending.
+pub async fn start_erasure_job(pool: &PgPool, id: Uuid) -> Result<Option<ErasureJob>> {
+    sqlx::query_as::<_, ErasureJob>(
+        r#"
+        UPDATE erasure_jobs SET status = 'running', started_at = NOW()
+        WHERE id = $1 AND status = 'pending'
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Lock a job for the rest of the run's transaction, so a restart elsewhere
+/// neither re-queues nor starts it meanwhile
+pub async fn lock_erasure_job<'e, E>(executor: E, id: Uuid) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query("SELECT id FROM erasure_jobs WHERE id = $1 FOR UPDATE")
+        .bind(id)
+        .fetch_one(executor)
+        .await?;
+    Ok(())
+}
+
+/// Put runs interrupted by a stop back to `pending` and return every pending
+/// job, oldest first. Running jobs locked by a live run are left alone.
+pub async fn requeue_erasure_jobs(pool: &PgPool) -> Result<Vec<Uuid>> {
+    sqlx::query_scalar(
+        r#"
+        WITH requeued AS (
+            UPDATE erasure_jobs SET status = 'pending', started_at = NULL
+            WHERE id IN (
+                SELECT id FROM erasure_jobs WHERE status = 'running'
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, created_at
+        )
+        SELECT id FROM (
+            SELECT id, created_at FROM requeued
+            UNION ALL
+            SELECT id, created_at FROM erasure_jobs WHERE status = 'pending'
+        ) jobs
+        ORDER BY created_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Store the report and forget the address
+pub async fn complete_erasure_job<'e, E>(executor: E, id: Uuid, report: &serde_json::Value) -> Result<ErasureJob>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, ErasureJob>(
+        r#"
+        UPDATE erasure_jobs
+        SET status = 'completed', report = $2, stellar_account = NULL, completed_at = NOW()
+        WHERE id = $1
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(report)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn fail_erasure_job(pool: &PgPool, id: Uuid, error: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE erasure_jobs
+        SET status = 'failed', error = $2, stellar_account = NULL, completed_at = NOW()
+        WHERE id = $1 AND status IN ('pending', 'running')
+        "#
+    )
+    .bind(id)
+    .bind(error)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn add_to_account_watchlist<'e, E>(
+    executor: E,
+    account_hash: &str,
+    reason: &str,
+    erasure_id: Uuid,
+) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query(
+        r#"
+        INSERT INTO account_watchlist (account_hash, reason, erasure_id)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (account_hash) DO UPDATE SET reason = EXCLUDED.reason, erasure_id = EXCLUDED.erasure_id
+        "#
+    )
+    .bind(account_hash)
+    .bind(reason)
+    .bind(erasure_id)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+pub async fn is_account_watchlisted(pool: &PgPool, account_hash: &str) -> Result<bool> {
+    sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM account_watchlist WHERE account_hash = $1)")
+        .bind(account_hash)
+        .fetch_one(pool)
+        .await
+}
+
+// --- Transaction Status Queries ---
+
+const STATUS_VIEW_COLUMNS: &str = "id, amount, asset_code, status, created_at, updated_at, \
+    anchor_transaction_id, callback_type, payout_tx_hash";
+
+pub async fn get_status_view_by_id(pool: &PgPool, id: Uuid) -> Result<Option<TransactionStatusView>> {
+    sqlx::query_as::<_, TransactionStatusView>(&format!(
+        "SELECT {} FROM transactions WHERE id = $1",
+        STATUS_VIEW_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn get_status_view_by_anchor_id(
+    pool: &PgPool,
+    anchor_transaction_id: &str,
+) -> Result<Option<TransactionStatusView>> {
+    sqlx::query_as::<_, TransactionStatusView>(&format!(
+        "SELECT {} FROM transactions WHERE anchor_transaction_id = $1 ORDER BY created_at LIMIT 1",
+        STATUS_VIEW_COLUMNS
+    ))
+    .bind(anchor_transaction_id)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn get_status_view_by_payout_hash(
+    pool: &PgPool,
+    payout_tx_hash: &str,
+) -> Result<Option<TransactionStatusView>> {
+    sqlx::query_as::<_, TransactionStatusView>(&format!(
+        "SELECT {} FROM transactions WHERE payout_tx_hash = $1 LIMIT 1",
+        STATUS_VIEW_COLUMNS
+    ))
+    .bind(payout_tx_hash)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Row counts of every stored status that isn't canonical, by status
+pub async fn count_noncanonical_statuses(pool: &PgPool) -> Result<Vec<(String, i64)>> {
+    let canonical: Vec<&str> = TransactionStatus::ALL.iter().map(|status| status.as_str()).collect();
+    sqlx::query_as::<_, (String, i64)>(
+        r#"
+        SELECT status, COUNT(*)
+        FROM transactions
+        WHERE status <> ALL($1)
+        GROUP BY status
+        ORDER BY status
+        "#,
+    )
+    .bind(&canonical)
+    .fetch_all(pool)
+    .await
+}
+
+/// Rewrite up to `limit` rows holding the legacy spelling `legacy` to
+/// `status`. Returns the account of every rewritten row.
+///
+/// A representation fix, not a status change: `updated_at` and the audit
+/// log are left alone.
+pub async fn rewrite_legacy_status_batch(
+    conn: &mut PgConnection,
+    legacy: &str,
+    status: TransactionStatus,
+    limit: i64,
+) -> Result<Vec<String>> {
+    sqlx::query_scalar::<_, String>(
+        r#"
+        UPDATE transactions SET status = $2
+        WHERE id IN (SELECT id FROM transactions WHERE status = $1 LIMIT $3)
+        RETURNING stellar_account
+        "#,
+    )
+    .bind(legacy)
+    .bind(status.as_str())
+    .bind(limit)
+    .fetch_all(conn)
+    .await
+}
+
+// --- Callback Correlation Queries ---
+
+/// Serialize callbacks for one external id until the end of the transaction,
+/// so two concurrent callbacks cannot both create it
+pub async fn lock_callback_id(conn: &mut PgConnection, key: &str) -> Result<()> {
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+        .bind(key)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Transaction created by a callback of `callback_type` for an external id, locked for update
+pub async fn get_transaction_by_callback_id(
+    conn: &mut PgConnection,
+    callback_type: &str,
+    anchor_transaction_id: &str,
+) -> Result<Option<Transaction>> {
+    sqlx::query_as::<_, Transaction>(
+        "SELECT * FROM transactions WHERE callback_type = $1 AND anchor_transaction_id = $2 \
+         ORDER BY created_at LIMIT 1 FOR UPDATE"
+    )
+    .bind(callback_type)
+    .bind(anchor_transaction_id)
+    .fetch_optional(conn)
+    .await
+}
+
+/// Store the status string last reported by the counterparty, verbatim
+pub async fn set_callback_status<'e, E>(executor: E, id: Uuid, callback_status: &str) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query("UPDATE transactions SET callback_status = $2, updated_at = NOW() WHERE id = $1")
+        .bind(id)
+        .bind(callback_status)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Flag a transaction for operator review. Reasons accumulate, newest last.
+pub async fn flag_transaction_for_review<'e, E>(executor: E, id: Uuid, reason: &str) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query(
+        r#"
+        UPDATE transactions
+        SET needs_review = true,
+            review_reason = CASE WHEN review_reason IS NULL THEN $2 ELSE review_reason || E'\n' || $2 END
+        WHERE id = $1
+        "#
+    )
+    .bind(id)
+    .bind(reason)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// --- Account Stats Queries ---
+
+/// Aggregate `transactions` for account $1 into `account_stats` rows, with $2
+/// the in-flight statuses
+const RAW_ACCOUNT_STATS: &str = r#"
+    SELECT stellar_account, asset_code,
+           COALESCE(SUM(amount) FILTER (WHERE status = 'completed'), 0) AS total_completed,
+           COALESCE(SUM(amount) FILTER (WHERE status = ANY($2)), 0) AS total_pending,
+           MIN(created_at) AS first_deposit_at,
+           MAX(created_at) AS last_deposit_at,
+           COUNT(*) AS deposit_count,
+           NOW() AS updated_at
+    FROM transactions
+    WHERE stellar_account = $1
+    GROUP BY stellar_account, asset_code
+"#;
+
+fn in_flight_statuses() -> Vec<String> {
+    TransactionStatus::ALL
+        .iter()
+        .filter(|status| status.is_in_flight())
+        .map(|status| status.to_string())
+        .collect()
+}
+
+/// Move a transaction's amount between the completed and pending buckets
+/// after a status change. Atomic increments only; if the row is missing the
+/// account is recomputed from raw data instead.
+async fn apply_account_stats_transition(
+    conn: &mut PgConnection,
+    previous: &Transaction,
+    new_status: &str,
+) -> Result<()> {
+    let (old_completed, old_pending) = AccountStats::contribution(&previous.status, &previous.amount);
+    let (new_completed, new_pending) = AccountStats::contribution(new_status, &previous.amount);
+    let completed_delta = new_completed - old_completed;
+    let pending_delta = new_pending - old_pending;
+    let zero = BigDecimal::from(0);
+    if completed_delta == zero && pending_delta == zero {
+        return Ok(());
+    }
+
+    let result = sqlx::query(
+        r#"
+        UPDATE account_stats
+        SET total_completed = total_completed + $3,
+            total_pending = total_pending + $4,
+            updated_at = NOW()
+        WHERE stellar_account = $1 AND asset_code = $2
+        "#
+    )
+    .bind(&previous.stellar_account)
+    .bind(&previous.asset_code)
+    .bind(completed_delta)
+    .bind(pending_delta)
+    .execute(&mut *conn)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        recompute_account_stats(conn, &previous.stellar_account).await?;
+    }
+    Ok(())
+}
+
+/// Stored stats for every asset the account has deposited
+pub async fn get_account_stats<'e, E>(executor: E, stellar_account: &str) -> Result<Vec<AccountStats>>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, AccountStats>(
+        "SELECT * FROM account_stats WHERE stellar_account = $1 ORDER BY asset_code"
+    )
+    .bind(stellar_account)
+    .fetch_all(executor)
+    .await
+}
+
+/// Stats computed from the transactions table, without touching `account_stats`
+pub async fn compute_account_stats<'e, E>(executor: E, stellar_account: &str) -> Result<Vec<AccountStats>>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, AccountStats>(&format!("{} ORDER BY asset_code", RAW_ACCOUNT_STATS))
+        .bind(stellar_account)
+        .bind(in_flight_statuses())
+        .fetch_all(executor)
+        .await
+}
+
+/// Replace the account's stats rows with values computed from raw data
+pub async fn recompute_account_stats(
+    conn: &mut PgConnection,
+    stellar_account: &str,
+) -> Result<Vec<AccountStats>> {
+    sqlx::query("DELETE FROM account_stats WHERE stellar_account = $1")
+        .bind(stellar_account)
+        .execute(&mut *conn)
+        .await?;
+
+    let mut stats = sqlx::query_as::<_, AccountStats>(&format!(
+        r#"
+        INSERT INTO account_stats (
+            stellar_account, asset_code, total_completed, total_pending,
+            first_deposit_at, last_deposit_at, deposit_count, updated_at
+        )
+        {}
+        ON CONFLICT (stellar_account, asset_code) DO UPDATE SET
+            total_completed = EXCLUDED.total_completed,
+            total_pending = EXCLUDED.total_pending,
+            first_deposit_at = EXCLUDED.first_deposit_at,
+            last_deposit_at = EXCLUDED.last_deposit_at,
+            deposit_count = EXCLUDED.deposit_count,
+            updated_at = EXCLUDED.updated_at
+        RETURNING *
+        "#,
+        RAW_ACCOUNT_STATS
+    ))
+    .bind(stellar_account)
+    .bind(in_flight_statuses())
+    .fetch_all(&mut *conn)
+    .await?;
+    stats.sort_by(|a, b| a.asset_code.cmp(&b.asset_code));
+    Ok(stats)
+}
+
+/// Accounts with a transaction created or updated since `since`
+pub async fn list_recently_active_accounts(pool: &PgPool, since: DateTime<Utc>) -> Result<Vec<String>> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT stellar_account FROM transactions WHERE updated_at >= $1"
+    )
+    .bind(since)
+    .fetch_all(pool)
+    .await
+}
+
+// --- Quote Queries ---
+
+pub async fn insert_quote(pool: &PgPool, quote: &Quote) -> Result<Quote> {
+    sqlx::query_as::<_, Quote>(
+        r#"
+        INSERT INTO quotes (
+            id, sell_asset, buy_asset, sell_amount, buy_amount, rate, total_price,
+            fee_total, fee_asset, expires_at, created_at
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        RETURNING *
+        "#
+    )
+    .bind(&quote.id)
+    .bind(&quote.sell_asset)
+    .bind(&quote.buy_asset)
+    .bind(&quote.sell_amount)
+    .bind(&quote.buy_amount)
+    .bind(&quote.rate)
+    .bind(&quote.total_price)
+    .bind(&quote.fee_total)
+    .bind(&quote.fee_asset)
+    .bind(quote.expires_at)
+    .bind(quote.created_at)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn get_quote<'e, E>(executor: E, id: &str) -> Result<Option<Quote>>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, Quote>("SELECT * FROM quotes WHERE id = $1")
+        .bind(id)
+        .fetch_optional(executor)
+        .await
+}
+
+/// Attach an unused, unexpired quote to a transaction. Returns false if the
+/// quote was used or expired in the meantime.
+pub async fn link_quote_to_transaction<'e, E>(executor: E, quote_id: &str, transaction_id: Uuid) -> Result<bool>
+where
+    E: PgExecutor<'e>,
+{
+    let result = sqlx::query(
+        r#"
+        UPDATE quotes SET transaction_id = $2
+        WHERE id = $1 AND transaction_id IS NULL AND expires_at > NOW()
+        "#
+    )
+    .bind(quote_id)
+    .bind(transaction_id)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+/// Delete quotes that were never used and expired before `expired_before`
+pub async fn purge_unused_quotes(pool: &PgPool, expired_before: DateTime<Utc>) -> Result<u64> {
+    let result = sqlx::query("DELETE FROM quotes WHERE transaction_id IS NULL AND expires_at < $1")
+        .bind(expired_before)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+// --- Webhook Subscription Queries ---
+
+/// `locale` defaults to `en` when not given
+pub async fn insert_webhook_subscription(
+    pool: &PgPool,
+    url: &str,
+    event_types: &[String],
+    max_in_flight: Option<i32>,
+    locale: Option<&str>,
+) -> Result<WebhookSubscription> {
+    sqlx::query_as::<_, WebhookSubscription>(
+        r#"
+        INSERT INTO webhook_subscriptions (url, event_types, max_in_flight, locale)
+        VALUES ($1, $2, $3, COALESCE($4, 'en'))
+        RETURNING *
+        "#,
+    )
+    .bind(url)
+    .bind(event_types)
+    .bind(max_in_flight)
+    .bind(locale)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn get_webhook_subscription(pool: &PgPool, id: Uuid) -> Result<WebhookSubscription> {
+    sqlx::query_as::<_, WebhookSubscription>("SELECT * FROM webhook_subscriptions WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn list_webhook_subscriptions(pool: &PgPool) -> Result<Vec<WebhookSubscription>> {
+    sqlx::query_as::<_, WebhookSubscription>("SELECT * FROM webhook_subscriptions ORDER BY created_at")
+        .fetch_all(pool)
+        .await
+}
+
+/// Enabled subscriptions that are not cooling down
+pub async fn list_claimable_webhook_subscriptions(pool: &PgPool) -> Result<Vec<WebhookSubscription>> {
+    sqlx::query_as::<_, WebhookSubscription>(
+        r#"
+        SELECT * FROM webhook_subscriptions
+        WHERE enabled AND (cooldown_until IS NULL OR cooldown_until <= NOW())
+        ORDER BY created_at
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Replace a subscription's signing secret
+pub async fn rotate_webhook_subscription_secret(
+    pool: &PgPool,
+    id: Uuid,
+    secret: &str,
+) -> Result<WebhookSubscription> {
+    sqlx::query_as::<_, WebhookSubscription>(
+        "UPDATE webhook_subscriptions SET secret = $2, updated_at = NOW() WHERE id = $1 RETURNING *",
+    )
+    .bind(id)
+    .bind(secret)
+    .fetch_one(pool)
+    .await
+}
+
+/// Stop claiming a subscription's deliveries until `until`. An existing longer
+/// cool-down is kept.
+pub async fn set_webhook_subscription_cooldown(
+    pool: &PgPool,
+    id: Uuid,
+    until: DateTime<Utc>,
+    reason: &str,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE webhook_subscriptions
+        SET cooldown_until = $2, cooldown_reason = $3, updated_at = NOW()
+        WHERE id = $1 AND (cooldown_until IS NULL OR cooldown_until < $2)
+        "#,
+    )
+    .bind(id)
+    .bind(until)
+    .bind(reason)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// --- Notification Template Queries ---
+
+pub async fn list_notification_templates<'e, E>(executor: E) -> Result<Vec<NotificationTemplate>>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, NotificationTemplate>(
+        "SELECT * FROM notification_templates ORDER BY event_type, locale",
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// The template of `event_type` for the first of `locales` that has one
+pub async fn find_notification_template<'e, E>(
+    executor: E,
+    event_type: &str,
+    locales: &[String],
+) -> Result<Option<NotificationTemplate>>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, NotificationTemplate>(
+        r#"
+        SELECT * FROM notification_templates
+        WHERE event_type = $1 AND locale = ANY($2)
+        ORDER BY array_position($2::text[], locale::text)
+        LIMIT 1
+        "#,
+    )
+    .bind(event_type)
+    .bind(locales)
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn upsert_notification_template<'e, E>(
+    executor: E,
+    event_type: &str,
+    locale: &str,
+    template: &str,
+) -> Result<NotificationTemplate>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, NotificationTemplate>(
+        r#"
+        INSERT INTO notification_templates (event_type, locale, template)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (event_type, locale)
+        DO UPDATE SET template = EXCLUDED.template, updated_at = NOW()
+        RETURNING *
+        "#,
+    )
+    .bind(event_type)
+    .bind(locale)
+    .bind(template)
+    .fetch_one(executor)
+    .await
+}
+
+/// Returns whether a template was deleted
+pub async fn delete_notification_template<'e, E>(
+    executor: E,
+    event_type: &str,
+    locale: &str,
+) -> Result<bool>
+where
+    E: PgExecutor<'e>,
+{
+    let result = sqlx::query("DELETE FROM notification_templates WHERE event_type = $1 AND locale = $2")
+        .bind(event_type)
+        .bind(locale)
+        .execute(executor)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Display decimals of an asset, from `metadata.decimals` in the registry
+pub async fn get_asset_display_decimals<'e, E>(
+    executor: E,
+    asset_code: &str,
+    asset_issuer: Option<&str>,
+) -> Result<Option<i32>>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_scalar::<_, Option<i32>>(
+        r#"
+        SELECT (metadata->>'decimals')::int FROM assets
+        WHERE asset_code = $1 AND asset_issuer IS NOT DISTINCT FROM $2 AND metadata ? 'decimals'
+        "#,
+    )
+    .bind(asset_code)
+    .bind(asset_issuer)
+    .fetch_optional(executor)
+    .await
+    .map(Option::flatten)
+}
+
+// --- Webhook Delivery Queries ---
+
+/// Queue an event for every enabled subscription listening to `event_type`
+pub async fn enqueue_webhook_deliveries<'e, E>(
+    executor: E,
+    event_type: &str,
+    payload: &serde_json::Value,
+    correlation_id: Option<&str>,
+) -> Result<u64>
+where
+    E: PgExecutor<'e>,
+{
+    let result = sqlx::query(
+        r#"
+        INSERT INTO webhook_deliveries (subscription_id, event_type, payload, correlation_id)
+        SELECT id, $1, $2, $3 FROM webhook_subscriptions
+        WHERE enabled AND (cardinality(event_types) = 0 OR $1 = ANY(event_types))
+        "#,
+    )
+    .bind(event_type)
+    .bind(payload)
+    .bind(correlation_id)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Claim up to `limit` due deliveries of one subscription. Rows whose lease
+/// expired (the claiming instance died) are claimed again.
+pub async fn claim_webhook_deliveries(
+    pool: &PgPool,
+    subscription_id: Uuid,
+    limit: i64,
+    lease_until: DateTime<Utc>,
+) -> Result<Vec<WebhookDelivery>> {
+    sqlx::query_as::<_, WebhookDelivery>(
+        r#"
+        UPDATE webhook_deliveries SET status = 'delivering', locked_until = $3
+        WHERE id IN (
+            SELECT id FROM webhook_deliveries
+            WHERE subscription_id = $1
+              AND next_attempt_at <= NOW()
+              AND (status = 'pending' OR (status = 'delivering' AND locked_until < NOW()))
+            ORDER BY next_attempt_at
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING *
+        "#,
+    )
+    .bind(subscription_id)
+    .bind(limit)
+    .bind(lease_until)
+    .fetch_all(pool)
+    .await
+}
+
+/// Hand claimed deliveries back without counting an attempt, e.g. when the
+/// claiming instance stops before sending them
+pub async fn release_webhook_deliveries(pool: &PgPool, ids: &[Uuid]) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        UPDATE webhook_deliveries SET status = 'pending', locked_until = NULL
+        WHERE id = ANY($1) AND status = 'delivering'
+        "#,
+    )
+    .bind(ids)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+pub async fn mark_webhook_delivered(pool: &PgPool, id: Uuid) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = 'delivered', attempts = attempts + 1, delivered_at = NOW(),
+            locked_until = NULL, last_error = NULL
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Record a failed attempt: back to `pending` at `next_attempt_at`, or
+/// `failed` once `max_attempts` is reached. Returns whether the delivery
+/// was dead-lettered.
+pub async fn mark_webhook_delivery_failed(
+    pool: &PgPool,
+    id: Uuid,
+    error: &str,
+    next_attempt_at: DateTime<Utc>,
+    max_attempts: i32,
+) -> Result<bool> {
+    let status: Option<String> = sqlx::query_scalar(
+        r#"
+        UPDATE webhook_deliveries
+        SET attempts = attempts + 1,
+            status = CASE WHEN attempts + 1 >= $4 THEN 'failed' ELSE 'pending' END,
+            next_attempt_at = $3, locked_until = NULL, last_error = $2
+        WHERE id = $1
+        RETURNING status
+        "#,
+    )
+    .bind(id)
+    .bind(error)
+    .bind(next_attempt_at)
+    .bind(max_attempts)
+    .fetch_optional(pool)
+    .await?;
+    Ok(status.as_deref() == Some(DELIVERY_DEAD_LETTER))
+}
+
+/// Dead-lettered deliveries, newest first, of one subscription or all
+pub async fn list_dead_letter_webhook_deliveries(
+    pool: &PgPool,
+    subscription_id: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<WebhookDelivery>> {
+    sqlx::query_as::<_, WebhookDelivery>(
+        r#"
+        SELECT * FROM webhook_deliveries
+        WHERE status = 'failed' AND ($1::uuid IS NULL OR subscription_id = $1)
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(subscription_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Queue a dead-lettered delivery again with a fresh set of attempts.
+/// `None` when there is no such delivery or it isn't dead-lettered.
+pub async fn redeliver_webhook_delivery(pool: &PgPool, id: Uuid) -> Result<Option<WebhookDelivery>> {
+    sqlx::query_as::<_, WebhookDelivery>(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = 'pending', attempts = 0, next_attempt_at = NOW(), locked_until = NULL
+        WHERE id = $1 AND status = 'failed'
+        RETURNING *
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Status of a delivery that used up its attempts
+pub const DELIVERY_DEAD_LETTER: &str = "failed";
+
+pub async fn count_pending_webhook_deliveries(pool: &PgPool, subscription_id: Uuid) -> Result<i64> {
+    sqlx::query_scalar(
+        "SELECT COUNT(*) FROM webhook_deliveries WHERE subscription_id = $1 AND status IN ('pending', 'delivering')",
+    )
+    .bind(subscription_id)
+    .fetch_one(pool)
+    .await
+}
+
+// --- Outbox Event Queries ---
+
+/// Published by every transaction status change, in the same unit of work
+pub const EVENT_TRANSACTION_STATUS_CHANGED: &str = "transaction.status_changed";
+
+/// Record an event and queue its webhook deliveries, in the caller's
+/// database transaction. Every outbound event goes through here, so the
+/// admin event stream sees exactly what subscribers are sent.
+pub async fn publish_event(
+    conn: &mut PgConnection,
+    event_type: &str,
+    payload: &serde_json::Value,
+    correlation_id: Option<&str>,
+) -> Result<OutboxEvent> {
+    let event = sqlx::query_as::<_, OutboxEvent>(
+        r#"
+        INSERT INTO outbox_events (event_type, asset_code, payload, correlation_id)
+        VALUES ($1, $2->>'asset_code', $2, $3)
+        RETURNING *
+        "#,
+    )
+    .bind(event_type)
+    .bind(payload)
+    .bind(correlation_id)
+    .fetch_one(&mut *conn)
+    .await?;
+    enqueue_webhook_deliveries(&mut *conn, event_type, payload, correlation_id).await?;
+    Ok(event)
+}
+
+/// Sequence number of the newest event, 0 when there are none
+pub async fn latest_outbox_event_seq(pool: &PgPool) -> Result<i64> {
+    sqlx::query_scalar("SELECT COALESCE(MAX(seq), 0) FROM outbox_events")
+        .fetch_one(pool)
+        .await
+}
+
+/// Events after `seq` created in the last `lookback_secs`, oldest first
+pub async fn list_outbox_events_after(
+    pool: &PgPool,
+    seq: i64,
+    lookback_secs: i64,
+    limit: i64,
+) -> Result<Vec<OutboxEvent>> {
+    sqlx::query_as::<_, OutboxEvent>(
+        r#"
+        SELECT * FROM outbox_events
+        WHERE seq > $1 AND created_at >= NOW() - make_interval(secs => $2)
+        ORDER BY seq
+        LIMIT $3
+        "#,
+    )
+    .bind(seq)
+    .bind(lookback_secs as f64)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// The events among `seqs` that exist, in order
+pub async fn list_outbox_events_by_seq(pool: &PgPool, seqs: &[i64]) -> Result<Vec<OutboxEvent>> {
+    sqlx::query_as::<_, OutboxEvent>(
+        "SELECT * FROM outbox_events WHERE seq = ANY($1) ORDER BY seq",
+    )
+    .bind(seqs)
+    .fetch_all(pool)
+    .await
+}
+
+/// Whether some event after `seq` can't be replayed: it is older than
+/// `lookback_secs`, or was already purged
+pub async fn outbox_events_missed_beyond_lookback(
+    pool: &PgPool,
+    seq: i64,
+    lookback_secs: i64,
+) -> Result<bool> {
+    sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM outbox_events
+            WHERE seq > $1 AND created_at < NOW() - make_interval(secs => $2)
+        ) OR COALESCE((SELECT MIN(seq) FROM outbox_events) > $1 + 1, FALSE)
+        "#,
+    )
+    .bind(seq)
+    .bind(lookback_secs as f64)
+    .fetch_one(pool)
+    .await
+}
+
+/// Delete events created before `before`. Returns the number deleted.
+pub async fn purge_outbox_events(pool: &PgPool, before: DateTime<Utc>) -> Result<u64> {
+    let result = sqlx::query("DELETE FROM outbox_events WHERE created_at < $1")
+        .bind(before)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+// --- API Token Queries ---
+
+pub async fn insert_api_token<'e, E>(executor: E, token: &ApiToken) -> Result<ApiToken>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, ApiToken>(
+        r#"
+        INSERT INTO api_tokens (id, name, token_prefix, token_hash, scope, expires_at, created_by, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING *
+        "#
+    )
+    .bind(token.id)
+    .bind(&token.name)
+    .bind(&token.token_prefix)
+    .bind(&token.token_hash)
+    .bind(&token.scope)
+    .bind(token.expires_at)
+    .bind(&token.created_by)
+    .bind(token.created_at)
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn get_api_token<'e, E>(executor: E, id: Uuid) -> Result<ApiToken>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, ApiToken>("SELECT * FROM api_tokens WHERE id = $1")
+        .bind(id)
+        .fetch_one(executor)
+        .await
+}
+
+/// Token for a secret hash, only while it is neither revoked nor expired
+/// The unrevoked token with this hash, if it has not expired at `now`
+pub async fn get_active_api_token_by_hash(
+    pool: &PgPool,
+    token_hash: &str,
+    now: DateTime<Utc>,
+) -> Result<Option<ApiToken>> {
+    sqlx::query_as::<_, ApiToken>(
+        r#"
+        SELECT * FROM api_tokens
+        WHERE token_hash = $1
+        AND revoked_at IS NULL
+        AND expires_at > $2
+        "#
+    )
+    .bind(token_hash)
+    .bind(now)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Revoke a token; returns `None` if it was already revoked
+pub async fn revoke_api_token<'e, E>(executor: E, id: Uuid) -> Result<Option<ApiToken>>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, ApiToken>(
+        r#"
+        UPDATE api_tokens SET revoked_at = NOW()
+        WHERE id = $1 AND revoked_at IS NULL
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Every delivery queued for one correlation id, oldest first
+pub async fn list_webhook_deliveries_by_correlation_id(
+    pool: &PgPool,
+    correlation_id: &str,
+) -> Result<Vec<WebhookDelivery>> {
+    sqlx::query_as::<_, WebhookDelivery>(
+        "SELECT * FROM webhook_deliveries WHERE correlation_id = $1 ORDER BY created_at, id",
+    )
+    .bind(correlation_id)
+    .fetch_all(pool)
+    .await
+}
+
+// --- Buffered Write Queries ---
+
+/// Insert audit entries in one statement, keeping each entry's own timestamp
+pub async fn insert_audit_logs<'e, E>(executor: E, entries: &[AuditLog]) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let mut query = sqlx::QueryBuilder::<Postgres>::new(
+        "INSERT INTO audit_logs (entity_id, entity_type, action, old_val, new_val, actor, timestamp) ",
+    );
+    query.push_values(entries, |mut row, entry| {
+        row.push_bind(entry.entity_id)
+            .push_bind(&entry.entity_type)
+            .push_bind(&entry.action)
+            .push_bind(&entry.old_val)
+            .push_bind(&entry.new_val)
+            .push_bind(&entry.actor)
+            .push_bind(entry.timestamp);
+    });
+    query.build().execute(executor).await?;
+    Ok(())
+}
+
+/// Add request counts to the usage rows; each key must appear at most once
+pub async fn increment_api_token_usage<'e, E>(executor: E, usage: &[ApiTokenUsage]) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    if usage.is_empty() {
+        return Ok(());
+    }
+    let mut query = sqlx::QueryBuilder::<Postgres>::new(
+        "INSERT INTO api_token_usage (token_id, method, route, day, requests) ",
+    );
+    query.push_values(usage, |mut row, entry| {
+        row.push_bind(entry.token_id)
+            .push_bind(&entry.method)
+            .push_bind(&entry.route)
+            .push_bind(entry.day)
+            .push_bind(entry.requests);
+    });
+    query.push(
+        r#"
+        ON CONFLICT (token_id, method, route, day) DO UPDATE
+        SET requests = api_token_usage.requests + EXCLUDED.requests,
+            updated_at = NOW()
+        "#,
+    );
+    query.build().execute(executor).await?;
+    Ok(())
+}
+
+pub async fn list_api_token_usage(pool: &PgPool, token_id: Uuid) -> Result<Vec<ApiTokenUsage>> {
+    sqlx::query_as::<_, ApiTokenUsage>(
+        r#"
+        SELECT token_id, method, route, day, requests FROM api_token_usage
+        WHERE token_id = $1
+        ORDER BY day, method, route
+        "#
+    )
+    .bind(token_id)
+    .fetch_all(pool)
+    .await
+}
+
+// --- Status Snapshot Queries ---
+
+/// Pending, processing and on-hold counts with the oldest pending creation time
+pub async fn get_transaction_backlog<'e, E>(executor: E) -> Result<TransactionBacklog>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, TransactionBacklog>(
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE status = 'pending') AS pending,
+            COUNT(*) FILTER (WHERE status IN ('processing', 'submitted')) AS processing,
+            COUNT(*) FILTER (WHERE status = 'pending_trustline') AS on_hold,
+            MIN(created_at) FILTER (WHERE status = 'pending') AS oldest_pending_at
+        FROM transactions
+        WHERE status IN ('pending', 'processing', 'submitted', 'pending_trustline')
+        "#
+    )
+    .fetch_one(executor)
+    .await
+}
+
+/// Export jobs waiting for or holding a worker
+pub async fn count_export_queue<'e, E>(executor: E) -> Result<i64>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_scalar("SELECT COUNT(*) FROM export_jobs WHERE status IN ('pending', 'running')")
+        .fetch_one(executor)
+        .await
+}
+
+/// Outbound webhook deliveries not yet delivered or given up on
+pub async fn count_webhook_backlog<'e, E>(executor: E) -> Result<i64>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_scalar(
+        "SELECT COUNT(*) FROM webhook_deliveries WHERE status IN ('pending', 'delivering')",
+    )
+    .fetch_one(executor)
+    .await
+}
+
+// --- Ingestion Outbox Queries ---
+
+/// Queue a callback's transaction under its final id. Returns `None` if a
+/// callback with the same anchor transaction id is already queued.
+pub async fn enqueue_ingestion<'e, E>(
+    executor: E,
+    tx: &Transaction,
+    schema_version: Option<&str>,
+    raw_body: Option<&str>,
+) -> Result<Option<Uuid>>
+where
+    E: PgExecutor<'e>,
+{
+    let payload = serde_json::to_value(tx).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO ingestion_outbox (
+            id, anchor_transaction_id, payload, schema_version, raw_body, created_at
+        ) VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (anchor_transaction_id) WHERE anchor_transaction_id IS NOT NULL DO NOTHING
+        RETURNING id
+        "#
+    )
+    .bind(tx.id)
+    .bind(&tx.anchor_transaction_id)
+    .bind(payload)
+    .bind(schema_version)
+    .bind(raw_body)
+    .bind(tx.created_at)
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn get_ingestion_entry<'e, E>(executor: E, id: Uuid) -> Result<Option<IngestionOutboxEntry>>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, IngestionOutboxEntry>("SELECT * FROM ingestion_outbox WHERE id = $1")
+        .bind(id)
+        .fetch_optional(executor)
+        .await
+}
+
+pub async fn get_ingestion_entry_by_anchor_id<'e, E>(
+    executor: E,
+    anchor_transaction_id: &str,
+) -> Result<Option<IngestionOutboxEntry>>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, IngestionOutboxEntry>(
+        "SELECT * FROM ingestion_outbox WHERE anchor_transaction_id = $1",
+    )
+    .bind(anchor_transaction_id)
+    .fetch_optional(executor)
+    .await
+}
+
+/// Lock up to `limit` due entries, oldest first. Entries locked by another
+/// drainer are skipped.
+pub async fn claim_ingestion_batch(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> Result<Vec<IngestionOutboxEntry>> {
+    sqlx::query_as::<_, IngestionOutboxEntry>(
+        r#"
+        SELECT * FROM ingestion_outbox
+        WHERE status = 'pending' AND next_attempt_at <= NOW()
+        ORDER BY created_at
+        LIMIT $1
+        FOR UPDATE SKIP LOCKED
+        "#
+    )
+    .bind(limit)
+    .fetch_all(conn)
+    .await
+}
+
+pub async fn delete_ingestion_entry<'e, E>(executor: E, id: Uuid) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query("DELETE FROM ingestion_outbox WHERE id = $1")
+        .bind(id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Record a failed attempt: retried at `retry_at`, or marked `failed` for good
+/// when `retry_at` is `None`
+pub async fn record_ingestion_failure<'e, E>(
+    executor: E,
+    id: Uuid,
+    error: &str,
+    retry_at: Option<DateTime<Utc>>,
+) -> Result<()>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query(
+        r#"
+        UPDATE ingestion_outbox
+        SET attempts = attempts + 1,
+            last_error = $2,
+            status = CASE WHEN $3::timestamptz IS NULL THEN 'failed' ELSE status END,
+            next_attempt_at = COALESCE($3, next_attempt_at)
+        WHERE id = $1
+        "#
+    )
+    .bind(id)
+    .bind(error)
+    .bind(retry_at)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,205 @@
+//! Opt-in EXPLAIN capture for slow queries.
+//!
+//! When enabled, a query that exceeds the slow threshold is re-run as
+//! `EXPLAIN (ANALYZE false, FORMAT JSON)` with the same bind parameters in a
+//! background task, and the plan is logged at warn level with the request id.
+//!
+//! The capture is installed once at startup with [`QueryPlanCapture::install`].
+//! Queries serving requests in [`crate::db::queries`] report to it through
+//! [`observe`], which does nothing while no capture is installed.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::middleware::request_logger::current_request_id;
+
+/// EXPLAIN re-executions are cut off after this long
+const EXPLAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Columns holding customer data. Query plans embed bound values, so plans of
+/// queries binding these columns are never captured.
+pub const SENSITIVE_COLUMNS: &[&str] = &["stellar_account", "anchor_transaction_id"];
+
+static INSTALLED: OnceLock<QueryPlanCapture> = OnceLock::new();
+
+/// Static description of a repository query, used to decide whether its plan
+/// may be captured.
+#[derive(Debug, Clone, Copy)]
+pub struct QueryMeta {
+    /// Stable name used in logs and the `query_plans_captured_total` metric
+    pub name: &'static str,
+    pub sql: &'static str,
+    /// Columns the bind parameters are compared against or written to
+    pub bound_columns: &'static [&'static str],
+}
+
+impl QueryMeta {
+    /// Whether any bind parameter touches one of the given sensitive columns.
+    /// Plans embed bound values in filter conditions, so those are never captured.
+    pub fn touches_sensitive(&self, sensitive_columns: &[&str]) -> bool {
+        self.bound_columns
+            .iter()
+            .any(|column| sensitive_columns.contains(column))
+    }
+}
+
+/// Bind parameter replayed into the EXPLAIN statement; `None` binds NULL
+#[derive(Debug, Clone)]
+pub enum PlanParam {
+    Uuid(Option<Uuid>),
+    Int(i64),
+    Text(Option<String>),
+    Timestamp(Option<DateTime<Utc>>),
+}
+
+#[derive(Clone)]
+pub struct QueryPlanCapture {
+    pool: PgPool,
+    slow_threshold: Duration,
+    explain_timeout: Duration,
+    sensitive_columns: &'static [&'static str],
+}
+
+impl QueryPlanCapture {
+    pub fn new(
+        pool: PgPool,
+        slow_threshold: Duration,
+        explain_timeout: Duration,
+        sensitive_columns: &'static [&'static str],
+    ) -> Self {
+        Self {
+            pool,
+            slow_threshold,
+            explain_timeout,
+            sensitive_columns,
+        }
+    }
+
+    /// Build the capture if `CAPTURE_QUERY_PLANS` is on. Config parsing already
+    /// refuses the flag in production.
+    pub fn from_config(
+        pool: PgPool,
+        config: &Config,
+        sensitive_columns: &'static [&'static str],
+    ) -> Option<Self> {
+        config.capture_query_plans.then(|| {
+            Self::new(
+                pool,
+                Duration::from_millis(config.slow_query_threshold_ms),
+                EXPLAIN_TIMEOUT,
+                sensitive_columns,
+            )
+        })
+    }
+
+    /// Make this the capture [`observe`] reports to. Called once at startup;
+    /// a second capture is ignored.
+    pub fn install(self) {
+        if INSTALLED.set(self).is_err() {
+            tracing::warn!("A query plan capture is already installed; keeping the first one");
+        }
+    }
+
+    /// Called after a query finished; captures its plan if it was slow.
+    pub fn observe(&self, meta: &'static QueryMeta, started: Instant, params: Vec<PlanParam>) {
+        let elapsed = started.elapsed();
+        if elapsed < self.slow_threshold || meta.touches_sensitive(self.sensitive_columns) {
+            return;
+        }
+
+        // Read the request id now; the spawned task is outside the request scope
+        let request_id = current_request_id();
+        let capture = self.clone();
+        tokio::spawn(async move {
+            let result = tokio::time::timeout(capture.explain_timeout, capture.explain(meta, params)).await;
+            match result {
+                Ok(Ok(plan)) => {
+                    crate::metrics::record_query_plan_captured(meta.name);
+                    tracing::warn!(
+                        request_id = request_id.as_deref().unwrap_or("-"),
+                        query = meta.name,
+                        elapsed_ms = elapsed.as_millis() as u64,
+                        plan = %plan,
+                        "Slow query plan captured"
+                    );
+                }
+                Ok(Err(e)) => {
+                    tracing::debug!(query = meta.name, "Failed to capture query plan: {}", e);
+                }
+                Err(_) => {
+                    tracing::debug!(query = meta.name, "Query plan capture timed out");
+                }
+            }
+        });
+    }
+
+    async fn explain(&self, meta: &QueryMeta, params: Vec<PlanParam>) -> sqlx::Result<serde_json::Value> {
+        let statement = format!("EXPLAIN (ANALYZE false, FORMAT JSON) {}", meta.sql);
+
+        let mut db_tx = self.pool.begin().await?;
+        // Server-side guard in addition to the client-side timeout
+        sqlx::query(&format!(
+            "SET LOCAL statement_timeout = {}",
+            self.explain_timeout.as_millis()
+        ))
+        .execute(&mut *db_tx)
+        .await?;
+
+        let mut query = sqlx::query_scalar::<_, serde_json::Value>(&statement);
+        for param in params {
+            query = match param {
+                PlanParam::Uuid(v) => query.bind(v),
+                PlanParam::Int(v) => query.bind(v),
+                PlanParam::Text(v) => query.bind(v),
+                PlanParam::Timestamp(v) => query.bind(v),
+            };
+        }
+        let plan = query.fetch_one(&mut *db_tx).await?;
+
+        db_tx.rollback().await?;
+        Ok(plan)
+    }
+}
+
+/// Report a finished query to the installed capture. `params` is only built
+/// when there is one.
+pub fn observe(
+    meta: &'static QueryMeta,
+    started: Instant,
+    params: impl FnOnce() -> Vec<PlanParam>,
+) {
+    if let Some(capture) = INSTALLED.get() {
+        capture.observe(meta, started, params());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SENSITIVE: &[&str] = &["stellar_account"];
+
+    #[test]
+    fn test_queries_binding_sensitive_columns_are_excluded() {
+        let by_account = QueryMeta {
+            name: "by_account",
+            sql: "SELECT * FROM transactions WHERE stellar_account = $1",
+            bound_columns: &["stellar_account"],
+        };
+        assert!(by_account.touches_sensitive(SENSITIVE));
+    }
+
+    #[test]
+    fn test_queries_without_sensitive_binds_are_captured() {
+        let list = QueryMeta {
+            name: "list",
+            sql: "SELECT * FROM transactions LIMIT $1 OFFSET $2",
+            bound_columns: &[],
+        };
+        assert!(!list.touches_sensitive(SENSITIVE));
+    }
+}
@@ -0,0 +1,321 @@
+//! Sandbox fixtures, loaded at startup when `APP_ENV=sandbox`.
+//!
+//! Seeding is idempotent. Seeded assets and flags are put back to the values
+//! below on every run; seeded transactions have fixed ids and are only
+//! inserted when missing. Integrators build against these values and
+//! `docs/sandbox.md` lists them, so change both together.
+
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::db::models::Asset;
+use crate::db::{queries, uow};
+use crate::stellar::sandbox::{FakeHorizon, ForcedFailure};
+
+/// Issuer of every seeded asset
+pub const SANDBOX_ISSUER: &str = "GCCPGHBCULBI3KTJ25N2HSOWPCIYPWFTXIQHBKSGN5BLGETBLWTZKNBZ";
+
+/// A documented sandbox account. Addresses are real strkeys, the ed25519
+/// key being the SHA-256 of `synapse-sandbox:<name>`, so callbacks and
+/// payouts accept them like any other account.
+#[derive(Debug, Clone, Copy)]
+pub struct SeedAccount {
+    pub address: &'static str,
+    pub description: &'static str,
+    /// What the fake Horizon does for this account instead of succeeding
+    pub failure: Option<ForcedFailure>,
+}
+
+pub const ACCOUNTS: &[SeedAccount] = &[
+    SeedAccount {
+        // alice
+        address: "GBK46SXF4VT2EAAI76U7EZUFKO4VOQNU6BRN6NBIE4EXT5OVQGJTSVVE",
+        description: "Completed and pending USDC deposits",
+        failure: None,
+    },
+    SeedAccount {
+        // bob
+        address: "GDCGP2UCOAAIBPQAQQ7VDR6XGHUSEHSKSHK3I5742RTL5DAI7SYSH3SR",
+        description: "A completed EURC deposit and a pending USDC withdrawal",
+        failure: None,
+    },
+    SeedAccount {
+        // not-found
+        address: "GBMTKZOF4HW2G7BPHQH7QE266CLCLWZVY5QSCU5WGR2CBTFHPTVJSJLB",
+        description: "Horizon reports the account as missing",
+        failure: Some(ForcedFailure::NotFound),
+    },
+    SeedAccount {
+        // no-trustline
+        address: "GARZBLTKYGZMNIQRGT3PFYAEM5DZHEFM3J6UR3G5BWZ2S5YVDU6VR3U2",
+        description: "The account exists but trusts no assets",
+        failure: Some(ForcedFailure::NoTrustline),
+    },
+    SeedAccount {
+        // submit-rejected
+        address: "GBNFYFI3HOVCX6VJHZ7CPFAMVCXTPQJRDRSH5662UME6CG2LBVOC2APO",
+        description: "Transactions from the account fail with tx_failed",
+        failure: Some(ForcedFailure::SubmitRejected),
+    },
+    SeedAccount {
+        // horizon-down
+        address: "GCA5FHZK2WNJOHHEJ5JM2DMQL37VFWXQPT3YGDYDSL7EHRCVNV7MEPOT",
+        description: "Every Horizon call for the account fails",
+        failure: Some(ForcedFailure::Unavailable),
+    },
+];
+
+/// The failure a seeded account forces; `None` for every other address
+pub fn forced_failure(address: &str) -> Option<ForcedFailure> {
+    ACCOUNTS
+        .iter()
+        .find(|account| account.address == address)
+        .and_then(|account| account.failure)
+}
+
+/// Registry assets, all issued by [`SANDBOX_ISSUER`], with their display decimals
+pub const ASSETS: &[(&str, i32)] = &[("USDC", 2), ("EURC", 2)];
+
+/// Feature flag values in the sandbox
+pub const FLAGS: &[(&str, bool)] = &[
+    ("experimental_processor", false),
+    ("new_asset_support", true),
+    ("payment_operations_listener", false),
+    ("refuse_unverified_asset_deposits", true),
+    ("trustline_effects_listener", false),
+];
+
+/// A seeded transaction; `key` makes its id and anchor transaction id
+#[derive(Debug, Clone, Copy)]
+pub struct SeedTransaction {
+    pub key: &'static str,
+    pub account: &'static str,
+    pub amount: &'static str,
+    pub asset_code: &'static str,
+    pub callback_type: &'static str,
+    pub status: &'static str,
+}
+
+pub const TRANSACTIONS: &[SeedTransaction] = &[
+    SeedTransaction {
+        key: "alice-deposit-1",
+        account: ACCOUNTS[0].address,
+        amount: "250.00",
+        asset_code: "USDC",
+        callback_type: "deposit",
+        status: "completed",
+    },
+    SeedTransaction {
+        key: "alice-deposit-2",
+        account: ACCOUNTS[0].address,
+        amount: "75.50",
+        asset_code: "USDC",
+        callback_type: "deposit",
+        status: "pending",
+    },
+    SeedTransaction {
+        key: "bob-deposit-1",
+        account: ACCOUNTS[1].address,
+        amount: "1000.00",
+        asset_code: "EURC",
+        callback_type: "deposit",
+        status: "completed",
+    },
+    SeedTransaction {
+        key: "bob-withdrawal-1",
+        account: ACCOUNTS[1].address,
+        amount: "40.00",
+        asset_code: "USDC",
+        callback_type: "withdrawal",
+        status: "pending",
+    },
+];
+
+/// Stable id of a seeded transaction
+pub fn transaction_id(key: &str) -> Uuid {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, format!("sandbox:transaction:{}", key).as_bytes())
+}
+
+/// Data tables [`reset`] empties. Audit logs, tokens, subscriptions and
+/// templates are configuration an integrator set up, and stay.
+const RESET_TABLES: &[&str] = &[
+    "transactions",
+    "anchor_transaction_ids",
+    "transaction_dlq",
+    "settlements",
+    "raw_callbacks",
+    "ingestion_outbox",
+    "webhook_deliveries",
+    "outbox_events",
+    "quotes",
+    "account_stats",
+    "export_jobs",
+    "processed_effects",
+    "processed_operations",
+    "stream_cursors",
+    "erasure_jobs",
+    "account_watchlist",
+];
+
+/// Rows a seeding run wrote
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct SeedReport {
+    pub assets: usize,
+    pub flags: usize,
+    /// Transactions inserted; existing ones are left alone
+    pub transactions: usize,
+}
+
+/// Load the fixtures
+pub async fn run(pool: &PgPool) -> sqlx::Result<SeedReport> {
+    uow::run(pool, |uow| Box::pin(async move { seed(uow.conn()).await })).await
+}
+
+/// Empty the data tables and load the fixtures again. Sandbox only.
+pub async fn reset(pool: &PgPool) -> sqlx::Result<SeedReport> {
+    uow::run(pool, |uow| {
+        Box::pin(async move {
+            sqlx::query(&format!("TRUNCATE {} CASCADE", RESET_TABLES.join(", ")))
+                .execute(&mut *uow.conn())
+                .await?;
+            seed(uow.conn()).await
+        })
+    })
+    .await
+}
+
+/// Make every account in `fake` trust the enabled registry assets
+pub async fn sync_trustlines(pool: &PgPool, fake: &FakeHorizon) -> sqlx::Result<()> {
+    let assets = Asset::fetch_all(pool).await?;
+    fake.set_assets(assets.into_iter().map(|asset| (asset.asset_code, asset.asset_issuer)));
+    Ok(())
+}
+
+async fn seed(conn: &mut PgConnection) -> sqlx::Result<SeedReport> {
+    let mut report = SeedReport::default();
+
+    for (code, decimals) in ASSETS {
+        sqlx::query(
+            r#"
+            INSERT INTO assets (asset_code, asset_issuer, metadata, enabled, verification_status, verified_at)
+            VALUES ($1, $2, $3, TRUE, 'verified', NOW())
+            ON CONFLICT (asset_code, asset_issuer) DO UPDATE SET
+                metadata = EXCLUDED.metadata,
+                enabled = TRUE,
+                verification_status = 'verified',
+                verification_error = NULL,
+                verified_at = NOW(),
+                updated_at = NOW()
+            "#,
+        )
+        .bind(code)
+        .bind(SANDBOX_ISSUER)
+        .bind(serde_json::json!({ "decimals": decimals, "sandbox": true }))
+        .execute(&mut *conn)
+        .await?;
+        report.assets += 1;
+    }
+
+    for (name, enabled) in FLAGS {
+        sqlx::query(
+            r#"
+            INSERT INTO feature_flags (name, enabled, description)
+            VALUES ($1, $2, 'Seeded by sandbox mode')
+            ON CONFLICT (name) DO UPDATE SET enabled = EXCLUDED.enabled, updated_at = NOW()
+            "#,
+        )
+        .bind(name)
+        .bind(enabled)
+        .execute(&mut *conn)
+        .await?;
+        report.flags += 1;
+    }
+
+    for seeded in TRANSACTIONS {
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO transactions (
+                id, stellar_account, amount, asset_code, asset_issuer, status,
+                anchor_transaction_id, callback_type, callback_status
+            )
+            SELECT $1, $2, $3::numeric, $4, $8, $5, $6, $7, $5
+            WHERE NOT EXISTS (SELECT 1 FROM transactions WHERE id = $1)
+            "#,
+        )
+        .bind(transaction_id(seeded.key))
+        .bind(seeded.account)
+        .bind(seeded.amount)
+        .bind(seeded.asset_code)
+        .bind(seeded.status)
+        .bind(format!("sandbox-{}", seeded.key))
+        .bind(seeded.callback_type)
+        .bind(SANDBOX_ISSUER)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        report.transactions += inserted as usize;
+    }
+
+    for account in ACCOUNTS {
+        queries::recompute_account_stats(conn, account.address).await?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stellar::strkey;
+    use crate::validation::validate_stellar_address;
+    use sha2::{Digest, Sha256};
+
+    #[test]
+    fn test_addresses_pass_validation() {
+        validate_stellar_address(SANDBOX_ISSUER).unwrap();
+        strkey::validate_account(SANDBOX_ISSUER).unwrap();
+        for account in ACCOUNTS {
+            validate_stellar_address(account.address).unwrap();
+            strkey::validate_account(account.address).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_addresses_derive_from_their_names() {
+        let names = ["alice", "bob", "not-found", "no-trustline", "submit-rejected", "horizon-down"];
+        let derive = |name: &str| {
+            let key: [u8; 32] = Sha256::digest(format!("synapse-sandbox:{}", name)).into();
+            strkey::encode_ed25519(&key)
+        };
+        assert_eq!(derive("issuer"), SANDBOX_ISSUER);
+        for (account, name) in ACCOUNTS.iter().zip(names) {
+            assert_eq!(account.address, derive(name));
+        }
+    }
+
+    #[test]
+    fn test_failure_accounts_force_their_failure() {
+        let forced: Vec<_> = ACCOUNTS
+            .iter()
+            .filter_map(|account| ForcedFailure::for_account(account.address))
+            .collect();
+        assert_eq!(
+            forced,
+            vec![
+                ForcedFailure::NotFound,
+                ForcedFailure::NoTrustline,
+                ForcedFailure::SubmitRejected,
+                ForcedFailure::Unavailable,
+            ]
+        );
+        assert_eq!(ForcedFailure::for_account(SANDBOX_ISSUER), None);
+    }
+
+    #[test]
+    fn test_seeded_transactions_use_seeded_accounts_and_assets() {
+        for seeded in TRANSACTIONS {
+            assert!(ACCOUNTS.iter().any(|account| account.address == seeded.account));
+            assert!(ASSETS.iter().any(|(code, _)| *code == seeded.asset_code));
+        }
+        assert_ne!(transaction_id("alice-deposit-1"), transaction_id("alice-deposit-2"));
+    }
+}
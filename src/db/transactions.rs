@@ -0,0 +1,68 @@
+//! Transaction queries for the admin API. They return
+//! [`TransactionDetail`], which carries the review, payout and metadata
+//! columns the public endpoints leave out.
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool, Result};
+use uuid::Uuid;
+
+use crate::db::models::TransactionDetail;
+use crate::db::queries::TransactionListFilter;
+
+/// Up to `limit` filtered transactions, newest first, starting after
+/// `cursor`; paged like [`crate::db::queries::list_transactions_page`]
+pub async fn list(
+    pool: &PgPool,
+    filter: &TransactionListFilter<'_>,
+    cursor: Option<(DateTime<Utc>, Uuid)>,
+    limit: i64,
+) -> Result<Vec<TransactionDetail>> {
+    let (cursor_ts, cursor_id) = cursor.unzip();
+    sqlx::query_as::<_, TransactionDetail>(
+        r#"
+        SELECT * FROM transactions
+        WHERE ($1::timestamptz IS NULL OR (created_at, id) < ($1, $2))
+        AND ($3::varchar IS NULL OR stellar_account = $3)
+        AND ($4::varchar IS NULL OR status = $4)
+        AND ($5::varchar IS NULL OR asset_code = $5)
+        AND ($6::timestamptz IS NULL OR created_at >= $6)
+        AND ($7::timestamptz IS NULL OR created_at < $7)
+        ORDER BY created_at DESC, id DESC
+        LIMIT $8
+        "#,
+    )
+    .bind(cursor_ts)
+    .bind(cursor_id)
+    .bind(filter.stellar_account)
+    .bind(filter.status)
+    .bind(filter.asset_code)
+    .bind(filter.from)
+    .bind(filter.until)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get(pool: &PgPool, id: Uuid) -> Result<Option<TransactionDetail>> {
+    sqlx::query_as::<_, TransactionDetail>("SELECT * FROM transactions WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Make a transaction claimable by the payment processor's next pass: drop
+/// its claim and any payment signed for it
+pub async fn release_payout(conn: &mut PgConnection, id: Uuid) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE transactions
+        SET payout_after = NULL, payout_tx_hash = NULL, payout_envelope = NULL,
+            payout_valid_until = NULL, updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,118 @@
+//! Unit of work: one database transaction around a multi-step operation.
+//!
+//! Handlers that write more than one row (transaction + audit + outbox) run
+//! their statements through a [`UnitOfWork`] so they either all apply or none
+//! do. Optional sub-steps run in a savepoint and can fail on their own.
+
+use futures::future::BoxFuture;
+use sqlx::{Connection, PgConnection, PgPool, Postgres, Transaction};
+
+/// An open database transaction
+pub struct UnitOfWork<'c> {
+    tx: Transaction<'c, Postgres>,
+}
+
+impl<'c> UnitOfWork<'c> {
+    pub async fn begin(pool: &PgPool) -> sqlx::Result<Self> {
+        Ok(Self { tx: pool.begin().await? })
+    }
+
+    /// Connection for running statements inside the unit of work
+    pub fn conn(&mut self) -> &mut PgConnection {
+        &mut *self.tx
+    }
+
+    pub async fn commit(self) -> sqlx::Result<()> {
+        self.tx.commit().await
+    }
+
+    pub async fn rollback(self) -> sqlx::Result<()> {
+        self.tx.rollback().await
+    }
+
+    /// Run a sub-step in a savepoint. On error only the sub-step's statements
+    /// are rolled back; the unit of work stays usable and the error is
+    /// returned for the caller to handle.
+    pub async fn savepoint<'b, T, E, F>(&'b mut self, f: F) -> Result<T, E>
+    where
+        F: for<'s> FnOnce(&'s mut UnitOfWork<'b>) -> BoxFuture<'s, Result<T, E>>,
+        E: From<sqlx::Error>,
+    {
+        // `begin` on a connection that is already in a transaction issues SAVEPOINT
+        let mut nested = UnitOfWork {
+            tx: self.tx.begin().await?,
+        };
+        match f(&mut nested).await {
+            Ok(value) => {
+                nested.commit().await?;
+                Ok(value)
+            }
+            Err(e) => {
+                nested.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Run `f` in a unit of work: commit if it returns `Ok`, roll back otherwise.
+///
+/// ```ignore
+/// let tx = uow::run(&pool, |uow| Box::pin(async move {
+///     let tx = queries::insert_transaction(uow.conn(), &tx).await?;
+///     AuditLog::log_creation(uow.conn(), tx.id, ENTITY_TRANSACTION, json!(tx), "system").await?;
+///     Ok::<_, AppError>(tx)
+/// })).await?;
+/// ```
+///
+/// The unit of work's lifetime parameter lets the closure borrow from the
+/// caller's scope: `&'u mut UnitOfWork<'a>` implies `'a: 'u`.
+///
+/// Runs in a `db.transaction` span, which the statements' `sqlx::query` log
+/// events are recorded on.
+#[tracing::instrument(name = "db.transaction", skip_all)]
+pub async fn run<'a, T, E, F>(pool: &'a PgPool, f: F) -> Result<T, E>
+where
+    F: for<'u> FnOnce(&'u mut UnitOfWork<'a>) -> BoxFuture<'u, Result<T, E>>,
+    E: From<sqlx::Error>,
+{
+    let mut uow = UnitOfWork::begin(pool).await?;
+    match f(&mut uow).await {
+        Ok(value) => {
+            uow.commit().await?;
+            Ok(value)
+        }
+        Err(e) => {
+            // Dropping would also roll back; doing it explicitly surfaces errors in logs
+            if let Err(rollback_error) = uow.rollback().await {
+                tracing::error!("Unit of work rollback failed: {}", rollback_error);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Either the pool or an open unit of work, for repository methods that can
+/// run standalone or as part of a larger transaction.
+pub enum DbConn<'a> {
+    Pool(&'a PgPool),
+    Conn(&'a mut PgConnection),
+}
+
+impl<'a> From<&'a PgPool> for DbConn<'a> {
+    fn from(pool: &'a PgPool) -> Self {
+        DbConn::Pool(pool)
+    }
+}
+
+impl<'a> From<&'a mut PgConnection> for DbConn<'a> {
+    fn from(conn: &'a mut PgConnection) -> Self {
+        DbConn::Conn(conn)
+    }
+}
+
+impl<'a, 'c> From<&'a mut UnitOfWork<'c>> for DbConn<'a> {
+    fn from(uow: &'a mut UnitOfWork<'c>) -> Self {
+        DbConn::Conn(uow.conn())
+    }
+}
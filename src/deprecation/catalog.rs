@@ -0,0 +1,34 @@
+//! Every active deprecation. Entries stay here until the element is removed;
+//! `deprecated_usage_total{element}` shows when that is safe.
+//!
+//! The dates here are defaults; `DEPRECATION_DATES` and `DEPRECATION_SUNSETS`
+//! replace them per element (see [`super::Schedule`]).
+
+use super::{Declared, Deprecation, ElementKind};
+
+/// `POST /callback/transaction`, the original single-schema callback
+pub static LEGACY_TRANSACTION_CALLBACK: Deprecation = Deprecation {
+    element: "POST /callback/transaction",
+    kind: ElementKind::Route,
+    replacement: Some("POST /callback"),
+    deprecated: "2026-02-21",
+    sunset: Some("2027-03-31"),
+};
+
+/// The flat `asset_code` of version 1 callbacks
+pub static CALLBACK_V1_ASSET_CODE: Deprecation = Deprecation {
+    element: "callback.v1.asset_code",
+    kind: ElementKind::Field,
+    replacement: Some("callback.v2.amount_in.asset_code (X-Callback-Schema-Version: 2)"),
+    deprecated: "2026-02-21",
+    sunset: Some("2027-06-30"),
+};
+
+pub struct CallbackV1AssetCode;
+
+impl Declared for CallbackV1AssetCode {
+    const DEPRECATION: &'static Deprecation = &CALLBACK_V1_ASSET_CODE;
+}
+
+/// Listed at `GET /.well-known/synapse/deprecations`
+pub static ALL: &[&Deprecation] = &[&LEGACY_TRANSACTION_CALLBACK, &CALLBACK_V1_ASSET_CODE];
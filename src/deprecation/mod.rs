@@ -0,0 +1,411 @@
+//! Machine-readable deprecation signaling.
+//!
+//! Deprecations are declared once in [`catalog`] and attached where the
+//! deprecated element lives:
+//!
+//! ```ignore
+//! // a route
+//! .route("/old", post(handler).layer(deprecation::route(&catalog::OLD_ROUTE)))
+//!
+//! // a request field
+//! #[serde(deserialize_with = "deprecation::deserialize::<_, _, catalog::OldField>")]
+//! pub old_field: String,
+//! ```
+//!
+//! Using a deprecated element records a [`notice`]. The [`signal`] middleware
+//! turns the notices of a request into `Deprecation`, `Sunset` and `Link`
+//! response headers and a `deprecations` array in JSON object bodies, and
+//! every notice is counted in `deprecated_usage_total`.
+//!
+//! The catalog's dates are defaults. `DEPRECATION_DATES` and
+//! `DEPRECATION_SUNSETS` override them per element at startup; see
+//! [`Schedule`].
+
+pub mod catalog;
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::NaiveDate;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+
+use crate::metrics;
+
+/// Where active deprecations are listed; also sent as the `Link` target
+pub const DEPRECATIONS_PATH: &str = "/.well-known/synapse/deprecations";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ElementKind {
+    Route,
+    Field,
+}
+
+/// One deprecated route or field. Serialized with the dates in effect, so
+/// configured overrides show in responses and in the listing.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Deprecation {
+    /// Stable name of the element, also the metric label
+    pub element: &'static str,
+    pub kind: ElementKind,
+    /// What integrators should use instead
+    pub replacement: Option<&'static str>,
+    /// Date the element was deprecated, `YYYY-MM-DD`; default for
+    /// `DEPRECATION_DATES`
+    pub deprecated: &'static str,
+    /// Date after which the element may be removed, `YYYY-MM-DD`; default
+    /// for `DEPRECATION_SUNSETS`
+    pub sunset: Option<&'static str>,
+}
+
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+impl Deprecation {
+    /// Deprecation date in effect
+    pub fn deprecated_on(&self) -> Option<NaiveDate> {
+        schedule().deprecated_on(self)
+    }
+
+    /// Sunset date in effect
+    pub fn sunset_on(&self) -> Option<NaiveDate> {
+        schedule().sunset_on(self)
+    }
+
+    /// `Deprecation` header value (RFC 9745): `@<unix seconds>`
+    pub fn deprecation_header(&self) -> Option<String> {
+        let date = self.deprecated_on()?;
+        Some(format!("@{}", date.and_hms_opt(0, 0, 0)?.and_utc().timestamp()))
+    }
+
+    /// `Sunset` header value (RFC 8594): an HTTP-date
+    pub fn sunset_header(&self) -> Option<String> {
+        let date = self.sunset_on()?;
+        Some(date.format("%a, %d %b %Y 00:00:00 GMT").to_string())
+    }
+}
+
+impl Serialize for Deprecation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Deprecation", 5)?;
+        state.serialize_field("element", self.element)?;
+        state.serialize_field("kind", &self.kind)?;
+        state.serialize_field("replacement", &self.replacement)?;
+        state.serialize_field("deprecated", &self.deprecated_on().map(|d| d.to_string()))?;
+        state.serialize_field("sunset", &self.sunset_on().map(|d| d.to_string()))?;
+        state.end()
+    }
+}
+
+/// Configured dates replacing the catalog's, keyed by element
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Schedule {
+    /// From `DEPRECATION_DATES`
+    pub deprecated: HashMap<String, NaiveDate>,
+    /// From `DEPRECATION_SUNSETS`
+    pub sunset: HashMap<String, NaiveDate>,
+}
+
+impl Schedule {
+    /// `DEPRECATION_DATES` and `DEPRECATION_SUNSETS`: comma-separated
+    /// `element=YYYY-MM-DD`, e.g.
+    /// `callback.v1.asset_code=2027-09-30,POST /callback/transaction=2027-06-30`.
+    /// Elements missing from the catalog are rejected.
+    pub fn parse(dates: &str, sunsets: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            deprecated: parse_dates("DEPRECATION_DATES", dates)?,
+            sunset: parse_dates("DEPRECATION_SUNSETS", sunsets)?,
+        })
+    }
+
+    fn deprecated_on(&self, deprecation: &Deprecation) -> Option<NaiveDate> {
+        match self.deprecated.get(deprecation.element) {
+            Some(date) => Some(*date),
+            None => parse_date(deprecation.deprecated),
+        }
+    }
+
+    fn sunset_on(&self, deprecation: &Deprecation) -> Option<NaiveDate> {
+        match self.sunset.get(deprecation.element) {
+            Some(date) => Some(*date),
+            None => parse_date(deprecation.sunset?),
+        }
+    }
+}
+
+fn parse_dates(var: &str, raw: &str) -> anyhow::Result<HashMap<String, NaiveDate>> {
+    let mut dates = HashMap::new();
+    for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        // Route elements contain spaces and slashes but never `=`
+        let (element, date) = entry.rsplit_once('=').ok_or_else(|| {
+            anyhow::anyhow!("{}: expected element=YYYY-MM-DD, got '{}'", var, entry)
+        })?;
+        let element = element.trim();
+        if !catalog::ALL.iter().any(|d| d.element == element) {
+            anyhow::bail!("{}: unknown element '{}'", var, element);
+        }
+        let date = parse_date(date.trim())
+            .ok_or_else(|| anyhow::anyhow!("{}: '{}' is not a YYYY-MM-DD date", var, date.trim()))?;
+        dates.insert(element.to_string(), date);
+    }
+    Ok(dates)
+}
+
+static SCHEDULE: OnceLock<Schedule> = OnceLock::new();
+
+fn schedule() -> &'static Schedule {
+    SCHEDULE.get_or_init(Schedule::default)
+}
+
+/// Apply the configured dates. Called once at startup, before serving;
+/// without it the catalog's dates apply.
+pub fn configure(schedule: Schedule) {
+    if SCHEDULE.set(schedule).is_err() {
+        tracing::warn!("Deprecation schedule was already in use; keeping the first one");
+    }
+}
+
+tokio::task_local! {
+    static NOTICES: RefCell<Vec<&'static Deprecation>>;
+}
+
+/// Record a use of a deprecated element by the current request
+pub fn notice(deprecation: &'static Deprecation) {
+    metrics::record_deprecated_usage(deprecation.element);
+    // Outside a `collect` scope (background work, tests) only the metric applies
+    let _ = NOTICES.try_with(|notices| {
+        let mut notices = notices.borrow_mut();
+        if !notices.iter().any(|seen| seen.element == deprecation.element) {
+            notices.push(deprecation);
+        }
+    });
+}
+
+/// Run `f`, returning the deprecations it noticed
+pub async fn collect<F: Future>(f: F) -> (F::Output, Vec<&'static Deprecation>) {
+    NOTICES
+        .scope(RefCell::new(Vec::new()), async move {
+            let output = f.await;
+            (output, NOTICES.with(|notices| notices.take()))
+        })
+        .await
+}
+
+/// Marker type tying a field to its catalog entry, for [`deserialize`] and
+/// [`serialize`]
+pub trait Declared {
+    const DEPRECATION: &'static Deprecation;
+}
+
+/// `deserialize_with` for a deprecated request field: notices the field when
+/// it is present
+pub fn deserialize<'de, D, T, M>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+    M: Declared,
+{
+    notice(M::DEPRECATION);
+    T::deserialize(deserializer)
+}
+
+/// `serialize_with` for a deprecated response field
+pub fn serialize<S, T, M>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize,
+    M: Declared,
+{
+    notice(M::DEPRECATION);
+    value.serialize(serializer)
+}
+
+/// Layer marking a single route deprecated, for `MethodRouter::layer`
+pub fn route(deprecation: &'static Deprecation) -> DeprecatedRoute {
+    DeprecatedRoute(deprecation)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DeprecatedRoute(&'static Deprecation);
+
+impl<S> tower::Layer<S> for DeprecatedRoute {
+    type Service = DeprecatedRouteService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DeprecatedRouteService {
+            inner,
+            deprecation: self.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeprecatedRouteService<S> {
+    inner: S,
+    deprecation: &'static Deprecation,
+}
+
+impl<S, B> tower::Service<axum::http::Request<B>> for DeprecatedRouteService<S>
+where
+    S: tower::Service<axum::http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: axum::http::Request<B>) -> Self::Future {
+        notice(self.deprecation);
+        self.inner.call(request)
+    }
+}
+
+/// Middleware signaling the deprecations a request used. Mounted once around
+/// the whole router.
+pub async fn signal(req: Request, next: Next) -> Response {
+    let (response, notices) = collect(next.run(req)).await;
+    if notices.is_empty() {
+        return response;
+    }
+    annotate(response, &notices).await
+}
+
+async fn annotate(response: Response, notices: &[&'static Deprecation]) -> Response {
+    let (mut parts, body) = response.into_parts();
+
+    // One value per header: the earliest deprecation and sunset apply
+    let deprecation = notices.iter().filter_map(|d| d.deprecation_header()).min();
+    let sunset = notices
+        .iter()
+        .filter_map(|d| Some((d.sunset_on()?, d.sunset_header()?)))
+        .min();
+    if let Some(value) = deprecation.and_then(|v| HeaderValue::from_str(&v).ok()) {
+        parts.headers.insert("deprecation", value);
+    }
+    if let Some(value) = sunset.and_then(|(_, v)| HeaderValue::from_str(&v).ok()) {
+        parts.headers.insert("sunset", value);
+    }
+    let link = format!("<{}>; rel=\"deprecation\"", DEPRECATIONS_PATH);
+    if let Ok(link) = HeaderValue::from_str(&link) {
+        parts.headers.append(header::LINK, link);
+    }
+
+    let is_json = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !is_json {
+        return Response::from_parts(parts, body);
+    }
+
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("Failed to buffer response for deprecation notices: {}", e);
+            return axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    // Only objects can carry the array; other bodies get the headers alone
+    let bytes = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(serde_json::Value::Object(mut object)) => {
+            object.insert("deprecations".to_string(), serde_json::json!(notices));
+            serde_json::to_vec(&object).map(Into::into).unwrap_or(bytes)
+        }
+        _ => bytes,
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Every active deprecation: `GET /.well-known/synapse/deprecations`
+pub async fn list_deprecations() -> impl IntoResponse {
+    Json(serde_json::json!({ "deprecations": catalog::ALL }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static OLD: Deprecation = Deprecation {
+        element: "test.old",
+        kind: ElementKind::Field,
+        replacement: Some("test.new"),
+        deprecated: "2026-01-01",
+        sunset: Some("2026-07-01"),
+    };
+
+    #[test]
+    fn test_header_values() {
+        assert_eq!(OLD.deprecation_header().as_deref(), Some("@1767225600"));
+        assert_eq!(OLD.sunset_header().as_deref(), Some("Wed, 01 Jul 2026 00:00:00 GMT"));
+    }
+
+    #[test]
+    fn test_catalog_dates_parse() {
+        for deprecation in catalog::ALL {
+            assert!(deprecation.deprecation_header().is_some(), "{}", deprecation.element);
+            if deprecation.sunset.is_some() {
+                assert!(deprecation.sunset_header().is_some(), "{}", deprecation.element);
+            }
+        }
+    }
+
+    #[test]
+    fn test_schedule_overrides_catalog_dates() {
+        let schedule = Schedule::parse(
+            "",
+            "callback.v1.asset_code=2027-09-30, POST /callback/transaction = 2027-06-30",
+        )
+        .unwrap();
+        let date = |d: &str| parse_date(d).unwrap();
+        assert_eq!(
+            schedule.sunset_on(&catalog::CALLBACK_V1_ASSET_CODE),
+            Some(date("2027-09-30"))
+        );
+        assert_eq!(
+            schedule.sunset_on(&catalog::LEGACY_TRANSACTION_CALLBACK),
+            Some(date("2027-06-30"))
+        );
+        // Not configured: the catalog's date
+        assert_eq!(
+            schedule.deprecated_on(&catalog::CALLBACK_V1_ASSET_CODE),
+            Some(date("2026-02-21"))
+        );
+        assert_eq!(Schedule::parse("", "").unwrap().sunset_on(&OLD), Some(date("2026-07-01")));
+    }
+
+    #[test]
+    fn test_schedule_rejects_unknown_elements_and_bad_dates() {
+        assert!(Schedule::parse("test.missing=2026-01-01", "").is_err());
+        assert!(Schedule::parse("", "callback.v1.asset_code=next year").is_err());
+        assert!(Schedule::parse("", "callback.v1.asset_code").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_collect_deduplicates_notices() {
+        let ((), notices) = collect(async {
+            notice(&OLD);
+            notice(&OLD);
+        })
+        .await;
+        assert_eq!(notices, vec![&OLD]);
+
+        // Outside a scope a notice is only counted
+        notice(&OLD);
+    }
+}
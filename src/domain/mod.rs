@@ -1,6 +1,8 @@
 //! Domain layer: core business entities.
 //! No external dependencies (database, HTTP, etc.).
 
+pub mod status;
 pub mod transaction;
 
+pub use status::{TransactionStatus, UnknownStatus};
 pub use transaction::Transaction;
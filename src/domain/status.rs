@@ -0,0 +1,277 @@
+//! Internal transaction status state machine values.
+
+use std::fmt;
+
+/// Status spellings written before statuses were canonical, and what each
+/// means now. Matched after [`normalize_legacy`], so case, surrounding
+/// whitespace and `-` or space separators don't matter.
+pub const LEGACY_ALIASES: &[(&str, TransactionStatus)] = &[
+    ("pending_anchor", TransactionStatus::Pending),
+    ("pending_user_transfer_start", TransactionStatus::Pending),
+    ("new", TransactionStatus::Pending),
+    ("received", TransactionStatus::Pending),
+    ("in_progress", TransactionStatus::Processing),
+    ("pending_stellar", TransactionStatus::Processing),
+    ("pending_external", TransactionStatus::Processing),
+    ("complete", TransactionStatus::Completed),
+    ("success", TransactionStatus::Completed),
+    ("done", TransactionStatus::Completed),
+    ("error", TransactionStatus::Failed),
+    ("failure", TransactionStatus::Failed),
+    ("dead_letter", TransactionStatus::Dlq),
+    ("pending_trust", TransactionStatus::PendingTrustline),
+];
+
+/// A stored status that is neither canonical nor a known legacy alias
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown transaction status {0:?}")]
+pub struct UnknownStatus(pub String);
+
+/// Lowercase, trimmed, with `-` and spaces as `_`
+fn normalize_legacy(raw: &str) -> String {
+    raw.trim()
+        .chars()
+        .map(|c| match c {
+            '-' | ' ' => '_',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+/// Every status a transaction row can be in.
+///
+/// Status strings are stored as text in the database; this enum is the single
+/// list of valid values. Code that maps statuses to external representations
+/// matches on it exhaustively, so adding a variant forces every mapping to be
+/// updated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransactionStatus {
+    Pending,
+    Processing,
+    /// A signed payment was sent to the network; its outcome isn't known yet
+    Submitted,
+    Completed,
+    Failed,
+    /// Moved to the dead letter queue after exhausting retries
+    Dlq,
+    /// The customer's trustline to the asset is missing or deauthorized
+    PendingTrustline,
+    /// The funds went back to the sender; never changes again
+    Refunded,
+}
+
+impl TransactionStatus {
+    pub const ALL: &'static [TransactionStatus] = &[
+        TransactionStatus::Pending,
+        TransactionStatus::Processing,
+        TransactionStatus::Submitted,
+        TransactionStatus::Completed,
+        TransactionStatus::Failed,
+        TransactionStatus::Dlq,
+        TransactionStatus::PendingTrustline,
+        TransactionStatus::Refunded,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionStatus::Pending => "pending",
+            TransactionStatus::Processing => "processing",
+            TransactionStatus::Submitted => "submitted",
+            TransactionStatus::Completed => "completed",
+            TransactionStatus::Failed => "failed",
+            TransactionStatus::Dlq => "dlq",
+            TransactionStatus::PendingTrustline => "pending_trustline",
+            TransactionStatus::Refunded => "refunded",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|status| status.as_str() == raw)
+    }
+
+    /// Read a stored status that may predate canonical statuses: a
+    /// canonical value in any case, or an entry of [`LEGACY_ALIASES`].
+    /// Anything else is an error; it is never guessed to be pending.
+    pub fn from_legacy(raw: &str) -> Result<Self, UnknownStatus> {
+        if let Some(status) = Self::parse(raw) {
+            return Ok(status);
+        }
+        let normalized = normalize_legacy(raw);
+        Self::parse(&normalized)
+            .or_else(|| {
+                LEGACY_ALIASES
+                    .iter()
+                    .find(|(alias, _)| *alias == normalized)
+                    .map(|(_, status)| *status)
+            })
+            .ok_or_else(|| UnknownStatus(raw.to_string()))
+    }
+
+    /// Completed, failed or refunded: the transaction's outcome is settled
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            TransactionStatus::Completed | TransactionStatus::Failed | TransactionStatus::Refunded
+        )
+    }
+
+    /// Whether a transaction in this status may move to `next`. Any status
+    /// may be refunded; otherwise terminal statuses never change, and nothing
+    /// goes back to pending except a dead-lettered transaction being retried.
+    /// Staying put is allowed.
+    pub fn can_transition_to(&self, next: TransactionStatus) -> bool {
+        if *self == next {
+            return true;
+        }
+        match self {
+            TransactionStatus::Completed | TransactionStatus::Failed => {
+                next == TransactionStatus::Refunded
+            }
+            TransactionStatus::Refunded => false,
+            TransactionStatus::Pending | TransactionStatus::Dlq => true,
+            TransactionStatus::Processing
+            | TransactionStatus::Submitted
+            | TransactionStatus::PendingTrustline => next != TransactionStatus::Pending,
+        }
+    }
+
+    /// Accepted but not yet paid out; counted as pending in account stats
+    pub fn is_in_flight(&self) -> bool {
+        matches!(
+            self,
+            TransactionStatus::Pending
+                | TransactionStatus::Processing
+                | TransactionStatus::Submitted
+                | TransactionStatus::PendingTrustline
+        )
+    }
+}
+
+impl fmt::Display for TransactionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_strings_roundtrip() {
+        for status in TransactionStatus::ALL {
+            assert_eq!(TransactionStatus::parse(status.as_str()), Some(*status));
+        }
+        assert_eq!(TransactionStatus::parse("unknown"), None);
+    }
+
+    #[test]
+    fn test_every_legacy_alias_maps_to_its_status() {
+        for (alias, status) in LEGACY_ALIASES {
+            assert_eq!(TransactionStatus::from_legacy(alias), Ok(*status), "{}", alias);
+            assert_eq!(
+                TransactionStatus::from_legacy(&alias.to_uppercase()),
+                Ok(*status),
+                "{} in upper case",
+                alias
+            );
+        }
+    }
+
+    #[test]
+    fn test_legacy_aliases_are_normalized_and_not_canonical() {
+        for (alias, _) in LEGACY_ALIASES {
+            assert_eq!(normalize_legacy(alias), *alias, "{} must be written normalized", alias);
+            assert_eq!(TransactionStatus::parse(alias), None, "{} is canonical", alias);
+        }
+    }
+
+    #[test]
+    fn test_from_legacy_accepts_canonical_values_in_any_case() {
+        for status in TransactionStatus::ALL {
+            assert_eq!(TransactionStatus::from_legacy(status.as_str()), Ok(*status));
+        }
+        assert_eq!(TransactionStatus::from_legacy("Pending"), Ok(TransactionStatus::Pending));
+        assert_eq!(TransactionStatus::from_legacy(" PENDING "), Ok(TransactionStatus::Pending));
+        assert_eq!(TransactionStatus::from_legacy("PENDING_anchor"), Ok(TransactionStatus::Pending));
+        assert_eq!(
+            TransactionStatus::from_legacy("Pending-Trustline"),
+            Ok(TransactionStatus::PendingTrustline)
+        );
+    }
+
+    #[test]
+    fn test_unknown_legacy_status_is_an_error() {
+        assert_eq!(
+            TransactionStatus::from_legacy("on_hold"),
+            Err(UnknownStatus("on_hold".to_string()))
+        );
+        assert!(TransactionStatus::from_legacy("").is_err());
+    }
+
+    #[test]
+    fn test_forward_transitions_are_allowed() {
+        use TransactionStatus::*;
+        for (from, to) in [
+            (Pending, Processing),
+            (Pending, Completed),
+            (Pending, Failed),
+            (Processing, Completed),
+            (Processing, Failed),
+            (Processing, PendingTrustline),
+            (PendingTrustline, Processing),
+            (Pending, Submitted),
+            (Submitted, Completed),
+            (Submitted, Failed),
+            (Submitted, PendingTrustline),
+            (Dlq, Pending),
+            (Completed, Refunded),
+            (Failed, Refunded),
+            (Pending, Refunded),
+            (Submitted, Refunded),
+        ] {
+            assert!(from.can_transition_to(to), "{} -> {}", from, to);
+        }
+        for status in TransactionStatus::ALL {
+            assert!(status.can_transition_to(*status), "{} -> {}", status, status);
+        }
+    }
+
+    #[test]
+    fn test_regressions_are_rejected() {
+        use TransactionStatus::*;
+        for (from, to) in [
+            (Completed, Pending),
+            (Completed, Failed),
+            (Failed, Completed),
+            (Failed, Pending),
+            (Processing, Pending),
+            (Submitted, Pending),
+            (PendingTrustline, Pending),
+            (Refunded, Completed),
+            (Refunded, Pending),
+            (Refunded, Failed),
+        ] {
+            assert!(!from.can_transition_to(to), "{} -> {}", from, to);
+        }
+        for status in TransactionStatus::ALL.iter().filter(|status| status.is_terminal()) {
+            // Refunding is the one way out of a terminal status
+            for next in TransactionStatus::ALL
+                .iter()
+                .filter(|next| *next != status && **next != TransactionStatus::Refunded)
+            {
+                assert!(!status.can_transition_to(*next), "{} -> {}", status, next);
+            }
+        }
+    }
+
+    #[test]
+    fn test_in_flight_statuses() {
+        let in_flight: Vec<_> = TransactionStatus::ALL
+            .iter()
+            .filter(|status| status.is_in_flight())
+            .map(|status| status.as_str())
+            .collect();
+        assert_eq!(in_flight, vec!["pending", "processing", "submitted", "pending_trustline"]);
+    }
+}
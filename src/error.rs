@@ -5,6 +5,9 @@ use axum::{
 };
 use serde_json::json;
 use thiserror::Error;
+use uuid::Uuid;
+
+use crate::domain::TransactionStatus;
 
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -28,6 +31,30 @@ pub enum AppError {
 
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    /// A concurrent callback for this anchor transaction id stored its
+    /// transaction first
+    #[error("Duplicate callback: {0}")]
+    DuplicateCallback(String),
+
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
+
+    /// The request contradicts the stored state, e.g. a status regression
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    /// The transaction's status may not move to `to`; see
+    /// [`TransactionStatus::can_transition_to`]
+    #[error("Invalid transition: transaction {id} cannot move from {from} to {to}")]
+    InvalidTransition {
+        id: Uuid,
+        from: TransactionStatus,
+        to: TransactionStatus,
+    },
 }
 
 impl AppError {
@@ -39,6 +66,11 @@ impl AppError {
             AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
             AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::DuplicateCallback(_) => StatusCode::CONFLICT,
+            AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::InvalidTransition { .. } => StatusCode::CONFLICT,
         }
     }
 }
@@ -95,6 +127,41 @@ mod tests {
         assert_eq!(error.status_code(), StatusCode::UNAUTHORIZED);
     }
 
+    #[test]
+    fn test_forbidden_error_status_code() {
+        let error = AppError::Forbidden("Outside token scope".to_string());
+        assert_eq!(error.status_code(), StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn test_duplicate_callback_error_status_code() {
+        let error = AppError::DuplicateCallback("anchor-1".to_string());
+        assert_eq!(error.status_code(), StatusCode::CONFLICT);
+    }
+
+    #[test]
+    fn test_unavailable_error_status_code() {
+        let error = AppError::Unavailable("Idempotency store unavailable".to_string());
+        assert_eq!(error.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[test]
+    fn test_conflict_error_status_code() {
+        let error = AppError::Conflict("completed -> pending".to_string());
+        assert_eq!(error.status_code(), StatusCode::CONFLICT);
+    }
+
+    #[test]
+    fn test_invalid_transition_error() {
+        let error = AppError::InvalidTransition {
+            id: Uuid::nil(),
+            from: TransactionStatus::Completed,
+            to: TransactionStatus::Pending,
+        };
+        assert_eq!(error.status_code(), StatusCode::CONFLICT);
+        assert!(error.to_string().contains("from completed to pending"));
+    }
+
     #[tokio::test]
     async fn test_validation_error_response() {
         let error = AppError::Validation("Invalid email format".to_string());
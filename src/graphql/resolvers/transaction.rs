@@ -1,6 +1,9 @@
 use async_graphql::{Object, Context, Result, Subscription, InputObject};
 use crate::AppState;
-use crate::db::{models::Transaction, queries};
+use crate::db::{models::Transaction, queries, uow};
+use crate::domain::TransactionStatus;
+use crate::error::AppError;
+use crate::services::transaction;
 use uuid::Uuid;
 use tokio_stream::Stream;
 use std::pin::Pin;
@@ -60,15 +63,21 @@ pub struct TransactionMutation;
 impl TransactionMutation {
     async fn force_complete_transaction(&self, ctx: &Context<'_>, id: Uuid) -> Result<Transaction> {
         let state = ctx.data::<AppState>()?;
-        // Mocking the update logic as it wasn't explicitly in queries.rs for a generic update
-        // but I can use sqlx directly or add a query.
-        sqlx::query_as::<_, Transaction>(
-            "UPDATE transactions SET status = 'completed', updated_at = NOW() WHERE id = $1 RETURNING *"
-        )
-        .bind(id)
-        .fetch_one(&state.db)
-        .await
-        .map_err(|e| e.into())
+        let updated = uow::run(&state.db, |uow| Box::pin(async move {
+            transaction::transition(
+                uow.conn(),
+                id,
+                TransactionStatus::Completed,
+                Some("forced complete"),
+                "admin",
+            )
+            .await
+        }))
+        .await;
+        updated.map_err(|e| match e {
+            AppError::NotFound(_) => format!("Transaction {} not found", id).into(),
+            e => e.to_string().into(),
+        })
     }
 
     async fn replay_dlq(&self, _ctx: &Context<'_>, id: Uuid) -> Result<bool> {
@@ -0,0 +1,91 @@
+use crate::AppState;
+use crate::error::AppError;
+use crate::middleware::auth::AdminPrincipal;
+use crate::validation::validate_stellar_account;
+use axum::{
+    Extension,
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+fn account_param(stellar_account: &str) -> Result<&str, AppError> {
+    validate_stellar_account(stellar_account).map_err(|e| AppError::Validation(e.to_string()))?;
+    Ok(stellar_account)
+}
+
+/// Per-asset deposit stats for an account: `GET /admin/accounts/:id/stats`
+pub async fn get_stats(
+    State(state): State<AppState>,
+    Path(stellar_account): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let stats = state.account_stats.summary(account_param(&stellar_account)?).await?;
+    Ok(Json(stats))
+}
+
+/// Rebuild an account's stats from its transactions:
+/// `POST /admin/accounts/:id/recompute-stats`
+pub async fn recompute_stats(
+    State(state): State<AppState>,
+    Path(stellar_account): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let recomputed = state.account_stats.recompute(account_param(&stellar_account)?).await?;
+    Ok(Json(recomputed))
+}
+
+/// Body of `POST /admin/accounts/:id/erasure`
+#[derive(Debug, Deserialize)]
+pub struct ErasureRequest {
+    /// Only count what would be affected. Defaults to true, so erasing
+    /// takes an explicit `"dry_run": false`.
+    #[serde(default = "default_dry_run")]
+    pub dry_run: bool,
+    /// Reject later callbacks for the account
+    #[serde(default)]
+    pub block_callbacks: bool,
+}
+
+fn default_dry_run() -> bool {
+    true
+}
+
+/// Erase or preview everything held about an account:
+/// `POST /admin/accounts/:id/erasure`. Runs as a job; poll
+/// `GET /admin/erasures/:id` for the report.
+pub async fn request_erasure(
+    State(state): State<AppState>,
+    Extension(principal): Extension<AdminPrincipal>,
+    Path(stellar_account): Path<String>,
+    Json(request): Json<ErasureRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let job = state
+        .erasure
+        .request(
+            account_param(&stellar_account)?,
+            request.dry_run,
+            request.block_callbacks,
+            &principal.actor(),
+        )
+        .await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Every erasure run for an account: `GET /admin/accounts/:id/erasures`
+pub async fn list_erasures(
+    State(state): State<AppState>,
+    Path(stellar_account): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let jobs = state.erasure.list_for_account(account_param(&stellar_account)?).await?;
+    Ok(Json(jobs))
+}
+
+/// An erasure run and, once completed, its certificate: `GET /admin/erasures/:id`
+pub async fn get_erasure(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(state.erasure.get(id).await?))
+}
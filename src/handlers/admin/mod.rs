@@ -0,0 +1,316 @@
+pub mod transactions;
+
+use crate::AppState;
+use crate::middleware::auth::AdminPrincipal;
+use crate::services::feature_flags::{FlagUpdate, TargetingRule, TARGETING_ATTRIBUTES};
+use crate::stellar::strkey;
+use crate::utils::diff::Diff;
+use axum::{
+    Extension,
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+
+/// Fields left out are not changed
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UpdateFlagRequest {
+    pub enabled: Option<bool>,
+    /// 0-100
+    pub rollout_percentage: Option<i16>,
+    /// `G` or `M` accounts; muxed accounts are stored as their base account
+    pub allowed_accounts: Option<Vec<String>>,
+    /// Replaces every rule; `[]` removes them
+    pub targeting: Option<Vec<TargetingRule>>,
+}
+
+impl UpdateFlagRequest {
+    pub fn into_update(self) -> Result<FlagUpdate, String> {
+        if self.enabled.is_none()
+            && self.rollout_percentage.is_none()
+            && self.allowed_accounts.is_none()
+            && self.targeting.is_none()
+        {
+            return Err("nothing to update".to_string());
+        }
+        if let Some(percentage) = self.rollout_percentage {
+            if !(0..=100).contains(&percentage) {
+                return Err(format!("rollout_percentage: must be 0-100, got {}", percentage));
+            }
+        }
+        let allowed_accounts = match self.allowed_accounts {
+            Some(accounts) => {
+                let mut base_accounts = Vec::with_capacity(accounts.len());
+                for account in accounts {
+                    let kind = strkey::validate_account(account.trim())
+                        .map_err(|e| format!("allowed_accounts: {}: {}", account, e))?;
+                    let base = kind.base_address();
+                    if !base_accounts.contains(&base) {
+                        base_accounts.push(base);
+                    }
+                }
+                Some(base_accounts)
+            }
+            None => None,
+        };
+        let targeting = match self.targeting {
+            Some(rules) => Some(rules.into_iter().map(targeting_rule).collect::<Result<_, _>>()?),
+            None => None,
+        };
+        Ok(FlagUpdate {
+            enabled: self.enabled,
+            rollout_percentage: self.rollout_percentage,
+            allowed_accounts,
+            targeting,
+        })
+    }
+}
+
+/// A rule on a known attribute with at least one value, values trimmed and
+/// deduplicated
+fn targeting_rule(rule: TargetingRule) -> Result<TargetingRule, String> {
+    let attribute = rule.attribute.trim().to_string();
+    if !TARGETING_ATTRIBUTES.contains(&attribute.as_str()) {
+        return Err(format!(
+            "targeting: unknown attribute '{}', expected one of {}",
+            attribute,
+            TARGETING_ATTRIBUTES.join(", ")
+        ));
+    }
+    let mut values = Vec::with_capacity(rule.values.len());
+    for value in rule.values {
+        let value = value.trim().to_string();
+        if value.is_empty() {
+            return Err(format!("targeting: {}: values must not be blank", attribute));
+        }
+        if !values.contains(&value) {
+            values.push(value);
+        }
+    }
+    if values.is_empty() {
+        return Err(format!("targeting: {}: needs at least one value", attribute));
+    }
+    Ok(TargetingRule { attribute, values })
+}
+
+/// Changes returned by `GET /admin/flags/:name/audit` when `limit` is omitted
+const DEFAULT_AUDIT_LIMIT: i64 = 50;
+const MAX_AUDIT_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct FlagAuditQuery {
+    pub limit: Option<i64>,
+}
+
+/// Admin mutation response: the updated entity plus what just changed
+#[derive(Debug, Serialize)]
+pub struct MutationResponse<T> {
+    #[serde(flatten)]
+    pub entity: T,
+    pub changes: Diff,
+}
+
+pub async fn get_flags(State(state): State<AppState>) -> impl IntoResponse {
+    match state.feature_flags.get_all().await {
+        Ok(flags) => (StatusCode::OK, Json(flags)).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to get feature flags: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Failed to retrieve feature flags"
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Force a full reload of the flag cache, outside the refresh schedule
+pub async fn refresh_flags(
+    State(state): State<AppState>,
+    Extension(principal): Extension<AdminPrincipal>,
+) -> impl IntoResponse {
+    match state.feature_flags.refresh_full().await {
+        Ok(report) => {
+            tracing::info!(admin = %principal.name, "Feature flag cache refreshed");
+            (StatusCode::OK, Json(report)).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to refresh feature flags: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Failed to refresh feature flags"
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn update_flag(
+    State(state): State<AppState>,
+    Extension(principal): Extension<AdminPrincipal>,
+    Path(name): Path<String>,
+    Json(payload): Json<UpdateFlagRequest>,
+) -> impl IntoResponse {
+    let update = match payload.into_update() {
+        Ok(update) => update,
+        Err(error) => {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": error })))
+                .into_response()
+        }
+    };
+    match state.feature_flags.update_with(&name, &update, &principal.actor()).await {
+        Ok((flag, changes)) => {
+            tracing::info!(
+                admin = %principal.name,
+                role = principal.role.as_str(),
+                flag = %name,
+                "Feature flag updated"
+            );
+            (
+                StatusCode::OK,
+                Json(MutationResponse {
+                    entity: flag,
+                    changes,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to update feature flag '{}': {}", name, e);
+            (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({
+                    "error": format!("Feature flag '{}' not found", name)
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// The recorded changes to a flag, newest first
+pub async fn get_flag_audit(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<FlagAuditQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT).clamp(1, MAX_AUDIT_LIMIT);
+    match state.feature_flags.history(&name, limit).await {
+        Ok(changes) => (StatusCode::OK, Json(changes)).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to get audit log of feature flag '{}': {}", name, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Failed to retrieve feature flag audit log"
+                })),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCOUNT: &str = "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ";
+    const MUXED: &str = "MA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJUAAAAAAAAAAAACJUQ";
+
+    #[test]
+    fn test_enabled_alone_is_still_accepted() {
+        let request: UpdateFlagRequest = serde_json::from_str(r#"{"enabled": true}"#).unwrap();
+        assert_eq!(
+            request.into_update().unwrap(),
+            FlagUpdate {
+                enabled: Some(true),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_rollout_percentage_must_be_in_range() {
+        for percentage in [0, 55, 100] {
+            let request = UpdateFlagRequest {
+                rollout_percentage: Some(percentage),
+                ..Default::default()
+            };
+            assert_eq!(request.into_update().unwrap().rollout_percentage, Some(percentage));
+        }
+        for percentage in [-1, 101] {
+            let request = UpdateFlagRequest {
+                rollout_percentage: Some(percentage),
+                ..Default::default()
+            };
+            assert!(request.into_update().unwrap_err().starts_with("rollout_percentage"));
+        }
+    }
+
+    #[test]
+    fn test_allowed_accounts_are_validated_and_stored_as_base_accounts() {
+        let request = UpdateFlagRequest {
+            allowed_accounts: Some(vec![ACCOUNT.to_string(), MUXED.to_string()]),
+            ..Default::default()
+        };
+        let update = request.into_update().unwrap();
+        assert_eq!(update.allowed_accounts, Some(vec![ACCOUNT.to_string()]));
+
+        let request = UpdateFlagRequest {
+            allowed_accounts: Some(vec!["GNOTANACCOUNT".to_string()]),
+            ..Default::default()
+        };
+        assert!(request.into_update().unwrap_err().starts_with("allowed_accounts"));
+    }
+
+    #[test]
+    fn test_targeting_rules_are_validated() {
+        let request: UpdateFlagRequest = serde_json::from_str(
+            r#"{"targeting": [
+                {"attribute": " asset_code ", "values": ["USDC", " USDC", "EURC"]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request.into_update().unwrap().targeting,
+            Some(vec![TargetingRule {
+                attribute: "asset_code".to_string(),
+                values: vec!["USDC".to_string(), "EURC".to_string()],
+            }])
+        );
+
+        let invalid = [
+            ("asset", vec!["USDC"]),
+            ("asset_code", vec![]),
+            ("asset_code", vec![" "]),
+        ];
+        for (attribute, values) in invalid {
+            let request = UpdateFlagRequest {
+                targeting: Some(vec![TargetingRule {
+                    attribute: attribute.to_string(),
+                    values: values.into_iter().map(str::to_string).collect(),
+                }]),
+                ..Default::default()
+            };
+            assert!(request.into_update().unwrap_err().starts_with("targeting"));
+        }
+
+        // An empty list clears the rules
+        let request = UpdateFlagRequest {
+            targeting: Some(Vec::new()),
+            ..Default::default()
+        };
+        assert_eq!(request.into_update().unwrap().targeting, Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_empty_update_is_rejected() {
+        assert!(UpdateFlagRequest::default().into_update().is_err());
+    }
+}
@@ -0,0 +1,112 @@
+//! Operator view of transactions: `GET /admin/transactions`,
+//! `GET /admin/transactions/:id` with its status history, and
+//! `POST /admin/transactions/:id/retry`.
+
+use crate::AppState;
+use crate::db::models::{StatusHistoryEntry, TransactionDetail};
+use crate::db::queries::{self, TransactionListFilter};
+use crate::db::{transactions, uow};
+use crate::error::AppError;
+use crate::handlers::transactions::{TransactionFilters, TransactionQuery};
+use crate::middleware::auth::AdminPrincipal;
+use crate::services::transaction;
+use crate::utils::cursor;
+use axum::{
+    Extension,
+    Json,
+    extract::{Path, Query, State},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+pub struct AdminTransactionPage {
+    /// Newest first
+    pub items: Vec<TransactionDetail>,
+    /// Pass as `cursor` for the next page; `null` on the last one
+    pub next_cursor: Option<String>,
+    pub filters: TransactionFilters,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AdminTransactionResponse {
+    #[serde(flatten)]
+    pub transaction: TransactionDetail,
+    /// Oldest first
+    pub status_history: Vec<StatusHistoryEntry>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RetryRequest {
+    /// Recorded in the status history
+    pub reason: Option<String>,
+}
+
+/// The same filters and paging as `GET /transactions`, without a token scope
+pub async fn list_transactions(
+    State(state): State<AppState>,
+    Query(query): Query<TransactionQuery>,
+) -> Result<Json<AdminTransactionPage>, AppError> {
+    let filters = query.filters(None)?;
+    let after = query.cursor()?;
+    let filter = TransactionListFilter {
+        stellar_account: filters.stellar_account.as_deref(),
+        status: filters.status.as_deref(),
+        asset_code: filters.asset_code.as_deref(),
+        from: filters.from,
+        until: filters.to,
+    };
+
+    // One extra row tells whether there is another page
+    let mut items = transactions::list(&state.db, &filter, after, filters.limit + 1).await?;
+    let next_cursor = if items.len() as i64 > filters.limit {
+        items.truncate(filters.limit as usize);
+        items
+            .last()
+            .map(|detail| cursor::encode(detail.transaction.created_at, detail.transaction.id))
+    } else {
+        None
+    };
+
+    Ok(Json(AdminTransactionPage { items, next_cursor, filters }))
+}
+
+pub async fn get_transaction(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<AdminTransactionResponse>, AppError> {
+    Ok(Json(find(&state.db, id).await?))
+}
+
+/// Send a failed transaction back to `pending`, so the payment processor
+/// and reconciliation pick it up again. Anything but `failed` is a 409.
+pub async fn retry_transaction(
+    State(state): State<AppState>,
+    Extension(principal): Extension<AdminPrincipal>,
+    Path(id): Path<Uuid>,
+    payload: Option<Json<RetryRequest>>,
+) -> Result<Json<AdminTransactionResponse>, AppError> {
+    let reason = payload
+        .and_then(|Json(request)| request.reason)
+        .map(|reason| format!("retried: {}", reason.trim()))
+        .unwrap_or_else(|| "retried".to_string());
+    let (reason, actor) = (&reason, &principal.actor());
+    uow::run(&state.db, |uow| Box::pin(async move {
+        transaction::retry(uow.conn(), id, Some(reason), actor).await
+    }))
+    .await?;
+
+    tracing::info!(transaction_id = %id, admin = %principal.name, "Failed transaction retried");
+    Ok(Json(find(&state.db, id).await?))
+}
+
+async fn find(pool: &sqlx::PgPool, id: Uuid) -> Result<AdminTransactionResponse, AppError> {
+    let transaction = transactions::get(pool, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Transaction {} not found", id)))?;
+    let status_history = queries::get_status_history(pool, id).await?;
+    Ok(AdminTransactionResponse {
+        transaction,
+        status_history,
+    })
+}
@@ -0,0 +1,230 @@
+use crate::AppState;
+use crate::db::audit::{AuditLog, ENTITY_ASSET};
+use crate::db::models::Asset;
+use crate::db::{queries, uow};
+use crate::error::AppError;
+use crate::handlers::admin::MutationResponse;
+use crate::middleware::auth::AdminPrincipal;
+use crate::utils::diff::{diff, diff_values, Diff};
+use crate::validation::{sanitize_string, validate_asset_code, validate_stellar_address};
+use axum::{
+    Extension,
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAssetRequest {
+    pub asset_code: String,
+    pub asset_issuer: Option<String>,
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAssetRequest {
+    pub asset_issuer: Option<String>,
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Fields rewritten on every save or verification run
+const ASSET_BOOKKEEPING_FIELDS: &[&str] = &["updated_at", "verified_at"];
+
+/// Diff an asset mutation, log it and write it to the audit log in the
+/// mutation's unit of work
+async fn record_asset_change(
+    conn: &mut PgConnection,
+    before: &Asset,
+    after: &Asset,
+    actor: &str,
+) -> Result<Diff, AppError> {
+    let changes = diff(before, after)
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .ignoring(ASSET_BOOKKEEPING_FIELDS);
+
+    AuditLog::log(
+        conn,
+        after.id,
+        ENTITY_ASSET,
+        "updated",
+        None,
+        Some(serde_json::json!({ "diff": changes })),
+        actor,
+    )
+    .await?;
+    tracing::info!(
+        asset_id = %after.id,
+        asset_code = %after.asset_code,
+        changes = %changes.to_json(),
+        "Asset updated"
+    );
+    Ok(changes)
+}
+
+fn not_found(id: Uuid) -> impl FnOnce(sqlx::Error) -> AppError {
+    move |e| match e {
+        sqlx::Error::RowNotFound => AppError::NotFound(format!("Asset {} not found", id)),
+        _ => AppError::Database(e),
+    }
+}
+
+fn validate_issuer(issuer: Option<String>) -> Result<Option<String>, AppError> {
+    let issuer = issuer
+        .map(|v| sanitize_string(&v))
+        .filter(|v| !v.is_empty());
+    if let Some(issuer) = &issuer {
+        validate_stellar_address(issuer).map_err(|err| AppError::Validation(err.to_string()))?;
+    }
+    Ok(issuer)
+}
+
+/// Add an asset to the registry and verify it against the issuer's home domain.
+///
+/// A failed verification does not block the save; the asset is stored as
+/// `unverified` with the reason.
+pub async fn create_asset(
+    State(state): State<AppState>,
+    Extension(principal): Extension<AdminPrincipal>,
+    Json(payload): Json<CreateAssetRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let asset_code = sanitize_string(&payload.asset_code);
+    validate_asset_code(&asset_code).map_err(|err| AppError::Validation(err.to_string()))?;
+    let asset_issuer = validate_issuer(payload.asset_issuer)?;
+    let metadata = payload.metadata.unwrap_or_else(|| serde_json::json!({}));
+
+    // Verified before the transaction, so it is not held open across the HTTP calls
+    let outcome = state
+        .asset_verifier
+        .verify(&asset_code, asset_issuer.as_deref())
+        .await;
+
+    let actor = principal.actor();
+    let (asset, changes) = uow::run(&state.db, |uow| {
+        Box::pin(async move {
+            let asset = queries::insert_asset(
+                uow.conn(),
+                &asset_code,
+                asset_issuer.as_deref(),
+                &metadata,
+                payload.enabled,
+            )
+            .await?;
+            let asset = queries::set_asset_verification(
+                uow.conn(),
+                asset.id,
+                outcome.status(),
+                outcome.error(),
+            )
+            .await?;
+
+            let created =
+                serde_json::to_value(&asset).map_err(|e| AppError::Internal(e.to_string()))?;
+            let changes =
+                diff_values(&serde_json::json!({}), &created).ignoring(ASSET_BOOKKEEPING_FIELDS);
+            AuditLog::log(uow.conn(), asset.id, ENTITY_ASSET, "created", None, Some(created), &actor)
+                .await?;
+            Ok::<_, AppError>((asset, changes))
+        })
+    })
+    .await?;
+    tracing::info!(
+        asset_id = %asset.id,
+        asset_code = %asset.asset_code,
+        changes = %changes.to_json(),
+        "Asset created"
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(MutationResponse {
+            entity: asset,
+            changes,
+        }),
+    ))
+}
+
+/// Update a registry asset and re-run issuer home-domain verification.
+pub async fn update_asset(
+    State(state): State<AppState>,
+    Extension(principal): Extension<AdminPrincipal>,
+    Path(id): Path<Uuid>,
+    Json(payload): Json<UpdateAssetRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let asset_issuer = validate_issuer(payload.asset_issuer)?;
+    let metadata = payload.metadata.unwrap_or_else(|| serde_json::json!({}));
+
+    let before = queries::get_asset(&state.db, id).await.map_err(not_found(id))?;
+    let outcome = state
+        .asset_verifier
+        .verify(&before.asset_code, asset_issuer.as_deref())
+        .await;
+
+    let actor = principal.actor();
+    let (asset, changes) = uow::run(&state.db, |uow| {
+        Box::pin(async move {
+            let asset = queries::update_asset(
+                uow.conn(),
+                id,
+                asset_issuer.as_deref(),
+                &metadata,
+                payload.enabled,
+            )
+            .await
+            .map_err(not_found(id))?;
+            let asset =
+                queries::set_asset_verification(uow.conn(), id, outcome.status(), outcome.error())
+                    .await?;
+            let changes = record_asset_change(uow.conn(), &before, &asset, &actor).await?;
+            Ok::<_, AppError>((asset, changes))
+        })
+    })
+    .await?;
+    Ok(Json(MutationResponse {
+        entity: asset,
+        changes,
+    }))
+}
+
+/// Re-run verification for a single asset on demand.
+pub async fn verify_asset(
+    State(state): State<AppState>,
+    Extension(principal): Extension<AdminPrincipal>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let before = queries::get_asset(&state.db, id).await.map_err(not_found(id))?;
+    let outcome = state
+        .asset_verifier
+        .verify(&before.asset_code, before.asset_issuer.as_deref())
+        .await;
+
+    let actor = principal.actor();
+    let (asset, changes) = uow::run(&state.db, |uow| {
+        Box::pin(async move {
+            let asset =
+                queries::set_asset_verification(uow.conn(), id, outcome.status(), outcome.error())
+                    .await
+                    .map_err(not_found(id))?;
+            let changes = record_asset_change(uow.conn(), &before, &asset, &actor).await?;
+            Ok::<_, AppError>((asset, changes))
+        })
+    })
+    .await?;
+    Ok(Json(MutationResponse {
+        entity: asset,
+        changes,
+    }))
+}
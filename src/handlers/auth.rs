@@ -5,10 +5,7 @@ use axum::{
     http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
-use hmac::{Hmac, Mac};
-use sha2::Sha256;
-
-type HmacSha256 = Hmac<Sha256>;
+use crate::utils::signature::{self, SignatureError, SIGNATURE_HEADER};
 
 /// Extractor that verifies the X-Stellar-Signature header
 /// against the request body using HMAC-SHA256
@@ -19,20 +16,11 @@ pub struct VerifiedWebhook {
 impl VerifiedWebhook {
     /// Verify the signature using constant-time comparison
     fn verify_signature(secret: &str, body: &[u8], signature_header: &str) -> Result<(), AuthError> {
-        // Decode the hex signature from the header
-        let expected_signature = hex::decode(signature_header)
-            .map_err(|_| AuthError::InvalidSignatureFormat)?;
-
-        // Compute HMAC-SHA256 of the body
-        let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
-            .map_err(|_| AuthError::InvalidSecret)?;
-        mac.update(body);
-
-        // Constant-time comparison to prevent timing attacks
-        mac.verify_slice(&expected_signature)
-            .map_err(|_| AuthError::SignatureMismatch)?;
-
-        Ok(())
+        signature::verify(secret, body, signature_header).map_err(|e| match e {
+            SignatureError::InvalidFormat => AuthError::InvalidSignatureFormat,
+            SignatureError::InvalidSecret => AuthError::InvalidSecret,
+            SignatureError::Mismatch => AuthError::SignatureMismatch,
+        })
     }
 }
 
@@ -45,7 +33,7 @@ impl FromRequest<crate::AppState> for VerifiedWebhook {
         
         // Extract the signature header
         let signature = headers
-            .get("X-Stellar-Signature")
+            .get(SIGNATURE_HEADER)
             .and_then(|v| v.to_str().ok())
             .ok_or(AuthError::MissingSignature)?;
 
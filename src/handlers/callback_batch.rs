@@ -0,0 +1,499 @@
+//! `POST /callback/transactions/batch`: many Anchor Platform callbacks in one
+//! request, for replays that would otherwise take a connection per callback.
+//!
+//! Every item is parsed and validated like a `/callback` body, under the
+//! batch's `X-Callback-Schema-Version`. The accepted items are stored in one
+//! unit of work, with their anchor transaction id claims, transactions and raw
+//! bodies each written in a single multi-row statement. The response is
+//! `207 Multi-Status` with one result per item, in request order.
+//!
+//! Batches only create transactions. An item whose anchor transaction id is
+//! already stored, or appears earlier in the batch, is a `duplicate` when it
+//! reports the same amount and asset and a `validation_error` otherwise;
+//! status updates still go through `/callback`. Batches are always stored
+//! before the response, whatever the ingestion mode.
+
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::ApiState;
+use crate::db::{models::Transaction, queries, uow};
+use crate::error::AppError;
+use crate::handlers::callback_schema::{CallbackPayload, CallbackSchemaVersion, parse_callback};
+use crate::handlers::webhook::{check_same_deposit, record_creation, validate_webhook_payload};
+use crate::metrics;
+use crate::services::quotes::QuoteRejection;
+use crate::services::{asset_verification, erasure};
+use crate::utils::correlation::CorrelationContext;
+
+/// Most callbacks accepted in one batch
+pub const MAX_BATCH_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchItemOutcome {
+    Created,
+    /// The anchor transaction id is already stored or earlier in the batch,
+    /// with the same amount and asset
+    Duplicate,
+    /// Not stored; `error` says why
+    ValidationError,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchItemResult {
+    /// Position of the item in the request array
+    pub index: usize,
+    /// The item's anchor transaction id, `null` if it has none
+    pub id: Option<String>,
+    pub outcome: BatchItemOutcome,
+    /// The created transaction or, for a duplicate, the one holding the id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CallbackBatchResponse {
+    pub created: usize,
+    pub duplicates: usize,
+    pub rejected: usize,
+    pub results: Vec<BatchItemResult>,
+}
+
+/// Where an item stands after validation
+enum Checked {
+    /// Will be stored unless its claim finds the id taken or its quote used
+    Accepted(String),
+    /// Same anchor transaction id, amount and asset as an earlier accepted
+    /// item
+    Duplicate(String),
+    Rejected(String),
+}
+
+/// An accepted item waiting to be stored
+struct Accepted {
+    anchor_transaction_id: String,
+    tx: Transaction,
+    raw: String,
+}
+
+/// Per-batch state, so shared checks run once
+#[derive(Default)]
+struct BatchChecks {
+    /// Amount and asset of each anchor transaction id accepted so far
+    deposits: HashMap<String, (BigDecimal, String)>,
+    /// Asset codes with their resolved issuers
+    allowed_assets: HashSet<(String, Option<String>)>,
+    allowed_accounts: HashSet<String>,
+    quotes: HashSet<String>,
+}
+
+/// What the unit of work stored
+#[derive(Default)]
+struct BatchWrite {
+    /// Transaction created for each claimed anchor transaction id
+    created: HashMap<String, Uuid>,
+    /// Transaction already holding each anchor transaction id that was taken
+    existing: HashMap<String, Uuid>,
+    /// Why each accepted anchor transaction id was not stored after all
+    rejected: HashMap<String, String>,
+}
+
+/// Batch of Anchor Platform transaction callbacks
+///
+/// Takes a JSON array of callbacks in the schema version selected with
+/// `X-Callback-Schema-Version` (default 1), at most 1000. Valid items are
+/// stored in one database transaction; invalid ones don't affect the rest.
+#[utoipa::path(
+    post,
+    path = "/callback/transactions/batch",
+    params(
+        ("X-Callback-Schema-Version" = Option<u8>, Header, description = "Callback payload schema version (1 or 2, default 1)")
+    ),
+    request_body = Vec<CallbackPayload>,
+    responses(
+        (status = 207, description = "One result per item: created, duplicate or validation_error", body = CallbackBatchResponse),
+        (status = 400, description = "Unsupported schema version, a body that is not an array, or an empty or oversized batch"),
+        (status = 500, description = "Database error; nothing in the batch was stored")
+    ),
+    tag = "Callbacks"
+)]
+pub async fn callback_batch(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let version = CallbackSchemaVersion::from_headers(&headers)?;
+    metrics::record_callback_schema_version(version.as_str());
+    let items = parse_batch(&body)?;
+
+    let mut batch = BatchChecks::default();
+    let mut checked = Vec::with_capacity(items.len());
+    let mut accepted = Vec::new();
+    for item in &items {
+        let id = item.get("id").and_then(|id| id.as_str()).map(str::to_string);
+        let result = match check_item(&state, version, item, &mut batch).await {
+            Ok(Ok(item)) => {
+                let anchor_transaction_id = item.anchor_transaction_id.clone();
+                accepted.push(item);
+                Checked::Accepted(anchor_transaction_id)
+            }
+            Ok(Err(duplicate)) => Checked::Duplicate(duplicate),
+            Err(e) => Checked::Rejected(item_error(e)?),
+        };
+        checked.push((id, result));
+    }
+
+    let write = store_batch(&state.app_state.db, version, accepted).await?;
+    let body = batch_response(checked, &write);
+    tracing::info!(
+        items = body.results.len(),
+        created = body.created,
+        duplicates = body.duplicates,
+        rejected = body.rejected,
+        "Callback batch stored"
+    );
+    Ok((StatusCode::MULTI_STATUS, Json(body)).into_response())
+}
+
+/// The body as an array of 1 to [`MAX_BATCH_SIZE`] items
+fn parse_batch(body: &[u8]) -> Result<Vec<serde_json::Value>, AppError> {
+    let items: Vec<serde_json::Value> = serde_json::from_slice(body).map_err(|e| {
+        AppError::BadRequest(format!("body must be a JSON array of callbacks: {}", e))
+    })?;
+    if items.is_empty() {
+        return Err(AppError::BadRequest("batch is empty".to_string()));
+    }
+    if items.len() > MAX_BATCH_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "batch has {} callbacks; at most {} are accepted",
+            items.len(),
+            MAX_BATCH_SIZE
+        )));
+    }
+    Ok(items)
+}
+
+/// Validate one item like a single callback. `Ok(Err(id))` for a repeat of
+/// an anchor transaction id accepted earlier in the batch, with its amount
+/// and asset.
+async fn check_item(
+    state: &ApiState,
+    version: CallbackSchemaVersion,
+    item: &serde_json::Value,
+    batch: &mut BatchChecks,
+) -> Result<Result<Accepted, String>, AppError> {
+    let raw = item.to_string();
+    let normalized = parse_callback(version, raw.as_bytes())?;
+    let payload = validate_webhook_payload(normalized.into())?;
+    let anchor_transaction_id = payload
+        .anchor_transaction_id
+        .clone()
+        .ok_or_else(|| AppError::Validation("id: must not be empty".to_string()))?;
+    if let Some((amount, asset_code)) = batch.deposits.get(&anchor_transaction_id) {
+        check_same_deposit(
+            &anchor_transaction_id,
+            (amount, asset_code),
+            (&payload.amount, &payload.asset_code),
+        )?;
+        return Ok(Err(anchor_transaction_id));
+    }
+
+    let app = &state.app_state;
+    let asset_code = &payload.asset_code;
+    let named = payload.asset_issuer.as_deref();
+    let asset_issuer = asset_verification::resolve_asset_issuer(&app.db, asset_code, named).await?;
+    let asset = (asset_code.clone(), asset_issuer.clone());
+    if !batch.allowed_assets.contains(&asset) {
+        let issuer = asset_issuer.as_deref();
+        asset_verification::ensure_deposits_allowed(&app.db, &app.feature_flags, asset_code, issuer)
+            .await?;
+        batch.allowed_assets.insert(asset);
+    }
+    if !batch.allowed_accounts.contains(&payload.stellar_address) {
+        erasure::ensure_callbacks_allowed(&app.db, &payload.stellar_address).await?;
+        batch.allowed_accounts.insert(payload.stellar_address.clone());
+    }
+    if let Some(quote_id) = &payload.quote_id {
+        // A quote prices one withdrawal, so only its first item may use it
+        if batch.quotes.contains(quote_id) {
+            return Err(QuoteRejection::AlreadyUsed(quote_id.clone()).into());
+        }
+        app.quotes
+            .verify(quote_id, &payload.asset_code, asset_issuer.as_deref(), &payload.amount)
+            .await?;
+        batch.quotes.insert(quote_id.clone());
+    }
+    batch.deposits.insert(
+        anchor_transaction_id.clone(),
+        (payload.amount.clone(), payload.asset_code.clone()),
+    );
+
+    let mut tx = Transaction::new(
+        payload.stellar_address,
+        payload.amount,
+        payload.asset_code,
+        payload.anchor_transaction_id,
+        payload.callback_type,
+        payload.callback_status,
+    );
+    tx.asset_issuer = asset_issuer;
+    tx.quote_id = payload.quote_id;
+    tx.muxed_id = payload.muxed_id.map(BigDecimal::from);
+    // Each item is its own deposit, so the batch request's id is not shared
+    let ctx = CorrelationContext::parse(&anchor_transaction_id)
+        .unwrap_or_else(|| CorrelationContext::for_transaction(&tx));
+    Ok(Ok(Accepted {
+        anchor_transaction_id,
+        tx: tx.with_correlation(&ctx),
+        raw,
+    }))
+}
+
+/// Errors that reject only their item; anything else fails the batch
+fn item_error(e: AppError) -> Result<String, AppError> {
+    match e {
+        AppError::Database(_)
+        | AppError::DatabaseError(_)
+        | AppError::Internal(_)
+        | AppError::Unavailable(_) => Err(e),
+        e => Ok(e.to_string()),
+    }
+}
+
+/// Claim the ids and insert the transactions whose claim succeeded, with
+/// their audit entries and events, all or nothing. Items whose quote was used
+/// in the meantime, or whose id is held by a transaction with another amount
+/// or asset, are rejected without failing the rest. Raw bodies are captured
+/// in a savepoint, as for single callbacks.
+async fn store_batch(
+    pool: &sqlx::PgPool,
+    version: CallbackSchemaVersion,
+    accepted: Vec<Accepted>,
+) -> Result<BatchWrite, AppError> {
+    if accepted.is_empty() {
+        return Ok(BatchWrite::default());
+    }
+
+    uow::run(pool, |uow| Box::pin(async move {
+        let claims: Vec<(String, Uuid)> = accepted
+            .iter()
+            .map(|item| (item.anchor_transaction_id.clone(), item.tx.id))
+            .collect();
+        let claimed: HashSet<String> = queries::claim_anchor_transaction_ids(uow.conn(), &claims)
+            .await?
+            .into_iter()
+            .collect();
+        let (new, taken): (Vec<_>, Vec<_>) = accepted
+            .into_iter()
+            .partition(|item| claimed.contains(&item.anchor_transaction_id));
+
+        let mut write = BatchWrite::default();
+        let mut released = Vec::new();
+        let mut bodies = Vec::with_capacity(new.len());
+        let mut txs = Vec::with_capacity(new.len());
+        for item in new {
+            // Re-checked under the write, as for single callbacks
+            if let Some(quote_id) = &item.tx.quote_id {
+                if !queries::link_quote_to_transaction(uow.conn(), quote_id, item.tx.id).await? {
+                    let error = AppError::from(QuoteRejection::Unavailable(quote_id.clone()));
+                    write.rejected.insert(item.anchor_transaction_id.clone(), error.to_string());
+                    released.push(item.anchor_transaction_id);
+                    continue;
+                }
+            }
+            write.created.insert(item.anchor_transaction_id, item.tx.id);
+            bodies.push((item.tx.id, item.raw));
+            txs.push(item.tx);
+        }
+        if !released.is_empty() {
+            queries::release_anchor_transaction_ids(uow.conn(), &released).await?;
+        }
+
+        if !txs.is_empty() {
+            let inserted = queries::insert_transactions(uow.conn(), &txs).await?;
+            for tx in &inserted {
+                record_creation(uow, tx).await?;
+            }
+
+            let captured = uow
+                .savepoint(|sp| Box::pin(async move {
+                    queries::insert_raw_callbacks(sp.conn(), version.as_str(), &bodies).await
+                }))
+                .await;
+            if let Err(e) = captured {
+                tracing::warn!(count = txs.len(), "Failed to capture raw batch callbacks: {}", e);
+            }
+        }
+
+        if !taken.is_empty() {
+            let ids: Vec<String> =
+                taken.iter().map(|item| item.anchor_transaction_id.clone()).collect();
+            let holders: HashMap<String, Transaction> =
+                queries::find_anchor_transaction_holders(uow.conn(), &ids)
+                    .await?
+                    .into_iter()
+                    .collect();
+            for item in taken {
+                let id = item.anchor_transaction_id;
+                let Some(holder) = holders.get(&id) else {
+                    continue;
+                };
+                let stored = (&holder.amount, holder.asset_code.as_str());
+                let reported = (&item.tx.amount, item.tx.asset_code.as_str());
+                match check_same_deposit(&id, stored, reported) {
+                    Ok(()) => {
+                        write.existing.insert(id, holder.id);
+                    }
+                    Err(e) => {
+                        write.rejected.insert(id, e.to_string());
+                    }
+                }
+            }
+        }
+        Ok(write)
+    }))
+    .await
+}
+
+/// One result per item, in request order, and the callback metric for each
+fn batch_response(
+    checked: Vec<(Option<String>, Checked)>,
+    write: &BatchWrite,
+) -> CallbackBatchResponse {
+    let holder = |anchor_transaction_id: &str| {
+        write
+            .created
+            .get(anchor_transaction_id)
+            .or_else(|| write.existing.get(anchor_transaction_id))
+            .map(Uuid::to_string)
+    };
+
+    let results: Vec<BatchItemResult> = checked
+        .into_iter()
+        .enumerate()
+        .map(|(index, (id, checked))| {
+            let (outcome, transaction_id, error) = match checked {
+                // Repeats share the outcome of the item they repeat
+                Checked::Accepted(anchor_transaction_id)
+                | Checked::Duplicate(anchor_transaction_id)
+                    if write.rejected.contains_key(&anchor_transaction_id) =>
+                {
+                    let error = write.rejected[&anchor_transaction_id].clone();
+                    (BatchItemOutcome::ValidationError, None, Some(error))
+                }
+                Checked::Accepted(anchor_transaction_id) => {
+                    match write.created.get(&anchor_transaction_id) {
+                        Some(id) => (BatchItemOutcome::Created, Some(id.to_string()), None),
+                        None => (BatchItemOutcome::Duplicate, holder(&anchor_transaction_id), None),
+                    }
+                }
+                Checked::Duplicate(anchor_transaction_id) => {
+                    (BatchItemOutcome::Duplicate, holder(&anchor_transaction_id), None)
+                }
+                Checked::Rejected(error) => (BatchItemOutcome::ValidationError, None, Some(error)),
+            };
+            metrics::record_callback(match outcome {
+                BatchItemOutcome::Created => metrics::CallbackStatus::Created,
+                BatchItemOutcome::Duplicate => metrics::CallbackStatus::Duplicate,
+                BatchItemOutcome::ValidationError => metrics::CallbackStatus::ValidationFailed,
+            });
+            BatchItemResult {
+                index,
+                id,
+                outcome,
+                transaction_id,
+                error,
+            }
+        })
+        .collect();
+
+    let count = |outcome: BatchItemOutcome| {
+        results.iter().filter(|result| result.outcome == outcome).count()
+    };
+    CallbackBatchResponse {
+        created: count(BatchItemOutcome::Created),
+        duplicates: count(BatchItemOutcome::Duplicate),
+        rejected: count(BatchItemOutcome::ValidationError),
+        results,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_batch_requires_a_bounded_array() {
+        assert_eq!(parse_batch(br#"[{"id": "a"}, {"id": "b"}]"#).unwrap().len(), 2);
+        assert!(matches!(parse_batch(br#"{"id": "a"}"#), Err(AppError::BadRequest(_))));
+        assert!(matches!(parse_batch(b"[]"), Err(AppError::BadRequest(_))));
+
+        let items = vec![serde_json::json!({}); MAX_BATCH_SIZE + 1];
+        let oversized = serde_json::to_vec(&items).unwrap();
+        assert!(matches!(parse_batch(&oversized), Err(AppError::BadRequest(_))));
+    }
+
+    #[test]
+    fn test_only_item_errors_reject_just_the_item() {
+        let invalid = AppError::Validation("amount: must be greater than zero".to_string());
+        assert_eq!(item_error(invalid).unwrap(), "Validation error: amount: must be greater than zero");
+        assert!(item_error(AppError::Forbidden("erased".to_string())).is_ok());
+
+        assert!(item_error(AppError::Unavailable("pool exhausted".to_string())).is_err());
+        assert!(item_error(AppError::Database(sqlx::Error::PoolTimedOut)).is_err());
+    }
+
+    #[test]
+    fn test_results_follow_request_order() {
+        let created = Uuid::new_v4();
+        let existing = Uuid::new_v4();
+        let write = BatchWrite {
+            created: HashMap::from([("a".to_string(), created)]),
+            existing: HashMap::from([("b".to_string(), existing)]),
+            rejected: HashMap::from([("c".to_string(), "quote used".to_string())]),
+        };
+        let checked = vec![
+            (Some("a".to_string()), Checked::Accepted("a".to_string())),
+            (None, Checked::Rejected("id: missing".to_string())),
+            (Some("b".to_string()), Checked::Accepted("b".to_string())),
+            (Some("a".to_string()), Checked::Duplicate("a".to_string())),
+            (Some("c".to_string()), Checked::Accepted("c".to_string())),
+            (Some("c".to_string()), Checked::Duplicate("c".to_string())),
+        ];
+
+        let response = batch_response(checked, &write);
+        assert_eq!((response.created, response.duplicates, response.rejected), (1, 2, 3));
+        let outcomes: Vec<_> = response
+            .results
+            .iter()
+            .map(|r| (r.index, r.outcome, r.transaction_id.clone()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (0, BatchItemOutcome::Created, Some(created.to_string())),
+                (1, BatchItemOutcome::ValidationError, None),
+                (2, BatchItemOutcome::Duplicate, Some(existing.to_string())),
+                (3, BatchItemOutcome::Duplicate, Some(created.to_string())),
+                (4, BatchItemOutcome::ValidationError, None),
+                (5, BatchItemOutcome::ValidationError, None),
+            ]
+        );
+        assert_eq!(response.results[1].error.as_deref(), Some("id: missing"));
+        assert_eq!(response.results[5].error.as_deref(), Some("quote used"));
+    }
+}
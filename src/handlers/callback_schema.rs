@@ -0,0 +1,286 @@
+//! Versioned Anchor Platform callback payloads.
+//!
+//! The anchor selects a payload shape with the `X-Callback-Schema-Version`
+//! header (default 1). Every version is parsed into its own wire struct and
+//! then mapped into [`NormalizedCallback`], so validation and persistence only
+//! ever see one shape.
+
+use axum::http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::deprecation::catalog::CallbackV1AssetCode;
+use crate::error::AppError;
+
+pub const SCHEMA_VERSION_HEADER: &str = "x-callback-schema-version";
+
+/// Callback payload schema versions understood by this service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallbackSchemaVersion {
+    V1,
+    V2,
+}
+
+impl CallbackSchemaVersion {
+    pub const SUPPORTED: &'static [CallbackSchemaVersion] =
+        &[CallbackSchemaVersion::V1, CallbackSchemaVersion::V2];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallbackSchemaVersion::V1 => "1",
+            CallbackSchemaVersion::V2 => "2",
+        }
+    }
+
+    /// Read the version from request headers, defaulting to version 1
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, AppError> {
+        let Some(raw) = headers.get(SCHEMA_VERSION_HEADER) else {
+            return Ok(CallbackSchemaVersion::V1);
+        };
+
+        let raw = raw.to_str().unwrap_or_default().trim();
+        Self::SUPPORTED
+            .iter()
+            .copied()
+            .find(|version| version.as_str() == raw)
+            .ok_or_else(|| {
+                let supported = Self::SUPPORTED
+                    .iter()
+                    .map(|v| v.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                AppError::BadRequest(format!(
+                    "Unsupported X-Callback-Schema-Version '{}'; supported versions: {}",
+                    raw, supported
+                ))
+            })
+    }
+}
+
+/// Version 1 callback: flat amount and asset fields
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct CallbackPayloadV1 {
+    /// Anchor transaction id
+    pub id: String,
+    /// Deposited amount as a decimal string
+    pub amount_in: String,
+    pub stellar_account: String,
+    #[serde(deserialize_with = "crate::deprecation::deserialize::<_, _, CallbackV1AssetCode>")]
+    pub asset_code: String,
+    /// Needed when the registry has more than one issuer of `asset_code`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset_issuer: Option<String>,
+    pub callback_type: Option<String>,
+    /// Status reported by the anchor
+    pub status: Option<String>,
+    /// Firm SEP-38 quote the withdrawal was priced with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_id: Option<String>,
+}
+
+/// Structured amount used by version 2 callbacks
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct CallbackAmount {
+    /// Amount as a decimal string
+    pub amount: String,
+    pub asset_code: String,
+    /// Needed when the registry has more than one issuer of `asset_code`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset_issuer: Option<String>,
+}
+
+/// Version 2 callback: amount and asset grouped into `amount_in`
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct CallbackPayloadV2 {
+    /// Anchor transaction id
+    pub id: String,
+    pub amount_in: CallbackAmount,
+    pub stellar_account: String,
+    pub callback_type: Option<String>,
+    /// Status reported by the anchor
+    pub status: Option<String>,
+    /// Firm SEP-38 quote the withdrawal was priced with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_id: Option<String>,
+}
+
+/// Either callback schema, for OpenAPI documentation
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum CallbackPayload {
+    V1(CallbackPayloadV1),
+    V2(CallbackPayloadV2),
+}
+
+/// Version-independent callback consumed by validation and business logic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedCallback {
+    pub anchor_transaction_id: String,
+    pub amount: String,
+    pub asset_code: String,
+    pub asset_issuer: Option<String>,
+    pub stellar_account: String,
+    pub callback_type: Option<String>,
+    pub callback_status: Option<String>,
+    pub quote_id: Option<String>,
+}
+
+impl From<CallbackPayloadV1> for NormalizedCallback {
+    fn from(payload: CallbackPayloadV1) -> Self {
+        Self {
+            anchor_transaction_id: payload.id,
+            amount: payload.amount_in,
+            asset_code: payload.asset_code,
+            asset_issuer: payload.asset_issuer,
+            stellar_account: payload.stellar_account,
+            callback_type: payload.callback_type,
+            callback_status: payload.status,
+            quote_id: payload.quote_id,
+        }
+    }
+}
+
+impl From<CallbackPayloadV2> for NormalizedCallback {
+    fn from(payload: CallbackPayloadV2) -> Self {
+        Self {
+            anchor_transaction_id: payload.id,
+            amount: payload.amount_in.amount,
+            asset_code: payload.amount_in.asset_code,
+            asset_issuer: payload.amount_in.asset_issuer,
+            stellar_account: payload.stellar_account,
+            callback_type: payload.callback_type,
+            callback_status: payload.status,
+            quote_id: payload.quote_id,
+        }
+    }
+}
+
+/// Parse a raw callback body according to the negotiated schema version
+pub fn parse_callback(
+    version: CallbackSchemaVersion,
+    body: &[u8],
+) -> Result<NormalizedCallback, AppError> {
+    let parsed = match version {
+        CallbackSchemaVersion::V1 => {
+            serde_json::from_slice::<CallbackPayloadV1>(body).map(NormalizedCallback::from)
+        }
+        CallbackSchemaVersion::V2 => {
+            serde_json::from_slice::<CallbackPayloadV2>(body).map(NormalizedCallback::from)
+        }
+    };
+
+    parsed.map_err(|e| {
+        AppError::Validation(format!(
+            "payload does not match callback schema version {}: {}",
+            version.as_str(),
+            e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const V1_FIXTURE: &str = r#"{
+        "id": "anchor-tx-123",
+        "amount_in": "100.50",
+        "stellar_account": "GABCDEFGHIJKLMNOPQRSTUVWXYZ234567ABCDEFGHIJKLMNOPQRSTUV",
+        "asset_code": "USD",
+        "callback_type": "deposit",
+        "status": "completed"
+    }"#;
+
+    const V2_FIXTURE: &str = r#"{
+        "id": "anchor-tx-123",
+        "amount_in": { "amount": "100.50", "asset_code": "USD" },
+        "stellar_account": "GABCDEFGHIJKLMNOPQRSTUVWXYZ234567ABCDEFGHIJKLMNOPQRSTUV",
+        "callback_type": "deposit",
+        "status": "completed"
+    }"#;
+
+    fn headers_with_version(version: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(SCHEMA_VERSION_HEADER, HeaderValue::from_static(version));
+        headers
+    }
+
+    #[test]
+    fn test_version_defaults_to_v1() {
+        let version = CallbackSchemaVersion::from_headers(&HeaderMap::new()).unwrap();
+        assert_eq!(version, CallbackSchemaVersion::V1);
+    }
+
+    #[test]
+    fn test_version_header_selects_v2() {
+        let version = CallbackSchemaVersion::from_headers(&headers_with_version("2")).unwrap();
+        assert_eq!(version, CallbackSchemaVersion::V2);
+    }
+
+    #[test]
+    fn test_unsupported_version_lists_supported_versions() {
+        let err = CallbackSchemaVersion::from_headers(&headers_with_version("3")).unwrap_err();
+        match err {
+            AppError::BadRequest(message) => assert!(message.contains("supported versions: 1, 2")),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_v1_fixture_normalizes() {
+        let normalized = parse_callback(CallbackSchemaVersion::V1, V1_FIXTURE.as_bytes()).unwrap();
+        assert_eq!(normalized.anchor_transaction_id, "anchor-tx-123");
+        assert_eq!(normalized.amount, "100.50");
+        assert_eq!(normalized.asset_code, "USD");
+        assert_eq!(normalized.callback_status.as_deref(), Some("completed"));
+    }
+
+    #[test]
+    fn test_v2_fixture_normalizes() {
+        let normalized = parse_callback(CallbackSchemaVersion::V2, V2_FIXTURE.as_bytes()).unwrap();
+        assert_eq!(normalized.amount, "100.50");
+        assert_eq!(normalized.asset_code, "USD");
+    }
+
+    #[test]
+    fn test_both_versions_normalize_identically() {
+        let v1 = parse_callback(CallbackSchemaVersion::V1, V1_FIXTURE.as_bytes()).unwrap();
+        let v2 = parse_callback(CallbackSchemaVersion::V2, V2_FIXTURE.as_bytes()).unwrap();
+        assert_eq!(v1, v2);
+    }
+
+    #[test]
+    fn test_quote_id_is_optional_and_normalized() {
+        let v1 = parse_callback(CallbackSchemaVersion::V1, V1_FIXTURE.as_bytes()).unwrap();
+        assert_eq!(v1.quote_id, None);
+
+        let mut with_quote: serde_json::Value = serde_json::from_str(V2_FIXTURE).unwrap();
+        with_quote["quote_id"] = "quote-1".into();
+        let v2 = parse_callback(CallbackSchemaVersion::V2, with_quote.to_string().as_bytes()).unwrap();
+        assert_eq!(v2.quote_id.as_deref(), Some("quote-1"));
+    }
+
+    #[tokio::test]
+    async fn test_only_v1_notices_its_deprecated_asset_code() {
+        let (parsed, notices) = crate::deprecation::collect(async {
+            parse_callback(CallbackSchemaVersion::V1, V1_FIXTURE.as_bytes())
+        })
+        .await;
+        assert!(parsed.is_ok());
+        assert_eq!(notices, vec![&crate::deprecation::catalog::CALLBACK_V1_ASSET_CODE]);
+
+        let (parsed, notices) = crate::deprecation::collect(async {
+            parse_callback(CallbackSchemaVersion::V2, V2_FIXTURE.as_bytes())
+        })
+        .await;
+        assert!(parsed.is_ok());
+        assert!(notices.is_empty());
+    }
+
+    #[test]
+    fn test_payload_must_match_declared_version() {
+        assert!(parse_callback(CallbackSchemaVersion::V1, V2_FIXTURE.as_bytes()).is_err());
+        assert!(parse_callback(CallbackSchemaVersion::V2, V1_FIXTURE.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,193 @@
+//! `GET /admin/events/stream`: outbox events as Server-Sent Events, for
+//! dashboards that would otherwise poll.
+
+use crate::AppState;
+use crate::db::models::OutboxEvent;
+use crate::error::AppError;
+use crate::metrics;
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::stream::{self, Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Comment lines sent while idle, so proxies keep the connection open
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EventStreamQuery {
+    /// Only events of this type, e.g. `transaction.created`
+    pub event_type: Option<String>,
+    /// Only events for this asset code
+    pub asset: Option<String>,
+}
+
+impl EventStreamQuery {
+    fn matches(&self, event: &OutboxEvent) -> bool {
+        if let Some(event_type) = &self.event_type {
+            if *event_type != event.event_type {
+                return false;
+            }
+        }
+        if let Some(asset) = &self.asset {
+            let same_asset = event
+                .asset_code
+                .as_deref()
+                .is_some_and(|code| code.eq_ignore_ascii_case(asset));
+            if !same_asset {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn sse_event(event: &OutboxEvent) -> Event {
+    Event::default()
+        .id(event.seq.to_string())
+        .event(event.event_type.as_str())
+        .data(
+            json!({
+                "seq": event.seq,
+                "event_type": event.event_type,
+                "asset_code": event.asset_code,
+                "payload": event.payload,
+                "correlation_id": event.correlation_id,
+                "created_at": event.created_at,
+            })
+            .to_string(),
+        )
+}
+
+/// `Last-Event-ID`, when the client sent a usable one
+fn last_event_id(headers: &HeaderMap) -> Option<i64> {
+    headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// Stream outbox events as they commit.
+///
+/// The first event is `snapshot`, carrying the newest sequence number at
+/// connect time. A client reconnecting with `Last-Event-ID` then gets the
+/// events it missed, within the replay bounds; if some are beyond them it
+/// gets `resync` instead and should reload. A client that falls too far
+/// behind gets `lagged` and is disconnected.
+pub async fn event_stream(
+    State(state): State<AppState>,
+    Query(query): Query<EventStreamQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    // Subscribe before reading the snapshot so nothing falls in between
+    let receiver = state.events.subscribe();
+    let snapshot = state.events.latest_seq().await?;
+    let resume_from = last_event_id(&headers);
+
+    let mut initial = vec![Event::default()
+        .id(resume_from.unwrap_or(snapshot).to_string())
+        .event("snapshot")
+        .data(json!({ "seq": snapshot }).to_string())];
+    let mut replayed = HashSet::new();
+    if let Some(seq) = resume_from {
+        let replay = state.events.replay(seq).await?;
+        if !replay.complete {
+            initial.push(
+                Event::default()
+                    .id(snapshot.to_string())
+                    .event("resync")
+                    .data(json!({ "seq": snapshot, "reason": "replay_bounds_exceeded" }).to_string()),
+            );
+        } else {
+            for event in replay.events.iter().filter(|event| query.matches(event)) {
+                replayed.insert(event.seq);
+                initial.push(sse_event(event));
+            }
+        }
+    }
+
+    let live = stream::unfold(
+        (receiver, query, replayed, false),
+        |(mut receiver, query, replayed, disconnected)| async move {
+            if disconnected {
+                return None;
+            }
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if replayed.contains(&event.seq) || !query.matches(&event) => continue,
+                    Ok(event) => {
+                        let sse = sse_event(&event);
+                        return Some((Ok(sse), (receiver, query, replayed, false)));
+                    }
+                    // Bounded channel: a slow client is dropped, not buffered for
+                    Err(RecvError::Lagged(missed)) => {
+                        metrics::record_event_stream_lagged();
+                        tracing::warn!(missed, "Admin event stream client fell behind; disconnecting");
+                        let lagged = Event::default()
+                            .event("lagged")
+                            .data(json!({ "missed": missed }).to_string());
+                        return Some((Ok(lagged), (receiver, query, replayed, true)));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        },
+    );
+
+    let events = stream::iter(initial.into_iter().map(Ok)).chain(live);
+    Ok(Sse::new(events).keep_alive(KeepAlive::new().interval(HEARTBEAT_INTERVAL)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: &str, asset_code: Option<&str>) -> OutboxEvent {
+        OutboxEvent {
+            seq: 1,
+            event_type: event_type.to_string(),
+            asset_code: asset_code.map(str::to_string),
+            payload: json!({}),
+            correlation_id: None,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_filters() {
+        let created = event("transaction.created", Some("USDC"));
+        assert!(EventStreamQuery::default().matches(&created));
+
+        let by_type = EventStreamQuery {
+            event_type: Some("transaction.created".to_string()),
+            asset: None,
+        };
+        assert!(by_type.matches(&created));
+        assert!(!by_type.matches(&event("transaction.completed", Some("USDC"))));
+
+        let by_asset = EventStreamQuery {
+            event_type: None,
+            asset: Some("usdc".to_string()),
+        };
+        assert!(by_asset.matches(&created));
+        assert!(!by_asset.matches(&event("transaction.created", Some("EURC"))));
+        assert!(!by_asset.matches(&event("transaction.created", None)));
+    }
+
+    #[test]
+    fn test_last_event_id() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(last_event_id(&headers), None);
+        headers.insert("last-event-id", "42".parse().unwrap());
+        assert_eq!(last_event_id(&headers), Some(42));
+        headers.insert("last-event-id", "not-a-seq".parse().unwrap());
+        assert_eq!(last_event_id(&headers), None);
+    }
+}
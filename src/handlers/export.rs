@@ -1,19 +1,26 @@
 use crate::AppState;
+use crate::error::AppError;
 use axum::{
-    extract::{Query, State},
-    response::IntoResponse,
+    Extension,
+    Json,
+    body::Body,
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
     http::{header, HeaderMap, header::HeaderValue, StatusCode},
 };
 use chrono::{DateTime, Utc};
-use csv::Writer;
+use csv::{Writer, WriterBuilder};
 use futures::stream::{Stream, StreamExt};
 use serde::Deserialize;
 use serde::Serialize;
 use sqlx::{Row, PgPool};
+use std::io::Write;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use crate::db::models::Transaction;
+use crate::db::models::{ExportJob, Transaction};
+use crate::services::api_tokens::TokenScope;
+use crate::services::export_jobs::NormalizedExportParams;
 
 /// Query parameters for the export endpoint
 #[derive(Debug, Deserialize, Default, Clone)]
@@ -99,6 +106,68 @@ impl From<&Transaction> for TransactionJsonRow {
     }
 }
 
+/// Column names of [`TransactionCsvRow`], written once at the top of an export
+const CSV_HEADER: &[&str] = &[
+    "id",
+    "stellar_account",
+    "amount",
+    "asset_code",
+    "status",
+    "created_at",
+    "updated_at",
+    "anchor_transaction_id",
+    "callback_type",
+    "callback_status",
+];
+
+/// Buffer a [`CsvEncoder`]'s writer fills and the stream drains after each row
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// One `csv::Writer` for a whole export, so quoting and the header are
+/// handled once per stream rather than per row
+struct CsvEncoder {
+    writer: Writer<SharedBuffer>,
+    buffer: SharedBuffer,
+}
+
+impl CsvEncoder {
+    fn new() -> Self {
+        let buffer = SharedBuffer::default();
+        let writer = WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(buffer.clone());
+        Self { writer, buffer }
+    }
+
+    fn header(&mut self) -> String {
+        self.writer.write_record(CSV_HEADER).unwrap();
+        self.take()
+    }
+
+    fn row(&mut self, row: &TransactionCsvRow) -> String {
+        self.writer.serialize(row).unwrap();
+        self.take()
+    }
+
+    fn take(&mut self) -> String {
+        self.writer.flush().unwrap();
+        let bytes = std::mem::take(&mut *self.buffer.0.lock().unwrap());
+        String::from_utf8(bytes).unwrap()
+    }
+}
+
 /// Batch size for cursor-based streaming
 const BATCH_SIZE: i64 = 1000;
 
@@ -109,7 +178,7 @@ type CsvStream = Pin<Box<dyn Stream<Item = Result<String, sqlx::Error>> + Send>>
 type JsonStream = Pin<Box<dyn Stream<Item = Result<String, sqlx::Error>> + Send>>;
 
 /// Parse date string to DateTime<Utc>
-fn parse_date(date_str: &str) -> Result<DateTime<Utc>, String> {
+pub(crate) fn parse_date(date_str: &str) -> Result<DateTime<Utc>, String> {
     // Handle both YYYY-MM-DD and YYYY-MM-DDTHH:MM:SSZ formats
     let date_str = if date_str.len() == 10 {
         format!("{}T00:00:00Z", date_str)
@@ -174,15 +243,13 @@ enum FilterValue {
 }
 
 /// Create a CSV stream from database rows - truly streaming without buffering
-fn create_csv_stream(pool: Arc<PgPool>, from: Option<String>, to: Option<String>, status: Option<String>, asset_code: Option<String>) -> CsvStream {
+pub(crate) fn create_csv_stream(pool: Arc<PgPool>, from: Option<String>, to: Option<String>, status: Option<String>, asset_code: Option<String>) -> CsvStream {
     let pool_clone = pool.clone();
     
     Box::pin(async_stream::stream! {
         let mut last_id: Option<uuid::Uuid> = None;
-        
-        // First, write CSV header
-        let headers = "id,stellar_account,amount,asset_code,status,created_at,updated_at,anchor_transaction_id,callback_type,callback_status";
-        yield Ok(headers.to_string());
+        let mut encoder = CsvEncoder::new();
+        yield Ok(encoder.header());
         
         loop {
             // Build base query with filters
@@ -244,11 +311,7 @@ fn create_csv_stream(pool: Arc<PgPool>, from: Option<String>, to: Option<String>
                         
                         last_id = Some(tx.id);
                         
-                        let csv_row = TransactionCsvRow::from(&tx);
-                        let mut wtr = Writer::from_writer(vec![]);
-                        wtr.serialize(csv_row).unwrap();
-                        let csv_line = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
-                        yield Ok(csv_line);
+                        yield Ok(encoder.row(&TransactionCsvRow::from(&tx)));
                     }
                     Err(e) => {
                         yield Err(e);
@@ -265,7 +328,7 @@ fn create_csv_stream(pool: Arc<PgPool>, from: Option<String>, to: Option<String>
 }
 
 /// Create a JSON stream from database rows - truly streaming without buffering
-fn create_json_stream(pool: Arc<PgPool>, from: Option<String>, to: Option<String>, status: Option<String>, asset_code: Option<String>) -> JsonStream {
+pub(crate) fn create_json_stream(pool: Arc<PgPool>, from: Option<String>, to: Option<String>, status: Option<String>, asset_code: Option<String>) -> JsonStream {
     let pool_clone = pool.clone();
     
     Box::pin(async_stream::stream! {
@@ -446,6 +509,172 @@ pub async fn export_transactions(
     }
 }
 
+/// Status of an asynchronous export job
+#[derive(Debug, Serialize)]
+pub struct ExportStatusResponse {
+    pub id: uuid::Uuid,
+    pub status: String,
+    pub format: String,
+    #[serde(serialize_with = "crate::utils::json::serialize_sorted")]
+    pub params: serde_json::Value,
+    pub reused: bool,
+    pub content_hash: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub row_count: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    pub download_url: Option<String>,
+}
+
+fn export_status_response(
+    state: &AppState,
+    job: ExportJob,
+    reused: bool,
+) -> ExportStatusResponse {
+    let download_url = if job.status == "ready" {
+        match state.export_jobs.download_url(&job) {
+            Ok(Some(url)) => Some(url),
+            Ok(None) => Some(format!("/admin/exports/{}/download", job.id)),
+            Err(e) => {
+                tracing::error!(export_id = %job.id, "Failed to build export download URL: {}", e);
+                Some(format!("/admin/exports/{}/download", job.id))
+            }
+        }
+    } else {
+        None
+    };
+
+    ExportStatusResponse {
+        id: job.id,
+        status: job.status,
+        format: job.format,
+        params: job.params,
+        reused,
+        content_hash: job.content_hash,
+        size_bytes: job.size_bytes,
+        row_count: job.row_count,
+        error: job.error,
+        created_at: job.created_at,
+        completed_at: job.completed_at,
+        expires_at: job.expires_at,
+        download_url,
+    }
+}
+
+/// Start an asynchronous export: `POST /admin/exports`
+///
+/// Returns immediately with the export id. Identical filter parameters within
+/// the freshness window return the existing export instead of regenerating it.
+/// A scoped token's filters are intersected with the requested ones first.
+pub async fn create_export(
+    State(state): State<AppState>,
+    scope: Option<Extension<TokenScope>>,
+    Json(query): Json<ExportQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let query = match scope {
+        Some(Extension(scope)) => scope.narrow_export(&query)?,
+        None => query,
+    };
+    let (job, reused) = state.export_jobs.request(&query).await?;
+    let location = format!("/admin/exports/{}", job.id);
+    let response = export_status_response(&state, job, reused);
+
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, location)],
+        Json(response),
+    ))
+}
+
+/// Export job, if a scoped token may see it: only jobs whose filters lie
+/// entirely inside the scope, whoever created them
+async fn visible_export(
+    state: &AppState,
+    scope: Option<Extension<TokenScope>>,
+    id: uuid::Uuid,
+) -> Result<ExportJob, AppError> {
+    let job = state.export_jobs.get(id).await?;
+    if let Some(Extension(scope)) = scope {
+        let covered = serde_json::from_value::<NormalizedExportParams>(job.params.clone())
+            .is_ok_and(|params| scope.covers_export(&params));
+        if !covered {
+            return Err(AppError::Forbidden(format!("Export {}: outside the token's scope", id)));
+        }
+    }
+    Ok(job)
+}
+
+/// Report export status: `GET /admin/exports/:id`
+pub async fn get_export(
+    State(state): State<AppState>,
+    scope: Option<Extension<TokenScope>>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let job = visible_export(&state, scope, id).await?;
+    Ok(Json(export_status_response(&state, job, false)))
+}
+
+/// Stream a ready export artifact: `GET /admin/exports/:id/download`
+///
+/// The artifact's content hash is used as a strong ETag, so clients can
+/// revalidate with `If-None-Match` without downloading the file again.
+pub async fn download_export(
+    State(state): State<AppState>,
+    scope: Option<Extension<TokenScope>>,
+    Path(id): Path<uuid::Uuid>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let job = visible_export(&state, scope, id).await?;
+
+    match job.status.as_str() {
+        "ready" => {}
+        "expired" => return Err(AppError::NotFound(format!("Export {} has expired", id))),
+        "failed" => {
+            return Err(AppError::BadRequest(format!(
+                "Export {} failed: {}",
+                id,
+                job.error.clone().unwrap_or_default()
+            )))
+        }
+        _ => return Err(AppError::BadRequest(format!("Export {} is not ready", id))),
+    }
+
+    let content_hash = job.content_hash.clone().unwrap_or_default();
+    let etag = format!("\"{}\"", content_hash);
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(',').any(|candidate| candidate.trim() == etag || candidate.trim() == "*"))
+        .unwrap_or(false);
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    let (content_type, extension) = if job.format == "ndjson" {
+        ("application/x-ndjson", "ndjson")
+    } else {
+        ("text/csv", "csv")
+    };
+    let filename = format!("transactions_{}.{}", job.id, extension);
+    let stream = state.export_jobs.open(&job).await?;
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response_headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", filename)).unwrap(),
+    );
+    response_headers.insert(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+    if let Some(size) = job.size_bytes {
+        response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(size as u64));
+    }
+
+    Ok((StatusCode::OK, response_headers, Body::from_stream(stream)).into_response())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -485,6 +714,41 @@ mod tests {
         assert_eq!(csv_row.stellar_account, "GABC123");
     }
 
+    #[test]
+    fn test_csv_header_is_written_once() {
+        use uuid::Uuid;
+        use sqlx::types::BigDecimal;
+
+        let tx = Transaction {
+            id: Uuid::new_v4(),
+            stellar_account: "GABC123".to_string(),
+            amount: BigDecimal::from(100),
+            asset_code: "USD".to_string(),
+            status: "pending".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            anchor_transaction_id: Some("anchor, \"quoted\"".to_string()),
+            callback_type: None,
+            callback_status: None,
+        };
+
+        let mut encoder = CsvEncoder::new();
+        let mut csv = encoder.header();
+        for _ in 0..3 {
+            csv.push_str(&encoder.row(&TransactionCsvRow::from(&tx)));
+        }
+
+        let header = CSV_HEADER.join(",");
+        assert_eq!(csv.lines().filter(|line| *line == header).count(), 1);
+        assert!(csv.starts_with(&format!("{}\n", header)));
+
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        assert_eq!(reader.headers().unwrap().iter().collect::<Vec<_>>(), CSV_HEADER);
+        let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(&records[0][7], "anchor, \"quoted\"");
+    }
+
     #[test]
     fn test_transaction_json_row_from() {
         use uuid::Uuid;
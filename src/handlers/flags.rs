@@ -0,0 +1,115 @@
+//! Evaluating a feature flag for one subject, and optionally an asset:
+//! `GET /flags/:name/evaluate`.
+
+use crate::error::AppError;
+use crate::services::feature_flags::{EvaluationReason, FlagContext};
+use crate::stellar::strkey;
+use crate::AppState;
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct EvaluateQuery {
+    /// `G` or `M` account; a muxed account is evaluated as its base account
+    pub subject: Option<String>,
+    /// Matched against `asset_code` targeting rules
+    pub asset_code: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FlagEvaluation {
+    pub flag: String,
+    /// The account the flag was evaluated for
+    pub subject: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_code: Option<String>,
+    pub enabled: bool,
+    pub reason: EvaluationReason,
+}
+
+/// The subject's base account
+fn subject(query: &EvaluateQuery) -> Result<String, AppError> {
+    let subject = query
+        .subject
+        .as_deref()
+        .map(str::trim)
+        .filter(|subject| !subject.is_empty())
+        .ok_or_else(|| AppError::Validation("subject: is required".to_string()))?;
+    let kind = strkey::validate_account(subject)
+        .map_err(|e| AppError::Validation(format!("subject: {}", e)))?;
+    Ok(kind.base_address())
+}
+
+/// The base account, with the asset code when one is given
+fn context(query: &EvaluateQuery) -> Result<FlagContext, AppError> {
+    let context = FlagContext::new(subject(query)?);
+    Ok(match asset_code(query) {
+        Some(asset_code) => context.with_asset_code(asset_code),
+        None => context,
+    })
+}
+
+fn asset_code(query: &EvaluateQuery) -> Option<&str> {
+    query.asset_code.as_deref().map(str::trim).filter(|code| !code.is_empty())
+}
+
+/// Whether a flag is on for a subject, and why. Unknown flags are off.
+pub async fn evaluate_flag(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<EvaluateQuery>,
+) -> Result<Json<FlagEvaluation>, AppError> {
+    let context = context(&query)?;
+    let evaluation = state.feature_flags.evaluate(&name, &context).await;
+    Ok(Json(FlagEvaluation {
+        flag: name,
+        subject: context.subject,
+        asset_code: asset_code(&query).map(str::to_string),
+        enabled: evaluation.enabled,
+        reason: evaluation.reason,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCOUNT: &str = "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ";
+    const MUXED: &str = "MA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJUAAAAAAAAAAAACJUQ";
+
+    fn query(subject: Option<&str>) -> EvaluateQuery {
+        EvaluateQuery {
+            subject: subject.map(str::to_string),
+            asset_code: None,
+        }
+    }
+
+    #[test]
+    fn test_subject_is_a_base_account() {
+        assert_eq!(subject(&query(Some(ACCOUNT))).unwrap(), ACCOUNT);
+        assert_eq!(subject(&query(Some(MUXED))).unwrap(), ACCOUNT);
+    }
+
+    #[test]
+    fn test_asset_code_is_added_to_the_context() {
+        assert_eq!(context(&query(Some(ACCOUNT))).unwrap(), FlagContext::new(ACCOUNT));
+        let with_asset = EvaluateQuery {
+            asset_code: Some(" USDC ".to_string()),
+            ..query(Some(MUXED))
+        };
+        assert_eq!(
+            context(&with_asset).unwrap(),
+            FlagContext::new(ACCOUNT).with_asset_code("USDC")
+        );
+    }
+
+    #[test]
+    fn test_subject_is_required_and_validated() {
+        assert!(matches!(subject(&query(None)), Err(AppError::Validation(_))));
+        assert!(matches!(subject(&query(Some(" "))), Err(AppError::Validation(_))));
+        assert!(matches!(subject(&query(Some("GBAD"))), Err(AppError::Validation(_))));
+    }
+}
@@ -1,4 +1,20 @@
+pub mod accounts;
+pub mod assets;
+pub mod callback_batch;
+pub mod callback_schema;
+pub mod events;
 pub mod export;
+pub mod flags;
+pub mod notification_templates;
+pub mod partitions;
+pub mod quotes;
+pub mod readiness;
+pub mod sandbox;
+pub mod sep24;
+pub mod sep31;
+pub mod status;
+pub mod tokens;
+pub mod transactions;
 
 use crate::AppState;
 use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
@@ -7,6 +23,7 @@ use utoipa::ToSchema;
 
 pub mod settlements;
 pub mod webhook;
+pub mod webhook_subscriptions;
 pub mod settlements;
 pub mod graphql;
 pub mod settlements;
@@ -20,6 +37,9 @@ pub struct HealthStatus {
     version: String,
     db_primary: String,
     db_replica: Option<String>,
+    redis: crate::services::redis_health::RedisHealthStatus,
+    /// Horizon circuit breaker, `closed` or `open`
+    horizon_circuit: String,
 }
 
 pub async fn health(State(state): State<AppState>) -> impl IntoResponse {
@@ -39,6 +59,10 @@ pub async fn health(State(state): State<AppState>) -> impl IntoResponse {
         version: "0.1.0".to_string(),
         db_primary: db_primary_status.to_string(),
         db_replica: db_replica_status,
+        // Redis outages degrade features but never make the service unhealthy
+        redis: state.redis_health.status(),
+        // An open breaker fails Horizon calls fast; the API itself still serves
+        horizon_circuit: state.horizon_client.circuit_state(),
     };
 
     let status_code = if overall_healthy {
@@ -50,6 +74,26 @@ pub async fn health(State(state): State<AppState>) -> impl IntoResponse {
     (status_code, Json(health_response))
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct VersionInfo {
+    version: String,
+    environment: String,
+    sandbox: bool,
+    /// Set only in sandbox mode
+    banner: Option<String>,
+}
+
+/// What is deployed: `GET /version`
+pub async fn version(State(state): State<AppState>) -> impl IntoResponse {
+    let sandbox = state.deployment.is_sandbox();
+    Json(VersionInfo {
+        version: state.deployment.version.clone(),
+        environment: state.deployment.environment.clone(),
+        sandbox,
+        banner: sandbox.then(|| crate::middleware::sandbox::SANDBOX_BANNER.to_string()),
+    })
+}
+
 pub async fn callback_transaction(State(_state): State<ApiState>) -> impl IntoResponse {
     StatusCode::NOT_IMPLEMENTED
 }
@@ -0,0 +1,63 @@
+use crate::AppState;
+use crate::db::queries;
+use crate::error::AppError;
+use crate::services::notifications::{self, EVENT_VARIABLES};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct PutTemplateRequest {
+    pub template: String,
+}
+
+/// All templates, plus the variables each event type makes available
+pub async fn list_templates(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let templates = queries::list_notification_templates(&state.db).await?;
+    let variables: serde_json::Map<String, serde_json::Value> = EVENT_VARIABLES
+        .iter()
+        .map(|(event_type, variables)| (event_type.to_string(), serde_json::json!(variables)))
+        .collect();
+
+    Ok(Json(serde_json::json!({
+        "templates": templates,
+        "variables": variables,
+    })))
+}
+
+/// Create or replace the template of an event type in one locale
+pub async fn put_template(
+    State(state): State<AppState>,
+    Path((event_type, locale)): Path<(String, String)>,
+    Json(payload): Json<PutTemplateRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let locale = notifications::parse_locale(&locale)
+        .map_err(|e| AppError::Validation(format!("locale: {}", e)))?;
+    notifications::validate_template(&event_type, &payload.template)
+        .map_err(|e| AppError::Validation(format!("template: {}", e)))?;
+
+    let template =
+        queries::upsert_notification_template(&state.db, &event_type, &locale, &payload.template)
+            .await?;
+    tracing::info!(event_type = %event_type, locale = %locale, "Notification template saved");
+    Ok(Json(template))
+}
+
+pub async fn delete_template(
+    State(state): State<AppState>,
+    Path((event_type, locale)): Path<(String, String)>,
+) -> Result<impl IntoResponse, AppError> {
+    let locale = notifications::parse_locale(&locale)
+        .map_err(|e| AppError::Validation(format!("locale: {}", e)))?;
+    if !queries::delete_notification_template(&state.db, &event_type, &locale).await? {
+        return Err(AppError::NotFound(format!(
+            "No {} template for {}",
+            locale, event_type
+        )));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,22 @@
+use crate::db::partition::PartitionManager;
+use crate::error::AppError;
+use crate::middleware::auth::AdminPrincipal;
+use axum::{Extension, Json, extract::State, response::IntoResponse};
+
+/// Partitions of `transactions` with their bounds, estimated row counts and
+/// sizes: `GET /admin/partitions`
+pub async fn list_partitions(
+    State(manager): State<PartitionManager>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(manager.partitions().await?))
+}
+
+/// Run partition maintenance now instead of waiting for the timer:
+/// `POST /admin/partitions/run`. Waits for a run already in progress.
+pub async fn run_maintenance(
+    State(manager): State<PartitionManager>,
+    Extension(principal): Extension<AdminPrincipal>,
+) -> Result<impl IntoResponse, AppError> {
+    tracing::info!(admin = %principal.name, "Partition maintenance triggered");
+    Ok(Json(manager.run().await?))
+}
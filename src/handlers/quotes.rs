@@ -0,0 +1,14 @@
+use crate::AppState;
+use crate::error::AppError;
+use crate::stellar::quotes::QuoteRequest;
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+
+/// Request a firm SEP-38 quote and store it for a withdrawal to use:
+/// `POST /admin/quotes`
+pub async fn create_quote(
+    State(state): State<AppState>,
+    Json(request): Json<QuoteRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let quote = state.quotes.accept(&request).await?;
+    Ok((StatusCode::CREATED, Json(quote)))
+}
@@ -0,0 +1,248 @@
+//! Kubernetes probes: `GET /health/live` and `GET /health/ready`.
+//!
+//! Liveness only says the process is serving requests. It touches no
+//! dependency, so a database outage takes pods out of rotation instead of
+//! getting them restarted. Readiness actively checks Postgres, Redis and
+//! Horizon, each within [`CHECK_TIMEOUT`], and reports every dependency with
+//! its latency. Only an outage that stops callback ingestion makes the pod
+//! `down`; the rest leave it `degraded` and still serving.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::AppState;
+
+/// Limit on each dependency check
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Ledgers Horizon's ingestion may trail its Core node before it is degraded
+pub const MAX_HORIZON_LAG: i64 = 10;
+
+/// Ordered from best to worst, so the overall status is the worst check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeStatus {
+    Up,
+    /// Serving, with some features failing or running in a fallback mode
+    Degraded,
+    /// Not able to serve; taken out of rotation
+    Down,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DependencyCheck {
+    pub status: ProbeStatus,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Dependency-specific readings, e.g. Horizon's latest ledger
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub details: BTreeMap<&'static str, serde_json::Value>,
+}
+
+impl DependencyCheck {
+    /// `Up` on success, else `failed` with the error
+    fn new(latency: Duration, result: Result<(), String>, failed: ProbeStatus) -> Self {
+        let (status, error) = match result {
+            Ok(()) => (ProbeStatus::Up, None),
+            Err(e) => (failed, Some(e)),
+        };
+        Self {
+            status,
+            latency_ms: latency.as_millis() as u64,
+            error,
+            details: BTreeMap::new(),
+        }
+    }
+
+    fn with(mut self, name: &'static str, value: impl Into<serde_json::Value>) -> Self {
+        self.details.insert(name, value.into());
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub status: ProbeStatus,
+    pub checks: BTreeMap<&'static str, DependencyCheck>,
+}
+
+impl Readiness {
+    pub fn new(checks: BTreeMap<&'static str, DependencyCheck>) -> Self {
+        let status = checks
+            .values()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(ProbeStatus::Up);
+        Self { status, checks }
+    }
+
+    /// `503` only when down; a degraded pod keeps receiving traffic
+    pub fn status_code(&self) -> StatusCode {
+        match self.status {
+            ProbeStatus::Down => StatusCode::SERVICE_UNAVAILABLE,
+            ProbeStatus::Up | ProbeStatus::Degraded => StatusCode::OK,
+        }
+    }
+}
+
+/// `GET /health/live`
+pub async fn live() -> impl IntoResponse {
+    Json(serde_json::json!({ "status": ProbeStatus::Up }))
+}
+
+/// `GET /health/ready`
+pub async fn ready(State(state): State<AppState>) -> impl IntoResponse {
+    let pools = &state.pool_manager;
+    let replica = async {
+        match pools.replica() {
+            Some(replica) => Some(check_postgres(replica, ProbeStatus::Degraded).await),
+            None => None,
+        }
+    };
+    let (primary, replica, redis, horizon) = tokio::join!(
+        check_postgres(pools.primary(), ProbeStatus::Down),
+        replica,
+        check_redis(&state),
+        check_horizon(&state),
+    );
+
+    let mut checks = BTreeMap::from([("postgres", primary), ("horizon", horizon)]);
+    if let Some(replica) = replica {
+        checks.insert("postgres_replica", replica);
+    }
+    if let Some(redis) = redis {
+        checks.insert("redis", redis);
+    }
+
+    let readiness = Readiness::new(checks);
+    if readiness.status != ProbeStatus::Up {
+        tracing::warn!(status = ?readiness.status, "Readiness check not up");
+    }
+    (readiness.status_code(), Json(readiness))
+}
+
+async fn check_postgres(pool: &PgPool, failed: ProbeStatus) -> DependencyCheck {
+    let (latency, result) = timed(sqlx::query("SELECT 1").execute(pool)).await;
+    DependencyCheck::new(latency, result.map(drop), failed)
+}
+
+/// Only a Redis outage that makes required features fail closed is `down`.
+/// `None` when no Redis probe runs.
+async fn check_redis(state: &AppState) -> Option<DependencyCheck> {
+    let health = &state.redis_health;
+    let (latency, result) = timed(async { health.ping().await.transpose() }).await;
+    let result = match result {
+        Ok(None) => return None,
+        Ok(Some(())) => Ok(()),
+        Err(e) => Err(e),
+    };
+
+    let failed = if health.has_required() {
+        ProbeStatus::Down
+    } else {
+        ProbeStatus::Degraded
+    };
+    Some(DependencyCheck::new(latency, result, failed))
+}
+
+/// Horizon only backs payouts and verification, so never more than `degraded`
+async fn check_horizon(state: &AppState) -> DependencyCheck {
+    let client = &state.horizon_client;
+    let (latency, result) = timed(client.get_root()).await;
+
+    let check = match &result {
+        Ok(root) => {
+            let lag = root.core_latest_ledger - root.history_latest_ledger;
+            let behind = (lag > MAX_HORIZON_LAG)
+                .then(|| format!("ingestion is {} ledgers behind Stellar Core", lag));
+            DependencyCheck::new(latency, behind.map_or(Ok(()), Err), ProbeStatus::Degraded)
+                .with("core_latest_ledger", root.core_latest_ledger)
+                .with("history_latest_ledger", root.history_latest_ledger)
+        }
+        Err(e) => DependencyCheck::new(latency, Err(e.clone()), ProbeStatus::Degraded),
+    };
+    check.with("circuit", client.circuit_state())
+}
+
+/// Run `check` within [`CHECK_TIMEOUT`]
+async fn timed<T, E: Display>(
+    check: impl Future<Output = Result<T, E>>,
+) -> (Duration, Result<T, String>) {
+    let started = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err(format!("timed out after {}s", CHECK_TIMEOUT.as_secs())),
+    };
+    (started.elapsed(), result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(status: ProbeStatus) -> DependencyCheck {
+        let result = match status {
+            ProbeStatus::Up => Ok(()),
+            _ => Err("unreachable".to_string()),
+        };
+        DependencyCheck::new(Duration::from_millis(3), result, status)
+    }
+
+    #[test]
+    fn test_overall_status_is_the_worst_check() {
+        let all_up = Readiness::new(BTreeMap::from([
+            ("postgres", check(ProbeStatus::Up)),
+            ("horizon", check(ProbeStatus::Up)),
+        ]));
+        assert_eq!((all_up.status, all_up.status_code()), (ProbeStatus::Up, StatusCode::OK));
+
+        let degraded = Readiness::new(BTreeMap::from([
+            ("postgres", check(ProbeStatus::Up)),
+            ("horizon", check(ProbeStatus::Degraded)),
+        ]));
+        assert_eq!(
+            (degraded.status, degraded.status_code()),
+            (ProbeStatus::Degraded, StatusCode::OK)
+        );
+
+        let down = Readiness::new(BTreeMap::from([
+            ("postgres", check(ProbeStatus::Down)),
+            ("horizon", check(ProbeStatus::Degraded)),
+        ]));
+        assert_eq!(
+            (down.status, down.status_code()),
+            (ProbeStatus::Down, StatusCode::SERVICE_UNAVAILABLE)
+        );
+    }
+
+    #[test]
+    fn test_check_body() {
+        let degraded = check(ProbeStatus::Degraded).with("circuit", "open");
+        let body = serde_json::to_value(degraded).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "status": "degraded",
+                "latency_ms": 3,
+                "error": "unreachable",
+                "details": { "circuit": "open" },
+            })
+        );
+
+        let up = serde_json::to_value(check(ProbeStatus::Up)).unwrap();
+        assert!(up.get("error").is_none() && up.get("details").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failing_check_reports_its_error() {
+        let (_, result) = timed(async { Err::<(), _>("connection refused") }).await;
+        assert_eq!(result, Err("connection refused".to_string()));
+    }
+}
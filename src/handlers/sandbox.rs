@@ -0,0 +1,36 @@
+use crate::AppState;
+use crate::db::audit::{named_entity_id, AuditLog, ENTITY_SANDBOX};
+use crate::db::seed;
+use crate::error::AppError;
+use crate::middleware::auth::AdminPrincipal;
+use axum::{Extension, Json, extract::State, response::IntoResponse};
+
+/// Empty the data tables and load the sandbox fixtures again:
+/// `POST /admin/sandbox/reset`. Only routed when `APP_ENV=sandbox`.
+pub async fn reset(
+    State(state): State<AppState>,
+    Extension(principal): Extension<AdminPrincipal>,
+) -> Result<impl IntoResponse, AppError> {
+    if !state.deployment.is_sandbox() {
+        return Err(AppError::Forbidden("sandbox reset: not a sandbox".to_string()));
+    }
+
+    let report = seed::reset(&state.db).await?;
+    if let Some(fake) = state.horizon_client.fake() {
+        seed::sync_trustlines(&state.db, fake).await?;
+    }
+    state
+        .buffered_writes
+        .audit(AuditLog::new(
+            named_entity_id(ENTITY_SANDBOX, "sandbox"),
+            ENTITY_SANDBOX,
+            "reset",
+            None,
+            Some(serde_json::json!(report)),
+            principal.actor(),
+        ))
+        .await?;
+    tracing::warn!(?report, "Sandbox data reset");
+
+    Ok(Json(serde_json::json!({ "reset": true, "seeded": report })))
+}
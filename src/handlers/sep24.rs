@@ -0,0 +1,228 @@
+//! Anchor-facing transaction status in the SEP-24 single-transaction shape.
+
+use crate::AppState;
+use crate::db::models::TransactionStatusView;
+use crate::db::queries;
+use crate::domain::{TransactionStatus, UnknownStatus};
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct Sep24TransactionQuery {
+    pub id: Option<String>,
+    pub external_transaction_id: Option<String>,
+    pub stellar_transaction_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Sep24TransactionResponse {
+    pub transaction: Sep24Transaction,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Sep24Transaction {
+    pub id: String,
+    pub kind: String,
+    pub status: &'static str,
+    pub amount_in: String,
+    pub amount_in_asset: String,
+    pub started_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stellar_transaction_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_transaction_id: Option<String>,
+}
+
+/// Error body in the SEP shape: `{"error": "..."}` and nothing else
+pub struct Sep24Error(StatusCode, String);
+
+impl IntoResponse for Sep24Error {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<sqlx::Error> for Sep24Error {
+    fn from(e: sqlx::Error) -> Self {
+        tracing::error!("SEP-24 transaction lookup failed: {}", e);
+        Sep24Error(StatusCode::INTERNAL_SERVER_ERROR, "internal error".to_string())
+    }
+}
+
+/// Map an internal status to its SEP-24 status string.
+///
+/// No wildcard arm: adding a `TransactionStatus` variant without a mapping
+/// must fail to compile.
+pub fn sep24_status(status: TransactionStatus) -> &'static str {
+    match status {
+        TransactionStatus::Pending => "pending_anchor",
+        TransactionStatus::Processing => "pending_stellar",
+        TransactionStatus::Submitted => "pending_stellar",
+        TransactionStatus::Completed => "completed",
+        TransactionStatus::Failed => "error",
+        // Still ours to resolve; the anchor sees it as in progress
+        TransactionStatus::Dlq => "pending_anchor",
+        TransactionStatus::PendingTrustline => "pending_trust",
+        TransactionStatus::Refunded => "refunded",
+    }
+}
+
+impl Sep24Transaction {
+    /// Legacy status spellings are read as their canonical value; a status
+    /// that isn't one of them is an error, not a guess
+    fn from_view(view: TransactionStatusView) -> Result<Self, UnknownStatus> {
+        let status = TransactionStatus::from_legacy(&view.status)?;
+
+        Ok(Self {
+            id: view.id.to_string(),
+            kind: view.callback_type.unwrap_or_else(|| "deposit".to_string()),
+            status: sep24_status(status),
+            amount_in: view.amount.to_string(),
+            amount_in_asset: view.asset_code,
+            started_at: view.created_at,
+            completed_at: (status == TransactionStatus::Completed).then_some(view.updated_at),
+            stellar_transaction_id: view.payout_tx_hash,
+            external_transaction_id: view.anchor_transaction_id,
+        })
+    }
+}
+
+/// `GET /sep24/transaction` — look a transaction up by our id, the anchor's
+/// id (`external_transaction_id`) or the payout hash (`stellar_transaction_id`).
+pub async fn get_transaction(
+    State(state): State<AppState>,
+    Query(query): Query<Sep24TransactionQuery>,
+) -> Result<Json<Sep24TransactionResponse>, Sep24Error> {
+    let view = if let Some(id) = query.id.as_deref() {
+        match Uuid::parse_str(id) {
+            Ok(id) => queries::get_status_view_by_id(&state.db, id).await?,
+            Err(_) => None,
+        }
+    } else if let Some(external_id) = query.external_transaction_id.as_deref() {
+        queries::get_status_view_by_anchor_id(&state.db, external_id).await?
+    } else if let Some(hash) = query.stellar_transaction_id.as_deref() {
+        queries::get_status_view_by_payout_hash(&state.db, hash).await?
+    } else {
+        return Err(Sep24Error(
+            StatusCode::BAD_REQUEST,
+            "one of id, external_transaction_id or stellar_transaction_id is required".to_string(),
+        ));
+    };
+
+    let view = view.ok_or_else(|| {
+        Sep24Error(StatusCode::NOT_FOUND, "transaction not found".to_string())
+    })?;
+
+    let id = view.id;
+    let transaction = Sep24Transaction::from_view(view).map_err(|e| {
+        tracing::error!(transaction_id = %id, "SEP-24 transaction lookup failed: {}", e);
+        Sep24Error(StatusCode::INTERNAL_SERVER_ERROR, "internal error".to_string())
+    })?;
+
+    Ok(Json(Sep24TransactionResponse { transaction }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::types::BigDecimal;
+    use std::str::FromStr;
+
+    const SEP24_STATUSES: &[&str] = &[
+        "incomplete",
+        "pending_user_transfer_start",
+        "pending_user_transfer_complete",
+        "pending_external",
+        "pending_anchor",
+        "pending_stellar",
+        "pending_trust",
+        "pending_user",
+        "completed",
+        "refunded",
+        "expired",
+        "no_market",
+        "too_small",
+        "too_large",
+        "error",
+    ];
+
+    #[test]
+    fn test_every_internal_status_maps_to_a_sep24_status() {
+        for status in TransactionStatus::ALL {
+            assert!(
+                SEP24_STATUSES.contains(&sep24_status(*status)),
+                "{} maps to a non-SEP-24 status",
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn test_status_mapping_table() {
+        let expected = [
+            (TransactionStatus::Pending, "pending_anchor"),
+            (TransactionStatus::Processing, "pending_stellar"),
+            (TransactionStatus::Submitted, "pending_stellar"),
+            (TransactionStatus::Completed, "completed"),
+            (TransactionStatus::Failed, "error"),
+            (TransactionStatus::Dlq, "pending_anchor"),
+            (TransactionStatus::PendingTrustline, "pending_trust"),
+            (TransactionStatus::Refunded, "refunded"),
+        ];
+        assert_eq!(expected.len(), TransactionStatus::ALL.len());
+        for (status, sep24) in expected {
+            assert_eq!(sep24_status(status), sep24);
+        }
+    }
+
+    fn view(status: &str) -> TransactionStatusView {
+        TransactionStatusView {
+            id: Uuid::new_v4(),
+            amount: BigDecimal::from_str("100.50").unwrap(),
+            asset_code: "USDC".to_string(),
+            status: status.to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            anchor_transaction_id: Some("anchor-1".to_string()),
+            callback_type: None,
+            payout_tx_hash: Some("abc123".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_completed_transaction_shape() {
+        let body = serde_json::to_value(Sep24Transaction::from_view(view("completed")).unwrap()).unwrap();
+        assert_eq!(body["status"], "completed");
+        assert_eq!(body["kind"], "deposit");
+        assert_eq!(body["amount_in"], "100.50");
+        assert_eq!(body["stellar_transaction_id"], "abc123");
+        assert_eq!(body["external_transaction_id"], "anchor-1");
+        assert!(body.get("completed_at").is_some());
+    }
+
+    #[test]
+    fn test_pending_transaction_has_no_completed_at() {
+        let body = serde_json::to_value(Sep24Transaction::from_view(view("pending")).unwrap()).unwrap();
+        assert_eq!(body["status"], "pending_anchor");
+        assert!(body.get("completed_at").is_none());
+    }
+
+    #[test]
+    fn test_legacy_status_is_mapped_and_unknown_status_is_refused() {
+        let body = serde_json::to_value(Sep24Transaction::from_view(view("Completed")).unwrap()).unwrap();
+        assert_eq!(body["status"], "completed");
+        assert!(body.get("completed_at").is_some());
+
+        assert!(Sep24Transaction::from_view(view("on_hold")).is_err());
+    }
+}
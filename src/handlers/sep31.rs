@@ -0,0 +1,483 @@
+//! SEP-31 transaction callbacks from the sending anchor of a corridor where we
+//! are the receiving anchor.
+//!
+//! Each callback carries the full transaction in the SEP-31 shape. The first
+//! callback for a SEP-31 id creates our transaction; later ones move it
+//! through our state machine. Statuses we cannot map are stored verbatim in
+//! `callback_status` and the transaction is flagged for review.
+
+use crate::ApiState;
+use crate::db::{models::Transaction, queries, uow};
+use crate::domain::TransactionStatus;
+use crate::error::AppError;
+use crate::handlers::webhook::{RawCallback, duplicate_callback_response, insert_callback_transaction};
+use crate::services::{asset_verification, erasure};
+use crate::utils::correlation::CorrelationContext;
+use crate::validation::{
+    AMOUNT_INPUT_MAX_LEN, ALLOWED_ASSET_CODES, ANCHOR_TRANSACTION_ID_MAX_LEN, ASSET_CODE_MAX_LEN,
+    sanitize_string, validate_max_len, validate_positive_amount, validate_stellar_address,
+};
+use axum::{
+    Json,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use tracing::Instrument;
+
+/// `callback_type` of transactions created from SEP-31 callbacks
+pub const CALLBACK_TYPE_SEP31: &str = "sep31";
+
+/// Longest status string stored verbatim
+pub const SEP31_STATUS_MAX_LEN: usize = 64;
+
+/// SEP-31 callback body: `{"transaction": {...}}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Sep31Callback {
+    pub transaction: Sep31Transaction,
+}
+
+/// The SEP-31 transaction object. Fields we do not use are ignored so that
+/// additions to the SEP do not break callbacks.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Sep31Transaction {
+    pub id: String,
+    pub status: String,
+    pub amount_in: Option<String>,
+    /// SEP-38 asset string, e.g. `stellar:USDC:G...`
+    pub amount_in_asset: Option<String>,
+    pub amount_out: Option<String>,
+    /// SEP-38 asset string, e.g. `iso4217:USD`
+    pub amount_out_asset: Option<String>,
+    pub stellar_account_id: Option<String>,
+    pub stellar_transaction_id: Option<String>,
+    pub external_transaction_id: Option<String>,
+}
+
+/// An asset in our code + issuer form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetId {
+    pub code: String,
+    /// Issuing account for Stellar assets; `None` for fiat and native XLM
+    pub issuer: Option<String>,
+}
+
+/// Normalize a SEP-38 asset string (`stellar:CODE:ISSUER`, `stellar:native`
+/// or `iso4217:CODE`).
+pub fn parse_asset(raw: &str) -> Result<AssetId, AppError> {
+    let invalid = || AppError::Validation(format!("amount_in_asset: unsupported asset '{}'", raw));
+    let mut parts = raw.trim().split(':');
+
+    let asset = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some("stellar"), Some("native"), None, None) => AssetId {
+            code: "XLM".to_string(),
+            issuer: None,
+        },
+        (Some("stellar"), Some(code), Some(issuer), None) => {
+            validate_stellar_address(issuer).map_err(|err| AppError::Validation(err.to_string()))?;
+            AssetId {
+                code: code.to_string(),
+                issuer: Some(issuer.to_string()),
+            }
+        }
+        (Some("iso4217"), Some(code), None, None) => AssetId {
+            code: code.to_string(),
+            issuer: None,
+        },
+        _ => return Err(invalid()),
+    };
+
+    let well_formed = !asset.code.is_empty()
+        && asset.code.len() <= ASSET_CODE_MAX_LEN
+        && asset
+            .code
+            .chars()
+            .all(|ch| ch.is_ascii_uppercase() || ch.is_ascii_digit());
+    if !well_formed {
+        return Err(invalid());
+    }
+    Ok(asset)
+}
+
+/// Map a SEP-31 status into our state machine. `None` for statuses we do not
+/// know; those are stored verbatim and flagged for review.
+pub fn map_sep31_status(status: &str) -> Option<TransactionStatus> {
+    match status {
+        "pending_sender"
+        | "pending_stellar"
+        | "pending_customer_info_update"
+        | "pending_transaction_info_update" => Some(TransactionStatus::Pending),
+        "pending_receiver" | "pending_external" => Some(TransactionStatus::Processing),
+        "completed" => Some(TransactionStatus::Completed),
+        "refunded" | "expired" | "error" => Some(TransactionStatus::Failed),
+        _ => None,
+    }
+}
+
+/// What a callback does to a transaction we already have
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sep31Update {
+    /// Same status as last time: a replay
+    Duplicate,
+    Apply {
+        /// New internal status, if it changes
+        new_status: Option<TransactionStatus>,
+        review_reason: Option<String>,
+    },
+}
+
+/// Decide how a callback reporting `incoming` changes a transaction that is
+/// in `current` and last reported `stored_callback_status`.
+pub fn plan_update(
+    current: TransactionStatus,
+    stored_callback_status: Option<&str>,
+    incoming: &str,
+) -> Sep31Update {
+    if stored_callback_status == Some(incoming) {
+        return Sep31Update::Duplicate;
+    }
+
+    match map_sep31_status(incoming) {
+        None => Sep31Update::Apply {
+            new_status: None,
+            review_reason: Some(format!("unknown SEP-31 status '{}'", incoming)),
+        },
+        Some(mapped) if mapped == current => Sep31Update::Apply {
+            new_status: None,
+            review_reason: None,
+        },
+        Some(mapped) if !current.can_transition_to(mapped) => Sep31Update::Apply {
+            new_status: None,
+            review_reason: Some(format!(
+                "SEP-31 status '{}' ({}) cannot move a {} transaction",
+                incoming, mapped, current
+            )),
+        },
+        Some(mapped) => Sep31Update::Apply {
+            new_status: Some(mapped),
+            review_reason: None,
+        },
+    }
+}
+
+struct ValidatedSep31Callback {
+    id: String,
+    status: String,
+    amount: BigDecimal,
+    asset: AssetId,
+    stellar_account: Option<String>,
+}
+
+fn validate_sep31_callback(tx: Sep31Transaction) -> Result<ValidatedSep31Callback, AppError> {
+    let id = sanitize_string(&tx.id);
+    let status = sanitize_string(&tx.status);
+    if id.is_empty() {
+        return Err(AppError::Validation("id: is required".to_string()));
+    }
+    if status.is_empty() {
+        return Err(AppError::Validation("status: is required".to_string()));
+    }
+    validate_max_len("id", &id, ANCHOR_TRANSACTION_ID_MAX_LEN)
+        .map_err(|err| AppError::Validation(err.to_string()))?;
+    validate_max_len("status", &status, SEP31_STATUS_MAX_LEN)
+        .map_err(|err| AppError::Validation(err.to_string()))?;
+
+    let amount_str = sanitize_string(tx.amount_in.as_deref().unwrap_or_default());
+    validate_max_len("amount_in", &amount_str, AMOUNT_INPUT_MAX_LEN)
+        .map_err(|err| AppError::Validation(err.to_string()))?;
+    let amount = amount_str
+        .parse::<BigDecimal>()
+        .map_err(|_| AppError::Validation("amount_in: must be a valid decimal".to_string()))?;
+    validate_positive_amount(&amount).map_err(|err| AppError::Validation(err.to_string()))?;
+
+    let asset = parse_asset(
+        tx.amount_in_asset
+            .as_deref()
+            .ok_or_else(|| AppError::Validation("amount_in_asset: is required".to_string()))?,
+    )?;
+
+    let stellar_account = tx
+        .stellar_account_id
+        .map(|account| sanitize_string(&account))
+        .filter(|account| !account.is_empty());
+    if let Some(account) = &stellar_account {
+        validate_stellar_address(account).map_err(|err| AppError::Validation(err.to_string()))?;
+    }
+
+    Ok(ValidatedSep31Callback {
+        id,
+        status,
+        amount,
+        asset,
+        stellar_account,
+    })
+}
+
+/// Accept only assets we settle: Stellar assets must be in the registry with
+/// the same issuer and enabled; others must be on the built-in allowlist or
+/// registered by code.
+async fn ensure_asset_supported(pool: &sqlx::PgPool, asset: &AssetId) -> Result<(), AppError> {
+    let registered = match &asset.issuer {
+        Some(issuer) => queries::get_asset_by_code_and_issuer(pool, &asset.code, issuer).await?,
+        None if ALLOWED_ASSET_CODES.contains(&asset.code.as_str()) => return Ok(()),
+        None => queries::get_registry_asset(pool, &asset.code, None).await?,
+    };
+
+    match registered {
+        Some(registered) if registered.enabled != Some(false) => Ok(()),
+        _ => Err(AppError::Validation(format!(
+            "amount_in_asset: {} is not a supported asset",
+            asset.code
+        ))),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Sep31CallbackResponse {
+    pub transaction_id: String,
+    pub status: String,
+    pub callback_status: String,
+    pub needs_review: bool,
+}
+
+enum Outcome {
+    Created(Transaction, bool),
+    Updated(Transaction, bool),
+    Duplicate(Transaction),
+}
+
+/// `POST /callback/sep31` — create or advance the transaction for a SEP-31 id
+pub async fn callback(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let pool = &state.app_state.db;
+    let parsed: Sep31Callback = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("Invalid SEP-31 callback: {}", e)))?;
+    let callback = validate_sep31_callback(parsed.transaction)?;
+    ensure_asset_supported(pool, &callback.asset).await?;
+
+    let raw_body = String::from_utf8_lossy(&body);
+    let ctx = CorrelationContext::for_callback(&headers, Some(&callback.id));
+    let span = ctx.span("sep31_callback");
+    let correlation = &ctx;
+    let outcome = uow::run(pool, |uow| Box::pin(async move {
+        queries::lock_callback_id(uow.conn(), &format!("{}:{}", CALLBACK_TYPE_SEP31, callback.id)).await?;
+        let existing =
+            queries::get_transaction_by_callback_id(uow.conn(), CALLBACK_TYPE_SEP31, &callback.id).await?;
+
+        let Some(existing) = existing else {
+            let Some(stellar_account) = callback.stellar_account.clone() else {
+                return Err(AppError::Validation(
+                    "stellar_account_id: is required for a new transaction".to_string(),
+                ));
+            };
+            asset_verification::ensure_deposits_allowed(
+                pool,
+                &state.app_state.feature_flags,
+                &callback.asset.code,
+                callback.asset.issuer.as_deref(),
+            )
+            .await?;
+            erasure::ensure_callbacks_allowed(pool, &stellar_account).await?;
+
+            let mapped = map_sep31_status(&callback.status);
+            let mut tx = Transaction::new(
+                stellar_account,
+                callback.amount.clone(),
+                callback.asset.code.clone(),
+                Some(callback.id.clone()),
+                Some(CALLBACK_TYPE_SEP31.to_string()),
+                Some(callback.status.clone()),
+            );
+            tx.asset_issuer = callback.asset.issuer.clone();
+            tx.status = mapped.unwrap_or(TransactionStatus::Pending).to_string();
+            let tx = tx.with_correlation(correlation);
+
+            let raw = RawCallback {
+                schema_version: CALLBACK_TYPE_SEP31,
+                body: &raw_body,
+            };
+            let inserted = insert_callback_transaction(uow, tx, Some(raw)).await?;
+            if mapped.is_none() {
+                let reason = format!("unknown SEP-31 status '{}'", callback.status);
+                queries::flag_transaction_for_review(uow.conn(), inserted.id, &reason).await?;
+            }
+            return Ok(Outcome::Created(inserted, mapped.is_none()));
+        };
+
+        let current = TransactionStatus::parse(&existing.status).unwrap_or(TransactionStatus::Pending);
+        let (new_status, mut review_reason) =
+            match plan_update(current, existing.callback_status.as_deref(), &callback.status) {
+                Sep31Update::Duplicate => return Ok(Outcome::Duplicate(existing)),
+                Sep31Update::Apply {
+                    new_status,
+                    review_reason,
+                } => (new_status, review_reason),
+            };
+        if review_reason.is_none() && existing.amount != callback.amount {
+            review_reason = Some(format!(
+                "SEP-31 amount_in changed from {} to {}",
+                existing.amount, callback.amount
+            ));
+        }
+
+        queries::set_callback_status(uow.conn(), existing.id, &callback.status).await?;
+        if let Some(reason) = &review_reason {
+            queries::flag_transaction_for_review(uow.conn(), existing.id, reason).await?;
+        }
+        let updated = match new_status {
+            Some(new_status) => queries::update_transaction_status(
+                uow.conn(),
+                existing.id,
+                &[existing.status.as_str()],
+                new_status.as_str(),
+                "anchor",
+            )
+            .await?
+            .map(|(_, updated)| updated),
+            None => None,
+        };
+        let updated = match updated {
+            Some(updated) => updated,
+            None => Transaction {
+                callback_status: Some(callback.status.clone()),
+                ..existing
+            },
+        };
+        Ok(Outcome::Updated(updated, review_reason.is_some()))
+    }).instrument(span))
+    .await?;
+
+    let (status, tx, needs_review) = match outcome {
+        Outcome::Duplicate(existing) => {
+            return Ok(duplicate_callback_response(
+                state.app_state.duplicate_callback_response,
+                &existing,
+            ));
+        }
+        Outcome::Created(tx, needs_review) => {
+            if needs_review {
+                tracing::warn!(transaction_id = %tx.id, correlation_id = %ctx, "SEP-31 callback with unknown status flagged for review");
+            }
+            (StatusCode::CREATED, tx, needs_review)
+        }
+        Outcome::Updated(tx, needs_review) => {
+            if needs_review {
+                // Updates belong to the transaction's lifecycle, not this request's
+                let correlation_id = CorrelationContext::for_transaction(&tx);
+                tracing::warn!(transaction_id = %tx.id, %correlation_id, "SEP-31 callback flagged for review");
+            }
+            (StatusCode::OK, tx, needs_review)
+        }
+    };
+
+    Ok((
+        status,
+        Json(Sep31CallbackResponse {
+            transaction_id: tx.id.to_string(),
+            status: tx.status,
+            callback_status: tx.callback_status.unwrap_or_default(),
+            needs_review,
+        }),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ISSUER: &str = "GBBD47IF6LWK7P7MDEVSCWR7DPUWV3NY3DTQEVFL4NAT4AQH3ZLLFLA5";
+
+    #[test]
+    fn test_parse_stellar_asset() {
+        assert_eq!(
+            parse_asset(&format!("stellar:USDC:{}", ISSUER)).unwrap(),
+            AssetId {
+                code: "USDC".to_string(),
+                issuer: Some(ISSUER.to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_fiat_and_native_assets() {
+        assert_eq!(parse_asset("iso4217:USD").unwrap().code, "USD");
+        assert_eq!(parse_asset("iso4217:USD").unwrap().issuer, None);
+        assert_eq!(parse_asset("stellar:native").unwrap().code, "XLM");
+    }
+
+    #[test]
+    fn test_parse_asset_rejects_malformed_strings() {
+        for raw in ["USDC", "stellar:USDC", "stellar:USDC:BAD", "iso4217:usd", "sep38:USD", "iso4217:"] {
+            assert!(parse_asset(raw).is_err(), "{} should be rejected", raw);
+        }
+    }
+
+    #[test]
+    fn test_status_mapping() {
+        assert_eq!(map_sep31_status("pending_sender"), Some(TransactionStatus::Pending));
+        assert_eq!(map_sep31_status("pending_receiver"), Some(TransactionStatus::Processing));
+        assert_eq!(map_sep31_status("pending_external"), Some(TransactionStatus::Processing));
+        assert_eq!(map_sep31_status("completed"), Some(TransactionStatus::Completed));
+        assert_eq!(map_sep31_status("refunded"), Some(TransactionStatus::Failed));
+        assert_eq!(map_sep31_status("pending_something_new"), None);
+    }
+
+    #[test]
+    fn test_replayed_status_is_duplicate() {
+        assert_eq!(
+            plan_update(TransactionStatus::Processing, Some("pending_receiver"), "pending_receiver"),
+            Sep31Update::Duplicate
+        );
+    }
+
+    #[test]
+    fn test_unknown_status_is_flagged_not_applied() {
+        let Sep31Update::Apply { new_status, review_reason } =
+            plan_update(TransactionStatus::Processing, Some("pending_receiver"), "on_hold")
+        else {
+            panic!("expected an update");
+        };
+        assert_eq!(new_status, None);
+        assert!(review_reason.unwrap().contains("on_hold"));
+    }
+
+    #[test]
+    fn test_status_after_terminal_is_flagged() {
+        let Sep31Update::Apply { new_status, review_reason } =
+            plan_update(TransactionStatus::Completed, Some("completed"), "refunded")
+        else {
+            panic!("expected an update");
+        };
+        assert_eq!(new_status, None);
+        assert!(review_reason.is_some());
+    }
+
+    #[test]
+    fn test_regression_is_flagged() {
+        let Sep31Update::Apply { new_status, review_reason } =
+            plan_update(TransactionStatus::Processing, Some("pending_receiver"), "pending_sender")
+        else {
+            panic!("expected an update");
+        };
+        assert_eq!(new_status, None);
+        assert!(review_reason.unwrap().contains("cannot move a processing transaction"));
+    }
+
+    #[test]
+    fn test_status_with_same_mapping_only_updates_callback_status() {
+        assert_eq!(
+            plan_update(TransactionStatus::Processing, Some("pending_receiver"), "pending_external"),
+            Sep31Update::Apply {
+                new_status: None,
+                review_reason: None,
+            }
+        );
+    }
+}
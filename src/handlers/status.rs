@@ -0,0 +1,8 @@
+use crate::AppState;
+use axum::{Json, extract::State, response::IntoResponse};
+
+/// Compact health snapshot for dashboards: `GET /admin/status-snapshot`.
+/// Always 200; fields that could not be read in time are `null`.
+pub async fn status_snapshot(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.status_snapshot.snapshot().await)
+}
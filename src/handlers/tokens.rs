@@ -0,0 +1,77 @@
+use crate::AppState;
+use crate::db::models::ApiToken;
+use crate::error::AppError;
+use crate::middleware::auth::AdminPrincipal;
+use crate::services::api_tokens::{token_status, CreateTokenRequest};
+use axum::{
+    Extension,
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Token row plus its lifecycle status
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    #[serde(flatten)]
+    pub token: ApiToken,
+    pub status: &'static str,
+}
+
+impl TokenResponse {
+    /// The token with its status at `now`
+    pub fn at(token: ApiToken, now: DateTime<Utc>) -> Self {
+        Self {
+            status: token_status(&token, now),
+            token,
+        }
+    }
+}
+
+/// Returned once, at creation; the secret cannot be retrieved later
+#[derive(Debug, Serialize)]
+pub struct CreatedTokenResponse {
+    #[serde(flatten)]
+    pub token: TokenResponse,
+    pub secret: String,
+}
+
+/// Issue a scoped token: `POST /admin/tokens`
+pub async fn create_token(
+    State(state): State<AppState>,
+    Extension(principal): Extension<AdminPrincipal>,
+    Json(payload): Json<CreateTokenRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let (token, secret) = state.api_tokens.create(payload, &principal.actor()).await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedTokenResponse {
+            token: TokenResponse::at(token, state.clock.now()),
+            secret,
+        }),
+    ))
+}
+
+/// Introspect a token: `GET /admin/tokens/:id`
+pub async fn get_token(
+    State(state): State<AppState>,
+    Extension(principal): Extension<AdminPrincipal>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let token = state.api_tokens.inspect(id, &principal.actor()).await?;
+    Ok(Json(TokenResponse::at(token, state.clock.now())))
+}
+
+/// Revoke a token: `DELETE /admin/tokens/:id`
+pub async fn revoke_token(
+    State(state): State<AppState>,
+    Extension(principal): Extension<AdminPrincipal>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let token = state.api_tokens.revoke(id, &principal.actor()).await?;
+    Ok(Json(TokenResponse::at(token, state.clock.now())))
+}
@@ -0,0 +1,470 @@
+//! Reading stored transactions back: `GET /transactions` and
+//! `GET /transactions/:id`.
+
+use crate::db::models::Transaction;
+use crate::db::queries::{self, TransactionListFilter};
+use crate::domain::TransactionStatus;
+use crate::error::AppError;
+use crate::handlers::export;
+use crate::services::api_tokens::TokenScope;
+use crate::utils::cursor;
+use crate::validation::validate_asset_code;
+use crate::{ApiState, AppState};
+use axum::{
+    Extension,
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Page size when `limit` is omitted
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TransactionQuery {
+    pub stellar_account: Option<String>,
+    pub status: Option<String>,
+    pub asset_code: Option<String>,
+    /// Earliest `created_at`, inclusive
+    pub from: Option<String>,
+    /// Latest `created_at`. A timestamp is exclusive; a date includes that day
+    pub to: Option<String>,
+    pub limit: Option<i64>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    /// `forward` (older items, the default) or `backward` (newer items)
+    pub direction: Option<String>,
+}
+
+/// The filters a page was selected with, after a scoped token's
+/// restrictions were applied
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct TransactionFilters {
+    pub stellar_account: Option<String>,
+    pub status: Option<String>,
+    pub asset_code: Option<String>,
+    /// Inclusive
+    pub from: Option<DateTime<Utc>>,
+    /// Exclusive
+    pub to: Option<DateTime<Utc>>,
+    pub limit: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TransactionPage {
+    /// Newest first
+    #[schema(value_type = Vec<crate::schemas::TransactionSchema>)]
+    pub data: Vec<Transaction>,
+    pub meta: PageMeta,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PageMeta {
+    /// Pass as `cursor`, with the same `direction`, for the next page;
+    /// `null` when there are no items
+    pub next_cursor: Option<String>,
+    /// Whether another page follows in this direction
+    pub has_more: bool,
+    pub filters: TransactionFilters,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+impl TransactionQuery {
+    /// Validate the query string. A scoped token's asset replaces the
+    /// requested one and its window clips the requested range, so the query
+    /// string has no way to widen a scope.
+    pub fn filters(&self, scope: Option<&TokenScope>) -> Result<TransactionFilters, AppError> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(AppError::Validation(format!(
+                "limit: must be between 1 and {}, got {}",
+                MAX_PAGE_SIZE, limit
+            )));
+        }
+
+        let status = match non_empty(&self.status) {
+            Some(raw) => Some(
+                TransactionStatus::parse(raw)
+                    .ok_or_else(|| {
+                        let known: Vec<&str> =
+                            TransactionStatus::ALL.iter().map(|s| s.as_str()).collect();
+                        AppError::Validation(format!(
+                            "status: '{}' is not one of {}",
+                            raw,
+                            known.join(", ")
+                        ))
+                    })?
+                    .as_str()
+                    .to_string(),
+            ),
+            None => None,
+        };
+
+        let asset_code = match non_empty(&self.asset_code) {
+            Some(code) => {
+                validate_asset_code(code).map_err(|err| AppError::Validation(err.to_string()))?;
+                Some(code.to_string())
+            }
+            None => None,
+        };
+
+        let from = non_empty(&self.from)
+            .map(|raw| {
+                export::parse_date(raw).map_err(|e| AppError::Validation(format!("from: {}", e)))
+            })
+            .transpose()?;
+        let to = non_empty(&self.to)
+            .map(|raw| {
+                let to = export::parse_date(raw)
+                    .map_err(|e| AppError::Validation(format!("to: {}", e)))?;
+                // A bare date covers the whole day
+                Ok::<_, AppError>(if raw.len() == 10 { to + Duration::days(1) } else { to })
+            })
+            .transpose()?;
+        if let (Some(from), Some(to)) = (from, to) {
+            if from >= to {
+                return Err(AppError::Validation("from: must be before to".to_string()));
+            }
+        }
+
+        let (asset_code, (from, to)) = match scope {
+            Some(scope) => (scope.asset_code.clone().or(asset_code), scope.clip_window(from, to)),
+            None => (asset_code, (from, to)),
+        };
+
+        Ok(TransactionFilters {
+            stellar_account: non_empty(&self.stellar_account).map(str::to_string),
+            status,
+            asset_code,
+            from,
+            to,
+            limit,
+        })
+    }
+
+    /// Whether the page reads towards newer items
+    pub fn backward(&self) -> Result<bool, AppError> {
+        match non_empty(&self.direction) {
+            None | Some("forward") => Ok(false),
+            Some("backward") => Ok(true),
+            Some(other) => Err(AppError::Validation(format!(
+                "direction: must be forward or backward, got '{}'",
+                other
+            ))),
+        }
+    }
+
+    pub fn cursor(&self) -> Result<Option<(DateTime<Utc>, Uuid)>, AppError> {
+        non_empty(&self.cursor)
+            .map(|raw| {
+                cursor::decode(raw).map_err(|e| AppError::Validation(format!("cursor: {}", e)))
+            })
+            .transpose()
+    }
+}
+
+/// List transactions
+///
+/// Newest first, one page at a time. Follow `next_cursor` while `has_more`
+/// to visit every matching transaction exactly once, across partitions,
+/// even while new transactions arrive. `direction=backward` pages towards
+/// newer transactions instead.
+#[utoipa::path(
+    get,
+    path = "/transactions",
+    params(
+        ("stellar_account" = Option<String>, Query, description = "Exact account"),
+        ("status" = Option<String>, Query, description = "Canonical status, e.g. completed"),
+        ("asset_code" = Option<String>, Query, description = "Asset code, e.g. USD"),
+        ("from" = Option<String>, Query, description = "Earliest created_at (inclusive), RFC 3339 or YYYY-MM-DD"),
+        ("to" = Option<String>, Query, description = "Latest created_at: exclusive timestamp, or a date to include that day"),
+        ("limit" = Option<i64>, Query, description = "Page size, 1 to 200 (default 50)"),
+        ("cursor" = Option<String>, Query, description = "next_cursor of the previous page"),
+        ("direction" = Option<String>, Query, description = "forward (older items, default) or backward (newer items)")
+    ),
+    responses(
+        (status = 200, description = "One page of transactions", body = TransactionPage),
+        (status = 400, description = "Invalid filter, limit or cursor"),
+        (status = 500, description = "Database error")
+    ),
+    tag = "Transactions"
+)]
+pub async fn list_transactions(
+    State(state): State<AppState>,
+    scope: Option<Extension<TokenScope>>,
+    Query(query): Query<TransactionQuery>,
+) -> Result<Json<TransactionPage>, AppError> {
+    list_page(&state.db, &query, scope.as_ref().map(|Extension(scope)| scope)).await
+}
+
+/// [`list_transactions`] for routers holding an [`ApiState`]
+pub async fn list_transactions_api(
+    State(api_state): State<ApiState>,
+    scope: Option<Extension<TokenScope>>,
+    Query(query): Query<TransactionQuery>,
+) -> Result<Json<TransactionPage>, AppError> {
+    list_page(&api_state.app_state.db, &query, scope.as_ref().map(|Extension(scope)| scope)).await
+}
+
+async fn list_page(
+    pool: &sqlx::PgPool,
+    query: &TransactionQuery,
+    scope: Option<&TokenScope>,
+) -> Result<Json<TransactionPage>, AppError> {
+    let filters = query.filters(scope)?;
+    let after = query.cursor()?;
+    let backward = query.backward()?;
+    let filter = TransactionListFilter {
+        stellar_account: filters.stellar_account.as_deref(),
+        status: filters.status.as_deref(),
+        asset_code: filters.asset_code.as_deref(),
+        from: filters.from,
+        until: filters.to,
+    };
+
+    // One extra row tells whether there is another page
+    let mut data =
+        queries::list_transactions_page(pool, &filter, after, backward, filters.limit + 1).await?;
+    let has_more = data.len() as i64 > filters.limit;
+    data.truncate(filters.limit as usize);
+    // The row furthest from the cursor, before backward pages are put newest first
+    let next_cursor = data.last().map(|tx| cursor::encode(tx.created_at, tx.id));
+    if backward {
+        data.reverse();
+    }
+
+    Ok(Json(TransactionPage {
+        data,
+        meta: PageMeta { next_cursor, has_more, filters },
+    }))
+}
+
+/// Get a specific transaction
+///
+/// Returns details for a specific transaction by ID
+#[utoipa::path(
+    get,
+    path = "/transactions/{id}",
+    params(
+        ("id" = String, Path, description = "Transaction ID")
+    ),
+    responses(
+        (status = 200, description = "Transaction found", body = crate::schemas::TransactionSchema),
+        (status = 202, description = "Callback queued and not stored yet", body = crate::schemas::TransactionSchema),
+        (status = 404, description = "Transaction not found"),
+        (status = 500, description = "Database error")
+    ),
+    tag = "Transactions"
+)]
+pub async fn get_transaction(
+    State(state): State<AppState>,
+    scope: Option<Extension<TokenScope>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    find_transaction(&state.db, scope.as_ref().map(|Extension(scope)| scope), id).await
+}
+
+/// [`get_transaction`] for routers holding an [`ApiState`]
+pub async fn get_transaction_api(
+    State(api_state): State<ApiState>,
+    scope: Option<Extension<TokenScope>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    find_transaction(&api_state.app_state.db, scope.as_ref().map(|Extension(scope)| scope), id)
+        .await
+}
+
+async fn find_transaction(
+    pool: &sqlx::PgPool,
+    scope: Option<&TokenScope>,
+    id: Uuid,
+) -> Result<(StatusCode, Json<Transaction>), AppError> {
+    let (status, transaction) = match queries::get_transaction(pool, id).await {
+        Ok(transaction) => (StatusCode::OK, transaction),
+        // Acknowledged asynchronously and not drained yet
+        Err(sqlx::Error::RowNotFound) => match queued_transaction(pool, id).await? {
+            Some(transaction) => (StatusCode::ACCEPTED, transaction),
+            None => return Err(AppError::NotFound(format!("Transaction {} not found", id))),
+        },
+        Err(e) => return Err(AppError::DatabaseError(e.to_string())),
+    };
+
+    // Out-of-scope rows are indistinguishable from missing ones
+    if scope.is_some_and(|scope| !scope.contains(&transaction)) {
+        return Err(AppError::NotFound(format!("Transaction {} not found", id)));
+    }
+
+    Ok((status, Json(transaction)))
+}
+
+/// The transaction a pending ingestion outbox entry will be stored as
+async fn queued_transaction(pool: &sqlx::PgPool, id: Uuid) -> Result<Option<Transaction>, AppError> {
+    let entry = queries::get_ingestion_entry(pool, id).await?;
+    Ok(entry
+        .filter(|entry| entry.status == "pending")
+        .and_then(|entry| serde_json::from_value(entry.payload).ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use sqlx::types::BigDecimal;
+    use std::str::FromStr;
+
+    fn at(raw: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(raw).unwrap().with_timezone(&Utc)
+    }
+
+    fn validation_message(query: TransactionQuery) -> String {
+        match query.filters(None) {
+            Err(AppError::Validation(message)) => message,
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_defaults() {
+        let filters = TransactionQuery::default().filters(None).unwrap();
+        assert_eq!(
+            filters,
+            TransactionFilters {
+                stellar_account: None,
+                status: None,
+                asset_code: None,
+                from: None,
+                to: None,
+                limit: DEFAULT_PAGE_SIZE,
+            }
+        );
+    }
+
+    #[test]
+    fn test_dates_and_timestamps() {
+        let query = TransactionQuery {
+            from: Some("2026-01-01".to_string()),
+            to: Some("2026-01-03".to_string()),
+            ..Default::default()
+        };
+        let filters = query.filters(None).unwrap();
+        assert_eq!(filters.from, Some(at("2026-01-01T00:00:00Z")));
+        // The whole of January 3rd
+        assert_eq!(filters.to, Some(at("2026-01-04T00:00:00Z")));
+
+        let query = TransactionQuery {
+            to: Some("2026-01-03T12:00:00Z".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(query.filters(None).unwrap().to, Some(at("2026-01-03T12:00:00Z")));
+    }
+
+    #[test]
+    fn test_rejects_out_of_range_limits() {
+        for limit in [0, -1, MAX_PAGE_SIZE + 1] {
+            let message =
+                validation_message(TransactionQuery { limit: Some(limit), ..Default::default() });
+            assert!(message.starts_with("limit:"), "{}", message);
+        }
+        let query = TransactionQuery { limit: Some(MAX_PAGE_SIZE), ..Default::default() };
+        assert_eq!(query.filters(None).unwrap().limit, MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_rejects_bad_filters() {
+        let message = validation_message(TransactionQuery {
+            status: Some("pending_anchor".to_string()),
+            ..Default::default()
+        });
+        assert!(message.contains("pending, processing, completed"), "{}", message);
+
+        let message = validation_message(TransactionQuery {
+            asset_code: Some("usd".to_string()),
+            ..Default::default()
+        });
+        assert!(message.starts_with("asset_code:"), "{}", message);
+
+        let message = validation_message(TransactionQuery {
+            from: Some("yesterday".to_string()),
+            ..Default::default()
+        });
+        assert!(message.starts_with("from:"), "{}", message);
+
+        let message = validation_message(TransactionQuery {
+            from: Some("2026-01-02".to_string()),
+            to: Some("2026-01-02T00:00:00Z".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(message, "from: must be before to");
+    }
+
+    #[test]
+    fn test_rejects_invalid_cursors() {
+        let query = TransactionQuery { cursor: Some("garbage".to_string()), ..Default::default() };
+        assert!(matches!(query.cursor(), Err(AppError::Validation(m)) if m.starts_with("cursor:")));
+
+        let id = Uuid::new_v4();
+        let created_at = at("2026-01-01T00:00:00.123456Z");
+        let query =
+            TransactionQuery { cursor: Some(cursor::encode(created_at, id)), ..Default::default() };
+        assert_eq!(query.cursor().unwrap(), Some((created_at, id)));
+    }
+
+    #[test]
+    fn test_direction() {
+        assert!(!TransactionQuery::default().backward().unwrap());
+        let query = |direction: &str| TransactionQuery {
+            direction: Some(direction.to_string()),
+            ..Default::default()
+        };
+        assert!(!query("forward").backward().unwrap());
+        assert!(query("backward").backward().unwrap());
+        assert!(matches!(
+            query("sideways").backward(),
+            Err(AppError::Validation(m)) if m.starts_with("direction:")
+        ));
+    }
+
+    #[test]
+    fn test_scope_cannot_be_widened() {
+        let scope = TokenScope {
+            routes: vec!["GET /transactions".to_string()],
+            asset_code: Some("USDC".to_string()),
+            from: NaiveDate::from_ymd_opt(2026, 1, 1),
+            to: NaiveDate::from_ymd_opt(2026, 3, 31),
+        };
+        let query = TransactionQuery {
+            asset_code: Some("EURC".to_string()),
+            from: Some("2000-01-01".to_string()),
+            to: Some("2026-02-15".to_string()),
+            ..Default::default()
+        };
+        let filters = query.filters(Some(&scope)).unwrap();
+        assert_eq!(filters.asset_code.as_deref(), Some("USDC"));
+        assert_eq!(filters.from, Some(at("2026-01-01T00:00:00Z")));
+        // Narrowing inside the scope still works
+        assert_eq!(filters.to, Some(at("2026-02-16T00:00:00Z")));
+    }
+
+    #[test]
+    fn test_amounts_serialize_as_strings() {
+        let tx = Transaction::new(
+            "GCAIJAF37NNZGSH6PO442CPK5CEHJNOXAYNHLDJBTQJKF7NIQ6CZ7DQW".to_string(),
+            BigDecimal::from_str("100.50").unwrap(),
+            "USD".to_string(),
+            None,
+            None,
+            None,
+        );
+        let json = serde_json::to_value(&tx).unwrap();
+        assert_eq!(json["amount"], "100.50");
+    }
+}
@@ -1,14 +1,34 @@
-use crate::AppState;
-use crate::db::{models::Transaction, queries};
+use crate::{ApiState, AppState};
+use crate::config::DuplicateCallbackResponse;
+use crate::db::audit::{AuditLog, ENTITY_TRANSACTION};
+use crate::db::{models::Transaction, queries, uow};
+use crate::domain::TransactionStatus;
 use crate::error::AppError;
+use crate::handlers::callback_schema::{
+    CallbackPayload, CallbackSchemaVersion, NormalizedCallback, parse_callback,
+};
+use crate::metrics;
+use crate::services::{asset_verification, erasure, transaction};
+use crate::services::ingestion::AckMode;
+use crate::services::quotes::QuoteRejection;
+use crate::utils::correlation::{CorrelationContext, CORRELATION_HEADER};
 use crate::validation::{
     AMOUNT_INPUT_MAX_LEN, ANCHOR_TRANSACTION_ID_MAX_LEN, CALLBACK_STATUS_MAX_LEN,
-    CALLBACK_TYPE_MAX_LEN, sanitize_string, validate_asset_code, validate_max_len,
-    validate_positive_amount, validate_stellar_address,
+    CALLBACK_TYPE_MAX_LEN, QUOTE_ID_MAX_LEN, sanitize_string, validate_asset_code, validate_max_len,
+    validate_positive_amount, validate_stellar_address, validate_stellar_strkey,
+};
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
 };
-use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
 use serde::{Deserialize, Serialize};
 use sqlx::types::BigDecimal;
+use tracing::Instrument;
+use utoipa::ToSchema;
+use uuid::Uuid;
 
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -16,24 +36,49 @@ pub struct WebhookTransactionRequest {
     pub stellar_address: String,
     pub amount: String,
     pub asset_code: String,
+    /// Needed when the registry has more than one issuer of `asset_code`
+    pub asset_issuer: Option<String>,
     pub anchor_transaction_id: Option<String>,
     pub callback_type: Option<String>,
     pub callback_status: Option<String>,
+    /// Firm SEP-38 quote; withdrawals only
+    pub quote_id: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct WebhookTransactionResponse {
     pub id: String,
     pub status: String,
+    pub outcome: CallbackOutcome,
+}
+
+pub(crate) struct ValidatedWebhookTransaction {
+    /// The `G` account, also for a muxed account
+    pub(crate) stellar_address: String,
+    pub(crate) muxed_id: Option<u64>,
+    pub(crate) amount: BigDecimal,
+    pub(crate) asset_code: String,
+    /// As named by the callback; see [`asset_verification::resolve_asset_issuer`]
+    pub(crate) asset_issuer: Option<String>,
+    pub(crate) anchor_transaction_id: Option<String>,
+    pub(crate) callback_type: Option<String>,
+    pub(crate) callback_status: Option<String>,
+    pub(crate) quote_id: Option<String>,
 }
 
-struct ValidatedWebhookTransaction {
-    stellar_address: String,
-    amount: BigDecimal,
-    asset_code: String,
-    anchor_transaction_id: Option<String>,
-    callback_type: Option<String>,
-    callback_status: Option<String>,
+impl From<NormalizedCallback> for WebhookTransactionRequest {
+    fn from(callback: NormalizedCallback) -> Self {
+        Self {
+            stellar_address: callback.stellar_account,
+            amount: callback.amount,
+            asset_code: callback.asset_code,
+            asset_issuer: callback.asset_issuer,
+            anchor_transaction_id: Some(callback.anchor_transaction_id),
+            callback_type: callback.callback_type,
+            callback_status: callback.callback_status,
+            quote_id: callback.quote_id,
+        }
+    }
 }
 
 fn sanitize_optional(value: Option<String>) -> Option<String> {
@@ -42,19 +87,24 @@ fn sanitize_optional(value: Option<String>) -> Option<String> {
         .and_then(|v| if v.is_empty() { None } else { Some(v) })
 }
 
-fn validate_webhook_payload(
+pub(crate) fn validate_webhook_payload(
     payload: WebhookTransactionRequest,
 ) -> Result<ValidatedWebhookTransaction, AppError> {
     let stellar_address = sanitize_string(&payload.stellar_address);
     let asset_code = sanitize_string(&payload.asset_code);
     let amount_str = sanitize_string(&payload.amount);
+    let asset_issuer = sanitize_optional(payload.asset_issuer);
     let anchor_transaction_id = sanitize_optional(payload.anchor_transaction_id);
     let callback_type = sanitize_optional(payload.callback_type);
     let callback_status = sanitize_optional(payload.callback_status);
+    let quote_id = sanitize_optional(payload.quote_id);
 
-    validate_stellar_address(&stellar_address)
+    let account = validate_stellar_strkey(&stellar_address)
         .map_err(|err| AppError::Validation(err.to_string()))?;
     validate_asset_code(&asset_code).map_err(|err| AppError::Validation(err.to_string()))?;
+    if let Some(issuer) = &asset_issuer {
+        validate_stellar_address(issuer).map_err(|err| AppError::Validation(err.to_string()))?;
+    }
     validate_max_len("amount", &amount_str, AMOUNT_INPUT_MAX_LEN)
         .map_err(|err| AppError::Validation(err.to_string()))?;
     if let Some(anchor_transaction_id) = &anchor_transaction_id {
@@ -73,6 +123,15 @@ fn validate_webhook_payload(
         validate_max_len("callback_status", callback_status, CALLBACK_STATUS_MAX_LEN)
             .map_err(|err| AppError::Validation(err.to_string()))?;
     }
+    if let Some(quote_id) = &quote_id {
+        validate_max_len("quote_id", quote_id, QUOTE_ID_MAX_LEN)
+            .map_err(|err| AppError::Validation(err.to_string()))?;
+        if callback_type.as_deref() != Some(CALLBACK_TYPE_WITHDRAWAL) {
+            return Err(AppError::Validation(
+                "quote_id: only accepted on withdrawal callbacks".to_string(),
+            ));
+        }
+    }
 
     let amount = amount_str
         .parse::<BigDecimal>()
@@ -80,23 +139,506 @@ fn validate_webhook_payload(
     validate_positive_amount(&amount).map_err(|err| AppError::Validation(err.to_string()))?;
 
     Ok(ValidatedWebhookTransaction {
-        stellar_address,
+        stellar_address: account.base_address(),
+        muxed_id: account.muxed_id(),
         amount,
         asset_code,
+        asset_issuer,
         anchor_transaction_id,
         callback_type,
         callback_status,
+        quote_id,
     })
 }
 
+pub const DUPLICATE_HEADER: &str = "x-duplicate";
+
+/// Set on new callback acknowledgments: `sync` (201, stored) or `async`
+/// (202, queued under the returned id)
+pub const INGESTION_MODE_HEADER: &str = "x-ingestion-mode";
+
+/// The only callback type that may carry a `quote_id`
+pub const CALLBACK_TYPE_WITHDRAWAL: &str = "withdrawal";
+
+/// Response for a callback whose anchor transaction is already stored.
+///
+/// Anchor platform versions disagree on what a duplicate acknowledgment looks
+/// like, so the body is chosen per deployment; the status, header and metric
+/// are the same in both modes.
+pub fn duplicate_callback_response(
+    mode: DuplicateCallbackResponse,
+    existing: &Transaction,
+) -> Response {
+    tracing::info!(
+        transaction_id = %existing.id,
+        anchor_transaction_id = ?existing.anchor_transaction_id,
+        "Duplicate callback received"
+    );
+    duplicate_response(
+        mode,
+        &CallbackResponse {
+            transaction_id: existing.id.to_string(),
+            status: existing.status.clone(),
+            callback_status: existing.callback_status.clone(),
+            outcome: CallbackOutcome::Duplicate,
+            correlation_id: Some(CorrelationContext::for_transaction(existing).to_string()),
+        },
+    )
+}
+
+/// [`duplicate_callback_response`] echoing the response the callback was
+/// last given, as remembered in Redis
+pub fn cached_callback_response(
+    mode: DuplicateCallbackResponse,
+    original: &CallbackResponse,
+) -> Response {
+    tracing::info!(
+        transaction_id = %original.transaction_id,
+        "Duplicate callback answered from the idempotency cache"
+    );
+    duplicate_response(mode, original)
+}
+
+fn duplicate_response(mode: DuplicateCallbackResponse, original: &CallbackResponse) -> Response {
+    metrics::record_duplicate_callback();
+    let body = match mode {
+        DuplicateCallbackResponse::Echo => serde_json::json!({
+            "transaction_id": original.transaction_id,
+            "status": original.status,
+        }),
+        DuplicateCallbackResponse::Ack => serde_json::json!({ "duplicate": true }),
+    };
+
+    let mut response = (StatusCode::OK, Json(body)).into_response();
+    response
+        .headers_mut()
+        .insert(DUPLICATE_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+/// The status a callback reports that differs from the one `stored` last;
+/// `None` for a replay. A callback without a status has nothing to update.
+fn status_update<'a>(stored: Option<&str>, incoming: Option<&'a str>) -> Option<&'a str> {
+    incoming.filter(|incoming| stored != Some(*incoming))
+}
+
+/// A callback's response as remembered for its replays, with the deposit the
+/// callback reported. `R` is a borrowed response when caching.
+#[derive(Serialize, Deserialize)]
+struct CachedCallback<R = CallbackResponse> {
+    #[serde(flatten)]
+    response: R,
+    /// Absent from responses cached before deposits were remembered
+    #[serde(default)]
+    amount: Option<String>,
+    #[serde(default)]
+    asset_code: Option<String>,
+}
+
+impl CachedCallback {
+    /// The remembered `(amount, asset_code)`
+    fn deposit(&self) -> Option<(BigDecimal, &str)> {
+        let amount = self.amount.as_deref()?.parse().ok()?;
+        Some((amount, self.asset_code.as_deref()?))
+    }
+}
+
+/// Answer a replayed callback from the idempotency cache, before the
+/// database is touched. A callback reporting another amount or asset than
+/// the cached one is rejected like it is against the stored transaction.
+async fn find_cached_duplicate(
+    state: &AppState,
+    anchor_transaction_id: Option<&str>,
+    callback_status: Option<&str>,
+    deposit: (&BigDecimal, &str),
+) -> Result<Option<Response>, AppError> {
+    let Some(anchor_transaction_id) = anchor_transaction_id else {
+        return Ok(None);
+    };
+    let Some(cached) = state.idempotency.cached_callback(anchor_transaction_id).await? else {
+        return Ok(None);
+    };
+    let cached = match serde_json::from_str::<CachedCallback>(&cached) {
+        Ok(cached) => cached,
+        Err(e) => {
+            tracing::warn!(anchor_transaction_id, "Unreadable cached callback response: {}", e);
+            return Ok(None);
+        }
+    };
+    // Older entries without the deposit are checked against the database
+    let Some((amount, asset_code)) = cached.deposit() else {
+        return Ok(None);
+    };
+    check_same_deposit(anchor_transaction_id, (&amount, asset_code), deposit)?;
+    // A status update, not a replay
+    if status_update(cached.response.callback_status.as_deref(), callback_status).is_some() {
+        return Ok(None);
+    }
+    Ok(Some(cached_callback_response(state.duplicate_callback_response, &cached.response)))
+}
+
+/// What a callback did to an anchor transaction that was already stored
+pub enum ExistingTransaction {
+    /// Repeated the transaction's last report
+    Replay(Transaction),
+    /// Reported a new status
+    Updated(Transaction),
+}
+
+/// Fails with [`AppError::Validation`] when a callback reports another amount
+/// or asset than is stored for its anchor transaction id. Both are
+/// `(amount, asset_code)`.
+pub(crate) fn check_same_deposit(
+    anchor_transaction_id: &str,
+    stored: (&BigDecimal, &str),
+    reported: (&BigDecimal, &str),
+) -> Result<(), AppError> {
+    if reported.0 != stored.0 {
+        return Err(AppError::Validation(format!(
+            "amount: {} differs from the amount {} stored for anchor transaction {}",
+            reported.0, stored.0, anchor_transaction_id
+        )));
+    }
+    if reported.1 != stored.1 {
+        return Err(AppError::Validation(format!(
+            "asset_code: {} differs from the asset {} stored for anchor transaction {}",
+            reported.1, stored.1, anchor_transaction_id
+        )));
+    }
+    Ok(())
+}
+
+/// Apply a later callback to the transaction stored for its anchor id: store
+/// the reported status, and move the transaction when the status maps to a
+/// different one. Statuses that map to nothing are stored and flagged for
+/// review.
+///
+/// Fails with [`AppError::Validation`] when the amount or asset differs from
+/// the stored one, replay or not, and with [`AppError::InvalidTransition`]
+/// when the move is not allowed, e.g. `completed` to `pending`. Nothing is
+/// written in either case.
+pub async fn update_callback_transaction(
+    pool: &sqlx::PgPool,
+    anchor_transaction_id: &str,
+    callback_status: Option<&str>,
+    amount: &BigDecimal,
+    asset_code: &str,
+) -> Result<ExistingTransaction, AppError> {
+    uow::run(pool, |uow| Box::pin(async move {
+        let existing = queries::lock_transaction_by_anchor_id(uow.conn(), anchor_transaction_id)
+            .await?
+            .ok_or_else(|| {
+                AppError::NotFound(format!("transaction for anchor id {}", anchor_transaction_id))
+            })?;
+        check_same_deposit(
+            anchor_transaction_id,
+            (&existing.amount, &existing.asset_code),
+            (amount, asset_code),
+        )?;
+        let Some(callback_status) =
+            status_update(existing.callback_status.as_deref(), callback_status)
+        else {
+            return Ok(ExistingTransaction::Replay(existing));
+        };
+
+        let current = TransactionStatus::from_legacy(&existing.status)
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        let next = TransactionStatus::from_legacy(callback_status).ok();
+        if let Some(next) = next {
+            if let Err(e) = transaction::check_transition(existing.id, current, next) {
+                tracing::warn!(
+                    transaction_id = %existing.id,
+                    anchor_transaction_id,
+                    callback_status,
+                    "Rejected callback moving a transaction from {} to {}",
+                    current,
+                    next
+                );
+                return Err(e);
+            }
+        }
+
+        queries::set_callback_status(uow.conn(), existing.id, callback_status).await?;
+        let moved = match next {
+            Some(next) if next != current => {
+                let reason = format!("callback status '{}'", callback_status);
+                let moved =
+                    transaction::transition(uow.conn(), existing.id, next, Some(&reason), "anchor")
+                        .await?;
+                Some(moved)
+            }
+            Some(_) => None,
+            None => {
+                let reason = format!("unknown callback status '{}'", callback_status);
+                queries::flag_transaction_for_review(uow.conn(), existing.id, &reason).await?;
+                None
+            }
+        };
+        Ok(ExistingTransaction::Updated(moved.unwrap_or(Transaction {
+            callback_status: Some(callback_status.to_string()),
+            ..existing
+        })))
+    }))
+    .await
+}
+
+/// Answer a callback whose anchor transaction is already stored; `None`
+/// when the transaction is new
+async fn answer_if_stored(
+    state: &AppState,
+    anchor_transaction_id: Option<&str>,
+    callback_status: Option<&str>,
+    deposit: (&BigDecimal, &str),
+) -> Result<Option<ExistingCallback>, AppError> {
+    let Some(anchor_transaction_id) = anchor_transaction_id else {
+        return Ok(None);
+    };
+    if queries::get_transaction_by_anchor_id(&state.db, anchor_transaction_id).await?.is_none() {
+        return Ok(None);
+    }
+    existing_callback(state, anchor_transaction_id, callback_status, deposit).await.map(Some)
+}
+
+/// How a callback for a stored anchor transaction was answered
+enum ExistingCallback {
+    Duplicate(Response),
+    /// The update's response, already cached for replays
+    Updated(CallbackResponse),
+}
+
+/// Answer a callback whose anchor transaction is stored, found up front or
+/// by losing the race to store it to a concurrent callback
+async fn existing_callback(
+    state: &AppState,
+    anchor_transaction_id: &str,
+    callback_status: Option<&str>,
+    (amount, asset_code): (&BigDecimal, &str),
+) -> Result<ExistingCallback, AppError> {
+    let updated = match update_callback_transaction(
+        &state.db,
+        anchor_transaction_id,
+        callback_status,
+        amount,
+        asset_code,
+    )
+    .await?
+    {
+        ExistingTransaction::Replay(existing) => {
+            return Ok(ExistingCallback::Duplicate(duplicate_callback_response(
+                state.duplicate_callback_response,
+                &existing,
+            )));
+        }
+        ExistingTransaction::Updated(updated) => updated,
+    };
+    tracing::info!(
+        transaction_id = %updated.id,
+        anchor_transaction_id,
+        callback_status = ?updated.callback_status,
+        status = %updated.status,
+        "Callback updated transaction"
+    );
+    let correlation_id = CorrelationContext::for_transaction(&updated).to_string();
+    let deposit = (&updated.amount, updated.asset_code.as_str());
+    let response = CallbackResponse {
+        transaction_id: updated.id.to_string(),
+        status: updated.status.clone(),
+        callback_status: updated.callback_status.clone(),
+        outcome: CallbackOutcome::Updated,
+        correlation_id: Some(correlation_id),
+    };
+    cache_callback_response(state, Some(anchor_transaction_id), deposit, &response).await;
+    Ok(ExistingCallback::Updated(response))
+}
+
+/// Remember the latest response to a callback, and the deposit it reported,
+/// for its replays
+async fn cache_callback_response(
+    state: &AppState,
+    anchor_transaction_id: Option<&str>,
+    (amount, asset_code): (&BigDecimal, &str),
+    response: &CallbackResponse,
+) {
+    let Some(anchor_transaction_id) = anchor_transaction_id else {
+        return;
+    };
+    let cached = CachedCallback {
+        response,
+        amount: Some(amount.to_string()),
+        asset_code: Some(asset_code.to_string()),
+    };
+    match serde_json::to_string(&cached) {
+        Ok(body) => state.idempotency.cache_callback(anchor_transaction_id, &body).await,
+        Err(e) => {
+            tracing::warn!(anchor_transaction_id, "Failed to serialize callback response: {}", e)
+        }
+    }
+}
+
+/// Event queued for outbound webhook subscribers when a transaction is created
+pub const EVENT_TRANSACTION_CREATED: &str = "transaction.created";
+
+/// Verbatim callback body, captured alongside the transaction
+pub struct RawCallback<'a> {
+    pub schema_version: &'a str,
+    pub body: &'a str,
+}
+
+/// Insert a new transaction, its audit entry and its outbox event in one unit
+/// of work, so a failure in any of them leaves nothing behind.
+///
+/// The raw body capture runs in a savepoint: if it fails, only the capture is
+/// rolled back and the transaction is still stored.
+///
+/// Fails with [`AppError::DuplicateCallback`], having written nothing, when
+/// another transaction holds the anchor transaction id.
+pub async fn persist_callback_transaction(
+    pool: &sqlx::PgPool,
+    tx: Transaction,
+    raw: Option<RawCallback<'_>>,
+) -> Result<Transaction, AppError> {
+    uow::run(pool, |uow| Box::pin(insert_callback_transaction(uow, tx, raw))).await
+}
+
+/// [`persist_callback_transaction`] inside a unit of work the caller owns
+pub async fn insert_callback_transaction(
+    uow: &mut uow::UnitOfWork<'_>,
+    tx: Transaction,
+    raw: Option<RawCallback<'_>>,
+) -> Result<Transaction, AppError> {
+    // Backstop for replays racing past the duplicate checks: the later claim
+    // waits for the earlier one to commit, then finds the id taken
+    if let Some(anchor_transaction_id) = tx.anchor_transaction_id.as_deref() {
+        if !queries::claim_anchor_transaction_id(uow.conn(), anchor_transaction_id, tx.id).await? {
+            return Err(AppError::DuplicateCallback(anchor_transaction_id.to_string()));
+        }
+    }
+    let inserted = queries::insert_transaction(uow.conn(), &tx).await?;
+    // Re-checked under the write: the quote may have been used or expired since
+    // the handler verified it
+    if let Some(quote_id) = &inserted.quote_id {
+        if !queries::link_quote_to_transaction(uow.conn(), quote_id, inserted.id).await? {
+            return Err(QuoteRejection::Unavailable(quote_id.clone()).into());
+        }
+    }
+    record_creation(uow, &inserted).await?;
+
+    if let Some(raw) = raw {
+        let transaction_id = inserted.id;
+        let captured = uow
+            .savepoint(|sp| Box::pin(async move {
+                queries::insert_raw_callback(sp.conn(), transaction_id, raw.schema_version, raw.body)
+                    .await
+            }))
+            .await;
+        if let Err(e) = captured {
+            tracing::warn!(transaction_id = %transaction_id, "Failed to capture raw callback: {}", e);
+        }
+    }
+
+    Ok(inserted)
+}
+
+/// Audit entry and [`EVENT_TRANSACTION_CREATED`] event for a transaction a
+/// callback just inserted
+pub(crate) async fn record_creation(
+    uow: &mut uow::UnitOfWork<'_>,
+    inserted: &Transaction,
+) -> Result<(), AppError> {
+    AuditLog::log_creation(
+        uow.conn(),
+        inserted.id,
+        ENTITY_TRANSACTION,
+        serde_json::json!({
+            "status": inserted.status,
+            "amount": inserted.amount.to_string(),
+            "asset_code": inserted.asset_code,
+            "anchor_transaction_id": inserted.anchor_transaction_id,
+            "quote_id": inserted.quote_id,
+        }),
+        "anchor",
+    )
+    .await?;
+    queries::publish_event(
+        uow.conn(),
+        EVENT_TRANSACTION_CREATED,
+        &serde_json::json!({
+            "transaction_id": inserted.id,
+            "status": inserted.status,
+            "amount": inserted.amount.to_string(),
+            "asset_code": inserted.asset_code,
+            "asset_issuer": inserted.asset_issuer,
+            "callback_type": inserted.callback_type,
+            "correlation_id": inserted.correlation_id,
+        }),
+        inserted.correlation_id.as_deref(),
+    )
+    .await?;
+    Ok(())
+}
+
 pub async fn transaction_callback(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(payload): Json<WebhookTransactionRequest>,
-) -> Result<impl IntoResponse, AppError> {
+) -> Result<Response, AppError> {
+    let result = handle_transaction_callback(state, headers, payload).await;
+    metrics::record_callback(callback_metric(&result));
+    result
+}
+
+async fn handle_transaction_callback(
+    state: AppState,
+    headers: HeaderMap,
+    payload: WebhookTransactionRequest,
+) -> Result<Response, AppError> {
     // Validate and sanitize all inputs before any DB interaction.
     let payload = validate_webhook_payload(payload)?;
+    let anchor_transaction_id = payload.anchor_transaction_id.clone();
+    let callback_status = payload.callback_status.clone();
+    let amount = payload.amount.clone();
+    let asset_code = payload.asset_code.clone();
+    let deposit = (&amount, asset_code.as_str());
+    if let Some(response) = find_cached_duplicate(
+        &state,
+        anchor_transaction_id.as_deref(),
+        callback_status.as_deref(),
+        deposit,
+    )
+    .await?
+    {
+        return Ok(response);
+    }
+    if let Some(answered) = answer_if_stored(
+        &state,
+        anchor_transaction_id.as_deref(),
+        callback_status.as_deref(),
+        deposit,
+    )
+    .await?
+    {
+        return Ok(legacy_callback_response(answered));
+    }
+    let asset_issuer = asset_verification::resolve_asset_issuer(
+        &state.db,
+        &payload.asset_code,
+        payload.asset_issuer.as_deref(),
+    )
+    .await?;
+    asset_verification::ensure_deposits_allowed(
+        &state.db,
+        &state.feature_flags,
+        &payload.asset_code,
+        asset_issuer.as_deref(),
+    )
+    .await?;
+    erasure::ensure_callbacks_allowed(&state.db, &payload.stellar_address).await?;
+    if let Some(quote_id) = &payload.quote_id {
+        let issuer = asset_issuer.as_deref();
+        state.quotes.verify(quote_id, &payload.asset_code, issuer, &payload.amount).await?;
+    }
 
-    let tx = Transaction::new(
+    let mut tx = Transaction::new(
         payload.stellar_address,
         payload.amount,
         payload.asset_code,
@@ -104,33 +646,150 @@ pub async fn transaction_callback(
         payload.callback_type,
         payload.callback_status,
     );
+    tx.asset_issuer = asset_issuer;
+    tx.quote_id = payload.quote_id;
+    tx.muxed_id = payload.muxed_id.map(BigDecimal::from);
+    let ctx = CorrelationContext::for_callback(&headers, tx.anchor_transaction_id.as_deref());
+    let tx = tx.with_correlation(&ctx);
 
-    let inserted = queries::insert_transaction(&state.db, &tx).await?;
+    let inserted = match persist_callback_transaction(&state.db, tx, None)
+        .instrument(ctx.span("callback"))
+        .await
+    {
+        Err(AppError::DuplicateCallback(anchor_transaction_id)) => {
+            let answered = existing_callback(
+                &state,
+                &anchor_transaction_id,
+                callback_status.as_deref(),
+                deposit,
+            )
+            .await?;
+            return Ok(legacy_callback_response(answered));
+        }
+        result => result?,
+    };
+    cache_callback_response(
+        &state,
+        anchor_transaction_id.as_deref(),
+        deposit,
+        &CallbackResponse {
+            transaction_id: inserted.id.to_string(),
+            status: inserted.status.clone(),
+            callback_status: inserted.callback_status.clone(),
+            outcome: CallbackOutcome::Created,
+            correlation_id: Some(ctx.to_string()),
+        },
+    )
+    .await;
 
     Ok((
         StatusCode::CREATED,
         Json(WebhookTransactionResponse {
             id: inserted.id.to_string(),
             status: inserted.status,
+            outcome: CallbackOutcome::Created,
         }),
-    ))
+    )
+        .into_response())
+}
+
+/// The status a callback's answer is counted under in
+/// `callbacks_received_total`
+fn callback_metric(result: &Result<Response, AppError>) -> metrics::CallbackStatus {
+    use metrics::CallbackStatus;
+    match result {
+        Ok(response) if response.headers().contains_key(DUPLICATE_HEADER) => {
+            CallbackStatus::Duplicate
+        }
+        Ok(response) => match response.status() {
+            StatusCode::CREATED => CallbackStatus::Created,
+            StatusCode::ACCEPTED => CallbackStatus::Queued,
+            _ => CallbackStatus::Updated,
+        },
+        Err(AppError::Validation(_) | AppError::BadRequest(_)) => CallbackStatus::ValidationFailed,
+        Err(AppError::Database(_) | AppError::DatabaseError(_)) => CallbackStatus::DbError,
+        Err(_) => CallbackStatus::Rejected,
+    }
+}
+
+/// `POST /callback` response for a callback of a stored transaction
+fn callback_response(answered: ExistingCallback) -> Response {
+    match answered {
+        ExistingCallback::Duplicate(response) => response,
+        ExistingCallback::Updated(updated) => (StatusCode::OK, Json(updated)).into_response(),
+    }
+}
+
+/// `POST /callback/transaction` response for a callback of a stored transaction
+fn legacy_callback_response(answered: ExistingCallback) -> Response {
+    match answered {
+        ExistingCallback::Duplicate(response) => response,
+        ExistingCallback::Updated(updated) => (
+            StatusCode::OK,
+            Json(WebhookTransactionResponse {
+                id: updated.transaction_id,
+                status: updated.status,
+                outcome: updated.outcome,
+            }),
+        )
+            .into_response(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const ACCOUNT: &str = "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ";
+    /// `ACCOUNT` with muxed id 1234
+    const MUXED_ACCOUNT: &str =
+        "MA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJUAAAAAAAAAAE2JUG6";
+
     fn valid_payload() -> WebhookTransactionRequest {
         WebhookTransactionRequest {
-            stellar_address: "G".to_owned() + &"A".repeat(55),
+            stellar_address: ACCOUNT.to_string(),
             amount: "42.50".to_string(),
             asset_code: "USD".to_string(),
+            asset_issuer: None,
             anchor_transaction_id: Some("anchor-1".to_string()),
             callback_type: Some("deposit".to_string()),
             callback_status: Some("completed".to_string()),
+            quote_id: None,
         }
     }
 
+    #[test]
+    fn cached_callback_remembers_the_deposit() {
+        let response = CallbackResponse {
+            transaction_id: Uuid::new_v4().to_string(),
+            status: "pending".to_string(),
+            callback_status: Some("pending_anchor".to_string()),
+            outcome: CallbackOutcome::Created,
+            correlation_id: None,
+        };
+        let cached = serde_json::to_string(&CachedCallback {
+            response: &response,
+            amount: Some("42.50".to_string()),
+            asset_code: Some("USD".to_string()),
+        })
+        .unwrap();
+
+        let cached: CachedCallback = serde_json::from_str(&cached).unwrap();
+        assert_eq!(cached.response.transaction_id, response.transaction_id);
+        let (amount, asset_code) = cached.deposit().unwrap();
+        assert_eq!(amount, "42.5".parse::<BigDecimal>().unwrap());
+        assert_eq!(asset_code, "USD");
+
+        // Entries cached before the deposit was remembered fall through to the database
+        let older: CachedCallback = serde_json::from_value(serde_json::json!({
+            "transaction_id": response.transaction_id,
+            "status": "pending",
+            "callback_status": "pending_anchor",
+        }))
+        .unwrap();
+        assert!(older.deposit().is_none());
+    }
+
     #[test]
     fn webhook_payload_rejects_unknown_fields() {
         let raw = r#"{
@@ -159,6 +818,46 @@ mod tests {
         assert!(parsed.is_err());
     }
 
+    #[test]
+    fn validate_webhook_payload_names_the_failed_strkey_check() {
+        let cases = [
+            ("G".to_owned() + &"A".repeat(55), "checksum does not match"),
+            (ACCOUNT.to_lowercase(), "uppercase base32"),
+            (format!("{}=", ACCOUNT), "padding"),
+            (format!("{}Y", &ACCOUNT[..ACCOUNT.len() - 1]), "checksum does not match"),
+            (
+                "SA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJUWVG".to_string(),
+                "version byte",
+            ),
+            ("GSHORT".to_string(), "got 6"),
+        ];
+        for (address, expected) in cases {
+            let mut payload = valid_payload();
+            payload.stellar_address = address.clone();
+            match validate_webhook_payload(payload) {
+                Err(AppError::Validation(message)) => {
+                    assert!(message.starts_with("stellar_address: "), "{}", message);
+                    assert!(message.contains(expected), "{}: {}", address, message);
+                }
+                _ => panic!("{} was accepted", address),
+            }
+        }
+    }
+
+    #[test]
+    fn validate_webhook_payload_stores_base_account_of_muxed_account() {
+        let mut payload = valid_payload();
+        payload.stellar_address = MUXED_ACCOUNT.to_string();
+
+        let parsed = validate_webhook_payload(payload).expect("muxed account is valid");
+        assert_eq!(parsed.stellar_address, ACCOUNT);
+        assert_eq!(parsed.muxed_id, Some(1234));
+
+        let parsed = validate_webhook_payload(valid_payload()).unwrap();
+        assert_eq!(parsed.stellar_address, ACCOUNT);
+        assert_eq!(parsed.muxed_id, None);
+    }
+
     #[test]
     fn validate_webhook_payload_rejects_invalid_asset_code() {
         let mut payload = valid_payload();
@@ -218,6 +917,18 @@ mod tests {
         assert!(parsed.is_err());
     }
 
+    #[test]
+    fn validate_webhook_payload_checks_a_named_asset_issuer() {
+        let mut payload = valid_payload();
+        payload.asset_issuer = Some(ACCOUNT.to_string());
+        let parsed = validate_webhook_payload(payload).expect("issuer is a valid account");
+        assert_eq!(parsed.asset_issuer.as_deref(), Some(ACCOUNT));
+
+        let mut payload = valid_payload();
+        payload.asset_issuer = Some("not-an-issuer".to_string());
+        assert!(validate_webhook_payload(payload).is_err());
+    }
+
     #[test]
     fn validate_webhook_payload_sanitizes_control_characters_in_optional_fields() {
         let mut payload = valid_payload();
@@ -231,6 +942,99 @@ mod tests {
         assert_eq!(parsed.callback_status.as_deref(), Some("completed"));
     }
 
+    #[test]
+    fn normalized_callback_is_validated_like_webhook_payload() {
+        let callback = NormalizedCallback {
+            anchor_transaction_id: "anchor-1".to_string(),
+            amount: "42.50".to_string(),
+            asset_code: "USD".to_string(),
+            asset_issuer: None,
+            stellar_account: ACCOUNT.to_string(),
+            callback_type: Some("deposit".to_string()),
+            callback_status: Some("completed".to_string()),
+            quote_id: None,
+        };
+        let parsed = validate_webhook_payload(callback.clone().into()).expect("callback should be valid");
+        assert_eq!(parsed.anchor_transaction_id.as_deref(), Some("anchor-1"));
+
+        let mut invalid = callback;
+        invalid.amount = "0".to_string();
+        assert!(validate_webhook_payload(invalid.into()).is_err());
+    }
+
+    fn stored_transaction() -> Transaction {
+        let mut tx = Transaction::new(
+            "G".to_owned() + &"A".repeat(55),
+            "42.50".parse().unwrap(),
+            "USD".to_string(),
+            Some("anchor-1".to_string()),
+            Some("deposit".to_string()),
+            Some("completed".to_string()),
+        );
+        tx.status = "completed".to_string();
+        tx
+    }
+
+    async fn body_json(response: Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn duplicate_echo_returns_stored_transaction() {
+        let tx = stored_transaction();
+        let response = duplicate_callback_response(DuplicateCallbackResponse::Echo, &tx);
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[DUPLICATE_HEADER], "true");
+        let body = body_json(response).await;
+        assert_eq!(body["transaction_id"], tx.id.to_string());
+        assert_eq!(body["status"], "completed");
+    }
+
+    #[tokio::test]
+    async fn duplicate_ack_returns_minimal_body() {
+        let response = duplicate_callback_response(DuplicateCallbackResponse::Ack, &stored_transaction());
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[DUPLICATE_HEADER], "true");
+        assert_eq!(body_json(response).await, serde_json::json!({ "duplicate": true }));
+    }
+
+    #[test]
+    fn status_update_ignores_replays() {
+        assert_eq!(status_update(Some("pending_anchor"), Some("completed")), Some("completed"));
+        assert_eq!(status_update(None, Some("completed")), Some("completed"));
+        assert_eq!(status_update(Some("completed"), Some("completed")), None);
+        assert_eq!(status_update(Some("completed"), None), None);
+    }
+
+    #[test]
+    fn cached_responses_without_outcome_still_parse() {
+        let cached: CallbackResponse =
+            serde_json::from_str(r#"{"transaction_id":"tx-1","status":"pending"}"#).unwrap();
+        assert_eq!(cached.outcome, CallbackOutcome::Created);
+        assert_eq!(cached.callback_status, None);
+    }
+
+    #[test]
+    fn validate_webhook_payload_accepts_quote_only_on_withdrawals() {
+        let mut payload = valid_payload();
+        payload.quote_id = Some("quote-1".to_string());
+        assert!(validate_webhook_payload(payload).is_err());
+
+        let mut payload = valid_payload();
+        payload.callback_type = Some(CALLBACK_TYPE_WITHDRAWAL.to_string());
+        payload.quote_id = Some(" quote-1 ".to_string());
+        let parsed = validate_webhook_payload(payload).expect("withdrawal with quote is valid");
+        assert_eq!(parsed.quote_id.as_deref(), Some("quote-1"));
+
+        let mut payload = valid_payload();
+        payload.callback_type = Some(CALLBACK_TYPE_WITHDRAWAL.to_string());
+        payload.quote_id = Some("q".repeat(QUOTE_ID_MAX_LEN + 1));
+        assert!(validate_webhook_payload(payload).is_err());
+    }
+
     #[test]
     fn validate_webhook_payload_rejects_overlong_optional_fields() {
         let mut payload = valid_payload();
@@ -245,143 +1049,307 @@ mod tests {
         payload.callback_status = Some("a".repeat(21));
         assert!(validate_webhook_payload(payload).is_err());
     }
-}
 
-/// Callback endpoint for transactions (placeholder)
-pub async fn callback(State(_state): State<ApiState>) -> impl IntoResponse {
-    StatusCode::NOT_IMPLEMENTED
-}
+    #[test]
+    fn callback_metric_names_the_outcome() {
+        use metrics::CallbackStatus;
+        let answered = |status: StatusCode| Ok(status.into_response());
+        assert_eq!(callback_metric(&answered(StatusCode::CREATED)), CallbackStatus::Created);
+        assert_eq!(callback_metric(&answered(StatusCode::ACCEPTED)), CallbackStatus::Queued);
+        assert_eq!(callback_metric(&answered(StatusCode::OK)), CallbackStatus::Updated);
 
-/// Get a specific transaction
-/// 
-/// Returns details for a specific transaction by ID
-#[utoipa::path(
-    get,
-    path = "/transactions/{id}",
-    params(
-        ("id" = String, Path, description = "Transaction ID")
-    ),
-    responses(
-        (status = 200, description = "Transaction found", body = crate::schemas::TransactionSchema),
-        (status = 404, description = "Transaction not found"),
-        (status = 500, description = "Database error")
-    ),
-    tag = "Transactions"
-)]
-pub async fn get_transaction(
-    State(state): State<ApiState>,
-    Path(id): Path<Uuid>,
-) -> Result<impl IntoResponse, AppError> {
-    let transaction = queries::get_transaction(&state.app_state.db, id).await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => AppError::NotFound(format!("Transaction {} not found", id)),
-            _ => AppError::DatabaseError(e.to_string()),
-        })?;
+        let duplicate =
+            duplicate_callback_response(DuplicateCallbackResponse::Ack, &stored_transaction());
+        assert_eq!(callback_metric(&Ok(duplicate)), CallbackStatus::Duplicate);
 
-    Ok(Json(transaction))}
+        let failed = |e: AppError| callback_metric(&Err(e));
+        assert_eq!(failed(AppError::Validation("amount".into())), CallbackStatus::ValidationFailed);
+        assert_eq!(failed(AppError::Database(sqlx::Error::PoolTimedOut)), CallbackStatus::DbError);
+        assert_eq!(failed(AppError::Conflict("regression".into())), CallbackStatus::Rejected);
+    }
+}
 
-#[derive(Debug, Deserialize)]
-pub struct ListQuery {
-    pub cursor: Option<String>,
-    pub limit: Option<i64>,
-    /// direction: "forward" (older items) or "backward" (newer items)
-    pub direction: Option<String>,
+/// Whether a callback created, queued or updated its transaction, or
+/// replayed one already stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CallbackOutcome {
+    #[default]
+    Created,
+    Queued,
+    Updated,
+    Duplicate,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CallbackResponse {
+    pub transaction_id: String,
+    pub status: String,
+    /// Status last reported by the anchor
+    pub callback_status: Option<String>,
+    /// Absent from responses cached before outcomes were reported
+    #[serde(default)]
+    pub outcome: CallbackOutcome,
+    /// The transaction's correlation id, also sent as `X-Correlation-Id`;
+    /// `null` in responses cached before it was reported
+    #[serde(default)]
+    pub correlation_id: Option<String>,
+}
+
+/// Anchor Platform transaction callback
+///
+/// Accepts every supported callback schema version, selected with the
+/// `X-Callback-Schema-Version` header (default 1). All versions are normalized
+/// before validation, so the rest of the handler is version independent.
+///
+/// The first callback for an anchor transaction id creates the transaction.
+/// Later ones with a new status update it through the status state machine.
 #[utoipa::path(
-    get,
-    path = "/transactions",
+    post,
+    path = "/callback",
     params(
-        ("cursor" = Option<String>, Query, description = "Cursor for pagination"),
-        ("limit" = Option<i64>, Query, description = "Page size"),
-        ("direction" = Option<String>, Query, description = "forward or backward")
+        ("X-Callback-Schema-Version" = Option<u8>, Header, description = "Callback payload schema version (1 or 2, default 1)")
     ),
+    request_body = CallbackPayload,
     responses(
-        (status = 200, description = "List transactions with pagination metadata"),
+        (status = 201, description = "Transaction created", body = CallbackResponse),
+        (status = 202, description = "Callback queued; the transaction will be stored under the returned id", body = CallbackResponse),
+        (status = 200, description = "Status update applied (`outcome` is `updated`), or a duplicate callback whose body depends on DUPLICATE_CALLBACK_RESPONSE", body = CallbackResponse),
+        (status = 400, description = "Unsupported schema version, invalid payload, or an amount that differs from the stored one"),
+        (status = 409, description = "Status regression, e.g. completed to pending"),
+        (status = 503, description = "Status update for a callback that is still queued"),
         (status = 500, description = "Database error")
     ),
-    tag = "Transactions"
+    tag = "Callbacks"
 )]
-pub async fn list_transactions(
-    State(state): State<AppState>,
-    Query(params): Query<ListQuery>,
-) -> Result<impl IntoResponse, AppError> {
-    let limit = params.limit.unwrap_or(25).min(100);
-    let backward = params.direction.as_deref() == Some("backward");
+pub async fn callback(
+    State(state): State<crate::ApiState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let result = handle_callback(state, headers, body).await;
+    metrics::record_callback(callback_metric(&result));
+    result
+}
 
-    let decoded_cursor = if let Some(ref c) = params.cursor {
-        match cursor_util::decode(c) {
-            Ok((ts, id)) => Some((ts, id)),
-            Err(e) => return Err(AppError::BadRequest(format!("invalid cursor: {}", e))),
-        }
-    } else {
-        None
-    };
+async fn handle_callback(
+    state: crate::ApiState,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    let version = CallbackSchemaVersion::from_headers(&headers)?;
+    metrics::record_callback_schema_version(version.as_str());
 
-    // fetch one extra to determine has_more
-    let fetch_limit = limit + 1;
-    let mut rows = queries::list_transactions(&state.db, fetch_limit, decoded_cursor, backward)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
-
-    let has_more = rows.len() as i64 > limit;
-    if has_more {
-        rows.truncate(limit as usize);
+    let normalized = parse_callback(version, &body)?;
+    let payload = validate_webhook_payload(normalized.into())?;
+    let anchor_transaction_id = payload.anchor_transaction_id.clone();
+    let callback_status = payload.callback_status.clone();
+    let amount = payload.amount.clone();
+    let asset_code = payload.asset_code.clone();
+    let deposit = (&amount, asset_code.as_str());
+    if let Some(response) = find_cached_duplicate(
+        &state.app_state,
+        anchor_transaction_id.as_deref(),
+        callback_status.as_deref(),
+        deposit,
+    )
+    .await?
+    {
+        return Ok(response);
     }
-
-    // next cursor is the last item in the returned rows
-    let next_cursor = rows.last().map(|r: &TxModel| cursor_util::encode(r.created_at, r.id));
-
-    let resp = serde_json::json!({
-        "data": rows,
-        "meta": {
-            "next_cursor": next_cursor,
-            "has_more": has_more
+    if let Some(answered) = answer_if_stored(
+        &state.app_state,
+        anchor_transaction_id.as_deref(),
+        callback_status.as_deref(),
+        deposit,
+    )
+    .await?
+    {
+        return Ok(callback_response(answered));
+    }
+    let ingestion = &state.app_state.ingestion;
+    if let Some(queued) = ingestion.find_queued(payload.anchor_transaction_id.as_deref()).await? {
+        check_same_deposit(
+            queued.anchor_transaction_id.as_deref().unwrap_or_default(),
+            (&queued.amount, &queued.asset_code),
+            deposit,
+        )?;
+        // The update can only be applied once the transaction is stored
+        if status_update(queued.callback_status.as_deref(), callback_status.as_deref()).is_some() {
+            return Err(AppError::Unavailable(format!(
+                "anchor transaction {} is still queued; retry the status update later",
+                queued.anchor_transaction_id.unwrap_or_default()
+            )));
         }
-    });
+        return Ok(duplicate_callback_response(
+            state.app_state.duplicate_callback_response,
+            &queued,
+        ));
+    }
+    let asset_issuer = asset_verification::resolve_asset_issuer(
+        &state.app_state.db,
+        &payload.asset_code,
+        payload.asset_issuer.as_deref(),
+    )
+    .await?;
+    asset_verification::ensure_deposits_allowed(
+        &state.app_state.db,
+        &state.app_state.feature_flags,
+        &payload.asset_code,
+        asset_issuer.as_deref(),
+    )
+    .await?;
+    erasure::ensure_callbacks_allowed(&state.app_state.db, &payload.stellar_address).await?;
+    if let Some(quote_id) = &payload.quote_id {
+        state
+            .app_state
+            .quotes
+            .verify(quote_id, &payload.asset_code, asset_issuer.as_deref(), &payload.amount)
+            .await?;
+    }
 
-    Ok(Json(resp))
-}
+    let mut tx = Transaction::new(
+        payload.stellar_address,
+        payload.amount,
+        payload.asset_code,
+        payload.anchor_transaction_id,
+        payload.callback_type,
+        payload.callback_status,
+    );
+    tx.asset_issuer = asset_issuer;
+    tx.quote_id = payload.quote_id;
+    tx.muxed_id = payload.muxed_id.map(BigDecimal::from);
+    let ctx = CorrelationContext::for_callback(&headers, tx.anchor_transaction_id.as_deref());
+    let tx = tx.with_correlation(&ctx);
 
-/// Wrapper to accept the router's ApiState without forcing all handlers to change.
-pub async fn list_transactions_api(
-    State(api_state): State<crate::ApiState>,
-    Query(params): Query<ListQuery>,
-) -> Result<impl IntoResponse, AppError> {
-    // forward to the AppState-based handler
-    let app_state = api_state.app_state;
-    // call the inner logic directly to avoid extractor conflicts
-    let limit = params.limit.unwrap_or(25).min(100);
-    let backward = params.direction.as_deref() == Some("backward");
-
-    let decoded_cursor = if let Some(ref c) = params.cursor {
-        match cursor_util::decode(c) {
-            Ok((ts, id)) => Some((ts, id)),
-            Err(e) => return Err(AppError::BadRequest(format!("invalid cursor: {}", e))),
+    let raw_body = String::from_utf8_lossy(&body);
+    let raw = RawCallback {
+        schema_version: version.as_str(),
+        body: &raw_body,
+    };
+    let mode = ingestion.ack_mode();
+    let (status, body) = match mode {
+        AckMode::Sync => {
+            let inserted = match ingestion
+                .persist(tx, Some(raw))
+                .instrument(ctx.span("callback"))
+                .await
+            {
+                Err(AppError::DuplicateCallback(anchor_transaction_id)) => {
+                    let answered = existing_callback(
+                        &state.app_state,
+                        &anchor_transaction_id,
+                        callback_status.as_deref(),
+                        deposit,
+                    )
+                    .await?;
+                    return Ok(callback_response(answered));
+                }
+                result => result?,
+            };
+            let body = CallbackResponse {
+                transaction_id: inserted.id.to_string(),
+                status: inserted.status,
+                callback_status: inserted.callback_status,
+                outcome: CallbackOutcome::Created,
+                correlation_id: Some(ctx.to_string()),
+            };
+            (StatusCode::CREATED, body)
+        }
+        AckMode::Async => {
+            let id = ingestion
+                .enqueue(&tx, Some(raw))
+                .instrument(ctx.span("callback"))
+                .await?;
+            let body = CallbackResponse {
+                transaction_id: id.to_string(),
+                status: "queued".to_string(),
+                callback_status: tx.callback_status.clone(),
+                outcome: CallbackOutcome::Queued,
+                correlation_id: Some(ctx.to_string()),
+            };
+            (StatusCode::ACCEPTED, body)
         }
-    } else {
-        None
     };
+    metrics::record_ingestion_ack(mode.as_str());
+    cache_callback_response(&state.app_state, anchor_transaction_id.as_deref(), deposit, &body)
+        .await;
 
-    let fetch_limit = limit + 1;
-    let mut rows = queries::list_transactions(&app_state.db, fetch_limit, decoded_cursor, backward)
-        .await
-        .map_err(|e| AppError::DatabaseError(e.to_string()))?;
+    Ok((
+        status,
+        [
+            (CORRELATION_HEADER, ctx.to_string()),
+            (INGESTION_MODE_HEADER, mode.as_str().to_string()),
+        ],
+        Json(body),
+    )
+        .into_response())
+}
 
-    let has_more = rows.len() as i64 > limit;
-    if has_more {
-        rows.truncate(limit as usize);
-    }
+/// One entry in a transaction's lifecycle
+#[derive(Debug, Serialize)]
+pub struct TransactionEvent {
+    pub at: chrono::DateTime<chrono::Utc>,
+    /// `audit` or `webhook`
+    pub source: &'static str,
+    pub action: String,
+    pub detail: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionTimeline {
+    pub transaction_id: Uuid,
+    /// Search logs for this id to see every step, including worker runs
+    pub correlation_id: String,
+    pub events: Vec<TransactionEvent>,
+}
 
-    let next_cursor = rows.last().map(|r: &TxModel| cursor_util::encode(r.created_at, r.id));
+/// Lifecycle timeline of a transaction: its audit trail and every outbound
+/// webhook queued under its correlation id
+pub async fn get_transaction_events(
+    State(state): State<ApiState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let pool = &state.app_state.db;
+    let transaction = queries::get_transaction(pool, id).await.map_err(|e| match e {
+        sqlx::Error::RowNotFound => AppError::NotFound(format!("Transaction {} not found", id)),
+        e => AppError::Database(e),
+    })?;
+    let ctx = CorrelationContext::for_transaction(&transaction);
 
-    let resp = serde_json::json!({
-        "data": rows,
-        "meta": {
-            "next_cursor": next_cursor,
-            "has_more": has_more
-        }
-    });
+    let mut events: Vec<TransactionEvent> =
+        queries::list_audit_logs_for_entity(pool, ENTITY_TRANSACTION, id)
+            .await?
+            .into_iter()
+            .map(|entry| TransactionEvent {
+                at: entry.timestamp,
+                source: "audit",
+                action: entry.action,
+                detail: serde_json::json!({
+                    "actor": entry.actor,
+                    "old": entry.old_val,
+                    "new": entry.new_val,
+                }),
+            })
+            .collect();
+    let deliveries = queries::list_webhook_deliveries_by_correlation_id(pool, ctx.as_str()).await?;
+    events.extend(deliveries.into_iter().map(|delivery| TransactionEvent {
+        at: delivery.created_at,
+        source: "webhook",
+        action: delivery.event_type,
+        detail: serde_json::json!({
+            "delivery_id": delivery.id,
+            "subscription_id": delivery.subscription_id,
+            "status": delivery.status,
+            "attempts": delivery.attempts,
+            "delivered_at": delivery.delivered_at,
+        }),
+    }));
+    events.sort_by_key(|event| event.at);
 
-    Ok(Json(resp))
+    Ok(Json(TransactionTimeline {
+        transaction_id: id,
+        correlation_id: ctx.to_string(),
+        events,
+    }))
 }
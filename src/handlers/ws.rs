@@ -15,6 +15,7 @@ use crate::AppState;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionStatusUpdate {
     pub transaction_id: Uuid,
+    pub correlation_id: String,
     pub status: String,
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub message: Option<String>,
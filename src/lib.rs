@@ -1,21 +1,28 @@
 pub mod config;
 pub mod db;
+pub mod deprecation;
 pub     Router::new()
         .route("/health", get(handlers::health))
         .route("/settlements", get(handlers::settlements::list_settlements))
         .route("/settlements/:id", get(handlers::settlements::get_settlement))
         .route("/callback", post(handlers::webhook::callback))
-        .route("/transactions", get(handlers::webhook::list_transactions_api))
-        .route("/transactions/:id", get(handlers::webhook::get_transaction))
         // .route("/graphql", post(handlers::graphql::graphql_handler).get(handlers::graphql::subscription_handler))
         // .route("/graphql/playground", get(handlers::graphql::graphql_playground))
         .with_state(state)
 pub mod handlers;
+pub mod loadgen;
 pub mod services;
 pub mod stellar;
+pub mod telemetry;
 pub mod graphql;
 pub mod schemas;
+pub mod server;
 pub mod middleware;
+pub mod utils;
+pub mod metrics;
+pub mod adapters;
+pub mod domain;
+pub mod ports;
 
 use axum::{Router, routing::{get, post}};
 use crate::stellar::HorizonClient;
@@ -25,6 +32,33 @@ use crate::stellar::HorizonClient;
 pub struct AppState {
     pub db: sqlx::PgPool,
     pub horizon_client: HorizonClient,
+    pub feature_flags: crate::services::FeatureFlagService,
+    pub export_jobs: crate::services::ExportJobService,
+    pub asset_verifier: crate::services::AssetVerifier,
+    pub duplicate_callback_response: crate::config::DuplicateCallbackResponse,
+    pub redis_health: crate::services::RedisHealth,
+    pub webhook_dispatcher: crate::services::WebhookDispatcher,
+    pub api_tokens: crate::services::ApiTokenService,
+    pub account_stats: crate::services::AccountStatsService,
+    pub quotes: crate::services::QuoteService,
+    pub ingestion: crate::services::IngestionService,
+    pub status_snapshot: crate::services::StatusSnapshotService,
+    /// Off-request-path audit and usage writes
+    pub buffered_writes: crate::services::BufferedWriter,
+    /// Version and environment, for `/version` and sandbox checks
+    pub deployment: crate::config::Deployment,
+    /// Account-wide erasure runs and their certificates
+    pub erasure: crate::services::ErasureService,
+    /// Outbox events for the admin event stream
+    pub events: crate::services::EventStream,
+    /// Redis record of callback responses, checked before the database
+    pub idempotency: crate::middleware::idempotency::IdempotencyService,
+    /// Time source for request handlers; services hold the same clock
+    pub clock: crate::utils::clock::SharedClock,
+    /// `ANCHOR_SIGNING_KEY` check of anchor callbacks
+    pub anchor_signatures: crate::middleware::anchor_signature::AnchorSignatureVerifier,
+    /// `ALLOWED_IPS` check of anchor callbacks
+    pub ip_filter: crate::middleware::ip_filter::IpFilterLayer,
 }
 
 #[derive(Clone)]
@@ -34,15 +68,56 @@ pub struct ApiState {
 }
 
 pub fn create_app(app_state: AppState) -> Router {
+    let sandbox = app_state.deployment.is_sandbox();
+    let signatures = app_state.anchor_signatures.clone();
+    let ip_filter = app_state.ip_filter.clone();
     let api_state = ApiState {
         app_state,
     };
     
-    Router::new()
+    let app = Router::new()
         .route("/health", get(handlers::health))
+        .route("/health/live", get(handlers::readiness::live))
+        .route("/health/ready", get(handlers::readiness::ready))
+        .route("/version", get(handlers::version))
         .route("/settlements", get(handlers::settlements::list_settlements))
         .route("/settlements/:id", get(handlers::settlements::get_settlement))
-        .route("/callback", post(handlers::webhook::callback))
-        .route("/transactions/:id", get(handlers::webhook::get_transaction))
-        .with_state(api_state)
+        .route(
+            "/callback",
+            post(handlers::webhook::callback)
+                .layer(axum::middleware::from_fn_with_state(
+                    signatures.clone(),
+                    middleware::anchor_signature::verify_anchor_signature,
+                ))
+                .layer(ip_filter.clone()),
+        )
+        .route(
+            "/callback/transactions/batch",
+            post(handlers::callback_batch::callback_batch)
+                .layer(axum::middleware::from_fn_with_state(
+                    signatures.clone(),
+                    middleware::anchor_signature::verify_anchor_signature,
+                ))
+                .layer(ip_filter.clone()),
+        )
+        .route(
+            "/callback/sep31",
+            post(handlers::sep31::callback)
+                .layer(axum::middleware::from_fn_with_state(
+                    signatures,
+                    middleware::anchor_signature::verify_anchor_signature,
+                ))
+                .layer(ip_filter),
+        )
+        .route("/transactions", get(handlers::transactions::list_transactions_api))
+        .route("/transactions/:id", get(handlers::transactions::get_transaction_api))
+        .route("/transactions/:id/events", get(handlers::webhook::get_transaction_events))
+        .route(deprecation::DEPRECATIONS_PATH, get(deprecation::list_deprecations))
+        .layer(axum::middleware::from_fn(deprecation::signal))
+        .with_state(api_state);
+    if sandbox {
+        app.layer(axum::middleware::from_fn(middleware::sandbox::banner))
+    } else {
+        app
+    }
 }
@@ -0,0 +1,711 @@
+//! Synthetic callback load generator for capacity testing.
+//!
+//! Payloads are built from the crate's own callback DTOs and signed with the
+//! same code the server verifies with, so generated traffic always matches
+//! what the server expects. A share of requests can be replays (idempotency)
+//! or deliberately malformed (validation).
+
+use clap::Args;
+use ed25519_dalek::SigningKey;
+use futures::FutureExt;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::handlers::callback_schema::{
+    CallbackAmount, CallbackPayloadV1, CallbackPayloadV2, CallbackSchemaVersion, SCHEMA_VERSION_HEADER,
+};
+use crate::middleware::anchor_signature::{self, SIGNATURE_HEADER};
+use crate::stellar::strkey;
+use crate::validation::{validate_asset_code, validate_stellar_strkey};
+
+/// Previously sent bodies kept around for duplicate injection
+const DUPLICATE_POOL_SIZE: usize = 1024;
+const DEFAULT_REQUESTS: u64 = 1000;
+
+#[derive(Debug, Clone, Args)]
+pub struct LoadgenArgs {
+    /// Callback endpoint to send to
+    #[arg(long, default_value = "http://localhost:3000/callback")]
+    pub target: String,
+    /// Requests per second
+    #[arg(long, default_value_t = 50)]
+    pub rate: u32,
+    /// Maximum requests in flight
+    #[arg(long, default_value_t = 10)]
+    pub concurrency: usize,
+    /// Stop after this many requests (default 1000 unless --duration-secs is set)
+    #[arg(long)]
+    pub requests: Option<u64>,
+    /// Stop after this many seconds
+    #[arg(long)]
+    pub duration_secs: Option<u64>,
+    /// JSON file with the accounts, asset mix and amount distribution to draw from
+    #[arg(long)]
+    pub seed_file: Option<PathBuf>,
+    /// Percentage of requests that replay an earlier payload
+    #[arg(long, default_value_t = 0.0)]
+    pub duplicate_pct: f64,
+    /// Percentage of requests with an invalid payload
+    #[arg(long, default_value_t = 0.0)]
+    pub malformed_pct: f64,
+    /// Callback schema version to generate (1 or 2)
+    #[arg(long, default_value = "1")]
+    pub schema_version: String,
+    /// `S...` seed of the key the server checks callbacks against with ANCHOR_SIGNING_KEY
+    /// (default: ANCHOR_SIGNING_SEED; unsigned if neither is set)
+    #[arg(long)]
+    pub signing_seed: Option<String>,
+    /// Random seed, for reproducible runs
+    #[arg(long)]
+    pub rng_seed: Option<u64>,
+    /// Per-request timeout in seconds
+    #[arg(long, default_value_t = 10)]
+    pub timeout_secs: u64,
+}
+
+/// Accounts, asset mix and amount distribution to draw payloads from
+#[derive(Debug, Clone, Deserialize)]
+pub struct SeedData {
+    pub accounts: Vec<String>,
+    pub assets: Vec<WeightedAsset>,
+    #[serde(default)]
+    pub amounts: AmountDistribution,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WeightedAsset {
+    pub code: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// Log-normal amounts: most near `median`, with a long tail of large ones
+#[derive(Debug, Clone, Deserialize)]
+pub struct AmountDistribution {
+    pub median: f64,
+    pub sigma: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for AmountDistribution {
+    fn default() -> Self {
+        Self {
+            median: 100.0,
+            sigma: 1.0,
+            min: 1.0,
+            max: 50_000.0,
+        }
+    }
+}
+
+impl SeedData {
+    pub fn load(path: &std::path::Path) -> anyhow::Result<Self> {
+        let seed: SeedData = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        seed.validate()?;
+        Ok(seed)
+    }
+
+    /// Built-in seed: a handful of accounts and the default asset
+    pub fn default_with(rng: &mut Rng) -> Self {
+        let accounts = (0..20)
+            .map(|_| {
+                let mut public_key = [0u8; 32];
+                for byte in public_key.iter_mut() {
+                    *byte = rng.below(256) as u8;
+                }
+                strkey::encode_ed25519(&public_key)
+            })
+            .collect();
+        Self {
+            accounts,
+            assets: vec![WeightedAsset {
+                code: "USD".to_string(),
+                weight: 1,
+            }],
+            amounts: AmountDistribution::default(),
+        }
+    }
+
+    /// Reject seeds the server would refuse, so "valid" traffic really is valid
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.accounts.is_empty() || self.assets.is_empty() {
+            anyhow::bail!("seed file needs at least one account and one asset");
+        }
+        for account in &self.accounts {
+            validate_stellar_strkey(account).map_err(|e| anyhow::anyhow!("account {}: {}", account, e))?;
+        }
+        for asset in &self.assets {
+            validate_asset_code(&asset.code).map_err(|e| anyhow::anyhow!("asset {}: {}", asset.code, e))?;
+        }
+        if self.assets.iter().all(|asset| asset.weight == 0) {
+            anyhow::bail!("at least one asset needs a non-zero weight");
+        }
+        let amounts = &self.amounts;
+        if !(amounts.min > 0.0 && amounts.min <= amounts.median && amounts.median <= amounts.max) {
+            anyhow::bail!("amounts must satisfy 0 < min <= median <= max");
+        }
+        Ok(())
+    }
+}
+
+/// SplitMix64: small, seedable and good enough for traffic shaping
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// Standard normal via Box-Muller
+    fn normal(&mut self) -> f64 {
+        let u1 = self.next_f64().max(f64::MIN_POSITIVE);
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PayloadKind {
+    Valid,
+    Duplicate,
+    Malformed,
+}
+
+impl PayloadKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PayloadKind::Valid => "valid",
+            PayloadKind::Duplicate => "duplicate",
+            PayloadKind::Malformed => "malformed",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GeneratedRequest {
+    pub kind: PayloadKind,
+    pub body: Vec<u8>,
+}
+
+/// Builds callback bodies: unique anchor ids, weighted assets, log-normal amounts
+pub struct PayloadGenerator {
+    rng: Rng,
+    seed: SeedData,
+    version: CallbackSchemaVersion,
+    run_id: String,
+    counter: u64,
+    duplicate_pct: f64,
+    malformed_pct: f64,
+    sent: Vec<Vec<u8>>,
+}
+
+impl PayloadGenerator {
+    pub fn new(
+        rng: Rng,
+        seed: SeedData,
+        version: CallbackSchemaVersion,
+        duplicate_pct: f64,
+        malformed_pct: f64,
+    ) -> Self {
+        let mut rng = rng;
+        let run_id = format!("{:08x}", rng.next_u64() as u32);
+        Self {
+            rng,
+            seed,
+            version,
+            run_id,
+            counter: 0,
+            duplicate_pct,
+            malformed_pct,
+            sent: Vec::new(),
+        }
+    }
+
+    pub fn next_request(&mut self) -> GeneratedRequest {
+        let roll = self.rng.next_f64() * 100.0;
+        if roll < self.malformed_pct {
+            return GeneratedRequest {
+                kind: PayloadKind::Malformed,
+                body: self.malformed_body(),
+            };
+        }
+        if roll < self.malformed_pct + self.duplicate_pct && !self.sent.is_empty() {
+            let index = self.rng.below(self.sent.len() as u64) as usize;
+            return GeneratedRequest {
+                kind: PayloadKind::Duplicate,
+                body: self.sent[index].clone(),
+            };
+        }
+
+        let body = serde_json::to_vec(&self.valid_payload()).expect("callback DTOs serialize");
+        if self.sent.len() < DUPLICATE_POOL_SIZE {
+            self.sent.push(body.clone());
+        } else {
+            let index = self.rng.below(DUPLICATE_POOL_SIZE as u64) as usize;
+            self.sent[index] = body.clone();
+        }
+        GeneratedRequest {
+            kind: PayloadKind::Valid,
+            body,
+        }
+    }
+
+    fn valid_payload(&mut self) -> serde_json::Value {
+        self.counter += 1;
+        let id = format!("loadgen-{}-{}", self.run_id, self.counter);
+        let account = self.seed.accounts[self.rng.below(self.seed.accounts.len() as u64) as usize].clone();
+        let asset_code = self.pick_asset();
+        let amount = self.amount();
+
+        let payload = match self.version {
+            CallbackSchemaVersion::V1 => serde_json::to_value(CallbackPayloadV1 {
+                id,
+                amount_in: amount,
+                stellar_account: account,
+                asset_code,
+                asset_issuer: None,
+                callback_type: Some("deposit".to_string()),
+                status: Some("completed".to_string()),
+                quote_id: None,
+            }),
+            CallbackSchemaVersion::V2 => serde_json::to_value(CallbackPayloadV2 {
+                id,
+                amount_in: CallbackAmount {
+                    amount,
+                    asset_code,
+                    asset_issuer: None,
+                },
+                stellar_account: account,
+                callback_type: Some("deposit".to_string()),
+                status: Some("completed".to_string()),
+                quote_id: None,
+            }),
+        };
+        payload.expect("callback DTOs serialize")
+    }
+
+    fn pick_asset(&mut self) -> String {
+        let total: u64 = self.seed.assets.iter().map(|a| a.weight as u64).sum();
+        let mut pick = self.rng.below(total);
+        for asset in &self.seed.assets {
+            if pick < asset.weight as u64 {
+                return asset.code.clone();
+            }
+            pick -= asset.weight as u64;
+        }
+        self.seed.assets[0].code.clone()
+    }
+
+    fn amount(&mut self) -> String {
+        let d = &self.seed.amounts;
+        let amount = (d.median * (d.sigma * self.rng.normal()).exp()).clamp(d.min, d.max);
+        format!("{:.2}", amount)
+    }
+
+    /// A payload the server must reject. Variants rotate so every validation
+    /// path sees traffic.
+    fn malformed_body(&mut self) -> Vec<u8> {
+        let mut payload = self.valid_payload();
+        let amount_field = match self.version {
+            CallbackSchemaVersion::V1 => "/amount_in",
+            CallbackSchemaVersion::V2 => "/amount_in/amount",
+        };
+        match self.rng.below(5) {
+            0 => *payload.pointer_mut(amount_field).unwrap() = "-5.00".into(),
+            1 => *payload.pointer_mut(amount_field).unwrap() = "12,50".into(),
+            2 => payload["stellar_account"] = "BADACCOUNT".into(),
+            3 => {
+                payload.as_object_mut().unwrap().remove("stellar_account");
+            }
+            _ => {
+                let mut body = serde_json::to_vec(&payload).unwrap();
+                body.truncate(body.len() / 2);
+                return body;
+            }
+        }
+        serde_json::to_vec(&payload).unwrap()
+    }
+}
+
+/// Parsed and checked loadgen arguments
+pub struct LoadgenConfig {
+    pub target: String,
+    pub rate: u32,
+    pub concurrency: usize,
+    pub requests: Option<u64>,
+    pub duration: Option<Duration>,
+    pub version: CallbackSchemaVersion,
+    pub signing_key: Option<SigningKey>,
+    /// Host the signature covers, the target's host without the port
+    pub host: String,
+    pub timeout: Duration,
+    pub generator: PayloadGenerator,
+}
+
+impl TryFrom<LoadgenArgs> for LoadgenConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(args: LoadgenArgs) -> anyhow::Result<Self> {
+        if args.rate == 0 || args.concurrency == 0 {
+            anyhow::bail!("--rate and --concurrency must be at least 1");
+        }
+        let pct_ok = |pct: f64| (0.0..=100.0).contains(&pct);
+        if !pct_ok(args.duplicate_pct)
+            || !pct_ok(args.malformed_pct)
+            || args.duplicate_pct + args.malformed_pct > 100.0
+        {
+            anyhow::bail!("--duplicate-pct and --malformed-pct must be 0-100 and sum to at most 100");
+        }
+        let version = CallbackSchemaVersion::SUPPORTED
+            .iter()
+            .copied()
+            .find(|v| v.as_str() == args.schema_version)
+            .ok_or_else(|| anyhow::anyhow!("unsupported --schema-version {}", args.schema_version))?;
+
+        let mut rng = Rng::new(args.rng_seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default()
+        }));
+        let seed = match &args.seed_file {
+            Some(path) => SeedData::load(path)?,
+            None => SeedData::default_with(&mut rng),
+        };
+
+        let host = reqwest::Url::parse(&args.target)
+            .map_err(|e| anyhow::anyhow!("--target {}: {}", args.target, e))?
+            .host_str()
+            .ok_or_else(|| anyhow::anyhow!("--target {} has no host", args.target))?
+            .to_string();
+        let signing_key = args
+            .signing_seed
+            .or_else(|| std::env::var("ANCHOR_SIGNING_SEED").ok())
+            .filter(|s| !s.is_empty())
+            .map(|seed| strkey::decode_secret_seed(seed.trim()))
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("--signing-seed: {}", e))?
+            .map(|seed| SigningKey::from_bytes(&seed));
+
+        Ok(Self {
+            target: args.target,
+            rate: args.rate,
+            concurrency: args.concurrency,
+            requests: match (args.requests, args.duration_secs) {
+                (None, None) => Some(DEFAULT_REQUESTS),
+                (requests, _) => requests,
+            },
+            duration: args.duration_secs.map(Duration::from_secs),
+            version,
+            signing_key,
+            host,
+            timeout: Duration::from_secs(args.timeout_secs),
+            generator: PayloadGenerator::new(rng, seed, version, args.duplicate_pct, args.malformed_pct),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Outcome {
+    kind: PayloadKind,
+    result: Result<u16, &'static str>,
+    latency: Duration,
+}
+
+/// Results of a run
+#[derive(Debug, Default)]
+pub struct Report {
+    pub sent: u64,
+    pub elapsed: Duration,
+    /// Sorted ascending
+    pub latencies: Vec<Duration>,
+    /// Responses by status code, or transport failures as `error:<kind>`
+    pub outcomes: BTreeMap<String, u64>,
+    /// Same breakdown per payload kind
+    pub outcomes_by_kind: BTreeMap<&'static str, BTreeMap<String, u64>>,
+}
+
+impl Report {
+    pub fn throughput(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.sent as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        percentile(&self.latencies, p)
+    }
+
+    fn record(&mut self, outcome: Outcome) {
+        let key = match outcome.result {
+            Ok(status) => status.to_string(),
+            Err(kind) => format!("error:{}", kind),
+        };
+        self.sent += 1;
+        self.latencies.push(outcome.latency);
+        *self.outcomes.entry(key.clone()).or_default() += 1;
+        *self
+            .outcomes_by_kind
+            .entry(outcome.kind.as_str())
+            .or_default()
+            .entry(key)
+            .or_default() += 1;
+    }
+}
+
+/// Nearest-rank percentile of sorted samples
+pub fn percentile(sorted: &[Duration], p: f64) -> Option<Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Requests:   {}", self.sent)?;
+        writeln!(f, "Elapsed:    {:.2}s", self.elapsed.as_secs_f64())?;
+        writeln!(f, "Throughput: {:.1} req/s", self.throughput())?;
+        writeln!(f, "Latency:")?;
+        for p in [50.0, 90.0, 95.0, 99.0, 100.0] {
+            let label = if p == 100.0 { "max".to_string() } else { format!("p{}", p) };
+            match self.percentile(p) {
+                Some(latency) => writeln!(f, "  {:<4} {:.1}ms", label, latency.as_secs_f64() * 1000.0)?,
+                None => writeln!(f, "  {:<4} -", label)?,
+            }
+        }
+        writeln!(f, "Responses:")?;
+        for (outcome, count) in &self.outcomes {
+            writeln!(f, "  {:<18} {}", outcome, count)?;
+        }
+        writeln!(f, "By payload kind:")?;
+        for (kind, outcomes) in &self.outcomes_by_kind {
+            let breakdown = outcomes
+                .iter()
+                .map(|(outcome, count)| format!("{}={}", outcome, count))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(f, "  {:<10} {}", kind, breakdown)?;
+        }
+        Ok(())
+    }
+}
+
+fn classify(error: &reqwest::Error) -> &'static str {
+    if error.is_timeout() {
+        "timeout"
+    } else if error.is_connect() {
+        "connect"
+    } else {
+        "other"
+    }
+}
+
+/// Send generated traffic at the configured rate and concurrency
+pub async fn execute(mut config: LoadgenConfig) -> anyhow::Result<Report> {
+    let client = reqwest::Client::builder().timeout(config.timeout).build()?;
+    let permits = Arc::new(Semaphore::new(config.concurrency));
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / config.rate as f64));
+    // Open loop, but never burst to catch up after stalling on concurrency
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    let started = Instant::now();
+    let deadline = config.duration.map(|duration| started + duration);
+    let mut in_flight = JoinSet::new();
+    let mut report = Report::default();
+    let mut issued = 0u64;
+
+    loop {
+        if config.requests.is_some_and(|max| issued >= max)
+            || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            break;
+        }
+        ticker.tick().await;
+        let permit = permits.clone().acquire_owned().await?;
+
+        let request = config.generator.next_request();
+        let mut builder = client
+            .post(&config.target)
+            .header("content-type", "application/json")
+            .header(SCHEMA_VERSION_HEADER, config.version.as_str());
+        if let Some(key) = &config.signing_key {
+            let timestamp = chrono::Utc::now().timestamp();
+            let signature = anchor_signature::sign(key, timestamp, &config.host, &request.body);
+            builder = builder.header(SIGNATURE_HEADER, signature);
+        }
+        let builder = builder.body(request.body);
+        issued += 1;
+
+        in_flight.spawn(async move {
+            let _permit = permit;
+            let sent_at = Instant::now();
+            let result = builder.send().await;
+            Outcome {
+                kind: request.kind,
+                result: result.map(|r| r.status().as_u16()).map_err(|e| classify(&e)),
+                latency: sent_at.elapsed(),
+            }
+        });
+
+        // Drain finished requests as we go so memory stays flat on long runs
+        while let Some(Some(done)) = in_flight.join_next().now_or_never() {
+            report.record(done?);
+        }
+    }
+
+    while let Some(done) = in_flight.join_next().await {
+        report.record(done?);
+    }
+    report.elapsed = started.elapsed();
+    report.latencies.sort_unstable();
+    Ok(report)
+}
+
+/// `synapse-core loadgen` entry point
+pub async fn run(args: LoadgenArgs) -> anyhow::Result<()> {
+    let config = LoadgenConfig::try_from(args)?;
+    println!(
+        "Sending to {} at {} req/s, concurrency {}",
+        config.target, config.rate, config.concurrency
+    );
+    let report = execute(config).await?;
+    print!("{}", report);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::callback_schema::parse_callback;
+    use std::collections::HashSet;
+
+    fn generator(duplicate_pct: f64, malformed_pct: f64) -> PayloadGenerator {
+        let mut rng = Rng::new(42);
+        let seed = SeedData::default_with(&mut rng);
+        PayloadGenerator::new(rng, seed, CallbackSchemaVersion::V1, duplicate_pct, malformed_pct)
+    }
+
+    #[test]
+    fn test_valid_payloads_parse_with_unique_ids() {
+        for version in CallbackSchemaVersion::SUPPORTED {
+            let mut rng = Rng::new(7);
+            let seed = SeedData::default_with(&mut rng);
+            let mut generator = PayloadGenerator::new(rng, seed, *version, 0.0, 0.0);
+            let mut ids = HashSet::new();
+            for _ in 0..200 {
+                let request = generator.next_request();
+                assert_eq!(request.kind, PayloadKind::Valid);
+                let parsed = parse_callback(*version, &request.body).unwrap();
+                validate_stellar_strkey(&parsed.stellar_account).unwrap();
+                assert!(ids.insert(parsed.anchor_transaction_id));
+            }
+        }
+    }
+
+    #[test]
+    fn test_generated_seed_is_valid() {
+        SeedData::default_with(&mut Rng::new(1)).validate().unwrap();
+    }
+
+    #[test]
+    fn test_duplicates_replay_earlier_bodies() {
+        let mut generator = generator(30.0, 0.0);
+        let mut sent = HashSet::new();
+        let mut duplicates = 0;
+        for _ in 0..500 {
+            let request = generator.next_request();
+            match request.kind {
+                PayloadKind::Duplicate => {
+                    duplicates += 1;
+                    assert!(sent.contains(&request.body));
+                }
+                _ => {
+                    sent.insert(request.body);
+                }
+            }
+        }
+        assert!((100..200).contains(&duplicates), "{} duplicates", duplicates);
+    }
+
+    #[test]
+    fn test_malformed_payloads_fail_parsing_or_validation() {
+        let mut generator = generator(0.0, 100.0);
+        for _ in 0..100 {
+            let request = generator.next_request();
+            assert_eq!(request.kind, PayloadKind::Malformed);
+            let rejected = match parse_callback(CallbackSchemaVersion::V1, &request.body) {
+                Err(_) => true,
+                Ok(parsed) => {
+                    validate_stellar_strkey(&parsed.stellar_account).is_err()
+                        || parsed
+                            .amount
+                            .parse::<sqlx::types::BigDecimal>()
+                            .map_or(true, |amount| amount <= 0.into())
+                }
+            };
+            assert!(rejected, "accepted: {}", String::from_utf8_lossy(&request.body));
+        }
+    }
+
+    #[test]
+    fn test_amounts_stay_within_bounds() {
+        let mut generator = generator(0.0, 0.0);
+        for _ in 0..1000 {
+            let amount: f64 = generator.amount().parse().unwrap();
+            assert!((1.0..=50_000.0).contains(&amount));
+        }
+    }
+
+    #[test]
+    fn test_seed_validation_rejects_unsupported_assets() {
+        let mut seed = SeedData::default_with(&mut Rng::new(1));
+        seed.assets = vec![WeightedAsset {
+            code: "usd".to_string(),
+            weight: 1,
+        }];
+        assert!(seed.validate().is_err());
+    }
+
+    #[test]
+    fn test_percentile_nearest_rank() {
+        let samples: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&samples, 50.0), Some(Duration::from_millis(50)));
+        assert_eq!(percentile(&samples, 99.0), Some(Duration::from_millis(99)));
+        assert_eq!(percentile(&samples, 100.0), Some(Duration::from_millis(100)));
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+}
@@ -3,24 +3,42 @@
 use metrics_exporter_prometheus::PrometheusHandle;
 use std::time::Duration;
 
-/// Status of a callback for metrics labeling
-#[derive(Debug, Clone, Copy)]
+/// How a callback was answered, for metrics labeling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CallbackStatus {
-    Success,
-    Error,
-    Invalid,
+    Created,
+    /// Acknowledged asynchronously through the ingestion outbox
+    Queued,
+    /// A new status for a stored transaction
+    Updated,
+    Duplicate,
+    ValidationFailed,
+    DbError,
+    /// Refused for any other reason, e.g. a status regression
+    Rejected,
 }
 
 impl CallbackStatus {
-    fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &'static str {
         match self {
-            CallbackStatus::Success => "success",
-            CallbackStatus::Error => "error",
-            CallbackStatus::Invalid => "invalid",
+            CallbackStatus::Created => "created",
+            CallbackStatus::Queued => "queued",
+            CallbackStatus::Updated => "updated",
+            CallbackStatus::Duplicate => "duplicate",
+            CallbackStatus::ValidationFailed => "validation_failed",
+            CallbackStatus::DbError => "db_error",
+            CallbackStatus::Rejected => "rejected",
         }
     }
 }
 
+/// Latency buckets for `http_request_duration_seconds`
+const HTTP_DURATION_BUCKETS: &[f64] =
+    &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Path label for requests no route matched, so unknown URLs share a series
+pub const UNMATCHED_PATH: &str = "unmatched";
+
 /// Initialize the Prometheus metrics exporter and register all metrics
 /// Returns a PrometheusHandle for rendering metrics
 pub fn init_metrics() -> Result<PrometheusHandle, Box<dyn std::error::Error>> {
@@ -31,13 +49,112 @@ pub fn init_metrics() -> Result<PrometheusHandle, Box<dyn std::error::Error>> {
         metrics_exporter_prometheus::Matcher::Full("transaction_processing_seconds".to_string()),
         &[0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0],
     )?;
+    let builder = builder.set_buckets_for_metric(
+        metrics_exporter_prometheus::Matcher::Full("http_request_duration_seconds".to_string()),
+        HTTP_DURATION_BUCKETS,
+    )?;
+    let builder = builder.set_buckets_for_metric(
+        metrics_exporter_prometheus::Matcher::Full("horizon_request_duration_seconds".to_string()),
+        HTTP_DURATION_BUCKETS,
+    )?;
     
     let handle = builder.install_recorder()?;
     
     // Register metrics with descriptors
     metrics::describe_counter!(
         "callbacks_received_total",
-        "Total number of callbacks received by status"
+        "Total number of callbacks received, by status (created, queued, updated, duplicate, validation_failed, db_error or rejected)"
+    );
+    
+    metrics::describe_counter!(
+        "http_requests_total",
+        "Total number of HTTP requests, by method, route template and status"
+    );
+    
+    metrics::describe_histogram!(
+        "http_request_duration_seconds",
+        metrics::Unit::Seconds,
+        "HTTP request duration in seconds, by method, route template and status"
+    );
+    
+    metrics::describe_gauge!(
+        "db_pool_connections",
+        "Primary database pool connections, by state (size, idle, acquired or max)"
+    );
+    
+    metrics::describe_gauge!(
+        "feature_flag_cache_size",
+        "Feature flags held in the in-memory cache"
+    );
+    
+    metrics::describe_gauge!(
+        "feature_flag_last_refresh_timestamp_seconds",
+        "Unix time of the last successful feature flag cache refresh; 0 before the first"
+    );
+    
+    metrics::describe_counter!(
+        "feature_flag_invalidations_total",
+        "Feature flag invalidation messages, by outcome (published, publish_failed, received or ignored)"
+    );
+    
+    metrics::describe_counter!(
+        "callback_schema_version_total",
+        "Total number of callbacks received by payload schema version"
+    );
+    
+    metrics::describe_counter!(
+        "duplicate_callbacks_total",
+        "Total number of callbacks for already known transactions"
+    );
+    
+    metrics::describe_counter!(
+        "query_plans_captured_total",
+        "Total number of slow query plans captured by query name"
+    );
+    
+    metrics::describe_gauge!(
+        "redis_available",
+        "Whether Redis is currently considered available (1) or not (0)"
+    );
+    
+    metrics::describe_gauge!(
+        "redis_feature_degraded",
+        "Whether a Redis-backed feature is running in its degraded mode, by feature"
+    );
+    
+    metrics::describe_counter!(
+        "webhook_deliveries_total",
+        "Total number of outbound webhook delivery attempts by outcome"
+    );
+    
+    metrics::describe_counter!(
+        "notification_messages_total",
+        "Total number of webhook display messages rendered, by outcome (rendered or fallback)"
+    );
+    
+    metrics::describe_gauge!(
+        "webhook_in_flight",
+        "Outbound webhook deliveries currently in flight on this instance, by subscription"
+    );
+    
+    metrics::describe_counter!(
+        "webhook_subscription_saturated_total",
+        "Total number of polls that found a subscription's in-flight slots saturated"
+    );
+    
+    metrics::describe_counter!(
+        "webhook_subscription_cooldowns_total",
+        "Total number of cool-downs applied to webhook subscriptions, by subscription and reason"
+    );
+    
+    metrics::describe_gauge!(
+        "http_open_connections",
+        "Current number of open HTTP connections when connection limits are enabled"
+    );
+    
+    metrics::describe_counter!(
+        "http_connection_limit_reached_total",
+        "Total number of times accepting paused because SERVER_MAX_CONNECTIONS was reached"
     );
     
     metrics::describe_histogram!(
@@ -51,15 +168,194 @@ pub fn init_metrics() -> Result<PrometheusHandle, Box<dyn std::error::Error>> {
         "Current number of active database connections"
     );
     
+    metrics::describe_counter!(
+        "deprecated_usage_total",
+        "Total number of requests using a deprecated route or field, by element"
+    );
+    
+    metrics::describe_gauge!(
+        "ingestion_mode_async",
+        "1 while callbacks are acknowledged asynchronously, 0 while synchronously"
+    );
+    
+    metrics::describe_counter!(
+        "ingestion_acks_total",
+        "Total number of acknowledged callbacks, by acknowledgment mode"
+    );
+    
+    metrics::describe_counter!(
+        "buffered_writes_total",
+        "Total number of buffered audit and usage writes, by kind and outcome (queued, written, sync, dropped or spilled)"
+    );
+    
+    metrics::describe_counter!(
+        "horizon_operations_total",
+        "Total number of Horizon payment operations read by the payment listener, by type and outcome (matched or unmatched)"
+    );
+    
+    metrics::describe_counter!(
+        "horizon_operations_skipped_total",
+        "Total number of Horizon operations the payment listener skipped because it does not handle their type"
+    );
+    
+    metrics::describe_histogram!(
+        "feature_flag_refresh_rows",
+        "Feature flag rows fetched per cache refresh, by mode (full or incremental)"
+    );
+    
+    metrics::describe_counter!(
+        "admin_event_stream_lagged_total",
+        "Admin event stream clients disconnected for falling behind"
+    );
+    
+    metrics::describe_counter!(
+        "horizon_requests_total",
+        "Total number of Horizon API calls, by endpoint and outcome (success, not_found, rejected, circuit_open or error)"
+    );
+    
+    metrics::describe_histogram!(
+        "horizon_request_duration_seconds",
+        metrics::Unit::Seconds,
+        "Horizon API call duration in seconds, retries included, by endpoint and outcome"
+    );
+    
+    metrics::describe_gauge!(
+        "horizon_circuit_open",
+        "Whether the Horizon client's circuit breaker is open (1) or lets calls through (0)"
+    );
+    
+    metrics::describe_gauge!(
+        "background_task_last_run_timestamp_seconds",
+        "Unix time a background task last finished a pass, by task"
+    );
+    
+    metrics::describe_gauge!(
+        "background_task_last_success_timestamp_seconds",
+        "Unix time a background task last finished a pass without error, by task"
+    );
+    
+    metrics::describe_counter!(
+        "background_task_failures_total",
+        "Total number of background task passes that failed, by task"
+    );
+    
     tracing::info!("Metrics registry initialized successfully");
     Ok(handle)
 }
 
-/// Record a callback reception event with the given status
+/// Record how a callback was answered
 pub fn record_callback(status: CallbackStatus) {
     metrics::counter!("callbacks_received_total", "status" => status.as_str()).increment(1);
 }
 
+/// Record a finished HTTP request. `path` is the route template, e.g.
+/// `/transactions/:id`, never the raw URL.
+pub fn record_http_request(method: &str, path: &str, status: u16, duration: Duration) {
+    metrics::counter!(
+        "http_requests_total",
+        "method" => method.to_string(),
+        "path" => path.to_string(),
+        "status" => status.to_string()
+    )
+    .increment(1);
+    metrics::histogram!(
+        "http_request_duration_seconds",
+        "method" => method.to_string(),
+        "path" => path.to_string(),
+        "status" => status.to_string()
+    )
+    .record(duration.as_secs_f64());
+}
+
+/// Sample the primary pool's connection gauges
+pub fn update_db_pool(pool: &sqlx::PgPool) {
+    let size = pool.size() as usize;
+    let idle = pool.num_idle();
+    metrics::gauge!("db_pool_connections", "state" => "size").set(size as f64);
+    metrics::gauge!("db_pool_connections", "state" => "idle").set(idle as f64);
+    metrics::gauge!("db_pool_connections", "state" => "acquired")
+        .set(size.saturating_sub(idle) as f64);
+    metrics::gauge!("db_pool_connections", "state" => "max")
+        .set(pool.options().get_max_connections() as f64);
+}
+
+/// Update the feature flag cache gauges
+pub fn update_flag_cache(size: usize, last_refreshed_at: Option<chrono::DateTime<chrono::Utc>>) {
+    metrics::gauge!("feature_flag_cache_size").set(size as f64);
+    metrics::gauge!("feature_flag_last_refresh_timestamp_seconds")
+        .set(last_refreshed_at.map_or(0.0, |at| at.timestamp() as f64));
+}
+
+/// Record a feature flag invalidation message sent or received
+pub fn record_flag_invalidation(outcome: &'static str) {
+    metrics::counter!("feature_flag_invalidations_total", "outcome" => outcome).increment(1);
+}
+
+/// Record the payload schema version a callback was sent with
+pub fn record_callback_schema_version(version: &'static str) {
+    metrics::counter!("callback_schema_version_total", "version" => version).increment(1);
+}
+
+/// Record a callback that replayed an already known transaction
+pub fn record_duplicate_callback() {
+    metrics::counter!("duplicate_callbacks_total").increment(1);
+}
+
+/// Record a captured slow query plan
+pub fn record_query_plan_captured(query: &'static str) {
+    metrics::counter!("query_plans_captured_total", "query" => query).increment(1);
+}
+
+/// Update the Redis availability gauge
+pub fn update_redis_available(available: bool) {
+    metrics::gauge!("redis_available").set(if available { 1.0 } else { 0.0 });
+}
+
+/// Update the degraded-mode gauge of a Redis-backed feature
+pub fn update_redis_degraded(feature: &'static str, degraded: bool) {
+    metrics::gauge!("redis_feature_degraded", "feature" => feature).set(if degraded { 1.0 } else { 0.0 });
+}
+
+/// Record an outbound webhook delivery attempt
+pub fn record_webhook_delivery(outcome: &'static str) {
+    metrics::counter!("webhook_deliveries_total", "outcome" => outcome).increment(1);
+}
+
+/// Record how a webhook display message was produced
+pub fn record_notification_message(outcome: &'static str) {
+    metrics::counter!("notification_messages_total", "outcome" => outcome).increment(1);
+}
+
+/// Update the in-flight webhook deliveries gauge of a subscription
+pub fn update_webhook_in_flight(subscription_id: uuid::Uuid, in_flight: usize) {
+    metrics::gauge!("webhook_in_flight", "subscription" => subscription_id.to_string()).set(in_flight as f64);
+}
+
+/// Record a poll that found a subscription saturated
+pub fn record_webhook_saturated(subscription_id: uuid::Uuid) {
+    metrics::counter!("webhook_subscription_saturated_total", "subscription" => subscription_id.to_string()).increment(1);
+}
+
+/// Record a cool-down applied to a webhook subscription
+pub fn record_webhook_cooldown(subscription_id: uuid::Uuid, reason: &'static str) {
+    metrics::counter!(
+        "webhook_subscription_cooldowns_total",
+        "subscription" => subscription_id.to_string(),
+        "reason" => reason
+    )
+    .increment(1);
+}
+
+/// Update the open HTTP connections gauge
+pub fn update_open_connections(count: usize) {
+    metrics::gauge!("http_open_connections").set(count as f64);
+}
+
+/// Record the accept loop pausing at the connection ceiling
+pub fn record_connection_limit_reached() {
+    metrics::counter!("http_connection_limit_reached_total").increment(1);
+}
+
 /// Record transaction processing duration
 pub fn record_transaction_duration(duration: Duration) {
     let seconds = duration.as_secs_f64();
@@ -71,19 +367,190 @@ pub fn update_db_connections(count: usize) {
     metrics::gauge!("active_db_connections").set(count as f64);
 }
 
+/// Record a request using a deprecated route or field
+pub fn record_deprecated_usage(element: &'static str) {
+    metrics::counter!("deprecated_usage_total", "element" => element).increment(1);
+}
+
+/// Update the active ingestion mode gauge
+pub fn update_ingestion_mode_async(is_async: bool) {
+    metrics::gauge!("ingestion_mode_async").set(if is_async { 1.0 } else { 0.0 });
+}
+
+/// Record a callback acknowledged in `mode` (`sync` or `async`)
+pub fn record_ingestion_ack(mode: &'static str) {
+    metrics::counter!("ingestion_acks_total", "mode" => mode).increment(1);
+}
+
+/// Record a buffered write of `kind` (`audit` or `usage`) with `outcome`
+pub fn record_buffered_write(kind: &'static str, outcome: &'static str) {
+    metrics::counter!("buffered_writes_total", "kind" => kind, "outcome" => outcome).increment(1);
+}
+
+/// Record a batch of buffered writes reaching Postgres
+pub fn record_buffered_writes_written(audit: usize, usage: usize) {
+    metrics::counter!("buffered_writes_total", "kind" => "audit", "outcome" => "written").increment(audit as u64);
+    metrics::counter!("buffered_writes_total", "kind" => "usage", "outcome" => "written").increment(usage as u64);
+}
+
+/// Record a payment operation of `operation_type` handled with `outcome`
+pub fn record_horizon_operation(operation_type: &'static str, outcome: &'static str) {
+    metrics::counter!("horizon_operations_total", "type" => operation_type, "outcome" => outcome).increment(1);
+}
+
+/// Record an operation skipped because its type is not handled
+pub fn record_horizon_operation_skipped(operation_type: &str) {
+    metrics::counter!("horizon_operations_skipped_total", "type" => operation_type.to_string()).increment(1);
+}
+
+/// Record the flag rows fetched by one cache refresh in `mode`
+pub fn record_flag_refresh(mode: &'static str, rows: usize) {
+    metrics::histogram!("feature_flag_refresh_rows", "mode" => mode).record(rows as f64);
+}
+
+/// Record an admin event stream client disconnected for falling behind
+pub fn record_event_stream_lagged() {
+    metrics::counter!("admin_event_stream_lagged_total").increment(1);
+}
+
+/// Record a finished Horizon API call by endpoint, e.g. `account_payments`
+pub fn record_horizon_request(endpoint: &'static str, outcome: &'static str, duration: Duration) {
+    metrics::counter!("horizon_requests_total", "endpoint" => endpoint, "outcome" => outcome)
+        .increment(1);
+    metrics::histogram!(
+        "horizon_request_duration_seconds",
+        "endpoint" => endpoint,
+        "outcome" => outcome
+    )
+    .record(duration.as_secs_f64());
+}
+
+/// Update the Horizon circuit breaker gauge
+pub fn update_horizon_circuit_open(open: bool) {
+    metrics::gauge!("horizon_circuit_open").set(if open { 1.0 } else { 0.0 });
+}
+
+/// Record a finished pass of a background task. A task whose last run
+/// timestamp stops advancing has stalled; one whose last success does has
+/// been failing.
+pub fn record_task_heartbeat(task: &'static str, succeeded: bool) {
+    let now = unix_now_secs() as f64;
+    metrics::gauge!("background_task_last_run_timestamp_seconds", "task" => task).set(now);
+    if succeeded {
+        metrics::gauge!("background_task_last_success_timestamp_seconds", "task" => task).set(now);
+    } else {
+        metrics::counter!("background_task_failures_total", "task" => task).increment(1);
+    }
+}
+
+/// Seconds of request history kept for the status snapshot
+pub const REQUEST_WINDOW_SECS: u64 = 300;
+
+/// Request and error counts per second over the last
+/// [`REQUEST_WINDOW_SECS`], kept in memory for the status snapshot
+#[derive(Debug, Default)]
+pub struct RequestWindow {
+    /// `(unix second, requests, errors)`, oldest first
+    buckets: std::sync::Mutex<std::collections::VecDeque<(u64, u64, u64)>>,
+}
+
+/// Rates over a [`RequestWindow`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RequestRates {
+    pub requests_per_minute: f64,
+    /// Share of responses that were 5xx; `None` without any requests
+    pub error_rate: Option<f64>,
+}
+
+impl RequestWindow {
+    pub fn record(&self, now_secs: u64, error: bool) {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        match buckets.back_mut() {
+            Some(bucket) if bucket.0 == now_secs => {
+                bucket.1 += 1;
+                bucket.2 += error as u64;
+            }
+            _ => buckets.push_back((now_secs, 1, error as u64)),
+        }
+        while buckets
+            .front()
+            .is_some_and(|bucket| bucket.0 + REQUEST_WINDOW_SECS <= now_secs)
+        {
+            buckets.pop_front();
+        }
+    }
+
+    pub fn rates(&self, now_secs: u64) -> RequestRates {
+        let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let (requests, errors) = buckets
+            .iter()
+            .filter(|bucket| bucket.0 + REQUEST_WINDOW_SECS > now_secs)
+            .fold((0, 0), |(requests, errors), bucket| (requests + bucket.1, errors + bucket.2));
+        RequestRates {
+            requests_per_minute: requests as f64 * 60.0 / REQUEST_WINDOW_SECS as f64,
+            error_rate: (requests > 0).then(|| errors as f64 / requests as f64),
+        }
+    }
+}
+
+/// Process-wide request window fed by [`track_requests`]
+pub fn request_window() -> &'static RequestWindow {
+    static WINDOW: std::sync::OnceLock<RequestWindow> = std::sync::OnceLock::new();
+    WINDOW.get_or_init(RequestWindow::default)
+}
+
+pub fn unix_now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Middleware counting every response into [`request_window`] and the HTTP
+/// request metrics. Routes merged after this layer, like `/metrics`, are not
+/// counted.
+pub async fn track_requests(
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let method = request.method().to_string();
+    let path = request
+        .extensions()
+        .get::<axum::extract::MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_PATH.to_string());
+    let started = std::time::Instant::now();
+
+    let response = next.run(request).await;
+    request_window().record(unix_now_secs(), response.status().is_server_error());
+    record_http_request(&method, &path, response.status().as_u16(), started.elapsed());
+    response
+}
+
+/// What the `/metrics` handler renders and samples
+#[derive(Clone)]
+pub struct MetricsState {
+    pub handle: PrometheusHandle,
+    pub pool: sqlx::PgPool,
+    pub feature_flags: crate::services::FeatureFlagService,
+}
+
 /// Handler for the /metrics endpoint
 /// Returns metrics in Prometheus text exposition format
 pub async fn metrics_handler(
-    metrics_handle: axum::extract::State<PrometheusHandle>,
-    pool: axum::extract::State<sqlx::PgPool>,
+    axum::extract::State(state): axum::extract::State<MetricsState>,
 ) -> impl axum::response::IntoResponse {
     use axum::http::{header, StatusCode};
     
-    // Update database connections gauge before rendering
-    let pool_size = pool.size() as usize;
-    update_db_connections(pool_size);
+    // Gauges sampled at scrape time rather than kept up to date
+    update_db_connections(state.pool.size() as usize);
+    update_db_pool(&state.pool);
+    update_flag_cache(
+        state.feature_flags.cached_flags().await,
+        state.feature_flags.last_refreshed_at(),
+    );
     
-    let metrics = metrics_handle.render();
+    let metrics = state.handle.render();
     (
         StatusCode::OK,
         [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
@@ -91,32 +558,35 @@ pub async fn metrics_handler(
     )
 }
 
-/// Middleware to restrict /metrics endpoint to allowed IPs
-pub async fn metrics_auth_middleware(
-    axum::extract::ConnectInfo(addr): axum::extract::ConnectInfo<std::net::SocketAddr>,
-    axum::extract::State(config): axum::extract::State<crate::config::Config>,
-    request: axum::http::Request<axum::body::Body>,
-    next: axum::middleware::Next,
-) -> Result<axum::response::Response, axum::http::StatusCode> {
-    let client_ip = addr.ip();
-    
-    // Allow if:
-    // - Metrics are disabled (shouldn't reach here, but defensive)
-    // - IP whitelist is empty (allow all)
-    // - IP is in whitelist
-    // - IP is localhost
-    if !config.metrics_enabled {
-        tracing::warn!("Metrics access attempt from {} but metrics are disabled", client_ip);
-        return Err(axum::http::StatusCode::FORBIDDEN);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_window_rates() {
+        let window = RequestWindow::default();
+        assert_eq!(window.rates(1_000).error_rate, None);
+
+        for _ in 0..9 {
+            window.record(1_000, false);
+        }
+        window.record(1_100, true);
+
+        let rates = window.rates(1_100);
+        assert_eq!(rates.requests_per_minute, 2.0);
+        assert_eq!(rates.error_rate, Some(0.1));
     }
-    
-    if config.metrics_allowed_ips.is_empty() 
-        || config.metrics_allowed_ips.contains(&client_ip) 
-        || client_ip.is_loopback() {
-        tracing::debug!("Metrics access granted to {}", client_ip);
-        Ok(next.run(request).await)
-    } else {
-        tracing::warn!("Unauthorized metrics access attempt from {}", client_ip);
-        Err(axum::http::StatusCode::FORBIDDEN)
+
+    #[test]
+    fn test_request_window_forgets_old_requests() {
+        let window = RequestWindow::default();
+        window.record(1_000, true);
+        window.record(1_000 + REQUEST_WINDOW_SECS - 1, false);
+
+        let rates = window.rates(1_000 + REQUEST_WINDOW_SECS);
+        assert_eq!(rates.error_rate, Some(0.0));
+
+        window.record(1_000 + 2 * REQUEST_WINDOW_SECS, false);
+        assert_eq!(window.buckets.lock().unwrap().len(), 1);
     }
 }
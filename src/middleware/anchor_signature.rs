@@ -0,0 +1,312 @@
+//! Anchor Platform callback signatures.
+//!
+//! The platform signs every callback with its ed25519 key and sends
+//! `Signature: t=<unix seconds>, s=<base64 signature>`. The signed message is
+//! `<t>.<host>.<body>`, where `host` is the host the callback was sent to,
+//! without a port. Callbacks with a missing, malformed, stale or wrong
+//! signature get 401.
+
+use std::time::Duration;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{HeaderMap, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use thiserror::Error;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::middleware::ip_filter::{TrustedProxies, extract_client_ip};
+use crate::stellar::strkey::{self, AccountKind};
+use crate::utils::clock::SharedClock;
+
+pub const SIGNATURE_HEADER: &str = "Signature";
+
+/// Signatures further than this from now, either way, are rejected so a
+/// captured callback can't be replayed later
+pub const MAX_SIGNATURE_AGE: Duration = Duration::from_secs(5 * 60);
+
+/// Larger callbacks are rejected before their signature is checked
+const MAX_CALLBACK_BODY: usize = 2 * 1024 * 1024;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    #[error("missing Signature header")]
+    Missing,
+    #[error("Signature header must be 't=<timestamp>, s=<base64 signature>'")]
+    Malformed,
+    #[error("signature timestamp is more than {} seconds from now", MAX_SIGNATURE_AGE.as_secs())]
+    Stale,
+    #[error("signature does not match the anchor signing key")]
+    Mismatch,
+}
+
+/// Checks callback signatures against `ANCHOR_SIGNING_KEY`
+#[derive(Clone)]
+pub struct AnchorSignatureVerifier {
+    /// `None`: verification is off and every callback passes
+    key: Option<VerifyingKey>,
+    /// Only used to log the client of a rejected callback
+    trusted_proxies: Option<TrustedProxies>,
+    clock: SharedClock,
+}
+
+impl AnchorSignatureVerifier {
+    pub fn new(key: VerifyingKey, clock: SharedClock) -> Self {
+        Self {
+            key: Some(key),
+            trusted_proxies: None,
+            clock,
+        }
+    }
+
+    /// Accept callbacks without checking them; for tests and local development
+    pub fn disabled() -> Self {
+        Self {
+            key: None,
+            trusted_proxies: None,
+            clock: crate::utils::clock::system(),
+        }
+    }
+
+    /// `ANCHOR_SIGNING_KEY`, disabled when it is unset. Failed callbacks are
+    /// logged with the client address the IP allowlist would see.
+    pub fn from_config(config: &Config, clock: SharedClock) -> anyhow::Result<Self> {
+        let mut verifier = match &config.anchor_signing_key {
+            Some(account) => Self::new(parse_signing_key(account)?, clock),
+            None => Self::disabled(),
+        };
+        verifier.trusted_proxies = TrustedProxies::from_config(config);
+        Ok(verifier)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Verify the `Signature` header of a callback sent to `host`
+    pub fn verify(
+        &self,
+        headers: &HeaderMap,
+        host: &str,
+        body: &[u8],
+    ) -> Result<(), SignatureError> {
+        let Some(key) = &self.key else {
+            return Ok(());
+        };
+        let raw = headers
+            .get(SIGNATURE_HEADER)
+            .ok_or(SignatureError::Missing)?
+            .to_str()
+            .map_err(|_| SignatureError::Malformed)?;
+        let (timestamp, signature) = parse_signature_header(raw)?;
+
+        let age = self.clock.now().timestamp().abs_diff(timestamp);
+        if age > MAX_SIGNATURE_AGE.as_secs() {
+            return Err(SignatureError::Stale);
+        }
+
+        key.verify(&signed_message(timestamp, host, body), &signature)
+            .map_err(|_| SignatureError::Mismatch)
+    }
+}
+
+/// The ed25519 key of a `G...` account
+pub fn parse_signing_key(account: &str) -> anyhow::Result<VerifyingKey> {
+    match strkey::validate_account(account.trim()) {
+        Ok(AccountKind::Ed25519 { public_key }) => VerifyingKey::from_bytes(&public_key)
+            .map_err(|_| anyhow::anyhow!("ANCHOR_SIGNING_KEY is not a valid ed25519 key")),
+        Ok(AccountKind::Muxed { .. }) => {
+            anyhow::bail!("ANCHOR_SIGNING_KEY must be a G account, not a muxed account")
+        }
+        Err(e) => anyhow::bail!("ANCHOR_SIGNING_KEY: {}", e),
+    }
+}
+
+/// `Signature` header value for a callback sent to `host` at `timestamp`,
+/// as the Anchor Platform would send it
+pub fn sign(key: &SigningKey, timestamp: i64, host: &str, body: &[u8]) -> String {
+    let signature = key.sign(&signed_message(timestamp, host, body));
+    format!("t={}, s={}", timestamp, BASE64.encode(signature.to_bytes()))
+}
+
+fn signed_message(timestamp: i64, host: &str, body: &[u8]) -> Vec<u8> {
+    let mut message = format!("{}.{}.", timestamp, host).into_bytes();
+    message.extend_from_slice(body);
+    message
+}
+
+fn parse_signature_header(raw: &str) -> Result<(i64, Signature), SignatureError> {
+    let mut timestamp = None;
+    let mut signature = None;
+    for part in raw.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("s", value)) => signature = BASE64.decode(value).ok(),
+            _ => return Err(SignatureError::Malformed),
+        }
+    }
+    let timestamp = timestamp.ok_or(SignatureError::Malformed)?;
+    let signature = signature
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        .ok_or(SignatureError::Malformed)?;
+    Ok((timestamp, signature))
+}
+
+/// `Host` without its port
+fn request_host(headers: &HeaderMap) -> &str {
+    let host = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    match host.strip_prefix('[') {
+        // IPv6 literals keep their brackets
+        Some(rest) => rest.find(']').map_or(host, |end| &host[..end + 2]),
+        None => host.split(':').next().unwrap_or(host),
+    }
+}
+
+/// Reject callbacks without a valid Anchor Platform signature. The body is
+/// buffered to be checked, then handed on unchanged.
+pub async fn verify_anchor_signature(
+    State(verifier): State<AnchorSignatureVerifier>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !verifier.is_enabled() {
+        return next.run(req).await;
+    }
+
+    let client_ip = extract_client_ip(req.headers(), req.extensions(), verifier.trusted_proxies);
+    let (parts, body) = req.into_parts();
+    let body: Bytes = match axum::body::to_bytes(body, MAX_CALLBACK_BODY).await {
+        Ok(body) => body,
+        Err(_) => {
+            return AppError::BadRequest("callback body is too large or unreadable".to_string())
+                .into_response();
+        }
+    };
+
+    if let Err(e) = verifier.verify(&parts.headers, request_host(&parts.headers), &body) {
+        tracing::warn!(
+            client_ip = ?client_ip,
+            path = %parts.uri.path(),
+            reason = %e,
+            "Rejected callback with an invalid signature"
+        );
+        return AppError::Unauthorized(e.to_string()).into_response();
+    }
+
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::clock::TestClock;
+    use axum::http::HeaderValue;
+
+    const NOW: i64 = 1_767_225_600;
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    fn verifier() -> AnchorSignatureVerifier {
+        let clock = TestClock::at(chrono::DateTime::from_timestamp(NOW, 0).unwrap());
+        AnchorSignatureVerifier::new(signing_key().verifying_key(), clock.shared())
+    }
+
+    fn headers(signature: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(SIGNATURE_HEADER, HeaderValue::from_str(signature).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_accepts_a_platform_signature() {
+        let body = br#"{"id":"123","status":"completed"}"#;
+        let signature = sign(&signing_key(), NOW - 30, "api.example.com", body);
+        assert_eq!(verifier().verify(&headers(&signature), "api.example.com", body), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_other_bodies_hosts_and_keys() {
+        let body = b"payload";
+        let signature = sign(&signing_key(), NOW, "api.example.com", body);
+        let verifier = verifier();
+        assert_eq!(
+            verifier.verify(&headers(&signature), "api.example.com", b"tampered"),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verifier.verify(&headers(&signature), "other.example.com", body),
+            Err(SignatureError::Mismatch)
+        );
+
+        let stranger = SigningKey::from_bytes(&[9; 32]);
+        let forged = sign(&stranger, NOW, "api.example.com", body);
+        assert_eq!(
+            verifier.verify(&headers(&forged), "api.example.com", body),
+            Err(SignatureError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn test_rejects_stale_and_malformed_signatures() {
+        let body = b"payload";
+        let verifier = verifier();
+        let old = NOW - MAX_SIGNATURE_AGE.as_secs() as i64 - 1;
+        let stale = sign(&signing_key(), old, "api.example.com", body);
+        assert_eq!(
+            verifier.verify(&headers(&stale), "api.example.com", body),
+            Err(SignatureError::Stale)
+        );
+
+        assert_eq!(
+            verifier.verify(&HeaderMap::new(), "api.example.com", body),
+            Err(SignatureError::Missing)
+        );
+        for malformed in ["", "t=abc, s=AAAA", "s=AAAA", "t=1767225600", "t=1767225600, s=!!"] {
+            assert_eq!(
+                verifier.verify(&headers(malformed), "api.example.com", body),
+                Err(SignatureError::Malformed),
+                "{}",
+                malformed
+            );
+        }
+    }
+
+    #[test]
+    fn test_disabled_verifier_accepts_anything() {
+        let verifier = AnchorSignatureVerifier::disabled();
+        assert!(!verifier.is_enabled());
+        assert_eq!(verifier.verify(&HeaderMap::new(), "", b"payload"), Ok(()));
+    }
+
+    #[test]
+    fn test_parse_signing_key() {
+        let account = strkey::encode_ed25519(signing_key().verifying_key().as_bytes());
+        assert_eq!(parse_signing_key(&account).unwrap(), signing_key().verifying_key());
+        assert!(parse_signing_key("GABC").is_err());
+    }
+
+    #[test]
+    fn test_request_host_drops_the_port() {
+        let host = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::HOST, HeaderValue::from_str(value).unwrap());
+            request_host(&headers).to_string()
+        };
+        assert_eq!(host("api.example.com"), "api.example.com");
+        assert_eq!(host("api.example.com:8443"), "api.example.com");
+        assert_eq!(host("[::1]:3000"), "[::1]");
+        assert_eq!(request_host(&HeaderMap::new()), "");
+    }
+}
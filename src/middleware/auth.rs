@@ -1,22 +1,443 @@
+//! Authentication of admin, scoped-token and anchor routes.
+//!
+//! Admin routes take a static API key from `ADMIN_API_KEYS` (or the legacy
+//! `ADMIN_API_KEY`) or an HS256 JWT signed with `ADMIN_JWT_SECRET`. Either
+//! way the caller is an [`AdminPrincipal`] with a role: `read_only` callers
+//! may only read, `operator` callers may also change things. The principal
+//! is put into the request extensions, so handlers can record who acted.
+
+use std::sync::Arc;
+
 use axum::{
     body::Body,
-    http::{Request, StatusCode},
+    extract::{MatchedPath, State},
+    http::{Method, Request, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::AdminAuthConfig;
+use crate::error::AppError;
+use crate::services::api_tokens::{ApiTokenService, TOKEN_PREFIX};
+use crate::utils::clock::{self, SharedClock};
+
+/// What an admin caller may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminRole {
+    /// `GET` and `HEAD` only
+    ReadOnly,
+    /// Everything, including changes
+    Operator,
+}
+
+impl AdminRole {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "read_only" => Some(AdminRole::ReadOnly),
+            "operator" => Some(AdminRole::Operator),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdminRole::ReadOnly => "read_only",
+            AdminRole::Operator => "operator",
+        }
+    }
+
+    /// The role a request needs when its route asks for nothing more:
+    /// reads are open to read-only callers, anything else needs an operator
+    pub fn required_for(method: &Method) -> Self {
+        if method == Method::GET || method == Method::HEAD {
+            AdminRole::ReadOnly
+        } else {
+            AdminRole::Operator
+        }
+    }
+}
+
+/// How the admin caller authenticated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    ApiKey,
+    Jwt,
+}
+
+/// The authenticated admin caller, in the request extensions of every
+/// admin route
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AdminPrincipal {
+    /// Key name, or the JWT's `sub`
+    pub name: String,
+    pub role: AdminRole,
+    pub method: AuthMethod,
+}
+
+impl AdminPrincipal {
+    /// Audit actor for changes the principal makes
+    pub fn actor(&self) -> String {
+        format!("admin:{}", self.name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AdminClaims {
+    sub: String,
+    role: AdminRole,
+    exp: i64,
+    #[serde(default)]
+    nbf: Option<i64>,
+    #[serde(default)]
+    iss: Option<String>,
+}
+
+struct Credentials {
+    /// sha256 of each key, so comparing doesn't leak how much of a key matched
+    api_keys: Vec<([u8; 32], AdminPrincipal)>,
+    jwt_secret: Option<String>,
+    jwt_issuer: Option<String>,
+}
+
+/// Checks admin credentials against [`AdminAuthConfig`]
+#[derive(Clone)]
+pub struct AdminAuthenticator {
+    credentials: Arc<Credentials>,
+    clock: SharedClock,
+}
+
+impl Default for AdminAuthenticator {
+    /// The development default: `admin-secret-key` as an operator key
+    fn default() -> Self {
+        Self::from_config(&AdminAuthConfig::default(), clock::system())
+    }
+}
+
+impl AdminAuthenticator {
+    pub fn from_config(config: &AdminAuthConfig, clock: SharedClock) -> Self {
+        let api_keys = config
+            .api_keys
+            .iter()
+            .map(|key| {
+                let principal = AdminPrincipal {
+                    name: key.name.clone(),
+                    role: key.role,
+                    method: AuthMethod::ApiKey,
+                };
+                (Sha256::digest(key.key.as_bytes()).into(), principal)
+            })
+            .collect();
+        Self {
+            credentials: Arc::new(Credentials {
+                api_keys,
+                jwt_secret: config.jwt_secret.clone(),
+                jwt_issuer: config.jwt_issuer.clone(),
+            }),
+            clock,
+        }
+    }
+
+    /// The principal of a bearer credential: an API key or a JWT
+    pub fn authenticate(&self, credential: &str) -> Option<AdminPrincipal> {
+        let digest: [u8; 32] = Sha256::digest(credential.as_bytes()).into();
+        if let Some((_, principal)) = self.credentials.api_keys.iter().find(|(key, _)| *key == digest) {
+            return Some(principal.clone());
+        }
+        if credential.matches('.').count() == 2 {
+            return self.verify_jwt(credential);
+        }
+        None
+    }
+
+    fn verify_jwt(&self, token: &str) -> Option<AdminPrincipal> {
+        let secret = self.credentials.jwt_secret.as_deref()?;
+        let (signed, signature) = token.rsplit_once('.')?;
+        let (header, claims) = signed.split_once('.')?;
+
+        let header: JwtHeader = serde_json::from_slice(&BASE64URL.decode(header).ok()?).ok()?;
+        // Only the algorithm the secret is for; never `none`
+        if header.alg != "HS256" {
+            return None;
+        }
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).ok()?;
+        mac.update(signed.as_bytes());
+        mac.verify_slice(&BASE64URL.decode(signature).ok()?).ok()?;
+
+        let claims: AdminClaims = serde_json::from_slice(&BASE64URL.decode(claims).ok()?).ok()?;
+        let now = self.clock.now().timestamp();
+        if claims.exp <= now || claims.nbf.is_some_and(|nbf| nbf > now) {
+            return None;
+        }
+        if let Some(issuer) = &self.credentials.jwt_issuer {
+            if claims.iss.as_ref() != Some(issuer) {
+                return None;
+            }
+        }
+        Some(AdminPrincipal {
+            name: claims.sub,
+            role: claims.role,
+            method: AuthMethod::Jwt,
+        })
+    }
+}
 
-pub async fn admin_auth(req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
-    let auth_header = req
-        .headers()
+fn authorization(req: &Request<Body>) -> Option<&str> {
+    req.headers()
         .get("Authorization")
-        .and_then(|h| h.to_str().ok());
+        .and_then(|h| h.to_str().ok())
+}
+
+fn bearer(req: &Request<Body>) -> Option<String> {
+    let auth = authorization(req)?;
+    Some(auth.strip_prefix("Bearer ").unwrap_or(auth).to_string())
+}
+
+fn forbidden(principal: &AdminPrincipal, required: AdminRole) -> Response {
+    AppError::Forbidden(format!(
+        "{} is {}, this route needs {}",
+        principal.name,
+        principal.role.as_str(),
+        required.as_str()
+    ))
+    .into_response()
+}
+
+/// Authenticates admin routes. Reads need `read_only`, anything else
+/// `operator`; [`require_operator`] raises a route to `operator` for reads
+/// too.
+pub async fn admin_auth(
+    State(admins): State<AdminAuthenticator>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let Some(principal) = bearer(&req).and_then(|credential| admins.authenticate(&credential)) else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    let required = AdminRole::required_for(req.method());
+    if principal.role < required {
+        tracing::warn!(admin = %principal.name, method = %req.method(), "Admin route denied to role {}", principal.role.as_str());
+        return Ok(forbidden(&principal, required));
+    }
+    req.extensions_mut().insert(principal);
+    Ok(next.run(req).await)
+}
+
+/// Route layer for admin routes only operators may use, reads included.
+/// Must run inside [`admin_auth`] or [`scoped_auth`].
+pub async fn require_operator(req: Request<Body>, next: Next) -> Response {
+    match req.extensions().get::<AdminPrincipal>() {
+        Some(principal) if principal.role == AdminRole::Operator => next.run(req).await,
+        Some(principal) => forbidden(principal, AdminRole::Operator),
+        None => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+/// State of [`scoped_auth`]: admin credentials are accepted as well as
+/// scoped tokens
+#[derive(Clone)]
+pub struct ScopedAuth {
+    pub tokens: ApiTokenService,
+    pub admins: AdminAuthenticator,
+}
+
+impl ScopedAuth {
+    pub fn new(tokens: ApiTokenService, admins: AdminAuthenticator) -> Self {
+        Self { tokens, admins }
+    }
+}
+
+/// Authenticates read routes that accept either admin credentials or a
+/// scoped API token.
+///
+/// Must be added with `route_layer` so the matched route is known. A scoped
+/// token is rejected with 403 outside its routes; inside them its
+/// [`TokenScope`](crate::services::api_tokens::TokenScope) is put into the
+/// request extensions for the handler to apply. Admin callers are checked
+/// like [`admin_auth`] checks them.
+pub async fn scoped_auth(
+    State(auth): State<ScopedAuth>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let Some(credential) = bearer(&req) else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+
+    if !credential.starts_with(TOKEN_PREFIX) {
+        let Some(principal) = auth.admins.authenticate(&credential) else {
+            return Err(StatusCode::UNAUTHORIZED);
+        };
+        let required = AdminRole::required_for(req.method());
+        if principal.role < required {
+            return Ok(forbidden(&principal, required));
+        }
+        req.extensions_mut().insert(principal);
+        return Ok(next.run(req).await);
+    }
+
+    let (token, scope) = match auth.tokens.authenticate(&credential).await {
+        Ok(Some(found)) => found,
+        // Unknown, revoked and expired tokens look the same to the caller
+        Ok(None) => return Err(StatusCode::UNAUTHORIZED),
+        Err(e) => return Ok(e.into_response()),
+    };
+
+    let method = req.method().as_str().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_default();
+    if !scope.allows(&method, &route) {
+        tracing::warn!(token_id = %token.id, %method, %route, "Scoped token used outside its routes");
+        return Ok(AppError::Forbidden(format!("{} {}: outside the token's scope", method, route))
+            .into_response());
+    }
+
+    tracing::debug!(token_id = %token.id, %method, %route, "Scoped token request");
+    auth.tokens.record_request(token.id, &method, &route);
+    req.extensions_mut().insert(scope);
+    Ok(next.run(req).await)
+}
+
+/// Authenticates anchor-facing endpoints with `ANCHOR_API_KEY`.
+pub async fn anchor_auth(req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
+    let auth_header = authorization(&req);
 
-    let admin_api_key = std::env::var("ADMIN_API_KEY").unwrap_or_else(|_| "admin-secret-key".to_string());
+    let Ok(anchor_api_key) = std::env::var("ANCHOR_API_KEY") else {
+        // No default key: anchor endpoints stay closed until one is configured
+        return Err(StatusCode::UNAUTHORIZED);
+    };
 
     match auth_header {
-        Some(auth) if auth == format!("Bearer {}", admin_api_key) || auth == admin_api_key => {
+        Some(auth) if auth == format!("Bearer {}", anchor_api_key) || auth == anchor_api_key => {
             Ok(next.run(req).await)
         }
         _ => Err(StatusCode::UNAUTHORIZED),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AdminApiKey;
+    use crate::utils::clock::TestClock;
+    use chrono::{DateTime, Utc};
+    use serde_json::json;
+
+    const SECRET: &str = "jwt-test-secret";
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-03-01T12:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    fn authenticator(issuer: Option<&str>) -> AdminAuthenticator {
+        let config = AdminAuthConfig {
+            api_keys: vec![
+                AdminApiKey {
+                    name: "dashboard".to_string(),
+                    role: AdminRole::ReadOnly,
+                    key: "read-key-0123456789".to_string(),
+                },
+                AdminApiKey {
+                    name: "alice".to_string(),
+                    role: AdminRole::Operator,
+                    key: "operator-key-0123456789".to_string(),
+                },
+            ],
+            jwt_secret: Some(SECRET.to_string()),
+            jwt_issuer: issuer.map(str::to_string),
+        };
+        AdminAuthenticator::from_config(&config, TestClock::at(now()).shared())
+    }
+
+    fn jwt(alg: &str, claims: serde_json::Value, secret: &str) -> String {
+        let header = BASE64URL.encode(json!({ "alg": alg, "typ": "JWT" }).to_string());
+        let claims = BASE64URL.encode(claims.to_string());
+        let signed = format!("{}.{}", header, claims);
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(signed.as_bytes());
+        format!("{}.{}", signed, BASE64URL.encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn test_api_keys_carry_their_role() {
+        let admins = authenticator(None);
+        let reader = admins.authenticate("read-key-0123456789").unwrap();
+        assert_eq!((reader.name.as_str(), reader.role), ("dashboard", AdminRole::ReadOnly));
+        let operator = admins.authenticate("operator-key-0123456789").unwrap();
+        assert_eq!(operator.role, AdminRole::Operator);
+        assert_eq!(operator.method, AuthMethod::ApiKey);
+        assert_eq!(operator.actor(), "admin:alice");
+        assert_eq!(admins.authenticate("operator-key"), None);
+    }
+
+    #[test]
+    fn test_valid_jwt() {
+        let exp = now().timestamp() + 60;
+        let token = jwt("HS256", json!({ "sub": "bob", "role": "operator", "exp": exp }), SECRET);
+        assert_eq!(
+            authenticator(None).authenticate(&token),
+            Some(AdminPrincipal {
+                name: "bob".to_string(),
+                role: AdminRole::Operator,
+                method: AuthMethod::Jwt,
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejected_jwts() {
+        let admins = authenticator(Some("https://auth.example.com"));
+        let exp = now().timestamp() + 60;
+        let claims = json!({ "sub": "bob", "role": "operator", "exp": exp, "iss": "https://auth.example.com" });
+        assert!(admins.authenticate(&jwt("HS256", claims.clone(), SECRET)).is_some());
+
+        assert!(admins.authenticate(&jwt("HS256", claims.clone(), "other-secret")).is_none());
+        assert!(admins.authenticate(&jwt("none", claims.clone(), SECRET)).is_none());
+        assert!(admins.authenticate(&jwt("HS512", claims, SECRET)).is_none());
+
+        let expired = json!({ "sub": "bob", "role": "operator", "exp": now().timestamp(), "iss": "https://auth.example.com" });
+        assert!(admins.authenticate(&jwt("HS256", expired, SECRET)).is_none());
+        let early = json!({ "sub": "bob", "role": "operator", "exp": exp, "nbf": exp, "iss": "https://auth.example.com" });
+        assert!(admins.authenticate(&jwt("HS256", early, SECRET)).is_none());
+        let wrong_issuer = json!({ "sub": "bob", "role": "operator", "exp": exp, "iss": "https://evil.example.com" });
+        assert!(admins.authenticate(&jwt("HS256", wrong_issuer, SECRET)).is_none());
+        let unknown_role = json!({ "sub": "bob", "role": "root", "exp": exp, "iss": "https://auth.example.com" });
+        assert!(admins.authenticate(&jwt("HS256", unknown_role, SECRET)).is_none());
+    }
+
+    #[test]
+    fn test_jwts_need_a_secret() {
+        let config = AdminAuthConfig {
+            jwt_secret: None,
+            ..Default::default()
+        };
+        let admins = AdminAuthenticator::from_config(&config, TestClock::at(now()).shared());
+        let exp = now().timestamp() + 60;
+        let token = jwt("HS256", json!({ "sub": "bob", "role": "operator", "exp": exp }), "");
+        assert!(admins.authenticate(&token).is_none());
+    }
+
+    #[test]
+    fn test_required_role() {
+        assert_eq!(AdminRole::required_for(&Method::GET), AdminRole::ReadOnly);
+        assert_eq!(AdminRole::required_for(&Method::HEAD), AdminRole::ReadOnly);
+        for method in [Method::POST, Method::PUT, Method::DELETE, Method::PATCH] {
+            assert_eq!(AdminRole::required_for(&method), AdminRole::Operator);
+        }
+        assert!(AdminRole::ReadOnly < AdminRole::Operator);
+    }
+}
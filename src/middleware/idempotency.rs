@@ -1,178 +1,103 @@
-use axum::{
-    Json,
-    extract::{Request, State},
-    http::StatusCode,
-    middleware::Next,
-    response::{IntoResponse, Response},
-};
 use redis::AsyncCommands;
-use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
-const IDEMPOTENCY_TTL: u64 = 86400; // 24 hours in seconds
-const IDEMPOTENCY_PREFIX: &str = "idempotency:";
+use crate::error::AppError;
+use crate::services::redis_health::{RedisDecision, RedisFeature, RedisHealth};
 
+const CALLBACK_PREFIX: &str = "callback:";
+
+/// How long the first response to a callback is remembered by default
+pub const DEFAULT_CALLBACK_TTL: Duration = Duration::from_secs(86400);
+
+/// Redis record of callback responses, so the callback handlers answer
+/// replays without touching the database
 #[derive(Clone)]
 pub struct IdempotencyService {
     redis_client: redis::Client,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct CachedResponse {
-    status: u16,
-    body: String,
+    health: RedisHealth,
+    callback_ttl: Duration,
 }
 
 impl IdempotencyService {
-    pub fn new(redis_url: &str) -> anyhow::Result<Self> {
+    pub fn new(redis_url: &str, health: RedisHealth) -> anyhow::Result<Self> {
         let redis_client = redis::Client::open(redis_url)?;
-        Ok(Self { redis_client })
+        Ok(Self {
+            redis_client,
+            health,
+            callback_ttl: DEFAULT_CALLBACK_TTL,
+        })
     }
 
-    /// Check if a request with this ID is already being processed or was completed
-    pub async fn check_idempotency(
-        &self,
-        idempotency_key: &str,
-    ) -> anyhow::Result<IdempotencyStatus> {
-        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
-        let key = format!("{}{}", IDEMPOTENCY_PREFIX, idempotency_key);
-
-        // Try to get existing value
-        let existing: Option<String> = conn.get(&key).await?;
-
-        match existing {
-            Some(value) => {
-                if value == "PROCESSING" {
-                    Ok(IdempotencyStatus::Processing)
-                } else {
-                    // Deserialize cached response
-                    let cached: CachedResponse = serde_json::from_str(&value)?;
-                    Ok(IdempotencyStatus::Completed(cached))
-                }
-            }
-            None => {
-                // Set PROCESSING lock with shorter TTL (5 minutes)
-                let _: () = conn.set_ex(&key, "PROCESSING", 300).await?;
-                Ok(IdempotencyStatus::New)
-            }
-        }
+    /// Remember callback responses for `ttl` instead of [`DEFAULT_CALLBACK_TTL`]
+    pub fn with_callback_ttl(mut self, ttl: Duration) -> Self {
+        self.callback_ttl = ttl;
+        self
     }
 
-    /// Store the successful response for future duplicate requests
-    pub async fn store_response(
+    /// The response body first given to the callback for this anchor
+    /// transaction id, if Redis still has it.
+    ///
+    /// While Redis is down this is a miss, and the callback handler's
+    /// database check catches the duplicate instead; a required idempotency
+    /// store fails the request.
+    pub async fn cached_callback(
         &self,
-        idempotency_key: &str,
-        status: u16,
-        body: String,
-    ) -> anyhow::Result<()> {
-        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
-        let key = format!("{}{}", IDEMPOTENCY_PREFIX, idempotency_key);
-
-        let cached = CachedResponse { status, body };
-        let serialized = serde_json::to_string(&cached)?;
-
-        // Store with 24-hour TTL
-        let _: () = conn.set_ex(&key, serialized, IDEMPOTENCY_TTL).await?;
-        Ok(())
-    }
-
-    /// Release the processing lock if an error occurs
-    pub async fn release_lock(&self, idempotency_key: &str) -> anyhow::Result<()> {
-        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
-        let key = format!("{}{}", IDEMPOTENCY_PREFIX, idempotency_key);
-        let _: () = conn.del(&key).await?;
-        Ok(())
-    }
-}
-
-#[derive(Debug)]
-pub enum IdempotencyStatus {
-    New,
-    Processing,
-    Completed(CachedResponse),
-}
-
-/// Middleware to handle idempotency for webhook requests
-pub async fn idempotency_middleware(
-    State(service): State<IdempotencyService>,
-    request: Request,
-    next: Next,
-) -> Response {
-    // Extract idempotency key from request
-    // This could be from headers, query params, or body
-    // For now, we'll extract from a custom header
-    let idempotency_key = match request.headers().get("x-idempotency-key") {
-        Some(key) => match key.to_str() {
-            Ok(k) => k.to_string(),
-            Err(_) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(serde_json::json!({
-                        "error": "Invalid idempotency key format"
-                    })),
-                )
-                    .into_response();
-            }
-        },
-        None => {
-            // If no idempotency key provided, proceed without idempotency check
-            return next.run(request).await;
+        anchor_transaction_id: &str,
+    ) -> Result<Option<String>, AppError> {
+        match self.health.decide(RedisFeature::Idempotency) {
+            RedisDecision::UseRedis => {}
+            RedisDecision::Degrade(_) => return Ok(None),
+            RedisDecision::FailClosed => return Err(unavailable()),
         }
-    };
-
-    // Check idempotency status
-    match service.check_idempotency(&idempotency_key).await {
-        Ok(IdempotencyStatus::New) => {
-            // Process the request
-            let response = next.run(request).await;
 
-            // If successful (2xx), cache the response
-            if response.status().is_success() {
-                // Extract response body and status
-                let status = response.status().as_u16();
-
-                // For simplicity, we'll store a success marker
-                // In production, you might want to capture the actual response body
-                let body = serde_json::json!({"status": "success"}).to_string();
-
-                if let Err(e) = service.store_response(&idempotency_key, status, body).await {
-                    tracing::error!("Failed to store idempotency response: {}", e);
-                }
-            } else {
-                // Release lock on failure
-                if let Err(e) = service.release_lock(&idempotency_key).await {
-                    tracing::error!("Failed to release idempotency lock: {}", e);
+        let key = format!("{}{}", CALLBACK_PREFIX, anchor_transaction_id);
+        let cached: redis::RedisResult<Option<String>> = async {
+            let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+            conn.get(&key).await
+        }
+        .await;
+        match cached {
+            Ok(cached) => {
+                self.health.record_success();
+                Ok(cached)
+            }
+            Err(e) => {
+                self.health.record_failure();
+                if self.health.is_required(RedisFeature::Idempotency) {
+                    return Err(unavailable());
                 }
+                tracing::warn!(
+                    "Callback idempotency lookup failed, falling back to the database: {}",
+                    e
+                );
+                Ok(None)
             }
-
-            response
         }
-        Ok(IdempotencyStatus::Processing) => {
-            // Request is currently being processed
-            (
-                StatusCode::TOO_MANY_REQUESTS,
-                Json(serde_json::json!({
-                    "error": "Request is currently being processed",
-                    "retry_after": 5
-                })),
-            )
-                .into_response()
+    }
+
+    /// Remember the response given to a new callback. A failure is only
+    /// logged: the database still rejects the replay.
+    pub async fn cache_callback(&self, anchor_transaction_id: &str, body: &str) {
+        if self.health.decide(RedisFeature::Idempotency) != RedisDecision::UseRedis {
+            return;
         }
-        Ok(IdempotencyStatus::Completed(cached)) => {
-            // Return cached response
-            let status = StatusCode::from_u16(cached.status).unwrap_or(StatusCode::OK);
-            (
-                status,
-                Json(serde_json::json!({
-                    "cached": true,
-                    "message": "Request already processed"
-                })),
-            )
-                .into_response()
+
+        let key = format!("{}{}", CALLBACK_PREFIX, anchor_transaction_id);
+        let stored: redis::RedisResult<()> = async {
+            let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+            conn.set_ex(&key, body, self.callback_ttl.as_secs()).await
         }
-        Err(e) => {
-            tracing::error!("Idempotency check failed: {}", e);
-            // On Redis failure, proceed with request (fail open)
-            next.run(request).await
+        .await;
+        match stored {
+            Ok(()) => self.health.record_success(),
+            Err(e) => {
+                self.health.record_failure();
+                tracing::warn!(anchor_transaction_id, "Failed to cache callback response: {}", e);
+            }
         }
     }
 }
+
+fn unavailable() -> AppError {
+    AppError::Unavailable("Idempotency store unavailable".to_string())
+}
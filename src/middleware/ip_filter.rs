@@ -1,3 +1,6 @@
+//! `ALLOWED_IPS` enforcement for the anchor callback routes and the admin
+//! routes. Requests from other addresses get 403.
+
 use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
 use std::task::{Context, Poll};
@@ -7,19 +10,70 @@ use axum::http::{HeaderMap, Request, StatusCode, header};
 use axum::response::{IntoResponse, Response};
 use tower::{Layer, Service};
 
-use crate::config::AllowedIps;
+use crate::config::{AllowedIps, Config, ProxyHeader};
+
+/// The proxies in front of the server and the header they record the client
+/// address in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct TrustedProxies {
+    pub header: ProxyHeader,
+    /// Proxies appending to `header` in front of the one that connects
+    pub depth: usize,
+}
+
+impl TrustedProxies {
+    /// `None` without `TRUST_PROXY_HEADERS`
+    pub fn from_config(config: &Config) -> Option<Self> {
+        config.trust_proxy_headers.then_some(Self {
+            header: config.trusted_proxy_header,
+            depth: config.trusted_proxy_depth,
+        })
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct IpFilterLayer {
     allowed_ips: AllowedIps,
-    trusted_proxy_depth: usize,
+    /// `None`: forwarded headers are ignored and the peer address is used
+    trusted_proxies: Option<TrustedProxies>,
 }
 
 impl IpFilterLayer {
+    /// Trust `X-Forwarded-For`, skipping the entries appended by
+    /// `trusted_proxy_depth` proxies in front of the one that connects
     pub fn new(allowed_ips: AllowedIps, trusted_proxy_depth: usize) -> Self {
         Self {
             allowed_ips,
-            trusted_proxy_depth,
+            trusted_proxies: Some(TrustedProxies {
+                header: ProxyHeader::XForwardedFor,
+                depth: trusted_proxy_depth,
+            }),
+        }
+    }
+
+    /// Check the peer address only; forwarded headers are ignored
+    pub fn direct(allowed_ips: AllowedIps) -> Self {
+        Self {
+            allowed_ips,
+            trusted_proxies: None,
+        }
+    }
+
+    /// Read the client address from `header` instead; no effect on a
+    /// [`direct`](Self::direct) filter
+    pub fn with_proxy_header(mut self, header: ProxyHeader) -> Self {
+        if let Some(proxies) = self.trusted_proxies.as_mut() {
+            proxies.header = header;
+        }
+        self
+    }
+
+    /// `ALLOWED_IPS`, trusting `TRUSTED_PROXY_HEADER` with
+    /// `TRUST_PROXY_HEADERS`
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            allowed_ips: config.allowed_ips.clone(),
+            trusted_proxies: TrustedProxies::from_config(config),
         }
     }
 }
@@ -31,7 +85,7 @@ impl<S> Layer<S> for IpFilterLayer {
         IpFilterService {
             inner,
             allowed_ips: self.allowed_ips.clone(),
-            trusted_proxy_depth: self.trusted_proxy_depth,
+            trusted_proxies: self.trusted_proxies,
         }
     }
 }
@@ -40,7 +94,7 @@ impl<S> Layer<S> for IpFilterLayer {
 pub struct IpFilterService<S> {
     inner: S,
     allowed_ips: AllowedIps,
-    trusted_proxy_depth: usize,
+    trusted_proxies: Option<TrustedProxies>,
 }
 
 impl<S, B> Service<Request<B>> for IpFilterService<S>
@@ -50,21 +104,22 @@ where
 {
     type Response = S::Response;
     type Error = S::Error;
-    type Future = futures_util::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+    type Future = futures::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.inner.poll_ready(cx)
     }
 
     fn call(&mut self, req: Request<B>) -> Self::Future {
-        let allowed_ips = self.allowed_ips.clone();
-        let trusted_proxy_depth = self.trusted_proxy_depth;
-
-        let client_ip = extract_client_ip(req.headers(), req.extensions(), trusted_proxy_depth);
-        let allowed = is_allowed(client_ip, &allowed_ips);
+        let client_ip = extract_client_ip(req.headers(), req.extensions(), self.trusted_proxies);
+        let allowed = is_allowed(client_ip, &self.allowed_ips);
 
         if !allowed {
-            tracing::warn!(client_ip = ?client_ip, "blocked callback request from non-whitelisted IP");
+            tracing::warn!(
+                client_ip = ?client_ip,
+                path = %req.uri().path(),
+                "blocked callback request from non-whitelisted IP"
+            );
             let response = StatusCode::FORBIDDEN.into_response();
             return Box::pin(async move { Ok(response) });
         }
@@ -78,18 +133,45 @@ fn is_allowed(client_ip: Option<IpAddr>, allowed_ips: &AllowedIps) -> bool {
     match allowed_ips {
         AllowedIps::Any => true,
         AllowedIps::Cidrs(cidrs) => client_ip
-            .map(|ip| cidrs.iter().any(|cidr| cidr.contains(&ip)))
+            .map(|ip| {
+                // A dual-stack listener reports IPv4 clients as ::ffff:a.b.c.d
+                let unmapped = unmap_ipv4(ip);
+                cidrs
+                    .iter()
+                    .any(|cidr| cidr.contains(&ip) || cidr.contains(&unmapped))
+            })
             .unwrap_or(false),
     }
 }
 
-fn extract_client_ip(
+/// The IPv4 address inside an IPv4-mapped IPv6 address
+fn unmap_ipv4(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+/// The client address the allowlist judges. Behind trusted proxies it comes
+/// from their header; if that is present but names no usable client (an
+/// `unknown` hop, garbage, a chain too short) there is none, rather than the
+/// proxy's own address. Only requests without the header fall back to the peer.
+pub(crate) fn extract_client_ip(
     headers: &HeaderMap,
     extensions: &axum::http::Extensions,
-    trusted_proxy_depth: usize,
+    trusted_proxies: Option<TrustedProxies>,
 ) -> Option<IpAddr> {
-    if let Some(ip) = extract_from_x_forwarded_for(headers, trusted_proxy_depth) {
-        return Some(ip);
+    if let Some(proxies) = trusted_proxies {
+        let name = match proxies.header {
+            ProxyHeader::XForwardedFor => header::X_FORWARDED_FOR,
+            ProxyHeader::Forwarded => header::FORWARDED,
+        };
+        if headers.contains_key(&name) {
+            return match proxies.header {
+                ProxyHeader::XForwardedFor => extract_from_x_forwarded_for(headers, proxies.depth),
+                ProxyHeader::Forwarded => extract_from_forwarded(headers, proxies.depth),
+            };
+        }
     }
 
     extensions
@@ -97,6 +179,9 @@ fn extract_client_ip(
         .map(|connect_info| connect_info.0.ip())
 }
 
+/// The untrusted entry next to those appended by the trusted proxies, which
+/// is as far left as the chain can be believed. `None` when the chain is too
+/// short to have one.
 fn extract_from_x_forwarded_for(headers: &HeaderMap, trusted_proxy_depth: usize) -> Option<IpAddr> {
     let raw = headers.get(header::X_FORWARDED_FOR)?.to_str().ok()?;
 
@@ -126,6 +211,42 @@ fn parse_ip_from_xff_entry(value: &str) -> Option<IpAddr> {
     None
 }
 
+/// [`extract_from_x_forwarded_for`] for RFC 7239 `Forwarded`. Every element
+/// is a hop, so one the proxies recorded as `unknown` or an obfuscated
+/// `_name` still counts; if it is the one picked the request has no client
+/// address and is refused.
+fn extract_from_forwarded(headers: &HeaderMap, trusted_proxy_depth: usize) -> Option<IpAddr> {
+    // Repeated headers are one list, in order
+    let mut chain = Vec::new();
+    for value in headers.get_all(header::FORWARDED) {
+        let raw = value.to_str().ok()?;
+        chain.extend(raw.split(',').map(forwarded_for));
+    }
+
+    if chain.is_empty() || trusted_proxy_depth >= chain.len() {
+        return None;
+    }
+
+    let index = chain.len() - 1 - trusted_proxy_depth;
+    chain[index]
+}
+
+/// The address in a `Forwarded` element's `for=` parameter, e.g.
+/// `for=192.0.2.60;proto=https` or `for="[2001:db8::1]:4711"`
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    let value = element.split(';').find_map(|pair| {
+        let (name, value) = pair.trim().split_once('=')?;
+        name.trim().eq_ignore_ascii_case("for").then_some(value.trim())
+    })?;
+    let value = value.trim_matches('"');
+
+    if let Some(rest) = value.strip_prefix('[') {
+        let (ip, _port) = rest.split_once(']')?;
+        return IpAddr::from_str(ip).ok();
+    }
+    parse_ip_from_xff_entry(value)
+}
+
 #[cfg(test)]
 mod tests {
     use std::convert::Infallible;
@@ -166,6 +287,85 @@ mod tests {
         assert_eq!(ip, None);
     }
 
+    fn forwarded(values: &[&'static str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(header::FORWARDED, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn forwarded_uses_for_parameter_next_to_trusted_proxies() {
+        let headers = forwarded(&["for=192.0.2.60;proto=https;by=203.0.113.43, for=198.51.100.7"]);
+
+        assert_eq!(
+            extract_from_forwarded(&headers, 1),
+            Some(IpAddr::from([192, 0, 2, 60]))
+        );
+        assert_eq!(
+            extract_from_forwarded(&headers, 0),
+            Some(IpAddr::from([198, 51, 100, 7]))
+        );
+        assert_eq!(extract_from_forwarded(&headers, 2), None);
+    }
+
+    #[test]
+    fn forwarded_parses_quoted_ipv6_and_ports() {
+        let headers = forwarded(&["For=\"[2001:db8:cafe::17]:4711\""]);
+        assert_eq!(
+            extract_from_forwarded(&headers, 0),
+            Some("2001:db8:cafe::17".parse().unwrap())
+        );
+
+        let headers = forwarded(&["for=\"192.0.2.43:47011\""]);
+        assert_eq!(
+            extract_from_forwarded(&headers, 0),
+            Some(IpAddr::from([192, 0, 2, 43]))
+        );
+    }
+
+    #[test]
+    fn forwarded_repeated_headers_form_one_chain() {
+        let headers = forwarded(&["for=192.0.2.60", "for=198.51.100.7"]);
+
+        assert_eq!(
+            extract_from_forwarded(&headers, 1),
+            Some(IpAddr::from([192, 0, 2, 60]))
+        );
+    }
+
+    #[test]
+    fn forwarded_unknown_and_obfuscated_hops_still_count() {
+        // The client spoofed an allowed address in front of the proxy's `unknown`
+        let headers = forwarded(&["for=203.0.113.10, for=unknown, for=198.51.100.7"]);
+        assert_eq!(extract_from_forwarded(&headers, 1), None);
+
+        let headers = forwarded(&["for=_hidden;proto=http"]);
+        assert_eq!(extract_from_forwarded(&headers, 0), None);
+
+        let headers = forwarded(&["proto=https"]);
+        assert_eq!(extract_from_forwarded(&headers, 0), None);
+    }
+
+    #[test]
+    fn only_the_configured_header_is_read() {
+        let mut headers = forwarded(&["for=203.0.113.10"]);
+        headers.insert(header::X_FORWARDED_FOR, HeaderValue::from_static("198.51.100.55"));
+        let extensions = axum::http::Extensions::new();
+        let trusted = |header| Some(TrustedProxies { header, depth: 0 });
+
+        assert_eq!(
+            extract_client_ip(&headers, &extensions, trusted(ProxyHeader::Forwarded)),
+            Some(IpAddr::from([203, 0, 113, 10]))
+        );
+        assert_eq!(
+            extract_client_ip(&headers, &extensions, trusted(ProxyHeader::XForwardedFor)),
+            Some(IpAddr::from([198, 51, 100, 55]))
+        );
+        assert_eq!(extract_client_ip(&headers, &extensions, None), None);
+    }
+
     #[test]
     fn cidr_allowlist_matches_ip() {
         let allowed = AllowedIps::Cidrs(vec![
@@ -301,6 +501,192 @@ mod tests {
         );
     }
 
+    fn cidrs(entries: &[&str]) -> AllowedIps {
+        AllowedIps::Cidrs(
+            entries
+                .iter()
+                .map(|entry| entry.parse::<IpNet>().expect("valid cidr"))
+                .collect(),
+        )
+    }
+
+    async fn status_for(
+        layer: IpFilterLayer,
+        peer: IpAddr,
+        xff: Option<&'static str>,
+    ) -> StatusCode {
+        let service = layer.layer(service_fn(|_req: Request<Body>| async move {
+            Ok::<Response, Infallible>(StatusCode::OK.into_response())
+        }));
+
+        let mut req = Request::builder()
+            .uri("/admin/flags")
+            .body(Body::empty())
+            .expect("request");
+        req.extensions_mut().insert(ConnectInfo(SocketAddr::new(peer, 443)));
+        if let Some(xff) = xff {
+            req.headers_mut()
+                .insert(header::X_FORWARDED_FOR, HeaderValue::from_static(xff));
+        }
+
+        service.oneshot(req).await.expect("response").status()
+    }
+
+    #[test]
+    fn single_host_entry_matches_only_that_ip() {
+        let allowed = cidrs(&["203.0.113.7/32"]);
+
+        assert!(is_allowed(Some(IpAddr::from([203, 0, 113, 7])), &allowed));
+        assert!(!is_allowed(Some(IpAddr::from([203, 0, 113, 8])), &allowed));
+        assert!(!is_allowed(None, &allowed));
+    }
+
+    #[test]
+    fn overlapping_ranges_match_either() {
+        let allowed = cidrs(&["10.0.0.0/8", "10.1.0.0/16", "10.1.2.3/32"]);
+
+        assert!(is_allowed(Some(IpAddr::from([10, 1, 2, 3])), &allowed));
+        assert!(is_allowed(Some(IpAddr::from([10, 1, 9, 9])), &allowed));
+        assert!(is_allowed(Some(IpAddr::from([10, 200, 0, 1])), &allowed));
+        assert!(!is_allowed(Some(IpAddr::from([11, 0, 0, 1])), &allowed));
+    }
+
+    #[test]
+    fn ipv6_and_ipv4_mapped_addresses() {
+        let allowed = cidrs(&["203.0.113.0/24", "2001:db8::/32"]);
+
+        let mapped: IpAddr = "::ffff:203.0.113.9".parse().unwrap();
+        assert!(is_allowed(Some(mapped), &allowed));
+        let mapped_outside: IpAddr = "::ffff:198.51.100.9".parse().unwrap();
+        assert!(!is_allowed(Some(mapped_outside), &allowed));
+
+        assert!(is_allowed(Some("2001:db8::1".parse().unwrap()), &allowed));
+        assert!(!is_allowed(Some("2001:db9::1".parse().unwrap()), &allowed));
+
+        // A mapped-address range still matches its own form
+        let mapped_range = cidrs(&["::ffff:203.0.113.0/120"]);
+        assert!(is_allowed(Some(mapped), &mapped_range));
+    }
+
+    #[tokio::test]
+    async fn forwarded_header_is_ignored_without_proxy_trust() {
+        let allowed = cidrs(&["203.0.113.0/24"]);
+        let outside = IpAddr::from([198, 51, 100, 20]);
+
+        // A client claiming an allowed address is judged by its own
+        let status =
+            status_for(IpFilterLayer::direct(allowed.clone()), outside, Some("203.0.113.10")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let status =
+            status_for(IpFilterLayer::direct(allowed), IpAddr::from([203, 0, 113, 10]), None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn spoofed_entry_left_of_the_proxy_is_ignored() {
+        let allowed = cidrs(&["203.0.113.0/24"]);
+        let proxy = IpAddr::from([10, 0, 0, 2]);
+
+        // The proxy appends the real client after whatever the client sent
+        let status = status_for(
+            IpFilterLayer::new(allowed.clone(), 0),
+            proxy,
+            Some("203.0.113.10, 198.51.100.20"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let status = status_for(IpFilterLayer::new(allowed, 0), proxy, Some("203.0.113.10")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    async fn status_behind_proxy(
+        layer: IpFilterLayer,
+        peer: IpAddr,
+        name: header::HeaderName,
+        value: HeaderValue,
+    ) -> StatusCode {
+        let service = layer.layer(service_fn(|_req: Request<Body>| async move {
+            Ok::<Response, Infallible>(StatusCode::OK.into_response())
+        }));
+
+        let mut req = Request::builder()
+            .uri("/callback/transaction")
+            .body(Body::empty())
+            .expect("request");
+        req.extensions_mut().insert(ConnectInfo(SocketAddr::new(peer, 443)));
+        req.headers_mut().insert(name, value);
+
+        service.oneshot(req).await.expect("response").status()
+    }
+
+    #[tokio::test]
+    async fn unusable_forwarded_header_does_not_fall_back_to_the_proxy() {
+        // The proxy itself is allowed, so a fallback to the peer would pass
+        let proxy = IpAddr::from([10, 0, 0, 2]);
+        let allowed = cidrs(&["10.0.0.0/8", "203.0.113.0/24"]);
+        let forwarded =
+            IpFilterLayer::new(allowed.clone(), 0).with_proxy_header(ProxyHeader::Forwarded);
+        let xff = IpFilterLayer::new(allowed, 1);
+
+        for value in ["for=unknown", "for=_hidden", "for=garbage", "proto=https"] {
+            let status = status_behind_proxy(
+                forwarded.clone(),
+                proxy,
+                header::FORWARDED,
+                HeaderValue::from_static(value),
+            )
+            .await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "Forwarded: {value}");
+        }
+
+        let non_ascii = HeaderValue::from_bytes(b"for=203.0.113.10\xff").unwrap();
+        let status =
+            status_behind_proxy(forwarded.clone(), proxy, header::FORWARDED, non_ascii).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // One entry is too short a chain for one proxy in front
+        let status = status_behind_proxy(
+            xff.clone(),
+            proxy,
+            header::X_FORWARDED_FOR,
+            HeaderValue::from_static("203.0.113.10"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let status = status_behind_proxy(
+            xff,
+            proxy,
+            header::X_FORWARDED_FOR,
+            HeaderValue::from_static("not-an-address, 10.0.0.3"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let status = status_behind_proxy(
+            forwarded,
+            proxy,
+            header::FORWARDED,
+            HeaderValue::from_static("for=203.0.113.10"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn any_allows_without_a_client_address() {
+        let layer = IpFilterLayer::direct(AllowedIps::Any);
+        let service = layer.layer(service_fn(|_req: Request<Body>| async move {
+            Ok::<Response, Infallible>(StatusCode::OK.into_response())
+        }));
+        let req = Request::builder().uri("/admin/flags").body(Body::empty()).expect("request");
+
+        let res = service.oneshot(req).await.expect("response");
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
     #[derive(Clone)]
     struct CaptureWarnLayer {
         events: Arc<Mutex<Vec<String>>>,
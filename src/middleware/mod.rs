@@ -1,3 +1,7 @@
+pub mod anchor_signature;
 pub mod idempotency;
 pub mod ip_filter;
 pub mod auth;
+pub mod request_logger;
+pub mod pretty_json;
+pub mod sandbox;
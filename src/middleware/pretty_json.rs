@@ -0,0 +1,72 @@
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::utils::json::reindent;
+
+/// Indents JSON responses of GET requests carrying `?pretty=true`.
+///
+/// Only mounted on admin routes. Without the parameter the response is passed
+/// through untouched, so the cost is a scan of the query string.
+pub async fn pretty_json(req: Request, next: Next) -> Response {
+    let pretty = req.method() == Method::GET && wants_pretty(req.uri().query());
+    let response = next.run(req).await;
+    if !pretty || !is_json(&response) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::error!("Failed to buffer response for pretty printing: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let body = match reindent(&bytes) {
+        Ok(pretty) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Body::from(pretty)
+        }
+        Err(e) => {
+            tracing::warn!("Response labelled JSON is not valid JSON, sent as is: {}", e);
+            Body::from(bytes)
+        }
+    };
+    Response::from_parts(parts, body)
+}
+
+fn wants_pretty(query: Option<&str>) -> bool {
+    query.is_some_and(|query| {
+        query
+            .split('&')
+            .any(|pair| pair == "pretty=true" || pair == "pretty=1")
+    })
+}
+
+fn is_json(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wants_pretty() {
+        assert!(wants_pretty(Some("pretty=true")));
+        assert!(wants_pretty(Some("limit=10&pretty=1")));
+        assert!(!wants_pretty(Some("pretty=false")));
+        assert!(!wants_pretty(Some("notpretty=true")));
+        assert!(!wants_pretty(None));
+    }
+}
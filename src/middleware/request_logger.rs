@@ -1,27 +1,49 @@
 use axum::{
     body::Body,
     extract::Request,
-    http::StatusCode,
+    http::{HeaderValue, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
 use std::time::Instant;
-use uuid::Uuid;
+use tracing::Instrument;
+
+use crate::telemetry;
 
 const MAX_BODY_LOG_SIZE: usize = 1024; // 1KB limit for body logging
 
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Request id of the request being handled on the current task, if any
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Log each request and its response inside the request's span, under the
+/// id from [`telemetry::request_id`], which is also set as `X-Request-Id` on
+/// the request and the response
 pub async fn request_logger_middleware(mut req: Request, next: Next) -> Response {
-    let request_id = Uuid::new_v4().to_string();
+    let request_id = telemetry::request_id(req.headers());
+    let span =
+        telemetry::request_span(&request_id, req.method(), req.uri().path(), req.headers());
+    let header_value: HeaderValue = request_id.parse().unwrap();
+    // Handlers downstream read the id the caller sent, or the one assigned here
+    req.headers_mut().insert(telemetry::REQUEST_ID_HEADER, header_value.clone());
+
+    let response = log_request(req, next, request_id).instrument(span).await;
+
+    let (mut parts, body) = response.into_parts();
+    parts.headers.insert(telemetry::REQUEST_ID_HEADER, header_value);
+    Response::from_parts(parts, body)
+}
+
+async fn log_request(mut req: Request, next: Next, request_id: String) -> Response {
     let method = req.method().clone();
     let uri = req.uri().clone();
     let start = Instant::now();
 
-    // Insert request ID into headers for downstream handlers
-    req.headers_mut().insert(
-        "x-request-id",
-        request_id.parse().unwrap(),
-    );
-
     // Log request
     let log_body = std::env::var("LOG_REQUEST_BODY")
         .unwrap_or_else(|_| "false".to_string())
@@ -35,7 +57,6 @@ pub async fn request_logger_middleware(mut req: Request, next: Next) -> Response
             Ok(bytes) => bytes,
             Err(_) => {
                 tracing::warn!(
-                    request_id = %request_id,
                     method = %method,
                     uri = %uri,
                     "Request body too large or failed to read"
@@ -53,7 +74,6 @@ pub async fn request_logger_middleware(mut req: Request, next: Next) -> Response
         };
 
         tracing::info!(
-            request_id = %request_id,
             method = %method,
             uri = %uri,
             body_size = bytes.len(),
@@ -65,7 +85,6 @@ pub async fn request_logger_middleware(mut req: Request, next: Next) -> Response
         req = Request::from_parts(parts, Body::from(bytes));
     } else {
         tracing::info!(
-            request_id = %request_id,
             method = %method,
             uri = %uri,
             "Incoming request"
@@ -73,14 +92,13 @@ pub async fn request_logger_middleware(mut req: Request, next: Next) -> Response
     }
 
     // Process request
-    let response = next.run(req).await;
+    let response = REQUEST_ID.scope(request_id, next.run(req)).await;
     
     let latency = start.elapsed();
     let status = response.status();
 
     // Log response
     tracing::info!(
-        request_id = %request_id,
         method = %method,
         uri = %uri,
         status = %status.as_u16(),
@@ -88,14 +106,7 @@ pub async fn request_logger_middleware(mut req: Request, next: Next) -> Response
         "Outgoing response"
     );
 
-    // Add request ID to response headers
-    let (mut parts, body) = response.into_parts();
-    parts.headers.insert(
-        "x-request-id",
-        request_id.parse().unwrap(),
-    );
-
-    Response::from_parts(parts, body)
+    response
 }
 
 #[cfg(test)]
@@ -124,4 +135,50 @@ mod tests {
 
         assert!(response.headers().contains_key("x-request-id"));
     }
+
+    #[tokio::test]
+    async fn test_request_logger_propagates_request_id() {
+        let app = Router::new()
+            .route(
+                "/test",
+                post(|req: axum::extract::Request| async move {
+                    let seen = req.headers()["x-request-id"].to_str().unwrap().to_string();
+                    assert_eq!(current_request_id().as_deref(), Some(seen.as_str()));
+                    seen
+                }),
+            )
+            .layer(axum::middleware::from_fn(request_logger_middleware));
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/test")
+                    .header("x-request-id", "req-from-anchor")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["x-request-id"], "req-from-anchor");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/test")
+                    .header(
+                        "traceparent",
+                        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+                    )
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["x-request-id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        let body = axum::body::to_bytes(response.into_body(), 1024).await.unwrap();
+        assert_eq!(&body[..], b"4bf92f3577b34da6a3ce929d0e0e4736");
+    }
 }
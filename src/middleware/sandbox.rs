@@ -0,0 +1,24 @@
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+
+/// Set on every response in sandbox mode
+pub const SANDBOX_HEADER: &str = "x-synapse-sandbox";
+
+/// Reported by `/version` and sent as [`SANDBOX_HEADER`]
+pub const SANDBOX_BANNER: &str = "SANDBOX: simulated Horizon and seeded data, not production";
+
+/// Marks responses as coming from a sandbox.
+///
+/// Mounted around the whole router only when `APP_ENV=sandbox`, so admin
+/// responses in particular can never be mistaken for production ones.
+pub async fn banner(req: Request, next: Next) -> Response {
+    let mut response = next.run(req).await;
+    response
+        .headers_mut()
+        .insert(SANDBOX_HEADER, HeaderValue::from_static(SANDBOX_BANNER));
+    response
+}
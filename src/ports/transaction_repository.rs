@@ -4,7 +4,8 @@
 use async_trait::async_trait;
 use uuid::Uuid;
 
-use crate::domain::Transaction;
+use crate::db::uow::DbConn;
+use crate::domain::{Transaction, UnknownStatus};
 
 /// Result type for repository operations.
 pub type RepositoryResult<T> = Result<T, RepositoryError>;
@@ -17,17 +18,24 @@ pub enum RepositoryError {
 
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
+
+    /// The stored status is neither canonical nor a known legacy alias
+    #[error("Transaction {id} has an {status}")]
+    UnknownStatus { id: Uuid, status: UnknownStatus },
 }
 
 /// Port for persisting and querying transactions.
+///
+/// Every method takes the connection to run on: the pool for a standalone
+/// call, or a unit of work's connection to take part in its transaction.
 #[async_trait]
 pub trait TransactionRepository: Send + Sync {
     /// Insert a new transaction.
-    async fn insert(&self, tx: &Transaction) -> RepositoryResult<Transaction>;
+    async fn insert(&self, conn: DbConn<'_>, tx: &Transaction) -> RepositoryResult<Transaction>;
 
     /// Get a transaction by ID.
-    async fn get_by_id(&self, id: Uuid) -> RepositoryResult<Transaction>;
+    async fn get_by_id(&self, conn: DbConn<'_>, id: Uuid) -> RepositoryResult<Transaction>;
 
     /// List transactions with pagination.
-    async fn list(&self, limit: i64, offset: i64) -> RepositoryResult<Vec<Transaction>>;
+    async fn list(&self, conn: DbConn<'_>, limit: i64, offset: i64) -> RepositoryResult<Vec<Transaction>>;
 }
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{OpenApi, ToSchema};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
@@ -28,6 +28,8 @@ pub struct TransactionSchema {
     pub callback_status: Option<String>,
     /// Associated settlement ID
     pub settlement_id: Option<String>,
+    /// Id of the muxed account the callback named, as a decimal string
+    pub muxed_id: Option<String>,
 }
 
 /// Settlement schema for OpenAPI documentation
@@ -52,3 +54,49 @@ pub struct SettlementSchema {
     /// Timestamp when settlement was last updated
     pub updated_at: DateTime<Utc>,
 }
+
+/// OpenAPI document for the public API
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        crate::handlers::webhook::callback,
+        crate::handlers::callback_batch::callback_batch,
+        crate::handlers::transactions::get_transaction,
+        crate::handlers::transactions::list_transactions,
+    ),
+    components(schemas(
+        TransactionSchema,
+        SettlementSchema,
+        crate::handlers::callback_schema::CallbackPayload,
+        crate::handlers::callback_schema::CallbackPayloadV1,
+        crate::handlers::callback_schema::CallbackPayloadV2,
+        crate::handlers::callback_schema::CallbackAmount,
+        crate::handlers::webhook::CallbackResponse,
+        crate::handlers::webhook::CallbackOutcome,
+        crate::handlers::callback_batch::CallbackBatchResponse,
+        crate::handlers::callback_batch::BatchItemResult,
+        crate::handlers::callback_batch::BatchItemOutcome,
+        crate::handlers::transactions::TransactionPage,
+        crate::handlers::transactions::PageMeta,
+        crate::handlers::transactions::TransactionFilters,
+    )),
+    tags(
+        (name = "Callbacks", description = "Anchor Platform callbacks"),
+        (name = "Transactions", description = "Transaction queries")
+    )
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi_describes_both_callback_schemas() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let schemas = &spec["components"]["schemas"];
+        assert!(schemas.get("CallbackPayloadV1").is_some());
+        assert!(schemas.get("CallbackPayloadV2").is_some());
+        assert!(schemas["CallbackPayload"].get("oneOf").is_some());
+    }
+}
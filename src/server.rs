@@ -0,0 +1,318 @@
+//! HTTP accept loop with connection hygiene limits.
+//!
+//! With every limit unset this is plain `axum::serve`. Otherwise connections
+//! are served by hand so that slow or idle clients can be reaped and the
+//! number of open sockets stays bounded.
+
+use axum::{extract::ConnectInfo, Router};
+use hyper::body::Incoming;
+use hyper::header::{HeaderValue, CONNECTION};
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tower::ServiceExt;
+
+use crate::config::ServerLimits;
+
+/// At most one "connection limit reached" warning per this interval
+const LIMIT_WARN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long open connections may keep running after shutdown begins
+pub const SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+
+pub async fn serve(listener: TcpListener, app: Router, limits: ServerLimits) -> std::io::Result<()> {
+    serve_with_shutdown(listener, app, limits, std::future::pending()).await
+}
+
+/// [`serve`] until `shutdown` resolves. Accepting stops then, every open
+/// connection is told to finish its in-flight requests and close, and they
+/// get up to [`SHUTDOWN_GRACE`] to do so before being dropped.
+pub async fn serve_with_shutdown<F>(
+    listener: TcpListener,
+    app: Router,
+    limits: ServerLimits,
+    shutdown: F,
+) -> std::io::Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let stopping = CancellationToken::new();
+    if limits.is_unlimited() {
+        let signalled = stopping.clone();
+        let serve = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(async move {
+                shutdown.await;
+                signalled.cancel();
+            });
+        // axum waits for connections without a limit; the grace is enforced here
+        return tokio::select! {
+            result = serve => result,
+            _ = async {
+                stopping.cancelled().await;
+                tokio::time::sleep(SHUTDOWN_GRACE).await;
+            } => {
+                tracing::warn!("Shutting down with HTTP connections still open");
+                Ok(())
+            }
+        };
+    }
+
+    tracing::info!(?limits, "HTTP server connection limits enabled");
+    let permits = limits.max_connections.map(|max| Arc::new(Semaphore::new(max)));
+    let open_connections = Arc::new(AtomicUsize::new(0));
+    let started = Instant::now();
+    let last_limit_warn = AtomicU64::new(0);
+    let connections = TaskTracker::new();
+    tokio::pin!(shutdown);
+
+    loop {
+        let (permit, stream, remote_addr) = tokio::select! {
+            biased;
+            _ = &mut shutdown => break,
+            next = next_connection(&listener, permits.as_ref(), &last_limit_warn, started, limits.max_connections) => next,
+        };
+
+        let app = app.clone();
+        let open_connections = open_connections.clone();
+        let stopping = stopping.clone();
+        connections.spawn(async move {
+            let _permit = permit;
+            crate::metrics::update_open_connections(open_connections.fetch_add(1, Ordering::Relaxed) + 1);
+            serve_connection(stream, remote_addr, app, limits, stopping).await;
+            crate::metrics::update_open_connections(open_connections.fetch_sub(1, Ordering::Relaxed) - 1);
+        });
+    }
+
+    stopping.cancel();
+    connections.close();
+    if tokio::time::timeout(SHUTDOWN_GRACE, connections.wait()).await.is_err() {
+        let remaining = open_connections.load(Ordering::Relaxed);
+        tracing::warn!(remaining, "Shutting down with HTTP connections still open");
+    }
+    Ok(())
+}
+
+async fn next_connection(
+    listener: &TcpListener,
+    permits: Option<&Arc<Semaphore>>,
+    last_limit_warn: &AtomicU64,
+    started: Instant,
+    max_connections: Option<usize>,
+) -> (Option<OwnedSemaphorePermit>, TcpStream, SocketAddr) {
+    // Backpressure: stop accepting while at the ceiling; new clients wait
+    // in the kernel backlog instead of holding file descriptors here
+    let permit = match permits {
+        Some(permits) => Some(match permits.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                crate::metrics::record_connection_limit_reached();
+                warn_limit_reached(last_limit_warn, started, max_connections);
+                permits.clone().acquire_owned().await.expect("semaphore is never closed")
+            }
+        }),
+        None => None,
+    };
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, remote_addr)) => return (permit, stream, remote_addr),
+            Err(e) => {
+                // EMFILE and friends; back off instead of spinning
+                tracing::error!("Failed to accept connection: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+fn warn_limit_reached(last_warn: &AtomicU64, started: Instant, max: Option<usize>) {
+    let now = started.elapsed().as_secs();
+    let last = last_warn.load(Ordering::Relaxed);
+    if (last == 0 || now >= last + LIMIT_WARN_INTERVAL.as_secs())
+        && last_warn
+            .compare_exchange(last, now.max(1), Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    {
+        tracing::warn!(max_connections = ?max, "HTTP connection limit reached, accept paused");
+    }
+}
+
+/// Per-connection activity, shared between the IO wrapper, the request
+/// service and the watchdog
+struct Activity {
+    opened: Instant,
+    /// Milliseconds since `opened` of the last read or write
+    last_io_ms: AtomicU64,
+    requests: AtomicU64,
+    in_flight: AtomicUsize,
+}
+
+impl Activity {
+    fn new() -> Self {
+        Self {
+            opened: Instant::now(),
+            last_io_ms: AtomicU64::new(0),
+            requests: AtomicU64::new(0),
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    fn touch(&self) {
+        self.last_io_ms
+            .store(self.opened.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// When the connection should be closed if nothing happens until then
+    fn deadline(&self, limits: &ServerLimits) -> Option<Instant> {
+        if self.in_flight.load(Ordering::Relaxed) > 0 {
+            return None;
+        }
+        if self.requests.load(Ordering::Relaxed) == 0 {
+            // Nothing received yet: the client gets the header read timeout
+            // from the moment it connected
+            if let Some(timeout) = limits.header_read_timeout {
+                return Some(self.opened + timeout);
+            }
+        }
+        let last_io = self.opened + Duration::from_millis(self.last_io_ms.load(Ordering::Relaxed));
+        limits.idle_timeout.map(|timeout| last_io + timeout)
+    }
+}
+
+/// TCP stream that records when it last moved bytes
+struct TrackedStream {
+    inner: TcpStream,
+    activity: Arc<Activity>,
+}
+
+impl AsyncRead for TrackedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if matches!(poll, Poll::Ready(Ok(()))) && buf.filled().len() > before {
+            self.activity.touch();
+        }
+        poll
+    }
+}
+
+impl AsyncWrite for TrackedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if matches!(poll, Poll::Ready(Ok(n)) if n > 0) {
+            self.activity.touch();
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+async fn serve_connection(
+    stream: TcpStream,
+    remote_addr: SocketAddr,
+    app: Router,
+    limits: ServerLimits,
+    stopping: CancellationToken,
+) {
+    let activity = Arc::new(Activity::new());
+    let io = TokioIo::new(TrackedStream {
+        inner: stream,
+        activity: activity.clone(),
+    });
+
+    let service_activity = activity.clone();
+    let service = hyper::service::service_fn(move |mut req: hyper::Request<Incoming>| {
+        let app = app.clone();
+        let activity = service_activity.clone();
+        async move {
+            activity.in_flight.fetch_add(1, Ordering::Relaxed);
+            let served = activity.requests.fetch_add(1, Ordering::Relaxed) + 1;
+            req.extensions_mut().insert(ConnectInfo(remote_addr));
+
+            let mut response = app
+                .oneshot(req.map(axum::body::Body::new))
+                .await
+                .unwrap_or_else(|never: Infallible| match never {});
+            if limits.max_requests_per_connection.is_some_and(|max| served >= max) {
+                response.headers_mut().insert(CONNECTION, HeaderValue::from_static("close"));
+            }
+
+            activity.touch();
+            activity.in_flight.fetch_sub(1, Ordering::Relaxed);
+            Ok::<_, Infallible>(response)
+        }
+    });
+
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    if let Some(timeout) = limits.header_read_timeout {
+        // Covers headers trickled in slowly on a kept-alive connection
+        builder.http1().timer(TokioTimer::new()).header_read_timeout(timeout);
+    }
+    let conn = builder.serve_connection_with_upgrades(io, service);
+    tokio::pin!(conn);
+
+    let mut closing = false;
+    loop {
+        let deadline = if closing { None } else { activity.deadline(&limits) };
+        let sleep = async {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                // Re-check periodically while a request is in flight
+                None => tokio::time::sleep(Duration::from_secs(1)).await,
+            }
+        };
+
+        tokio::select! {
+            result = conn.as_mut() => {
+                if let Err(e) = result {
+                    tracing::debug!(%remote_addr, "Connection closed with error: {}", e);
+                }
+                break;
+            }
+            _ = stopping.cancelled(), if !closing => {
+                // In-flight requests finish; keep-alive ends after them
+                conn.as_mut().graceful_shutdown();
+                closing = true;
+            }
+            _ = sleep => {
+                // Traffic may have arrived while sleeping
+                if activity.deadline(&limits).is_some_and(|deadline| Instant::now() >= deadline) {
+                    tracing::debug!(%remote_addr, "Closing idle connection");
+                    if activity.requests.load(Ordering::Relaxed) == 0 {
+                        // Never sent a request; nothing to finish gracefully
+                        break;
+                    }
+                    conn.as_mut().graceful_shutdown();
+                    closing = true;
+                }
+            }
+        }
+    }
+}
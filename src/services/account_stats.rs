@@ -0,0 +1,180 @@
+//! Materialized per-account deposit statistics.
+//!
+//! `account_stats` is kept current by the transaction queries themselves:
+//! inserts and status transitions increment it in the same statement or unit
+//! of work. This service reads it (falling back to the transactions table for
+//! accounts without a row) and repairs accounts whose stored totals have
+//! drifted from the raw data.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+use crate::db::models::AccountStats;
+use crate::db::{queries, uow};
+use crate::error::AppError;
+use crate::utils::clock::{self, SharedClock, Ticker};
+use crate::utils::shutdown::Shutdown;
+
+/// Result of recomputing one account from raw data
+#[derive(Debug, Serialize)]
+pub struct StatsRecompute {
+    pub stellar_account: String,
+    /// Whether the stored rows differed from the recomputed ones
+    pub drifted: bool,
+    pub stats: Vec<AccountStats>,
+}
+
+/// True unless `stored` and `raw` hold the same totals for the same assets
+pub fn has_drifted(stored: &[AccountStats], raw: &[AccountStats]) -> bool {
+    if stored.len() != raw.len() {
+        return true;
+    }
+    let by_asset: HashMap<&str, &AccountStats> =
+        stored.iter().map(|stats| (stats.asset_code.as_str(), stats)).collect();
+    !raw.iter().all(|computed| {
+        by_asset
+            .get(computed.asset_code.as_str())
+            .is_some_and(|stats| stats.same_totals(computed))
+    })
+}
+
+#[derive(Clone)]
+pub struct AccountStatsService {
+    pool: PgPool,
+    clock: SharedClock,
+    shutdown: Shutdown,
+}
+
+impl AccountStatsService {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            clock: clock::system(),
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    /// Schedule drift checks on `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Stop the drift check between passes once `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Per-asset stats for an account. Accounts with no stored row yet are
+    /// computed from the transactions table.
+    pub async fn summary(&self, stellar_account: &str) -> Result<Vec<AccountStats>, AppError> {
+        let stored = queries::get_account_stats(&self.pool, stellar_account).await?;
+        if !stored.is_empty() {
+            return Ok(stored);
+        }
+        tracing::debug!(stellar_account, "No account_stats row, computing from transactions");
+        Ok(queries::compute_account_stats(&self.pool, stellar_account).await?)
+    }
+
+    /// Rebuild the account's rows from raw data
+    pub async fn recompute(&self, stellar_account: &str) -> Result<StatsRecompute, AppError> {
+        let (stored, stats) = uow::run(&self.pool, |uow| Box::pin(async move {
+            let stored = queries::get_account_stats(uow.conn(), stellar_account).await?;
+            let stats = queries::recompute_account_stats(uow.conn(), stellar_account).await?;
+            Ok::<_, AppError>((stored, stats))
+        }))
+        .await?;
+
+        let drifted = has_drifted(&stored, &stats);
+        if drifted {
+            tracing::warn!(stellar_account, "account_stats drifted from transactions, recomputed");
+        }
+        Ok(StatsRecompute {
+            stellar_account: stellar_account.to_string(),
+            drifted,
+            stats,
+        })
+    }
+
+    /// Compare stored and raw stats for every account active since `since`
+    /// and recompute the ones that differ. Returns how many were repaired.
+    pub async fn repair_drift(&self, since: DateTime<Utc>) -> Result<usize, AppError> {
+        let mut repaired = 0;
+        for account in queries::list_recently_active_accounts(&self.pool, since).await? {
+            let stored = queries::get_account_stats(&self.pool, &account).await?;
+            let raw = queries::compute_account_stats(&self.pool, &account).await?;
+            if has_drifted(&stored, &raw) && self.recompute(&account).await?.drifted {
+                repaired += 1;
+            }
+        }
+        Ok(repaired)
+    }
+
+    /// Check recently active accounts every `interval`, looking back twice as
+    /// far so a slow run leaves no gap
+    pub fn start(&self, interval: std::time::Duration) {
+        let service = self.clone();
+        let lookback = chrono::Duration::from_std(interval * 2).unwrap_or(chrono::Duration::hours(2));
+        self.shutdown.spawn(async move {
+            let mut ticker = Ticker::new(service.clock.clone(), interval);
+            loop {
+                tokio::select! {
+                    _ = service.shutdown.cancelled() => break,
+                    _ = ticker.tick() => {}
+                }
+                match service.repair_drift(service.clock.now() - lookback).await {
+                    Ok(0) => {}
+                    Ok(repaired) => tracing::warn!("Repaired account_stats drift for {} accounts", repaired),
+                    Err(e) => tracing::error!("account_stats drift check failed: {:?}", e),
+                }
+            }
+            tracing::info!("account_stats drift check stopped");
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::types::BigDecimal;
+
+    fn stats(asset_code: &str, completed: &str, count: i64) -> AccountStats {
+        AccountStats {
+            stellar_account: format!("G{}", "A".repeat(55)),
+            asset_code: asset_code.to_string(),
+            total_completed: completed.parse().unwrap(),
+            total_pending: BigDecimal::from(0),
+            first_deposit_at: None,
+            last_deposit_at: None,
+            deposit_count: count,
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_matching_totals_are_not_drift() {
+        let stored = vec![stats("USD", "100.50", 2), stats("USDC", "5", 1)];
+        let mut raw = vec![stats("USDC", "5.00", 1), stats("USD", "100.5", 2)];
+        raw[0].updated_at = Utc::now() + chrono::Duration::minutes(5);
+        assert!(!has_drifted(&stored, &raw));
+    }
+
+    #[test]
+    fn test_differing_totals_are_drift() {
+        let stored = vec![stats("USD", "100.50", 2)];
+        assert!(has_drifted(&stored, &[stats("USD", "100.50", 3)]));
+        assert!(has_drifted(&stored, &[stats("USD", "90", 2)]));
+        assert!(has_drifted(&stored, &[stats("EUR", "100.50", 2)]));
+    }
+
+    #[test]
+    fn test_missing_or_extra_assets_are_drift() {
+        let stored = vec![stats("USD", "100.50", 2)];
+        assert!(has_drifted(&[], &stored));
+        assert!(has_drifted(&stored, &[]));
+        assert!(has_drifted(&stored, &[stats("USD", "100.50", 2), stats("EUR", "1", 1)]));
+    }
+}
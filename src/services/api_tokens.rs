@@ -0,0 +1,573 @@
+//! Scoped, expiring API tokens for third-party read access.
+//!
+//! A token carries a [`TokenScope`]: the routes it may call plus filters
+//! (asset, date range) that are forced onto every list and export query it
+//! makes. Request parameters can only narrow those filters, never widen them.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::audit::{AuditLog, ENTITY_API_TOKEN};
+use crate::db::models::{ApiToken, Transaction};
+use crate::db::{queries, uow};
+use crate::error::AppError;
+use crate::handlers::export::{self, ExportQuery};
+use crate::services::buffered_writer::{BufferedWriter, TokenRequest};
+use crate::services::export_jobs::NormalizedExportParams;
+use crate::utils::clock::{self, SharedClock};
+
+/// Every scoped token secret starts with this, so it can be told apart from
+/// the admin key without a database lookup
+pub const TOKEN_PREFIX: &str = "syt_";
+
+/// Routes a scoped token may be granted, as `METHOD /matched/path`. All of
+/// them apply the scope's filters; anything else stays admin-key only.
+pub const SCOPEABLE_ROUTES: &[&str] = &[
+    "GET /transactions",
+    "GET /transactions/:id",
+    "POST /admin/exports",
+    "GET /admin/exports/:id",
+    "GET /admin/exports/:id/download",
+];
+
+/// What a token may access. `from` and `to` are inclusive days, like the
+/// export filters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenScope {
+    pub routes: Vec<String>,
+    #[serde(default)]
+    pub asset_code: Option<String>,
+    #[serde(default)]
+    pub from: Option<NaiveDate>,
+    #[serde(default)]
+    pub to: Option<NaiveDate>,
+}
+
+impl TokenScope {
+    /// Validate and normalize a scope submitted by an admin
+    pub fn normalized(mut self) -> Result<Self, AppError> {
+        if self.routes.is_empty() {
+            return Err(AppError::Validation("scope.routes: must not be empty".to_string()));
+        }
+        for route in &self.routes {
+            if !SCOPEABLE_ROUTES.contains(&route.as_str()) {
+                return Err(AppError::Validation(format!(
+                    "scope.routes: '{}' cannot be granted to a scoped token",
+                    route
+                )));
+            }
+        }
+        self.routes.sort();
+        self.routes.dedup();
+
+        self.asset_code = self
+            .asset_code
+            .map(|code| code.trim().to_ascii_uppercase())
+            .filter(|code| !code.is_empty());
+
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                return Err(AppError::Validation("scope: from must not be after to".to_string()));
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn from_value(value: &serde_json::Value) -> Result<Self, AppError> {
+        serde_json::from_value(value.clone())
+            .map_err(|e| AppError::Internal(format!("Stored token scope is invalid: {}", e)))
+    }
+
+    pub fn allows(&self, method: &str, matched_path: &str) -> bool {
+        let route = format!("{} {}", method, matched_path);
+        self.routes.iter().any(|allowed| *allowed == route)
+    }
+
+    /// Start of the allowed window (inclusive)
+    pub fn window_start(&self) -> Option<DateTime<Utc>> {
+        self.from.map(start_of_day)
+    }
+
+    /// End of the allowed window (exclusive)
+    pub fn window_end(&self) -> Option<DateTime<Utc>> {
+        self.to.map(|to| start_of_day(to) + Duration::days(1))
+    }
+
+    /// Clip a requested `[from, until)` window to the scope's window
+    pub fn clip_window(
+        &self,
+        from: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        (max_bound(from, self.window_start()), min_bound(until, self.window_end()))
+    }
+
+    /// Whether a single row falls inside the scope
+    pub fn contains(&self, tx: &Transaction) -> bool {
+        self.asset_code.as_deref().map_or(true, |code| tx.asset_code == code)
+            && self.window_start().map_or(true, |start| tx.created_at >= start)
+            && self.window_end().map_or(true, |end| tx.created_at < end)
+    }
+
+    /// Intersect export filters with the scope.
+    ///
+    /// Missing filters are filled in from the scope and an overlapping date
+    /// range is clipped to it. Filters that cannot match anything in scope (a
+    /// different asset, a range entirely outside the window) are rejected.
+    pub fn narrow_export(&self, query: &ExportQuery) -> Result<ExportQuery, AppError> {
+        let mut narrowed = query.clone();
+
+        if let Some(scope_asset) = &self.asset_code {
+            match non_empty(&query.asset_code) {
+                Some(requested) if !requested.eq_ignore_ascii_case(scope_asset) => {
+                    return Err(out_of_scope("asset_code"));
+                }
+                _ => narrowed.asset_code = Some(scope_asset.clone()),
+            }
+        }
+
+        // Parsed here rather than left to the export code, which skips
+        // unparseable dates; a skipped filter would mean an unbounded range
+        let requested_start = parse_filter_date("from", &query.from)?;
+        let requested_end = parse_filter_date("to", &query.to)?.map(|to| to + Duration::days(1));
+
+        let (start, end) = self.clip_window(requested_start, requested_end);
+        if let (Some(start), Some(end)) = (start, end) {
+            if start >= end {
+                return Err(out_of_scope("from/to"));
+            }
+        }
+
+        narrowed.from = start.map(|start| start.to_rfc3339());
+        // The export filter adds the day back when it builds the query
+        narrowed.to = end.map(|end| (end - Duration::days(1)).to_rfc3339());
+        Ok(narrowed)
+    }
+
+    /// Whether an existing export job only covers rows inside the scope
+    pub fn covers_export(&self, params: &NormalizedExportParams) -> bool {
+        let parse = |value: &Option<String>| {
+            value.as_deref().and_then(|raw| export::parse_date(raw).ok())
+        };
+
+        let asset_ok = match (&self.asset_code, &params.asset_code) {
+            (Some(scope_asset), Some(asset)) => scope_asset == asset,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let start_ok = match (self.window_start(), parse(&params.from)) {
+            (Some(scope_start), Some(start)) => start >= scope_start,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let end_ok = match (self.window_end(), parse(&params.to)) {
+            (Some(scope_end), Some(to)) => to + Duration::days(1) <= scope_end,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        asset_ok && start_ok && end_ok
+    }
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).expect("midnight is a valid time").and_utc()
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn parse_filter_date(field: &str, value: &Option<String>) -> Result<Option<DateTime<Utc>>, AppError> {
+    match non_empty(value) {
+        Some(raw) => export::parse_date(raw)
+            .map(Some)
+            .map_err(|e| AppError::Validation(format!("{}: {}", field, e))),
+        None => Ok(None),
+    }
+}
+
+fn max_bound(a: Option<DateTime<Utc>>, b: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn min_bound(a: Option<DateTime<Utc>>, b: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn out_of_scope(field: &str) -> AppError {
+    AppError::Forbidden(format!("{}: outside the token's scope", field))
+}
+
+pub fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+fn generate_secret() -> String {
+    // Two v4 UUIDs: 244 random bits from the OS generator
+    format!("{}{}{}", TOKEN_PREFIX, Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Lifecycle state reported by introspection
+pub fn token_status(token: &ApiToken, now: DateTime<Utc>) -> &'static str {
+    if token.revoked_at.is_some() {
+        "revoked"
+    } else if token.expires_at <= now {
+        "expired"
+    } else {
+        "active"
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateTokenRequest {
+    pub name: String,
+    pub scope: TokenScope,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Issues, introspects, revokes and authenticates scoped tokens.
+#[derive(Clone)]
+pub struct ApiTokenService {
+    pool: PgPool,
+    clock: SharedClock,
+    /// Where authenticated requests are counted, if anywhere
+    usage: Option<BufferedWriter>,
+}
+
+impl ApiTokenService {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            clock: clock::system(),
+            usage: None,
+        }
+    }
+
+    /// Judge expiry against `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Count requests per token, route and day in `api_token_usage`
+    pub fn with_usage(mut self, writer: BufferedWriter) -> Self {
+        self.usage = Some(writer);
+        self
+    }
+
+    /// Count a request made with a token; counts may be dropped under load
+    pub fn record_request(&self, token_id: Uuid, method: &str, route: &str) {
+        if let Some(usage) = &self.usage {
+            usage.usage(TokenRequest {
+                token_id,
+                method: method.to_string(),
+                route: route.to_string(),
+                at: self.clock.now(),
+            });
+        }
+    }
+
+    /// Create a token; the returned secret is not stored and cannot be shown again
+    pub async fn create(
+        &self,
+        request: CreateTokenRequest,
+        actor: &str,
+    ) -> Result<(ApiToken, String), AppError> {
+        let name = request.name.trim().to_string();
+        if name.is_empty() || name.len() > 100 {
+            return Err(AppError::Validation("name: must be 1-100 characters".to_string()));
+        }
+        let now = self.clock.now();
+        if request.expires_at <= now {
+            return Err(AppError::Validation("expires_at: must be in the future".to_string()));
+        }
+        let scope = request.scope.normalized()?;
+
+        let secret = generate_secret();
+        let token = ApiToken {
+            id: Uuid::new_v4(),
+            name,
+            token_prefix: secret[..TOKEN_PREFIX.len() + 8].to_string(),
+            token_hash: hash_secret(&secret),
+            scope: serde_json::to_value(&scope).expect("scope is always serializable"),
+            expires_at: request.expires_at,
+            revoked_at: None,
+            created_by: actor.to_string(),
+            created_at: now,
+        };
+
+        let token = uow::run(&self.pool, |uow| {
+            Box::pin(async move {
+                let token = queries::insert_api_token(uow.conn(), &token).await?;
+                AuditLog::log_creation(uow.conn(), token.id, ENTITY_API_TOKEN, json!(token), actor)
+                    .await?;
+                Ok::<_, AppError>(token)
+            })
+        })
+        .await?;
+
+        tracing::info!(token_id = %token.id, name = %token.name, expires_at = %token.expires_at, "Scoped API token created");
+        Ok((token, secret))
+    }
+
+    /// Look up a token for introspection; the lookup itself is audited
+    pub async fn inspect(&self, id: Uuid, actor: &str) -> Result<ApiToken, AppError> {
+        uow::run(&self.pool, |uow| {
+            Box::pin(async move {
+                let token = queries::get_api_token(uow.conn(), id)
+                    .await
+                    .map_err(|e| not_found(id, e))?;
+                AuditLog::log(uow.conn(), id, ENTITY_API_TOKEN, "inspected", None, None, actor)
+                    .await?;
+                Ok::<_, AppError>(token)
+            })
+        })
+        .await
+    }
+
+    /// Revoke a token. Revoking an already revoked token is a no-op.
+    pub async fn revoke(&self, id: Uuid, actor: &str) -> Result<ApiToken, AppError> {
+        let (token, revoked) = uow::run(&self.pool, |uow| {
+            Box::pin(async move {
+                match queries::revoke_api_token(uow.conn(), id).await? {
+                    Some(token) => {
+                        AuditLog::log(
+                            uow.conn(),
+                            id,
+                            ENTITY_API_TOKEN,
+                            "revoked",
+                            Some(json!({ "revoked_at": null })),
+                            Some(json!({ "revoked_at": token.revoked_at })),
+                            actor,
+                        )
+                        .await?;
+                        Ok::<_, AppError>((token, true))
+                    }
+                    None => {
+                        let token = queries::get_api_token(uow.conn(), id)
+                            .await
+                            .map_err(|e| not_found(id, e))?;
+                        Ok((token, false))
+                    }
+                }
+            })
+        })
+        .await?;
+
+        if revoked {
+            tracing::info!(token_id = %id, "Scoped API token revoked");
+        }
+        Ok(token)
+    }
+
+    /// The token for a presented secret, if it exists and is still active.
+    /// Expired tokens stop authenticating here without any cleanup job.
+    pub async fn authenticate(&self, secret: &str) -> Result<Option<(ApiToken, TokenScope)>, AppError> {
+        let token_hash = hash_secret(secret);
+        let Some(token) =
+            queries::get_active_api_token_by_hash(&self.pool, &token_hash, self.clock.now()).await?
+        else {
+            return Ok(None);
+        };
+        let scope = TokenScope::from_value(&token.scope)?;
+        Ok(Some((token, scope)))
+    }
+}
+
+fn not_found(id: Uuid, e: sqlx::Error) -> AppError {
+    match e {
+        sqlx::Error::RowNotFound => AppError::NotFound(format!("Token {} not found", id)),
+        e => AppError::Database(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+    use std::str::FromStr;
+
+    fn date(raw: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(raw, "%Y-%m-%d").unwrap()
+    }
+
+    fn q1_usdc() -> TokenScope {
+        TokenScope {
+            routes: vec!["GET /transactions".to_string(), "POST /admin/exports".to_string()],
+            asset_code: Some("USDC".to_string()),
+            from: Some(date("2026-01-01")),
+            to: Some(date("2026-03-31")),
+        }
+    }
+
+    fn export_query(asset_code: Option<&str>, from: Option<&str>, to: Option<&str>) -> ExportQuery {
+        ExportQuery {
+            asset_code: asset_code.map(str::to_string),
+            from: from.map(str::to_string),
+            to: to.map(str::to_string),
+            format: "csv".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn tx_at(asset_code: &str, created_at: &str) -> Transaction {
+        let mut tx = Transaction::new(
+            format!("G{}", "A".repeat(55)),
+            BigDecimal::from_str("10").unwrap(),
+            asset_code.to_string(),
+            None,
+            None,
+            None,
+        );
+        tx.created_at = DateTime::parse_from_rfc3339(created_at).unwrap().with_timezone(&Utc);
+        tx
+    }
+
+    #[test]
+    fn test_normalized_rejects_unscopeable_routes() {
+        let mut scope = q1_usdc();
+        scope.routes.push("POST /admin/tokens".to_string());
+        assert!(matches!(scope.normalized(), Err(AppError::Validation(_))));
+
+        let mut scope = q1_usdc();
+        scope.routes.clear();
+        assert!(scope.normalized().is_err());
+    }
+
+    #[test]
+    fn test_normalized_rejects_inverted_range_and_uppercases_asset() {
+        let mut scope = q1_usdc();
+        scope.from = Some(date("2026-04-01"));
+        assert!(scope.normalized().is_err());
+
+        let mut scope = q1_usdc();
+        scope.asset_code = Some(" usdc ".to_string());
+        assert_eq!(scope.normalized().unwrap().asset_code.as_deref(), Some("USDC"));
+    }
+
+    #[test]
+    fn test_scope_rejects_unknown_fields() {
+        let value = json!({ "routes": ["GET /transactions"], "status": "completed" });
+        assert!(serde_json::from_value::<TokenScope>(value).is_err());
+    }
+
+    #[test]
+    fn test_allows_only_listed_routes() {
+        let scope = q1_usdc();
+        assert!(scope.allows("GET", "/transactions"));
+        assert!(scope.allows("POST", "/admin/exports"));
+        assert!(!scope.allows("GET", "/admin/exports/:id"));
+        assert!(!scope.allows("POST", "/transactions"));
+    }
+
+    #[test]
+    fn test_contains_checks_asset_and_window() {
+        let scope = q1_usdc();
+        assert!(scope.contains(&tx_at("USDC", "2026-01-01T00:00:00Z")));
+        assert!(scope.contains(&tx_at("USDC", "2026-03-31T23:59:59Z")));
+        assert!(!scope.contains(&tx_at("USDC", "2026-04-01T00:00:00Z")));
+        assert!(!scope.contains(&tx_at("USDC", "2025-12-31T23:59:59Z")));
+        assert!(!scope.contains(&tx_at("EURC", "2026-02-01T00:00:00Z")));
+    }
+
+    #[test]
+    fn test_narrow_export_fills_in_missing_filters() {
+        let narrowed = q1_usdc().narrow_export(&export_query(None, None, None)).unwrap();
+        assert_eq!(narrowed.asset_code.as_deref(), Some("USDC"));
+        assert_eq!(narrowed.from.as_deref(), Some("2026-01-01T00:00:00+00:00"));
+        assert_eq!(narrowed.to.as_deref(), Some("2026-03-31T00:00:00+00:00"));
+    }
+
+    #[test]
+    fn test_narrow_export_clips_wider_range() {
+        let query = export_query(Some("usdc"), Some("2020-01-01"), Some("2030-12-31"));
+        let narrowed = q1_usdc().narrow_export(&query).unwrap();
+        assert_eq!(narrowed.from.as_deref(), Some("2026-01-01T00:00:00+00:00"));
+        assert_eq!(narrowed.to.as_deref(), Some("2026-03-31T00:00:00+00:00"));
+    }
+
+    #[test]
+    fn test_narrow_export_keeps_narrower_range() {
+        let query = export_query(None, Some("2026-02-01"), Some("2026-02-28"));
+        let narrowed = q1_usdc().narrow_export(&query).unwrap();
+        assert_eq!(narrowed.from.as_deref(), Some("2026-02-01T00:00:00+00:00"));
+        assert_eq!(narrowed.to.as_deref(), Some("2026-02-28T00:00:00+00:00"));
+    }
+
+    #[test]
+    fn test_narrow_export_rejects_other_asset() {
+        let query = export_query(Some("EURC"), None, None);
+        assert!(matches!(q1_usdc().narrow_export(&query), Err(AppError::Forbidden(_))));
+    }
+
+    #[test]
+    fn test_narrow_export_rejects_range_outside_window() {
+        let query = export_query(None, Some("2026-04-01"), Some("2026-06-30"));
+        assert!(matches!(q1_usdc().narrow_export(&query), Err(AppError::Forbidden(_))));
+    }
+
+    #[test]
+    fn test_narrow_export_rejects_unparseable_dates() {
+        // The export filter ignores dates it cannot parse, which would lift the bound
+        let query = export_query(None, Some("last quarter"), None);
+        assert!(matches!(q1_usdc().narrow_export(&query), Err(AppError::Validation(_))));
+    }
+
+    #[test]
+    fn test_narrowed_query_is_covered_by_scope() {
+        let scope = q1_usdc();
+        let narrowed = scope.narrow_export(&export_query(None, Some("2020-01-01"), None)).unwrap();
+        let params = NormalizedExportParams::from_query(&narrowed).unwrap();
+        assert!(scope.covers_export(&params));
+    }
+
+    #[test]
+    fn test_covers_export_rejects_wider_jobs() {
+        let scope = q1_usdc();
+        let unfiltered = NormalizedExportParams::from_query(&export_query(None, None, None)).unwrap();
+        assert!(!scope.covers_export(&unfiltered));
+
+        let longer = NormalizedExportParams::from_query(&export_query(
+            Some("USDC"),
+            Some("2026-01-01"),
+            Some("2026-04-01"),
+        ))
+        .unwrap();
+        assert!(!scope.covers_export(&longer));
+    }
+
+    #[test]
+    fn test_unfiltered_scope_covers_everything() {
+        let scope = TokenScope {
+            routes: vec!["GET /transactions".to_string()],
+            asset_code: None,
+            from: None,
+            to: None,
+        };
+        let params = NormalizedExportParams::from_query(&export_query(None, None, None)).unwrap();
+        assert!(scope.covers_export(&params));
+        assert!(scope.contains(&tx_at("EURC", "2019-05-01T00:00:00Z")));
+    }
+
+    #[test]
+    fn test_secrets_are_prefixed_and_distinct() {
+        let a = generate_secret();
+        let b = generate_secret();
+        assert!(a.starts_with(TOKEN_PREFIX));
+        assert_ne!(a, b);
+        assert_eq!(hash_secret(&a).len(), 64);
+        assert_ne!(hash_secret(&a), hash_secret(&b));
+    }
+}
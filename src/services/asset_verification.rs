@@ -0,0 +1,318 @@
+use chrono::{Duration as ChronoDuration, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::time::Duration;
+
+use crate::db::models::Asset;
+use crate::db::queries;
+use crate::error::AppError;
+use crate::services::payment_listener::NATIVE_ASSET_CODE;
+use crate::services::FeatureFlagService;
+use crate::stellar::HorizonClient;
+
+pub const STATUS_VERIFIED: &str = "verified";
+pub const STATUS_UNVERIFIED: &str = "unverified";
+
+/// Feature flag that makes the callback handler refuse deposits for unverified assets
+pub const REFUSE_UNVERIFIED_DEPOSITS_FLAG: &str = "refuse_unverified_asset_deposits";
+
+/// Upper bound on a stellar.toml body; real files are a few KB
+const MAX_TOML_BYTES: usize = 100 * 1024;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+struct StellarToml {
+    #[serde(rename = "CURRENCIES", default)]
+    currencies: Vec<TomlCurrency>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TomlCurrency {
+    code: Option<String>,
+    issuer: Option<String>,
+}
+
+/// Result of checking an asset against its issuer's home domain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationOutcome {
+    Verified,
+    Unverified(String),
+}
+
+impl VerificationOutcome {
+    pub fn status(&self) -> &'static str {
+        match self {
+            VerificationOutcome::Verified => STATUS_VERIFIED,
+            VerificationOutcome::Unverified(_) => STATUS_UNVERIFIED,
+        }
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            VerificationOutcome::Verified => None,
+            VerificationOutcome::Unverified(reason) => Some(reason),
+        }
+    }
+}
+
+/// Verifies that an asset's issuer publishes it in the CURRENCIES section of
+/// the stellar.toml served from the issuer account's `home_domain`.
+#[derive(Clone)]
+pub struct AssetVerifier {
+    pool: PgPool,
+    horizon_client: HorizonClient,
+    http: Client,
+}
+
+impl AssetVerifier {
+    pub fn new(pool: PgPool, horizon_client: HorizonClient) -> Self {
+        let http = Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::limited(3))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            pool,
+            horizon_client,
+            http,
+        }
+    }
+
+    /// Check the code/issuer pair against the issuer's home domain.
+    /// Never fails: every problem is reported as `Unverified` with a reason.
+    pub async fn check(&self, asset_code: &str, asset_issuer: Option<&str>) -> VerificationOutcome {
+        let Some(issuer) = asset_issuer else {
+            return VerificationOutcome::Unverified("asset has no issuer".to_string());
+        };
+
+        let account = match self.horizon_client.get_account(issuer).await {
+            Ok(account) => account,
+            Err(e) => {
+                return VerificationOutcome::Unverified(format!(
+                    "failed to load issuer account: {}",
+                    e
+                ))
+            }
+        };
+
+        // Sandbox issuers have no home domain; an issuer account that loads is enough
+        if self.horizon_client.fake().is_some() {
+            return VerificationOutcome::Verified;
+        }
+
+        let Some(home_domain) = account.home_domain.filter(|d| !d.trim().is_empty()) else {
+            return VerificationOutcome::Unverified("issuer account has no home_domain".to_string());
+        };
+
+        let body = match self.fetch_stellar_toml(home_domain.trim()).await {
+            Ok(body) => body,
+            Err(e) => {
+                return VerificationOutcome::Unverified(format!(
+                    "failed to fetch stellar.toml from {}: {}",
+                    home_domain, e
+                ))
+            }
+        };
+
+        match toml_lists_asset(&body, asset_code, issuer) {
+            Ok(true) => VerificationOutcome::Verified,
+            Ok(false) => VerificationOutcome::Unverified(format!(
+                "{} does not list {}:{} in CURRENCIES",
+                home_domain, asset_code, issuer
+            )),
+            Err(e) => VerificationOutcome::Unverified(format!("invalid stellar.toml: {}", e)),
+        }
+    }
+
+    /// [`AssetVerifier::check`], logging a failed verification. The caller
+    /// stores the outcome, in the same transaction as its own change to the asset.
+    pub async fn verify(&self, asset_code: &str, asset_issuer: Option<&str>) -> VerificationOutcome {
+        let outcome = self.check(asset_code, asset_issuer).await;
+        if let VerificationOutcome::Unverified(reason) = &outcome {
+            tracing::warn!(
+                asset_code = %asset_code,
+                asset_issuer = ?asset_issuer,
+                "Asset failed home-domain verification: {}",
+                reason
+            );
+        }
+        outcome
+    }
+
+    /// Verify an asset and store the result on its registry row.
+    pub async fn verify_and_store(&self, asset: &Asset) -> anyhow::Result<Asset> {
+        let outcome = self
+            .verify(&asset.asset_code, asset.asset_issuer.as_deref())
+            .await;
+
+        let updated =
+            queries::set_asset_verification(&self.pool, asset.id, outcome.status(), outcome.error())
+                .await?;
+        Ok(updated)
+    }
+
+    /// Re-verify every asset whose last verification is older than `max_age`.
+    /// Returns the number of assets checked.
+    pub async fn reverify_stale(&self, max_age: ChronoDuration) -> anyhow::Result<usize> {
+        let due = queries::list_assets_due_for_verification(&self.pool, Utc::now() - max_age).await?;
+
+        for asset in &due {
+            if let Err(e) = self.verify_and_store(asset).await {
+                tracing::error!(asset_code = %asset.asset_code, "Failed to store asset verification: {}", e);
+            }
+        }
+
+        if !due.is_empty() {
+            tracing::info!("Re-verified {} assets", due.len());
+        }
+        Ok(due.len())
+    }
+
+    async fn fetch_stellar_toml(&self, home_domain: &str) -> anyhow::Result<String> {
+        if home_domain.contains('/') || home_domain.contains(':') {
+            anyhow::bail!("home_domain must be a bare domain name");
+        }
+        let url = format!("https://{}/.well-known/stellar.toml", home_domain);
+
+        let mut response = self.http.get(&url).send().await?.error_for_status()?;
+
+        if response
+            .content_length()
+            .is_some_and(|len| len as usize > MAX_TOML_BYTES)
+        {
+            anyhow::bail!("stellar.toml exceeds {} bytes", MAX_TOML_BYTES);
+        }
+
+        // Content-Length can be absent or wrong, so enforce the limit while reading
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > MAX_TOML_BYTES {
+                anyhow::bail!("stellar.toml exceeds {} bytes", MAX_TOML_BYTES);
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(String::from_utf8(body)?)
+    }
+}
+
+/// The issuer of a callback's asset: the one the callback names, or else the
+/// registry's only issuer of the code. A code the registry has several
+/// issuers of must be named, so one issuer's USDC is never taken for
+/// another's.
+pub async fn resolve_asset_issuer(
+    pool: &PgPool,
+    asset_code: &str,
+    named: Option<&str>,
+) -> Result<Option<String>, AppError> {
+    if asset_code == NATIVE_ASSET_CODE {
+        return match named {
+            Some(_) => Err(AppError::Validation("asset_issuer: lumens have no issuer".to_string())),
+            None => Ok(None),
+        };
+    }
+    if let Some(issuer) = named {
+        return Ok(Some(issuer.to_string()));
+    }
+
+    let mut issuers = queries::list_asset_issuers(pool, asset_code).await?;
+    match issuers.len() {
+        0 | 1 => Ok(issuers.pop()),
+        n => Err(AppError::Validation(format!(
+            "asset_issuer: required, the registry has {} issuers of {}",
+            n, asset_code
+        ))),
+    }
+}
+
+/// Reject deposits for unverified registry assets when the feature flag is on.
+/// Assets missing from the registry are left to the regular validation.
+pub async fn ensure_deposits_allowed(
+    pool: &PgPool,
+    feature_flags: &FeatureFlagService,
+    asset_code: &str,
+    asset_issuer: Option<&str>,
+) -> Result<(), AppError> {
+    if !feature_flags.is_enabled(REFUSE_UNVERIFIED_DEPOSITS_FLAG).await {
+        return Ok(());
+    }
+
+    match queries::get_registry_asset(pool, asset_code, asset_issuer).await? {
+        Some(asset) if !asset.is_verified() => Err(AppError::Validation(format!(
+            "asset_code: {} has not passed issuer home-domain verification",
+            asset_code
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Whether a stellar.toml body lists the given code/issuer pair in CURRENCIES
+fn toml_lists_asset(body: &str, asset_code: &str, issuer: &str) -> Result<bool, toml::de::Error> {
+    let parsed: StellarToml = toml::from_str(body)?;
+    Ok(parsed.currencies.iter().any(|currency| {
+        currency.code.as_deref() == Some(asset_code) && currency.issuer.as_deref() == Some(issuer)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ISSUER: &str = "GBBD47UZQ5CSKQPV456PYYH4FSYJHBWGQJUVNMCNWZ2NBEHKQPW3KXKJ";
+
+    #[test]
+    fn test_toml_lists_matching_currency() {
+        let body = format!(
+            r#"
+            NETWORK_PASSPHRASE = "Test SDF Network ; September 2015"
+
+            [[CURRENCIES]]
+            code = "EUR"
+            issuer = "{issuer}"
+
+            [[CURRENCIES]]
+            code = "USD"
+            issuer = "{issuer}"
+            "#,
+            issuer = ISSUER
+        );
+        assert!(toml_lists_asset(&body, "USD", ISSUER).unwrap());
+    }
+
+    #[test]
+    fn test_toml_requires_code_and_issuer_to_match_together() {
+        let body = format!(
+            r#"
+            [[CURRENCIES]]
+            code = "EUR"
+            issuer = "{}"
+
+            [[CURRENCIES]]
+            code = "USD"
+            issuer = "GOTHERISSUER"
+            "#,
+            ISSUER
+        );
+        assert!(!toml_lists_asset(&body, "USD", ISSUER).unwrap());
+    }
+
+    #[test]
+    fn test_toml_without_currencies_is_not_a_match() {
+        assert!(!toml_lists_asset("VERSION = \"2.0.0\"", "USD", ISSUER).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_toml_is_an_error() {
+        assert!(toml_lists_asset("[[CURRENCIES", "USD", ISSUER).is_err());
+    }
+
+    #[test]
+    fn test_outcome_status() {
+        assert_eq!(VerificationOutcome::Verified.status(), "verified");
+        let unverified = VerificationOutcome::Unverified("no home_domain".to_string());
+        assert_eq!(unverified.status(), "unverified");
+        assert_eq!(unverified.error(), Some("no home_domain"));
+    }
+}
@@ -0,0 +1,537 @@
+//! Account-wide erasure for "delete everything you hold about G..." requests.
+//!
+//! Everything linked to an account is listed in [`ErasureCategory::ALL`], one
+//! category per table. A run walks that registry and applies the configured
+//! [`ErasureAction`] to each category, or only counts the rows in dry-run
+//! mode. Tables that hold no account data are listed in [`NOT_ACCOUNT_DATA`]
+//! with the reason; a test fails if a migration adds a table to neither.
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+use crate::db::audit::{AuditLog, ENTITY_ERASURE};
+use crate::db::models::ErasureJob;
+use crate::db::{queries, uow};
+use crate::error::AppError;
+use crate::utils::diff::REDACTED;
+use crate::utils::shutdown::Shutdown;
+
+/// Data linked to an account, one table each
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErasureCategory {
+    AccountStats,
+    RawCallbacks,
+    IngestionOutbox,
+    WebhookDeliveries,
+    AuditLogs,
+    TransactionDlq,
+    Transactions,
+}
+
+impl ErasureCategory {
+    /// Every category, in the order a run applies them. Transactions come
+    /// last: the other categories find their rows through them.
+    pub const ALL: &'static [ErasureCategory] = &[
+        ErasureCategory::AccountStats,
+        ErasureCategory::RawCallbacks,
+        ErasureCategory::IngestionOutbox,
+        ErasureCategory::WebhookDeliveries,
+        ErasureCategory::AuditLogs,
+        ErasureCategory::TransactionDlq,
+        ErasureCategory::Transactions,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErasureCategory::AccountStats => "account_stats",
+            ErasureCategory::RawCallbacks => "raw_callbacks",
+            ErasureCategory::IngestionOutbox => "ingestion_outbox",
+            ErasureCategory::WebhookDeliveries => "webhook_deliveries",
+            ErasureCategory::AuditLogs => "audit_logs",
+            ErasureCategory::TransactionDlq => "transaction_dlq",
+            ErasureCategory::Transactions => "transactions",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|c| c.as_str() == raw.trim())
+    }
+
+    /// The table holding the category's rows
+    pub fn table(&self) -> &'static str {
+        self.as_str()
+    }
+
+    /// Rows linked to the account in `$1`
+    fn scope(&self) -> &'static str {
+        match self {
+            ErasureCategory::AccountStats => "stellar_account = $1",
+            ErasureCategory::RawCallbacks => {
+                "transaction_id IN (SELECT id FROM transactions WHERE stellar_account = $1)"
+            }
+            ErasureCategory::IngestionOutbox => {
+                "payload->>'stellar_account' = $1 OR raw_body LIKE '%' || $1 || '%'"
+            }
+            ErasureCategory::WebhookDeliveries => "payload::text LIKE '%' || $1 || '%'",
+            ErasureCategory::AuditLogs => {
+                "entity_id IN (SELECT id FROM transactions WHERE stellar_account = $1) \
+                 OR old_val::text LIKE '%' || $1 || '%' OR new_val::text LIKE '%' || $1 || '%'"
+            }
+            ErasureCategory::TransactionDlq => {
+                "stellar_account = $1 OR transaction_id IN (SELECT id FROM transactions WHERE stellar_account = $1)"
+            }
+            ErasureCategory::Transactions => "stellar_account = $1",
+        }
+    }
+
+    /// `SET` clause of a redaction, for categories that support one
+    fn redaction(&self) -> Option<String> {
+        match self {
+            ErasureCategory::RawCallbacks => Some(format!("body = '{}'", REDACTED)),
+            ErasureCategory::WebhookDeliveries => {
+                Some("payload = jsonb_build_object('redacted', true)".to_string())
+            }
+            ErasureCategory::AuditLogs => Some(format!(
+                "old_val = CASE WHEN old_val IS NULL THEN NULL ELSE to_jsonb('{0}'::text) END, \
+                 new_val = CASE WHEN new_val IS NULL THEN NULL ELSE to_jsonb('{0}'::text) END",
+                REDACTED
+            )),
+            ErasureCategory::TransactionDlq => {
+                Some(format!("stellar_account = '{}', stack_trace = NULL", REDACTED))
+            }
+            ErasureCategory::Transactions => Some(format!(
+                "stellar_account = '{}', muxed_id = NULL, metadata = NULL, updated_at = NOW()",
+                REDACTED
+            )),
+            ErasureCategory::AccountStats | ErasureCategory::IngestionOutbox => None,
+        }
+    }
+
+    /// Actions the category supports. Transactions and audit logs are kept
+    /// for settlement and compliance, so they can only be redacted.
+    pub fn actions(&self) -> &'static [ErasureAction] {
+        match self {
+            ErasureCategory::AccountStats | ErasureCategory::IngestionOutbox => {
+                &[ErasureAction::Delete, ErasureAction::Retain]
+            }
+            ErasureCategory::RawCallbacks
+            | ErasureCategory::WebhookDeliveries
+            | ErasureCategory::TransactionDlq => {
+                &[ErasureAction::Delete, ErasureAction::Redact, ErasureAction::Retain]
+            }
+            ErasureCategory::AuditLogs | ErasureCategory::Transactions => {
+                &[ErasureAction::Redact, ErasureAction::Retain]
+            }
+        }
+    }
+
+    pub fn default_action(&self) -> ErasureAction {
+        match self {
+            ErasureCategory::AccountStats
+            | ErasureCategory::RawCallbacks
+            | ErasureCategory::IngestionOutbox
+            | ErasureCategory::WebhookDeliveries => ErasureAction::Delete,
+            ErasureCategory::AuditLogs
+            | ErasureCategory::TransactionDlq
+            | ErasureCategory::Transactions => ErasureAction::Redact,
+        }
+    }
+}
+
+/// Tables with no account data, and why
+pub const NOT_ACCOUNT_DATA: &[(&str, &str)] = &[
+    ("account_watchlist", "keyed by the sha256 of the address"),
+    ("anchor_transaction_ids", "anchor and transaction ids"),
+    ("api_token_usage", "request counts per token"),
+    ("api_tokens", "operator credentials"),
+    ("assets", "asset registry"),
+    ("erasure_jobs", "the address is cleared when a run finishes"),
+    ("export_jobs", "artifacts expire after EXPORT_RETENTION_HOURS"),
+    ("feature_flags", "configuration; rollout allowlists are kept by operators"),
+    ("flag_generations", "configuration"),
+    ("notification_templates", "configuration"),
+    ("outbox_events", "transaction ids and amounts; purged after 24 hours"),
+    ("processed_effects", "Horizon effect ids"),
+    ("processed_operations", "Horizon operation ids"),
+    ("quotes", "prices only"),
+    ("settlements", "per-asset aggregates"),
+    ("stream_cursors", "Horizon paging tokens"),
+    ("webhook_subscriptions", "subscriber endpoints"),
+];
+
+/// What a run does to a category's rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErasureAction {
+    Delete,
+    /// Overwrite the account and personal fields, keep the row
+    Redact,
+    /// Keep the rows as they are; still counted in the report
+    Retain,
+}
+
+impl ErasureAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErasureAction::Delete => "delete",
+            ErasureAction::Redact => "redact",
+            ErasureAction::Retain => "retain",
+        }
+    }
+
+    fn parse(raw: &str) -> Option<Self> {
+        [ErasureAction::Delete, ErasureAction::Redact, ErasureAction::Retain]
+            .into_iter()
+            .find(|a| a.as_str() == raw.trim())
+    }
+}
+
+/// Action per category; categories not configured use their default
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ErasurePolicy {
+    overrides: HashMap<ErasureCategory, ErasureAction>,
+}
+
+impl ErasurePolicy {
+    pub fn action(&self, category: ErasureCategory) -> ErasureAction {
+        self.overrides
+            .get(&category)
+            .copied()
+            .unwrap_or_else(|| category.default_action())
+    }
+
+    /// Every category with its action, recorded on each run
+    pub fn to_json(&self) -> serde_json::Value {
+        let actions: BTreeMap<&str, &str> = ErasureCategory::ALL
+            .iter()
+            .map(|c| (c.as_str(), self.action(*c).as_str()))
+            .collect();
+        serde_json::json!(actions)
+    }
+}
+
+/// Parse `ERASURE_POLICY`, e.g. `raw_callbacks=redact,transaction_dlq=delete`
+pub fn parse_erasure_policy(raw: &str) -> anyhow::Result<ErasurePolicy> {
+    let mut policy = ErasurePolicy::default();
+    for entry in raw.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (name, action) = entry
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("ERASURE_POLICY: expected category=action, got '{}'", entry))?;
+        let category = ErasureCategory::parse(name)
+            .ok_or_else(|| anyhow::anyhow!("ERASURE_POLICY: unknown category '{}'", name.trim()))?;
+        let action = ErasureAction::parse(action)
+            .filter(|a| category.actions().contains(a))
+            .ok_or_else(|| {
+                let supported: Vec<&str> = category.actions().iter().map(|a| a.as_str()).collect();
+                anyhow::anyhow!(
+                    "ERASURE_POLICY: {} supports {}, got '{}'",
+                    category.as_str(),
+                    supported.join(", "),
+                    action.trim()
+                )
+            })?;
+        policy.overrides.insert(category, action);
+    }
+    Ok(policy)
+}
+
+/// Rows one category had, and what was done to them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CategoryReport {
+    pub category: ErasureCategory,
+    pub table: String,
+    pub action: ErasureAction,
+    pub rows: u64,
+}
+
+/// Identifies an account in certificates and the watchlist without storing it
+pub fn account_hash(stellar_account: &str) -> String {
+    hex::encode(Sha256::digest(stellar_account.as_bytes()))
+}
+
+/// Reject callbacks for accounts erased with `block_callbacks`
+pub async fn ensure_callbacks_allowed(pool: &PgPool, stellar_account: &str) -> Result<(), AppError> {
+    if queries::is_account_watchlisted(pool, &account_hash(stellar_account)).await? {
+        return Err(AppError::Forbidden(
+            "stellar_account: the account was erased and no longer accepts callbacks".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Runs account erasures in the background and keeps their certificates
+#[derive(Clone)]
+pub struct ErasureService {
+    pool: PgPool,
+    policy: ErasurePolicy,
+    shutdown: Shutdown,
+}
+
+impl ErasureService {
+    pub fn new(pool: PgPool, policy: ErasurePolicy) -> Self {
+        Self {
+            pool,
+            policy,
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    /// Track runs on `shutdown` so the process waits for them to finish
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Queue an erasure run for an account and start it
+    pub async fn request(
+        &self,
+        stellar_account: &str,
+        dry_run: bool,
+        block_callbacks: bool,
+        actor: &str,
+    ) -> Result<ErasureJob, AppError> {
+        let job = ErasureJob {
+            id: Uuid::new_v4(),
+            account_hash: account_hash(stellar_account),
+            stellar_account: Some(stellar_account.to_string()),
+            dry_run,
+            block_callbacks: block_callbacks && !dry_run,
+            status: "pending".to_string(),
+            policy: self.policy.to_json(),
+            report: None,
+            error: None,
+            requested_by: actor.to_string(),
+            created_at: Utc::now(),
+            started_at: None,
+            completed_at: None,
+        };
+        let job = queries::insert_erasure_job(&self.pool, &job).await?;
+        self.start(job.id);
+        Ok(job)
+    }
+
+    /// Start every job left pending or running by the last stop. A run is one
+    /// database transaction, so an interrupted one changed nothing and starts
+    /// over. Returns the number of jobs started.
+    pub async fn resume(&self) -> Result<usize, AppError> {
+        let job_ids = queries::requeue_erasure_jobs(&self.pool).await?;
+        for job_id in &job_ids {
+            tracing::info!(erasure_id = %job_id, "Resuming erasure");
+            self.start(*job_id);
+        }
+        Ok(job_ids.len())
+    }
+
+    fn start(&self, job_id: Uuid) {
+        let service = self.clone();
+        self.shutdown.spawn(async move {
+            if let Err(e) = service.run(job_id).await {
+                tracing::error!(erasure_id = %job_id, "Erasure failed: {}", e);
+                if let Err(e) = queries::fail_erasure_job(&service.pool, job_id, &e.to_string()).await {
+                    tracing::error!(erasure_id = %job_id, "Failed to mark erasure as failed: {}", e);
+                }
+            }
+        });
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<ErasureJob, AppError> {
+        queries::get_erasure_job(&self.pool, id).await.map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::NotFound(format!("Erasure {} not found", id)),
+            _ => AppError::Database(e),
+        })
+    }
+
+    /// Every run for an account, newest first
+    pub async fn list_for_account(&self, stellar_account: &str) -> Result<Vec<ErasureJob>, AppError> {
+        Ok(queries::list_erasure_jobs_by_account_hash(&self.pool, &account_hash(stellar_account)).await?)
+    }
+
+    /// Apply the policy to every category in one database transaction, or
+    /// only count the rows for a dry run
+    pub async fn run(&self, job_id: Uuid) -> anyhow::Result<ErasureJob> {
+        let Some(job) = queries::start_erasure_job(&self.pool, job_id).await? else {
+            anyhow::bail!("erasure {} is not pending", job_id);
+        };
+        let Some(stellar_account) = job.stellar_account.clone() else {
+            anyhow::bail!("erasure {} has no account", job_id);
+        };
+        let policy = self.policy.clone();
+
+        let job = uow::run(&self.pool, |uow| Box::pin(async move {
+            queries::lock_erasure_job(uow.conn(), job.id).await?;
+            let mut report = Vec::with_capacity(ErasureCategory::ALL.len());
+            for category in ErasureCategory::ALL {
+                let action = policy.action(*category);
+                let rows = apply(uow.conn(), *category, action, &stellar_account, job.dry_run).await?;
+                report.push(CategoryReport {
+                    category: *category,
+                    table: category.table().to_string(),
+                    action,
+                    rows,
+                });
+            }
+            let report = serde_json::to_value(&report).expect("reports are always serializable");
+
+            if !job.dry_run {
+                if job.block_callbacks {
+                    queries::add_to_account_watchlist(uow.conn(), &job.account_hash, "erased", job.id).await?;
+                }
+                AuditLog::log(
+                    uow.conn(),
+                    job.id,
+                    ENTITY_ERASURE,
+                    "executed",
+                    None,
+                    Some(serde_json::json!({ "account_hash": job.account_hash, "report": report })),
+                    &job.requested_by,
+                )
+                .await?;
+            }
+            queries::complete_erasure_job(uow.conn(), job.id, &report).await
+        }))
+        .await?;
+
+        tracing::info!(
+            erasure_id = %job.id,
+            account_hash = %job.account_hash,
+            dry_run = job.dry_run,
+            "Erasure completed"
+        );
+        Ok(job)
+    }
+}
+
+/// Count the category's rows, then apply the action unless this is a dry run.
+/// Returns the rows affected, or that would be.
+async fn apply(
+    conn: &mut PgConnection,
+    category: ErasureCategory,
+    action: ErasureAction,
+    stellar_account: &str,
+    dry_run: bool,
+) -> sqlx::Result<u64> {
+    let table = category.table();
+    let scope = category.scope();
+    let statement = match (action, dry_run) {
+        (ErasureAction::Delete, false) => format!("DELETE FROM {} WHERE {}", table, scope),
+        (ErasureAction::Redact, false) => match category.redaction() {
+            Some(set) => format!("UPDATE {} SET {} WHERE {}", table, set, scope),
+            None => unreachable!("{} has no redaction", category.as_str()),
+        },
+        (ErasureAction::Retain, _) | (_, true) => {
+            let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {} WHERE {}", table, scope))
+                .bind(stellar_account)
+                .fetch_one(&mut *conn)
+                .await?;
+            return Ok(count as u64);
+        }
+    };
+    let result = sqlx::query(&statement).bind(stellar_account).execute(&mut *conn).await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tables created by the migrations, partitions excluded
+    fn migration_tables() -> Vec<String> {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/migrations");
+        let mut tables = Vec::new();
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let sql = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+            for line in sql.lines().map(str::trim) {
+                let Some(rest) = line.strip_prefix("CREATE TABLE ") else {
+                    continue;
+                };
+                if line.contains("PARTITION OF") {
+                    continue;
+                }
+                let rest = rest.strip_prefix("IF NOT EXISTS ").unwrap_or(rest);
+                let name = rest.split(|c: char| c == '(' || c.is_whitespace()).next().unwrap();
+                tables.push(name.to_string());
+            }
+        }
+        tables.sort();
+        tables.dedup();
+        tables
+    }
+
+    #[test]
+    fn test_every_table_is_a_category_or_not_account_data() {
+        for table in migration_tables() {
+            let is_category = ErasureCategory::ALL.iter().any(|c| c.table() == table);
+            let is_exempt = NOT_ACCOUNT_DATA.iter().any(|(name, _)| *name == table);
+            assert!(
+                is_category ^ is_exempt,
+                "table {} must be an ErasureCategory or listed in NOT_ACCOUNT_DATA (and not both)",
+                table
+            );
+        }
+    }
+
+    #[test]
+    fn test_registry_lists_only_existing_tables() {
+        let tables = migration_tables();
+        for category in ErasureCategory::ALL {
+            assert!(tables.iter().any(|t| t == category.table()), "{} has no table", category.as_str());
+        }
+        for (name, _) in NOT_ACCOUNT_DATA {
+            assert!(tables.iter().any(|t| t == name), "{} no longer exists", name);
+        }
+    }
+
+    #[test]
+    fn test_transactions_are_applied_last() {
+        assert_eq!(ErasureCategory::ALL.last(), Some(&ErasureCategory::Transactions));
+    }
+
+    #[test]
+    fn test_every_category_supports_its_default_and_redaction() {
+        for category in ErasureCategory::ALL {
+            assert!(category.actions().contains(&category.default_action()));
+            assert!(category.actions().contains(&ErasureAction::Retain));
+            assert_eq!(
+                category.actions().contains(&ErasureAction::Redact),
+                category.redaction().is_some(),
+                "{}",
+                category.as_str()
+            );
+            assert_eq!(ErasureCategory::parse(category.as_str()), Some(*category));
+        }
+    }
+
+    #[test]
+    fn test_parse_policy_overrides_defaults() {
+        let policy = parse_erasure_policy("raw_callbacks=redact, transactions=retain").unwrap();
+        assert_eq!(policy.action(ErasureCategory::RawCallbacks), ErasureAction::Redact);
+        assert_eq!(policy.action(ErasureCategory::Transactions), ErasureAction::Retain);
+        assert_eq!(policy.action(ErasureCategory::AccountStats), ErasureAction::Delete);
+        assert_eq!(parse_erasure_policy("").unwrap(), ErasurePolicy::default());
+
+        let json = policy.to_json();
+        assert_eq!(json.as_object().unwrap().len(), ErasureCategory::ALL.len());
+        assert_eq!(json["raw_callbacks"], "redact");
+    }
+
+    #[test]
+    fn test_parse_policy_rejects_unsupported_actions() {
+        assert!(parse_erasure_policy("transactions=delete").is_err());
+        assert!(parse_erasure_policy("account_stats=redact").is_err());
+        assert!(parse_erasure_policy("kyc=delete").is_err());
+        assert!(parse_erasure_policy("raw_callbacks").is_err());
+    }
+
+    #[test]
+    fn test_account_hash_is_stable() {
+        let account = "GBBD47UZQ5CSKQPV456PYYH4FSYJHBWGQJUVNMCNWZ2NBEHKQPW3KXKJ";
+        assert_eq!(account_hash(account), account_hash(account));
+        assert_eq!(account_hash(account).len(), 64);
+        assert_ne!(account_hash(account), account_hash("GOTHER"));
+    }
+}
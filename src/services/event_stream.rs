@@ -0,0 +1,308 @@
+//! In-process feed of outbox events for the admin event stream.
+//!
+//! Events are written to `outbox_events` inside the transaction that caused
+//! them. A trigger sends `NOTIFY outbox_events` with each one, which Postgres
+//! only delivers once that transaction commits. The relay treats a
+//! notification as a wake-up and reads the events it hasn't relayed yet.
+//! Events from other instances arrive the same way, and a dropped listener
+//! connection loses nothing.
+//!
+//! Sequence numbers are taken at insert but become visible at commit, so
+//! event 10 can commit after event 11. The relay records such a gap and keeps
+//! reading after event 11; the gap is looked up on its own until it fills or
+//! [`GAP_GRACE`] passes (the writer rolled back).
+
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+use crate::db::models::OutboxEvent;
+use crate::db::queries;
+use crate::utils::shutdown::Shutdown;
+
+/// Channel the `outbox_events_notify` trigger notifies on
+pub const NOTIFY_CHANNEL: &str = "outbox_events";
+/// Events a subscriber may fall behind by before it is disconnected
+pub const SUBSCRIBER_CAPACITY: usize = 256;
+/// Events replayed to a reconnecting client at most...
+pub const REPLAY_LIMIT: i64 = 1000;
+/// ...and only from this far back
+pub const REPLAY_LOOKBACK_SECS: i64 = 3600;
+/// Events are purged once older than this
+pub const RETENTION_HOURS: i64 = 24;
+/// Events read per relay query
+const RELAY_BATCH_SIZE: i64 = 500;
+/// How long a missing sequence number is waited for before it is taken to
+/// belong to a rolled back write
+pub const GAP_GRACE: Duration = Duration::from_secs(60);
+
+/// What the relay has broadcast
+#[derive(Debug, Default)]
+struct RelayState {
+    /// Highest sequence number read; the next read starts after it
+    cursor: i64,
+    /// Missing sequence numbers below `cursor`, and when each was first missed
+    gaps: BTreeMap<i64, Instant>,
+}
+
+impl RelayState {
+    fn starting_after(seq: i64) -> Self {
+        Self {
+            cursor: seq,
+            ..Self::default()
+        }
+    }
+
+    /// Take a read of events after `cursor` or in `gaps`. Returns the ones
+    /// not relayed yet.
+    fn advance(&mut self, mut events: Vec<OutboxEvent>, now: Instant) -> Vec<OutboxEvent> {
+        events.sort_by_key(|event| event.seq);
+        let mut fresh = Vec::with_capacity(events.len());
+        for event in events {
+            if event.seq > self.cursor {
+                for skipped in self.cursor + 1..event.seq {
+                    self.gaps.insert(skipped, now);
+                }
+                self.cursor = event.seq;
+                fresh.push(event);
+            } else if self.gaps.remove(&event.seq).is_some() {
+                fresh.push(event);
+            }
+        }
+
+        let before = self.gaps.len();
+        self.gaps.retain(|_, missed_at| now.duration_since(*missed_at) < GAP_GRACE);
+        if self.gaps.len() < before {
+            tracing::debug!(
+                expired = before - self.gaps.len(),
+                "Gave up on outbox sequence gaps; their writes rolled back"
+            );
+        }
+        fresh
+    }
+}
+
+/// Events a reconnecting client missed
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub events: Vec<OutboxEvent>,
+    /// False when some missed events are beyond the lookback or the limit;
+    /// the client should reload instead of relying on the replay
+    pub complete: bool,
+}
+
+#[derive(Clone)]
+pub struct EventStream {
+    pool: PgPool,
+    sender: broadcast::Sender<OutboxEvent>,
+    shutdown: Shutdown,
+}
+
+impl EventStream {
+    pub fn new(pool: PgPool) -> Self {
+        let (sender, _) = broadcast::channel(SUBSCRIBER_CAPACITY);
+        Self {
+            pool,
+            sender,
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    /// Stop the relay and the sweep once `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Live events from now on. A receiver more than [`SUBSCRIBER_CAPACITY`]
+    /// events behind gets `RecvError::Lagged`.
+    pub fn subscribe(&self) -> broadcast::Receiver<OutboxEvent> {
+        self.sender.subscribe()
+    }
+
+    pub async fn latest_seq(&self) -> sqlx::Result<i64> {
+        queries::latest_outbox_event_seq(&self.pool).await
+    }
+
+    /// Events after `seq`, bounded by [`REPLAY_LIMIT`] and
+    /// [`REPLAY_LOOKBACK_SECS`]
+    pub async fn replay(&self, seq: i64) -> sqlx::Result<Replay> {
+        let mut events =
+            queries::list_outbox_events_after(&self.pool, seq, REPLAY_LOOKBACK_SECS, REPLAY_LIMIT + 1)
+                .await?;
+        let truncated = events.len() as i64 > REPLAY_LIMIT;
+        events.truncate(REPLAY_LIMIT as usize);
+        let aged_out =
+            queries::outbox_events_missed_beyond_lookback(&self.pool, seq, REPLAY_LOOKBACK_SECS).await?;
+        Ok(Replay {
+            events,
+            complete: !truncated && !aged_out,
+        })
+    }
+
+    /// Start the relay and the hourly retention sweep
+    pub fn start(&self) {
+        let relay = self.clone();
+        self.shutdown.spawn(async move {
+            let mut state = None;
+            loop {
+                tokio::select! {
+                    _ = relay.shutdown.cancelled() => break,
+                    result = relay.relay(&mut state) => {
+                        if let Err(e) = result {
+                            tracing::error!("Outbox event relay failed: {}", e);
+                        }
+                    }
+                }
+                tokio::select! {
+                    _ = relay.shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+                }
+            }
+            tracing::info!("Outbox event relay stopped");
+        });
+
+        let sweeper = self.clone();
+        self.shutdown.spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(3600));
+            loop {
+                tokio::select! {
+                    _ = sweeper.shutdown.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+                let before = chrono::Utc::now() - chrono::Duration::hours(RETENTION_HOURS);
+                match queries::purge_outbox_events(&sweeper.pool, before).await {
+                    Ok(0) => {}
+                    Ok(purged) => tracing::info!("Purged {} outbox events", purged),
+                    Err(e) => tracing::error!("Outbox event purge failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Listen and relay until the listener fails. `state` survives restarts
+    /// of the loop, so events written in between are still relayed.
+    async fn relay(&self, state: &mut Option<RelayState>) -> anyhow::Result<()> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(NOTIFY_CHANNEL).await?;
+        if state.is_none() {
+            *state = Some(RelayState::starting_after(self.latest_seq().await?));
+        }
+        let state = state.as_mut().expect("relay state was just set");
+        // Catch up on anything committed while not listening
+        self.relay_pending(state).await?;
+
+        loop {
+            // Wake up for gaps even when nothing is written
+            let timeout = if state.gaps.is_empty() { Duration::from_secs(30) } else { Duration::from_secs(1) };
+            if let Ok(notification) = tokio::time::timeout(timeout, listener.recv()).await {
+                notification?;
+            }
+            self.relay_pending(state).await?;
+        }
+    }
+
+    async fn relay_pending(&self, state: &mut RelayState) -> sqlx::Result<()> {
+        // Late commits into recorded gaps first, so they go out before newer
+        // events read below
+        if !state.gaps.is_empty() {
+            let seqs: Vec<i64> = state.gaps.keys().copied().collect();
+            let events = queries::list_outbox_events_by_seq(&self.pool, &seqs).await?;
+            self.broadcast(state.advance(events, Instant::now()));
+        }
+        loop {
+            let events = queries::list_outbox_events_after(
+                &self.pool,
+                state.cursor,
+                REPLAY_LOOKBACK_SECS,
+                RELAY_BATCH_SIZE,
+            )
+            .await?;
+            let full_batch = events.len() as i64 == RELAY_BATCH_SIZE;
+            self.broadcast(state.advance(events, Instant::now()));
+            if !full_batch {
+                return Ok(());
+            }
+        }
+    }
+
+    fn broadcast(&self, events: Vec<OutboxEvent>) {
+        for event in events {
+            // No receivers is not an error: nobody is watching
+            let _ = self.sender.send(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(seq: i64) -> OutboxEvent {
+        OutboxEvent {
+            seq,
+            event_type: "transaction.created".to_string(),
+            asset_code: Some("USDC".to_string()),
+            payload: serde_json::json!({}),
+            correlation_id: None,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    fn seqs(events: Vec<OutboxEvent>) -> Vec<i64> {
+        events.into_iter().map(|event| event.seq).collect()
+    }
+
+    #[test]
+    fn test_events_are_relayed_once() {
+        let mut state = RelayState::starting_after(10);
+        let now = Instant::now();
+        assert_eq!(seqs(state.advance(vec![event(11), event(12)], now)), vec![11, 12]);
+        assert_eq!(state.cursor, 12);
+        assert_eq!(seqs(state.advance(vec![event(12), event(13)], now)), vec![13]);
+        assert!(state.gaps.is_empty());
+    }
+
+    #[test]
+    fn test_late_commit_below_a_later_event_is_still_relayed() {
+        let mut state = RelayState::starting_after(10);
+        let now = Instant::now();
+
+        // 11 commits after 12 ...
+        assert_eq!(seqs(state.advance(vec![event(12)], now)), vec![12]);
+        assert_eq!(state.cursor, 12, "reading moves on past the gap");
+        assert_eq!(state.gaps.keys().copied().collect::<Vec<_>>(), vec![11]);
+
+        // ... and is relayed once the gap lookup finds it
+        assert_eq!(seqs(state.advance(vec![event(11)], now)), vec![11]);
+        assert!(state.gaps.is_empty());
+        assert!(state.advance(vec![event(11), event(12)], now).is_empty());
+    }
+
+    #[test]
+    fn test_gap_does_not_hold_back_later_events() {
+        let mut state = RelayState::starting_after(10);
+        let now = Instant::now();
+        state.advance(vec![event(12)], now);
+
+        // Later reads start after the newest event, not below the gap
+        assert_eq!(seqs(state.advance(vec![event(13), event(14)], now)), vec![13, 14]);
+        assert_eq!(state.cursor, 14);
+        assert_eq!(state.gaps.len(), 1);
+    }
+
+    #[test]
+    fn test_gap_of_a_rolled_back_write_expires() {
+        let mut state = RelayState::starting_after(10);
+        let start = Instant::now();
+        state.advance(vec![event(12)], start);
+        assert_eq!(state.gaps.len(), 1);
+
+        let later = start + GAP_GRACE;
+        assert_eq!(seqs(state.advance(vec![event(13)], later)), vec![13]);
+        assert_eq!(state.cursor, 13);
+        assert!(state.gaps.is_empty());
+    }
+}
@@ -0,0 +1,369 @@
+use chrono::Duration;
+use futures::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::db::models::ExportJob;
+use crate::db::queries;
+use crate::error::AppError;
+use crate::handlers::export::{self, ExportQuery};
+use crate::services::export_storage::{ByteStream, ExportStorage};
+use crate::utils::clock::{self, SharedClock};
+
+/// Export filter parameters after normalization.
+///
+/// Two requests that mean the same thing (different casing, `json` vs `ndjson`,
+/// `2025-01-01` vs `2025-01-01T00:00:00Z`) normalize to the same value and
+/// therefore the same parameter hash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NormalizedExportParams {
+    pub format: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub status: Option<String>,
+    pub asset_code: Option<String>,
+}
+
+impl NormalizedExportParams {
+    pub fn from_query(query: &ExportQuery) -> Result<Self, AppError> {
+        let format = match query.format.trim().to_ascii_lowercase().as_str() {
+            "csv" => "csv",
+            "json" | "ndjson" => "ndjson",
+            other => {
+                return Err(AppError::Validation(format!(
+                    "format: must be 'csv' or 'ndjson', got '{}'",
+                    other
+                )))
+            }
+        };
+
+        Ok(Self {
+            format: format.to_string(),
+            from: normalize_date("from", &query.from)?,
+            to: normalize_date("to", &query.to)?,
+            status: non_empty(&query.status).map(|s| s.to_ascii_lowercase()),
+            asset_code: non_empty(&query.asset_code).map(|s| s.to_ascii_uppercase()),
+        })
+    }
+
+    /// sha256 over the canonical JSON encoding (field order is fixed by the struct)
+    pub fn hash(&self) -> String {
+        let canonical = serde_json::to_vec(self).expect("params are always serializable");
+        hex::encode(Sha256::digest(canonical))
+    }
+
+    fn extension(&self) -> &'static str {
+        if self.format == "ndjson" {
+            "ndjson"
+        } else {
+            "csv"
+        }
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+fn normalize_date(field: &str, value: &Option<String>) -> Result<Option<String>, AppError> {
+    match non_empty(value) {
+        Some(raw) => export::parse_date(&raw)
+            .map(|dt| Some(dt.to_rfc3339()))
+            .map_err(|e| AppError::Validation(format!("{}: {}", field, e))),
+        None => Ok(None),
+    }
+}
+
+/// Generates exports in the background and serves the stored artifacts.
+#[derive(Clone)]
+pub struct ExportJobService {
+    pool: PgPool,
+    storage: Arc<dyn ExportStorage>,
+    retention: Duration,
+    freshness: Duration,
+    clock: SharedClock,
+}
+
+impl ExportJobService {
+    pub fn new(
+        pool: PgPool,
+        storage: Arc<dyn ExportStorage>,
+        retention_hours: i64,
+        freshness_minutes: i64,
+    ) -> Self {
+        Self {
+            pool,
+            storage,
+            retention: Duration::hours(retention_hours),
+            freshness: Duration::minutes(freshness_minutes),
+            clock: clock::system(),
+        }
+    }
+
+    /// Judge freshness and expiry on `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Create an export job, or return an existing job for identical parameters
+    /// that is still running or finished within the freshness window.
+    ///
+    /// Returns the job and whether it was reused.
+    pub async fn request(&self, query: &ExportQuery) -> Result<(ExportJob, bool), AppError> {
+        let params = NormalizedExportParams::from_query(query)?;
+        let params_hash = params.hash();
+
+        let fresh_after = self.clock.now() - self.freshness;
+        if let Some(existing) =
+            queries::find_reusable_export_job(&self.pool, &params_hash, fresh_after).await?
+        {
+            tracing::info!(
+                export_id = %existing.id,
+                params_hash = %params_hash,
+                "Reusing existing export job"
+            );
+            return Ok((existing, true));
+        }
+
+        let now = self.clock.now();
+        let job = ExportJob {
+            id: Uuid::new_v4(),
+            params_hash,
+            params: serde_json::to_value(&params)
+                .map_err(|e| AppError::Internal(e.to_string()))?,
+            format: params.format.clone(),
+            status: "pending".to_string(),
+            storage_key: None,
+            content_hash: None,
+            size_bytes: None,
+            row_count: None,
+            error: None,
+            created_at: now,
+            completed_at: None,
+            expires_at: now + self.retention,
+        };
+        let job = queries::insert_export_job(&self.pool, &job).await?;
+
+        let service = self.clone();
+        let job_id = job.id;
+        tokio::spawn(async move {
+            if let Err(e) = service.generate(job_id, params).await {
+                tracing::error!(export_id = %job_id, "Export generation failed: {}", e);
+                if let Err(e) = queries::fail_export_job(&service.pool, job_id, &e.to_string()).await {
+                    tracing::error!(export_id = %job_id, "Failed to mark export as failed: {}", e);
+                }
+            }
+        });
+
+        Ok((job, false))
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<ExportJob, AppError> {
+        queries::get_export_job(&self.pool, id).await.map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::NotFound(format!("Export {} not found", id)),
+            _ => AppError::Database(e),
+        })
+    }
+
+    /// Write the export to a temp file while hashing it, then hand the file to
+    /// the storage backend under its content-addressed key.
+    async fn generate(&self, job_id: Uuid, params: NormalizedExportParams) -> anyhow::Result<()> {
+        queries::mark_export_job_running(&self.pool, job_id).await?;
+        tracing::info!(export_id = %job_id, format = %params.format, "Generating export");
+
+        let pool = Arc::new(self.pool.clone());
+        let mut rows = if params.format == "ndjson" {
+            export::create_json_stream(
+                pool,
+                params.from.clone(),
+                params.to.clone(),
+                params.status.clone(),
+                params.asset_code.clone(),
+            )
+        } else {
+            export::create_csv_stream(
+                pool,
+                params.from.clone(),
+                params.to.clone(),
+                params.status.clone(),
+                params.asset_code.clone(),
+            )
+        };
+
+        let tmp_path = std::env::temp_dir().join(format!("synapse-export-{}.tmp", job_id));
+        let mut file = tokio::fs::File::create(&tmp_path).await?;
+        let mut hasher = Sha256::new();
+        let mut size_bytes: i64 = 0;
+        let mut row_count: i64 = 0;
+
+        while let Some(line) = rows.next().await {
+            let mut line = line?;
+            if params.format == "ndjson" {
+                line.push('\n');
+            }
+            hasher.update(line.as_bytes());
+            file.write_all(line.as_bytes()).await?;
+            size_bytes += line.len() as i64;
+            row_count += 1;
+        }
+        file.flush().await?;
+        drop(file);
+
+        // The CSV header is not a data row
+        if params.format == "csv" {
+            row_count = row_count.saturating_sub(1);
+        }
+
+        let content_hash = hex::encode(hasher.finalize());
+        let storage_key = format!("{}.{}", content_hash, params.extension());
+
+        if self.storage.exists(&storage_key).await? {
+            // Identical bytes already stored by an earlier job
+            tokio::fs::remove_file(&tmp_path).await.ok();
+        } else {
+            self.storage.put_file(&storage_key, &tmp_path).await?;
+        }
+
+        queries::complete_export_job(
+            &self.pool,
+            job_id,
+            &storage_key,
+            &content_hash,
+            size_bytes,
+            row_count,
+        )
+        .await?;
+
+        tracing::info!(
+            export_id = %job_id,
+            content_hash = %content_hash,
+            size_bytes,
+            row_count,
+            "Export ready"
+        );
+        Ok(())
+    }
+
+    /// Direct download URL for a ready job, if the backend supports one
+    pub fn download_url(&self, job: &ExportJob) -> anyhow::Result<Option<String>> {
+        let Some(key) = job.storage_key.as_deref() else {
+            return Ok(None);
+        };
+        let ttl = (job.expires_at - self.clock.now())
+            .to_std()
+            .unwrap_or_default()
+            .min(std::time::Duration::from_secs(3600));
+        self.storage.download_url(key, ttl)
+    }
+
+    pub async fn open(&self, job: &ExportJob) -> Result<ByteStream, AppError> {
+        let key = job
+            .storage_key
+            .as_deref()
+            .ok_or_else(|| AppError::BadRequest(format!("Export {} is not ready", job.id)))?;
+        self.storage
+            .open(key)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to open export artifact: {}", e)))
+    }
+
+    /// Expire jobs past their retention and delete artifacts no longer
+    /// referenced by any ready job. Returns the number of expired jobs.
+    pub async fn sweep_expired(&self) -> anyhow::Result<usize> {
+        let expired = queries::expire_export_jobs(&self.pool, self.clock.now()).await?;
+
+        for job in &expired {
+            let Some(key) = job.storage_key.as_deref() else {
+                continue;
+            };
+            if queries::export_artifact_in_use(&self.pool, key).await? {
+                continue;
+            }
+            if let Err(e) = self.storage.delete(key).await {
+                tracing::error!(export_id = %job.id, storage_key = %key, "Failed to delete export artifact: {}", e);
+            }
+        }
+
+        if !expired.is_empty() {
+            tracing::info!("Expired {} export jobs", expired.len());
+        }
+        Ok(expired.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(format: &str) -> ExportQuery {
+        ExportQuery {
+            format: format.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_equivalent_params_share_a_hash() {
+        let a = ExportQuery {
+            format: "JSON".to_string(),
+            from: Some("2025-01-01".to_string()),
+            status: Some(" Completed ".to_string()),
+            asset_code: Some("usd".to_string()),
+            ..Default::default()
+        };
+        let b = ExportQuery {
+            format: "ndjson".to_string(),
+            from: Some("2025-01-01T00:00:00Z".to_string()),
+            status: Some("completed".to_string()),
+            asset_code: Some("USD".to_string()),
+            ..Default::default()
+        };
+
+        let a = NormalizedExportParams::from_query(&a).unwrap();
+        let b = NormalizedExportParams::from_query(&b).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.hash(), b.hash());
+    }
+
+    #[test]
+    fn test_different_params_have_different_hashes() {
+        let csv = NormalizedExportParams::from_query(&query("csv")).unwrap();
+        let ndjson = NormalizedExportParams::from_query(&query("ndjson")).unwrap();
+        assert_ne!(csv.hash(), ndjson.hash());
+    }
+
+    #[test]
+    fn test_empty_filters_are_ignored() {
+        let q = ExportQuery {
+            format: "csv".to_string(),
+            status: Some("   ".to_string()),
+            ..Default::default()
+        };
+        let params = NormalizedExportParams::from_query(&q).unwrap();
+        assert_eq!(params.status, None);
+    }
+
+    #[test]
+    fn test_invalid_format_and_dates_are_rejected() {
+        assert!(NormalizedExportParams::from_query(&query("xlsx")).is_err());
+
+        let q = ExportQuery {
+            format: "csv".to_string(),
+            to: Some("not-a-date".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            NormalizedExportParams::from_query(&q),
+            Err(AppError::Validation(_))
+        ));
+    }
+}
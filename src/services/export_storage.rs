@@ -0,0 +1,220 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{Stream, StreamExt};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::ExportStorageConfig;
+
+/// Stream of artifact bytes returned by a storage backend
+pub type ByteStream = Pin<Box<dyn Stream<Item = std::io::Result<Bytes>> + Send>>;
+
+/// Backend that holds generated export artifacts.
+///
+/// Artifacts are content-addressed: the key is derived from the sha256 of the
+/// file, so identical exports share one stored object.
+#[async_trait]
+pub trait ExportStorage: Send + Sync {
+    /// Upload a finished local file under `key`
+    async fn put_file(&self, key: &str, path: &Path) -> anyhow::Result<()>;
+
+    /// Whether an artifact already exists under `key`
+    async fn exists(&self, key: &str) -> anyhow::Result<bool>;
+
+    /// Stream the artifact stored under `key`
+    async fn open(&self, key: &str) -> anyhow::Result<ByteStream>;
+
+    /// Remove the artifact stored under `key`; missing artifacts are not an error
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+
+    /// A direct download URL valid for `ttl`, if the backend supports one.
+    /// Backends without direct URLs return `None` and are served through the API.
+    fn download_url(&self, key: &str, ttl: Duration) -> anyhow::Result<Option<String>>;
+}
+
+/// Build the configured storage backend
+pub fn from_config(config: &ExportStorageConfig) -> anyhow::Result<Arc<dyn ExportStorage>> {
+    match config {
+        ExportStorageConfig::Local { path } => Ok(Arc::new(LocalDiskStorage::new(path))),
+        ExportStorageConfig::S3 {
+            bucket,
+            region,
+            endpoint,
+            access_key,
+            secret_key,
+        } => Ok(Arc::new(S3Storage::new(
+            bucket,
+            region,
+            endpoint.as_deref(),
+            access_key.as_deref(),
+            secret_key.as_deref(),
+        )?)),
+    }
+}
+
+/// Stores artifacts as files in a directory on local disk
+pub struct LocalDiskStorage {
+    root: PathBuf,
+}
+
+impl LocalDiskStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        // Keys are generated internally (hex hash + extension), but never allow
+        // them to escape the storage root.
+        let file_name = Path::new(key)
+            .file_name()
+            .map(|name| name.to_owned())
+            .unwrap_or_default();
+        self.root.join(file_name)
+    }
+}
+
+#[async_trait]
+impl ExportStorage for LocalDiskStorage {
+    async fn put_file(&self, key: &str, path: &Path) -> anyhow::Result<()> {
+        tokio::fs::create_dir_all(&self.root).await?;
+        let target = self.path_for(key);
+        // rename is atomic on the same filesystem; fall back to copy across devices
+        if tokio::fs::rename(path, &target).await.is_err() {
+            tokio::fs::copy(path, &target).await?;
+            tokio::fs::remove_file(path).await.ok();
+        }
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        Ok(tokio::fs::try_exists(self.path_for(key)).await?)
+    }
+
+    async fn open(&self, key: &str) -> anyhow::Result<ByteStream> {
+        let file = tokio::fs::File::open(self.path_for(key)).await?;
+        Ok(Box::pin(tokio_util::io::ReaderStream::new(file)))
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        match tokio::fs::remove_file(self.path_for(key)).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn download_url(&self, _key: &str, _ttl: Duration) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+/// Stores artifacts in an S3-compatible bucket (AWS, MinIO, R2, ...)
+pub struct S3Storage {
+    bucket: s3::Bucket,
+}
+
+impl S3Storage {
+    pub fn new(
+        bucket: &str,
+        region: &str,
+        endpoint: Option<&str>,
+        access_key: Option<&str>,
+        secret_key: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let region = match endpoint {
+            Some(endpoint) => s3::Region::Custom {
+                region: region.to_string(),
+                endpoint: endpoint.to_string(),
+            },
+            None => region.parse()?,
+        };
+        let credentials = s3::creds::Credentials::new(access_key, secret_key, None, None, None)?;
+        let mut bucket = s3::Bucket::new(bucket, region, credentials)?;
+        if endpoint.is_some() {
+            // Most self-hosted S3 implementations only support path-style addressing
+            bucket = bucket.with_path_style();
+        }
+        Ok(Self { bucket })
+    }
+}
+
+#[async_trait]
+impl ExportStorage for S3Storage {
+    async fn put_file(&self, key: &str, path: &Path) -> anyhow::Result<()> {
+        let mut file = tokio::fs::File::open(path).await?;
+        self.bucket.put_object_stream(&mut file, key).await?;
+        tokio::fs::remove_file(path).await.ok();
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        match self.bucket.head_object(key).await {
+            Ok((_, code)) => Ok(code == 200),
+            Err(s3::error::S3Error::HttpFailWithBody(404, _)) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn open(&self, key: &str) -> anyhow::Result<ByteStream> {
+        let response = self.bucket.get_object_stream(key).await?;
+        Ok(Box::pin(response.bytes.map(Ok::<Bytes, std::io::Error>)))
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.bucket.delete_object(key).await?;
+        Ok(())
+    }
+
+    fn download_url(&self, key: &str, ttl: Duration) -> anyhow::Result<Option<String>> {
+        let url = self.bucket.presign_get(key, ttl.as_secs() as u32, None)?;
+        Ok(Some(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_storage_roundtrip() {
+        let root = std::env::temp_dir().join(format!("synapse-export-test-{}", uuid::Uuid::new_v4()));
+        let storage = LocalDiskStorage::new(&root);
+
+        let source = std::env::temp_dir().join(format!("synapse-export-src-{}", uuid::Uuid::new_v4()));
+        tokio::fs::write(&source, b"id,amount\n1,10\n").await.unwrap();
+
+        storage.put_file("abc.csv", &source).await.unwrap();
+        assert!(storage.exists("abc.csv").await.unwrap());
+
+        let mut stream = storage.open("abc.csv").await.unwrap();
+        let mut contents = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            contents.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(contents, b"id,amount\n1,10\n");
+
+        storage.delete("abc.csv").await.unwrap();
+        assert!(!storage.exists("abc.csv").await.unwrap());
+        // Deleting twice is fine
+        storage.delete("abc.csv").await.unwrap();
+
+        tokio::fs::remove_dir_all(&root).await.ok();
+    }
+
+    #[test]
+    fn test_local_storage_keys_cannot_escape_root() {
+        let storage = LocalDiskStorage::new("/tmp/exports");
+        assert_eq!(storage.path_for("../../etc/passwd"), PathBuf::from("/tmp/exports/passwd"));
+    }
+
+    #[test]
+    fn test_local_storage_has_no_direct_url() {
+        let storage = LocalDiskStorage::new("/tmp/exports");
+        assert!(storage
+            .download_url("abc.csv", Duration::from_secs(60))
+            .unwrap()
+            .is_none());
+    }
+}
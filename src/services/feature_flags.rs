@@ -1,103 +1,914 @@
 use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use redis::aio::MultiplexedConnection;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sha2::{Digest, Sha256};
+use sqlx::types::Json;
+use sqlx::{FromRow, PgConnection, PgPool, Row};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::db::audit::{named_entity_id, AuditLog, ENTITY_FEATURE_FLAG};
+use crate::metrics;
+use crate::utils::diff::{diff, Diff};
+use crate::utils::shutdown::Shutdown;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct FeatureFlag {
     pub name: String,
     pub enabled: bool,
     pub description: Option<String>,
     pub updated_at: DateTime<Utc>,
+    /// Share of subjects the flag is on for while `enabled` is off, 0-100
+    pub rollout_percentage: i16,
+    /// Accounts the flag is on for while `enabled` is off
+    pub allowed_accounts: Vec<String>,
+    /// Attribute rules the flag is on for while `enabled` is off
+    pub targeting: Json<Vec<TargetingRule>>,
+}
+
+impl FeatureFlag {
+    pub fn rule(&self) -> FlagRule {
+        FlagRule {
+            enabled: self.enabled,
+            rollout_percentage: self.rollout_percentage,
+            allowed_accounts: self.allowed_accounts.clone(),
+            targeting: self.targeting.0.clone(),
+        }
+    }
+}
+
+/// Columns of a [`FeatureFlag`]
+const FLAG_COLUMNS: &str =
+    "name, enabled, description, updated_at, rollout_percentage, allowed_accounts, targeting";
+
+/// The asset a flag is evaluated for, e.g. `USDC`
+pub const ATTRIBUTE_ASSET_CODE: &str = "asset_code";
+
+/// Attributes a [`TargetingRule`] may match on
+pub const TARGETING_ATTRIBUTES: &[&str] = &[ATTRIBUTE_ASSET_CODE];
+
+/// On for subjects whose `attribute` is one of `values`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetingRule {
+    pub attribute: String,
+    pub values: Vec<String>,
+}
+
+impl TargetingRule {
+    pub fn matches(&self, context: &FlagContext) -> bool {
+        context
+            .attribute(&self.attribute)
+            .is_some_and(|value| self.values.iter().any(|v| v == value))
+    }
+}
+
+/// What a flag is evaluated for: the subject, bucketed for rollouts and
+/// matched against the allowlist, and attributes for targeting rules
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlagContext {
+    /// Usually a Stellar account
+    pub subject: String,
+    pub attributes: HashMap<String, String>,
+}
+
+impl FlagContext {
+    pub fn new(subject: impl Into<String>) -> Self {
+        Self {
+            subject: subject.into(),
+            attributes: HashMap::new(),
+        }
+    }
+
+    pub fn with(mut self, attribute: &str, value: impl Into<String>) -> Self {
+        self.attributes.insert(attribute.to_string(), value.into());
+        self
+    }
+
+    pub fn with_asset_code(self, asset_code: impl Into<String>) -> Self {
+        self.with(ATTRIBUTE_ASSET_CODE, asset_code)
+    }
+
+    pub fn attribute(&self, attribute: &str) -> Option<&str> {
+        self.attributes.get(attribute).map(String::as_str)
+    }
+}
+
+impl From<&str> for FlagContext {
+    fn from(subject: &str) -> Self {
+        Self::new(subject)
+    }
+}
+
+impl From<&String> for FlagContext {
+    fn from(subject: &String) -> Self {
+        Self::new(subject.as_str())
+    }
+}
+
+impl From<&FlagContext> for FlagContext {
+    fn from(context: &FlagContext) -> Self {
+        context.clone()
+    }
+}
+
+/// Fields of a flag to change; `None` leaves the field as it is
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlagUpdate {
+    pub enabled: Option<bool>,
+    pub rollout_percentage: Option<i16>,
+    pub allowed_accounts: Option<Vec<String>>,
+    pub targeting: Option<Vec<TargetingRule>>,
+}
+
+/// One change to a flag, as recorded in `feature_flag_audit`
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct FlagChange {
+    pub id: i64,
+    pub flag_name: String,
+    pub actor: String,
+    /// The whole flag before and after the change
+    pub before: serde_json::Value,
+    pub after: serde_json::Value,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// What decides a flag for a subject, as cached
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FlagRule {
+    /// On for every subject
+    pub enabled: bool,
+    pub rollout_percentage: i16,
+    pub allowed_accounts: Vec<String>,
+    pub targeting: Vec<TargetingRule>,
+}
+
+impl From<bool> for FlagRule {
+    fn from(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Default::default()
+        }
+    }
+}
+
+/// Why a flag is on or off for a subject
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvaluationReason {
+    GloballyOn,
+    Allowlisted,
+    /// Matched a targeting rule
+    Targeted,
+    BucketedIn,
+    BucketedOut,
+    /// Not in the cache; off
+    UnknownFlag,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Evaluation {
+    pub enabled: bool,
+    pub reason: EvaluationReason,
+}
+
+/// The subject's bucket for `flag`, 0-99. The hash covers the flag name, so
+/// each flag rolls out to a different set of subjects.
+pub fn rollout_bucket(flag: &str, subject: &str) -> u8 {
+    let digest = Sha256::digest(format!("{}:{}", flag, subject).as_bytes());
+    let mut head = [0u8; 8];
+    head.copy_from_slice(&digest[..8]);
+    (u64::from_be_bytes(head) % 100) as u8
+}
+
+impl FlagRule {
+    /// On when the flag is on for everyone, lists the subject, has a
+    /// targeting rule matching the context, or the subject's bucket is below
+    /// the rollout percentage. Raising the percentage only ever adds
+    /// subjects.
+    pub fn evaluate(&self, flag: &str, context: impl Into<FlagContext>) -> Evaluation {
+        let context = context.into();
+        let subject = context.subject.as_str();
+        let (enabled, reason) = if self.enabled {
+            (true, EvaluationReason::GloballyOn)
+        } else if self.allowed_accounts.iter().any(|account| account == subject) {
+            (true, EvaluationReason::Allowlisted)
+        } else if self.targeting.iter().any(|rule| rule.matches(&context)) {
+            (true, EvaluationReason::Targeted)
+        } else if i16::from(rollout_bucket(flag, subject)) < self.rollout_percentage {
+            (true, EvaluationReason::BucketedIn)
+        } else {
+            (false, EvaluationReason::BucketedOut)
+        };
+        Evaluation { enabled, reason }
+    }
+}
+
+/// Every flag as of one generation of the `feature_flags` table
+#[derive(Debug, Clone)]
+pub struct FlagSnapshot {
+    pub generation: i64,
+    pub flags: HashMap<String, (FlagRule, i64)>,
+}
+
+/// Flags changed after generation `since`, read together with the
+/// generation they are current as of
+#[derive(Debug, Clone)]
+pub struct FlagChanges {
+    pub since: i64,
+    pub generation: i64,
+    pub flags: HashMap<String, (FlagRule, i64)>,
+    /// Flags deleted after `since`, with the generation of the deletion
+    pub deleted: HashMap<String, i64>,
+}
+
+/// Scheduled refreshes between two full reloads, unless configured
+pub const DEFAULT_FULL_REFRESH_EVERY: u64 = 20;
+
+/// Redis channel flag changes are announced on, unless configured
+pub const DEFAULT_INVALIDATION_CHANNEL: &str = "synapse:feature_flags:invalidate";
+
+/// Wait before resubscribing after the invalidation subscription drops
+const INVALIDATION_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Longest a flag update waits to announce itself. A publish that times out
+/// is spread by the scheduled refresh instead.
+const PUBLISH_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Published after a flag changes, so other instances refresh without
+/// waiting for the schedule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlagInvalidation {
+    pub flag: String,
+    /// Generation the change was committed at
+    pub generation: i64,
+}
+
+#[derive(Clone)]
+struct Invalidation {
+    client: redis::Client,
+    channel: String,
+    /// Publishing connection, opened on first use and reopened after an error
+    publisher: Arc<Mutex<Option<MultiplexedConnection>>>,
+}
+
+impl Invalidation {
+    async fn publish(&self, payload: &str) -> anyhow::Result<()> {
+        let cached = self.publisher.lock().await.clone();
+        let mut conn = match cached {
+            Some(conn) => conn,
+            None => {
+                let conn = self.client.get_multiplexed_async_connection().await?;
+                *self.publisher.lock().await = Some(conn.clone());
+                conn
+            }
+        };
+        let result = redis::cmd("PUBLISH")
+            .arg(&self.channel)
+            .arg(payload)
+            .query_async::<_, i64>(&mut conn)
+            .await;
+        if result.is_err() {
+            *self.publisher.lock().await = None;
+        }
+        result?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefreshMode {
+    /// Every flag; also drops flags deleted from the table
+    Full,
+    /// Only flags changed since the last refresh
+    Incremental,
+}
+
+impl RefreshMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RefreshMode::Full => "full",
+            RefreshMode::Incremental => "incremental",
+        }
+    }
+}
+
+/// What one cache refresh read and applied
+#[derive(Debug, Clone, Serialize)]
+pub struct RefreshReport {
+    pub mode: RefreshMode,
+    /// Flag rows fetched from the database
+    pub rows: usize,
+    /// Flags written into the cache; fewer than `rows` when newer values
+    /// were already cached
+    pub applied: usize,
+    pub generation: i64,
+}
+
+/// Flag values plus the generation each was read at.
+///
+/// Writers race: a slow full refresh can finish after a single-flag update
+/// that happened later. Comparing generations keeps the newer value.
+#[derive(Debug, Default)]
+pub struct FlagCache {
+    generation: i64,
+    /// Generation of the last refresh read from the database. Incremental
+    /// refreshes fetch changes after it. Unlike `generation`, local updates
+    /// don't move it: another instance may have committed an earlier
+    /// generation this cache hasn't read yet.
+    watermark: i64,
+    flags: HashMap<String, (FlagRule, i64)>,
+}
+
+impl FlagCache {
+    pub fn generation(&self) -> i64 {
+        self.generation
+    }
+
+    pub fn watermark(&self) -> i64 {
+        self.watermark
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        self.rule(name).map(|rule| rule.enabled)
+    }
+
+    pub fn rule(&self, name: &str) -> Option<&FlagRule> {
+        self.flags.get(name).map(|(rule, _)| rule)
+    }
+
+    /// Whether an invalidation announces a change this cache hasn't applied.
+    /// An instance's own updates are already cached when it hears them.
+    pub fn is_stale(&self, invalidation: &FlagInvalidation) -> bool {
+        match self.flags.get(&invalidation.flag) {
+            Some((_, generation)) => *generation < invalidation.generation,
+            None => true,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.flags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty()
+    }
+
+    /// Swap in a full refresh unless something newer was applied since it was
+    /// read. Returns whether the snapshot was applied.
+    pub fn apply_snapshot(&mut self, snapshot: FlagSnapshot) -> bool {
+        if snapshot.generation < self.generation {
+            tracing::debug!(
+                snapshot_generation = snapshot.generation,
+                cached_generation = self.generation,
+                "Discarding stale feature flag refresh"
+            );
+            return false;
+        }
+        self.generation = snapshot.generation;
+        self.watermark = snapshot.generation;
+        self.flags = snapshot.flags;
+        true
+    }
+
+    /// Merge the flags changed or deleted since a watermark, keeping any
+    /// newer cached value. Returns the number of changes applied.
+    pub fn apply_changes(&mut self, changes: FlagChanges) -> usize {
+        let mut applied = 0;
+        for (name, (rule, generation)) in changes.flags {
+            if self.apply_flag(&name, rule, generation) {
+                applied += 1;
+            }
+        }
+        // After the upserts: a flag deleted and created again keeps the
+        // newer row
+        for (name, generation) in changes.deleted {
+            if self.remove_flag(&name, generation) {
+                applied += 1;
+            }
+        }
+        self.watermark = self.watermark.max(changes.generation);
+        self.generation = self.generation.max(changes.generation);
+        applied
+    }
+
+    /// Apply a single flag change unless the cached value is newer. Returns
+    /// whether the change was applied.
+    pub fn apply_flag(&mut self, name: &str, rule: impl Into<FlagRule>, generation: i64) -> bool {
+        if let Some((_, cached)) = self.flags.get(name) {
+            if generation < *cached {
+                tracing::debug!(
+                    flag = name,
+                    generation,
+                    cached_generation = *cached,
+                    "Discarding stale feature flag update"
+                );
+                return false;
+            }
+        }
+        self.flags.insert(name.to_string(), (rule.into(), generation));
+        self.generation = self.generation.max(generation);
+        true
+    }
+
+    /// Drop a flag deleted at `generation` unless the cached value is newer.
+    /// Returns whether a cached flag was removed.
+    pub fn remove_flag(&mut self, name: &str, generation: i64) -> bool {
+        self.generation = self.generation.max(generation);
+        match self.flags.get(name) {
+            Some((_, cached)) if *cached < generation => {
+                self.flags.remove(name);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The generation counter, joined with a flag row or a tombstone when there
+/// is one
+#[derive(FromRow)]
+struct FlagGenerationRow {
+    generation: i64,
+    name: Option<String>,
+    deleted: Option<bool>,
+    enabled: Option<bool>,
+    rollout_percentage: Option<i16>,
+    allowed_accounts: Option<Vec<String>>,
+    targeting: Option<Json<Vec<TargetingRule>>>,
+    flag_generation: Option<i64>,
+}
+
+impl FlagGenerationRow {
+    fn into_flag(self) -> Option<(String, (FlagRule, i64))> {
+        let rule = FlagRule {
+            enabled: self.enabled?,
+            rollout_percentage: self.rollout_percentage?,
+            allowed_accounts: self.allowed_accounts?,
+            targeting: self.targeting?.0,
+        };
+        Some((self.name?, (rule, self.flag_generation?)))
+    }
+
+    fn into_deletion(self) -> Option<(String, i64)> {
+        match self.deleted {
+            Some(true) => Some((self.name?, self.flag_generation?)),
+            _ => None,
+        }
+    }
+}
+
+/// Record a flag change in `feature_flag_audit`
+async fn record_change(
+    conn: &mut PgConnection,
+    before: &FeatureFlag,
+    after: &FeatureFlag,
+    actor: &str,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        "INSERT INTO feature_flag_audit (flag_name, actor, before, after)
+         VALUES ($1, $2, $3, $4)",
+    )
+    .bind(&after.name)
+    .bind(actor)
+    .bind(Json(before))
+    .bind(Json(after))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Whether the `count`th scheduled refresh (from 0) reloads every flag
+fn full_refresh_due(count: u64, full_refresh_every: u64) -> bool {
+    count % full_refresh_every.max(1) == 0
 }
 
 #[derive(Clone)]
 pub struct FeatureFlagService {
     pool: PgPool,
-    cache: Arc<RwLock<HashMap<String, bool>>>,
+    cache: Arc<RwLock<FlagCache>>,
+    full_refresh_every: u64,
+    refreshes: Arc<AtomicU64>,
+    /// Unix seconds of the last successful refresh; 0 before the first
+    last_refreshed: Arc<AtomicI64>,
+    invalidation: Option<Invalidation>,
+    shutdown: Shutdown,
 }
 
 impl FeatureFlagService {
     pub fn new(pool: PgPool) -> Self {
         Self {
             pool,
-            cache: Arc::new(RwLock::new(HashMap::new())),
+            cache: Arc::new(RwLock::new(FlagCache::default())),
+            full_refresh_every: DEFAULT_FULL_REFRESH_EVERY,
+            refreshes: Arc::new(AtomicU64::new(0)),
+            last_refreshed: Arc::new(AtomicI64::new(0)),
+            invalidation: None,
+            shutdown: Shutdown::new(),
         }
     }
 
-    pub fn start(&self, refresh_interval_hours: u64) {
+    /// Announce each update on a Redis channel, and refresh when another
+    /// instance announces one; see [`Self::start_invalidation_listener`]
+    pub fn with_invalidation(mut self, client: redis::Client, channel: impl Into<String>) -> Self {
+        self.invalidation = Some(Invalidation {
+            client,
+            channel: channel.into(),
+            publisher: Arc::new(Mutex::new(None)),
+        });
+        self
+    }
+
+    /// Stop the refresher and the invalidation listener between refreshes
+    /// once `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Reload every flag on every `n`th scheduled refresh; the others only
+    /// fetch changes. `1` makes every refresh a full one.
+    pub fn with_full_refresh_every(mut self, n: u64) -> Self {
+        self.full_refresh_every = n.max(1);
+        self
+    }
+
+    pub fn start(&self, refresh_interval: Duration) {
         let service = self.clone();
-        tokio::spawn(async move {
+        self.shutdown.spawn(async move {
+            let mut interval = tokio::time::interval(refresh_interval);
             loop {
-                if let Err(e) = service.refresh_cache().await {
+                tokio::select! {
+                    _ = service.shutdown.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+                let result = service.refresh_cache().await;
+                metrics::record_task_heartbeat("feature_flag_refresher", result.is_ok());
+                if let Err(e) = result {
                     tracing::error!("Failed to refresh feature flags cache: {}", e);
                 }
-                tokio::time::sleep(tokio::time::Duration::from_secs(
-                    refresh_interval_hours * 3600,
-                ))
-                .await;
             }
+            tracing::info!("Feature flag refresher stopped");
+        });
+    }
+
+    /// Subscribe to the invalidation channel and fetch the changes each
+    /// message announces. The scheduled refresh keeps running, so a lost
+    /// message or a Redis outage only delays a change until then. Does nothing
+    /// without [`Self::with_invalidation`].
+    pub fn start_invalidation_listener(&self) {
+        let Some(invalidation) = self.invalidation.clone() else {
+            return;
+        };
+        let service = self.clone();
+        self.shutdown.spawn(async move {
+            loop {
+                if let Err(e) = service.listen(&invalidation).await {
+                    tracing::warn!(
+                        channel = %invalidation.channel,
+                        "Feature flag invalidation subscription lost: {}",
+                        e
+                    );
+                }
+                tokio::select! {
+                    _ = service.shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(INVALIDATION_RECONNECT_DELAY) => {}
+                }
+            }
+            tracing::info!("Feature flag invalidation listener stopped");
         });
     }
 
-    pub async fn refresh_cache(&self) -> anyhow::Result<()> {
-        let flags = sqlx::query_as::<_, (String, bool)>("SELECT name, enabled FROM feature_flags")
-            .fetch_all(&self.pool)
-            .await?;
+    /// Run one subscription until the connection drops, or until shutdown
+    async fn listen(&self, invalidation: &Invalidation) -> anyhow::Result<()> {
+        let mut pubsub = invalidation.client.get_async_connection().await?.into_pubsub();
+        pubsub.subscribe(&invalidation.channel).await?;
+        tracing::info!(channel = %invalidation.channel, "Subscribed to feature flag invalidations");
+
+        // Messages published while unsubscribed are gone; catch up on them
+        self.refresh_changes().await?;
 
-        let mut cache = self.cache.write().await;
-        cache.clear();
-        for (name, enabled) in flags {
-            cache.insert(name, enabled);
+        let mut messages = pubsub.on_message();
+        loop {
+            let message = tokio::select! {
+                _ = self.shutdown.cancelled() => return Ok(()),
+                message = messages.next() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+            };
+            let payload: String = message.get_payload()?;
+            // An unreadable message still means something changed
+            let stale = match serde_json::from_str::<FlagInvalidation>(&payload) {
+                Ok(change) => self.cache.read().await.is_stale(&change),
+                Err(e) => {
+                    tracing::debug!("Unreadable feature flag invalidation {:?}: {}", payload, e);
+                    true
+                }
+            };
+            if !stale {
+                metrics::record_flag_invalidation("ignored");
+                continue;
+            }
+            metrics::record_flag_invalidation("received");
+            if let Err(e) = self.refresh_changes().await {
+                tracing::error!("Failed to refresh feature flags after invalidation: {}", e);
+            }
         }
-        tracing::info!("Feature flags cache refreshed with {} flags", cache.len());
-        Ok(())
+
+        anyhow::bail!("subscription closed")
     }
 
-    pub async fn is_enabled(&self, name: &str) -> bool {
-        self.cache.read().await.get(name).copied().unwrap_or(false)
+    /// Tell the other instances a flag changed, waiting at most
+    /// [`PUBLISH_TIMEOUT`]. A failure is logged rather than returned: the
+    /// change is committed, and the scheduled refresh still spreads it.
+    async fn publish_invalidation(&self, name: &str, generation: i64) {
+        let Some(invalidation) = &self.invalidation else {
+            return;
+        };
+        let message = FlagInvalidation {
+            flag: name.to_string(),
+            generation,
+        };
+        let result = async {
+            let payload = serde_json::to_string(&message)?;
+            tokio::time::timeout(PUBLISH_TIMEOUT, invalidation.publish(&payload))
+                .await
+                .map_err(|_| anyhow::anyhow!("timed out after {:?}", PUBLISH_TIMEOUT))?
+        }
+        .await;
+        match result {
+            Ok(()) => metrics::record_flag_invalidation("published"),
+            Err(e) => {
+                metrics::record_flag_invalidation("publish_failed");
+                tracing::warn!(flag = name, "Failed to publish feature flag invalidation: {}", e);
+            }
+        }
     }
 
-    pub async fn get_all(&self) -> anyhow::Result<Vec<FeatureFlag>> {
-        let flags = sqlx::query_as::<_, (String, bool, Option<String>, DateTime<Utc>)>(
-            "SELECT name, enabled, description, updated_at FROM feature_flags ORDER BY name",
+    /// Scheduled refresh. The first and every `full_refresh_every`th one
+    /// reload every flag; the rest only fetch flags changed or deleted since
+    /// the last refresh.
+    pub async fn refresh_cache(&self) -> anyhow::Result<RefreshReport> {
+        let count = self.refreshes.fetch_add(1, Ordering::Relaxed);
+        if full_refresh_due(count, self.full_refresh_every) {
+            self.refresh_full().await
+        } else {
+            self.refresh_changes().await
+        }
+    }
+
+    /// Reload every flag, dropping any no longer in the table
+    pub async fn refresh_full(&self) -> anyhow::Result<RefreshReport> {
+        let snapshot = self.load_snapshot().await?;
+        let rows = snapshot.flags.len();
+        let generation = snapshot.generation;
+        let applied = self.apply_snapshot(snapshot).await;
+        metrics::record_flag_refresh(RefreshMode::Full.as_str(), rows);
+        self.mark_refreshed();
+        if applied {
+            tracing::info!("Feature flags cache refreshed with {} flags", rows);
+        }
+        Ok(RefreshReport {
+            mode: RefreshMode::Full,
+            rows,
+            applied: if applied { rows } else { 0 },
+            generation,
+        })
+    }
+
+    /// Fetch and merge only the flags changed since the cache's watermark
+    pub async fn refresh_changes(&self) -> anyhow::Result<RefreshReport> {
+        let since = self.cache.read().await.watermark();
+        let changes = self.load_changes(since).await?;
+        let rows = changes.flags.len() + changes.deleted.len();
+        let generation = changes.generation;
+        let applied = self.apply_changes(changes).await;
+        metrics::record_flag_refresh(RefreshMode::Incremental.as_str(), rows);
+        self.mark_refreshed();
+        if rows > 0 {
+            tracing::info!(since, generation, rows, applied, "Feature flags cache updated");
+        }
+        Ok(RefreshReport {
+            mode: RefreshMode::Incremental,
+            rows,
+            applied,
+            generation,
+        })
+    }
+
+    /// Read every flag and the current generation in one statement
+    pub async fn load_snapshot(&self) -> anyhow::Result<FlagSnapshot> {
+        // LEFT JOIN so an empty flags table still yields the generation row
+        let rows = sqlx::query_as::<_, FlagGenerationRow>(
+            "SELECT g.generation, f.name, false AS deleted, f.enabled, f.rollout_percentage,
+                    f.allowed_accounts, f.targeting, f.generation AS flag_generation
+             FROM flag_generations g LEFT JOIN feature_flags f ON true",
         )
         .fetch_all(&self.pool)
-        .await?
-        .into_iter()
-        .map(|(name, enabled, description, updated_at)| FeatureFlag {
-            name,
-            enabled,
-            description,
-            updated_at,
+        .await?;
+
+        Ok(FlagSnapshot {
+            generation: rows.first().map(|row| row.generation).unwrap_or(0),
+            flags: rows.into_iter().filter_map(FlagGenerationRow::into_flag).collect(),
+        })
+    }
+
+    /// Read the flags changed or deleted after generation `since`, and the
+    /// current generation, in one statement.
+    ///
+    /// The watermark is the database's own generation counter, read in the
+    /// same snapshot as the rows, so neither clock skew nor a transaction
+    /// committing mid-read can hide a change: an uncommitted change holds a
+    /// generation above the one read here and is fetched next time.
+    pub async fn load_changes(&self, since: i64) -> anyhow::Result<FlagChanges> {
+        let rows = sqlx::query_as::<_, FlagGenerationRow>(
+            "SELECT g.generation, f.name, f.deleted, f.enabled, f.rollout_percentage,
+                    f.allowed_accounts, f.targeting, f.generation AS flag_generation
+             FROM flag_generations g LEFT JOIN (
+                 SELECT name, false AS deleted, enabled, rollout_percentage, allowed_accounts,
+                        targeting, generation
+                 FROM feature_flags
+                 UNION ALL
+                 SELECT name, true, NULL, NULL, NULL, NULL, generation
+                 FROM feature_flag_tombstones
+             ) f ON f.generation > $1",
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let generation = rows.first().map(|row| row.generation).unwrap_or(since);
+        let (deleted, flags): (Vec<_>, Vec<_>) =
+            rows.into_iter().partition(|row| row.deleted == Some(true));
+        Ok(FlagChanges {
+            since,
+            generation,
+            flags: flags.into_iter().filter_map(FlagGenerationRow::into_flag).collect(),
+            deleted: deleted.into_iter().filter_map(FlagGenerationRow::into_deletion).collect(),
         })
-        .collect();
+    }
+
+    fn mark_refreshed(&self) {
+        self.last_refreshed.store(Utc::now().timestamp(), Ordering::Relaxed);
+    }
+
+    /// When the cache last refreshed successfully
+    pub fn last_refreshed_at(&self) -> Option<DateTime<Utc>> {
+        match self.last_refreshed.load(Ordering::Relaxed) {
+            0 => None,
+            secs => DateTime::from_timestamp(secs, 0),
+        }
+    }
+
+    /// Flags currently in the cache
+    pub async fn cached_flags(&self) -> usize {
+        self.cache.read().await.len()
+    }
+
+    pub async fn apply_snapshot(&self, snapshot: FlagSnapshot) -> bool {
+        self.cache.write().await.apply_snapshot(snapshot)
+    }
+
+    pub async fn apply_changes(&self, changes: FlagChanges) -> usize {
+        self.cache.write().await.apply_changes(changes)
+    }
+
+    /// Per-flag invalidation: apply one flag value read at `generation`
+    pub async fn apply_flag(&self, name: &str, rule: impl Into<FlagRule>, generation: i64) -> bool {
+        self.cache.write().await.apply_flag(name, rule, generation)
+    }
+
+    /// Whether the flag is on for everyone; rollouts and allowlists are
+    /// only read by [`Self::is_enabled_for`]
+    pub async fn is_enabled(&self, name: &str) -> bool {
+        self.cache.read().await.get(name).unwrap_or(false)
+    }
+
+    /// Whether the flag is on for a subject, usually a Stellar account, or a
+    /// [`FlagContext`] that also carries attributes such as the asset code.
+    /// The same context gets the same answer until the flag changes.
+    pub async fn is_enabled_for(&self, name: &str, context: impl Into<FlagContext>) -> bool {
+        self.evaluate(name, context).await.enabled
+    }
+
+    /// [`Self::is_enabled_for`], with the reason
+    pub async fn evaluate(&self, name: &str, context: impl Into<FlagContext>) -> Evaluation {
+        match self.cache.read().await.rule(name) {
+            Some(rule) => rule.evaluate(name, context),
+            None => Evaluation {
+                enabled: false,
+                reason: EvaluationReason::UnknownFlag,
+            },
+        }
+    }
+
+    pub async fn get_all(&self) -> anyhow::Result<Vec<FeatureFlag>> {
+        let flags = sqlx::query_as::<_, FeatureFlag>(&format!(
+            "SELECT {} FROM feature_flags ORDER BY name",
+            FLAG_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
 
         Ok(flags)
     }
 
-    pub async fn update(&self, name: &str, enabled: bool) -> anyhow::Result<FeatureFlag> {
-        let flag = sqlx::query_as::<_, (String, bool, Option<String>, DateTime<Utc>)>(
-            "UPDATE feature_flags SET enabled = $1, updated_at = NOW() WHERE name = $2 
-             RETURNING name, enabled, description, updated_at",
-        )
-        .bind(enabled)
+    /// Set a flag and audit the change. Returns the updated flag and the
+    /// fields that changed.
+    pub async fn update(
+        &self,
+        name: &str,
+        enabled: bool,
+        actor: &str,
+    ) -> anyhow::Result<(FeatureFlag, Diff)> {
+        let update = FlagUpdate {
+            enabled: Some(enabled),
+            ..Default::default()
+        };
+        self.update_with(name, &update, actor).await
+    }
+
+    /// Change the given fields of a flag and audit the change. Returns the
+    /// updated flag and the fields that changed.
+    pub async fn update_with(
+        &self,
+        name: &str,
+        update: &FlagUpdate,
+        actor: &str,
+    ) -> anyhow::Result<(FeatureFlag, Diff)> {
+        let mut tx = self.pool.begin().await?;
+
+        let before = sqlx::query_as::<_, FeatureFlag>(&format!(
+            "SELECT {} FROM feature_flags WHERE name = $1 FOR UPDATE",
+            FLAG_COLUMNS
+        ))
         .bind(name)
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
-        // Update cache
-        self.cache.write().await.insert(name.to_string(), enabled);
-        tracing::info!("Feature flag '{}' updated to {}", name, enabled);
+        let row = sqlx::query(&format!(
+            "UPDATE feature_flags SET
+                 enabled = COALESCE($1, enabled),
+                 rollout_percentage = COALESCE($2, rollout_percentage),
+                 allowed_accounts = COALESCE($3, allowed_accounts),
+                 targeting = COALESCE($4, targeting),
+                 updated_at = NOW()
+             WHERE name = $5
+             RETURNING {}, generation",
+            FLAG_COLUMNS
+        ))
+        .bind(update.enabled)
+        .bind(update.rollout_percentage)
+        .bind(update.allowed_accounts.as_deref())
+        .bind(update.targeting.as_ref().map(Json))
+        .bind(name)
+        .fetch_one(&mut *tx)
+        .await?;
+        let generation: i64 = row.try_get("generation")?;
+        let after = FeatureFlag::from_row(&row)?;
 
-        Ok(FeatureFlag {
-            name: flag.0,
-            enabled: flag.1,
-            description: flag.2,
-            updated_at: flag.3,
-        })
+        let changes = diff(&before, &after)?.ignoring(&["updated_at"]);
+        AuditLog::log_diff(
+            &mut *tx,
+            named_entity_id(ENTITY_FEATURE_FLAG, name),
+            ENTITY_FEATURE_FLAG,
+            &changes,
+            actor,
+        )
+        .await?;
+        if !changes.is_empty() {
+            record_change(&mut *tx, &before, &after, actor).await?;
+        }
+        tx.commit().await?;
+
+        // Update cache, then the other instances'
+        self.apply_flag(name, after.rule(), generation).await;
+        self.publish_invalidation(name, generation).await;
+        tracing::info!(flag = name, actor, changes = %changes.to_json(), "Feature flag updated");
+
+        Ok((after, changes))
+    }
+
+    /// The latest changes to a flag, newest first
+    pub async fn history(&self, name: &str, limit: i64) -> anyhow::Result<Vec<FlagChange>> {
+        let changes = sqlx::query_as::<_, FlagChange>(
+            "SELECT id, flag_name, actor, before, after, changed_at
+             FROM feature_flag_audit
+             WHERE flag_name = $1
+             ORDER BY changed_at DESC, id DESC
+             LIMIT $2",
+        )
+        .bind(name)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(changes)
     }
 }
 
@@ -112,8 +923,301 @@ mod tests {
             enabled: true,
             description: Some("Test flag".to_string()),
             updated_at: Utc::now(),
+            rollout_percentage: 0,
+            allowed_accounts: Vec::new(),
+            targeting: Json(Vec::new()),
         };
         assert_eq!(flag.name, "test_flag");
         assert!(flag.enabled);
     }
+
+    fn snapshot(generation: i64, flags: &[(&str, bool, i64)]) -> FlagSnapshot {
+        FlagSnapshot {
+            generation,
+            flags: flags
+                .iter()
+                .map(|(name, enabled, g)| (name.to_string(), (FlagRule::from(*enabled), *g)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_slow_refresh_does_not_overwrite_newer_flag_update() {
+        let mut cache = FlagCache::default();
+        assert!(cache.apply_snapshot(snapshot(3, &[("a", false, 2), ("b", false, 3)])));
+
+        // A full refresh reads generation 4...
+        let slow_refresh = snapshot(4, &[("a", false, 2), ("b", true, 4)]);
+        // ...then "a" is flipped at generation 5 and applied first
+        assert!(cache.apply_flag("a", true, 5));
+        assert!(!cache.apply_snapshot(slow_refresh));
+
+        assert_eq!(cache.get("a"), Some(true));
+        assert_eq!(cache.generation(), 5);
+    }
+
+    #[test]
+    fn test_newer_refresh_replaces_cache() {
+        let mut cache = FlagCache::default();
+        assert!(cache.apply_flag("a", true, 5));
+        assert!(cache.apply_snapshot(snapshot(6, &[("a", false, 6)])));
+        assert_eq!(cache.get("a"), Some(false));
+        assert_eq!(cache.generation(), 6);
+    }
+
+    #[test]
+    fn test_refresh_at_same_generation_applies() {
+        let mut cache = FlagCache::default();
+        assert!(cache.apply_snapshot(snapshot(2, &[("a", true, 2)])));
+        assert!(cache.apply_snapshot(snapshot(2, &[("a", true, 2)])));
+    }
+
+    fn changes(since: i64, generation: i64, flags: &[(&str, bool, i64)]) -> FlagChanges {
+        let snapshot = snapshot(generation, flags);
+        FlagChanges {
+            since,
+            generation,
+            flags: snapshot.flags,
+            deleted: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_changes_merge_into_cache_and_move_watermark() {
+        let mut cache = FlagCache::default();
+        assert!(cache.apply_snapshot(snapshot(3, &[("a", false, 2), ("b", false, 3)])));
+        assert_eq!(cache.watermark(), 3);
+
+        assert_eq!(cache.apply_changes(changes(3, 5, &[("b", true, 5)])), 1);
+        assert_eq!(cache.get("a"), Some(false));
+        assert_eq!(cache.get("b"), Some(true));
+        assert_eq!(cache.watermark(), 5);
+        assert_eq!(cache.generation(), 5);
+
+        // Nothing changed: the watermark still follows the database
+        assert_eq!(cache.apply_changes(changes(5, 5, &[])), 0);
+        assert_eq!(cache.watermark(), 5);
+    }
+
+    #[test]
+    fn test_local_update_does_not_move_watermark() {
+        let mut cache = FlagCache::default();
+        assert!(cache.apply_snapshot(snapshot(3, &[("a", false, 2), ("b", false, 3)])));
+
+        // This instance updates "a" at 5 while another has committed "b" at 4
+        assert!(cache.apply_flag("a", true, 5));
+        assert_eq!(cache.generation(), 5);
+        assert_eq!(cache.watermark(), 3);
+
+        // So the next incremental refresh still asks for changes after 3
+        assert_eq!(cache.apply_changes(changes(3, 5, &[("a", true, 5), ("b", true, 4)])), 2);
+        assert_eq!(cache.get("b"), Some(true));
+        assert_eq!(cache.watermark(), 5);
+    }
+
+    #[test]
+    fn test_change_older_than_cached_flag_is_discarded() {
+        let mut cache = FlagCache::default();
+        assert!(cache.apply_snapshot(snapshot(3, &[("a", false, 2)])));
+        assert!(cache.apply_flag("a", true, 6));
+
+        // A refresh that read "a" at 4 finishes after the update at 6
+        assert_eq!(cache.apply_changes(changes(3, 4, &[("a", false, 4)])), 0);
+        assert_eq!(cache.get("a"), Some(true));
+        assert_eq!(cache.watermark(), 4);
+    }
+
+    #[test]
+    fn test_incremental_refresh_drops_deleted_flags() {
+        let mut cache = FlagCache::default();
+        assert!(cache.apply_snapshot(snapshot(3, &[("a", true, 2), ("gone", true, 3)])));
+
+        let mut deletion = changes(3, 4, &[]);
+        deletion.deleted.insert("gone".to_string(), 4);
+        assert_eq!(cache.apply_changes(deletion), 1);
+        assert_eq!(cache.get("gone"), None);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.watermark(), 4);
+    }
+
+    #[test]
+    fn test_flag_created_again_after_deletion_is_kept() {
+        let mut cache = FlagCache::default();
+        assert!(cache.apply_snapshot(snapshot(3, &[("a", true, 3)])));
+
+        // Deleted at 4, created again at 5, both read by the same refresh
+        let mut recreated = changes(3, 5, &[("a", false, 5)]);
+        recreated.deleted.insert("a".to_string(), 4);
+        assert_eq!(cache.apply_changes(recreated), 1);
+        assert_eq!(cache.get("a"), Some(false));
+
+        // A deletion older than the cached row is discarded
+        assert!(!cache.remove_flag("a", 4));
+        assert_eq!(cache.get("a"), Some(false));
+    }
+
+    #[test]
+    fn test_full_refresh_schedule() {
+        let full: Vec<u64> = (0..7).filter(|count| full_refresh_due(*count, 3)).collect();
+        assert_eq!(full, vec![0, 3, 6]);
+        assert!((0..3).all(|count| full_refresh_due(count, 1)));
+        // Zero is treated as every refresh rather than dividing by zero
+        assert!(full_refresh_due(1, 0));
+    }
+
+    #[test]
+    fn test_invalidation_is_stale_until_its_generation_is_cached() {
+        let mut cache = FlagCache::default();
+        assert!(cache.apply_snapshot(snapshot(3, &[("a", false, 3)])));
+        let change = |flag: &str, generation| FlagInvalidation {
+            flag: flag.to_string(),
+            generation,
+        };
+
+        assert!(cache.is_stale(&change("a", 4)));
+        // A flag created on another instance
+        assert!(cache.is_stale(&change("new", 4)));
+
+        // This instance's own update is cached before its message arrives
+        assert!(cache.apply_flag("a", true, 4));
+        assert!(!cache.is_stale(&change("a", 4)));
+        assert!(!cache.is_stale(&change("a", 3)));
+    }
+
+    #[test]
+    fn test_stale_flag_update_is_discarded() {
+        let mut cache = FlagCache::default();
+        assert!(cache.apply_flag("a", true, 7));
+        assert!(!cache.apply_flag("a", false, 6));
+        assert_eq!(cache.get("a"), Some(true));
+    }
+
+    fn rollout(percentage: i16) -> FlagRule {
+        FlagRule {
+            rollout_percentage: percentage,
+            ..Default::default()
+        }
+    }
+
+    fn subjects() -> Vec<String> {
+        (0..1_000).map(|i| format!("GSUBJECT{:04}", i)).collect()
+    }
+
+    #[test]
+    fn test_rollout_is_deterministic_per_subject() {
+        let rule = rollout(30);
+        for subject in subjects() {
+            assert_eq!(rule.evaluate("f", &subject), rule.evaluate("f", &subject));
+            assert_eq!(rollout_bucket("f", &subject), rollout_bucket("f", &subject));
+        }
+        // The bucket depends on the flag too
+        let moved = subjects()
+            .iter()
+            .filter(|s| rollout_bucket("f", s) != rollout_bucket("g", s))
+            .count();
+        assert!(moved > 900);
+    }
+
+    #[test]
+    fn test_raising_rollout_only_turns_subjects_on() {
+        let (before, after) = (rollout(10), rollout(50));
+        let mut on = (0, 0);
+        for subject in subjects() {
+            let was = before.evaluate("f", &subject).enabled;
+            let is = after.evaluate("f", &subject).enabled;
+            assert!(!was || is, "{} turned off going from 10% to 50%", subject);
+            on = (on.0 + was as usize, on.1 + is as usize);
+        }
+        // Roughly the share of subjects asked for
+        assert!((50..150).contains(&on.0), "{} of 1000 on at 10%", on.0);
+        assert!((420..580).contains(&on.1), "{} of 1000 on at 50%", on.1);
+    }
+
+    #[test]
+    fn test_evaluation_reasons() {
+        assert_eq!(
+            FlagRule::from(true).evaluate("f", "GA"),
+            Evaluation {
+                enabled: true,
+                reason: EvaluationReason::GloballyOn
+            }
+        );
+        let allowlisted = FlagRule {
+            allowed_accounts: vec!["GA".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(allowlisted.evaluate("f", "GA").reason, EvaluationReason::Allowlisted);
+        assert_eq!(allowlisted.evaluate("f", "GB").reason, EvaluationReason::BucketedOut);
+        assert_eq!(rollout(100).evaluate("f", "GB").reason, EvaluationReason::BucketedIn);
+        assert!(subjects().iter().all(|s| !rollout(0).evaluate("f", s).enabled));
+        assert!(subjects().iter().all(|s| rollout(100).evaluate("f", s).enabled));
+    }
+
+    fn targeting_usdc() -> FlagRule {
+        FlagRule {
+            targeting: vec![TargetingRule {
+                attribute: ATTRIBUTE_ASSET_CODE.to_string(),
+                values: vec!["USDC".to_string(), "EURC".to_string()],
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_targeting_rule_matches_context_attributes() {
+        let rule = targeting_usdc();
+        for subject in subjects() {
+            let usdc = FlagContext::new(subject.as_str()).with_asset_code("USDC");
+            assert_eq!(rule.evaluate("f", &usdc).reason, EvaluationReason::Targeted);
+            let xlm = FlagContext::new(subject.as_str()).with_asset_code("XLM");
+            assert!(!rule.evaluate("f", &xlm).enabled);
+            // A context without the attribute matches no rule
+            assert!(!rule.evaluate("f", &subject).enabled);
+        }
+        // Asset codes are case sensitive on Stellar
+        let lower = FlagContext::new("GA").with_asset_code("usdc");
+        assert!(!rule.evaluate("f", lower).enabled);
+    }
+
+    #[test]
+    fn test_allowlist_is_checked_before_targeting_and_rollout() {
+        let rule = FlagRule {
+            rollout_percentage: 100,
+            allowed_accounts: vec!["GA".to_string()],
+            ..targeting_usdc()
+        };
+        let context = FlagContext::new("GA").with_asset_code("USDC");
+        assert_eq!(rule.evaluate("f", &context).reason, EvaluationReason::Allowlisted);
+        let context = FlagContext::new("GB").with_asset_code("USDC");
+        assert_eq!(rule.evaluate("f", &context).reason, EvaluationReason::Targeted);
+        assert_eq!(rule.evaluate("f", "GB").reason, EvaluationReason::BucketedIn);
+    }
+
+    #[test]
+    fn test_targeting_rules_round_trip_as_json() {
+        let rules: Vec<TargetingRule> =
+            serde_json::from_str(r#"[{"attribute": "asset_code", "values": ["USDC", "EURC"]}]"#)
+                .unwrap();
+        assert_eq!(rules, targeting_usdc().targeting);
+    }
+
+    #[test]
+    fn test_cached_rule_survives_refresh() {
+        let mut cache = FlagCache::default();
+        let rule = FlagRule {
+            rollout_percentage: 25,
+            allowed_accounts: vec!["GA".to_string()],
+            ..targeting_usdc()
+        };
+        let flags = HashMap::from([("f".to_string(), (rule.clone(), 2))]);
+        let snapshot = |generation| FlagSnapshot {
+            generation,
+            flags: flags.clone(),
+        };
+        assert!(cache.apply_snapshot(snapshot(2)));
+        assert_eq!(cache.rule("f"), Some(&rule));
+        assert!(cache.apply_snapshot(snapshot(3)));
+        assert_eq!(cache.rule("f"), Some(&rule));
+        assert_eq!(cache.rule("missing"), None);
+    }
 }
@@ -1,11 +1,51 @@
+pub mod account_stats;
+pub mod api_tokens;
+pub mod asset_verification;
+pub mod buffered_writer;
+pub mod erasure;
+pub mod event_stream;
+pub mod export_jobs;
+pub mod export_storage;
+pub mod feature_flags;
+pub mod ingestion;
+pub mod notifications;
+pub mod payment_listener;
+pub mod payment_processor;
+pub mod payout_listener;
 pub mod processor;
+pub mod quotes;
+pub mod reconciliation;
+pub mod redis_health;
 pub mod settlement;
+pub mod status_snapshot;
+pub mod transaction;
 pub mod transaction_processor;
+pub mod trustline_listener;
+pub mod webhook_dispatcher;
 pub mod scheduler;
 pub mod transaction_processor_job;
 
+pub use account_stats::AccountStatsService;
+pub use api_tokens::ApiTokenService;
+pub use asset_verification::AssetVerifier;
+pub use buffered_writer::BufferedWriter;
+pub use erasure::ErasureService;
+pub use event_stream::EventStream;
+pub use export_jobs::ExportJobService;
+pub use feature_flags::FeatureFlagService;
+pub use ingestion::IngestionService;
+pub use notifications::NotificationRenderer;
+pub use payment_listener::PaymentListener;
+pub use payment_processor::PaymentProcessor;
+pub use payout_listener::PayoutListener;
 pub use processor::run_processor;
+pub use quotes::QuoteService;
+pub use reconciliation::ReconciliationWorker;
+pub use redis_health::RedisHealth;
 pub use settlement::SettlementService;
+pub use status_snapshot::StatusSnapshotService;
 pub use transaction_processor::TransactionProcessor;
+pub use trustline_listener::TrustlineListener;
+pub use webhook_dispatcher::WebhookDispatcher;
 pub use scheduler::{JobScheduler, Job, JobStatus};
 pub use transaction_processor_job::TransactionProcessorJob;
@@ -0,0 +1,417 @@
+//! Human-readable `display_message` for outbound webhook events.
+//!
+//! Each event type has Fluent templates, one per locale, stored in
+//! `notification_templates`. A template may only reference the variables
+//! whitelisted for its event type, which are read from the event payload when
+//! the delivery is sent. The rendered text is HTML-escaped, because some
+//! consumers show it in web pages. Rendering never blocks a delivery: any
+//! failure falls back to [`GENERIC_MESSAGE`].
+
+use fluent_bundle::{FluentArgs, FluentBundle, FluentResource};
+use fluent_syntax::ast::{Entry, Expression, InlineExpression, Pattern, PatternElement};
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use std::str::FromStr;
+use unic_langid::LanguageIdentifier;
+
+use crate::db::queries::{self, EVENT_TRANSACTION_STATUS_CHANGED};
+use crate::handlers::webhook::EVENT_TRANSACTION_CREATED;
+use crate::metrics;
+
+/// Locale of last resort when looking up templates
+pub const DEFAULT_LOCALE: &str = "en";
+/// Sent when an event has no usable template
+pub const GENERIC_MESSAGE: &str = "There is an update on your transaction.";
+/// Stellar amounts have 7 decimal places; used when the asset sets none
+pub const DEFAULT_DISPLAY_DECIMALS: u32 = 7;
+
+/// Payload fields each event type exposes to its templates
+pub static EVENT_VARIABLES: &[(&str, &[&str])] = &[
+    (
+        EVENT_TRANSACTION_CREATED,
+        &["transaction_id", "status", "amount", "asset_code", "callback_type"],
+    ),
+    (
+        EVENT_TRANSACTION_STATUS_CHANGED,
+        &["transaction_id", "status", "previous_status", "amount", "asset_code", "callback_type"],
+    ),
+];
+
+/// Id of the single message a template is wrapped in
+const MESSAGE_ID: &str = "display-message";
+
+pub fn variables_for(event_type: &str) -> Option<&'static [&'static str]> {
+    EVENT_VARIABLES
+        .iter()
+        .find(|(event, _)| *event == event_type)
+        .map(|(_, variables)| *variables)
+}
+
+/// Canonical form of a BCP 47 locale tag, e.g. `pt-br` becomes `pt-BR`
+pub fn parse_locale(locale: &str) -> Result<String, String> {
+    LanguageIdentifier::from_str(locale.trim())
+        .map(|id| id.to_string())
+        .map_err(|_| format!("'{}' is not a valid locale", locale))
+}
+
+/// Locales tried in order for a subscription: the exact tag, its language,
+/// then [`DEFAULT_LOCALE`]
+pub fn locale_chain(locale: &str) -> Vec<String> {
+    let mut chain = vec![locale.to_string()];
+    if let Ok(id) = LanguageIdentifier::from_str(locale) {
+        chain.push(id.language.to_string());
+    }
+    chain.push(DEFAULT_LOCALE.to_string());
+    chain.dedup();
+    chain
+}
+
+/// Wrap a template in a Fluent message. Every line is indented, so a template
+/// can never define further messages.
+fn message_source(template: &str) -> String {
+    let mut source = format!("{} =\n", MESSAGE_ID);
+    for line in template.lines() {
+        source.push_str("    ");
+        source.push_str(line);
+        source.push('\n');
+    }
+    source
+}
+
+/// Check that a template parses and only references variables `event_type`
+/// whitelists
+pub fn validate_template(event_type: &str, template: &str) -> Result<(), String> {
+    let allowed = variables_for(event_type)
+        .ok_or_else(|| format!("unknown event type '{}'", event_type))?;
+    if template.trim().is_empty() {
+        return Err("template must not be empty".to_string());
+    }
+
+    let source = message_source(template);
+    let resource = fluent_syntax::parser::parse(source.as_str())
+        .map_err(|(_, errors)| format!("template does not parse: {:?}", errors[0].kind))?;
+    let mut referenced = Vec::new();
+    for entry in &resource.body {
+        if let Entry::Message(message) = entry {
+            if let Some(pattern) = &message.value {
+                pattern_variables(pattern, &mut referenced)?;
+            }
+        }
+    }
+
+    let unknown: Vec<&str> = referenced
+        .into_iter()
+        .filter(|name| !allowed.contains(name))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(format!(
+            "unknown variables for {}: {} (available: {})",
+            event_type,
+            unknown.join(", "),
+            allowed.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+fn pattern_variables<'s>(pattern: &Pattern<&'s str>, out: &mut Vec<&'s str>) -> Result<(), String> {
+    for element in &pattern.elements {
+        if let PatternElement::Placeable { expression } = element {
+            expression_variables(expression, out)?;
+        }
+    }
+    Ok(())
+}
+
+fn expression_variables<'s>(
+    expression: &Expression<&'s str>,
+    out: &mut Vec<&'s str>,
+) -> Result<(), String> {
+    match expression {
+        Expression::Inline(inline) => inline_variables(inline, out),
+        Expression::Select { selector, variants } => {
+            inline_variables(selector, out)?;
+            for variant in variants {
+                pattern_variables(&variant.value, out)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn inline_variables<'s>(
+    inline: &InlineExpression<&'s str>,
+    out: &mut Vec<&'s str>,
+) -> Result<(), String> {
+    match inline {
+        InlineExpression::VariableReference { id } => {
+            out.push(id.name);
+            Ok(())
+        }
+        InlineExpression::StringLiteral { .. } | InlineExpression::NumberLiteral { .. } => Ok(()),
+        InlineExpression::Placeable { expression } => expression_variables(expression, out),
+        // Functions, messages and terms could pull in text the whitelist does not cover
+        _ => Err("templates may only use variables, literals and selectors".to_string()),
+    }
+}
+
+/// Render a validated template and HTML-escape the result. Fails if a
+/// referenced variable is missing from `args`.
+pub fn render_template(
+    locale: &str,
+    template: &str,
+    args: &[(&'static str, String)],
+) -> Result<String, String> {
+    let langid = LanguageIdentifier::from_str(locale).map_err(|e| e.to_string())?;
+    let resource = FluentResource::try_new(message_source(template))
+        .map_err(|(_, errors)| format!("template does not parse: {:?}", errors[0].kind))?;
+    let mut bundle = FluentBundle::new(vec![langid]);
+    // No Unicode isolation marks around placeables: consumers display the text as is
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .map_err(|errors| format!("{:?}", errors))?;
+
+    let pattern = bundle
+        .get_message(MESSAGE_ID)
+        .and_then(|message| message.value())
+        .ok_or_else(|| "template has no text".to_string())?;
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.clone());
+    }
+    let mut errors = Vec::new();
+    let text = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+    if let Some(error) = errors.first() {
+        return Err(format!("{:?}", error));
+    }
+    Ok(html_escape(text.trim()))
+}
+
+/// Round an amount to the asset's display decimals, e.g. `100.0000000` at 2
+/// decimals is `100.00`
+pub fn format_amount(amount: &str, decimals: u32) -> Option<String> {
+    let amount = BigDecimal::from_str(amount.trim()).ok()?;
+    let decimals = i64::from(decimals);
+    Some(amount.round(decimals).with_scale(decimals).to_string())
+}
+
+/// Escape text for HTML element content and attribute values
+pub fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Template arguments for an event: its whitelisted payload fields, with
+/// `amount` formatted to `decimals`. Missing and null fields are left out.
+pub fn template_args(
+    event_type: &str,
+    payload: &serde_json::Value,
+    decimals: u32,
+) -> Vec<(&'static str, String)> {
+    let Some(variables) = variables_for(event_type) else {
+        return Vec::new();
+    };
+    variables
+        .iter()
+        .filter_map(|name| {
+            let value = match payload.get(*name)? {
+                serde_json::Value::Null => return None,
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            let value = if *name == "amount" {
+                format_amount(&value, decimals)?
+            } else {
+                value
+            };
+            Some((*name, value))
+        })
+        .collect()
+}
+
+/// Renders `display_message` for deliveries at send time
+#[derive(Clone)]
+pub struct NotificationRenderer {
+    pool: PgPool,
+}
+
+impl NotificationRenderer {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// The message for one event, falling back to [`GENERIC_MESSAGE`]
+    pub async fn display_message(
+        &self,
+        event_type: &str,
+        locale: &str,
+        payload: &serde_json::Value,
+    ) -> String {
+        match self.render(event_type, locale, payload).await {
+            Ok(Some(message)) => {
+                metrics::record_notification_message("rendered");
+                message
+            }
+            Ok(None) => {
+                metrics::record_notification_message("fallback");
+                html_escape(GENERIC_MESSAGE)
+            }
+            Err(e) => {
+                tracing::warn!(event_type, locale, "Failed to render display message: {}", e);
+                metrics::record_notification_message("fallback");
+                html_escape(GENERIC_MESSAGE)
+            }
+        }
+    }
+
+    /// `None` when the event type has no template for the locale
+    async fn render(
+        &self,
+        event_type: &str,
+        locale: &str,
+        payload: &serde_json::Value,
+    ) -> Result<Option<String>, String> {
+        let locales = locale_chain(locale);
+        let template = queries::find_notification_template(&self.pool, event_type, &locales)
+            .await
+            .map_err(|e| e.to_string())?;
+        let Some(template) = template else {
+            return Ok(None);
+        };
+
+        let asset_issuer = payload.get("asset_issuer").and_then(|v| v.as_str());
+        let decimals = match payload.get("asset_code").and_then(|v| v.as_str()) {
+            Some(asset_code) => {
+                queries::get_asset_display_decimals(&self.pool, asset_code, asset_issuer)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .and_then(|decimals| u32::try_from(decimals).ok())
+            }
+            None => None,
+        };
+        let args = template_args(event_type, payload, decimals.unwrap_or(DEFAULT_DISPLAY_DECIMALS));
+        render_template(&template.locale, &template.template, &args).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const DEPOSIT: &str = "{ $callback_type ->
+    [deposit] Your deposit of { $amount } { $asset_code } is being processed
+   *[other] Your transaction of { $amount } { $asset_code } is being processed
+}";
+
+    fn payload() -> serde_json::Value {
+        json!({
+            "transaction_id": "3f1c",
+            "status": "pending",
+            "amount": "100.0000000",
+            "asset_code": "USDC",
+            "callback_type": "deposit",
+        })
+    }
+
+    #[test]
+    fn test_renders_with_display_decimals() {
+        let args = template_args(EVENT_TRANSACTION_CREATED, &payload(), 2);
+        assert_eq!(
+            render_template("en", DEPOSIT, &args).unwrap(),
+            "Your deposit of 100.00 USDC is being processed"
+        );
+
+        let mut withdrawal = payload();
+        withdrawal["callback_type"] = json!("withdrawal");
+        let args = template_args(EVENT_TRANSACTION_CREATED, &withdrawal, 7);
+        assert_eq!(
+            render_template("en", DEPOSIT, &args).unwrap(),
+            "Your transaction of 100.0000000 USDC is being processed"
+        );
+    }
+
+    #[test]
+    fn test_output_is_html_escaped() {
+        let mut payload = payload();
+        payload["asset_code"] = json!("<script>alert(1)</script>");
+        let args = template_args(EVENT_TRANSACTION_CREATED, &payload, 2);
+        let message = render_template("en", "<b>{ $asset_code }</b> & more", &args).unwrap();
+        assert_eq!(
+            message,
+            "&lt;b&gt;&lt;script&gt;alert(1)&lt;/script&gt;&lt;/b&gt; &amp; more"
+        );
+    }
+
+    #[test]
+    fn test_missing_variable_fails_rendering() {
+        let mut payload = payload();
+        payload["asset_code"] = serde_json::Value::Null;
+        let args = template_args(EVENT_TRANSACTION_CREATED, &payload, 2);
+        assert!(render_template("en", DEPOSIT, &args).is_err());
+    }
+
+    #[test]
+    fn test_validation_checks_variables_against_event_type() {
+        assert!(validate_template(EVENT_TRANSACTION_CREATED, DEPOSIT).is_ok());
+
+        let err =
+            validate_template(EVENT_TRANSACTION_CREATED, "Hi { $customer_name }").unwrap_err();
+        assert!(err.contains("customer_name"), "{}", err);
+        assert!(validate_template("transaction.unknown", "Hi").is_err());
+        assert!(validate_template(EVENT_TRANSACTION_CREATED, "Hi { other-message }").is_err());
+        assert!(validate_template(EVENT_TRANSACTION_CREATED, "Hi { $amount").is_err());
+        assert!(validate_template(EVENT_TRANSACTION_CREATED, "  ").is_err());
+    }
+
+    #[test]
+    fn test_status_change_template() {
+        let mut payload = payload();
+        payload["status"] = json!("completed");
+        payload["previous_status"] = json!("processing");
+        let template = "Your transaction of { $amount } { $asset_code } is now { $status }";
+        assert!(validate_template(EVENT_TRANSACTION_STATUS_CHANGED, template).is_ok());
+        let args = template_args(EVENT_TRANSACTION_STATUS_CHANGED, &payload, 2);
+        assert_eq!(
+            render_template("en", template, &args).unwrap(),
+            "Your transaction of 100.00 USDC is now completed"
+        );
+    }
+
+    #[test]
+    fn test_template_cannot_define_other_messages() {
+        let template = "Hello\nsecret = { $transaction_id }";
+        let args = template_args(EVENT_TRANSACTION_CREATED, &payload(), 2);
+        assert_eq!(
+            render_template("en", template, &args).unwrap(),
+            "Hello\nsecret = 3f1c"
+        );
+    }
+
+    #[test]
+    fn test_locale_chain() {
+        assert_eq!(locale_chain("pt-BR"), vec!["pt-BR", "pt", "en"]);
+        assert_eq!(locale_chain("en"), vec!["en"]);
+        assert_eq!(parse_locale("pt-br").unwrap(), "pt-BR");
+        assert!(parse_locale("not a locale").is_err());
+    }
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount("100", 2).as_deref(), Some("100.00"));
+        assert_eq!(format_amount("12.3456789", 4).as_deref(), Some("12.3457"));
+        assert_eq!(format_amount("5.5", 0).as_deref(), Some("6"));
+        assert_eq!(format_amount("abc", 2), None);
+    }
+}
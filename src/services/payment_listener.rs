@@ -0,0 +1,553 @@
+use serde::Serialize;
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::db::{queries, uow};
+use crate::domain::TransactionStatus;
+use crate::handlers::ws::TransactionStatusUpdate;
+use crate::metrics;
+use crate::services::FeatureFlagService;
+use crate::services::processor::publish_status_update;
+use crate::stellar::{HorizonClient, Operation};
+use crate::utils::correlation::CorrelationContext;
+use crate::utils::shutdown::Shutdown;
+
+/// Feature flag gating the payment listener
+pub const PAYMENT_LISTENER_FLAG: &str = "payment_operations_listener";
+
+/// Name of this listener's row in `stream_cursors`
+pub const CURSOR_NAME: &str = "payment_operations";
+
+/// Key under which a match is recorded in the transaction's metadata
+pub const METADATA_KEY: &str = "stellar_payment";
+
+/// Asset code the registry uses for lumens
+pub const NATIVE_ASSET_CODE: &str = "XLM";
+
+/// Actor on the audit entries of matched transactions
+const ACTOR: &str = "payment_listener";
+const PAGE_SIZE: u32 = 200;
+
+/// One side of a payment: what was received, or what the sender paid with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PaymentLeg {
+    pub asset_code: String,
+    pub asset_issuer: Option<String>,
+    pub amount: String,
+}
+
+impl PaymentLeg {
+    fn new(asset_type: Option<&str>, asset_code: Option<&str>, asset_issuer: Option<&str>, amount: &str) -> Option<Self> {
+        let (asset_code, asset_issuer) = match asset_type {
+            Some("native") => (NATIVE_ASSET_CODE.to_string(), None),
+            _ => (asset_code?.to_string(), asset_issuer.map(str::to_string)),
+        };
+        Some(Self {
+            asset_code,
+            asset_issuer,
+            amount: amount.to_string(),
+        })
+    }
+
+    fn is_same_asset(&self, other: &PaymentLeg) -> bool {
+        self.asset_code == other.asset_code && self.asset_issuer == other.asset_issuer
+    }
+}
+
+/// A payment into the listened account, whatever operation carried it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IncomingPayment {
+    pub operation_id: String,
+    pub operation_type: &'static str,
+    pub transaction_hash: Option<String>,
+    pub from: String,
+    #[serde(skip)]
+    pub memo: Option<String>,
+    /// Matched against the transaction: the destination amount and asset
+    pub received: PaymentLeg,
+    /// What the sender paid with, when it is a different asset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sent: Option<PaymentLeg>,
+}
+
+/// How the listener handles an operation from the payments endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsedOperation {
+    Incoming(IncomingPayment),
+    /// `account_merge` into the listened account. Horizon doesn't put the
+    /// merged balance on the operation; it is read from its effects.
+    Merge {
+        operation_id: String,
+        transaction_hash: Option<String>,
+        from: String,
+        memo: Option<String>,
+    },
+    /// A handled type, but not a payment into the listened account
+    NotIncoming,
+    /// A type the listener doesn't handle, such as `create_account`
+    Unsupported(String),
+}
+
+/// Classify an operation for `account`
+pub fn parse_operation(operation: &Operation, account: &str) -> ParsedOperation {
+    let memo = operation.transaction.as_ref().and_then(|tx| tx.memo.clone());
+    let operation_type = match operation.operation_type.as_str() {
+        "payment" => "payment",
+        "path_payment_strict_receive" => "path_payment_strict_receive",
+        "path_payment_strict_send" => "path_payment_strict_send",
+        "account_merge" => {
+            return match (&operation.account, &operation.into) {
+                (Some(from), Some(into)) if into == account => ParsedOperation::Merge {
+                    operation_id: operation.id.clone(),
+                    transaction_hash: operation.transaction_hash.clone(),
+                    from: from.clone(),
+                    memo,
+                },
+                _ => ParsedOperation::NotIncoming,
+            };
+        }
+        other => return ParsedOperation::Unsupported(other.to_string()),
+    };
+
+    if operation.to.as_deref() != Some(account) {
+        return ParsedOperation::NotIncoming;
+    }
+    let (Some(from), Some(amount)) = (&operation.from, &operation.amount) else {
+        return ParsedOperation::NotIncoming;
+    };
+    let Some(received) = PaymentLeg::new(
+        operation.asset_type.as_deref(),
+        operation.asset_code.as_deref(),
+        operation.asset_issuer.as_deref(),
+        amount,
+    ) else {
+        return ParsedOperation::NotIncoming;
+    };
+
+    // Path payments also carry what the sender paid with
+    let sent = operation.source_amount.as_deref().and_then(|source_amount| {
+        PaymentLeg::new(
+            operation.source_asset_type.as_deref(),
+            operation.source_asset_code.as_deref(),
+            operation.source_asset_issuer.as_deref(),
+            source_amount,
+        )
+    });
+
+    ParsedOperation::Incoming(IncomingPayment {
+        operation_id: operation.id.clone(),
+        operation_type,
+        transaction_hash: operation.transaction_hash.clone(),
+        from: from.clone(),
+        memo,
+        sent: sent.filter(|sent| !sent.is_same_asset(&received)),
+        received,
+    })
+}
+
+/// What happened to an incoming payment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchOutcome {
+    /// Moved this pending transaction to `processing`
+    Matched(Uuid),
+    /// No pending transaction has the payment's memo, asset and amount
+    Unmatched,
+    /// The operation was already processed
+    Duplicate,
+}
+
+/// Watches payments into the anchor's receiving account and matches them to
+/// pending transactions by memo, asset and amount. Plain payments, both kinds
+/// of path payment and account merges are matched the same way.
+#[derive(Clone)]
+pub struct PaymentListener {
+    pool: PgPool,
+    horizon_client: HorizonClient,
+    feature_flags: FeatureFlagService,
+    tx_broadcast: broadcast::Sender<TransactionStatusUpdate>,
+    account: String,
+    start_cursor: Option<String>,
+    shutdown: Shutdown,
+}
+
+impl PaymentListener {
+    pub fn new(
+        pool: PgPool,
+        horizon_client: HorizonClient,
+        feature_flags: FeatureFlagService,
+        tx_broadcast: broadcast::Sender<TransactionStatusUpdate>,
+        account: String,
+    ) -> Self {
+        Self {
+            pool,
+            horizon_client,
+            feature_flags,
+            tx_broadcast,
+            account,
+            start_cursor: None,
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    /// Stop between polls once `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Paging token to start from when no cursor is stored. Without one the
+    /// listener reads the account's payments from the oldest.
+    pub fn with_start_cursor(mut self, start_cursor: Option<String>) -> Self {
+        self.start_cursor = start_cursor;
+        self
+    }
+
+    pub fn start(&self, poll_interval: Duration) {
+        let listener = self.clone();
+        self.shutdown.spawn(async move {
+            let mut interval = tokio::time::interval(poll_interval);
+            loop {
+                tokio::select! {
+                    _ = listener.shutdown.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+                if !listener.feature_flags.is_enabled(PAYMENT_LISTENER_FLAG).await {
+                    continue;
+                }
+                if let Err(e) = listener.poll_once().await {
+                    tracing::error!("Payment listener failed: {}", e);
+                }
+            }
+            tracing::info!("Payment listener stopped");
+        });
+    }
+
+    /// Process one page of operations after the stored cursor.
+    /// Returns the number of transactions matched.
+    pub async fn poll_once(&self) -> anyhow::Result<usize> {
+        let cursor = queries::get_stream_cursor(&self.pool, CURSOR_NAME)
+            .await?
+            .or_else(|| self.start_cursor.clone());
+        let operations = self
+            .horizon_client
+            .get_payments(&self.account, cursor.as_deref(), PAGE_SIZE)
+            .await?;
+
+        let mut matched = 0;
+        for operation in &operations {
+            let payment = match parse_operation(operation, &self.account) {
+                ParsedOperation::Incoming(payment) => Some(payment),
+                ParsedOperation::Merge {
+                    operation_id,
+                    transaction_hash,
+                    from,
+                    memo,
+                } => self.merged_balance(operation_id, transaction_hash, from, memo).await?,
+                ParsedOperation::NotIncoming => None,
+                ParsedOperation::Unsupported(operation_type) => {
+                    tracing::debug!(operation_id = %operation.id, "Skipping {} operation", operation_type);
+                    metrics::record_horizon_operation_skipped(&operation_type);
+                    None
+                }
+            };
+
+            if let Some(payment) = payment {
+                let outcome = self.handle_payment(&payment).await?;
+                if matches!(outcome, MatchOutcome::Matched(_)) {
+                    matched += 1;
+                }
+            }
+        }
+        // Every operation on the page was looked at, matched or not
+        if let Some(last) = operations.last() {
+            queries::save_stream_cursor(&self.pool, CURSOR_NAME, &last.paging_token).await?;
+        }
+
+        Ok(matched)
+    }
+
+    /// The lumens an `account_merge` credited to the listened account
+    async fn merged_balance(
+        &self,
+        operation_id: String,
+        transaction_hash: Option<String>,
+        from: String,
+        memo: Option<String>,
+    ) -> anyhow::Result<Option<IncomingPayment>> {
+        let effects = self.horizon_client.get_operation_effects(&operation_id).await?;
+        let credited = effects
+            .iter()
+            .find(|effect| effect.effect_type == "account_credited" && effect.account == self.account)
+            .and_then(|effect| effect.amount.clone());
+
+        let Some(amount) = credited else {
+            tracing::warn!(operation_id = %operation_id, "account_merge without an account_credited effect");
+            return Ok(None);
+        };
+        Ok(Some(IncomingPayment {
+            operation_id,
+            operation_type: "account_merge",
+            transaction_hash,
+            from,
+            memo,
+            received: PaymentLeg {
+                asset_code: NATIVE_ASSET_CODE.to_string(),
+                asset_issuer: None,
+                amount,
+            },
+            sent: None,
+        }))
+    }
+
+    /// Match a payment to a pending transaction, at most once per operation
+    pub async fn handle_payment(&self, payment: &IncomingPayment) -> anyhow::Result<MatchOutcome> {
+        let candidate = match &payment.memo {
+            Some(memo) if self.is_registry_asset(&payment.received).await? => {
+                BigDecimal::from_str(&payment.received.amount).ok().map(|amount| (memo.clone(), amount))
+            }
+            _ => None,
+        };
+
+        let metadata = serde_json::to_value(payment)?;
+        let outcome = uow::run(&self.pool, |uow| Box::pin(async move {
+            if !queries::mark_operation_processed(uow.conn(), &payment.operation_id, payment.operation_type).await? {
+                return Ok::<_, anyhow::Error>(MatchOutcome::Duplicate);
+            }
+            let Some((memo, amount)) = candidate else {
+                return Ok(MatchOutcome::Unmatched);
+            };
+            let received = &payment.received;
+            let Some(id) = queries::find_pending_payment_match(
+                uow.conn(),
+                &memo,
+                &received.asset_code,
+                received.asset_issuer.as_deref(),
+                &amount,
+            )
+            .await?
+            else {
+                return Ok(MatchOutcome::Unmatched);
+            };
+
+            // Re-checked under the row lock; a concurrent change wins
+            let updated = queries::update_transaction_status(
+                uow.conn(),
+                id,
+                &[TransactionStatus::Pending.as_str()],
+                TransactionStatus::Processing.as_str(),
+                ACTOR,
+            )
+            .await?;
+            if updated.is_none() {
+                return Ok(MatchOutcome::Unmatched);
+            }
+            queries::set_transaction_metadata(uow.conn(), id, METADATA_KEY, metadata).await?;
+            queries::set_operation_transaction(uow.conn(), &payment.operation_id, id).await?;
+            Ok(MatchOutcome::Matched(id))
+        }))
+        .await?;
+
+        match &outcome {
+            MatchOutcome::Matched(id) => {
+                let tx = queries::get_transaction(&self.pool, *id).await?;
+                let ctx = CorrelationContext::for_transaction(&tx);
+                tracing::info!(
+                    transaction_id = %id,
+                    correlation_id = %ctx,
+                    operation_id = %payment.operation_id,
+                    "{} of {} {} matched",
+                    payment.operation_type,
+                    payment.received.amount,
+                    payment.received.asset_code
+                );
+                metrics::record_horizon_operation(payment.operation_type, "matched");
+                publish_status_update(
+                    &self.tx_broadcast,
+                    &ctx,
+                    *id,
+                    TransactionStatus::Processing.as_str().to_string(),
+                    Some(format!("received via {}", payment.operation_type)),
+                );
+            }
+            MatchOutcome::Unmatched => {
+                tracing::warn!(
+                    operation_id = %payment.operation_id,
+                    from = %payment.from,
+                    memo = ?payment.memo,
+                    "{} of {} {} matched no pending transaction",
+                    payment.operation_type,
+                    payment.received.amount,
+                    payment.received.asset_code
+                );
+                metrics::record_horizon_operation(payment.operation_type, "unmatched");
+            }
+            MatchOutcome::Duplicate => {}
+        }
+        Ok(outcome)
+    }
+
+    /// Lumens, or a registry asset with the same issuer. Anyone can issue
+    /// an asset called USDC.
+    async fn is_registry_asset(&self, leg: &PaymentLeg) -> anyhow::Result<bool> {
+        if leg.asset_code == NATIVE_ASSET_CODE && leg.asset_issuer.is_none() {
+            return Ok(true);
+        }
+        let Some(issuer) = leg.asset_issuer.as_deref() else {
+            return Ok(false);
+        };
+        let asset =
+            queries::get_asset_by_code_and_issuer(&self.pool, &leg.asset_code, issuer).await?;
+        Ok(asset.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stellar::OperationTransaction;
+
+    const ANCHOR: &str = "GANCHOR";
+    const ISSUER: &str = "GISSUER";
+
+    fn operation(operation_type: &str) -> Operation {
+        Operation {
+            id: "12884905985".to_string(),
+            paging_token: "12884905985".to_string(),
+            operation_type: operation_type.to_string(),
+            transaction_hash: Some("abc123".to_string()),
+            from: Some("GCUSTOMER".to_string()),
+            to: Some(ANCHOR.to_string()),
+            amount: Some("100.0000000".to_string()),
+            asset_type: Some("credit_alphanum4".to_string()),
+            asset_code: Some("USDC".to_string()),
+            asset_issuer: Some(ISSUER.to_string()),
+            transaction: Some(OperationTransaction {
+                memo: Some("anchor-tx-1".to_string()),
+                memo_type: Some("text".to_string()),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn incoming(parsed: ParsedOperation) -> IncomingPayment {
+        match parsed {
+            ParsedOperation::Incoming(payment) => payment,
+            other => panic!("expected an incoming payment, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_plain_payment() {
+        let payment = incoming(parse_operation(&operation("payment"), ANCHOR));
+        assert_eq!(payment.operation_type, "payment");
+        assert_eq!(payment.memo.as_deref(), Some("anchor-tx-1"));
+        assert_eq!(payment.received.asset_code, "USDC");
+        assert_eq!(payment.received.amount, "100.0000000");
+        assert_eq!(payment.sent, None);
+    }
+
+    #[test]
+    fn test_cross_asset_path_payment_keeps_both_legs() {
+        let mut op = operation("path_payment_strict_receive");
+        op.source_amount = Some("92.5000000".to_string());
+        op.source_asset_type = Some("credit_alphanum4".to_string());
+        op.source_asset_code = Some("EURC".to_string());
+        op.source_asset_issuer = Some("GEURISSUER".to_string());
+
+        let payment = incoming(parse_operation(&op, ANCHOR));
+        // Matched on what arrived
+        assert_eq!(payment.received.asset_code, "USDC");
+        assert_eq!(payment.received.amount, "100.0000000");
+        assert_eq!(
+            payment.sent,
+            Some(PaymentLeg {
+                asset_code: "EURC".to_string(),
+                asset_issuer: Some("GEURISSUER".to_string()),
+                amount: "92.5000000".to_string(),
+            })
+        );
+
+        let metadata = serde_json::to_value(&payment).unwrap();
+        assert_eq!(metadata["sent"]["asset_code"], "EURC");
+        assert_eq!(metadata["received"]["asset_code"], "USDC");
+    }
+
+    #[test]
+    fn test_same_asset_path_payment_has_one_leg() {
+        let mut op = operation("path_payment_strict_send");
+        op.source_amount = Some("100.0000000".to_string());
+        op.source_asset_type = op.asset_type.clone();
+        op.source_asset_code = op.asset_code.clone();
+        op.source_asset_issuer = op.asset_issuer.clone();
+
+        let payment = incoming(parse_operation(&op, ANCHOR));
+        assert_eq!(payment.operation_type, "path_payment_strict_send");
+        assert_eq!(payment.sent, None);
+        assert!(serde_json::to_value(&payment).unwrap().get("sent").is_none());
+    }
+
+    #[test]
+    fn test_native_payment() {
+        let mut op = operation("payment");
+        op.asset_type = Some("native".to_string());
+        op.asset_code = None;
+        op.asset_issuer = None;
+        let payment = incoming(parse_operation(&op, ANCHOR));
+        assert_eq!(payment.received.asset_code, NATIVE_ASSET_CODE);
+        assert_eq!(payment.received.asset_issuer, None);
+    }
+
+    #[test]
+    fn test_outgoing_payment_is_not_incoming() {
+        let mut op = operation("payment");
+        op.from = Some(ANCHOR.to_string());
+        op.to = Some("GCUSTOMER".to_string());
+        assert_eq!(parse_operation(&op, ANCHOR), ParsedOperation::NotIncoming);
+    }
+
+    #[test]
+    fn test_account_merge_into_us() {
+        let mut op = operation("account_merge");
+        op.account = Some("GCUSTOMER".to_string());
+        op.into = Some(ANCHOR.to_string());
+        assert_eq!(
+            parse_operation(&op, ANCHOR),
+            ParsedOperation::Merge {
+                operation_id: "12884905985".to_string(),
+                transaction_hash: Some("abc123".to_string()),
+                from: "GCUSTOMER".to_string(),
+                memo: Some("anchor-tx-1".to_string()),
+            }
+        );
+
+        op.into = Some("GSOMEONEELSE".to_string());
+        assert_eq!(parse_operation(&op, ANCHOR), ParsedOperation::NotIncoming);
+    }
+
+    #[test]
+    fn test_unknown_types_are_unsupported() {
+        assert_eq!(
+            parse_operation(&operation("create_account"), ANCHOR),
+            ParsedOperation::Unsupported("create_account".to_string())
+        );
+    }
+
+    #[test]
+    fn test_page_with_unknown_types_deserializes() {
+        let page = r#"[
+            {"id": "1", "paging_token": "1", "type": "create_account", "funder": "GFUNDER",
+             "account": "GNEW", "starting_balance": "5.0000000"},
+            {"id": "2", "paging_token": "2", "type": "payment", "from": "GCUSTOMER", "to": "GANCHOR",
+             "amount": "1.0000000", "asset_type": "native",
+             "transaction": {"memo": "anchor-tx-1", "memo_type": "text", "fee_charged": "100"}}
+        ]"#;
+        let operations: Vec<Operation> = serde_json::from_str(page).unwrap();
+        assert_eq!(operations.len(), 2);
+        assert!(matches!(parse_operation(&operations[0], ANCHOR), ParsedOperation::Unsupported(_)));
+        assert!(matches!(parse_operation(&operations[1], ANCHOR), ParsedOperation::Incoming(_)));
+    }
+}
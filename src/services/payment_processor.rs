@@ -0,0 +1,571 @@
+//! Stellar payouts of pending deposits.
+//!
+//! Each pass claims pending deposits, signs a payment of the transaction's
+//! amount and asset to its `stellar_account` with the signing key, and submits
+//! it to Horizon. The envelope is stored, and the transaction moved to
+//! `submitted`, before it is sent: if the answer is lost, the next pass looks
+//! the hash up and sends the same envelope again rather than paying twice. A
+//! new envelope is only signed once the last one was rejected or is past its
+//! time bounds, when it can no longer land.
+
+use chrono::{DateTime, Utc};
+use ed25519_dalek::SigningKey;
+use serde_json::json;
+use sqlx::PgPool;
+use tokio::sync::broadcast;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::config::PaymentProcessorConfig;
+use crate::db::models::{Payout, Transaction};
+use crate::db::{queries, uow};
+use crate::domain::TransactionStatus;
+use crate::handlers::ws::TransactionStatusUpdate;
+use crate::services::payment_listener::NATIVE_ASSET_CODE;
+use crate::services::processor::publish_status_update;
+use crate::stellar::strkey::{self, AccountKind};
+use crate::stellar::transaction::{
+    to_stroops, PaymentAsset, PaymentTransaction, SignedTransaction, TransactionError,
+};
+use crate::stellar::{HorizonClient, HorizonError};
+use crate::utils::clock::{self, SharedClock, Ticker};
+use crate::utils::correlation::CorrelationContext;
+use crate::utils::shutdown::Shutdown;
+
+/// Key under which the outcome is recorded in the transaction's metadata
+pub const METADATA_KEY: &str = "payout";
+
+/// Actor on the audit entries of paid out transactions
+const ACTOR: &str = "payment_processor";
+
+/// Past its time bounds, an envelope is only given up once ledgers closed in
+/// this window can't still include it
+const LEDGER_GRACE: chrono::Duration = chrono::Duration::seconds(60);
+
+/// What a rejected payment means for its transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// Worth signing again, e.g. a stale sequence or an underfunded source
+    Retry,
+    /// The destination doesn't trust the asset yet
+    NoTrust,
+    /// Will never succeed for this transaction
+    Fatal,
+}
+
+/// Sort the result codes Horizon rejected a payment with
+pub fn classify_rejection(codes: &[String]) -> Rejection {
+    if codes.iter().any(|code| code == "op_no_trust" || code == "op_not_authorized") {
+        return Rejection::NoTrust;
+    }
+    let fatal = ["op_no_destination", "op_malformed", "op_no_issuer", "op_line_full"];
+    if codes.iter().any(|code| fatal.contains(&code.as_str())) {
+        return Rejection::Fatal;
+    }
+    Rejection::Retry
+}
+
+/// The account a deposit is paid to: its muxed account when the callback
+/// named one
+pub fn payout_destination(tx: &Transaction) -> Result<AccountKind, TransactionError> {
+    let account = strkey::validate_account(&tx.stellar_account)
+        .map_err(|e| TransactionError::Account(tx.stellar_account.clone(), e))?;
+    let Some(muxed_id) = &tx.muxed_id else {
+        return Ok(account);
+    };
+    let id = muxed_id.to_string().parse::<u64>().map_err(|_| {
+        TransactionError::Account(tx.stellar_account.clone(), strkey::StrkeyError::Version('M'))
+    })?;
+    Ok(AccountKind::Muxed {
+        public_key: *account.public_key(),
+        id,
+    })
+}
+
+/// What a pass did with one transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayoutOutcome {
+    /// The payment landed; completed
+    Completed { hash: String },
+    /// Sent, but Horizon's answer didn't arrive; looked up on the next pass
+    Unconfirmed { hash: String },
+    /// Rejected for a reason that may pass; signed again on a later pass
+    Retry { codes: Vec<String> },
+    /// The destination has no trustline for the asset; pending_trustline
+    PendingTrustline { codes: Vec<String> },
+    /// Can never be paid; failed
+    Failed { reason: String },
+    /// The asset has no issuer in the registry; it stays pending
+    UnknownAsset,
+    /// No longer in the status it was claimed in once locked
+    Skipped,
+}
+
+/// Periodically claims pending deposits and pays them out on Stellar
+#[derive(Clone)]
+pub struct PaymentProcessor {
+    pool: PgPool,
+    horizon_client: HorizonClient,
+    tx_broadcast: broadcast::Sender<TransactionStatusUpdate>,
+    config: PaymentProcessorConfig,
+    signing_key: SigningKey,
+    clock: SharedClock,
+    shutdown: Shutdown,
+}
+
+impl PaymentProcessor {
+    pub fn new(
+        pool: PgPool,
+        horizon_client: HorizonClient,
+        tx_broadcast: broadcast::Sender<TransactionStatusUpdate>,
+        config: PaymentProcessorConfig,
+        signing_key: SigningKey,
+    ) -> Self {
+        Self {
+            pool,
+            horizon_client,
+            tx_broadcast,
+            config,
+            signing_key,
+            clock: clock::system(),
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    /// Set time bounds on `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Stop between passes once `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// The `G` account payments are made from
+    pub fn source_account(&self) -> String {
+        strkey::encode_ed25519(self.signing_key.verifying_key().as_bytes())
+    }
+
+    pub fn start(&self) {
+        let processor = self.clone();
+        self.shutdown.spawn(async move {
+            let mut ticker = Ticker::new(processor.clock.clone(), processor.config.poll_interval);
+            loop {
+                tokio::select! {
+                    _ = processor.shutdown.cancelled() => break,
+                    _ = ticker.tick() => {}
+                }
+                if let Err(e) = processor.run_once().await {
+                    tracing::error!("Payment processor pass failed: {:?}", e);
+                }
+            }
+            tracing::info!("Payment processor stopped");
+        });
+    }
+
+    /// Claim one batch and pay out each transaction in it. A failure on one
+    /// transaction is logged and leaves it for a later pass.
+    pub async fn run_once(&self) -> anyhow::Result<Vec<(Uuid, PayoutOutcome)>> {
+        let now = self.clock.now();
+        // Until the next pass, other processors leave a claimed transaction alone
+        let lease = chrono::Duration::from_std(self.config.poll_interval)?;
+        let claimed =
+            queries::claim_transactions_for_payout(&self.pool, now, now + lease, self.config.batch_size)
+                .await?;
+
+        // Read once per pass and counted up locally; read again after anything
+        // but a success, since the source's sequence may then be off
+        let mut sequence = None;
+        let mut outcomes = Vec::with_capacity(claimed.len());
+        for payout in claimed {
+            let ctx = CorrelationContext::for_transaction(&payout.transaction);
+            let span = tracing::info_span!(
+                "pay_out_transaction",
+                transaction_id = %payout.transaction.id,
+                correlation_id = %ctx
+            );
+            let outcome = async {
+                match self.pay_out(&payout, &ctx, now, &mut sequence).await {
+                    Ok(outcome) => Some(outcome),
+                    Err(e) => {
+                        tracing::error!("Payout failed: {:?}", e);
+                        None
+                    }
+                }
+            }
+            .instrument(span)
+            .await;
+            if !matches!(outcome, Some(PayoutOutcome::Completed { .. })) {
+                sequence = None;
+            }
+            if let Some(outcome) = outcome {
+                outcomes.push((payout.transaction.id, outcome));
+            }
+        }
+        Ok(outcomes)
+    }
+
+    async fn pay_out(
+        &self,
+        payout: &Payout,
+        ctx: &CorrelationContext,
+        now: DateTime<Utc>,
+        sequence: &mut Option<i64>,
+    ) -> anyhow::Result<PayoutOutcome> {
+        let tx = &payout.transaction;
+        let client = self.horizon_client.correlated(ctx);
+        let submitted = tx.status == TransactionStatus::Submitted.as_str();
+
+        if let (true, Some(hash), Some(envelope)) =
+            (submitted, &payout.payout_tx_hash, &payout.payout_envelope)
+        {
+            match client.get_transaction(hash).await? {
+                Some(landed) if landed.successful => {
+                    return self.complete(ctx, tx.id, &landed.hash, now).await;
+                }
+                Some(landed) => {
+                    let reason = format!("payment {} failed in ledger {}", landed.hash, landed.ledger);
+                    return self.fail(ctx, tx.id, TransactionStatus::Submitted, reason, now).await;
+                }
+                None => {
+                    let expired = payout
+                        .payout_valid_until
+                        .map_or(true, |valid_until| now > valid_until + LEDGER_GRACE);
+                    if !expired {
+                        tracing::debug!(hash = %hash, "Payment not in a ledger yet, sending it again");
+                        return self.submit(&client, ctx, tx.id, hash, envelope, false, now).await;
+                    }
+                    tracing::info!(hash = %hash, "Payment past its time bounds, signing a new one");
+                }
+            }
+        }
+
+        let from = if submitted {
+            TransactionStatus::Submitted
+        } else {
+            TransactionStatus::Pending
+        };
+        let Some(asset) = self.payment_asset(tx).await? else {
+            tracing::warn!("{} has no issuer in the asset registry, not paid out", tx.asset_code);
+            return Ok(PayoutOutcome::UnknownAsset);
+        };
+        let destination = match payout_destination(tx) {
+            Ok(destination) => destination,
+            Err(e) => return self.fail(ctx, tx.id, from, e.to_string(), now).await,
+        };
+        let amount = match to_stroops(&tx.amount) {
+            Ok(amount) => amount,
+            Err(e) => return self.fail(ctx, tx.id, from, e.to_string(), now).await,
+        };
+
+        let next_sequence = match *sequence {
+            Some(last) => last + 1,
+            None => self.current_sequence(&client).await? + 1,
+        };
+        let valid_until = now + chrono::Duration::from_std(self.config.submission_timeout)?;
+        let signed = PaymentTransaction {
+            source: *self.signing_key.verifying_key().as_bytes(),
+            sequence: next_sequence,
+            fee: self.config.base_fee,
+            valid_until,
+            destination,
+            asset,
+            amount,
+        }
+        .sign(&self.signing_key, &self.config.network_passphrase);
+
+        if !self.store_envelope(tx.id, from, &signed, valid_until).await? {
+            return Ok(PayoutOutcome::Skipped);
+        }
+        *sequence = Some(next_sequence);
+        if from == TransactionStatus::Pending {
+            publish_status_update(
+                &self.tx_broadcast,
+                ctx,
+                tx.id,
+                TransactionStatus::Submitted.as_str().to_string(),
+                Some("payment submitted to Stellar".to_string()),
+            );
+        }
+        self.submit(&client, ctx, tx.id, &signed.hash, &signed.envelope_xdr, true, now).await
+    }
+
+    /// Move the transaction to `submitted` and record the envelope; false
+    /// when it left `from` in the meantime
+    async fn store_envelope(
+        &self,
+        id: Uuid,
+        from: TransactionStatus,
+        signed: &SignedTransaction,
+        valid_until: DateTime<Utc>,
+    ) -> anyhow::Result<bool> {
+        uow::run(&self.pool, |uow| Box::pin(async move {
+            let locked = if from == TransactionStatus::Pending {
+                queries::update_transaction_status(
+                    uow.conn(),
+                    id,
+                    &[TransactionStatus::Pending.as_str()],
+                    TransactionStatus::Submitted.as_str(),
+                    ACTOR,
+                )
+                .await?
+                .is_some()
+            } else {
+                queries::lock_transaction_with_status(uow.conn(), id, from.as_str())
+                    .await?
+                    .is_some()
+            };
+            if locked {
+                queries::set_payout_envelope(
+                    uow.conn(),
+                    id,
+                    &signed.hash,
+                    &signed.envelope_xdr,
+                    valid_until,
+                )
+                .await?;
+            }
+            Ok::<_, anyhow::Error>(locked)
+        }))
+        .await
+    }
+
+    /// Send a stored envelope. `fresh`: it was signed in this pass, so a
+    /// rejection means it will never land and it is dropped; an envelope sent
+    /// before may still be queued from an earlier attempt and is kept until
+    /// it expires.
+    async fn submit(
+        &self,
+        client: &HorizonClient,
+        ctx: &CorrelationContext,
+        id: Uuid,
+        hash: &str,
+        envelope: &str,
+        fresh: bool,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<PayoutOutcome> {
+        let result = client.submit_transaction(&self.source_account(), envelope).await;
+        let codes = match result {
+            Ok(landed) if landed.successful => {
+                return self.complete(ctx, id, &landed.hash, now).await;
+            }
+            Ok(landed) => {
+                let reason = format!("payment {} failed in ledger {}", landed.hash, landed.ledger);
+                return self.fail(ctx, id, TransactionStatus::Submitted, reason, now).await;
+            }
+            Err(HorizonError::OperationFailed(codes)) => codes,
+            Err(HorizonError::TransactionFailed(code)) => vec![code],
+            Err(e) => {
+                tracing::warn!(hash = %hash, "Payment outcome unknown, checked next pass: {}", e);
+                return Ok(PayoutOutcome::Unconfirmed {
+                    hash: hash.to_string(),
+                });
+            }
+        };
+
+        match classify_rejection(&codes) {
+            Rejection::NoTrust => {
+                let codes = &codes;
+                let outcome = uow::run(&self.pool, |uow| Box::pin(async move {
+                    let moved = queries::update_transaction_status(
+                        uow.conn(),
+                        id,
+                        &[TransactionStatus::Submitted.as_str()],
+                        TransactionStatus::PendingTrustline.as_str(),
+                        ACTOR,
+                    )
+                    .await?;
+                    if moved.is_none() {
+                        return Ok::<_, anyhow::Error>(PayoutOutcome::Skipped);
+                    }
+                    queries::clear_payout_envelope(uow.conn(), id).await?;
+                    let metadata = json!({ "outcome": "pending_trustline", "codes": codes, "at": now });
+                    queries::set_transaction_metadata(uow.conn(), id, METADATA_KEY, metadata).await?;
+                    Ok(PayoutOutcome::PendingTrustline { codes: codes.clone() })
+                }))
+                .await?;
+                if outcome != PayoutOutcome::Skipped {
+                    tracing::info!(codes = ?codes, "Destination has no trustline, waiting for one");
+                    publish_status_update(
+                        &self.tx_broadcast,
+                        ctx,
+                        id,
+                        TransactionStatus::PendingTrustline.as_str().to_string(),
+                        Some("destination has no trustline for the asset".to_string()),
+                    );
+                }
+                Ok(outcome)
+            }
+            Rejection::Fatal => {
+                self.fail(ctx, id, TransactionStatus::Submitted, codes.join(", "), now).await
+            }
+            Rejection::Retry => {
+                tracing::error!(hash = %hash, codes = ?codes, "Payment rejected, signed again later");
+                let mut conn = self.pool.acquire().await?;
+                if fresh {
+                    queries::clear_payout_envelope(&mut *conn, id).await?;
+                }
+                let metadata = json!({ "outcome": "rejected", "codes": codes, "at": now });
+                queries::set_transaction_metadata(&mut *conn, id, METADATA_KEY, metadata).await?;
+                Ok(PayoutOutcome::Retry { codes })
+            }
+        }
+    }
+
+    async fn complete(
+        &self,
+        ctx: &CorrelationContext,
+        id: Uuid,
+        hash: &str,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<PayoutOutcome> {
+        let outcome = uow::run(&self.pool, |uow| Box::pin(async move {
+            let moved = queries::update_transaction_status(
+                uow.conn(),
+                id,
+                &[TransactionStatus::Submitted.as_str()],
+                TransactionStatus::Completed.as_str(),
+                ACTOR,
+            )
+            .await?;
+            if moved.is_none() {
+                return Ok::<_, anyhow::Error>(PayoutOutcome::Skipped);
+            }
+            queries::set_payout_tx_hash(uow.conn(), id, hash).await?;
+            let metadata = json!({ "outcome": "completed", "hash": hash, "at": now });
+            queries::set_transaction_metadata(uow.conn(), id, METADATA_KEY, metadata).await?;
+            Ok(PayoutOutcome::Completed { hash: hash.to_string() })
+        }))
+        .await?;
+
+        if outcome != PayoutOutcome::Skipped {
+            tracing::info!(hash = %hash, "Payment landed, completed");
+            publish_status_update(
+                &self.tx_broadcast,
+                ctx,
+                id,
+                TransactionStatus::Completed.as_str().to_string(),
+                Some(format!("paid out in {}", hash)),
+            );
+        }
+        Ok(outcome)
+    }
+
+    async fn fail(
+        &self,
+        ctx: &CorrelationContext,
+        id: Uuid,
+        from: TransactionStatus,
+        reason: String,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<PayoutOutcome> {
+        let reason_ref = &reason;
+        let outcome = uow::run(&self.pool, |uow| Box::pin(async move {
+            let moved = queries::update_transaction_status(
+                uow.conn(),
+                id,
+                &[from.as_str()],
+                TransactionStatus::Failed.as_str(),
+                ACTOR,
+            )
+            .await?;
+            if moved.is_none() {
+                return Ok::<_, anyhow::Error>(PayoutOutcome::Skipped);
+            }
+            queries::clear_payout_envelope(uow.conn(), id).await?;
+            let metadata = json!({ "outcome": "failed", "reason": reason_ref, "at": now });
+            queries::set_transaction_metadata(uow.conn(), id, METADATA_KEY, metadata).await?;
+            Ok(PayoutOutcome::Failed { reason: reason_ref.clone() })
+        }))
+        .await?;
+
+        if outcome != PayoutOutcome::Skipped {
+            tracing::warn!(reason = %reason, "Payout failed");
+            publish_status_update(
+                &self.tx_broadcast,
+                ctx,
+                id,
+                TransactionStatus::Failed.as_str().to_string(),
+                Some(reason),
+            );
+        }
+        Ok(outcome)
+    }
+
+    /// Lumens, or the transaction's asset if the registry still lists it with
+    /// that issuer; `None` otherwise, since paying in someone else's USDC is
+    /// worse than not paying
+    async fn payment_asset(&self, tx: &Transaction) -> anyhow::Result<Option<PaymentAsset>> {
+        if tx.asset_code == NATIVE_ASSET_CODE {
+            return Ok(Some(PaymentAsset::Native));
+        }
+        let Some(issuer) = tx.asset_issuer.as_deref() else {
+            return Ok(None);
+        };
+        match queries::get_asset_by_code_and_issuer(&self.pool, &tx.asset_code, issuer).await? {
+            Some(_) => Ok(Some(PaymentAsset::credit(&tx.asset_code, issuer)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn current_sequence(&self, client: &HorizonClient) -> anyhow::Result<i64> {
+        let account = client.get_account(&self.source_account()).await?;
+        account.sequence.parse().map_err(|_| {
+            anyhow::anyhow!("Horizon returned sequence {:?} for the source account", account.sequence)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::types::BigDecimal;
+
+    const ACCOUNT: &str = "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ";
+
+    fn codes(codes: &[&str]) -> Vec<String> {
+        codes.iter().map(|code| code.to_string()).collect()
+    }
+
+    #[test]
+    fn test_classify_rejection() {
+        assert_eq!(classify_rejection(&codes(&["op_no_trust"])), Rejection::NoTrust);
+        assert_eq!(classify_rejection(&codes(&["op_not_authorized"])), Rejection::NoTrust);
+        assert_eq!(classify_rejection(&codes(&["op_no_destination"])), Rejection::Fatal);
+        assert_eq!(classify_rejection(&codes(&["op_line_full"])), Rejection::Fatal);
+        assert_eq!(classify_rejection(&codes(&["op_underfunded"])), Rejection::Retry);
+        assert_eq!(classify_rejection(&codes(&["tx_bad_seq"])), Rejection::Retry);
+        assert_eq!(classify_rejection(&codes(&["tx_insufficient_fee"])), Rejection::Retry);
+        assert_eq!(classify_rejection(&codes(&["something_new"])), Rejection::Retry);
+    }
+
+    #[test]
+    fn test_payout_destination() {
+        let mut tx = Transaction::new(
+            ACCOUNT.to_string(),
+            BigDecimal::from(10),
+            "USDC".to_string(),
+            None,
+            Some("deposit".to_string()),
+            None,
+        );
+        let account = payout_destination(&tx).unwrap();
+        assert_eq!(account.base_address(), ACCOUNT);
+        assert_eq!(account.muxed_id(), None);
+
+        tx.muxed_id = Some(BigDecimal::from(42));
+        let muxed = payout_destination(&tx).unwrap();
+        assert_eq!(muxed.base_address(), ACCOUNT);
+        assert_eq!(muxed.muxed_id(), Some(42));
+
+        tx.muxed_id = Some(BigDecimal::from(-1));
+        assert!(payout_destination(&tx).is_err());
+
+        tx.stellar_account = "GNOTANACCOUNT".to_string();
+        tx.muxed_id = None;
+        assert!(payout_destination(&tx).is_err());
+    }
+}
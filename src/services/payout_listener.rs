@@ -0,0 +1,418 @@
+//! On-chain confirmation of payouts.
+//!
+//! The payment processor completes a payout from Horizon's answer to its
+//! submission, or from looking the hash up on a later pass. This listener
+//! confirms it from the ledger instead: it follows the source account's
+//! payments stream and matches each payment to the transaction whose
+//! `payout_tx_hash` it was sent in. A matching payment completes a
+//! `submitted` transaction; one whose destination, asset or amount differs
+//! from the transaction is flagged for review. The stream's cursor is kept in
+//! `stream_cursors`, so a restart resumes after the last payment handled.
+
+use serde_json::json;
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::db::models::Transaction;
+use crate::db::{queries, uow};
+use crate::domain::TransactionStatus;
+use crate::handlers::ws::TransactionStatusUpdate;
+use crate::metrics;
+use crate::services::payment_listener::NATIVE_ASSET_CODE;
+use crate::services::payment_processor::payout_destination;
+use crate::services::processor::publish_status_update;
+use crate::stellar::stream::PaymentStream;
+use crate::stellar::Operation;
+use crate::utils::correlation::CorrelationContext;
+use crate::utils::shutdown::Shutdown;
+
+/// Name of this listener's row in `stream_cursors`
+pub const CURSOR_NAME: &str = "payout_payments";
+
+/// Key under which the confirming payment is recorded in the transaction's
+/// metadata
+pub const METADATA_KEY: &str = "payout_confirmation";
+
+/// Actor on the audit entries of confirmed transactions
+const ACTOR: &str = "payout_listener";
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// What happened to a payment from the source account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfirmOutcome {
+    /// Moved this submitted transaction to `completed`
+    Completed(Uuid),
+    /// The transaction was already completed, by the payment processor
+    Confirmed(Uuid),
+    /// The payment doesn't match its transaction, which is flagged for review
+    Mismatch { id: Uuid, reason: String },
+    /// No transaction was paid out in the payment's Stellar transaction
+    Unknown,
+    /// The operation was already processed
+    Duplicate,
+    /// Not a `payment` sent by the source account
+    NotPayout,
+}
+
+/// Compare a payment with the transaction it pays out. `issuer` is the
+/// issuer of the transaction's asset, `None` for lumens or an asset without
+/// a known issuer.
+pub fn check_payout(tx: &Transaction, operation: &Operation, issuer: Option<&str>) -> Result<(), String> {
+    let destination = payout_destination(tx).map_err(|e| e.to_string())?;
+    let expected_to = destination.base_address();
+    if operation.to.as_deref() != Some(expected_to.as_str()) {
+        return Err(format!(
+            "paid to {} instead of {}",
+            operation.to.as_deref().unwrap_or("nobody"),
+            expected_to
+        ));
+    }
+
+    let paid_asset = match operation.asset_type.as_deref() {
+        Some("native") => (NATIVE_ASSET_CODE, None),
+        _ => (
+            operation.asset_code.as_deref().unwrap_or_default(),
+            operation.asset_issuer.as_deref(),
+        ),
+    };
+    let expected_asset = if tx.asset_code == NATIVE_ASSET_CODE {
+        (NATIVE_ASSET_CODE, None)
+    } else {
+        (tx.asset_code.as_str(), Some(issuer.ok_or("transaction has no asset issuer")?))
+    };
+    if paid_asset != expected_asset {
+        return Err(format!(
+            "paid in {}{} instead of {}",
+            paid_asset.0,
+            paid_asset.1.map(|issuer| format!(":{}", issuer)).unwrap_or_default(),
+            tx.asset_code
+        ));
+    }
+
+    let amount = operation.amount.as_deref().unwrap_or_default();
+    match BigDecimal::from_str(amount) {
+        Ok(paid) if paid == tx.amount => Ok(()),
+        _ => Err(format!("paid {} instead of {}", amount, tx.amount)),
+    }
+}
+
+/// Follows the payments the payment processor's source account makes and
+/// confirms each payout against its transaction
+#[derive(Clone)]
+pub struct PayoutListener {
+    pool: PgPool,
+    stream: PaymentStream,
+    tx_broadcast: broadcast::Sender<TransactionStatusUpdate>,
+    shutdown: Shutdown,
+}
+
+impl PayoutListener {
+    pub fn new(
+        pool: PgPool,
+        stream: PaymentStream,
+        tx_broadcast: broadcast::Sender<TransactionStatusUpdate>,
+    ) -> Self {
+        Self {
+            pool,
+            stream,
+            tx_broadcast,
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    /// Stop between payments once `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Keep the stream open, reconnecting from the stored cursor with a
+    /// backoff when it drops
+    pub fn start(&self) {
+        let listener = self.clone();
+        self.shutdown.spawn(async move {
+            let mut backoff = MIN_BACKOFF;
+            while !listener.shutdown.is_cancelled() {
+                match listener.follow().await {
+                    Ok(0) => tracing::debug!("Payout stream closed"),
+                    Ok(handled) => {
+                        tracing::debug!("Payout stream closed after {} payments", handled);
+                        backoff = MIN_BACKOFF;
+                    }
+                    Err(e) => tracing::warn!("Payout stream failed: {:?}", e),
+                }
+                tokio::select! {
+                    _ = listener.shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(backoff) => {}
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            tracing::info!("Payout listener stopped");
+        });
+    }
+
+    /// Handle payments from the stored cursor until the connection ends or
+    /// shutdown begins. Returns the number of payments handled.
+    pub async fn follow(&self) -> anyhow::Result<usize> {
+        let cursor = queries::get_stream_cursor(&self.pool, CURSOR_NAME).await?;
+        let mut events = self.stream.connect(cursor.as_deref()).await?;
+        let mut handled = 0;
+        loop {
+            let next = tokio::select! {
+                _ = self.shutdown.cancelled() => break,
+                next = events.next() => next?,
+            };
+            let Some(operation) = next else {
+                break;
+            };
+            match self.handle_operation(&operation).await {
+                Ok(_) => {}
+                // The database is down: resume from this payment on reconnect
+                Err(e) if is_transient(&e) => return Err(e),
+                // Retrying would fail the same way and hold up every later payout
+                Err(e) => self.skip_operation(&operation, &e).await?,
+            }
+            queries::save_stream_cursor(&self.pool, CURSOR_NAME, &operation.paging_token).await?;
+            handled += 1;
+        }
+        Ok(handled)
+    }
+
+    /// Confirm a payout, at most once per operation
+    pub async fn handle_operation(&self, operation: &Operation) -> anyhow::Result<ConfirmOutcome> {
+        let hash = match &operation.transaction_hash {
+            Some(hash)
+                if operation.operation_type == "payment"
+                    && operation.from.as_deref() == Some(self.stream.account()) =>
+            {
+                hash
+            }
+            _ => return Ok(ConfirmOutcome::NotPayout),
+        };
+
+        let pool = &self.pool;
+        let outcome = uow::run(pool, |uow| Box::pin(async move {
+            if !queries::mark_operation_processed(uow.conn(), &operation.id, "payment").await? {
+                return Ok::<_, anyhow::Error>(ConfirmOutcome::Duplicate);
+            }
+            let Some(tx) = queries::lock_transaction_by_payout_hash(uow.conn(), hash).await? else {
+                return Ok(ConfirmOutcome::Unknown);
+            };
+            queries::set_operation_transaction(uow.conn(), &operation.id, tx.id).await?;
+
+            let status = tx.current_status().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            let issuer = tx.asset_issuer.as_deref();
+            let checked = check_payout(&tx, operation, issuer).and_then(|()| match status {
+                TransactionStatus::Submitted | TransactionStatus::Completed => Ok(()),
+                other => Err(format!("landed while the transaction is {}", other.as_str())),
+            });
+
+            let mut metadata = json!({
+                "operation_id": operation.id,
+                "hash": hash,
+                "amount": operation.amount,
+                "at": operation.created_at,
+            });
+            let outcome = match checked {
+                Err(reason) => {
+                    let reason = format!("payout {}: {}", hash, reason);
+                    queries::flag_transaction_for_review(uow.conn(), tx.id, &reason).await?;
+                    metadata["mismatch"] = json!(reason);
+                    ConfirmOutcome::Mismatch { id: tx.id, reason }
+                }
+                Ok(()) if status == TransactionStatus::Completed => ConfirmOutcome::Confirmed(tx.id),
+                Ok(()) => {
+                    let reason = format!("payment {} in ledger", operation.id);
+                    queries::update_transaction_status_with_reason(
+                        uow.conn(),
+                        tx.id,
+                        &[tx.status.as_str()],
+                        TransactionStatus::Completed.as_str(),
+                        Some(&reason),
+                        ACTOR,
+                    )
+                    .await?;
+                    ConfirmOutcome::Completed(tx.id)
+                }
+            };
+            queries::set_transaction_metadata(uow.conn(), tx.id, METADATA_KEY, metadata).await?;
+            Ok(outcome)
+        }))
+        .await?;
+
+        self.report(operation, hash, &outcome).await?;
+        Ok(outcome)
+    }
+
+    /// Flag the transaction a payment failed on, so the cursor can move past
+    /// it
+    async fn skip_operation(&self, operation: &Operation, error: &anyhow::Error) -> anyhow::Result<()> {
+        let hash = operation.transaction_hash.as_deref().unwrap_or_default();
+        let reason = format!("payout {}: operation {} failed: {}", hash, operation.id, error);
+        let reason = &reason;
+        let flagged = uow::run(&self.pool, |uow| Box::pin(async move {
+            let Some(tx) = queries::lock_transaction_by_payout_hash(uow.conn(), hash).await? else {
+                return Ok::<_, anyhow::Error>(None);
+            };
+            queries::flag_transaction_for_review(uow.conn(), tx.id, reason).await?;
+            Ok(Some(tx.id))
+        }))
+        .await?;
+        tracing::error!(
+            operation_id = %operation.id,
+            transaction_id = ?flagged,
+            hash = %hash,
+            "Skipped payout operation: {}",
+            error
+        );
+        metrics::record_horizon_operation("payment", "payout_failed");
+        Ok(())
+    }
+
+    async fn report(&self, operation: &Operation, hash: &str, outcome: &ConfirmOutcome) -> anyhow::Result<()> {
+        let id = match outcome {
+            ConfirmOutcome::Completed(id)
+            | ConfirmOutcome::Confirmed(id)
+            | ConfirmOutcome::Mismatch { id, .. } => *id,
+            ConfirmOutcome::Unknown => {
+                tracing::warn!(
+                    operation_id = %operation.id,
+                    hash = %hash,
+                    to = ?operation.to,
+                    "Payment of {} from the source account pays out no transaction",
+                    operation.amount.as_deref().unwrap_or_default()
+                );
+                metrics::record_horizon_operation("payment", "unknown_payout");
+                return Ok(());
+            }
+            ConfirmOutcome::Duplicate | ConfirmOutcome::NotPayout => return Ok(()),
+        };
+
+        let tx = queries::get_transaction(&self.pool, id).await?;
+        let correlation_id = CorrelationContext::for_transaction(&tx);
+        match outcome {
+            ConfirmOutcome::Completed(_) => {
+                tracing::info!(
+                    transaction_id = %id,
+                    correlation_id = %correlation_id,
+                    hash = %hash,
+                    "Payout in ledger, completed"
+                );
+                metrics::record_horizon_operation("payment", "payout_completed");
+                publish_status_update(
+                    &self.tx_broadcast,
+                    &correlation_id,
+                    id,
+                    TransactionStatus::Completed.as_str().to_string(),
+                    Some(format!("paid out in {}", hash)),
+                );
+            }
+            ConfirmOutcome::Mismatch { reason, .. } => {
+                tracing::error!(
+                    transaction_id = %id,
+                    correlation_id = %correlation_id,
+                    "Payout doesn't match its transaction, flagged for review: {}",
+                    reason
+                );
+                metrics::record_horizon_operation("payment", "payout_mismatch");
+            }
+            _ => {
+                tracing::debug!(transaction_id = %id, hash = %hash, "Payout confirmed");
+                metrics::record_horizon_operation("payment", "payout_confirmed");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether handling a payment failed on the database rather than on the
+/// payment itself
+fn is_transient(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.is::<sqlx::Error>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stellar::strkey;
+
+    const ISSUER: &str = "GISSUER";
+
+    fn account(byte: u8) -> String {
+        strkey::encode_ed25519(&[byte; 32])
+    }
+
+    fn transaction(asset_code: &str) -> Transaction {
+        Transaction::new(
+            account(1),
+            BigDecimal::from_str("12.5").unwrap(),
+            asset_code.to_string(),
+            None,
+            Some("deposit".to_string()),
+            None,
+        )
+    }
+
+    fn payment(asset_code: Option<&str>, amount: &str) -> Operation {
+        Operation {
+            id: "12884905985".to_string(),
+            paging_token: "12884905985".to_string(),
+            operation_type: "payment".to_string(),
+            transaction_hash: Some("abc123".to_string()),
+            from: Some(account(2)),
+            to: Some(account(1)),
+            amount: Some(amount.to_string()),
+            asset_type: Some(if asset_code.is_some() { "credit_alphanum4" } else { "native" }.to_string()),
+            asset_code: asset_code.map(str::to_string),
+            asset_issuer: asset_code.map(|_| ISSUER.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_matching_payout() {
+        assert_eq!(check_payout(&transaction("XLM"), &payment(None, "12.5000000"), None), Ok(()));
+        assert_eq!(
+            check_payout(&transaction("USDC"), &payment(Some("USDC"), "12.5000000"), Some(ISSUER)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_muxed_payout_is_paid_to_the_base_account() {
+        let mut tx = transaction("XLM");
+        tx.muxed_id = Some(BigDecimal::from(42));
+        assert_eq!(check_payout(&tx, &payment(None, "12.5"), None), Ok(()));
+    }
+
+    #[test]
+    fn test_mismatched_payouts() {
+        let tx = transaction("USDC");
+        let amount = check_payout(&tx, &payment(Some("USDC"), "12.4999999"), Some(ISSUER)).unwrap_err();
+        assert!(amount.contains("12.4999999"), "{}", amount);
+
+        let asset = check_payout(&tx, &payment(None, "12.5"), Some(ISSUER)).unwrap_err();
+        assert!(asset.contains("XLM"), "{}", asset);
+        let issuer = check_payout(&tx, &payment(Some("USDC"), "12.5"), Some("GOTHER")).unwrap_err();
+        assert!(issuer.contains(ISSUER), "{}", issuer);
+        assert!(check_payout(&tx, &payment(Some("USDC"), "12.5"), None).is_err());
+
+        let mut elsewhere = payment(Some("USDC"), "12.5");
+        elsewhere.to = Some(account(3));
+        let destination = check_payout(&tx, &elsewhere, Some(ISSUER)).unwrap_err();
+        assert!(destination.contains(&account(3)), "{}", destination);
+    }
+
+    #[test]
+    fn test_database_errors_are_transient() {
+        assert!(is_transient(&anyhow::Error::from(sqlx::Error::PoolTimedOut)));
+        assert!(is_transient(&anyhow::Error::from(sqlx::Error::PoolTimedOut).context("locking")));
+        assert!(!is_transient(&anyhow::anyhow!("Invalid status: bogus")));
+    }
+}
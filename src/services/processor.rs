@@ -2,16 +2,20 @@ use tokio::sync::broadcast;
 use uuid::Uuid;
 
 use crate::handlers::ws::TransactionStatusUpdate;
+use crate::utils::correlation::CorrelationContext;
 
 /// Publish a transaction status update to all WebSocket clients
 pub fn publish_status_update(
     tx: &broadcast::Sender<TransactionStatusUpdate>,
+    ctx: &CorrelationContext,
     transaction_id: Uuid,
     status: String,
     message: Option<String>,
 ) {
+    let _span = ctx.span("publish_status_update").entered();
     let update = TransactionStatusUpdate {
         transaction_id,
+        correlation_id: ctx.to_string(),
         status,
         timestamp: chrono::Utc::now(),
         message,
@@ -0,0 +1,265 @@
+//! Firm SEP-38 quotes for withdrawals.
+//!
+//! Quotes are requested from the Anchor Platform and stored when accepted. A
+//! withdrawal callback may then name one with `quote_id`; it is checked
+//! against the callback's asset, issuer included, and amount and linked to
+//! the new transaction,
+//! so each quote prices at most one withdrawal.
+
+use chrono::{DateTime, Utc};
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use thiserror::Error;
+
+use crate::db::models::Quote;
+use crate::db::queries;
+use crate::error::AppError;
+use crate::stellar::quotes::{self, QuoteClient, QuoteError, QuoteRequest};
+use crate::utils::clock::{self, SharedClock};
+
+/// Why a callback's `quote_id` was refused. Each maps to a validation error
+/// whose message starts with a stable code.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum QuoteRejection {
+    #[error("quote_not_found: quote {0} does not exist")]
+    NotFound(String),
+    #[error("quote_expired: quote {id} expired at {expires_at}")]
+    Expired { id: String, expires_at: DateTime<Utc> },
+    #[error("quote_used: quote {0} already priced another transaction")]
+    AlreadyUsed(String),
+    #[error("quote_asset_mismatch: quote {id} sells {quoted}, not {asset}")]
+    AssetMismatch {
+        id: String,
+        quoted: String,
+        /// `CODE:ISSUER`, or the code alone without an issuer
+        asset: String,
+    },
+    #[error("quote_amount_mismatch: amount {amount} is not within tolerance of quoted {quoted}")]
+    AmountMismatch { amount: BigDecimal, quoted: BigDecimal },
+    /// Used or expired between verification and linking
+    #[error("quote_unavailable: quote {0} is no longer available")]
+    Unavailable(String),
+}
+
+impl From<QuoteRejection> for AppError {
+    fn from(rejection: QuoteRejection) -> Self {
+        AppError::Validation(rejection.to_string())
+    }
+}
+
+/// Check that `quote` may price a withdrawal of `amount` `asset_code` issued
+/// by `asset_issuer`. `tolerance` is relative: 0.01 accepts amounts within
+/// 1% of the quoted sell amount.
+pub fn check_quote(
+    quote: &Quote,
+    asset_code: &str,
+    asset_issuer: Option<&str>,
+    amount: &BigDecimal,
+    tolerance: &BigDecimal,
+    now: DateTime<Utc>,
+) -> Result<(), QuoteRejection> {
+    if quote.transaction_id.is_some() {
+        return Err(QuoteRejection::AlreadyUsed(quote.id.clone()));
+    }
+    if quote.expires_at <= now {
+        return Err(QuoteRejection::Expired {
+            id: quote.id.clone(),
+            expires_at: quote.expires_at,
+        });
+    }
+    let sold = &quote.sell_asset;
+    if (quotes::asset_code(sold), quotes::asset_issuer(sold)) != (Some(asset_code), asset_issuer) {
+        return Err(QuoteRejection::AssetMismatch {
+            id: quote.id.clone(),
+            quoted: quote.sell_asset.clone(),
+            asset: match asset_issuer {
+                Some(issuer) => format!("{}:{}", asset_code, issuer),
+                None => asset_code.to_string(),
+            },
+        });
+    }
+    if (amount - &quote.sell_amount).abs() > &quote.sell_amount * tolerance {
+        return Err(QuoteRejection::AmountMismatch {
+            amount: amount.clone(),
+            quoted: quote.sell_amount.clone(),
+        });
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct QuoteService {
+    pool: PgPool,
+    /// `None` when no SEP-38 server is configured; stored quotes still verify
+    client: Option<QuoteClient>,
+    amount_tolerance: BigDecimal,
+    clock: SharedClock,
+}
+
+impl QuoteService {
+    pub fn new(pool: PgPool, client: Option<QuoteClient>, amount_tolerance: BigDecimal) -> Self {
+        Self {
+            pool,
+            client,
+            amount_tolerance,
+            clock: clock::system(),
+        }
+    }
+
+    /// Judge expiry against `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Request a firm quote from the Anchor Platform and store it
+    pub async fn accept(&self, request: &QuoteRequest) -> Result<Quote, AppError> {
+        let client = self
+            .client
+            .as_ref()
+            .ok_or_else(|| AppError::BadRequest("SEP-38 quotes are not configured".to_string()))?;
+        if request.sell_amount.is_some() == request.buy_amount.is_some() {
+            return Err(AppError::Validation(
+                "exactly one of sell_amount and buy_amount is required".to_string(),
+            ));
+        }
+
+        let firm = client.request_quote(request).await.map_err(|e| match e {
+            QuoteError::Rejected { status, message } if (400..500).contains(&status) => {
+                AppError::BadRequest(format!("quote rejected: {}", message))
+            }
+            e => AppError::Internal(e.to_string()),
+        })?;
+        let quote = queries::insert_quote(&self.pool, &Quote::from(firm)).await?;
+        tracing::info!(
+            quote_id = %quote.id,
+            sell_asset = %quote.sell_asset,
+            buy_asset = %quote.buy_asset,
+            expires_at = %quote.expires_at,
+            "Accepted SEP-38 quote"
+        );
+        Ok(quote)
+    }
+
+    /// Look up `quote_id` and check it against a withdrawal callback
+    pub async fn verify(
+        &self,
+        quote_id: &str,
+        asset_code: &str,
+        asset_issuer: Option<&str>,
+        amount: &BigDecimal,
+    ) -> Result<Quote, AppError> {
+        let quote = queries::get_quote(&self.pool, quote_id)
+            .await?
+            .ok_or_else(|| QuoteRejection::NotFound(quote_id.to_string()))?;
+        let now = self.clock.now();
+        check_quote(&quote, asset_code, asset_issuer, amount, &self.amount_tolerance, now)?;
+        Ok(quote)
+    }
+
+    /// Delete unused quotes that expired more than `retention` ago
+    pub async fn purge_expired(&self, retention: chrono::Duration) -> Result<u64, AppError> {
+        let purged = queries::purge_unused_quotes(&self.pool, self.clock.now() - retention).await?;
+        if purged > 0 {
+            tracing::info!("Purged {} expired unused quotes", purged);
+        }
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    fn quote(sell_amount: &str) -> Quote {
+        Quote {
+            id: "quote-1".to_string(),
+            sell_asset: "stellar:USDC:GA5ZSEJYB37JRC5AVCIA5MOP4RHTM335X2KGX3IHOJAPP5RE34K4KZVN".to_string(),
+            buy_asset: "iso4217:USD".to_string(),
+            sell_amount: sell_amount.parse().unwrap(),
+            buy_amount: "100".parse().unwrap(),
+            rate: "1".parse().unwrap(),
+            total_price: "1.02".parse().unwrap(),
+            fee_total: "2".parse().unwrap(),
+            fee_asset: "iso4217:USD".to_string(),
+            expires_at: Utc::now() + Duration::minutes(10),
+            transaction_id: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    const ISSUER: &str = "GA5ZSEJYB37JRC5AVCIA5MOP4RHTM335X2KGX3IHOJAPP5RE34K4KZVN";
+
+    fn check(quote: &Quote, asset_code: &str, amount: &str) -> Result<(), QuoteRejection> {
+        check_issued(quote, asset_code, Some(ISSUER), amount)
+    }
+
+    fn check_issued(
+        quote: &Quote,
+        asset_code: &str,
+        asset_issuer: Option<&str>,
+        amount: &str,
+    ) -> Result<(), QuoteRejection> {
+        let (amount, tolerance) = (amount.parse().unwrap(), "0.01".parse().unwrap());
+        check_quote(quote, asset_code, asset_issuer, &amount, &tolerance, Utc::now())
+    }
+
+    #[test]
+    fn test_matching_quote_is_accepted_within_tolerance() {
+        let quote = quote("102");
+        assert_eq!(check(&quote, "USDC", "102"), Ok(()));
+        assert_eq!(check(&quote, "USDC", "101.00"), Ok(()));
+        assert_eq!(check(&quote, "USDC", "103.02"), Ok(()));
+    }
+
+    #[test]
+    fn test_amount_outside_tolerance_is_rejected() {
+        let quote = quote("102");
+        assert!(matches!(check(&quote, "USDC", "100"), Err(QuoteRejection::AmountMismatch { .. })));
+        assert!(matches!(check(&quote, "USDC", "103.03"), Err(QuoteRejection::AmountMismatch { .. })));
+    }
+
+    #[test]
+    fn test_other_asset_is_rejected() {
+        assert!(matches!(
+            check(&quote("102"), "USD", "102"),
+            Err(QuoteRejection::AssetMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_same_code_from_another_issuer_is_rejected() {
+        let other = "GBBD47IF6LWK7P7MDEVSCWR7DPUWV3NY3DTQEVFL4NAT4AQH3ZLLFLA5";
+        let rejection = check_issued(&quote("102"), "USDC", Some(other), "102").unwrap_err();
+        match rejection {
+            QuoteRejection::AssetMismatch { asset, .. } => {
+                assert_eq!(asset, format!("USDC:{}", other))
+            }
+            other => panic!("unexpected rejection: {:?}", other),
+        }
+        assert!(check_issued(&quote("102"), "USDC", None, "102").is_err());
+    }
+
+    #[test]
+    fn test_expired_quote_is_a_specific_validation_error() {
+        let mut expired = quote("102");
+        expired.expires_at = Utc::now() - Duration::seconds(1);
+        let rejection = check(&expired, "USDC", "102").unwrap_err();
+        assert!(matches!(rejection, QuoteRejection::Expired { .. }));
+
+        match AppError::from(rejection) {
+            AppError::Validation(message) => assert!(message.starts_with("quote_expired:")),
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_used_quote_is_rejected_before_other_checks() {
+        let mut used = quote("102");
+        used.transaction_id = Some(Uuid::new_v4());
+        used.expires_at = Utc::now() - Duration::seconds(1);
+        assert_eq!(check(&used, "USD", "1"), Err(QuoteRejection::AlreadyUsed("quote-1".to_string())));
+    }
+}
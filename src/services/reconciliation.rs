@@ -0,0 +1,492 @@
+//! Reconciliation of pending deposits against Horizon.
+//!
+//! A deposit stays `pending` until the anchor's callback reports it done. When
+//! that callback never arrives, the worker looks for the payment itself: it
+//! pages through the payments received by the transaction's `stellar_account`
+//! and completes the transaction once one from the source account has its
+//! asset and amount. A transaction pending longer than the maximum age without
+//! such a payment is failed. Deposits the payment processor has claimed are
+//! left to it.
+
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use std::str::FromStr;
+use tokio::sync::broadcast;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::config::ReconciliationConfig;
+use crate::db::models::Transaction;
+use crate::db::{queries, uow};
+use crate::domain::TransactionStatus;
+use crate::handlers::ws::TransactionStatusUpdate;
+use crate::services::payment_listener::{
+    parse_operation, IncomingPayment, ParsedOperation, NATIVE_ASSET_CODE,
+};
+use crate::services::processor::publish_status_update;
+use crate::stellar::{HorizonClient, HorizonError, Operation, Order};
+use crate::utils::clock::{self, SharedClock, Ticker};
+use crate::utils::correlation::CorrelationContext;
+use crate::utils::shutdown::Shutdown;
+
+/// Key under which the outcome is recorded in the transaction's metadata
+pub const METADATA_KEY: &str = "reconciliation";
+
+/// Actor on the audit entries of reconciled transactions
+const ACTOR: &str = "reconciliation";
+const PAGE_SIZE: u32 = 200;
+/// Pages read per transaction; older payments are left to the next pass
+const MAX_PAGES: usize = 5;
+
+/// What a transaction should have received
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectedPayment {
+    pub asset_code: String,
+    /// `None` for lumens
+    pub asset_issuer: Option<String>,
+    pub amount: BigDecimal,
+}
+
+/// Lumens, or the transaction's asset with its issuer. Anyone can issue an
+/// asset called USDC, so an asset without a known issuer never matches.
+fn expected_payment(tx: &Transaction) -> Option<ExpectedPayment> {
+    let asset_issuer = if tx.asset_code == NATIVE_ASSET_CODE {
+        None
+    } else {
+        Some(tx.asset_issuer.clone()?)
+    };
+    Some(ExpectedPayment {
+        asset_code: tx.asset_code.clone(),
+        asset_issuer,
+        amount: tx.amount.clone(),
+    })
+}
+
+/// Payments into the account in the expected asset
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PaymentMatch {
+    /// Exactly the expected amount
+    pub full: Vec<IncomingPayment>,
+    /// Less than the expected amount
+    pub partial: Vec<IncomingPayment>,
+}
+
+/// Sort the payments `account` received from `source` since `since` by how
+/// they compare to `expected`. Other senders, other assets, larger amounts and
+/// outgoing operations are left out.
+pub fn match_payment(
+    expected: &ExpectedPayment,
+    source: &str,
+    account: &str,
+    since: DateTime<Utc>,
+    operations: &[Operation],
+) -> PaymentMatch {
+    let mut found = PaymentMatch::default();
+    for operation in operations {
+        if operation.created_at.is_some_and(|created_at| created_at < since) {
+            continue;
+        }
+        let ParsedOperation::Incoming(payment) = parse_operation(operation, account) else {
+            continue;
+        };
+        // Anyone can send the customer the amount; only our payout pays for it
+        if payment.from != source {
+            continue;
+        }
+        if payment.received.asset_code != expected.asset_code
+            || payment.received.asset_issuer != expected.asset_issuer
+        {
+            continue;
+        }
+        let Ok(amount) = BigDecimal::from_str(&payment.received.amount) else {
+            continue;
+        };
+        if amount == expected.amount {
+            found.full.push(payment);
+        } else if amount < expected.amount {
+            found.partial.push(payment);
+        }
+    }
+    found
+}
+
+/// What a pass did with one transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReconcileOutcome {
+    /// Completed, paid by this operation
+    Completed { operation_id: String },
+    /// Only payments smaller than the transaction arrived; it stays pending
+    PartialAmount { received: Vec<String> },
+    /// No payment yet; it stays pending
+    NotFound,
+    /// Pending past the maximum age without a payment; failed
+    Expired,
+    /// No longer pending once locked
+    Skipped,
+}
+
+/// Periodically claims pending deposits older than the minimum age and
+/// checks Horizon for their payment
+#[derive(Clone)]
+pub struct ReconciliationWorker {
+    pool: PgPool,
+    horizon_client: HorizonClient,
+    tx_broadcast: broadcast::Sender<TransactionStatusUpdate>,
+    config: ReconciliationConfig,
+    /// The `G` account deposits are paid from
+    source_account: String,
+    clock: SharedClock,
+    shutdown: Shutdown,
+}
+
+impl ReconciliationWorker {
+    pub fn new(
+        pool: PgPool,
+        horizon_client: HorizonClient,
+        tx_broadcast: broadcast::Sender<TransactionStatusUpdate>,
+        config: ReconciliationConfig,
+        source_account: String,
+    ) -> Self {
+        Self {
+            pool,
+            horizon_client,
+            tx_broadcast,
+            config,
+            source_account,
+            clock: clock::system(),
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    /// Measure transaction ages on `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Stop between passes once `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    pub fn start(&self) {
+        let worker = self.clone();
+        self.shutdown.spawn(async move {
+            let mut ticker = Ticker::new(worker.clock.clone(), worker.config.poll_interval);
+            loop {
+                tokio::select! {
+                    _ = worker.shutdown.cancelled() => break,
+                    _ = ticker.tick() => {}
+                }
+                if let Err(e) = worker.run_once().await {
+                    tracing::error!("Reconciliation pass failed: {:?}", e);
+                }
+            }
+            tracing::info!("Reconciliation worker stopped");
+        });
+    }
+
+    /// Claim one batch and reconcile each transaction in it. A failure on one
+    /// transaction is logged and leaves it for a later pass.
+    pub async fn run_once(&self) -> anyhow::Result<Vec<(Uuid, ReconcileOutcome)>> {
+        let now = self.clock.now();
+        let min_age = chrono::Duration::from_std(self.config.min_age)?;
+        // Until the next pass, other workers leave a claimed transaction alone
+        let lease = chrono::Duration::from_std(self.config.poll_interval)?;
+        let claimed = queries::claim_transactions_for_reconciliation(
+            &self.pool,
+            now - min_age,
+            now,
+            now + lease,
+            self.config.batch_size,
+        )
+        .await?;
+
+        let mut outcomes = Vec::with_capacity(claimed.len());
+        for tx in claimed {
+            let ctx = CorrelationContext::for_transaction(&tx);
+            let span = tracing::info_span!(
+                "reconcile_transaction",
+                transaction_id = %tx.id,
+                correlation_id = %ctx
+            );
+            let outcome = async {
+                match self.reconcile(&tx, &ctx, now).await {
+                    Ok(outcome) => Some(outcome),
+                    Err(e) => {
+                        tracing::error!("Reconciliation failed: {:?}", e);
+                        None
+                    }
+                }
+            }
+            .instrument(span)
+            .await;
+            if let Some(outcome) = outcome {
+                outcomes.push((tx.id, outcome));
+            }
+        }
+        Ok(outcomes)
+    }
+
+    async fn reconcile(
+        &self,
+        tx: &Transaction,
+        ctx: &CorrelationContext,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<ReconcileOutcome> {
+        let found = match expected_payment(tx) {
+            Some(expected) => {
+                let operations = self.recent_payments(tx, ctx).await?;
+                match_payment(
+                    &expected,
+                    &self.source_account,
+                    &tx.stellar_account,
+                    tx.created_at,
+                    &operations,
+                )
+            }
+            None => {
+                tracing::debug!("{} has no known issuer, nothing to match", tx.asset_code);
+                PaymentMatch::default()
+            }
+        };
+        let expired = now - tx.created_at >= chrono::Duration::from_std(self.config.max_age)?;
+
+        let id = tx.id;
+        let found = &found;
+        let outcome = uow::run(&self.pool, |uow| Box::pin(async move {
+            // Re-checked under the row lock; a callback or a payout claim in the
+            // meantime wins
+            if queries::lock_reconcilable_transaction(uow.conn(), id).await?.is_none() {
+                return Ok::<_, anyhow::Error>(ReconcileOutcome::Skipped);
+            }
+
+            // An operation pays for one transaction only
+            let mut payment = None;
+            for candidate in &found.full {
+                if queries::mark_operation_processed(
+                    uow.conn(),
+                    &candidate.operation_id,
+                    candidate.operation_type,
+                )
+                .await?
+                {
+                    payment = Some(candidate);
+                    break;
+                }
+            }
+
+            if let Some(payment) = payment {
+                queries::update_transaction_status(
+                    uow.conn(),
+                    id,
+                    &[TransactionStatus::Pending.as_str()],
+                    TransactionStatus::Completed.as_str(),
+                    ACTOR,
+                )
+                .await?;
+                queries::set_operation_transaction(uow.conn(), &payment.operation_id, id).await?;
+                let metadata = json!({
+                    "outcome": "completed",
+                    "payment": payment,
+                    "checked_at": now,
+                });
+                queries::set_transaction_metadata(uow.conn(), id, METADATA_KEY, metadata).await?;
+                return Ok(ReconcileOutcome::Completed {
+                    operation_id: payment.operation_id.clone(),
+                });
+            }
+
+            if expired {
+                queries::update_transaction_status(
+                    uow.conn(),
+                    id,
+                    &[TransactionStatus::Pending.as_str()],
+                    TransactionStatus::Failed.as_str(),
+                    ACTOR,
+                )
+                .await?;
+                let metadata = json!({ "outcome": "expired", "checked_at": now });
+                queries::set_transaction_metadata(uow.conn(), id, METADATA_KEY, metadata).await?;
+                return Ok(ReconcileOutcome::Expired);
+            }
+
+            if found.partial.is_empty() {
+                return Ok(ReconcileOutcome::NotFound);
+            }
+            Ok(ReconcileOutcome::PartialAmount {
+                received: found.partial.iter().map(|p| p.received.amount.clone()).collect(),
+            })
+        }))
+        .await?;
+
+        match &outcome {
+            ReconcileOutcome::Completed { operation_id } => {
+                tracing::info!(operation_id = %operation_id, "Payment found on Horizon, completed");
+                publish_status_update(
+                    &self.tx_broadcast,
+                    ctx,
+                    id,
+                    TransactionStatus::Completed.as_str().to_string(),
+                    Some("payment found on Horizon".to_string()),
+                );
+            }
+            ReconcileOutcome::Expired => {
+                tracing::warn!("No payment found within {:?}, failed", self.config.max_age);
+                publish_status_update(
+                    &self.tx_broadcast,
+                    ctx,
+                    id,
+                    TransactionStatus::Failed.as_str().to_string(),
+                    Some("no payment found on Horizon".to_string()),
+                );
+            }
+            ReconcileOutcome::PartialAmount { received } => {
+                tracing::warn!(
+                    expected = %tx.amount,
+                    received = ?received,
+                    "Only partial payments of {} found",
+                    tx.asset_code
+                );
+            }
+            ReconcileOutcome::NotFound => tracing::debug!("No payment found yet"),
+            ReconcileOutcome::Skipped => {}
+        }
+        Ok(outcome)
+    }
+
+    /// The account's payments, newest first, back to the transaction's creation
+    async fn recent_payments(
+        &self,
+        tx: &Transaction,
+        ctx: &CorrelationContext,
+    ) -> anyhow::Result<Vec<Operation>> {
+        let client = self.horizon_client.correlated(ctx);
+        let mut operations = Vec::new();
+        let account = &tx.stellar_account;
+        let mut cursor = None;
+        for _ in 0..MAX_PAGES {
+            let page = client
+                .get_account_payments(account, cursor.as_deref(), PAGE_SIZE, Order::Desc)
+                .await;
+            let page = match page {
+                Ok(page) => page,
+                // Not funded yet, so nothing can have been paid to it
+                Err(HorizonError::AccountNotFound(_)) => break,
+                Err(e) => return Err(e.into()),
+            };
+            let reached_creation = page
+                .records
+                .last()
+                .and_then(|operation| operation.created_at)
+                .is_some_and(|created_at| created_at < tx.created_at);
+            operations.extend(page.records);
+            match page.next_cursor {
+                Some(next) if !reached_creation => cursor = Some(next),
+                _ => break,
+            }
+        }
+        Ok(operations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const SOURCE: &str = "GANCHOR";
+    const ACCOUNT: &str = "GCUSTOMER";
+    const ISSUER: &str = "GISSUER";
+
+    fn since() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 0).unwrap()
+    }
+
+    fn expected() -> ExpectedPayment {
+        ExpectedPayment {
+            asset_code: "USDC".to_string(),
+            asset_issuer: Some(ISSUER.to_string()),
+            amount: "100".parse().unwrap(),
+        }
+    }
+
+    fn payment(id: &str, amount: &str, minutes_after: i64) -> Operation {
+        Operation {
+            id: id.to_string(),
+            paging_token: id.to_string(),
+            operation_type: "payment".to_string(),
+            created_at: Some(since() + chrono::Duration::minutes(minutes_after)),
+            from: Some(SOURCE.to_string()),
+            to: Some(ACCOUNT.to_string()),
+            amount: Some(amount.to_string()),
+            asset_type: Some("credit_alphanum4".to_string()),
+            asset_code: Some("USDC".to_string()),
+            asset_issuer: Some(ISSUER.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn ids(payments: &[IncomingPayment]) -> Vec<&str> {
+        payments.iter().map(|p| p.operation_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_exact_amount_is_a_full_match() {
+        let operations = [payment("1", "100.0000000", 5)];
+        let found = match_payment(&expected(), SOURCE, ACCOUNT, since(), &operations);
+        assert_eq!(ids(&found.full), ["1"]);
+        assert!(found.partial.is_empty());
+    }
+
+    #[test]
+    fn test_smaller_amount_is_partial_and_larger_is_ignored() {
+        let operations = [payment("1", "40.0000000", 5), payment("2", "150.0000000", 6)];
+        let found = match_payment(&expected(), SOURCE, ACCOUNT, since(), &operations);
+        assert!(found.full.is_empty());
+        assert_eq!(ids(&found.partial), ["1"]);
+    }
+
+    #[test]
+    fn test_payments_before_the_transaction_do_not_count() {
+        let operations = [payment("1", "100", -5)];
+        let found = match_payment(&expected(), SOURCE, ACCOUNT, since(), &operations);
+        assert_eq!(found, PaymentMatch::default());
+    }
+
+    #[test]
+    fn test_other_issuers_and_outgoing_payments_do_not_count() {
+        let mut other_issuer = payment("1", "100", 5);
+        other_issuer.asset_issuer = Some("GIMPOSTOR".to_string());
+        let mut outgoing = payment("2", "100", 5);
+        outgoing.from = Some(ACCOUNT.to_string());
+        outgoing.to = Some(SOURCE.to_string());
+        let found = match_payment(&expected(), SOURCE, ACCOUNT, since(), &[other_issuer, outgoing]);
+        assert_eq!(found, PaymentMatch::default());
+    }
+
+    #[test]
+    fn test_payments_from_other_senders_do_not_count() {
+        let mut stranger = payment("1", "100", 5);
+        stranger.from = Some("GSTRANGER".to_string());
+        let found = match_payment(&expected(), SOURCE, ACCOUNT, since(), &[stranger]);
+        assert_eq!(found, PaymentMatch::default());
+    }
+
+    #[test]
+    fn test_native_payment_matches_lumens() {
+        let mut native = payment("1", "100", 5);
+        native.asset_type = Some("native".to_string());
+        native.asset_code = None;
+        native.asset_issuer = None;
+        let lumens = ExpectedPayment {
+            asset_code: NATIVE_ASSET_CODE.to_string(),
+            asset_issuer: None,
+            amount: "100".parse().unwrap(),
+        };
+        assert_eq!(ids(&match_payment(&lumens, SOURCE, ACCOUNT, since(), &[native]).full), ["1"]);
+    }
+}
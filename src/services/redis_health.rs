@@ -0,0 +1,466 @@
+//! Central Redis availability tracking and per-feature degradation policy.
+//!
+//! Redis is an optimisation for most features, not a dependency of callback
+//! ingestion. Every Redis-backed feature asks [`RedisHealth::decide`] before
+//! using Redis and reports the outcome of each call, so an outage is detected
+//! once and every feature degrades the same documented way.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Features that use Redis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedisFeature {
+    Idempotency,
+    RateLimiting,
+    ResponseCache,
+    DistributedLocks,
+}
+
+impl RedisFeature {
+    pub const ALL: &'static [RedisFeature] = &[
+        RedisFeature::Idempotency,
+        RedisFeature::RateLimiting,
+        RedisFeature::ResponseCache,
+        RedisFeature::DistributedLocks,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RedisFeature::Idempotency => "idempotency",
+            RedisFeature::RateLimiting => "rate_limiting",
+            RedisFeature::ResponseCache => "response_cache",
+            RedisFeature::DistributedLocks => "distributed_locks",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|f| f.as_str() == raw.trim())
+    }
+
+    /// How the feature behaves while Redis is unavailable
+    pub fn fallback(&self) -> Fallback {
+        match self {
+            RedisFeature::Idempotency => Fallback::DatabaseDedup,
+            RedisFeature::RateLimiting => Fallback::FailOpen,
+            RedisFeature::ResponseCache => Fallback::Bypass,
+            RedisFeature::DistributedLocks => Fallback::LocalLock,
+        }
+    }
+}
+
+/// Degraded behavior of a feature while Redis is unavailable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fallback {
+    /// Allow the request without the check
+    FailOpen,
+    /// Skip the cache and serve from the source
+    Bypass,
+    /// Rely on the database duplicate check
+    DatabaseDedup,
+    /// Use an in-process lock, valid for this instance only
+    LocalLock,
+}
+
+/// What a feature should do right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedisDecision {
+    UseRedis,
+    Degrade(Fallback),
+    /// The feature is configured as required and must reject the request
+    FailClosed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RedisHealthStatus {
+    pub available: bool,
+    pub consecutive_failures: u32,
+    /// Active behavior per feature: `normal`, a fallback name, or `fail_closed`
+    pub features: HashMap<&'static str, String>,
+}
+
+struct Inner {
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    required: HashSet<RedisFeature>,
+    consecutive_failures: AtomicU32,
+    opened_at: Mutex<Option<Instant>>,
+    /// Client of the background probe, reused by readiness checks
+    probe_client: Mutex<Option<redis::Client>>,
+}
+
+/// Circuit-breaker style tracker of Redis availability
+#[derive(Clone)]
+pub struct RedisHealth {
+    inner: Arc<Inner>,
+}
+
+impl RedisHealth {
+    pub fn new(failure_threshold: u32, reset_timeout: Duration, required: HashSet<RedisFeature>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                failure_threshold: failure_threshold.max(1),
+                reset_timeout,
+                required,
+                consecutive_failures: AtomicU32::new(0),
+                opened_at: Mutex::new(None),
+                probe_client: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Whether Redis should be tried. After `reset_timeout` an open breaker
+    /// lets calls through again so a recovered Redis is picked up automatically.
+    pub fn is_available(&self) -> bool {
+        match *self.inner.opened_at.lock().unwrap() {
+            None => true,
+            Some(opened_at) => opened_at.elapsed() >= self.inner.reset_timeout,
+        }
+    }
+
+    pub fn record_success(&self) {
+        self.inner.consecutive_failures.store(0, Ordering::Relaxed);
+        let mut opened_at = self.inner.opened_at.lock().unwrap();
+        if opened_at.take().is_some() {
+            tracing::info!("Redis recovered, restoring normal behavior");
+            self.update_metrics(true);
+        }
+    }
+
+    pub fn record_failure(&self) {
+        let failures = self.inner.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures < self.inner.failure_threshold {
+            return;
+        }
+
+        let mut opened_at = self.inner.opened_at.lock().unwrap();
+        if opened_at.is_none() {
+            tracing::error!(failures, "Redis unavailable, degrading Redis-backed features");
+        }
+        // (Re)start the reset window, including after a failed half-open probe
+        *opened_at = Some(Instant::now());
+        drop(opened_at);
+        self.update_metrics(false);
+    }
+
+    pub fn is_required(&self, feature: RedisFeature) -> bool {
+        self.inner.required.contains(&feature)
+    }
+
+    /// Whether any feature fails closed while Redis is unavailable
+    pub fn has_required(&self) -> bool {
+        !self.inner.required.is_empty()
+    }
+
+    pub fn decide(&self, feature: RedisFeature) -> RedisDecision {
+        if self.is_available() {
+            RedisDecision::UseRedis
+        } else if self.is_required(feature) {
+            RedisDecision::FailClosed
+        } else {
+            RedisDecision::Degrade(feature.fallback())
+        }
+    }
+
+    pub fn status(&self) -> RedisHealthStatus {
+        let available = self.is_available();
+        let features = RedisFeature::ALL
+            .iter()
+            .map(|feature| {
+                let mode = match self.decide(*feature) {
+                    RedisDecision::UseRedis => "normal".to_string(),
+                    RedisDecision::FailClosed => "fail_closed".to_string(),
+                    RedisDecision::Degrade(fallback) => serde_json::to_value(fallback)
+                        .ok()
+                        .and_then(|v| v.as_str().map(str::to_string))
+                        .unwrap_or_default(),
+                };
+                (feature.as_str(), mode)
+            })
+            .collect();
+
+        RedisHealthStatus {
+            available,
+            consecutive_failures: self.inner.consecutive_failures.load(Ordering::Relaxed),
+            features,
+        }
+    }
+
+    fn update_metrics(&self, available: bool) {
+        crate::metrics::update_redis_available(available);
+        for feature in RedisFeature::ALL {
+            crate::metrics::update_redis_degraded(feature.as_str(), !available);
+        }
+    }
+
+    /// Periodically PING Redis so recovery is noticed even without traffic.
+    pub fn start_probe(&self, client: redis::Client, interval: Duration) {
+        *self.inner.probe_client.lock().unwrap() = Some(client.clone());
+        let health = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if !health.is_available() {
+                    continue;
+                }
+                match ping(&client).await {
+                    Ok(()) => health.record_success(),
+                    Err(e) => {
+                        tracing::debug!("Redis health probe failed: {}", e);
+                        health.record_failure();
+                    }
+                }
+            }
+        });
+    }
+
+    /// PING Redis now, whatever the breaker says, and record the outcome.
+    /// `None` until [`RedisHealth::start_probe`] has provided a client.
+    pub async fn ping(&self) -> Option<redis::RedisResult<()>> {
+        let client = self.inner.probe_client.lock().unwrap().clone()?;
+        let result = ping(&client).await;
+        match &result {
+            Ok(()) => self.record_success(),
+            Err(_) => self.record_failure(),
+        }
+        Some(result)
+    }
+}
+
+async fn ping(client: &redis::Client) -> redis::RedisResult<()> {
+    let mut conn = client.get_multiplexed_async_connection().await?;
+    redis::cmd("PING").query_async::<_, String>(&mut conn).await?;
+    Ok(())
+}
+
+/// Parse `REDIS_REQUIRED_FEATURES`, a comma-separated list of feature names
+pub fn parse_required_features(raw: &str) -> anyhow::Result<HashSet<RedisFeature>> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            RedisFeature::parse(name).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "REDIS_REQUIRED_FEATURES: unknown feature '{}' (expected idempotency, \
+                     rate_limiting, response_cache or distributed_locks)",
+                    name
+                )
+            })
+        })
+        .collect()
+}
+
+/// In-process replacement for distributed locks while Redis is down.
+/// Only serializes work within this instance.
+#[derive(Clone, Default)]
+pub struct LocalLocks {
+    locks: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl LocalLocks {
+    pub async fn lock(&self, key: &str) -> tokio::sync::OwnedMutexGuard<()> {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+}
+
+/// Guard for a lock acquired through [`DistributedLock::acquire`]
+pub enum LockGuard {
+    Redis { client: redis::Client, key: String, token: String },
+    Local(tokio::sync::OwnedMutexGuard<()>),
+}
+
+impl LockGuard {
+    pub async fn release(self) {
+        if let LockGuard::Redis { client, key, token } = self {
+            // Only delete the key if we still own it
+            let script = redis::Script::new(
+                "if redis.call('GET', KEYS[1]) == ARGV[1] then \
+                 return redis.call('DEL', KEYS[1]) else return 0 end",
+            );
+            if let Ok(mut conn) = client.get_multiplexed_async_connection().await {
+                let _: redis::RedisResult<i32> =
+                    script.key(&key).arg(&token).invoke_async(&mut conn).await;
+            }
+        }
+    }
+}
+
+/// Redis `SET NX PX` lock that falls back to [`LocalLocks`] during outages
+#[derive(Clone)]
+pub struct DistributedLock {
+    client: redis::Client,
+    health: RedisHealth,
+    local: LocalLocks,
+}
+
+impl DistributedLock {
+    pub fn new(client: redis::Client, health: RedisHealth) -> Self {
+        Self {
+            client,
+            health,
+            local: LocalLocks::default(),
+        }
+    }
+
+    /// Try to take the lock. Returns `Ok(None)` if another holder has it.
+    pub async fn acquire(&self, key: &str, ttl: Duration) -> anyhow::Result<Option<LockGuard>> {
+        if self.health.decide(RedisFeature::DistributedLocks) == RedisDecision::UseRedis {
+            match self.try_redis(key, ttl).await {
+                Ok(guard) => {
+                    self.health.record_success();
+                    return Ok(guard);
+                }
+                Err(e) => {
+                    self.health.record_failure();
+                    tracing::warn!("Redis lock for '{}' failed: {}", key, e);
+                }
+            }
+        }
+
+        match self.health.decide(RedisFeature::DistributedLocks) {
+            RedisDecision::FailClosed => {
+                anyhow::bail!("Redis is unavailable and distributed locks are required")
+            }
+            _ => {
+                tracing::error!(
+                    lock = key,
+                    "Redis unavailable, using an in-process lock; other instances are not excluded"
+                );
+                Ok(Some(LockGuard::Local(self.local.lock(key).await)))
+            }
+        }
+    }
+
+    async fn try_redis(&self, key: &str, ttl: Duration) -> redis::RedisResult<Option<LockGuard>> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let token = uuid::Uuid::new_v4().to_string();
+        let key = format!("lock:{}", key);
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg(&token)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(acquired.map(|_| LockGuard::Redis {
+            client: self.client.clone(),
+            key,
+            token,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(required: &[RedisFeature]) -> RedisHealth {
+        RedisHealth::new(2, Duration::from_millis(50), required.iter().copied().collect())
+    }
+
+    #[test]
+    fn test_available_until_failure_threshold() {
+        let health = health(&[]);
+        health.record_failure();
+        assert!(health.is_available());
+        health.record_failure();
+        assert!(!health.is_available());
+    }
+
+    #[test]
+    fn test_success_resets_failures() {
+        let health = health(&[]);
+        health.record_failure();
+        health.record_success();
+        health.record_failure();
+        assert!(health.is_available());
+    }
+
+    #[test]
+    fn test_degradation_policy_per_feature() {
+        let health = health(&[]);
+        health.record_failure();
+        health.record_failure();
+
+        assert_eq!(
+            health.decide(RedisFeature::RateLimiting),
+            RedisDecision::Degrade(Fallback::FailOpen)
+        );
+        assert_eq!(
+            health.decide(RedisFeature::ResponseCache),
+            RedisDecision::Degrade(Fallback::Bypass)
+        );
+        assert_eq!(
+            health.decide(RedisFeature::Idempotency),
+            RedisDecision::Degrade(Fallback::DatabaseDedup)
+        );
+        assert_eq!(
+            health.decide(RedisFeature::DistributedLocks),
+            RedisDecision::Degrade(Fallback::LocalLock)
+        );
+    }
+
+    #[test]
+    fn test_required_features_fail_closed() {
+        let health = health(&[RedisFeature::Idempotency]);
+        health.record_failure();
+        health.record_failure();
+
+        assert_eq!(health.decide(RedisFeature::Idempotency), RedisDecision::FailClosed);
+        assert_eq!(health.status().features["idempotency"], "fail_closed");
+        assert_eq!(health.status().features["rate_limiting"], "fail_open");
+    }
+
+    #[test]
+    fn test_recovers_after_reset_timeout() {
+        let health = health(&[]);
+        health.record_failure();
+        health.record_failure();
+        assert!(!health.is_available());
+
+        std::thread::sleep(Duration::from_millis(60));
+        // Half-open: calls are let through, and a success closes the breaker
+        assert!(health.is_available());
+        health.record_success();
+        assert_eq!(health.decide(RedisFeature::Idempotency), RedisDecision::UseRedis);
+        assert_eq!(health.status().features["idempotency"], "normal");
+    }
+
+    #[test]
+    fn test_parse_required_features() {
+        let required = parse_required_features("idempotency, distributed_locks").unwrap();
+        assert!(required.contains(&RedisFeature::Idempotency));
+        assert!(required.contains(&RedisFeature::DistributedLocks));
+        assert!(parse_required_features("").unwrap().is_empty());
+        assert!(parse_required_features("sessions").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_local_locks_serialize_same_key() {
+        let locks = LocalLocks::default();
+        let guard = locks.lock("settlement").await;
+
+        let contender = locks.clone();
+        let waiting = tokio::spawn(async move { contender.lock("settlement").await; });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        drop(guard);
+        waiting.await.unwrap();
+    }
+}
@@ -0,0 +1,294 @@
+//! Compact health snapshot for dashboards: `GET /admin/status-snapshot`.
+//!
+//! Every field is read independently against one shared deadline. A field
+//! whose source fails or misses the deadline falls back to its last good
+//! value (if recent enough) or to `null`, so a slow database never makes the
+//! whole snapshot slow. Each field carries the time its value was read.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::db::models::TransactionBacklog;
+use crate::db::queries;
+use crate::metrics;
+use crate::services::RedisHealth;
+use crate::stellar::HorizonClient;
+
+/// Overall time allowed for one snapshot
+pub const SNAPSHOT_BUDGET: Duration = Duration::from_millis(200);
+/// Database readings younger than this are reused without querying
+pub const CACHE_TTL: Duration = Duration::from_secs(5);
+/// Oldest cached reading served when a fresh read fails
+pub const MAX_STALENESS: Duration = Duration::from_secs(300);
+
+/// One snapshot field: `value` is `null` when it could not be read, and
+/// `as_of` is when the value was read
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reading<T> {
+    pub value: Option<T>,
+    pub as_of: Option<DateTime<Utc>>,
+}
+
+impl<T> Reading<T> {
+    pub fn at(value: T, as_of: DateTime<Utc>) -> Self {
+        Self {
+            value: Some(value),
+            as_of: Some(as_of),
+        }
+    }
+
+    pub fn unavailable() -> Self {
+        Self {
+            value: None,
+            as_of: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatusSnapshot {
+    pub generated_at: DateTime<Utc>,
+    pub deploy_version: String,
+    pub requests_per_minute: Reading<f64>,
+    /// Share of 5xx responses over the last five minutes
+    pub error_rate: Reading<f64>,
+    pub pending: Reading<i64>,
+    pub processing: Reading<i64>,
+    pub on_hold: Reading<i64>,
+    /// Seconds since the oldest pending transaction was created; 0 when none
+    pub oldest_pending_age_secs: Reading<i64>,
+    /// Export jobs pending or running
+    pub job_queue_depth: Reading<i64>,
+    /// Outbound webhook deliveries pending or delivering
+    pub webhook_backlog: Reading<i64>,
+    pub horizon_circuit: Reading<String>,
+    pub redis_healthy: Reading<bool>,
+    pub db_healthy: Reading<bool>,
+}
+
+/// Last good value of one database-backed field
+struct Slot<T>(Mutex<Option<(T, DateTime<Utc>)>>);
+
+impl<T: Clone> Slot<T> {
+    fn new() -> Self {
+        Self(Mutex::new(None))
+    }
+
+    fn younger_than(&self, age: Duration, now: DateTime<Utc>) -> Option<Reading<T>> {
+        let cached = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let (value, as_of) = cached.as_ref()?;
+        let age = chrono::Duration::from_std(age).ok()?;
+        (now - *as_of < age).then(|| Reading::at(value.clone(), *as_of))
+    }
+
+    fn store(&self, value: T, as_of: DateTime<Utc>) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some((value, as_of));
+    }
+}
+
+/// Read one field before `deadline`, reusing or falling back to `slot`.
+/// `read` is only polled when the cached value is older than [`CACHE_TTL`].
+async fn read_within<T, F>(name: &str, slot: &Slot<T>, deadline: Instant, read: F) -> Reading<T>
+where
+    T: Clone,
+    F: Future<Output = Result<T, sqlx::Error>>,
+{
+    let now = Utc::now();
+    if let Some(cached) = slot.younger_than(CACHE_TTL, now) {
+        return cached;
+    }
+    match tokio::time::timeout_at(deadline, read).await {
+        Ok(Ok(value)) => {
+            slot.store(value.clone(), now);
+            Reading::at(value, now)
+        }
+        Ok(Err(e)) => {
+            tracing::warn!(field = name, "Status snapshot read failed: {:?}", e);
+            slot.younger_than(MAX_STALENESS, now).unwrap_or_else(Reading::unavailable)
+        }
+        Err(_) => {
+            tracing::warn!(field = name, "Status snapshot read missed its deadline");
+            slot.younger_than(MAX_STALENESS, now).unwrap_or_else(Reading::unavailable)
+        }
+    }
+}
+
+struct Slots {
+    backlog: Slot<TransactionBacklog>,
+    job_queue_depth: Slot<i64>,
+    webhook_backlog: Slot<i64>,
+}
+
+#[derive(Clone)]
+pub struct StatusSnapshotService {
+    pool: PgPool,
+    horizon_client: HorizonClient,
+    redis_health: RedisHealth,
+    deploy_version: String,
+    slots: Arc<Slots>,
+}
+
+impl StatusSnapshotService {
+    pub fn new(
+        pool: PgPool,
+        horizon_client: HorizonClient,
+        redis_health: RedisHealth,
+        deploy_version: String,
+    ) -> Self {
+        Self {
+            pool,
+            horizon_client,
+            redis_health,
+            deploy_version,
+            slots: Arc::new(Slots {
+                backlog: Slot::new(),
+                job_queue_depth: Slot::new(),
+                webhook_backlog: Slot::new(),
+            }),
+        }
+    }
+
+    /// Build a snapshot within [`SNAPSHOT_BUDGET`]
+    pub async fn snapshot(&self) -> StatusSnapshot {
+        let deadline = Instant::now() + SNAPSHOT_BUDGET;
+        let slots = &self.slots;
+
+        let (backlog, job_queue_depth, webhook_backlog, db_healthy) = tokio::join!(
+            read_within(
+                "transaction_backlog",
+                &slots.backlog,
+                deadline,
+                queries::get_transaction_backlog(&self.pool),
+            ),
+            read_within(
+                "job_queue_depth",
+                &slots.job_queue_depth,
+                deadline,
+                queries::count_export_queue(&self.pool),
+            ),
+            read_within(
+                "webhook_backlog",
+                &slots.webhook_backlog,
+                deadline,
+                queries::count_webhook_backlog(&self.pool),
+            ),
+            self.ping_db(deadline),
+        );
+
+        let now = Utc::now();
+        let rates = metrics::request_window().rates(metrics::unix_now_secs());
+        let backlog_field = |field: fn(&TransactionBacklog) -> i64| Reading {
+            value: backlog.value.as_ref().map(field),
+            as_of: backlog.as_of,
+        };
+
+        StatusSnapshot {
+            generated_at: now,
+            deploy_version: self.deploy_version.clone(),
+            requests_per_minute: Reading::at(rates.requests_per_minute, now),
+            error_rate: Reading {
+                value: rates.error_rate,
+                as_of: Some(now),
+            },
+            pending: backlog_field(|b| b.pending),
+            processing: backlog_field(|b| b.processing),
+            on_hold: backlog_field(|b| b.on_hold),
+            oldest_pending_age_secs: Reading {
+                value: backlog.value.as_ref().map(|b| oldest_pending_age_secs(b, now)),
+                as_of: backlog.as_of,
+            },
+            job_queue_depth,
+            webhook_backlog,
+            horizon_circuit: Reading::at(self.horizon_client.circuit_state(), now),
+            redis_healthy: Reading::at(self.redis_health.is_available(), now),
+            db_healthy,
+        }
+    }
+
+    /// `SELECT 1` on the primary; failing or missing the deadline is unhealthy
+    async fn ping_db(&self, deadline: Instant) -> Reading<bool> {
+        let ping = sqlx::query("SELECT 1").execute(&self.pool);
+        let healthy = matches!(tokio::time::timeout_at(deadline, ping).await, Ok(Ok(_)));
+        Reading::at(healthy, Utc::now())
+    }
+}
+
+/// Age of the oldest pending transaction at `now`. Measured at response time
+/// so a cached backlog reading does not under-report it.
+fn oldest_pending_age_secs(backlog: &TransactionBacklog, now: DateTime<Utc>) -> i64 {
+    backlog
+        .oldest_pending_at
+        .map(|created_at| (now - created_at).num_seconds().max(0))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deadline_in(millis: u64) -> Instant {
+        Instant::now() + Duration::from_millis(millis)
+    }
+
+    #[tokio::test]
+    async fn test_successful_read_is_cached() {
+        let slot = Slot::new();
+        let first = read_within("test", &slot, deadline_in(200), async { Ok(7) }).await;
+        assert_eq!(first.value, Some(7));
+
+        // Within the TTL the read is not polled at all
+        let second = read_within("test", &slot, deadline_in(200), async { Ok(8) }).await;
+        assert_eq!(second, first);
+    }
+
+    #[tokio::test]
+    async fn test_slow_read_degrades_to_null() {
+        let slot: Slot<i64> = Slot::new();
+        let reading = read_within("test", &slot, deadline_in(20), async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(1)
+        })
+        .await;
+        assert_eq!(reading, Reading::unavailable());
+    }
+
+    #[tokio::test]
+    async fn test_failed_read_serves_recent_stale_value() {
+        let slot = Slot::new();
+        let as_of = Utc::now() - chrono::Duration::seconds(30);
+        slot.store(3, as_of);
+
+        let reading = read_within("test", &slot, deadline_in(200), async {
+            Err(sqlx::Error::PoolTimedOut)
+        })
+        .await;
+        assert_eq!(reading, Reading::at(3, as_of));
+
+        slot.store(3, Utc::now() - chrono::Duration::hours(1));
+        let reading = read_within("test", &slot, deadline_in(200), async {
+            Err(sqlx::Error::PoolTimedOut)
+        })
+        .await;
+        assert_eq!(reading, Reading::unavailable());
+    }
+
+    #[test]
+    fn test_oldest_pending_age() {
+        let now = Utc::now();
+        let mut backlog = TransactionBacklog {
+            pending: 1,
+            processing: 0,
+            on_hold: 0,
+            oldest_pending_at: Some(now - chrono::Duration::seconds(90)),
+        };
+        assert_eq!(oldest_pending_age_secs(&backlog, now), 90);
+
+        backlog.oldest_pending_at = None;
+        assert_eq!(oldest_pending_age_secs(&backlog, now), 0);
+    }
+}
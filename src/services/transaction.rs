@@ -0,0 +1,124 @@
+//! Transaction status changes.
+//!
+//! [`transition`] is the checked way to move a transaction: it locks the row,
+//! refuses anything [`TransactionStatus::can_transition_to`] doesn't allow
+//! with [`AppError::InvalidTransition`], and records the change in the audit
+//! log and `transaction_status_history` and publishes it to the outbox. Workers that only ever make one
+//! known-legal move, guarded by the status they expect, may call
+//! [`queries::update_transaction_status`] directly; the history is written
+//! either way. [`retry`] is the operator's way back out of `failed`.
+
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::db::models::Transaction;
+use crate::db::{queries, transactions};
+use crate::domain::TransactionStatus;
+use crate::error::AppError;
+
+/// `Ok` when a transaction `id` in `from` may move to `to`
+pub fn check_transition(
+    id: Uuid,
+    from: TransactionStatus,
+    to: TransactionStatus,
+) -> Result<(), AppError> {
+    if from.can_transition_to(to) {
+        Ok(())
+    } else {
+        Err(AppError::InvalidTransition { id, from, to })
+    }
+}
+
+/// Move transaction `id` to `to` on `conn`, which should be a unit of work
+/// so the change commits with whatever the caller does next. Moving to the
+/// status it is already in changes nothing and records nothing.
+pub async fn transition(
+    conn: &mut PgConnection,
+    id: Uuid,
+    to: TransactionStatus,
+    reason: Option<&str>,
+    actor: &str,
+) -> Result<Transaction, AppError> {
+    let current = queries::lock_transaction(&mut *conn, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("transaction {}", id)))?;
+    let from = current
+        .current_status()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    check_transition(id, from, to)?;
+    if from == to {
+        return Ok(current);
+    }
+
+    let (_, updated) = queries::update_transaction_status_with_reason(
+        conn,
+        id,
+        &[current.status.as_str()],
+        to.as_str(),
+        reason,
+        actor,
+    )
+    .await?
+    .ok_or_else(|| AppError::Internal(format!("transaction {} changed while locked", id)))?;
+    Ok(updated)
+}
+
+/// Send a failed transaction `id` back to `pending`, for the workers to pick
+/// up again. This is the one way out of `failed` besides a refund, and is
+/// meant for an operator who has fixed whatever made it fail.
+pub async fn retry(
+    conn: &mut PgConnection,
+    id: Uuid,
+    reason: Option<&str>,
+    actor: &str,
+) -> Result<Transaction, AppError> {
+    let current = queries::lock_transaction(&mut *conn, id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("transaction {}", id)))?;
+    let from = current
+        .current_status()
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    if from != TransactionStatus::Failed {
+        return Err(AppError::InvalidTransition {
+            id,
+            from,
+            to: TransactionStatus::Pending,
+        });
+    }
+
+    transactions::release_payout(&mut *conn, id).await?;
+    let (_, updated) = queries::update_transaction_status_with_reason(
+        conn,
+        id,
+        &[current.status.as_str()],
+        TransactionStatus::Pending.as_str(),
+        reason,
+        actor,
+    )
+    .await?
+    .ok_or_else(|| AppError::Internal(format!("transaction {} changed while locked", id)))?;
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_transition() {
+        let id = Uuid::new_v4();
+        assert!(check_transition(id, TransactionStatus::Pending, TransactionStatus::Submitted).is_ok());
+        assert!(check_transition(id, TransactionStatus::Completed, TransactionStatus::Refunded).is_ok());
+        assert!(check_transition(id, TransactionStatus::Failed, TransactionStatus::Failed).is_ok());
+
+        match check_transition(id, TransactionStatus::Completed, TransactionStatus::Pending) {
+            Err(AppError::InvalidTransition { id: rejected, from, to }) => {
+                assert_eq!(rejected, id);
+                assert_eq!(from, TransactionStatus::Completed);
+                assert_eq!(to, TransactionStatus::Pending);
+            }
+            other => panic!("expected an invalid transition: {:?}", other),
+        }
+        assert!(check_transition(id, TransactionStatus::Refunded, TransactionStatus::Completed).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::db::queries;
+use crate::handlers::ws::TransactionStatusUpdate;
+use crate::services::FeatureFlagService;
+use crate::services::processor::publish_status_update;
+use crate::stellar::{Effect, HorizonClient};
+use crate::utils::correlation::CorrelationContext;
+use crate::utils::shutdown::Shutdown;
+
+/// Feature flag gating the effects listener
+pub const TRUSTLINE_LISTENER_FLAG: &str = "trustline_effects_listener";
+
+/// Name of this listener's row in `stream_cursors`
+pub const CURSOR_NAME: &str = "trustline_effects";
+
+pub const STATUS_PENDING_TRUSTLINE: &str = "pending_trustline";
+
+/// States in which a transaction has been received but not yet paid out
+pub const PRE_PAYOUT_STATUSES: &[&str] = &["pending", "processing"];
+
+const EFFECT_TYPES: &[&str] = &["trustline_removed", "trustline_deauthorized"];
+const PAGE_SIZE: u32 = 200;
+
+/// Watches Horizon effects for customers removing or losing authorization on
+/// a trustline to one of our assets, and parks their unpaid transactions in
+/// `pending_trustline` so the payout worker doesn't submit a doomed payment.
+#[derive(Clone)]
+pub struct TrustlineListener {
+    pool: PgPool,
+    horizon_client: HorizonClient,
+    feature_flags: FeatureFlagService,
+    tx_broadcast: broadcast::Sender<TransactionStatusUpdate>,
+    start_cursor: Option<String>,
+    shutdown: Shutdown,
+}
+
+impl TrustlineListener {
+    pub fn new(
+        pool: PgPool,
+        horizon_client: HorizonClient,
+        feature_flags: FeatureFlagService,
+        tx_broadcast: broadcast::Sender<TransactionStatusUpdate>,
+    ) -> Self {
+        Self {
+            pool,
+            horizon_client,
+            feature_flags,
+            tx_broadcast,
+            start_cursor: None,
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    /// Stop between polls once `shutdown` is cancelled
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Paging token to start from when no cursor is stored. Without one the
+    /// listener starts after the newest effect on Horizon.
+    pub fn with_start_cursor(mut self, start_cursor: Option<String>) -> Self {
+        self.start_cursor = start_cursor;
+        self
+    }
+
+    pub fn start(&self, poll_interval: Duration) {
+        let listener = self.clone();
+        self.shutdown.spawn(async move {
+            let mut interval = tokio::time::interval(poll_interval);
+            loop {
+                tokio::select! {
+                    _ = listener.shutdown.cancelled() => break,
+                    _ = interval.tick() => {}
+                }
+                if !listener.feature_flags.is_enabled(TRUSTLINE_LISTENER_FLAG).await {
+                    continue;
+                }
+                if let Err(e) = listener.poll_once().await {
+                    tracing::error!("Trustline effects listener failed: {}", e);
+                }
+            }
+            tracing::info!("Trustline listener stopped");
+        });
+    }
+
+    /// Process one page of effects after the stored cursor.
+    /// Returns the number of transactions moved to `pending_trustline`.
+    pub async fn poll_once(&self) -> anyhow::Result<usize> {
+        let cursor = self.cursor().await?;
+        let effects = self
+            .horizon_client
+            .get_effects(cursor.as_deref(), PAGE_SIZE)
+            .await?;
+
+        let mut moved = 0;
+        for effect in &effects {
+            if EFFECT_TYPES.contains(&effect.effect_type.as_str())
+                && queries::mark_effect_processed(&self.pool, &effect.id).await?
+            {
+                moved += self.handle_effect(effect).await?;
+            }
+        }
+        // Every effect on the page was looked at, ours or not
+        if let Some(last) = effects.last() {
+            queries::save_stream_cursor(&self.pool, CURSOR_NAME, &last.paging_token).await?;
+        }
+
+        Ok(moved)
+    }
+
+    /// The stored cursor; on a fresh database the configured start cursor or
+    /// the newest effect, stored so the listener moves on from there
+    async fn cursor(&self) -> anyhow::Result<Option<String>> {
+        if let Some(cursor) = queries::get_stream_cursor(&self.pool, CURSOR_NAME).await? {
+            return Ok(Some(cursor));
+        }
+        let start = match &self.start_cursor {
+            Some(cursor) => Some(cursor.clone()),
+            None => self.horizon_client.latest_effect_cursor().await?,
+        };
+        if let Some(cursor) = &start {
+            queries::save_stream_cursor(&self.pool, CURSOR_NAME, cursor).await?;
+        }
+        Ok(start)
+    }
+
+    async fn handle_effect(&self, effect: &Effect) -> anyhow::Result<usize> {
+        let (Some(asset_code), Some(asset_issuer)) =
+            (effect.asset_code.as_deref(), effect.asset_issuer.as_deref())
+        else {
+            return Ok(0);
+        };
+
+        // Only assets in our registry with the same issuer are ours
+        if queries::get_asset_by_code_and_issuer(&self.pool, asset_code, asset_issuer)
+            .await?
+            .is_none()
+        {
+            return Ok(0);
+        }
+
+        let account = affected_account(effect);
+        let moved = queries::move_to_pending_trustline(
+            &self.pool,
+            account,
+            asset_code,
+            asset_issuer,
+            PRE_PAYOUT_STATUSES,
+        )
+        .await?;
+
+        for tx in &moved {
+            let ctx = CorrelationContext::for_transaction(tx);
+            tracing::warn!(
+                transaction_id = %tx.id,
+                correlation_id = %ctx,
+                stellar_account = %account,
+                effect_id = %effect.id,
+                "Trustline {} for {}, transaction moved to pending_trustline",
+                effect.effect_type,
+                asset_code
+            );
+            self.publish(&ctx, tx.id, format!("{} for {}", effect.effect_type, asset_code));
+        }
+
+        Ok(moved.len())
+    }
+
+    fn publish(&self, ctx: &CorrelationContext, transaction_id: Uuid, message: String) {
+        publish_status_update(
+            &self.tx_broadcast,
+            ctx,
+            transaction_id,
+            STATUS_PENDING_TRUSTLINE.to_string(),
+            Some(message),
+        );
+    }
+}
+
+/// The account whose trustline changed. Authorization effects belong to the
+/// issuer and carry the customer in `trustor`.
+fn affected_account(effect: &Effect) -> &str {
+    effect.trustor.as_deref().unwrap_or(&effect.account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(effect_type: &str, trustor: Option<&str>) -> Effect {
+        Effect {
+            id: "0000000012884905985-0000000001".to_string(),
+            paging_token: "12884905985-1".to_string(),
+            account: "GISSUER".to_string(),
+            effect_type: effect_type.to_string(),
+            created_at: "2021-01-01T00:00:00Z".to_string(),
+            asset_code: Some("USD".to_string()),
+            asset_issuer: Some("GISSUER".to_string()),
+            trustor: trustor.map(str::to_string),
+            amount: None,
+        }
+    }
+
+    #[test]
+    fn test_affected_account_for_removed_trustline() {
+        let mut removed = effect("trustline_removed", None);
+        removed.account = "GCUSTOMER".to_string();
+        assert_eq!(affected_account(&removed), "GCUSTOMER");
+    }
+
+    #[test]
+    fn test_affected_account_for_deauthorized_trustline() {
+        let deauthorized = effect("trustline_deauthorized", Some("GCUSTOMER"));
+        assert_eq!(affected_account(&deauthorized), "GCUSTOMER");
+    }
+}
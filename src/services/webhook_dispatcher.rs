@@ -0,0 +1,504 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::config::WebhookDispatchConfig;
+use crate::db::models::{WebhookDelivery, WebhookSubscription};
+use crate::db::queries;
+use crate::services::notifications::NotificationRenderer;
+use crate::utils::clock::{self, SharedClock, Ticker};
+use crate::utils::correlation::CorrelationContext;
+use crate::utils::shutdown::Shutdown;
+use crate::utils::signature;
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+pub const COOLDOWN_SATURATED: &str = "saturated";
+pub const COOLDOWN_FAILURE_RATE: &str = "failure_rate";
+
+/// The failure rate is not judged on fewer attempts than this
+const MIN_FAILURE_SAMPLES: usize = 5;
+const MAX_BACKOFF_SECS: i64 = 3600;
+
+struct SubscriptionSlot {
+    permits: Arc<Semaphore>,
+    limit: usize,
+    outcomes: VecDeque<bool>,
+}
+
+/// In-process concurrency and failure tracking, one slot per subscription.
+///
+/// Each subscription gets its own semaphore, so a subscriber that hangs can
+/// only ever tie up its own `max_in_flight` deliveries.
+#[derive(Clone)]
+pub struct DeliveryLimiter {
+    max_in_flight: usize,
+    failure_window: usize,
+    failure_rate_threshold: f64,
+    slots: Arc<Mutex<HashMap<Uuid, SubscriptionSlot>>>,
+}
+
+impl DeliveryLimiter {
+    pub fn new(max_in_flight: usize, failure_window: usize, failure_rate_threshold: f64) -> Self {
+        Self {
+            max_in_flight,
+            failure_window: failure_window.max(1),
+            failure_rate_threshold,
+            slots: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Effective in-flight limit for a subscription's optional override
+    pub fn limit_for(&self, max_in_flight: Option<i32>) -> usize {
+        max_in_flight
+            .filter(|limit| *limit > 0)
+            .map(|limit| limit as usize)
+            .unwrap_or(self.max_in_flight)
+    }
+
+    fn with_slot<T>(
+        &self,
+        subscription_id: Uuid,
+        max_in_flight: Option<i32>,
+        f: impl FnOnce(&mut SubscriptionSlot) -> T,
+    ) -> T {
+        let limit = self.limit_for(max_in_flight);
+        let mut slots = self.slots.lock().unwrap();
+        let slot = slots.entry(subscription_id).or_insert_with(|| SubscriptionSlot {
+            permits: Arc::new(Semaphore::new(limit)),
+            limit,
+            outcomes: VecDeque::new(),
+        });
+        // Raising the limit takes effect immediately; lowering it drains naturally
+        // because permits held by running deliveries are not revoked
+        if limit > slot.limit {
+            slot.permits.add_permits(limit - slot.limit);
+            slot.limit = limit;
+        } else if limit < slot.limit {
+            let removed = slot.permits.forget_permits(slot.limit - limit);
+            slot.limit -= removed;
+        }
+        f(slot)
+    }
+
+    pub fn available(&self, subscription_id: Uuid, max_in_flight: Option<i32>) -> usize {
+        self.with_slot(subscription_id, max_in_flight, |slot| slot.permits.available_permits())
+    }
+
+    /// Take one in-flight slot, or `None` if the subscription is saturated
+    pub fn try_acquire(
+        &self,
+        subscription_id: Uuid,
+        max_in_flight: Option<i32>,
+    ) -> Option<OwnedSemaphorePermit> {
+        self.with_slot(subscription_id, max_in_flight, |slot| {
+            slot.permits.clone().try_acquire_owned().ok()
+        })
+    }
+
+    pub fn in_flight(&self, subscription_id: Uuid) -> usize {
+        let slots = self.slots.lock().unwrap();
+        slots
+            .get(&subscription_id)
+            .map(|slot| slot.limit.saturating_sub(slot.permits.available_permits()))
+            .unwrap_or(0)
+    }
+
+    /// Failure rate over the recent window, once there are enough samples
+    pub fn failure_rate(&self, subscription_id: Uuid) -> Option<f64> {
+        let slots = self.slots.lock().unwrap();
+        slots.get(&subscription_id).and_then(failure_rate)
+    }
+
+    /// Record a delivery attempt. Returns true if the failure rate just crossed
+    /// the threshold; the window is then cleared so the subscription starts
+    /// fresh after its cool-down.
+    pub fn record_outcome(&self, subscription_id: Uuid, success: bool) -> bool {
+        let mut slots = self.slots.lock().unwrap();
+        let Some(slot) = slots.get_mut(&subscription_id) else {
+            return false;
+        };
+        slot.outcomes.push_back(success);
+        while slot.outcomes.len() > self.failure_window {
+            slot.outcomes.pop_front();
+        }
+        match failure_rate(slot) {
+            Some(rate) if rate >= self.failure_rate_threshold => {
+                slot.outcomes.clear();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn failure_rate(slot: &SubscriptionSlot) -> Option<f64> {
+    let samples = slot.outcomes.len();
+    if samples < MIN_FAILURE_SAMPLES {
+        return None;
+    }
+    let failures = slot.outcomes.iter().filter(|ok| !**ok).count();
+    Some(failures as f64 / samples as f64)
+}
+
+/// `X-Webhook-Signature` value: `t=<unix time>,v1=<hex HMAC-SHA256 of
+/// "<t>.<body>">`. The timestamp lets subscribers refuse old replays.
+pub fn sign_delivery(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut signed = format!("{}.", timestamp).into_bytes();
+    signed.extend_from_slice(body);
+    format!("t={},v1={}", timestamp, signature::sign(secret, &signed))
+}
+
+/// POST one delivery to the subscriber, signed with `secret` when given.
+/// Non-2xx responses and timeouts are errors.
+pub async fn send_delivery(
+    http: &reqwest::Client,
+    url: &str,
+    delivery: &WebhookDelivery,
+    secret: Option<&str>,
+    timeout: Duration,
+) -> Result<(), String> {
+    let body = serde_json::to_vec(&delivery.payload).map_err(|e| e.to_string())?;
+    let mut request = http
+        .post(url)
+        .timeout(timeout)
+        .header("Content-Type", "application/json")
+        .header("X-Webhook-Event", &delivery.event_type)
+        .header("X-Webhook-Delivery", delivery.id.to_string());
+    if let Some(secret) = secret {
+        let signature = sign_delivery(secret, Utc::now().timestamp(), &body);
+        request = request.header(SIGNATURE_HEADER, signature);
+    }
+    if let Some(ctx) = delivery.correlation_id.as_deref().and_then(CorrelationContext::parse) {
+        request = ctx.apply(request);
+    }
+    let response = request
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("subscriber responded with HTTP {}", response.status()))
+    }
+}
+
+/// Exponential backoff after the given number of failed attempts, capped at an hour
+pub fn retry_backoff(attempts: i32) -> chrono::Duration {
+    let secs = 10i64.saturating_mul(1i64 << attempts.clamp(0, 20));
+    chrono::Duration::seconds(secs.min(MAX_BACKOFF_SECS))
+}
+
+/// Delivers queued outbound webhooks with bounded concurrency per subscription.
+///
+/// Each poll claims at most `claim_batch_size` deliveries in total and never
+/// more for a subscription than it has free in-flight slots. A subscription
+/// that is saturated or failing gets a cool-down written to its row, which
+/// every instance honours when claiming.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    pool: PgPool,
+    http: reqwest::Client,
+    config: WebhookDispatchConfig,
+    limiter: DeliveryLimiter,
+    notifications: NotificationRenderer,
+    clock: SharedClock,
+    shutdown: Shutdown,
+}
+
+impl WebhookDispatcher {
+    pub fn new(pool: PgPool, config: WebhookDispatchConfig) -> Self {
+        let limiter = DeliveryLimiter::new(
+            config.max_in_flight,
+            config.failure_window,
+            config.failure_rate_threshold,
+        );
+        Self {
+            notifications: NotificationRenderer::new(pool.clone()),
+            pool,
+            http: reqwest::Client::new(),
+            config,
+            limiter,
+            clock: clock::system(),
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    /// Schedule polls, leases, retries and cool-downs on `clock` instead of
+    /// the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Stop claiming once `shutdown` is cancelled. A delivery still waiting
+    /// on its subscriber is abandoned and its row released, so another
+    /// instance picks it up without waiting for the lease.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    pub fn limiter(&self) -> &DeliveryLimiter {
+        &self.limiter
+    }
+
+    pub fn start(&self, poll_interval: Duration) {
+        let dispatcher = self.clone();
+        self.shutdown.spawn(async move {
+            let mut ticker = Ticker::new(dispatcher.clock.clone(), poll_interval);
+            loop {
+                tokio::select! {
+                    _ = dispatcher.shutdown.cancelled() => break,
+                    _ = ticker.tick() => {}
+                }
+                if let Err(e) = dispatcher.poll_once().await {
+                    tracing::error!("Webhook dispatcher poll failed: {}", e);
+                }
+            }
+            tracing::info!("Webhook dispatcher stopped");
+        });
+    }
+
+    /// Claim and spawn due deliveries. Returns the number claimed.
+    pub async fn poll_once(&self) -> anyhow::Result<usize> {
+        let subscriptions = queries::list_claimable_webhook_subscriptions(&self.pool).await?;
+        let mut budget = self.config.claim_batch_size;
+        let mut claimed = 0;
+
+        for subscription in subscriptions {
+            if budget <= 0 {
+                break;
+            }
+
+            let available = self.limiter.available(subscription.id, subscription.max_in_flight);
+            crate::metrics::update_webhook_in_flight(
+                subscription.id,
+                self.limiter.in_flight(subscription.id),
+            );
+            if available == 0 {
+                crate::metrics::record_webhook_saturated(subscription.id);
+                self.cool_down(&subscription, COOLDOWN_SATURATED).await;
+                continue;
+            }
+
+            let lease_until = self.clock.now()
+                + chrono::Duration::seconds(self.config.delivery_timeout_secs as i64 + 30);
+            let deliveries = queries::claim_webhook_deliveries(
+                &self.pool,
+                subscription.id,
+                (available as i64).min(budget),
+                lease_until,
+            )
+            .await?;
+            budget -= deliveries.len() as i64;
+            claimed += deliveries.len();
+
+            let mut deliveries = deliveries.into_iter();
+            while let Some(delivery) = deliveries.next() {
+                // Only this task claims for the subscription, so the slots
+                // counted above are still free. If not, the rest are released
+                // for the next poll.
+                let Some(permit) = self.limiter.try_acquire(subscription.id, subscription.max_in_flight)
+                else {
+                    let unsent: Vec<Uuid> =
+                        std::iter::once(delivery.id).chain(deliveries.map(|d| d.id)).collect();
+                    self.release(&unsent).await;
+                    break;
+                };
+                let dispatcher = self.clone();
+                let subscription = subscription.clone();
+                let span = delivery
+                    .correlation_id
+                    .as_deref()
+                    .and_then(CorrelationContext::parse)
+                    .map_or_else(tracing::Span::none, |ctx| ctx.span("webhook_delivery"));
+                self.shutdown.spawn(
+                    async move {
+                        let _permit = permit;
+                        dispatcher.deliver(&subscription, delivery).await;
+                    }
+                    .instrument(span),
+                );
+            }
+        }
+
+        Ok(claimed)
+    }
+
+    async fn deliver(&self, subscription: &WebhookSubscription, mut delivery: WebhookDelivery) {
+        // Rendered per attempt, so template edits apply to retries too
+        let message = self
+            .notifications
+            .display_message(&delivery.event_type, &subscription.locale, &delivery.payload)
+            .await;
+        if let Some(payload) = delivery.payload.as_object_mut() {
+            payload.insert("display_message".to_string(), message.into());
+        }
+
+        let timeout = Duration::from_secs(self.config.delivery_timeout_secs);
+        let send = send_delivery(
+            &self.http,
+            &subscription.url,
+            &delivery,
+            Some(&subscription.secret),
+            timeout,
+        );
+        let result = tokio::select! {
+            biased;
+            _ = self.shutdown.cancelled() => {
+                tracing::info!(delivery_id = %delivery.id, "Releasing delivery on shutdown");
+                self.release(&[delivery.id]).await;
+                return;
+            }
+            result = send => result,
+        };
+
+        let persisted = match &result {
+            Ok(()) => {
+                crate::metrics::record_webhook_delivery("delivered");
+                queries::mark_webhook_delivered(&self.pool, delivery.id).await
+            }
+            Err(error) => {
+                crate::metrics::record_webhook_delivery("failed");
+                tracing::warn!(
+                    subscription_id = %subscription.id,
+                    delivery_id = %delivery.id,
+                    attempt = delivery.attempts + 1,
+                    "Webhook delivery failed: {}",
+                    error
+                );
+                queries::mark_webhook_delivery_failed(
+                    &self.pool,
+                    delivery.id,
+                    error,
+                    self.clock.now() + retry_backoff(delivery.attempts),
+                    self.config.max_attempts,
+                )
+                .await
+                .map(|dead_lettered| {
+                    if dead_lettered {
+                        crate::metrics::record_webhook_delivery("dead_lettered");
+                        tracing::error!(
+                            subscription_id = %subscription.id,
+                            delivery_id = %delivery.id,
+                            "Webhook delivery dead-lettered after {} attempts",
+                            delivery.attempts + 1
+                        );
+                    }
+                })
+            }
+        };
+        if let Err(e) = persisted {
+            tracing::error!(delivery_id = %delivery.id, "Failed to record webhook delivery result: {}", e);
+            self.release(&[delivery.id]).await;
+        }
+
+        if self.limiter.record_outcome(subscription.id, result.is_ok()) {
+            self.cool_down(subscription, COOLDOWN_FAILURE_RATE).await;
+        }
+    }
+
+    /// Best effort: if this fails too, the lease still expires
+    async fn release(&self, ids: &[Uuid]) {
+        if let Err(e) = queries::release_webhook_deliveries(&self.pool, ids).await {
+            tracing::error!(deliveries = ids.len(), "Failed to release webhook deliveries: {}", e);
+        }
+    }
+
+    async fn cool_down(&self, subscription: &WebhookSubscription, reason: &'static str) {
+        let until = self.clock.now() + chrono::Duration::seconds(self.config.cooldown_secs as i64);
+        match queries::set_webhook_subscription_cooldown(&self.pool, subscription.id, until, reason).await {
+            Ok(()) => {
+                crate::metrics::record_webhook_cooldown(subscription.id, reason);
+                tracing::warn!(
+                    subscription_id = %subscription.id,
+                    cooldown_until = %until,
+                    "Webhook subscription cooling down: {}",
+                    reason
+                );
+            }
+            Err(e) => {
+                tracing::error!(subscription_id = %subscription.id, "Failed to set webhook cool-down: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limiter_bounds_in_flight_per_subscription() {
+        let limiter = DeliveryLimiter::new(2, 20, 0.5);
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+
+        let _p1 = limiter.try_acquire(a, None).unwrap();
+        let _p2 = limiter.try_acquire(a, None).unwrap();
+        assert!(limiter.try_acquire(a, None).is_none());
+        assert_eq!(limiter.in_flight(a), 2);
+        assert!(limiter.try_acquire(b, None).is_some());
+    }
+
+    #[test]
+    fn test_subscription_override_limit() {
+        let limiter = DeliveryLimiter::new(2, 20, 0.5);
+        let a = Uuid::new_v4();
+        assert_eq!(limiter.available(a, Some(5)), 5);
+        assert_eq!(limiter.available(a, None), 2);
+    }
+
+    #[test]
+    fn test_failure_rate_threshold_trips_once() {
+        let limiter = DeliveryLimiter::new(2, 10, 0.5);
+        let a = Uuid::new_v4();
+        limiter.available(a, None);
+
+        for _ in 0..4 {
+            assert!(!limiter.record_outcome(a, false));
+        }
+        // Fifth sample makes the window large enough to judge
+        assert!(limiter.record_outcome(a, false));
+        assert_eq!(limiter.failure_rate(a), None);
+    }
+
+    #[test]
+    fn test_mostly_successful_subscription_does_not_trip() {
+        let limiter = DeliveryLimiter::new(2, 10, 0.5);
+        let a = Uuid::new_v4();
+        limiter.available(a, None);
+
+        for i in 0..10 {
+            assert!(!limiter.record_outcome(a, i % 3 != 0));
+        }
+        assert!(limiter.failure_rate(a).unwrap() < 0.5);
+    }
+
+    #[test]
+    fn test_sign_delivery() {
+        let body = br#"{"transaction_id":"3f1c"}"#;
+        let header = sign_delivery("whsec_test", 1767225600, body);
+        let (timestamp, mac) = header.split_once(",v1=").unwrap();
+        assert_eq!(timestamp, "t=1767225600");
+        let signed = [b"1767225600.".as_slice(), body].concat();
+        assert_eq!(signature::verify("whsec_test", &signed, mac), Ok(()));
+        // The timestamp is covered by the signature
+        assert_ne!(sign_delivery("whsec_test", 1767225601, body), header);
+    }
+
+    #[test]
+    fn test_retry_backoff_is_capped() {
+        assert_eq!(retry_backoff(0), chrono::Duration::seconds(10));
+        assert_eq!(retry_backoff(2), chrono::Duration::seconds(40));
+        assert_eq!(retry_backoff(30), chrono::Duration::seconds(MAX_BACKOFF_SECS));
+    }
+}
@@ -1,10 +1,18 @@
+use chrono::{DateTime, Utc};
 use failsafe::futures::CircuitBreaker as FuturesCircuitBreaker;
 use failsafe::{backoff, failure_policy, Config, Error as FailsafeError, StateMachine};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+use crate::config::HorizonConfig;
+use crate::metrics;
+use crate::stellar::sandbox::FakeHorizon;
+use crate::telemetry;
+use crate::utils::correlation::CorrelationContext;
+
 #[derive(Error, Debug)]
 pub enum HorizonError {
     #[error("HTTP request failed: {0}")]
@@ -15,6 +23,25 @@ pub enum HorizonError {
     InvalidResponse(String),
     #[error("Circuit breaker open: {0}")]
     CircuitBreakerOpen(String),
+    /// Horizon rejected a submission; carries the transaction result code
+    #[error("Transaction failed: {0}")]
+    TransactionFailed(String),
+    /// A submission failed with `tx_failed` because of its operations;
+    /// carries their result codes, such as `op_no_trust`
+    #[error("Operation failed: {}", .0.join(", "))]
+    OperationFailed(Vec<String>),
+}
+
+impl HorizonError {
+    /// `outcome` label of `horizon_requests_total`
+    pub fn metric_outcome(&self) -> &'static str {
+        match self {
+            HorizonError::AccountNotFound(_) => "not_found",
+            HorizonError::CircuitBreakerOpen(_) => "circuit_open",
+            HorizonError::TransactionFailed(_) | HorizonError::OperationFailed(_) => "rejected",
+            HorizonError::RequestError(_) | HorizonError::InvalidResponse(_) => "error",
+        }
+    }
 }
 
 /// Response from Horizon /accounts endpoint
@@ -39,31 +66,233 @@ pub struct Balance {
     pub asset_issuer: Option<String>,
 }
 
+/// A single operation effect from the Horizon /effects endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Effect {
+    pub id: String,
+    pub paging_token: String,
+    /// Account the effect belongs to; for authorization effects this is the issuer
+    pub account: String,
+    #[serde(rename = "type")]
+    pub effect_type: String,
+    pub created_at: String,
+    pub asset_code: Option<String>,
+    pub asset_issuer: Option<String>,
+    /// Set on authorization effects: the account whose trustline changed
+    pub trustor: Option<String>,
+    /// Set on balance effects such as `account_credited`
+    pub amount: Option<String>,
+}
+
+/// A payment-like operation from the Horizon `/accounts/{id}/payments`
+/// endpoint, joined with its transaction. Fields are set depending on
+/// `operation_type`, so every one of them is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: String,
+    pub paging_token: String,
+    #[serde(rename = "type")]
+    pub operation_type: String,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub transaction_hash: Option<String>,
+    /// `payment` and path payments: sender, recipient and the received amount
+    #[serde(default)]
+    pub from: Option<String>,
+    #[serde(default)]
+    pub to: Option<String>,
+    #[serde(default)]
+    pub amount: Option<String>,
+    #[serde(default)]
+    pub asset_type: Option<String>,
+    #[serde(default)]
+    pub asset_code: Option<String>,
+    #[serde(default)]
+    pub asset_issuer: Option<String>,
+    /// Path payments: the amount and asset the sender paid with
+    #[serde(default)]
+    pub source_amount: Option<String>,
+    #[serde(default)]
+    pub source_asset_type: Option<String>,
+    #[serde(default)]
+    pub source_asset_code: Option<String>,
+    #[serde(default)]
+    pub source_asset_issuer: Option<String>,
+    /// `account_merge`: the merged account and the account it merged into
+    #[serde(default)]
+    pub account: Option<String>,
+    #[serde(default)]
+    pub into: Option<String>,
+    #[serde(default)]
+    pub transaction: Option<OperationTransaction>,
+}
+
+/// The parts of an operation's transaction the payment listener reads
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OperationTransaction {
+    #[serde(default)]
+    pub memo: Option<String>,
+    #[serde(default)]
+    pub memo_type: Option<String>,
+}
+
+/// Response from Horizon `POST /transactions`, and the parts of
+/// `GET /transactions/{hash}` the payment processor reads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitResponse {
+    pub hash: String,
+    pub ledger: i64,
+    pub successful: bool,
+    /// Fee the network charged, in stroops
+    #[serde(default)]
+    pub fee_charged: Option<String>,
+    /// Base64 `TransactionResult` XDR, with the result of every operation
+    #[serde(default)]
+    pub result_xdr: Option<String>,
+    /// Close time of the ledger the transaction landed in
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// The parts of Horizon's root resource, `GET /`, the readiness check reads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HorizonRoot {
+    #[serde(default)]
+    pub horizon_version: String,
+    /// Latest ledger Horizon has ingested
+    pub history_latest_ledger: i64,
+    /// Latest ledger of Horizon's Stellar Core node
+    pub core_latest_ledger: i64,
+    #[serde(default)]
+    pub network_passphrase: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubmitErrorBody {
+    extras: Option<SubmitErrorExtras>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubmitErrorExtras {
+    result_codes: SubmitResultCodes,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubmitResultCodes {
+    transaction: String,
+    #[serde(default)]
+    operations: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EffectsPage {
+    #[serde(rename = "_embedded")]
+    embedded: EffectsEmbedded,
+}
+
+#[derive(Debug, Deserialize)]
+struct EffectsEmbedded {
+    records: Vec<Effect>,
+}
+
+/// Order of a Horizon collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+impl Order {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Order::Asc => "asc",
+            Order::Desc => "desc",
+        }
+    }
+}
+
+/// One page of an account's payments
+#[derive(Debug, Clone, Default)]
+pub struct PaymentsPage {
+    pub records: Vec<Operation>,
+    /// Cursor of the following page; `None` when this page was the last
+    pub next_cursor: Option<String>,
+}
+
+/// Retries of requests Horizon answers with `429` or a `5xx`. The delay
+/// doubles from `base_delay` up to `max_delay`; a `Retry-After` header
+/// replaces it, within the same cap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry`, counting from 0
+    fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        let backoff = self.base_delay.saturating_mul(2u32.saturating_pow(retry));
+        retry_after.unwrap_or(backoff).min(self.max_delay)
+    }
+}
+
+fn is_retryable(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// `Retry-After` in seconds; Horizon doesn't send the HTTP-date form
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[derive(Debug, Deserialize)]
+struct OperationsPage {
+    #[serde(rename = "_embedded")]
+    embedded: OperationsEmbedded,
+}
+
+#[derive(Debug, Deserialize)]
+struct OperationsEmbedded {
+    records: Vec<Operation>,
+}
+
 /// HTTP client for interacting with the Stellar Horizon API
 #[derive(Clone)]
 pub struct HorizonClient {
     client: Client,
     base_url: String,
     circuit_breaker: StateMachine<failure_policy::ConsecutiveFailures<backoff::EqualJittered>, ()>,
+    /// Sent as `X-Correlation-Id` on every request when set
+    correlation: Option<CorrelationContext>,
+    /// Answers every call in place of Horizon when set
+    sandbox: Option<Arc<FakeHorizon>>,
+    retry: RetryPolicy,
 }
 
 impl HorizonClient {
     /// Creates a new HorizonClient with the specified base URL
     pub fn new(base_url: String) -> Self {
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
-            .unwrap_or_default();
-
-        let backoff = backoff::equal_jittered(Duration::from_secs(60), Duration::from_secs(120));
-        let policy = failure_policy::consecutive_failures(3, backoff);
-        let circuit_breaker = Config::new().failure_policy(policy).build();
-
-        HorizonClient {
-            client,
-            base_url,
-            circuit_breaker,
-        }
+        Self::from_config(base_url, &HorizonConfig::default())
     }
 
     /// Creates a new HorizonClient with custom circuit breaker configuration
@@ -72,22 +301,76 @@ impl HorizonClient {
         failure_threshold: u32,
         reset_timeout_secs: u64,
     ) -> Self {
+        let config = HorizonConfig {
+            circuit_failure_threshold: failure_threshold,
+            circuit_reset_timeout: Duration::from_secs(reset_timeout_secs),
+            ..HorizonConfig::default()
+        };
+        Self::from_config(base_url, &config)
+    }
+
+    /// A client with the timeout, retries and circuit breaker of `config`
+    pub fn from_config(base_url: String, config: &HorizonConfig) -> Self {
         let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
+            .timeout(config.request_timeout)
             .build()
             .unwrap_or_default();
 
         let backoff = backoff::equal_jittered(
-            Duration::from_secs(reset_timeout_secs),
-            Duration::from_secs(reset_timeout_secs * 2),
+            config.circuit_reset_timeout,
+            config.circuit_reset_timeout * 2,
         );
-        let policy = failure_policy::consecutive_failures(failure_threshold, backoff);
+        let policy =
+            failure_policy::consecutive_failures(config.circuit_failure_threshold, backoff);
         let circuit_breaker = Config::new().failure_policy(policy).build();
 
         HorizonClient {
             client,
             base_url,
             circuit_breaker,
+            correlation: None,
+            sandbox: None,
+            retry: RetryPolicy {
+                max_retries: config.max_retries,
+                base_delay: config.retry_base_delay,
+                max_delay: config.retry_max_delay,
+            },
+        }
+    }
+
+    /// A client answered by `fake` instead of Horizon, for `APP_ENV=sandbox`
+    pub fn sandbox(fake: Arc<FakeHorizon>) -> Self {
+        Self {
+            sandbox: Some(fake),
+            ..Self::new("sandbox://horizon".to_string())
+        }
+    }
+
+    /// Retry rate-limited and failed requests by `retry` instead of the default
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// The fake answering this client, if it is a sandbox client
+    pub fn fake(&self) -> Option<&Arc<FakeHorizon>> {
+        self.sandbox.as_ref()
+    }
+
+    /// A client whose requests carry `ctx`'s correlation id. It shares the
+    /// connection pool and circuit breaker with `self`.
+    pub fn correlated(&self, ctx: &CorrelationContext) -> Self {
+        Self {
+            correlation: Some(ctx.clone()),
+            ..self.clone()
+        }
+    }
+
+    fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        let request = telemetry::inject(self.client.get(url));
+        match &self.correlation {
+            Some(ctx) => ctx.apply(request),
+            None => request,
         }
     }
 
@@ -100,35 +383,392 @@ impl HorizonClient {
         }
     }
 
+    /// The circuit breaker's answer as a [`HorizonError`], recorded in
+    /// `horizon_requests_total`, `horizon_request_duration_seconds` and
+    /// `horizon_circuit_open`
+    fn finish<T>(
+        &self,
+        endpoint: &'static str,
+        started: Instant,
+        result: Result<T, FailsafeError<HorizonError>>,
+    ) -> Result<T, HorizonError> {
+        let result = match result {
+            Ok(value) => Ok(value),
+            Err(FailsafeError::Rejected) => Err(HorizonError::CircuitBreakerOpen(
+                "Horizon API circuit breaker is open".to_string(),
+            )),
+            Err(FailsafeError::Inner(e)) => Err(e),
+        };
+        let outcome = match &result {
+            Ok(_) => "success",
+            Err(e) => e.metric_outcome(),
+        };
+        metrics::record_horizon_request(endpoint, outcome, started.elapsed());
+        metrics::update_horizon_circuit_open(!self.circuit_breaker.is_call_permitted());
+        result
+    }
+
     /// Fetches account details from the Horizon API
+    #[tracing::instrument(name = "horizon.get_account", skip(self))]
     pub async fn get_account(&self, address: &str) -> Result<AccountResponse, HorizonError> {
+        if let Some(fake) = &self.sandbox {
+            return fake.get_account(address).await;
+        }
         let url = format!(
             "{}/accounts/{}",
             self.base_url.trim_end_matches('/'),
             address
         );
 
+        let request = self.get(&url);
+        let retry = self.retry;
+
+        let started = Instant::now();
         let result = self
             .circuit_breaker
-            .call(async move {
-                let response = client.get(&url).send().await?;
+            .call_with(is_outage, async move {
+                let response = send_with_retry(request, retry).await?;
 
                 if response.status() == 404 {
-                    return Err(HorizonError::AccountNotFound(addr));
+                    return Err(HorizonError::AccountNotFound(address.to_string()));
                 }
 
-                let account = response.json::<AccountResponse>().await?;
+                let account = response.error_for_status()?.json::<AccountResponse>().await?;
                 Ok(account)
             })
             .await;
 
-        match result {
-            Ok(account) => Ok(account),
-            Err(FailsafeError::Rejected) => Err(HorizonError::CircuitBreakerOpen(
-                "Horizon API circuit breaker is open".to_string(),
-            )),
-            Err(FailsafeError::Inner(e)) => Err(e),
+        self.finish("account", started, result)
+    }
+
+    /// Fetches effects in ascending order starting after `cursor`, or from
+    /// the oldest one without a cursor
+    #[tracing::instrument(name = "horizon.get_effects", skip(self))]
+    pub async fn get_effects(
+        &self,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<Effect>, HorizonError> {
+        if let Some(fake) = &self.sandbox {
+            return fake.get_effects(cursor, limit).await;
+        }
+        let mut url = format!(
+            "{}/effects?order=asc&limit={}",
+            self.base_url.trim_end_matches('/'),
+            limit
+        );
+        if let Some(cursor) = cursor {
+            url.push_str("&cursor=");
+            url.push_str(cursor);
+        }
+        let request = self.get(&url);
+        let retry = self.retry;
+
+        let started = Instant::now();
+        let result = self
+            .circuit_breaker
+            .call_with(is_outage, async move {
+                let response = send_with_retry(request, retry).await?.error_for_status()?;
+                let page = response.json::<EffectsPage>().await?;
+                Ok(page.embedded.records)
+            })
+            .await;
+
+        self.finish("effects", started, result)
+    }
+
+    /// Paging token of the newest effect, for streams that start from the
+    /// present instead of replaying history
+    #[tracing::instrument(name = "horizon.latest_effect_cursor", skip(self))]
+    pub async fn latest_effect_cursor(&self) -> Result<Option<String>, HorizonError> {
+        if let Some(fake) = &self.sandbox {
+            return fake.latest_effect_cursor().await;
         }
+        let url = format!("{}/effects?order=desc&limit=1", self.base_url.trim_end_matches('/'));
+        let request = self.get(&url);
+        let retry = self.retry;
+
+        let started = Instant::now();
+        let result = self
+            .circuit_breaker
+            .call_with(is_outage, async move {
+                let response = send_with_retry(request, retry).await?.error_for_status()?;
+                let page = response.json::<EffectsPage>().await?;
+                Ok(page.embedded.records.into_iter().next().map(|effect| effect.paging_token))
+            })
+            .await;
+
+        self.finish("effects", started, result)
+    }
+
+    /// Fetches payment-like operations received or sent by `account`, in
+    /// ascending order starting after `cursor` (the oldest one without a
+    /// cursor), each joined with its transaction
+    #[tracing::instrument(name = "horizon.get_payments", skip(self))]
+    pub async fn get_payments(
+        &self,
+        account: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<Operation>, HorizonError> {
+        if let Some(fake) = &self.sandbox {
+            return fake.get_payments(account, cursor, limit).await;
+        }
+        let mut url = format!(
+            "{}/accounts/{}/payments?order=asc&limit={}&join=transactions",
+            self.base_url.trim_end_matches('/'),
+            account,
+            limit
+        );
+        if let Some(cursor) = cursor {
+            url.push_str("&cursor=");
+            url.push_str(cursor);
+        }
+        let request = self.get(&url);
+        let retry = self.retry;
+
+        let started = Instant::now();
+        let result = self
+            .circuit_breaker
+            .call_with(is_outage, async move {
+                let response = send_with_retry(request, retry).await?.error_for_status()?;
+                let page = response.json::<OperationsPage>().await?;
+                Ok(page.embedded.records)
+            })
+            .await;
+
+        self.finish("payments", started, result)
+    }
+
+    /// Fetches one page of payment-like operations received or sent by
+    /// `account`, each joined with its transaction. Without a cursor the page
+    /// starts at the oldest operation for [`Order::Asc`] and the newest for
+    /// [`Order::Desc`]. Requests answered with `429` or a `5xx` are retried
+    /// per the client's [`RetryPolicy`].
+    #[tracing::instrument(name = "horizon.get_account_payments", skip(self))]
+    pub async fn get_account_payments(
+        &self,
+        account: &str,
+        cursor: Option<&str>,
+        limit: u32,
+        order: Order,
+    ) -> Result<PaymentsPage, HorizonError> {
+        if let Some(fake) = &self.sandbox {
+            return fake.get_account_payments(account, cursor, limit, order).await;
+        }
+        let mut url = format!(
+            "{}/accounts/{}/payments?order={}&limit={}&join=transactions",
+            self.base_url.trim_end_matches('/'),
+            account,
+            order.as_str(),
+            limit
+        );
+        if let Some(cursor) = cursor {
+            url.push_str("&cursor=");
+            url.push_str(cursor);
+        }
+        let request = self.get(&url);
+        let retry = self.retry;
+
+        let started = Instant::now();
+        let result = self
+            .circuit_breaker
+            .call_with(is_outage, async move {
+                let response = send_with_retry(request, retry).await?;
+                if response.status() == 404 {
+                    return Err(HorizonError::AccountNotFound(account.to_string()));
+                }
+                let page = response.error_for_status()?.json::<OperationsPage>().await?;
+                let records = page.embedded.records;
+                let next_cursor = (records.len() as u32 >= limit)
+                    .then(|| records.last().map(|op| op.paging_token.clone()))
+                    .flatten();
+                Ok(PaymentsPage { records, next_cursor })
+            })
+            .await;
+
+        self.finish("account_payments", started, result)
+    }
+
+    /// Fetches the effects of a single operation
+    #[tracing::instrument(name = "horizon.get_operation_effects", skip(self))]
+    pub async fn get_operation_effects(&self, operation_id: &str) -> Result<Vec<Effect>, HorizonError> {
+        if let Some(fake) = &self.sandbox {
+            return fake.get_operation_effects(operation_id).await;
+        }
+        let url = format!(
+            "{}/operations/{}/effects?limit=200",
+            self.base_url.trim_end_matches('/'),
+            operation_id
+        );
+        let request = self.get(&url);
+        let retry = self.retry;
+
+        let started = Instant::now();
+        let result = self
+            .circuit_breaker
+            .call_with(is_outage, async move {
+                let response = send_with_retry(request, retry).await?.error_for_status()?;
+                let page = response.json::<EffectsPage>().await?;
+                Ok(page.embedded.records)
+            })
+            .await;
+
+        self.finish("operation_effects", started, result)
+    }
+
+    /// Looks a transaction up by hash; `None` if Horizon has not seen it
+    /// in a ledger
+    #[tracing::instrument(name = "horizon.get_transaction", skip(self))]
+    pub async fn get_transaction(&self, hash: &str) -> Result<Option<SubmitResponse>, HorizonError> {
+        if let Some(fake) = &self.sandbox {
+            return fake.get_transaction(hash).await;
+        }
+        let url = format!("{}/transactions/{}", self.base_url.trim_end_matches('/'), hash);
+        let request = self.get(&url);
+        let retry = self.retry;
+
+        let started = Instant::now();
+        let result = self
+            .circuit_breaker
+            .call_with(is_outage, async move {
+                let response = send_with_retry(request, retry).await?;
+                if response.status() == 404 {
+                    return Ok(None);
+                }
+                let transaction = response.error_for_status()?.json::<SubmitResponse>().await?;
+                Ok(Some(transaction))
+            })
+            .await;
+
+        self.finish("transaction", started, result)
+    }
+
+    /// Fetches Horizon's root resource, for readiness checks. A probe rather
+    /// than a call: it is neither retried nor counted by the circuit breaker,
+    /// so a failing probe can't open the breaker for real traffic.
+    #[tracing::instrument(name = "horizon.get_root", skip(self))]
+    pub async fn get_root(&self) -> Result<HorizonRoot, HorizonError> {
+        if let Some(fake) = &self.sandbox {
+            return Ok(fake.get_root());
+        }
+        let url = format!("{}/", self.base_url.trim_end_matches('/'));
+
+        let started = Instant::now();
+        let result = async {
+            let response = self.get(&url).send().await?.error_for_status()?;
+            Ok(response.json::<HorizonRoot>().await?)
+        }
+        .await;
+
+        let outcome = match &result {
+            Ok(_) => "success",
+            Err(e) => e.metric_outcome(),
+        };
+        metrics::record_horizon_request("root", outcome, started.elapsed());
+        result
+    }
+
+    /// Submits a signed transaction envelope. `source_account` is the
+    /// envelope's source; Horizon reads it from the envelope, the sandbox
+    /// uses it to force failures.
+    #[tracing::instrument(name = "horizon.submit_transaction", skip(self, envelope_xdr))]
+    pub async fn submit_transaction(
+        &self,
+        source_account: &str,
+        envelope_xdr: &str,
+    ) -> Result<SubmitResponse, HorizonError> {
+        if let Some(fake) = &self.sandbox {
+            return fake.submit_transaction(source_account, envelope_xdr).await;
+        }
+
+        let url = format!("{}/transactions", self.base_url.trim_end_matches('/'));
+        let mut request = telemetry::inject(self.client.post(&url).form(&[("tx", envelope_xdr)]));
+        if let Some(ctx) = &self.correlation {
+            request = ctx.apply(request);
+        }
+
+        let started = Instant::now();
+        let result = self
+            .circuit_breaker
+            .call_with(is_outage, async move {
+                let response = request.send().await?;
+                if response.status() == 400 {
+                    let body = response.json::<SubmitErrorBody>().await?;
+                    let codes = body.extras.map(|extras| extras.result_codes);
+                    return Err(match codes {
+                        Some(codes)
+                            if codes.transaction == "tx_failed" && !codes.operations.is_empty() =>
+                        {
+                            HorizonError::OperationFailed(codes.operations)
+                        }
+                        Some(codes) => HorizonError::TransactionFailed(codes.transaction),
+                        None => HorizonError::TransactionFailed("tx_failed".to_string()),
+                    });
+                }
+                let submitted = response.error_for_status()?.json::<SubmitResponse>().await?;
+                Ok(submitted)
+            })
+            .await;
+
+        self.finish("submit", started, result)
+    }
+}
+
+/// Send an idempotent `request`, retrying timeouts, failed connections and
+/// `429` and `5xx` answers. The last answer is returned whatever its status.
+/// Submissions never come through here: a timed out one may still land.
+async fn send_with_retry(
+    request: reqwest::RequestBuilder,
+    retry: RetryPolicy,
+) -> Result<reqwest::Response, HorizonError> {
+    let mut attempt = 0;
+    loop {
+        let next = request.try_clone().ok_or_else(|| {
+            HorizonError::InvalidResponse("request cannot be retried".to_string())
+        })?;
+        let delay = match next.send().await {
+            Ok(response) if !is_retryable(response.status()) || attempt >= retry.max_retries => {
+                return Ok(response);
+            }
+            Ok(response) => {
+                let delay = retry.delay(attempt, retry_after(&response));
+                tracing::debug!(
+                    status = %response.status(),
+                    attempt = attempt + 1,
+                    "Horizon request will be retried in {:?}",
+                    delay
+                );
+                delay
+            }
+            Err(e) if (e.is_timeout() || e.is_connect()) && attempt < retry.max_retries => {
+                let delay = retry.delay(attempt, None);
+                tracing::debug!(
+                    error = %e,
+                    attempt = attempt + 1,
+                    "Horizon request will be retried in {:?}",
+                    delay
+                );
+                delay
+            }
+            Err(e) => return Err(e.into()),
+        };
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Whether a failed call counts towards opening the circuit breaker: Horizon
+/// being unreachable or failing, not answering for a missing account or
+/// refusing a submission
+fn is_outage(error: &HorizonError) -> bool {
+    match error {
+        HorizonError::RequestError(e) => e.status().map_or(true, |status| status.is_server_error()),
+        HorizonError::InvalidResponse(_) => true,
+        HorizonError::AccountNotFound(_)
+        | HorizonError::CircuitBreakerOpen(_)
+        | HorizonError::TransactionFailed(_)
+        | HorizonError::OperationFailed(_) => false,
     }
 }
 
@@ -203,6 +843,203 @@ mod tests {
         assert!(matches!(result, Err(HorizonError::AccountNotFound(_))));
     }
 
+    #[tokio::test]
+    async fn test_correlated_client_sends_correlation_header() {
+        use crate::utils::correlation::CORRELATION_HEADER;
+
+        let mut server = mockito::Server::new();
+
+        let _mock = server
+            .mock("GET", mockito::Matcher::Regex(r".*/effects.*".into()))
+            .match_header(CORRELATION_HEADER, "req-horizon-1")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"_embedded": {"records": []}}"#)
+            .create();
+
+        let ctx = CorrelationContext::parse("req-horizon-1").unwrap();
+        let client = HorizonClient::new(server.url()).correlated(&ctx);
+        assert!(client.get_effects(None, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_submit_transaction_reports_result_code() {
+        let mut server = mockito::Server::new();
+
+        let _mock = server
+            .mock("POST", "/transactions")
+            .with_status(400)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"extras": {"result_codes": {"transaction": "tx_bad_seq"}}}"#)
+            .create();
+
+        let client = HorizonClient::new(server.url());
+        let result = client.submit_transaction("GSOURCE", "AAAA").await;
+        assert!(matches!(result, Err(HorizonError::TransactionFailed(code)) if code == "tx_bad_seq"));
+    }
+
+    #[tokio::test]
+    async fn test_submit_transaction_reports_operation_codes() {
+        let mut server = mockito::Server::new();
+
+        let _mock = server
+            .mock("POST", "/transactions")
+            .with_status(400)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"extras": {"result_codes": {"transaction": "tx_failed", "operations": ["op_no_trust"]}}}"#,
+            )
+            .create();
+
+        let client = HorizonClient::new(server.url());
+        let result = client.submit_transaction("GSOURCE", "AAAA").await;
+        assert!(matches!(result, Err(HorizonError::OperationFailed(codes)) if codes == ["op_no_trust"]));
+    }
+
+    #[tokio::test]
+    async fn test_get_transaction() {
+        let mut server = mockito::Server::new();
+
+        let _found = server
+            .mock("GET", "/transactions/abc123")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"hash": "abc123", "ledger": 42, "successful": true, "memo_type": "none",
+                    "fee_charged": "100", "result_xdr": "AAAAAAAAAGQAAAAAAAAAAQAAAAAAAAABAAAAAAAAAAA=",
+                    "created_at": "2026-02-21T10:00:00Z"}"#,
+            )
+            .create();
+        let _missing = server.mock("GET", "/transactions/def456").with_status(404).create();
+
+        let client = HorizonClient::new(server.url());
+        let found = client.get_transaction("abc123").await.unwrap().unwrap();
+        assert_eq!(found.ledger, 42);
+        assert!(found.successful);
+        assert_eq!(found.fee_charged.as_deref(), Some("100"));
+        assert!(found.result_xdr.is_some());
+        assert_eq!(found.created_at.unwrap().to_rfc3339(), "2026-02-21T10:00:00+00:00");
+        assert!(client.get_transaction("def456").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_root_bypasses_the_circuit_breaker() {
+        let mut server = mockito::Server::new();
+
+        let _down = server.mock("GET", "/").with_status(503).create();
+        let client = HorizonClient::with_circuit_breaker(server.url(), 1, 60);
+        assert!(client.get_root().await.is_err());
+        assert!(client.get_root().await.is_err());
+        assert_eq!(client.circuit_state(), "closed");
+
+        let mut server = mockito::Server::new();
+        let _up = server
+            .mock("GET", "/")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"horizon_version": "2.30.0", "history_latest_ledger": 41,
+                    "core_latest_ledger": 42, "network_passphrase": "Test SDF Network"}"#,
+            )
+            .create();
+        let root = HorizonClient::new(server.url()).get_root().await.unwrap();
+        assert_eq!((root.history_latest_ledger, root.core_latest_ledger), (41, 42));
+    }
+
+    #[tokio::test]
+    async fn test_sandbox_client_never_calls_horizon() {
+        let client = HorizonClient::sandbox(Arc::new(FakeHorizon::new(Duration::ZERO)));
+        assert!(client.fake().is_some());
+        let missing = crate::db::seed::ACCOUNTS[2].address;
+        assert!(client.get_account(missing).await.is_err());
+        assert!(client.get_effects(None, 10).await.unwrap().is_empty());
+        assert!(client.submit_transaction("GSOURCE", "AAAA").await.unwrap().successful);
+    }
+
+    #[tokio::test]
+    async fn test_missing_accounts_do_not_open_the_circuit() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", mockito::Matcher::Regex(r".*/accounts/.*".into()))
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let client = HorizonClient::with_circuit_breaker(server.url(), 2, 60);
+        for _ in 0..3 {
+            let result = client.get_account("GMISSING").await;
+            assert!(matches!(result, Err(HorizonError::AccountNotFound(_))));
+        }
+        assert_eq!(client.circuit_state(), "closed");
+    }
+
+    #[tokio::test]
+    async fn test_get_transaction_retries_rate_limits() {
+        let mut server = mockito::Server::new_async().await;
+        let limited = server
+            .mock("GET", "/transactions/abc")
+            .with_status(429)
+            .with_header("retry-after", "0")
+            .expect(1)
+            .create_async()
+            .await;
+        let found = server
+            .mock("GET", "/transactions/abc")
+            .with_status(404)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = HorizonClient::new(server.url()).with_retry(RetryPolicy {
+            max_retries: 1,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        });
+        assert!(client.get_transaction("abc").await.unwrap().is_none());
+        limited.assert_async().await;
+        found.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_connection_failures_surface_after_retries() {
+        // Nothing listens on port 1
+        let client = HorizonClient::new("http://127.0.0.1:1".to_string()).with_retry(RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        });
+        let result = client.get_effects(None, 10).await;
+        assert!(matches!(result, Err(HorizonError::RequestError(e)) if e.is_connect()));
+        assert_eq!(client.circuit_state(), "closed");
+    }
+
+    #[test]
+    fn test_from_config() {
+        let config = HorizonConfig {
+            max_retries: 5,
+            retry_base_delay: Duration::from_millis(100),
+            retry_max_delay: Duration::from_secs(2),
+            ..HorizonConfig::default()
+        };
+        let client = HorizonClient::from_config("http://localhost".to_string(), &config);
+        assert_eq!(
+            client.retry,
+            RetryPolicy {
+                max_retries: 5,
+                base_delay: Duration::from_millis(100),
+                max_delay: Duration::from_secs(2),
+            }
+        );
+    }
+
+    #[test]
+    fn test_metric_outcome() {
+        assert_eq!(HorizonError::AccountNotFound("G".into()).metric_outcome(), "not_found");
+        assert_eq!(HorizonError::CircuitBreakerOpen("open".into()).metric_outcome(), "circuit_open");
+        assert_eq!(HorizonError::TransactionFailed("tx_bad_seq".into()).metric_outcome(), "rejected");
+        assert_eq!(HorizonError::InvalidResponse("eof".into()).metric_outcome(), "error");
+    }
+
     #[test]
     fn test_circuit_breaker_state() {
         let client = HorizonClient::new("https://horizon-testnet.stellar.org".to_string());
@@ -231,7 +1068,12 @@ mod tests {
             .expect_at_least(3)
             .create();
 
-        let client = HorizonClient::with_circuit_breaker(server.url(), 3, 1);
+        let retry = RetryPolicy {
+            max_retries: 1,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        };
+        let client = HorizonClient::with_circuit_breaker(server.url(), 3, 1).with_retry(retry);
 
         // Make 3 failing requests to trip the circuit breaker
         for _ in 0..3 {
@@ -242,4 +1084,181 @@ mod tests {
         let result = client.get_account("TEST_ACCOUNT").await;
         assert!(matches!(result, Err(HorizonError::CircuitBreakerOpen(_))));
     }
+
+    #[tokio::test]
+    async fn test_get_effects_parses_records() {
+        let mut server = mockito::Server::new();
+
+        let mock_response = r#"{
+            "_embedded": {
+                "records": [
+                    {
+                        "id": "0000000012884905985-0000000001",
+                        "paging_token": "12884905985-1",
+                        "account": "GBBD47UZQ5CSKQPV456PYYH4FSYJHBWGQJUVNMCNWZ2NBEHKQPW3KXKJ",
+                        "type": "trustline_removed",
+                        "type_i": 22,
+                        "created_at": "2021-01-01T00:00:00Z",
+                        "asset_type": "credit_alphanum4",
+                        "asset_code": "USD",
+                        "asset_issuer": "GCKFBEIYV2U22IO2BJ4KVJOIP7XPWQGQFKKWXR6DOSJBV7STMAQSMTGG"
+                    }
+                ]
+            }
+        }"#;
+
+        let _mock = server
+            .mock("GET", mockito::Matcher::Regex(r".*/effects.*".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(mock_response)
+            .create();
+
+        let client = HorizonClient::new(server.url());
+        let effects = client.get_effects(Some("12884905984-1"), 200).await.unwrap();
+
+        assert_eq!(effects.len(), 1);
+        assert_eq!(effects[0].effect_type, "trustline_removed");
+        assert_eq!(effects[0].asset_code.as_deref(), Some("USD"));
+        assert!(effects[0].trustor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_effects_without_cursor_starts_from_oldest() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/effects")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("order".into(), "asc".into()),
+                mockito::Matcher::UrlEncoded("limit".into(), "200".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"_embedded": {"records": []}}"#)
+            .create();
+
+        let client = HorizonClient::new(server.url());
+        assert!(client.get_effects(None, 200).await.unwrap().is_empty());
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_latest_effect_cursor_reads_newest_paging_token() {
+        let mut server = mockito::Server::new();
+        let body = r#"{"_embedded": {"records": [{
+            "id": "0000000012884905985-0000000001",
+            "paging_token": "12884905985-1",
+            "account": "GBBD47UZQ5CSKQPV456PYYH4FSYJHBWGQJUVNMCNWZ2NBEHKQPW3KXKJ",
+            "type": "account_credited",
+            "created_at": "2021-01-01T00:00:00Z"
+        }]}}"#;
+        let _mock = server
+            .mock("GET", "/effects")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("order".into(), "desc".into()),
+                mockito::Matcher::UrlEncoded("limit".into(), "1".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(body)
+            .create();
+
+        let client = HorizonClient::new(server.url());
+        let cursor = client.latest_effect_cursor().await.unwrap();
+        assert_eq!(cursor.as_deref(), Some("12884905985-1"));
+    }
+
+    #[tokio::test]
+    async fn test_get_account_payments_pages_with_cursor() {
+        let mut server = mockito::Server::new();
+
+        let body = r#"{"_embedded": {"records": [
+            {"id": "200", "paging_token": "200", "type": "payment",
+             "created_at": "2026-01-02T00:00:00Z", "amount": "10.0000000"},
+            {"id": "100", "paging_token": "100", "type": "payment",
+             "created_at": "2026-01-01T00:00:00Z", "amount": "5.0000000"}
+        ]}}"#;
+        let mut mocks = Vec::new();
+        for limit in ["2", "3"] {
+            let mock = server
+                .mock("GET", "/accounts/GANCHOR/payments")
+                .match_query(mockito::Matcher::AllOf(vec![
+                    mockito::Matcher::UrlEncoded("order".into(), "desc".into()),
+                    mockito::Matcher::UrlEncoded("limit".into(), limit.into()),
+                    mockito::Matcher::UrlEncoded("join".into(), "transactions".into()),
+                    mockito::Matcher::UrlEncoded("cursor".into(), "300".into()),
+                ]))
+                .with_status(200)
+                .with_header("content-type", "application/json")
+                .with_body(body)
+                .create();
+            mocks.push(mock);
+        }
+
+        let client = HorizonClient::new(server.url());
+        let page = client
+            .get_account_payments("GANCHOR", Some("300"), 2, Order::Desc)
+            .await
+            .unwrap();
+        assert_eq!(page.records.len(), 2);
+        assert_eq!(page.records[1].created_at.unwrap().to_rfc3339(), "2026-01-01T00:00:00+00:00");
+        assert_eq!(page.next_cursor.as_deref(), Some("100"));
+
+        // A short page is the last one
+        let page = client
+            .get_account_payments("GANCHOR", Some("300"), 3, Order::Desc)
+            .await
+            .unwrap();
+        assert_eq!(page.records.len(), 2);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_account_payments_retries_server_errors() {
+        let mut server = mockito::Server::new();
+
+        let mock = server
+            .mock("GET", mockito::Matcher::Regex(r".*/payments.*".into()))
+            .with_status(503)
+            .expect(3)
+            .create();
+
+        let client = HorizonClient::new(server.url()).with_retry(RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        });
+        let result = client.get_account_payments("GANCHOR", None, 10, Order::Asc).await;
+        assert!(matches!(result, Err(HorizonError::RequestError(_))));
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_get_account_payments_does_not_retry_client_errors() {
+        let mut server = mockito::Server::new();
+
+        let mock = server
+            .mock("GET", mockito::Matcher::Regex(r".*/payments.*".into()))
+            .with_status(400)
+            .expect(1)
+            .create();
+
+        let client = HorizonClient::new(server.url()).with_retry(RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        });
+        assert!(client.get_account_payments("GANCHOR", None, 10, Order::Asc).await.is_err());
+        mock.assert();
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_within_cap() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(0, None), Duration::from_millis(500));
+        assert_eq!(policy.delay(2, None), Duration::from_secs(2));
+        assert_eq!(policy.delay(10, None), Duration::from_secs(10));
+        assert_eq!(policy.delay(0, Some(Duration::from_secs(3))), Duration::from_secs(3));
+        assert_eq!(policy.delay(0, Some(Duration::from_secs(60))), Duration::from_secs(10));
+    }
 }
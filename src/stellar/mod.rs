@@ -1,6 +1,15 @@
 pub mod client;
+pub mod quotes;
+pub mod sandbox;
+pub mod strkey;
+pub mod stream;
+pub mod transaction;
 
 pub use client::HorizonClient;
+pub use quotes::QuoteClient;
 
-pub use client::{AccountResponse, Balance, HorizonClient, HorizonError};
+pub use client::{
+    AccountResponse, Balance, Effect, HorizonClient, HorizonError, HorizonRoot, Operation,
+    OperationTransaction, Order, PaymentsPage, RetryPolicy, SubmitResponse,
+};
 
@@ -0,0 +1,326 @@
+//! SEP-38 (Anchor RFQ) client for the Anchor Platform.
+//!
+//! Assets use SEP-38 identifiers: `stellar:CODE:ISSUER` for on-chain assets
+//! and `iso4217:USD` for fiat. Amounts and prices are decimal strings on the
+//! wire and [`BigDecimal`] here.
+
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sqlx::types::BigDecimal;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum QuoteError {
+    #[error("HTTP request failed: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("Quote server rejected the request ({status}): {message}")]
+    Rejected { status: u16, message: String },
+}
+
+/// SEP-38 transaction context a price or quote is requested for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteContext {
+    Sep6,
+    Sep24,
+    Sep31,
+}
+
+/// `GET /prices`: indicative prices for everything `sell_asset` can buy
+#[derive(Debug, Clone, Serialize)]
+pub struct PricesRequest {
+    pub sell_asset: String,
+    pub sell_amount: BigDecimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sell_delivery_method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buy_delivery_method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country_code: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuyAssetPrice {
+    pub asset: String,
+    pub price: BigDecimal,
+    pub decimals: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct PricesResponse {
+    buy_assets: Vec<BuyAssetPrice>,
+}
+
+/// `GET /price`: indicative price for one pair. Exactly one of
+/// `sell_amount` and `buy_amount` is set.
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceRequest {
+    pub sell_asset: String,
+    pub buy_asset: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sell_amount: Option<BigDecimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buy_amount: Option<BigDecimal>,
+    pub context: QuoteContext,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuoteFee {
+    pub total: BigDecimal,
+    pub asset: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceResponse {
+    pub total_price: BigDecimal,
+    pub price: BigDecimal,
+    pub sell_amount: BigDecimal,
+    pub buy_amount: BigDecimal,
+    pub fee: QuoteFee,
+}
+
+/// `POST /quote`: request a firm quote. Exactly one of `sell_amount` and
+/// `buy_amount` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteRequest {
+    pub sell_asset: String,
+    pub buy_asset: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sell_amount: Option<BigDecimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buy_amount: Option<BigDecimal>,
+    /// Earliest expiry the quote must honour
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expire_after: Option<DateTime<Utc>>,
+    pub context: QuoteContext,
+}
+
+/// Firm quote returned by `POST /quote`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FirmQuote {
+    pub id: String,
+    pub expires_at: DateTime<Utc>,
+    pub total_price: BigDecimal,
+    pub price: BigDecimal,
+    pub sell_asset: String,
+    pub sell_amount: BigDecimal,
+    pub buy_asset: String,
+    pub buy_amount: BigDecimal,
+    pub fee: QuoteFee,
+}
+
+/// Asset code of a SEP-38 asset identifier (`stellar:USDC:G...` -> `USDC`,
+/// `iso4217:USD` -> `USD`)
+pub fn asset_code(asset: &str) -> Option<&str> {
+    let mut parts = asset.split(':');
+    match (parts.next(), parts.next()) {
+        (Some("stellar" | "iso4217"), Some(code)) if !code.is_empty() => Some(code),
+        _ => None,
+    }
+}
+
+/// Issuer of a SEP-38 Stellar asset identifier (`stellar:USDC:G...` ->
+/// `G...`); `None` for lumens and off-chain assets
+pub fn asset_issuer(asset: &str) -> Option<&str> {
+    let mut parts = asset.split(':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("stellar"), Some(_), Some(issuer)) if !issuer.is_empty() => Some(issuer),
+        _ => None,
+    }
+}
+
+/// HTTP client for the Anchor Platform's SEP-38 endpoints
+#[derive(Clone)]
+pub struct QuoteClient {
+    client: Client,
+    base_url: String,
+    /// SEP-10 JWT sent as a bearer token; `POST /quote` requires one
+    auth_token: Option<String>,
+}
+
+impl QuoteClient {
+    pub fn new(base_url: String, auth_token: Option<String>) -> Self {
+        let client = Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .build()
+            .unwrap_or_default();
+        Self {
+            client,
+            base_url,
+            auth_token,
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    fn authorized(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.auth_token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn parse<T: serde::de::DeserializeOwned>(response: reqwest::Response) -> Result<T, QuoteError> {
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            // SEP-38 errors are `{"error": "..."}`; fall back to the raw body
+            let message = serde_json::from_str::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|v| v["error"].as_str().map(str::to_string))
+                .unwrap_or(body);
+            return Err(QuoteError::Rejected {
+                status: status.as_u16(),
+                message,
+            });
+        }
+        Ok(response.json::<T>().await?)
+    }
+
+    /// Indicative prices for every asset `sell_asset` can be exchanged for
+    pub async fn prices(&self, request: &PricesRequest) -> Result<Vec<BuyAssetPrice>, QuoteError> {
+        let response = self
+            .authorized(self.client.get(self.url("/prices")).query(request))
+            .send()
+            .await?;
+        Ok(Self::parse::<PricesResponse>(response).await?.buy_assets)
+    }
+
+    /// Indicative price for one pair
+    pub async fn price(&self, request: &PriceRequest) -> Result<PriceResponse, QuoteError> {
+        let response = self
+            .authorized(self.client.get(self.url("/price")).query(request))
+            .send()
+            .await?;
+        Self::parse(response).await
+    }
+
+    /// Request a firm quote the anchor will honour until `expires_at`
+    pub async fn request_quote(&self, request: &QuoteRequest) -> Result<FirmQuote, QuoteError> {
+        let response = self
+            .authorized(self.client.post(self.url("/quote")).json(request))
+            .send()
+            .await?;
+        Self::parse(response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USDC: &str = "stellar:USDC:GA5ZSEJYB37JRC5AVCIA5MOP4RHTM335X2KGX3IHOJAPP5RE34K4KZVN";
+
+    #[test]
+    fn test_asset_code_of_sep38_identifiers() {
+        assert_eq!(asset_code(USDC), Some("USDC"));
+        assert_eq!(asset_code("iso4217:USD"), Some("USD"));
+        assert_eq!(asset_code("USDC"), None);
+        assert_eq!(asset_code("stellar:"), None);
+        assert_eq!(asset_code("other:USD"), None);
+    }
+
+    #[test]
+    fn test_asset_issuer_of_sep38_identifiers() {
+        assert_eq!(
+            asset_issuer(USDC),
+            Some("GA5ZSEJYB37JRC5AVCIA5MOP4RHTM335X2KGX3IHOJAPP5RE34K4KZVN")
+        );
+        assert_eq!(asset_issuer("stellar:native"), None);
+        assert_eq!(asset_issuer("iso4217:USD"), None);
+        assert_eq!(asset_issuer("stellar:USDC:"), None);
+    }
+
+    #[test]
+    fn test_amounts_serialize_as_decimal_strings() {
+        let request = QuoteRequest {
+            sell_asset: USDC.to_string(),
+            buy_asset: "iso4217:USD".to_string(),
+            sell_amount: Some("100.50".parse().unwrap()),
+            buy_amount: None,
+            expire_after: None,
+            context: QuoteContext::Sep6,
+        };
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["sell_amount"], "100.50");
+        assert_eq!(body["context"], "sep6");
+        assert!(body.get("buy_amount").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_request_quote_parses_firm_quote() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("POST", "/sep38/quote")
+            .match_header("authorization", "Bearer jwt-1")
+            .with_status(201)
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                r#"{{
+                    "id": "de762cda-a193-4961-861e-57b31fed6eb3",
+                    "expires_at": "2026-02-21T10:30:00Z",
+                    "total_price": "1.02",
+                    "price": "1.00",
+                    "sell_asset": "{}",
+                    "sell_amount": "102",
+                    "buy_asset": "iso4217:USD",
+                    "buy_amount": "100",
+                    "fee": {{ "total": "2.00", "asset": "{}" }}
+                }}"#,
+                USDC, USDC
+            ))
+            .create_async()
+            .await;
+
+        let client = QuoteClient::new(format!("{}/sep38/", server.url()), Some("jwt-1".to_string()));
+        let quote = client
+            .request_quote(&QuoteRequest {
+                sell_asset: USDC.to_string(),
+                buy_asset: "iso4217:USD".to_string(),
+                sell_amount: Some("102".parse().unwrap()),
+                buy_amount: None,
+                expire_after: None,
+                context: QuoteContext::Sep6,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(quote.id, "de762cda-a193-4961-861e-57b31fed6eb3");
+        assert_eq!(quote.sell_amount, "102".parse::<BigDecimal>().unwrap());
+        assert_eq!(quote.fee.total, "2".parse::<BigDecimal>().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_rejection_carries_sep38_error_message() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", mockito::Matcher::Regex(r"^/price\?.*".into()))
+            .with_status(400)
+            .with_body(r#"{"error": "unsupported pair"}"#)
+            .create_async()
+            .await;
+
+        let client = QuoteClient::new(server.url(), None);
+        let err = client
+            .price(&PriceRequest {
+                sell_asset: USDC.to_string(),
+                buy_asset: "iso4217:EUR".to_string(),
+                sell_amount: Some("10".parse().unwrap()),
+                buy_amount: None,
+                context: QuoteContext::Sep6,
+            })
+            .await
+            .unwrap_err();
+
+        match err {
+            QuoteError::Rejected { status, message } => {
+                assert_eq!(status, 400);
+                assert_eq!(message, "unsupported pair");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+    }
+}
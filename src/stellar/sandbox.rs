@@ -0,0 +1,310 @@
+//! Deterministic in-process stand-in for Horizon, used when `APP_ENV=sandbox`.
+//!
+//! Every account exists and trusts every registry asset. Submissions succeed
+//! after a fixed delay with a hash derived from the envelope, and effects and
+//! payments are always empty. Integrators force failures by using one of the
+//! seeded failure accounts; see [`ForcedFailure`].
+
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::stellar::client::{
+    AccountResponse, Balance, Effect, HorizonError, HorizonRoot, Operation, Order, PaymentsPage,
+    SubmitResponse,
+};
+use crate::stellar::transaction::BASE_FEE;
+use crate::utils::clock::{self, SharedClock};
+
+/// Balance every sandbox account holds, in XLM and in each trusted asset
+pub const SANDBOX_BALANCE: &str = "10000.0000000";
+
+/// Failure a seeded sandbox account forces on the calls made for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForcedFailure {
+    /// The account does not exist
+    NotFound,
+    /// The account exists but trusts no assets
+    NoTrustline,
+    /// Transactions from the account fail with `tx_failed`
+    SubmitRejected,
+    /// Every call for the account fails as if Horizon were down
+    Unavailable,
+}
+
+impl ForcedFailure {
+    /// The failure [`seed::ACCOUNTS`](crate::db::seed::ACCOUNTS) lists for `address`
+    pub fn for_account(address: &str) -> Option<Self> {
+        crate::db::seed::forced_failure(address)
+    }
+}
+
+fn unavailable(address: &str) -> HorizonError {
+    HorizonError::InvalidResponse(format!("503 Service Unavailable (sandbox failure forced by {})", address))
+}
+
+#[derive(Debug)]
+pub struct FakeHorizon {
+    /// Registry assets every account trusts, as `(code, issuer)`
+    assets: RwLock<Vec<(String, String)>>,
+    submission_delay: Duration,
+    clock: SharedClock,
+    /// Ledger of the last submission
+    ledger: AtomicI64,
+}
+
+impl FakeHorizon {
+    pub fn new(submission_delay: Duration) -> Self {
+        Self {
+            assets: RwLock::new(Vec::new()),
+            submission_delay,
+            clock: clock::system(),
+            ledger: AtomicI64::new(0),
+        }
+    }
+
+    /// Wait out submission delays on `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Replace the assets accounts trust; issuer-less assets are skipped
+    pub fn set_assets<I>(&self, assets: I)
+    where
+        I: IntoIterator<Item = (String, Option<String>)>,
+    {
+        let mut assets: Vec<(String, String)> = assets
+            .into_iter()
+            .filter_map(|(code, issuer)| Some((code, issuer?)))
+            .collect();
+        assets.sort();
+        assets.dedup();
+        *self.assets.write().unwrap() = assets;
+    }
+
+    /// Horizon's root resource, with the ledger of the last submission
+    pub fn get_root(&self) -> HorizonRoot {
+        let ledger = self.ledger.load(Ordering::SeqCst);
+        HorizonRoot {
+            horizon_version: "sandbox".to_string(),
+            history_latest_ledger: ledger,
+            core_latest_ledger: ledger,
+            network_passphrase: String::new(),
+        }
+    }
+
+    pub async fn get_account(&self, address: &str) -> Result<AccountResponse, HorizonError> {
+        let failure = ForcedFailure::for_account(address);
+        match failure {
+            Some(ForcedFailure::NotFound) => return Err(HorizonError::AccountNotFound(address.to_string())),
+            Some(ForcedFailure::Unavailable) => return Err(unavailable(address)),
+            _ => {}
+        }
+
+        let mut balances = vec![Balance {
+            balance: SANDBOX_BALANCE.to_string(),
+            limit: None,
+            asset_type: "native".to_string(),
+            asset_code: None,
+            asset_issuer: None,
+        }];
+        if failure != Some(ForcedFailure::NoTrustline) {
+            for (code, issuer) in self.assets.read().unwrap().iter() {
+                balances.push(Balance {
+                    balance: SANDBOX_BALANCE.to_string(),
+                    limit: Some("922337203685.4775807".to_string()),
+                    asset_type: if code.len() <= 4 { "credit_alphanum4" } else { "credit_alphanum12" }
+                        .to_string(),
+                    asset_code: Some(code.clone()),
+                    asset_issuer: Some(issuer.clone()),
+                });
+            }
+        }
+
+        // Same address, same sequence
+        let digest = Sha256::digest(address.as_bytes());
+        let sequence = u32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) as u64;
+        Ok(AccountResponse {
+            id: address.to_string(),
+            account_id: address.to_string(),
+            subentry_count: balances.len() as i32 - 1,
+            balances,
+            sequence: (sequence << 32).to_string(),
+            home_domain: None,
+            last_modified_ledger: 1,
+            last_modified_time: "2026-01-01T00:00:00Z".to_string(),
+        })
+    }
+
+    pub async fn get_effects(&self, _cursor: Option<&str>, _limit: u32) -> Result<Vec<Effect>, HorizonError> {
+        Ok(Vec::new())
+    }
+
+    pub async fn latest_effect_cursor(&self) -> Result<Option<String>, HorizonError> {
+        Ok(None)
+    }
+
+    pub async fn get_payments(
+        &self,
+        account: &str,
+        _cursor: Option<&str>,
+        _limit: u32,
+    ) -> Result<Vec<Operation>, HorizonError> {
+        if ForcedFailure::for_account(account) == Some(ForcedFailure::Unavailable) {
+            return Err(unavailable(account));
+        }
+        Ok(Vec::new())
+    }
+
+    pub async fn get_account_payments(
+        &self,
+        account: &str,
+        _cursor: Option<&str>,
+        _limit: u32,
+        _order: Order,
+    ) -> Result<PaymentsPage, HorizonError> {
+        if ForcedFailure::for_account(account) == Some(ForcedFailure::Unavailable) {
+            return Err(unavailable(account));
+        }
+        Ok(PaymentsPage::default())
+    }
+
+    pub async fn get_operation_effects(&self, _operation_id: &str) -> Result<Vec<Effect>, HorizonError> {
+        Ok(Vec::new())
+    }
+
+    /// Submissions are not recorded, so no transaction is ever found; a
+    /// submission whose outcome was lost is sent again
+    pub async fn get_transaction(&self, _hash: &str) -> Result<Option<SubmitResponse>, HorizonError> {
+        Ok(None)
+    }
+
+    /// Succeeds after the submission delay, with the sha256 of the envelope as hash
+    pub async fn submit_transaction(
+        &self,
+        source_account: &str,
+        envelope_xdr: &str,
+    ) -> Result<SubmitResponse, HorizonError> {
+        match ForcedFailure::for_account(source_account) {
+            Some(ForcedFailure::Unavailable) => return Err(unavailable(source_account)),
+            Some(ForcedFailure::NotFound) => {
+                return Err(HorizonError::TransactionFailed("tx_no_source_account".to_string()))
+            }
+            _ => {}
+        }
+
+        self.clock.sleep(self.submission_delay).await;
+        if ForcedFailure::for_account(source_account) == Some(ForcedFailure::SubmitRejected) {
+            return Err(HorizonError::TransactionFailed("tx_failed".to_string()));
+        }
+
+        Ok(SubmitResponse {
+            hash: hex::encode(Sha256::digest(envelope_xdr.as_bytes())),
+            ledger: self.ledger.fetch_add(1, Ordering::SeqCst) + 1,
+            successful: true,
+            fee_charged: Some(BASE_FEE.to_string()),
+            result_xdr: None,
+            created_at: Some(self.clock.now()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::seed::{ACCOUNTS, SANDBOX_ISSUER};
+    use crate::utils::clock::TestClock;
+    use std::sync::Arc;
+
+    const ISSUER: &str = SANDBOX_ISSUER;
+    const ALICE: &str = ACCOUNTS[0].address;
+
+    /// The seeded account forcing `failure`
+    fn failing(failure: ForcedFailure) -> &'static str {
+        ACCOUNTS
+            .iter()
+            .find(|account| account.failure == Some(failure))
+            .map(|account| account.address)
+            .unwrap()
+    }
+
+    fn fake() -> FakeHorizon {
+        let fake = FakeHorizon::new(Duration::from_secs(2));
+        fake.set_assets([
+            ("USDC".to_string(), Some(ISSUER.to_string())),
+            ("XLM".to_string(), None),
+        ]);
+        fake
+    }
+
+    #[test]
+    fn test_forced_failures_come_from_the_seed_registry() {
+        assert_eq!(ForcedFailure::for_account(ALICE), None);
+        for failure in [
+            ForcedFailure::NotFound,
+            ForcedFailure::NoTrustline,
+            ForcedFailure::SubmitRejected,
+            ForcedFailure::Unavailable,
+        ] {
+            assert_eq!(ForcedFailure::for_account(failing(failure)), Some(failure));
+        }
+        // An address merely spelled like the old failure accounts is an ordinary one
+        assert_eq!(ForcedFailure::for_account("GFAILNOTFOUNDAAAA"), None);
+    }
+
+    #[tokio::test]
+    async fn test_accounts_exist_and_trust_registry_assets() {
+        let fake = fake();
+        let account = fake.get_account(ALICE).await.unwrap();
+        assert_eq!(account.balances.len(), 2);
+        assert_eq!(account.balances[1].asset_code.as_deref(), Some("USDC"));
+        assert_eq!(account.balances[1].asset_issuer.as_deref(), Some(ISSUER));
+        // Deterministic across calls
+        assert_eq!(fake.get_account(ALICE).await.unwrap().sequence, account.sequence);
+    }
+
+    #[tokio::test]
+    async fn test_forced_account_failures() {
+        let fake = fake();
+        assert!(matches!(
+            fake.get_account(failing(ForcedFailure::NotFound)).await,
+            Err(HorizonError::AccountNotFound(_))
+        ));
+        assert!(matches!(
+            fake.get_account(failing(ForcedFailure::Unavailable)).await,
+            Err(HorizonError::InvalidResponse(_))
+        ));
+        let no_trustline = fake.get_account(failing(ForcedFailure::NoTrustline)).await.unwrap();
+        assert_eq!(no_trustline.balances.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_submission_succeeds_after_delay() {
+        let clock = TestClock::new();
+        let fake = Arc::new(fake().with_clock(clock.shared()));
+        let submission = tokio::spawn({
+            let fake = fake.clone();
+            async move { fake.submit_transaction(ALICE, "AAAAenvelope").await }
+        });
+
+        tokio::task::yield_now().await;
+        assert!(!submission.is_finished());
+        clock.advance(Duration::from_secs(2));
+
+        let response = submission.await.unwrap().unwrap();
+        assert_eq!(response.hash, hex::encode(Sha256::digest(b"AAAAenvelope")));
+        assert_eq!(response.ledger, 1);
+        assert!(response.successful);
+    }
+
+    #[tokio::test]
+    async fn test_forced_submission_failure() {
+        let fake = FakeHorizon::new(Duration::ZERO);
+        assert!(matches!(
+            fake.submit_transaction(failing(ForcedFailure::SubmitRejected), "AAAAenvelope").await,
+            Err(HorizonError::TransactionFailed(code)) if code == "tx_failed"
+        ));
+    }
+}
@@ -0,0 +1,300 @@
+//! Horizon server-sent event streams.
+//!
+//! Horizon streams a collection when it is requested with
+//! `Accept: text/event-stream`: it sends the records after `cursor`, then each
+//! new one as it is ingested. Every event carries the record as JSON `data`
+//! and its paging token as `id`, so a consumer that stores the last id can
+//! reconnect from it. The first event of a connection is `open`, with the data
+//! `"hello"`.
+
+use reqwest::header::{ACCEPT, CACHE_CONTROL};
+use reqwest::{Client, Response, StatusCode};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::metrics;
+use crate::stellar::{HorizonError, Operation};
+
+/// A connection with nothing on it for this long is given up, so a silently
+/// dropped connection is noticed and reopened from the cursor
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// One server-sent event
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    pub id: Option<String>,
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Splits a `text/event-stream` body into events. Chunks may end anywhere,
+/// even inside a UTF-8 character; the rest is kept for the next one.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    id: Option<String>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// The events completed by `chunk`
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if let Some(event) = self.line(line) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    fn line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            // A blank line ends the event; one without data is dropped
+            let event = SseEvent {
+                id: self.id.take(),
+                event: self.event.take(),
+                data: self.data.join("\n"),
+            };
+            let had_data = !self.data.is_empty();
+            self.data.clear();
+            return had_data.then_some(event);
+        }
+        if line.starts_with(':') {
+            // Comment, used as a keep-alive
+            return None;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "id" => self.id = Some(value.to_string()),
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            // `retry` and unknown fields
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Opens streams of an account's payments: `payment`, path payment,
+/// `create_account` and `account_merge` operations, oldest first
+#[derive(Clone)]
+pub struct PaymentStream {
+    client: Client,
+    base_url: String,
+    account: String,
+    idle_timeout: Duration,
+}
+
+impl PaymentStream {
+    pub fn new(base_url: String, account: String) -> Self {
+        // No overall timeout: the response never ends on its own
+        let client = Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+        Self {
+            client,
+            base_url,
+            account,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        }
+    }
+
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    pub fn account(&self) -> &str {
+        &self.account
+    }
+
+    /// Stream the payments after `cursor`; from now on when there is none
+    pub async fn connect(&self, cursor: Option<&str>) -> Result<PaymentEvents, HorizonError> {
+        let started = Instant::now();
+        let result = self.open(cursor).await;
+        let outcome = match &result {
+            Ok(_) => "success",
+            Err(e) => e.metric_outcome(),
+        };
+        metrics::record_horizon_request("payment_stream", outcome, started.elapsed());
+        result
+    }
+
+    async fn open(&self, cursor: Option<&str>) -> Result<PaymentEvents, HorizonError> {
+        let url = format!(
+            "{}/accounts/{}/payments",
+            self.base_url.trim_end_matches('/'),
+            self.account
+        );
+        let response = self
+            .client
+            .get(&url)
+            .query(&[("cursor", cursor.unwrap_or("now"))])
+            .header(ACCEPT, "text/event-stream")
+            .header(CACHE_CONTROL, "no-cache")
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(HorizonError::AccountNotFound(self.account.clone()));
+        }
+        Ok(PaymentEvents {
+            response: response.error_for_status()?,
+            parser: SseParser::default(),
+            pending: VecDeque::new(),
+            idle_timeout: self.idle_timeout,
+        })
+    }
+}
+
+/// An open payment stream
+pub struct PaymentEvents {
+    response: Response,
+    parser: SseParser,
+    pending: VecDeque<SseEvent>,
+    idle_timeout: Duration,
+}
+
+impl PaymentEvents {
+    /// The next payment, waiting for Horizon to see one. `None` once Horizon
+    /// closes the connection.
+    pub async fn next(&mut self) -> Result<Option<Operation>, HorizonError> {
+        loop {
+            while let Some(event) = self.pending.pop_front() {
+                if let Some(operation) = parse_payment_event(&event)? {
+                    return Ok(Some(operation));
+                }
+            }
+            let chunk = tokio::time::timeout(self.idle_timeout, self.response.chunk())
+                .await
+                .map_err(|_| {
+                    HorizonError::InvalidResponse(format!(
+                        "payment stream idle for {:?}",
+                        self.idle_timeout
+                    ))
+                })??;
+            match chunk {
+                Some(chunk) => self.pending.extend(self.parser.feed(&chunk)),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// The operation an event carries; `None` for the `open` greeting
+pub fn parse_payment_event(event: &SseEvent) -> Result<Option<Operation>, HorizonError> {
+    if event.event.as_deref() == Some("open") || event.data == "\"hello\"" {
+        return Ok(None);
+    }
+    serde_json::from_str(&event.data)
+        .map(Some)
+        .map_err(|e| HorizonError::InvalidResponse(format!("payment stream event: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYMENT: &str = r#"{"id":"12884905985","paging_token":"12884905985","type":"payment","transaction_hash":"abc123","from":"GANCHOR","to":"GCUSTOMER","amount":"10.0000000","asset_type":"native"}"#;
+
+    #[test]
+    fn test_parser_splits_events() {
+        let body = format!(
+            "retry: 1000\nevent: open\ndata: \"hello\"\n\n: keep-alive\n\nid: 12884905985\ndata: {}\n\n",
+            PAYMENT
+        );
+        let events = SseParser::default().feed(body.as_bytes());
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event.as_deref(), Some("open"));
+        assert_eq!(events[1].id.as_deref(), Some("12884905985"));
+        assert_eq!(events[1].data, PAYMENT);
+    }
+
+    #[test]
+    fn test_parser_joins_chunks_split_anywhere() {
+        let body = format!("id: 1\r\ndata: {}\r\n\r\n", PAYMENT).into_bytes();
+        for split in 1..body.len() {
+            let mut parser = SseParser::default();
+            let mut events = parser.feed(&body[..split]);
+            events.extend(parser.feed(&body[split..]));
+            assert_eq!(events.len(), 1, "split at {}", split);
+            assert_eq!(events[0].data, PAYMENT);
+        }
+    }
+
+    #[test]
+    fn test_parser_joins_data_lines() {
+        let events = SseParser::default().feed(b"data: first\ndata:second\n\n");
+        assert_eq!(events[0].data, "first\nsecond");
+        assert_eq!(events[0].id, None);
+    }
+
+    #[test]
+    fn test_parse_payment_event() {
+        let hello = SseEvent {
+            event: Some("open".to_string()),
+            data: "\"hello\"".to_string(),
+            ..Default::default()
+        };
+        assert!(parse_payment_event(&hello).unwrap().is_none());
+
+        let payment = SseEvent {
+            id: Some("12884905985".to_string()),
+            data: PAYMENT.to_string(),
+            ..Default::default()
+        };
+        let operation = parse_payment_event(&payment).unwrap().unwrap();
+        assert_eq!(operation.paging_token, "12884905985");
+        assert_eq!(operation.transaction_hash.as_deref(), Some("abc123"));
+
+        let broken = SseEvent {
+            data: "{".to_string(),
+            ..Default::default()
+        };
+        assert!(parse_payment_event(&broken).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_from_cursor() {
+        let mut server = mockito::Server::new_async().await;
+        let body = format!("event: open\ndata: \"hello\"\n\nid: 12884905985\ndata: {}\n\n", PAYMENT);
+        let mock = server
+            .mock("GET", "/accounts/GANCHOR/payments")
+            .match_query(mockito::Matcher::UrlEncoded("cursor".into(), "100".into()))
+            .match_header("accept", "text/event-stream")
+            .with_header("content-type", "text/event-stream")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let stream = PaymentStream::new(server.url(), "GANCHOR".to_string());
+        let mut events = stream.connect(Some("100")).await.unwrap();
+        let operation = events.next().await.unwrap().unwrap();
+        assert_eq!(operation.id, "12884905985");
+        assert!(events.next().await.unwrap().is_none());
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_stream_of_unknown_account() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", mockito::Matcher::Regex("^/accounts/GMISSING/payments".to_string()))
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let stream = PaymentStream::new(server.url(), "GMISSING".to_string());
+        assert!(matches!(
+            stream.connect(None).await,
+            Err(HorizonError::AccountNotFound(_))
+        ));
+    }
+}
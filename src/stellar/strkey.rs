@@ -0,0 +1,314 @@
+//! Stellar account strkeys (SEP-23).
+//!
+//! A strkey is the RFC 4648 base32 encoding, without padding, of a version
+//! byte, the payload and a CRC16-XModem checksum of both, little-endian.
+//! Accounts are either `G...` ed25519 public keys or `M...` muxed accounts,
+//! which add a 64-bit id to a public key. `S...` secret seeds share the
+//! `G` layout.
+
+use thiserror::Error;
+
+/// `G`: ed25519 public key
+const VERSION_ED25519: u8 = 6 << 3;
+/// `M`: muxed account
+const VERSION_MUXED: u8 = 12 << 3;
+/// `S`: ed25519 secret seed
+const VERSION_SEED: u8 = 18 << 3;
+
+pub const ED25519_ADDRESS_LEN: usize = 56;
+pub const MUXED_ADDRESS_LEN: usize = 69;
+
+const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum StrkeyError {
+    #[error("padding is not allowed")]
+    Padding,
+    #[error("must contain only uppercase base32 characters (A-Z, 2-7)")]
+    Character,
+    #[error(
+        "must be {} characters for a G account or {} for an M account, got {0}",
+        ED25519_ADDRESS_LEN,
+        MUXED_ADDRESS_LEN
+    )]
+    Length(usize),
+    #[error("must be {} characters for a secret seed, got {0}", ED25519_ADDRESS_LEN)]
+    SeedLength(usize),
+    #[error("version byte is not a {0} account")]
+    Version(char),
+    #[error("unused trailing bits must be zero")]
+    TrailingBits,
+    #[error("checksum does not match")]
+    Checksum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountKind {
+    /// `G...`
+    Ed25519 { public_key: [u8; 32] },
+    /// `M...`: the account of `public_key`, told apart by `id`
+    Muxed { public_key: [u8; 32], id: u64 },
+}
+
+impl AccountKind {
+    pub fn public_key(&self) -> &[u8; 32] {
+        match self {
+            AccountKind::Ed25519 { public_key } | AccountKind::Muxed { public_key, .. } => {
+                public_key
+            }
+        }
+    }
+
+    /// The `G` address, which for a muxed account is the underlying account
+    pub fn base_address(&self) -> String {
+        encode_ed25519(self.public_key())
+    }
+
+    pub fn muxed_id(&self) -> Option<u64> {
+        match self {
+            AccountKind::Ed25519 { .. } => None,
+            AccountKind::Muxed { id, .. } => Some(*id),
+        }
+    }
+}
+
+/// Decode and check a `G` or `M` account address
+pub fn validate_account(address: &str) -> Result<AccountKind, StrkeyError> {
+    let values = base32_values(address)?;
+    let (version, kind) = match address.len() {
+        ED25519_ADDRESS_LEN => (VERSION_ED25519, 'G'),
+        MUXED_ADDRESS_LEN => (VERSION_MUXED, 'M'),
+        len => return Err(StrkeyError::Length(len)),
+    };
+    let data = checked_payload(&values, version, kind)?;
+
+    let mut public_key = [0u8; 32];
+    public_key.copy_from_slice(&data[..32]);
+    Ok(match kind {
+        'G' => AccountKind::Ed25519 { public_key },
+        _ => {
+            let mut id = [0u8; 8];
+            id.copy_from_slice(&data[32..40]);
+            AccountKind::Muxed {
+                public_key,
+                id: u64::from_be_bytes(id),
+            }
+        }
+    })
+}
+
+/// The ed25519 seed of an `S...` secret key
+pub fn decode_secret_seed(secret: &str) -> Result<[u8; 32], StrkeyError> {
+    let values = base32_values(secret)?;
+    if secret.len() != ED25519_ADDRESS_LEN {
+        return Err(StrkeyError::SeedLength(secret.len()));
+    }
+    let data = checked_payload(&values, VERSION_SEED, 'S')?;
+    let mut seed = [0u8; 32];
+    seed.copy_from_slice(&data);
+    Ok(seed)
+}
+
+/// The `S` secret key of an ed25519 seed
+pub fn encode_secret_seed(seed: &[u8; 32]) -> String {
+    encode(VERSION_SEED, seed)
+}
+
+fn base32_values(text: &str) -> Result<Vec<u8>, StrkeyError> {
+    if text.contains('=') {
+        return Err(StrkeyError::Padding);
+    }
+    text.bytes()
+        .map(|byte| {
+            ALPHABET
+                .iter()
+                .position(|c| *c == byte)
+                .map(|value| value as u8)
+                .ok_or(StrkeyError::Character)
+        })
+        .collect()
+}
+
+/// The payload after the version byte, once version and checksum are checked
+fn checked_payload(values: &[u8], version: u8, kind: char) -> Result<Vec<u8>, StrkeyError> {
+    let bytes = decode_base32(values)?;
+    let (data, checksum) = bytes.split_at(bytes.len() - 2);
+    if data[0] != version {
+        return Err(StrkeyError::Version(kind));
+    }
+    if crc16_xmodem(data).to_le_bytes() != checksum {
+        return Err(StrkeyError::Checksum);
+    }
+    Ok(data[1..].to_vec())
+}
+
+fn encode(version: u8, payload: &[u8]) -> String {
+    let mut data = Vec::with_capacity(payload.len() + 3);
+    data.push(version);
+    data.extend_from_slice(payload);
+    data.extend_from_slice(&crc16_xmodem(&data).to_le_bytes());
+    encode_base32(&data)
+}
+
+/// The `G` address of an ed25519 public key
+pub fn encode_ed25519(public_key: &[u8; 32]) -> String {
+    encode(VERSION_ED25519, public_key)
+}
+
+/// Bytes of 5-bit values. Bits left over after the last whole byte must be
+/// zero, so each address has one spelling.
+fn decode_base32(values: &[u8]) -> Result<Vec<u8>, StrkeyError> {
+    let mut bytes = Vec::with_capacity(values.len() * 5 / 8);
+    let mut buffer: u16 = 0;
+    let mut bits = 0;
+    for value in values {
+        buffer = (buffer << 5) | u16::from(*value);
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    if buffer != 0 {
+        return Err(StrkeyError::TrailingBits);
+    }
+    Ok(bytes)
+}
+
+fn encode_base32(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer: u16 = 0;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | u16::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[usize::from((buffer >> bits) & 0x1f)] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        out.push(ALPHABET[usize::from((buffer << (5 - bits)) & 0x1f)] as char);
+    }
+    out
+}
+
+fn crc16_xmodem(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for byte in data {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Vectors from SEP-23
+    const ACCOUNT: &str = "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ";
+    const MUXED_ID_0: &str =
+        "MA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJUAAAAAAAAAAAACJUQ";
+    const MUXED_ID_2_63: &str =
+        "MA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVAAAAAAAAAAAAAJLK";
+
+    #[test]
+    fn test_ed25519_account() {
+        let kind = validate_account(ACCOUNT).unwrap();
+        assert!(matches!(kind, AccountKind::Ed25519 { .. }));
+        assert_eq!(kind.public_key()[..4], [0x3f, 0x0c, 0x34, 0xbf]);
+        assert_eq!(kind.base_address(), ACCOUNT);
+        assert_eq!(kind.muxed_id(), None);
+    }
+
+    #[test]
+    fn test_muxed_accounts() {
+        let kind = validate_account(MUXED_ID_0).unwrap();
+        assert_eq!(kind.muxed_id(), Some(0));
+        assert_eq!(kind.base_address(), ACCOUNT);
+
+        let kind = validate_account(MUXED_ID_2_63).unwrap();
+        assert_eq!(kind.muxed_id(), Some(9223372036854775808));
+        assert_eq!(kind.base_address(), ACCOUNT);
+    }
+
+    #[test]
+    fn test_encoding_round_trips() {
+        let public_key = *validate_account(ACCOUNT).unwrap().public_key();
+        assert_eq!(encode_ed25519(&public_key), ACCOUNT);
+        assert_eq!(crc16_xmodem(b"123456789"), 0x31c3);
+    }
+
+    #[test]
+    fn test_rejects_case_and_padding() {
+        assert_eq!(validate_account(&ACCOUNT.to_lowercase()), Err(StrkeyError::Character));
+        let mixed = format!("G{}", ACCOUNT[1..].to_lowercase());
+        assert_eq!(validate_account(&mixed), Err(StrkeyError::Character));
+        assert_eq!(validate_account(&format!("{}=", ACCOUNT)), Err(StrkeyError::Padding));
+        assert_eq!(
+            validate_account(&format!("{}====", &MUXED_ID_0[..MUXED_ID_0.len() - 4])),
+            Err(StrkeyError::Padding)
+        );
+        // 0, 1, 8 and 9 are not in the base32 alphabet
+        assert_eq!(validate_account(&format!("G{}", "1".repeat(55))), Err(StrkeyError::Character));
+    }
+
+    #[test]
+    fn test_rejects_flipped_checksum_bits() {
+        // Z -> Y flips the lowest checksum bit
+        let flipped = format!("{}Y", &ACCOUNT[..ACCOUNT.len() - 1]);
+        assert_eq!(validate_account(&flipped), Err(StrkeyError::Checksum));
+        // A flipped payload bit no longer matches the checksum either
+        let flipped = ACCOUNT.replacen("GA7Q", "GA7R", 1);
+        assert_eq!(validate_account(&flipped), Err(StrkeyError::Checksum));
+        assert_eq!(
+            validate_account(&format!("G{}", "A".repeat(55))),
+            Err(StrkeyError::Checksum)
+        );
+    }
+
+    #[test]
+    fn test_rejects_wrong_length_and_version() {
+        assert_eq!(validate_account("GAAAAAAAACGC6"), Err(StrkeyError::Length(13)));
+        assert_eq!(validate_account(""), Err(StrkeyError::Length(0)));
+        // A secret seed: valid strkey, not an account
+        assert_eq!(
+            validate_account("SA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJUWVG"),
+            Err(StrkeyError::Version('G'))
+        );
+        // A muxed version byte at the length of a G address
+        assert_eq!(
+            validate_account("MA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJUAPG"),
+            Err(StrkeyError::Version('G'))
+        );
+    }
+
+    #[test]
+    fn test_rejects_unused_bits() {
+        // From SEP-23: the last character sets the unused bit
+        assert_eq!(
+            validate_account("MA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVAAAAAAAAAAAAAJLL"),
+            Err(StrkeyError::TrailingBits)
+        );
+    }
+
+    #[test]
+    fn test_secret_seed() {
+        const SEED: &str = "SA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJUWVG";
+        let seed = decode_secret_seed(SEED).unwrap();
+        assert_eq!(seed, *validate_account(ACCOUNT).unwrap().public_key());
+        assert_eq!(encode_secret_seed(&seed), SEED);
+
+        assert_eq!(decode_secret_seed(ACCOUNT), Err(StrkeyError::Version('S')));
+        assert_eq!(decode_secret_seed("SA7Q"), Err(StrkeyError::SeedLength(4)));
+    }
+}
@@ -0,0 +1,330 @@
+//! Signed payment transactions for Horizon `POST /transactions`.
+//!
+//! Only what the payment processor sends is encoded: a `v1` transaction
+//! envelope with one `payment` operation, time bounds and no memo, in the
+//! XDR layout of `Stellar-transaction.x`. The hash the envelope is signed
+//! over is the hash Horizon reports for it, so it is known before
+//! submission.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signer, SigningKey};
+use sha2::{Digest, Sha256};
+use sqlx::types::BigDecimal;
+use thiserror::Error;
+
+use crate::stellar::strkey::{self, AccountKind};
+
+pub const PUBLIC_NETWORK_PASSPHRASE: &str = "Public Global Stellar Network ; September 2015";
+pub const TESTNET_PASSPHRASE: &str = "Test SDF Network ; September 2015";
+
+/// Minimum fee per operation, in stroops
+pub const BASE_FEE: u32 = 100;
+
+/// Stroops in one unit of an asset
+const STROOPS_PER_UNIT: i64 = 10_000_000;
+
+const ENVELOPE_TYPE_TX: i32 = 2;
+const KEY_TYPE_ED25519: i32 = 0;
+const KEY_TYPE_MUXED_ED25519: i32 = 0x100;
+const PRECOND_TIME: i32 = 1;
+const MEMO_NONE: i32 = 0;
+const OPERATION_PAYMENT: i32 = 1;
+const ASSET_TYPE_NATIVE: i32 = 0;
+const ASSET_TYPE_CREDIT_ALPHANUM4: i32 = 1;
+const ASSET_TYPE_CREDIT_ALPHANUM12: i32 = 2;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TransactionError {
+    #[error("amount {0} is not a positive amount with at most 7 decimals")]
+    Amount(String),
+    #[error("asset code {0:?} must be 1 to 12 letters or digits")]
+    AssetCode(String),
+    #[error("invalid account {0}: {1}")]
+    Account(String, strkey::StrkeyError),
+}
+
+/// What a payment is made in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentAsset {
+    Native,
+    Credit { code: String, issuer: [u8; 32] },
+}
+
+impl PaymentAsset {
+    /// A credit asset; the issuer must be a `G` account
+    pub fn credit(code: &str, issuer: &str) -> Result<Self, TransactionError> {
+        if code.is_empty() || code.len() > 12 || !code.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(TransactionError::AssetCode(code.to_string()));
+        }
+        match strkey::validate_account(issuer) {
+            Ok(AccountKind::Ed25519 { public_key }) => Ok(Self::Credit {
+                code: code.to_string(),
+                issuer: public_key,
+            }),
+            Ok(AccountKind::Muxed { .. }) => Err(TransactionError::Account(
+                issuer.to_string(),
+                strkey::StrkeyError::Version('G'),
+            )),
+            Err(e) => Err(TransactionError::Account(issuer.to_string(), e)),
+        }
+    }
+}
+
+/// A one-operation payment from `source`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentTransaction {
+    /// Public key of the paying account
+    pub source: [u8; 32],
+    /// The source account's current sequence number plus one
+    pub sequence: i64,
+    /// Total fee, in stroops
+    pub fee: u32,
+    /// The network rejects the transaction after this time, so an envelope
+    /// that hasn't landed by then never will
+    pub valid_until: DateTime<Utc>,
+    /// `G` or `M` account
+    pub destination: AccountKind,
+    pub asset: PaymentAsset,
+    /// In stroops, see [`to_stroops`]
+    pub amount: i64,
+}
+
+/// An envelope ready for [`crate::stellar::HorizonClient::submit_transaction`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTransaction {
+    /// Hex transaction hash, as Horizon reports it
+    pub hash: String,
+    /// Base64 XDR `TransactionEnvelope`
+    pub envelope_xdr: String,
+}
+
+impl PaymentTransaction {
+    /// The transaction hash on the network with `network_passphrase`
+    pub fn hash(&self, network_passphrase: &str) -> [u8; 32] {
+        let mut payload = Xdr::default();
+        payload.bytes(&Sha256::digest(network_passphrase.as_bytes()));
+        payload.int(ENVELOPE_TYPE_TX);
+        self.write(&mut payload);
+        Sha256::digest(&payload.0).into()
+    }
+
+    pub fn sign(&self, key: &SigningKey, network_passphrase: &str) -> SignedTransaction {
+        let hash = self.hash(network_passphrase);
+        let signature = key.sign(&hash);
+
+        let mut envelope = Xdr::default();
+        envelope.int(ENVELOPE_TYPE_TX);
+        self.write(&mut envelope);
+        envelope.uint(1);
+        // Signature hint: the last four bytes of the signer's public key
+        envelope.bytes(&key.verifying_key().as_bytes()[28..]);
+        envelope.uint(64);
+        envelope.bytes(&signature.to_bytes());
+
+        SignedTransaction {
+            hash: hex::encode(hash),
+            envelope_xdr: BASE64.encode(&envelope.0),
+        }
+    }
+
+    /// XDR `Transaction`
+    fn write(&self, xdr: &mut Xdr) {
+        xdr.int(KEY_TYPE_ED25519);
+        xdr.bytes(&self.source);
+        xdr.uint(self.fee);
+        xdr.hyper(self.sequence);
+        xdr.int(PRECOND_TIME);
+        xdr.uhyper(0);
+        xdr.uhyper(self.valid_until.timestamp().max(0) as u64);
+        xdr.int(MEMO_NONE);
+
+        // One operation, without its own source account
+        xdr.uint(1);
+        xdr.uint(0);
+        xdr.int(OPERATION_PAYMENT);
+        match &self.destination {
+            AccountKind::Ed25519 { public_key } => {
+                xdr.int(KEY_TYPE_ED25519);
+                xdr.bytes(public_key);
+            }
+            AccountKind::Muxed { public_key, id } => {
+                xdr.int(KEY_TYPE_MUXED_ED25519);
+                xdr.uhyper(*id);
+                xdr.bytes(public_key);
+            }
+        }
+        match &self.asset {
+            PaymentAsset::Native => xdr.int(ASSET_TYPE_NATIVE),
+            PaymentAsset::Credit { code, issuer } => {
+                // Codes are NUL-padded to a fixed 4 or 12 bytes
+                let width = if code.len() <= 4 { 4 } else { 12 };
+                xdr.int(if width == 4 {
+                    ASSET_TYPE_CREDIT_ALPHANUM4
+                } else {
+                    ASSET_TYPE_CREDIT_ALPHANUM12
+                });
+                let mut padded = code.as_bytes().to_vec();
+                padded.resize(width, 0);
+                xdr.bytes(&padded);
+                xdr.int(KEY_TYPE_ED25519);
+                xdr.bytes(issuer);
+            }
+        }
+        xdr.hyper(self.amount);
+
+        // Transaction ext
+        xdr.int(0);
+    }
+}
+
+/// `amount` in stroops. Stellar amounts have 7 decimals; anything finer, and
+/// zero or negative amounts, can't be paid.
+pub fn to_stroops(amount: &BigDecimal) -> Result<i64, TransactionError> {
+    let invalid = || TransactionError::Amount(amount.to_string());
+    let stroops = amount * BigDecimal::from(STROOPS_PER_UNIT);
+    if !stroops.is_integer() {
+        return Err(invalid());
+    }
+    let stroops: i64 = stroops.with_scale(0).to_string().parse().map_err(|_| invalid())?;
+    if stroops <= 0 {
+        return Err(invalid());
+    }
+    Ok(stroops)
+}
+
+/// Big-endian XDR writer. Every value written is a multiple of four bytes,
+/// so no padding is needed.
+#[derive(Default)]
+struct Xdr(Vec<u8>);
+
+impl Xdr {
+    fn int(&mut self, value: i32) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn uint(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn hyper(&mut self, value: i64) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn uhyper(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    /// Fixed-length opaque data
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signature, Verifier};
+    use std::str::FromStr;
+
+    const DESTINATION: &str = "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ";
+    const MUXED: &str = "MA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJUAAAAAAAAAAAACJUQ";
+
+    fn key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    fn payment(asset: PaymentAsset, destination: &str) -> PaymentTransaction {
+        PaymentTransaction {
+            source: key().verifying_key().to_bytes(),
+            sequence: 4_294_967_297,
+            fee: BASE_FEE,
+            valid_until: DateTime::from_timestamp(1_767_225_600, 0).unwrap(),
+            destination: strkey::validate_account(destination).unwrap(),
+            asset,
+            amount: 1_000_000_000,
+        }
+    }
+
+    fn usdc() -> PaymentAsset {
+        PaymentAsset::credit("USDC", DESTINATION).unwrap()
+    }
+
+    fn envelope(signed: &SignedTransaction) -> Vec<u8> {
+        BASE64.decode(&signed.envelope_xdr).unwrap()
+    }
+
+    #[test]
+    fn test_envelope_layout() {
+        let signed = payment(usdc(), DESTINATION).sign(&key(), TESTNET_PASSPHRASE);
+        let bytes = envelope(&signed);
+
+        // type + tx (source 36, fee 4, seq 8, time bounds 20, memo 4,
+        // one payment 4 + 4 + 4 + 36 + 44 + 8, ext 4) + one signature 4 + 4 + 4 + 64
+        assert_eq!(bytes.len(), 4 + 176 + 76);
+        assert_eq!(bytes[..4], ENVELOPE_TYPE_TX.to_be_bytes());
+        assert_eq!(bytes[8..40], key().verifying_key().to_bytes());
+        assert_eq!(bytes[40..44], BASE_FEE.to_be_bytes());
+        assert_eq!(bytes[44..52], 4_294_967_297i64.to_be_bytes());
+        assert_eq!(bytes[64..72], 1_767_225_600u64.to_be_bytes());
+        // Asset code, NUL-padded, right after the asset type
+        assert_eq!(bytes[124..128], ASSET_TYPE_CREDIT_ALPHANUM4.to_be_bytes());
+        assert_eq!(bytes[128..132], *b"USDC");
+        assert_eq!(bytes[168..176], 1_000_000_000i64.to_be_bytes());
+        assert!(signed.envelope_xdr.starts_with("AAAAAgAAAA"));
+    }
+
+    #[test]
+    fn test_signature_covers_the_network_hash() {
+        let tx = payment(usdc(), DESTINATION);
+        let signed = tx.sign(&key(), TESTNET_PASSPHRASE);
+        assert_eq!(signed.hash, hex::encode(tx.hash(TESTNET_PASSPHRASE)));
+        assert_ne!(tx.hash(TESTNET_PASSPHRASE), tx.hash(PUBLIC_NETWORK_PASSPHRASE));
+
+        let bytes = envelope(&signed);
+        let signature = Signature::from_slice(&bytes[bytes.len() - 64..]).unwrap();
+        assert!(key().verifying_key().verify(&tx.hash(TESTNET_PASSPHRASE), &signature).is_ok());
+        // Hint
+        assert_eq!(bytes[bytes.len() - 72..bytes.len() - 68], key().verifying_key().as_bytes()[28..]);
+    }
+
+    #[test]
+    fn test_native_and_long_code_assets_and_muxed_destinations() {
+        let native = envelope(&payment(PaymentAsset::Native, DESTINATION).sign(&key(), TESTNET_PASSPHRASE));
+        // No code or issuer
+        assert_eq!(native.len(), 256 - 40);
+
+        let long = PaymentAsset::credit("LONGASSET", DESTINATION).unwrap();
+        let bytes = envelope(&payment(long, DESTINATION).sign(&key(), TESTNET_PASSPHRASE));
+        assert_eq!(bytes.len(), 256 + 8);
+        assert_eq!(bytes[124..128], ASSET_TYPE_CREDIT_ALPHANUM12.to_be_bytes());
+        assert_eq!(bytes[128..140], *b"LONGASSET\0\0\0");
+
+        let bytes = envelope(&payment(usdc(), MUXED).sign(&key(), TESTNET_PASSPHRASE));
+        assert_eq!(bytes.len(), 256 + 8);
+        assert_eq!(bytes[88..92], KEY_TYPE_MUXED_ED25519.to_be_bytes());
+        assert_eq!(bytes[92..100], 0u64.to_be_bytes());
+    }
+
+    #[test]
+    fn test_invalid_assets() {
+        assert!(PaymentAsset::credit("", DESTINATION).is_err());
+        assert!(PaymentAsset::credit("TOOLONGASSETX", DESTINATION).is_err());
+        assert!(PaymentAsset::credit("US-D", DESTINATION).is_err());
+        assert!(PaymentAsset::credit("USDC", MUXED).is_err());
+        assert!(PaymentAsset::credit("USDC", "GABC").is_err());
+    }
+
+    #[test]
+    fn test_to_stroops() {
+        let stroops = |amount: &str| to_stroops(&BigDecimal::from_str(amount).unwrap());
+        assert_eq!(stroops("100"), Ok(1_000_000_000));
+        assert_eq!(stroops("0.0000001"), Ok(1));
+        assert_eq!(stroops("12.3400000"), Ok(123_400_000));
+        assert!(stroops("0.00000001").is_err());
+        assert!(stroops("0").is_err());
+        assert!(stroops("-1").is_err());
+        assert!(stroops("922337203685.4775808").is_err());
+    }
+}
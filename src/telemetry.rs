@@ -0,0 +1,247 @@
+//! Log output and distributed tracing.
+//!
+//! Every request runs in a `request` span carrying its `request_id`, so each
+//! log line written while it is handled includes the id. The id is the
+//! caller's `X-Request-Id`, else the trace id of its W3C `traceparent`, else a
+//! fresh UUID. With `OTEL_EXPORTER_OTLP_ENDPOINT` set, spans are also exported
+//! over OTLP, continuing the caller's trace, and outgoing Horizon requests
+//! carry a `traceparent` of their own.
+
+use std::collections::HashMap;
+
+use axum::http::{HeaderMap, Method};
+use opentelemetry::propagation::Extractor;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::Resource;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use uuid::Uuid;
+
+use crate::config::{Config, LogFormat};
+use crate::utils::correlation::CorrelationContext;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Exports the spans still buffered when dropped; hold it until shutdown
+#[must_use]
+pub struct TelemetryGuard {
+    exporting: bool,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if self.exporting {
+            opentelemetry::global::shutdown_tracer_provider();
+        }
+    }
+}
+
+/// Install the global subscriber: `RUST_LOG` filtering, `LOG_FORMAT` output,
+/// and OTLP export when an endpoint is configured. Must run inside the Tokio
+/// runtime, which exports the spans.
+pub fn init(config: &Config) -> anyhow::Result<TelemetryGuard> {
+    let env_filter =
+        tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into());
+    let (json, text) = match config.log_format {
+        LogFormat::Json => (Some(tracing_subscriber::fmt::layer().json()), None),
+        LogFormat::Text => (None, Some(tracing_subscriber::fmt::layer())),
+    };
+
+    let telemetry = &config.telemetry;
+    let otlp = match &telemetry.otlp_endpoint {
+        Some(endpoint) => {
+            opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(endpoint.clone()),
+                )
+                .with_trace_config(opentelemetry_sdk::trace::config().with_resource(
+                    Resource::new(vec![KeyValue::new(
+                        "service.name",
+                        telemetry.service_name.clone(),
+                    )]),
+                ))
+                .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+            Some(tracing_opentelemetry::layer().with_tracer(tracer))
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(env_filter)
+        .with(json)
+        .with(text)
+        .with(otlp)
+        .init();
+    if let Some(endpoint) = &telemetry.otlp_endpoint {
+        tracing::info!(
+            endpoint = %endpoint,
+            service = %telemetry.service_name,
+            "Exporting spans over OTLP"
+        );
+    }
+
+    Ok(TelemetryGuard {
+        exporting: telemetry.otlp_endpoint.is_some(),
+    })
+}
+
+/// A W3C `traceparent` header: `00-<trace id>-<parent id>-<flags>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceParent {
+    /// 32 lowercase hex digits
+    pub trace_id: String,
+    /// 16 lowercase hex digits, the caller's span
+    pub parent_id: String,
+    pub sampled: bool,
+}
+
+impl TraceParent {
+    /// `None` for anything the spec says to ignore, such as all-zero ids
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut fields = value.trim().split('-');
+        let (version, trace_id, parent_id, flags) =
+            (fields.next()?, fields.next()?, fields.next()?, fields.next()?);
+        // Version 00 has exactly four fields; later versions may append more
+        if version == "00" && fields.next().is_some() {
+            return None;
+        }
+
+        let hex = |field: &str, len: usize| {
+            field.len() == len && field.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        };
+        let zero = |field: &str| field.bytes().all(|b| b == b'0');
+        if !hex(version, 2) || version == "ff" || !hex(flags, 2) {
+            return None;
+        }
+        if !hex(trace_id, 32) || zero(trace_id) || !hex(parent_id, 16) || zero(parent_id) {
+            return None;
+        }
+
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            parent_id: parent_id.to_string(),
+            sampled: u8::from_str_radix(flags, 16).ok()? & 1 == 1,
+        })
+    }
+}
+
+/// The caller's `X-Request-Id` when well formed, else the trace id of its
+/// `traceparent`, else a fresh UUID
+pub fn request_id(headers: &HeaderMap) -> String {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    header(REQUEST_ID_HEADER)
+        .and_then(CorrelationContext::parse)
+        .map(|ctx| ctx.as_str().to_string())
+        .or_else(|| {
+            header(TRACEPARENT_HEADER)
+                .and_then(TraceParent::parse)
+                .map(|traceparent| traceparent.trace_id)
+        })
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Span for one inbound request. When spans are exported it continues the
+/// trace of the caller's `traceparent`.
+pub fn request_span(request_id: &str, method: &Method, path: &str, headers: &HeaderMap) -> Span {
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %method,
+        path = %path,
+        otel.kind = "server",
+    );
+    let parent = opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(headers))
+    });
+    span.set_parent(parent);
+    span
+}
+
+/// Add the current span's `traceparent` to an outgoing request. Without
+/// export the propagator writes nothing.
+pub fn inject(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let context = Span::current().context();
+    let mut headers = HashMap::new();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut headers)
+    });
+    headers
+        .into_iter()
+        .fold(request, |request, (name, value)| request.header(name, value))
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, HeaderValue::from_static(value));
+        }
+        map
+    }
+
+    #[test]
+    fn test_parse_traceparent() {
+        let parsed = TraceParent::parse(TRACEPARENT).unwrap();
+        assert_eq!(parsed.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(parsed.parent_id, "00f067aa0ba902b7");
+        assert!(parsed.sampled);
+
+        let unsampled = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00";
+        assert!(!TraceParent::parse(unsampled).unwrap().sampled);
+        // A later version may carry more fields
+        assert!(TraceParent::parse(&format!("01-{}-extra", &TRACEPARENT[3..])).is_some());
+    }
+
+    #[test]
+    fn test_parse_traceparent_rejects_invalid_headers() {
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert_eq!(TraceParent::parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_request_id_prefers_header_then_trace_id() {
+        let both = headers(&[(REQUEST_ID_HEADER, "req-1"), (TRACEPARENT_HEADER, TRACEPARENT)]);
+        assert_eq!(request_id(&both), "req-1");
+
+        let trace_only = headers(&[(TRACEPARENT_HEADER, TRACEPARENT)]);
+        assert_eq!(request_id(&trace_only), "4bf92f3577b34da6a3ce929d0e0e4736");
+
+        let malformed = headers(&[(REQUEST_ID_HEADER, "not valid!")]);
+        assert!(Uuid::parse_str(&request_id(&malformed)).is_ok());
+    }
+}
@@ -1,6 +1,7 @@
 //! Process deposit use case.
 //! Handles deposit logic using the TransactionRepository.
 
+use crate::db::uow::DbConn;
 use crate::domain::Transaction;
 use crate::ports::{RepositoryError, TransactionRepository};
 use bigdecimal::BigDecimal;
@@ -36,7 +37,12 @@ impl ProcessDeposit {
         }
     }
 
-    pub async fn execute(&self, input: DepositInput) -> Result<DepositOutput, RepositoryError> {
+    /// Runs on `conn`, so the deposit can be part of a caller's unit of work.
+    pub async fn execute(
+        &self,
+        conn: DbConn<'_>,
+        input: DepositInput,
+    ) -> Result<DepositOutput, RepositoryError> {
         let tx = Transaction::new(
             input.stellar_account,
             input.amount,
@@ -46,7 +52,7 @@ impl ProcessDeposit {
             input.callback_status,
         );
 
-        let inserted = self.transaction_repository.insert(&tx).await?;
+        let inserted = self.transaction_repository.insert(conn, &tx).await?;
 
         Ok(DepositOutput {
             transaction_id: inserted.id,
@@ -0,0 +1,222 @@
+//! Injectable time source.
+//!
+//! Time-dependent logic asks a [`Clock`] for the current time instead of
+//! calling `Utc::now()`, and background loops schedule their ticks with a
+//! [`Ticker`] on the same clock. Production uses [`SystemClock`]; tests use a
+//! [`TestClock`] and [`TestClock::advance`] it past expiries and windows
+//! without sleeping.
+
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+pub trait Clock: Send + Sync + fmt::Debug {
+    /// Wall-clock time, for timestamps and expiry comparisons
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Monotonic time, for measuring and scheduling
+    fn instant(&self) -> Instant;
+
+    /// Resolve once [`Clock::instant`] reaches `deadline`
+    fn sleep_until(&self, deadline: Instant) -> Sleep;
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        self.sleep_until(self.instant() + duration)
+    }
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The real clock
+pub fn system() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Sleep {
+        Box::pin(tokio::time::sleep_until(tokio::time::Instant::from_std(deadline)))
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time.
+#[derive(Clone)]
+pub struct TestClock {
+    wall_start: DateTime<Utc>,
+    instant_start: Instant,
+    /// Time advanced since the start; sleepers wait for it to pass their deadline
+    elapsed: Arc<watch::Sender<Duration>>,
+}
+
+impl TestClock {
+    /// A clock stopped at `now`
+    pub fn at(now: DateTime<Utc>) -> Self {
+        Self {
+            wall_start: now,
+            instant_start: Instant::now(),
+            elapsed: Arc::new(watch::channel(Duration::ZERO).0),
+        }
+    }
+
+    pub fn new() -> Self {
+        Self::at(Utc::now())
+    }
+
+    /// Move time forward, waking every sleeper whose deadline has passed.
+    /// Woken tasks run at their next poll, so callers yield before asserting.
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed.send_modify(|elapsed| *elapsed += duration);
+    }
+
+    pub fn shared(&self) -> SharedClock {
+        Arc::new(self.clone())
+    }
+
+    fn elapsed(&self) -> Duration {
+        *self.elapsed.borrow()
+    }
+}
+
+impl Default for TestClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for TestClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestClock").field("now", &self.now()).finish()
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.wall_start
+            + chrono::Duration::from_std(self.elapsed()).expect("test clock advanced out of range")
+    }
+
+    fn instant(&self) -> Instant {
+        self.instant_start + self.elapsed()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Sleep {
+        let target = deadline.saturating_duration_since(self.instant_start);
+        let mut elapsed = self.elapsed.subscribe();
+        Box::pin(async move {
+            // The sender lives as long as any clone of the clock; if they are
+            // all gone, time never moves again
+            let closed = elapsed.wait_for(|elapsed| *elapsed >= target).await.is_err();
+            if closed {
+                std::future::pending::<()>().await;
+            }
+        })
+    }
+}
+
+/// Periodic ticks on a [`Clock`], like `tokio::time::interval`: the first
+/// tick is immediate, and ticks missed while busy are skipped.
+pub struct Ticker {
+    clock: SharedClock,
+    period: Duration,
+    next: Instant,
+}
+
+impl Ticker {
+    pub fn new(clock: SharedClock, period: Duration) -> Self {
+        let next = clock.instant();
+        Self {
+            clock,
+            period,
+            next,
+        }
+    }
+
+    pub async fn tick(&mut self) {
+        self.clock.sleep_until(self.next).await;
+        let now = self.clock.instant();
+        while self.next <= now {
+            self.next += self.period;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[test]
+    fn test_advance_moves_both_clocks() {
+        let start = Utc::now();
+        let clock = TestClock::at(start);
+        let instant = clock.instant();
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.now(), start + chrono::Duration::seconds(90));
+        assert_eq!(clock.instant() - instant, Duration::from_secs(90));
+        // Clones share the same time
+        assert_eq!(clock.clone().now(), clock.now());
+    }
+
+    #[tokio::test]
+    async fn test_sleep_wakes_only_once_deadline_passes() {
+        let clock = TestClock::new();
+        let sleep = tokio::spawn(clock.sleep(Duration::from_secs(60)));
+
+        clock.advance(Duration::from_secs(59));
+        settle().await;
+        assert!(!sleep.is_finished());
+
+        clock.advance(Duration::from_secs(1));
+        settle().await;
+        assert!(sleep.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_ticker_fast_forwards() {
+        let clock = TestClock::new();
+        let ticks = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counted = ticks.clone();
+        let mut ticker = Ticker::new(clock.shared(), Duration::from_secs(3600));
+        tokio::spawn(async move {
+            loop {
+                ticker.tick().await;
+                counted.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        });
+
+        settle().await;
+        assert_eq!(ticks.load(std::sync::atomic::Ordering::SeqCst), 1);
+        for _ in 0..3 {
+            clock.advance(Duration::from_secs(3600));
+            settle().await;
+        }
+        assert_eq!(ticks.load(std::sync::atomic::Ordering::SeqCst), 4);
+
+        // A long jump runs one catch-up tick, not one per missed period
+        clock.advance(Duration::from_secs(5 * 3600));
+        settle().await;
+        assert_eq!(ticks.load(std::sync::atomic::Ordering::SeqCst), 5);
+    }
+}
@@ -0,0 +1,164 @@
+//! Correlation id for a transaction's whole lifecycle.
+//!
+//! A deposit spans the callback request, worker runs, Horizon calls and
+//! outbound webhooks. They all carry the same [`CorrelationContext`]: it is
+//! stored on the transaction row, recorded on every span it instruments, and
+//! sent as [`CORRELATION_HEADER`] on outgoing HTTP requests.
+
+use std::fmt;
+use tracing::Span;
+use uuid::Uuid;
+
+use crate::db::models::Transaction;
+
+pub const CORRELATION_HEADER: &str = "x-correlation-id";
+
+/// Longer or oddly formed inbound ids are replaced rather than stored
+const MAX_CORRELATION_ID_LEN: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorrelationContext {
+    id: String,
+}
+
+impl CorrelationContext {
+    /// Use `id` if it is a well-formed correlation id
+    pub fn parse(id: &str) -> Option<Self> {
+        let id = id.trim();
+        let valid = !id.is_empty()
+            && id.len() <= MAX_CORRELATION_ID_LEN
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+        valid.then(|| Self { id: id.to_string() })
+    }
+
+    /// Context for a new transaction created by a callback: an explicit
+    /// correlation header, else the request id, else the anchor's
+    /// transaction id, else a fresh id
+    pub fn for_callback(
+        headers: &axum::http::HeaderMap,
+        anchor_transaction_id: Option<&str>,
+    ) -> Self {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        header(CORRELATION_HEADER)
+            .and_then(Self::parse)
+            .or_else(|| header("x-request-id").and_then(Self::parse))
+            .or_else(|| {
+                crate::middleware::request_logger::current_request_id()
+                    .as_deref()
+                    .and_then(Self::parse)
+            })
+            .or_else(|| anchor_transaction_id.and_then(Self::parse))
+            .unwrap_or_else(|| Self {
+                id: Uuid::new_v4().to_string(),
+            })
+    }
+
+    /// Context of an existing transaction. Rows stored before correlation ids
+    /// existed fall back to the transaction id.
+    pub fn for_transaction(tx: &Transaction) -> Self {
+        tx.correlation_id
+            .as_deref()
+            .and_then(Self::parse)
+            .unwrap_or_else(|| Self {
+                id: tx.id.to_string(),
+            })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.id
+    }
+
+    /// Span carrying the id; log lines emitted inside it include the field
+    pub fn span(&self, name: &'static str) -> Span {
+        tracing::info_span!("correlated", operation = name, correlation_id = %self.id)
+    }
+
+    /// Add the correlation header to an outgoing request
+    pub fn apply(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        request.header(CORRELATION_HEADER, &self.id)
+    }
+}
+
+impl fmt::Display for CorrelationContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderMap, HeaderValue};
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, HeaderValue::from_static(value));
+        }
+        map
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_ids() {
+        assert!(CorrelationContext::parse("req-1a2b_3c.4:5").is_some());
+        assert!(CorrelationContext::parse("").is_none());
+        assert!(CorrelationContext::parse("has space").is_none());
+        assert!(CorrelationContext::parse("line\nbreak").is_none());
+        assert!(CorrelationContext::parse(&"a".repeat(MAX_CORRELATION_ID_LEN + 1)).is_none());
+    }
+
+    #[test]
+    fn test_callback_prefers_explicit_header_then_request_id() {
+        let both = headers(&[(CORRELATION_HEADER, "corr-1"), ("x-request-id", "req-1")]);
+        assert_eq!(CorrelationContext::for_callback(&both, Some("anchor-1")).as_str(), "corr-1");
+
+        let request_only = headers(&[("x-request-id", "req-1")]);
+        assert_eq!(
+            CorrelationContext::for_callback(&request_only, Some("anchor-1")).as_str(),
+            "req-1"
+        );
+    }
+
+    #[test]
+    fn test_callback_falls_back_to_anchor_id_then_fresh_id() {
+        let none = HeaderMap::new();
+        assert_eq!(CorrelationContext::for_callback(&none, Some("anchor-1")).as_str(), "anchor-1");
+
+        let fresh = CorrelationContext::for_callback(&none, None);
+        assert!(Uuid::parse_str(fresh.as_str()).is_ok());
+    }
+
+    #[test]
+    fn test_malformed_header_is_skipped() {
+        let bad = headers(&[(CORRELATION_HEADER, "not valid!"), ("x-request-id", "req-2")]);
+        assert_eq!(CorrelationContext::for_callback(&bad, None).as_str(), "req-2");
+    }
+
+    #[test]
+    fn test_transaction_without_stored_id_uses_its_id() {
+        let mut tx = Transaction::new(
+            format!("G{}", "A".repeat(55)),
+            "10".parse().unwrap(),
+            "USD".to_string(),
+            None,
+            None,
+            None,
+        );
+        assert_eq!(CorrelationContext::for_transaction(&tx).as_str(), tx.id.to_string());
+
+        tx.correlation_id = Some("req-3".to_string());
+        assert_eq!(CorrelationContext::for_transaction(&tx).as_str(), "req-3");
+    }
+
+    #[test]
+    fn test_apply_sets_header() {
+        let ctx = CorrelationContext::parse("req-4").unwrap();
+        let request = ctx
+            .apply(reqwest::Client::new().get("http://localhost/"))
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()[CORRELATION_HEADER], "req-4");
+    }
+}
@@ -1,23 +1,52 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
 /// Cursor helpers: encode/decode a (created_at, id) tuple into a base64 string.
 /// Format used internally: "{created_at_rfc3339}|{uuid}" then base64 encoded.
-
+/// RFC 3339 keeps the sub-second digits, so the next page starts exactly
+/// after the cursor's row.
 pub fn encode(created_at: DateTime<Utc>, id: Uuid) -> String {
-    let s = format!("{}|{}", created_at.to_rfc3339(), id.to_string());
-    base64::encode(s)
+    BASE64.encode(format!("{}|{}", created_at.to_rfc3339(), id))
 }
 
 pub fn decode(cursor: &str) -> Result<(DateTime<Utc>, Uuid), String> {
-    let decoded = base64::decode(cursor).map_err(|e| format!("base64 decode error: {}", e))?;
+    let decoded = BASE64.decode(cursor).map_err(|e| format!("base64 decode error: {}", e))?;
     let s = String::from_utf8(decoded).map_err(|e| format!("utf8 error: {}", e))?;
-    let mut parts = s.splitn(2, '|');
-    let ts_str = parts.next().ok_or_else(|| "missing timestamp in cursor".to_string())?;
-    let id_str = parts.next().ok_or_else(|| "missing id in cursor".to_string())?;
+    let (ts_str, id_str) = s.split_once('|').ok_or_else(|| "missing id in cursor".to_string())?;
     let ts = DateTime::parse_from_rfc3339(ts_str)
         .map_err(|e| format!("timestamp parse error: {}", e))?
         .with_timezone(&Utc);
     let id = Uuid::parse_str(id_str).map_err(|e| format!("uuid parse error: {}", e))?;
     Ok((ts, id))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_keeps_microseconds() {
+        let created_at = DateTime::from_timestamp_micros(1_767_225_600_123_456).unwrap();
+        let id = Uuid::new_v4();
+        assert_eq!(decode(&encode(created_at, id)).unwrap(), (created_at, id));
+    }
+
+    #[test]
+    fn test_decodes_cursors_from_earlier_pages() {
+        let id = Uuid::parse_str("6f1c2f4e-0000-4000-8000-000000000000").unwrap();
+        let cursor = BASE64.encode(format!("2026-01-01T00:00:00.123456+00:00|{}", id));
+        let created_at = DateTime::from_timestamp_micros(1_767_225_600_123_456).unwrap();
+        assert_eq!(decode(&cursor).unwrap(), (created_at, id));
+    }
+
+    #[test]
+    fn test_rejects_malformed_cursors() {
+        assert!(decode("not-a-cursor").is_err());
+        assert!(decode(&BASE64.encode("no separator")).is_err());
+        assert!(decode(&BASE64.encode("abc|6f1c2f4e-0000-4000-8000-000000000000")).is_err());
+        assert!(decode(&BASE64.encode("2026-01-01T00:00:00Z|not-a-uuid")).is_err());
+        assert!(decode(&BASE64.encode([0xff, 0xfe])).is_err());
+    }
+}
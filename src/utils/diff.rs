@@ -0,0 +1,220 @@
+//! Field-level before/after diffs of admin DTOs for logs, audit rows and
+//! mutation responses.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use super::sanitize::is_sensitive_field;
+
+/// Stands in for the value of a sensitive field in a diff
+pub const REDACTED: &str = "[REDACTED]";
+
+/// One changed field. A side is omitted when the field is absent on that
+/// side, so `{"before": null}` means "was null, now absent".
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<Value>,
+}
+
+/// Changed fields keyed by dotted path (`metadata.limits.daily`). Nested
+/// objects are compared field by field; arrays and scalars as a whole.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Diff(BTreeMap<String, Change>);
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Change> {
+        self.0.get(path)
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    /// Drop bookkeeping fields that change on every write, like `updated_at`
+    pub fn ignoring(mut self, paths: &[&str]) -> Self {
+        self.0.retain(|path, _| !paths.contains(&path.as_str()));
+        self
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or(Value::Null)
+    }
+}
+
+/// Diff two serializable values of the same type
+pub fn diff<T: Serialize>(before: &T, after: &T) -> serde_json::Result<Diff> {
+    Ok(diff_values(&serde_json::to_value(before)?, &serde_json::to_value(after)?))
+}
+
+pub fn diff_values(before: &Value, after: &Value) -> Diff {
+    let mut diff = Diff::default();
+    walk(&mut diff, "", false, Some(before), Some(after));
+    diff
+}
+
+fn walk(diff: &mut Diff, path: &str, sensitive: bool, before: Option<&Value>, after: Option<&Value>) {
+    if let (Some(Value::Object(before)), Some(Value::Object(after))) = (before, after) {
+        let mut keys: Vec<&String> = before.keys().chain(after.keys()).collect();
+        keys.sort_unstable();
+        keys.dedup();
+        for key in keys {
+            let child = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            walk(
+                diff,
+                &child,
+                sensitive || is_sensitive_field(key),
+                before.get(key),
+                after.get(key),
+            );
+        }
+        return;
+    }
+
+    // Differing types (object vs string, null vs absent) land here too
+    if before != after {
+        let redact = |value: Option<&Value>| {
+            value.map(|value| match value {
+                Value::Null => Value::Null,
+                _ if sensitive => Value::String(REDACTED.to_string()),
+                other => other.clone(),
+            })
+        };
+        diff.0.insert(
+            path.to_string(),
+            Change {
+                before: redact(before),
+                after: redact(after),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_unchanged_values_have_empty_diff() {
+        let value = json!({"a": 1, "b": {"c": [1, 2]}});
+        assert!(diff_values(&value, &value).is_empty());
+    }
+
+    #[test]
+    fn test_only_changed_fields_are_reported() {
+        let diff = diff_values(&json!({"a": 1, "b": 2}), &json!({"a": 1, "b": 3}));
+        assert_eq!(diff.len(), 1);
+        assert_eq!(
+            diff.get("b"),
+            Some(&Change { before: Some(json!(2)), after: Some(json!(3)) })
+        );
+    }
+
+    #[test]
+    fn test_nested_objects_use_dotted_paths() {
+        let diff = diff_values(
+            &json!({"metadata": {"limits": {"daily": 10, "weekly": 50}}}),
+            &json!({"metadata": {"limits": {"daily": 20, "weekly": 50}}}),
+        );
+        assert_eq!(diff.paths().collect::<Vec<_>>(), vec!["metadata.limits.daily"]);
+    }
+
+    #[test]
+    fn test_key_changing_type_is_reported_whole() {
+        let diff = diff_values(
+            &json!({"metadata": {"tier": "gold"}}),
+            &json!({"metadata": "none"}),
+        );
+        assert_eq!(
+            diff.get("metadata"),
+            Some(&Change {
+                before: Some(json!({"tier": "gold"})),
+                after: Some(json!("none")),
+            })
+        );
+    }
+
+    #[test]
+    fn test_null_to_absent_is_a_change() {
+        let diff = diff_values(&json!({"issuer": null}), &json!({}));
+        let change = diff.get("issuer").unwrap();
+        assert_eq!(change.before, Some(Value::Null));
+        assert_eq!(change.after, None);
+        assert_eq!(diff.to_json(), json!({"issuer": {"before": null}}));
+    }
+
+    #[test]
+    fn test_absent_to_value_omits_before() {
+        let diff = diff_values(&json!({}), &json!({"issuer": "GABC"}));
+        assert_eq!(diff.to_json(), json!({"issuer": {"after": "GABC"}}));
+    }
+
+    #[test]
+    fn test_arrays_compare_whole() {
+        let diff = diff_values(&json!({"events": ["a", "b"]}), &json!({"events": ["a", "c"]}));
+        assert_eq!(diff.paths().collect::<Vec<_>>(), vec!["events"]);
+    }
+
+    #[test]
+    fn test_secrets_are_redacted_but_still_reported() {
+        let diff = diff_values(
+            &json!({"config": {"api_key": "old-key-123456"}}),
+            &json!({"config": {"api_key": "new-key-654321"}}),
+        );
+        assert_eq!(
+            diff.to_json(),
+            json!({"config.api_key": {"before": REDACTED, "after": REDACTED}})
+        );
+    }
+
+    #[test]
+    fn test_values_under_a_secret_key_are_redacted() {
+        let diff = diff_values(
+            &json!({"secret": {"value": "a"}}),
+            &json!({"secret": {"value": "b"}}),
+        );
+        assert_eq!(diff.get("secret.value").unwrap().after, Some(json!(REDACTED)));
+    }
+
+    #[test]
+    fn test_ignoring_drops_bookkeeping_fields() {
+        let diff = diff_values(
+            &json!({"enabled": false, "updated_at": "2026-01-01"}),
+            &json!({"enabled": true, "updated_at": "2026-01-02"}),
+        )
+        .ignoring(&["updated_at"]);
+        assert_eq!(diff.paths().collect::<Vec<_>>(), vec!["enabled"]);
+    }
+
+    #[test]
+    fn test_diff_of_structs() {
+        #[derive(Serialize)]
+        struct Flag {
+            name: &'static str,
+            enabled: bool,
+        }
+        let diff = diff(
+            &Flag { name: "x", enabled: false },
+            &Flag { name: "x", enabled: true },
+        )
+        .unwrap();
+        assert_eq!(diff.to_json(), json!({"enabled": {"before": false, "after": true}}));
+    }
+}
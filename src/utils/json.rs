@@ -0,0 +1,100 @@
+use serde::{Serialize, Serializer};
+use serde_json::ser::PrettyFormatter;
+use serde_json::Value;
+
+/// Serializes a JSON value with object keys in sorted order at every level,
+/// without cloning it. Use through `#[serde(serialize_with = ...)]` on free-form
+/// fields of admin DTOs so two fetches of the same record diff cleanly.
+struct Sorted<'a>(&'a Value);
+
+impl Serialize for Sorted<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::Object(map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+                serializer.collect_map(entries.into_iter().map(|(k, v)| (k, Sorted(v))))
+            }
+            Value::Array(items) => serializer.collect_seq(items.iter().map(Sorted)),
+            other => other.serialize(serializer),
+        }
+    }
+}
+
+pub fn serialize_sorted<S: Serializer>(value: &Value, serializer: S) -> Result<S::Ok, S::Error> {
+    Sorted(value).serialize(serializer)
+}
+
+pub fn serialize_sorted_opt<S: Serializer>(
+    value: &Option<Value>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value.as_ref().map(Sorted).serialize(serializer)
+}
+
+/// Re-indent JSON with `serde_json`'s `PrettyFormatter`, as
+/// `serde_json::to_string_pretty` would, keeping the original key order.
+/// Fails if `compact` is not a single JSON value.
+pub fn reindent(compact: &[u8]) -> serde_json::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(compact.len() * 2);
+    let mut deserializer = serde_json::Deserializer::from_slice(compact);
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, PrettyFormatter::new());
+    serde_transcode::transcode(&mut deserializer, &mut serializer)?;
+    deserializer.end()?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Serialize)]
+    struct Dto {
+        zeta: u32,
+        #[serde(serialize_with = "serialize_sorted")]
+        metadata: Value,
+        alpha: u32,
+    }
+
+    #[test]
+    fn test_reindent_matches_serde_pretty() {
+        let value = json!({
+            "b": [1, 2, {"c": "x,y:{z}"}],
+            "a": {},
+            "e": [],
+            "d": "quote \" and \\ backslash"
+        });
+        let compact = serde_json::to_vec(&value).unwrap();
+        let expected = serde_json::to_string_pretty(&value).unwrap();
+        assert_eq!(String::from_utf8(reindent(&compact).unwrap()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_reindent_keeps_key_order() {
+        let pretty = reindent(br#"{"zeta":1,"alpha":{"y":2,"x":[]}}"#).unwrap();
+        assert_eq!(
+            String::from_utf8(pretty).unwrap(),
+            "{\n  \"zeta\": 1,\n  \"alpha\": {\n    \"y\": 2,\n    \"x\": []\n  }\n}"
+        );
+        assert!(reindent(b"{\"truncated\":").is_err());
+    }
+
+    #[test]
+    fn test_sorted_keeps_struct_order_and_sorts_maps() {
+        let mut metadata = serde_json::Map::new();
+        metadata.insert("name".to_string(), json!("USD Coin"));
+        metadata.insert("decimals".to_string(), json!(7));
+        metadata.insert("links".to_string(), json!({"web": "w", "docs": "d"}));
+
+        let dto = Dto {
+            zeta: 1,
+            metadata: Value::Object(metadata),
+            alpha: 2,
+        };
+        assert_eq!(
+            serde_json::to_string(&dto).unwrap(),
+            r#"{"zeta":1,"metadata":{"decimals":7,"links":{"docs":"d","web":"w"},"name":"USD Coin"},"alpha":2}"#
+        );
+    }
+}
@@ -1 +1,8 @@
+pub mod clock;
+pub mod correlation;
+pub mod cursor;
+pub mod diff;
+pub mod json;
 pub mod sanitize;
+pub mod shutdown;
+pub mod signature;
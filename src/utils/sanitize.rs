@@ -20,7 +20,7 @@ pub fn sanitize_json(value: &Value) -> Value {
     }
 }
 
-fn is_sensitive_field(key: &str) -> bool {
+pub fn is_sensitive_field(key: &str) -> bool {
     matches!(
         key.to_lowercase().as_str(),
         "stellar_account" | "account" | "password" | "secret" | "token" | "api_key" | "authorization"
@@ -0,0 +1,101 @@
+//! Coordinated shutdown of background workers.
+//!
+//! One [`Shutdown`] is created at startup and handed to every worker. Workers
+//! spawn their loops through it and wait for [`Shutdown::cancelled`] between
+//! iterations, never during one, so a signal lets the current iteration
+//! finish. After the HTTP server has drained, [`Shutdown::wait`] waits for
+//! the workers to exit before the pools close.
+
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+/// How long workers get to finish their current iteration
+pub const WORKER_GRACE: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    tasks: TaskTracker,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawn a worker loop that [`Shutdown::wait`] waits for
+    pub fn spawn<F>(&self, task: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.tasks.spawn(task)
+    }
+
+    /// Begin shutting down; every `cancelled` resolves
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Resolves once shutdown has begun
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+
+    /// Wait up to `grace` for the spawned workers to exit. `false` if some
+    /// were still running; they are dropped with the runtime.
+    pub async fn wait(&self, grace: Duration) -> bool {
+        self.tasks.close();
+        tokio::time::timeout(grace, self.tasks.wait()).await.is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_workers_finish_their_iteration_then_exit() {
+        let shutdown = Shutdown::new();
+        let iterations = Arc::new(AtomicU32::new(0));
+
+        let worker = shutdown.clone();
+        let counted = iterations.clone();
+        shutdown.spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = worker.cancelled() => break,
+                    _ = tokio::time::sleep(Duration::from_millis(5)) => {}
+                }
+                // An iteration that outlasts the signal still completes
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                counted.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        shutdown.cancel();
+        assert!(shutdown.wait(Duration::from_secs(1)).await);
+        assert_eq!(iterations.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wait_gives_up_after_grace() {
+        let shutdown = Shutdown::new();
+        shutdown.spawn(std::future::pending::<()>());
+
+        shutdown.cancel();
+        assert!(shutdown.is_cancelled());
+        assert!(!shutdown.wait(Duration::from_millis(20)).await);
+    }
+}
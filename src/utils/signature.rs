@@ -0,0 +1,54 @@
+//! HMAC-SHA256 callback body signatures, shared by the webhook verifier and
+//! the load generator so the two can never disagree.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SIGNATURE_HEADER: &str = "X-Stellar-Signature";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureError {
+    InvalidFormat,
+    InvalidSecret,
+    Mismatch,
+}
+
+/// Hex-encoded HMAC-SHA256 of `body`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    // HMAC accepts keys of any length, so this cannot fail
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC takes any key length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Verify a hex signature using constant-time comparison
+pub fn verify(secret: &str, body: &[u8], signature_hex: &str) -> Result<(), SignatureError> {
+    let expected = hex::decode(signature_hex).map_err(|_| SignatureError::InvalidFormat)?;
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).map_err(|_| SignatureError::InvalidSecret)?;
+    mac.update(body);
+    mac.verify_slice(&expected).map_err(|_| SignatureError::Mismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_then_verify() {
+        let body = br#"{"id":"123","status":"completed"}"#;
+        let signature = sign("test_secret_key", body);
+        assert_eq!(signature.len(), 64);
+        assert_eq!(verify("test_secret_key", body, &signature), Ok(()));
+    }
+
+    #[test]
+    fn test_verify_rejects_wrong_secret_and_garbage() {
+        let body = b"payload";
+        let signature = sign("secret-a", body);
+        assert_eq!(verify("secret-b", body, &signature), Err(SignatureError::Mismatch));
+        assert_eq!(verify("secret-a", body, "not-hex"), Err(SignatureError::InvalidFormat));
+    }
+}
@@ -2,12 +2,15 @@ use serde::Deserialize;
 use sqlx::types::BigDecimal;
 use std::fmt;
 
+use crate::stellar::strkey::{self, AccountKind};
+
 pub const STELLAR_ACCOUNT_LEN: usize = 56;
 pub const ASSET_CODE_MAX_LEN: usize = 12;
 pub const ANCHOR_TRANSACTION_ID_MAX_LEN: usize = 255;
 pub const CALLBACK_TYPE_MAX_LEN: usize = 20;
 pub const CALLBACK_STATUS_MAX_LEN: usize = 20;
 pub const AMOUNT_INPUT_MAX_LEN: usize = 64;
+pub const QUOTE_ID_MAX_LEN: usize = 64;
 pub const ALLOWED_ASSET_CODES: &[&str] = &["USD"];
 
 #[derive(Debug, Deserialize)]
@@ -113,6 +116,15 @@ pub fn validate_stellar_address(stellar_address: &str) -> ValidationResult {
     Ok(())
 }
 
+/// Full strkey check of a `G` or `M` account: alphabet, length, version byte
+/// and checksum
+pub fn validate_stellar_strkey(stellar_address: &str) -> Result<AccountKind, ValidationError> {
+    let stellar_address = sanitize_string(stellar_address);
+    validate_required("stellar_address", &stellar_address)?;
+    strkey::validate_account(&stellar_address)
+        .map_err(|err| ValidationError::new("stellar_address", err.to_string()))
+}
+
 pub fn validate_stellar_account(account: &str) -> ValidationResult {
     validate_stellar_address(account)
 }
@@ -190,6 +202,17 @@ mod tests {
         assert!(validate_stellar_address(&format!(" {} ", valid_stellar_address())).is_ok());
     }
 
+    #[test]
+    fn validates_stellar_strkey() {
+        let account = "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ";
+        assert!(validate_stellar_strkey(account).is_ok());
+        assert!(validate_stellar_strkey(&format!(" {} ", account)).is_ok());
+        // Shaped like an account, but the checksum is wrong
+        let err = validate_stellar_strkey(&valid_stellar_address()).unwrap_err();
+        assert_eq!(err.to_string(), "stellar_address: checksum does not match");
+        assert!(validate_stellar_strkey("   ").is_err());
+    }
+
     #[test]
     fn validates_asset_code() {
         assert!(validate_asset_code("USD").is_ok());
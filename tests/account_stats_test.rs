@@ -0,0 +1,192 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::{get, post},
+    Router,
+};
+use serde_json::Value;
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use std::str::FromStr;
+use synapse_core::db::models::Transaction;
+use synapse_core::db::{queries, uow};
+use synapse_core::handlers::accounts;
+use synapse_core::services::AccountStatsService;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+/// A valid account nobody else uses
+fn unique_account() -> String {
+    format!("G{}{}", Uuid::new_v4().simple().to_string().to_uppercase(), "A".repeat(23))
+}
+
+fn amount(raw: &str) -> BigDecimal {
+    BigDecimal::from_str(raw).unwrap()
+}
+
+async fn insert(pool: &PgPool, account: &str, asset_code: &str, value: &str) -> Transaction {
+    let tx = Transaction::new(
+        account.to_string(),
+        amount(value),
+        asset_code.to_string(),
+        None,
+        None,
+        None,
+    );
+    queries::insert_transaction(pool, &tx).await.unwrap()
+}
+
+async fn transition(pool: &PgPool, id: Uuid, status: &'static str) {
+    uow::run(pool, |uow| Box::pin(async move {
+        queries::update_transaction_status(uow.conn(), id, &[], status, "system").await
+    }))
+    .await
+    .unwrap()
+    .expect("transaction exists");
+}
+
+async fn assert_matches_raw(pool: &PgPool, account: &str) {
+    let stored = queries::get_account_stats(pool, account).await.unwrap();
+    let raw = queries::compute_account_stats(pool, account).await.unwrap();
+    assert!(!stored.is_empty());
+    assert_eq!(stored.len(), raw.len());
+    for (stored, raw) in stored.iter().zip(&raw) {
+        assert!(stored.same_totals(raw), "stored {:?} != raw {:?}", stored, raw);
+    }
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_inserts_and_transitions_keep_stats_in_step() {
+    let pool = common::setup_pool().await;
+    let account = unique_account();
+
+    let first = insert(&pool, &account, "USD", "100.50").await;
+    let second = insert(&pool, &account, "USD", "20").await;
+    let third = insert(&pool, &account, "USD", "5").await;
+    insert(&pool, &account, "EUR", "7.25").await;
+
+    transition(&pool, first.id, "processing").await;
+    transition(&pool, first.id, "completed").await;
+    transition(&pool, second.id, "failed").await;
+    transition(&pool, third.id, "pending_trustline").await;
+
+    let stats = queries::get_account_stats(&pool, &account).await.unwrap();
+    let usd = stats.iter().find(|s| s.asset_code == "USD").unwrap();
+    assert_eq!(usd.total_completed, amount("100.50"));
+    assert_eq!(usd.total_pending, amount("5"));
+    assert_eq!(usd.deposit_count, 3);
+    assert_eq!(usd.first_deposit_at, Some(first.created_at));
+    assert_eq!(usd.last_deposit_at, Some(third.created_at));
+
+    assert_matches_raw(&pool, &account).await;
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_concurrent_writes_do_not_lose_increments() {
+    let pool = common::setup_pool().await;
+    let account = unique_account();
+
+    let inserts = (0..20).map(|_| {
+        let pool = pool.clone();
+        let account = account.clone();
+        tokio::spawn(async move { insert(&pool, &account, "USD", "1.10").await })
+    });
+    let inserted: Vec<Transaction> = futures::future::join_all(inserts)
+        .await
+        .into_iter()
+        .map(|joined| joined.unwrap())
+        .collect();
+
+    let completions = inserted.iter().take(10).map(|tx| {
+        let pool = pool.clone();
+        let id = tx.id;
+        tokio::spawn(async move { transition(&pool, id, "completed").await })
+    });
+    for joined in futures::future::join_all(completions).await {
+        joined.unwrap();
+    }
+
+    let stats = queries::get_account_stats(&pool, &account).await.unwrap();
+    assert_eq!(stats[0].deposit_count, 20);
+    assert_eq!(stats[0].total_completed, amount("11.00"));
+    assert_eq!(stats[0].total_pending, amount("11.00"));
+    assert_matches_raw(&pool, &account).await;
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_missing_row_falls_back_to_raw_and_transition_recreates_it() {
+    let pool = common::setup_pool().await;
+    let account = unique_account();
+    let tx = insert(&pool, &account, "USD", "42").await;
+
+    sqlx::query("DELETE FROM account_stats WHERE stellar_account = $1")
+        .bind(&account)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let summary = AccountStatsService::new(pool.clone()).summary(&account).await.unwrap();
+    assert_eq!(summary.len(), 1);
+    assert_eq!(summary[0].total_pending, amount("42"));
+    assert!(queries::get_account_stats(&pool, &account).await.unwrap().is_empty());
+
+    transition(&pool, tx.id, "completed").await;
+    let stats = queries::get_account_stats(&pool, &account).await.unwrap();
+    assert_eq!(stats[0].total_completed, amount("42"));
+    assert_eq!(stats[0].total_pending, amount("0"));
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_drift_is_repaired_by_job_and_endpoint() {
+    let pool = common::setup_pool().await;
+    let state = common::app_state(pool.clone());
+    let account = unique_account();
+    insert(&pool, &account, "USD", "10").await;
+
+    let corrupt = "UPDATE account_stats SET total_pending = 999, deposit_count = 7 WHERE stellar_account = $1";
+    sqlx::query(corrupt).bind(&account).execute(&pool).await.unwrap();
+
+    let since = chrono::Utc::now() - chrono::Duration::minutes(5);
+    assert!(state.account_stats.repair_drift(since).await.unwrap() >= 1);
+    assert_matches_raw(&pool, &account).await;
+
+    sqlx::query(corrupt).bind(&account).execute(&pool).await.unwrap();
+    let app = Router::new()
+        .route("/admin/accounts/:id/stats", get(accounts::get_stats))
+        .route("/admin/accounts/:id/recompute-stats", post(accounts::recompute_stats))
+        .with_state(state);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/admin/accounts/{}/recompute-stats", account))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["drifted"], true);
+    assert_eq!(body["stats"][0]["deposit_count"], 1);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/admin/accounts/not-an-account/stats")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
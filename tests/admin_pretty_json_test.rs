@@ -0,0 +1,134 @@
+use axum::{
+    body::Body,
+    http::{header, Request},
+    middleware::from_fn,
+    routing::get,
+    Json, Router,
+};
+use chrono::{TimeZone, Utc};
+use serde_json::json;
+use synapse_core::db::models::Asset;
+use synapse_core::handlers::export::ExportStatusResponse;
+use synapse_core::middleware::pretty_json::pretty_json;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+fn asset() -> Asset {
+    let at = Utc.with_ymd_and_hms(2026, 2, 21, 10, 0, 0).unwrap();
+    Asset {
+        id: Uuid::nil(),
+        asset_code: "USDC".to_string(),
+        asset_issuer: Some("GISSUER".to_string()),
+        // Deliberately out of order; output must be sorted
+        metadata: Some(json!({"name": "USD Coin", "decimals": 7, "anchor": {"url": "u", "kind": "fiat"}})),
+        enabled: Some(true),
+        verification_status: "verified".to_string(),
+        verification_error: None,
+        verified_at: Some(at),
+        created_at: Some(at),
+        updated_at: Some(at),
+    }
+}
+
+fn export_status() -> ExportStatusResponse {
+    let at = Utc.with_ymd_and_hms(2026, 2, 21, 10, 0, 0).unwrap();
+    ExportStatusResponse {
+        id: Uuid::nil(),
+        status: "ready".to_string(),
+        format: "csv".to_string(),
+        params: json!({"to": "2026-02-01", "from": "2026-01-01", "asset_code": "USDC"}),
+        reused: false,
+        content_hash: Some("abc".to_string()),
+        size_bytes: Some(1024),
+        row_count: Some(10),
+        error: None,
+        created_at: at,
+        completed_at: Some(at),
+        expires_at: at,
+        download_url: None,
+    }
+}
+
+fn app() -> Router {
+    Router::new()
+        .route("/admin/assets/snapshot", get(|| async { Json(asset()) }))
+        .route("/admin/exports/snapshot", get(|| async { Json(export_status()) }))
+        .layer(from_fn(pretty_json))
+}
+
+async fn fetch(uri: &str) -> String {
+    let response = app()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert!(response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("application/json"));
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_asset_pretty_snapshot() {
+    let body = fetch("/admin/assets/snapshot?pretty=true").await;
+    assert_eq!(
+        body,
+        r#"{
+  "id": "00000000-0000-0000-0000-000000000000",
+  "asset_code": "USDC",
+  "asset_issuer": "GISSUER",
+  "metadata": {
+    "anchor": {
+      "kind": "fiat",
+      "url": "u"
+    },
+    "decimals": 7,
+    "name": "USD Coin"
+  },
+  "enabled": true,
+  "verification_status": "verified",
+  "verification_error": null,
+  "verified_at": "2026-02-21T10:00:00Z",
+  "created_at": "2026-02-21T10:00:00Z",
+  "updated_at": "2026-02-21T10:00:00Z"
+}"#
+    );
+}
+
+#[tokio::test]
+async fn test_export_status_pretty_snapshot() {
+    let body = fetch("/admin/exports/snapshot?pretty=true").await;
+    assert_eq!(
+        body,
+        r#"{
+  "id": "00000000-0000-0000-0000-000000000000",
+  "status": "ready",
+  "format": "csv",
+  "params": {
+    "asset_code": "USDC",
+    "from": "2026-01-01",
+    "to": "2026-02-01"
+  },
+  "reused": false,
+  "content_hash": "abc",
+  "size_bytes": 1024,
+  "row_count": 10,
+  "error": null,
+  "created_at": "2026-02-21T10:00:00Z",
+  "completed_at": "2026-02-21T10:00:00Z",
+  "expires_at": "2026-02-21T10:00:00Z",
+  "download_url": null
+}"#
+    );
+}
+
+#[tokio::test]
+async fn test_compact_without_parameter() {
+    let body = fetch("/admin/exports/snapshot").await;
+    assert!(!body.contains('\n'));
+    assert!(body.contains(r#""params":{"asset_code":"USDC","from":"2026-01-01","to":"2026-02-01"}"#));
+}
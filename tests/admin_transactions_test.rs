@@ -0,0 +1,126 @@
+mod common;
+
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use synapse_core::db::models::{Transaction, TransactionStatus};
+use synapse_core::db::queries::{self, TransactionListFilter};
+use synapse_core::db::{transactions, uow};
+use synapse_core::error::AppError;
+use synapse_core::services::transaction;
+use uuid::Uuid;
+
+async fn insert(pool: &PgPool, account: &str) -> Transaction {
+    let tx = Transaction::new(
+        account.to_string(),
+        BigDecimal::from(40),
+        "USDC".to_string(),
+        Some(format!("anchor-{}", Uuid::new_v4())),
+        Some("deposit".to_string()),
+        None,
+    );
+    queries::insert_transaction(pool, &tx).await.unwrap()
+}
+
+async fn fail(pool: &PgPool, id: Uuid) {
+    uow::run(pool, |uow| Box::pin(async move {
+        transaction::transition(uow.conn(), id, TransactionStatus::Failed, Some("rejected"), "test").await
+    }))
+    .await
+    .unwrap();
+}
+
+async fn retry(pool: &PgPool, id: Uuid) -> Result<Transaction, AppError> {
+    uow::run(pool, |uow| Box::pin(async move {
+        transaction::retry(uow.conn(), id, Some("retried"), "admin").await
+    }))
+    .await
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_list_filters_by_account_and_status() {
+    let pool = common::setup_pool().await;
+    let account = format!("GADMIN{}", Uuid::new_v4().simple()).to_uppercase();
+    let pending = insert(&pool, &account).await;
+    let failed = insert(&pool, &account).await;
+    fail(&pool, failed.id).await;
+
+    let filter = TransactionListFilter {
+        stellar_account: Some(&account),
+        ..Default::default()
+    };
+    let all = transactions::list(&pool, &filter, None, 10).await.unwrap();
+    let ids: Vec<Uuid> = all.iter().map(|detail| detail.transaction.id).collect();
+    assert_eq!(ids, vec![failed.id, pending.id]);
+
+    let filter = TransactionListFilter {
+        stellar_account: Some(&account),
+        status: Some("failed"),
+        ..Default::default()
+    };
+    let failed_only = transactions::list(&pool, &filter, None, 10).await.unwrap();
+    assert_eq!(failed_only.len(), 1);
+    assert_eq!(failed_only[0].transaction.id, failed.id);
+    assert!(!failed_only[0].needs_review);
+
+    // The second page starts after the first row
+    let first = &all[0].transaction;
+    let filter = TransactionListFilter {
+        stellar_account: Some(&account),
+        ..Default::default()
+    };
+    let page = transactions::list(&pool, &filter, Some((first.created_at, first.id)), 10).await.unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].transaction.id, pending.id);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_retry_sends_a_failed_transaction_back_to_pending() {
+    let pool = common::setup_pool().await;
+    let tx = insert(&pool, "GADMINRETRY").await;
+    fail(&pool, tx.id).await;
+    sqlx::query("UPDATE transactions SET payout_tx_hash = 'stale', payout_after = NOW() + INTERVAL '1 hour' WHERE id = $1")
+        .bind(tx.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let retried = retry(&pool, tx.id).await.unwrap();
+    assert_eq!(retried.status, "pending");
+    let detail = transactions::get(&pool, tx.id).await.unwrap().unwrap();
+    assert_eq!(detail.payout_tx_hash, None);
+    let payout_after: Option<chrono::DateTime<chrono::Utc>> =
+        sqlx::query_scalar("SELECT payout_after FROM transactions WHERE id = $1")
+            .bind(tx.id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(payout_after, None);
+
+    let history = queries::get_status_history(&pool, tx.id).await.unwrap();
+    let last = history.last().unwrap();
+    assert_eq!((last.old_status.as_str(), last.new_status.as_str()), ("failed", "pending"));
+    assert_eq!(last.actor, "admin");
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_only_failed_transactions_are_retried() {
+    let pool = common::setup_pool().await;
+    let tx = insert(&pool, "GADMINRETRY").await;
+
+    let error = retry(&pool, tx.id).await.unwrap_err();
+    assert!(matches!(
+        error,
+        AppError::InvalidTransition {
+            from: TransactionStatus::Pending,
+            to: TransactionStatus::Pending,
+            ..
+        }
+    ));
+    assert!(queries::get_status_history(&pool, tx.id).await.unwrap().is_empty());
+
+    let error = retry(&pool, Uuid::new_v4()).await.unwrap_err();
+    assert!(matches!(error, AppError::NotFound(_)));
+}
@@ -0,0 +1,89 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{header, Request, StatusCode},
+    middleware::from_fn_with_state,
+    routing::post,
+    Router,
+};
+use chrono::Utc;
+use ed25519_dalek::SigningKey;
+use synapse_core::middleware::anchor_signature::{
+    self, sign, verify_anchor_signature, AnchorSignatureVerifier, SIGNATURE_HEADER,
+};
+use synapse_core::utils::clock::TestClock;
+use tower::ServiceExt;
+
+const HOST: &str = "synapse.example.com";
+
+fn anchor_key() -> SigningKey {
+    SigningKey::from_bytes(&[7; 32])
+}
+
+/// A callback route that echoes the body it was handed
+fn app(verifier: AnchorSignatureVerifier) -> Router {
+    Router::new().route(
+        "/callback",
+        post(|body: Bytes| async move { body })
+            .layer(from_fn_with_state(verifier, verify_anchor_signature)),
+    )
+}
+
+fn verifier() -> AnchorSignatureVerifier {
+    AnchorSignatureVerifier::new(anchor_key().verifying_key(), TestClock::at(Utc::now()).shared())
+}
+
+async fn post_callback(
+    app: Router,
+    signature: Option<String>,
+    body: &'static str,
+) -> (StatusCode, Bytes) {
+    let mut request = Request::post("/callback").header(header::HOST, format!("{}:443", HOST));
+    if let Some(signature) = signature {
+        request = request.header(SIGNATURE_HEADER, signature);
+    }
+    let response = app.oneshot(request.body(Body::from(body)).unwrap()).await.unwrap();
+    let status = response.status();
+    (status, axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap())
+}
+
+#[tokio::test]
+async fn test_signed_callback_reaches_the_handler_unchanged() {
+    let body = r#"{"anchor_transaction_id":"anchor-1","status":"completed"}"#;
+    let signature = sign(&anchor_key(), Utc::now().timestamp(), HOST, body.as_bytes());
+
+    let (status, echoed) = post_callback(app(verifier()), Some(signature), body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(echoed, body.as_bytes());
+}
+
+#[tokio::test]
+async fn test_unsigned_and_forged_callbacks_get_401() {
+    let body = r#"{"anchor_transaction_id":"anchor-1","status":"completed"}"#;
+    let (status, _) = post_callback(app(verifier()), None, body).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let stranger = SigningKey::from_bytes(&[9; 32]);
+    let forged = sign(&stranger, Utc::now().timestamp(), HOST, body.as_bytes());
+    let (status, _) = post_callback(app(verifier()), Some(forged), body).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Signed for another body
+    let other = sign(&anchor_key(), Utc::now().timestamp(), HOST, b"{}");
+    let (status, _) = post_callback(app(verifier()), Some(other), body).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let replayed = sign(
+        &anchor_key(),
+        Utc::now().timestamp() - anchor_signature::MAX_SIGNATURE_AGE.as_secs() as i64 - 60,
+        HOST,
+        body.as_bytes(),
+    );
+    let (status, _) = post_callback(app(verifier()), Some(replayed), body).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_disabled_verifier_lets_callbacks_through() {
+    let (status, _) = post_callback(app(AnchorSignatureVerifier::disabled()), None, "{}").await;
+    assert_eq!(status, StatusCode::OK);
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use sqlx::PgPool;
+use std::time::Duration;
+use synapse_core::config::BufferedWriteConfig;
+use synapse_core::db::audit::AuditLog;
+use synapse_core::db::queries;
+use synapse_core::services::api_tokens::{CreateTokenRequest, TokenScope};
+use synapse_core::services::buffered_writer::{BufferedWriteStats, ShutdownReport};
+use synapse_core::services::{ApiTokenService, BufferedWriter};
+use synapse_core::utils::clock::TestClock;
+use uuid::Uuid;
+
+/// Flushes only on batch size or shutdown: the test clock never reaches the next tick
+fn started_writer(pool: &PgPool, batch_size: usize) -> BufferedWriter {
+    let clock = TestClock::new();
+    let writer = BufferedWriter::new(
+        pool.clone(),
+        BufferedWriteConfig {
+            capacity: 100,
+            batch_size,
+            flush_interval: Duration::from_secs(3600),
+            shutdown_timeout: Duration::from_secs(5),
+        },
+    )
+    .with_clock(clock.shared());
+    writer.start();
+    writer
+}
+
+fn audit_entry(entity_id: Uuid) -> AuditLog {
+    AuditLog::new(entity_id, "asset", "created", None, Some(serde_json::json!({"asset_code": "USDC"})), "test")
+}
+
+async fn audit_rows(pool: &PgPool, entity_id: Uuid) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM audit_logs WHERE entity_id = $1")
+        .bind(entity_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn create_token(pool: &PgPool) -> Uuid {
+    let (token, _) = ApiTokenService::new(pool.clone())
+        .create(
+            CreateTokenRequest {
+                name: "usage".to_string(),
+                scope: TokenScope {
+                    routes: vec!["GET /transactions".to_string()],
+                    asset_code: None,
+                    from: None,
+                    to: None,
+                },
+                expires_at: chrono::Utc::now() + chrono::Duration::days(1),
+            },
+            "test",
+        )
+        .await
+        .unwrap();
+    token.id
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_full_batch_is_written_without_waiting_for_a_tick() {
+    let pool = common::setup_pool().await;
+    let writer = started_writer(&pool, 2);
+    let entity_id = Uuid::new_v4();
+
+    writer.audit(audit_entry(entity_id)).await.unwrap();
+    writer.audit(audit_entry(entity_id)).await.unwrap();
+
+    for _ in 0..50 {
+        if audit_rows(&pool, entity_id).await == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(audit_rows(&pool, entity_id).await, 2);
+    assert_eq!(writer.stats(), BufferedWriteStats::default());
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_shutdown_flushes_everything_queued() {
+    let pool = common::setup_pool().await;
+    let writer = started_writer(&pool, 1000);
+    let tokens = ApiTokenService::new(pool.clone()).with_usage(writer.clone());
+    let token_id = create_token(&pool).await;
+    let entity_id = Uuid::new_v4();
+    // Let the immediate first tick pass before queueing
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    for _ in 0..3 {
+        writer.audit(audit_entry(entity_id)).await.unwrap();
+        tokens.record_request(token_id, "GET", "/transactions");
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(audit_rows(&pool, entity_id).await, 0);
+
+    let report = writer.shutdown().await;
+    assert_eq!(report, ShutdownReport { written: 6, spilled: 0 });
+    assert_eq!(audit_rows(&pool, entity_id).await, 3);
+
+    // Counts for one token, route and day are summed into a single row
+    let usage = queries::list_api_token_usage(&pool, token_id).await.unwrap();
+    assert_eq!(usage.len(), 1);
+    assert_eq!(usage[0].route, "/transactions");
+    assert_eq!(usage[0].requests, 3);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_stopped_writer_writes_audit_synchronously_and_drops_usage() {
+    let pool = common::setup_pool().await;
+    let writer = started_writer(&pool, 1000);
+    let tokens = ApiTokenService::new(pool.clone()).with_usage(writer.clone());
+    let token_id = create_token(&pool).await;
+    let entity_id = Uuid::new_v4();
+
+    // Stop the writer mid-stream
+    tokens.record_request(token_id, "GET", "/transactions");
+    writer.audit(audit_entry(entity_id)).await.unwrap();
+    writer.shutdown().await;
+    assert_eq!(audit_rows(&pool, entity_id).await, 1);
+
+    // The audit entry is on disk as soon as the call returns
+    writer.audit(audit_entry(entity_id)).await.unwrap();
+    assert_eq!(audit_rows(&pool, entity_id).await, 2);
+
+    tokens.record_request(token_id, "GET", "/transactions");
+    tokens.record_request(token_id, "GET", "/transactions");
+    assert_eq!(
+        writer.stats(),
+        BufferedWriteStats {
+            sync_audit_writes: 1,
+            dropped_usage: 2,
+        }
+    );
+    let usage = queries::list_api_token_usage(&pool, token_id).await.unwrap();
+    assert_eq!(usage[0].requests, 1);
+}
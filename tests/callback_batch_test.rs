@@ -0,0 +1,148 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{json, Value};
+use synapse_core::{create_app, AppState};
+use tower::ServiceExt;
+
+const ACCOUNT: &str = "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ";
+
+fn callback(id: &str, amount: &str) -> Value {
+    json!({
+        "id": id,
+        "amount_in": amount,
+        "stellar_account": ACCOUNT,
+        "asset_code": "USD",
+        "callback_type": "deposit",
+        "status": "pending"
+    })
+}
+
+async fn post_batch(state: AppState, items: Value) -> (StatusCode, Value) {
+    let response = create_app(state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/callback/transactions/batch")
+                .header("content-type", "application/json")
+                .body(Body::from(items.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_batch_reports_each_item() {
+    let pool = common::setup_pool().await;
+    let stored = format!("anchor-batch-{}", uuid::Uuid::new_v4());
+    let (status, _) = post_batch(common::app_state(pool.clone()), json!([callback(&stored, "5")])).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+
+    let new = format!("anchor-batch-{}", uuid::Uuid::new_v4());
+    let items = json!([
+        callback(&new, "10"),
+        callback(&stored, "5"),
+        callback("anchor-batch-invalid", "-1"),
+        callback(&new, "10"),
+    ]);
+    let (status, body) = post_batch(common::app_state(pool.clone()), items).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert_eq!((body["created"].as_u64(), body["duplicates"].as_u64()), (Some(1), Some(2)));
+    assert_eq!(body["rejected"], 1);
+
+    let results = body["results"].as_array().unwrap();
+    let outcomes: Vec<&str> = results.iter().map(|r| r["outcome"].as_str().unwrap()).collect();
+    assert_eq!(outcomes, ["created", "duplicate", "validation_error", "duplicate"]);
+    assert_eq!(results[1]["id"], stored.as_str());
+    assert!(results[2]["error"].as_str().unwrap().contains("amount"));
+    // The repeat inside the batch points at the transaction its first item created
+    assert_eq!(results[3]["transaction_id"], results[0]["transaction_id"]);
+
+    let created: uuid::Uuid = results[0]["transaction_id"].as_str().unwrap().parse().unwrap();
+    let (anchor_transaction_id, raw): (Option<String>, i64) = sqlx::query_as(
+        r#"
+        SELECT t.anchor_transaction_id,
+               (SELECT COUNT(*) FROM raw_callbacks r WHERE r.transaction_id = t.id)
+        FROM transactions t WHERE t.id = $1
+        "#,
+    )
+    .bind(created)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!((anchor_transaction_id.as_deref(), raw), (Some(new.as_str()), 1));
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_repeats_with_another_amount_are_rejected() {
+    let pool = common::setup_pool().await;
+    let stored = format!("anchor-batch-{}", uuid::Uuid::new_v4());
+    let (status, _) = post_batch(common::app_state(pool.clone()), json!([callback(&stored, "5")])).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+
+    let new = format!("anchor-batch-{}", uuid::Uuid::new_v4());
+    let items = json!([callback(&stored, "6"), callback(&new, "10"), callback(&new, "11")]);
+    let (status, body) = post_batch(common::app_state(pool.clone()), items).await;
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert_eq!((body["created"].as_u64(), body["rejected"].as_u64()), (Some(1), Some(2)));
+
+    let results = body["results"].as_array().unwrap();
+    let outcomes: Vec<&str> = results.iter().map(|r| r["outcome"].as_str().unwrap()).collect();
+    assert_eq!(outcomes, ["validation_error", "created", "validation_error"]);
+    assert!(results[0]["error"].as_str().unwrap().contains("stored for anchor transaction"));
+    assert!(results[2]["error"].as_str().unwrap().contains("11"));
+    let stored_amount: String = sqlx::query_scalar(
+        "SELECT amount::text FROM transactions WHERE anchor_transaction_id = $1",
+    )
+    .bind(&stored)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(stored_amount.parse::<f64>().unwrap(), 5.0);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_batch_adds_each_account_once_to_stats() {
+    let pool = common::setup_pool().await;
+    let before: Option<i64> = sqlx::query_scalar(
+        "SELECT deposit_count FROM account_stats WHERE stellar_account = $1 AND asset_code = 'USD'",
+    )
+    .bind(ACCOUNT)
+    .fetch_optional(&pool)
+    .await
+    .unwrap();
+
+    let items: Vec<Value> = (0..3)
+        .map(|_| callback(&format!("anchor-batch-{}", uuid::Uuid::new_v4()), "1"))
+        .collect();
+    let (status, body) = post_batch(common::app_state(pool.clone()), json!(items)).await;
+    assert_eq!((status, body["created"].as_u64()), (StatusCode::MULTI_STATUS, Some(3)));
+
+    let after: i64 = sqlx::query_scalar(
+        "SELECT deposit_count FROM account_stats WHERE stellar_account = $1 AND asset_code = 'USD'",
+    )
+    .bind(ACCOUNT)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(after, before.unwrap_or(0) + 3);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_batch_rejects_a_body_that_is_not_an_array() {
+    let pool = common::setup_pool().await;
+    let (status, _) = post_batch(common::app_state(pool), callback("anchor-batch-single", "1")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,187 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use synapse_core::create_app;
+use synapse_core::db::queries;
+use tower::ServiceExt;
+
+fn payload(anchor_id: &str, status: &str, amount: &str) -> Value {
+    json!({
+        "id": anchor_id,
+        "amount_in": amount,
+        "stellar_account": "GCAIJAF37NNZGSH6PO442CPK5CEHJNOXAYNHLDJBTQJKF7NIQ6CZ7DQW",
+        "asset_code": "USD",
+        "callback_type": "deposit",
+        "status": status
+    })
+}
+
+async fn post_callback(pool: &PgPool, body: &Value) -> (StatusCode, Value) {
+    let response = create_app(common::app_state(pool.clone()))
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/callback")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+/// `(rows, status, callback_status)` of the transactions stored for the id
+async fn stored(pool: &PgPool, anchor_id: &str) -> (i64, String, Option<String>) {
+    sqlx::query_as(
+        "SELECT COUNT(*) OVER (), status, callback_status FROM transactions \
+         WHERE anchor_transaction_id = $1 ORDER BY created_at DESC LIMIT 1",
+    )
+    .bind(anchor_id)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+fn anchor_id() -> String {
+    format!("anchor-lifecycle-{}", uuid::Uuid::new_v4())
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_lifecycle_updates_one_transaction() {
+    let pool = common::setup_pool().await;
+    let id = anchor_id();
+
+    let (status, created) = post_callback(&pool, &payload(&id, "pending_anchor", "100.50")).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(created["outcome"], "created");
+
+    let (status, body) = post_callback(&pool, &payload(&id, "pending_stellar", "100.50")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["outcome"], "updated");
+    assert_eq!(body["status"], "processing");
+    assert_eq!(body["transaction_id"], created["transaction_id"]);
+    // Every answer names the correlation id to grep the logs for
+    assert!(created["correlation_id"].is_string());
+    assert_eq!(body["correlation_id"], created["correlation_id"]);
+
+    // The amount may be spelled differently as long as it is the same
+    let (status, body) = post_callback(&pool, &payload(&id, "completed", "100.5")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["outcome"], "updated");
+    assert_eq!(body["status"], "completed");
+
+    // A replay of the last callback is a duplicate
+    let (status, _) = post_callback(&pool, &payload(&id, "completed", "100.50")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert_eq!(
+        stored(&pool, &id).await,
+        (1, "completed".to_string(), Some("completed".to_string()))
+    );
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_error_callback_fails_transaction() {
+    let pool = common::setup_pool().await;
+    let id = anchor_id();
+
+    post_callback(&pool, &payload(&id, "pending_anchor", "10")).await;
+    let (status, body) = post_callback(&pool, &payload(&id, "error", "10")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "failed");
+    assert_eq!(stored(&pool, &id).await, (1, "failed".to_string(), Some("error".to_string())));
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_status_regression_is_rejected() {
+    let pool = common::setup_pool().await;
+    let id = anchor_id();
+
+    post_callback(&pool, &payload(&id, "pending_anchor", "10")).await;
+    post_callback(&pool, &payload(&id, "completed", "10")).await;
+    let (status, body) = post_callback(&pool, &payload(&id, "pending_anchor", "10")).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(body["error"].as_str().unwrap().contains("from completed to pending"), "{}", body);
+
+    assert_eq!(
+        stored(&pool, &id).await,
+        (1, "completed".to_string(), Some("completed".to_string()))
+    );
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_amount_change_is_rejected() {
+    let pool = common::setup_pool().await;
+    let id = anchor_id();
+
+    post_callback(&pool, &payload(&id, "pending_anchor", "10")).await;
+    let (status, body) = post_callback(&pool, &payload(&id, "completed", "12")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("amount"), "{}", body);
+
+    assert_eq!(
+        stored(&pool, &id).await,
+        (1, "pending".to_string(), Some("pending_anchor".to_string()))
+    );
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_replay_with_another_deposit_is_rejected() {
+    let pool = common::setup_pool().await;
+    let id = anchor_id();
+
+    post_callback(&pool, &payload(&id, "pending_anchor", "10")).await;
+    let (status, body) = post_callback(&pool, &payload(&id, "pending_anchor", "12")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("amount"), "{}", body);
+
+    let mut other_asset = payload(&id, "pending_anchor", "10");
+    other_asset["asset_code"] = json!("EUR");
+    let (status, body) = post_callback(&pool, &other_asset).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("asset_code"), "{}", body);
+
+    // The same deposit is still a replay
+    let (status, _) = post_callback(&pool, &payload(&id, "pending_anchor", "10.0")).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_callback_names_the_issuer_of_a_shared_code() {
+    let pool = common::setup_pool().await;
+    let code = format!("T{}", &uuid::Uuid::new_v4().simple().to_string()[..6]).to_uppercase();
+    let issuer = "GBBD47IF6LWK7P7MDEVSCWR7DPUWV3NY3DTQEVFL4NAT4AQH3ZLLFLA5";
+    let other = "GA5ZSEJYB37JRC5AVCIA5MOP4RHTM335X2KGX3IHOJAPP5RE34K4KZVN";
+    for registered in [issuer, other] {
+        queries::insert_asset(&pool, &code, Some(registered), &json!({}), true).await.unwrap();
+    }
+
+    // Two issuers share the code, so a callback has to say which one it means
+    let mut body = payload(&anchor_id(), "pending_anchor", "10");
+    body["asset_code"] = json!(code);
+    let (status, rejected) = post_callback(&pool, &body).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(rejected["error"].as_str().unwrap().contains("asset_issuer"));
+
+    body["asset_issuer"] = json!(issuer);
+    let (status, created) = post_callback(&pool, &body).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let id = created["transaction_id"].as_str().unwrap().parse().unwrap();
+    let tx = queries::get_transaction(&pool, id).await.unwrap();
+    assert_eq!(tx.asset_issuer.as_deref(), Some(issuer));
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use sqlx::PgPool;
+use std::time::Duration;
+use synapse_core::config::{IngestionConfig, IngestionMode};
+use synapse_core::db::models::{Quote, Transaction};
+use synapse_core::db::queries;
+use synapse_core::error::AppError;
+use synapse_core::services::api_tokens::{CreateTokenRequest, TokenScope};
+use synapse_core::services::{ApiTokenService, IngestionService, QuoteService};
+use synapse_core::utils::clock::{Clock, TestClock};
+use uuid::Uuid;
+
+const ISSUER: &str = "GBBD47IF6LWK7P7MDEVSCWR7DPUWV3NY3DTQEVFL4NAT4AQH3ZLLFLA5";
+const SELL_ASSET: &str = "stellar:USD:GBBD47IF6LWK7P7MDEVSCWR7DPUWV3NY3DTQEVFL4NAT4AQH3ZLLFLA5";
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_quote_expires_when_clock_passes_expiry() {
+    let pool = common::setup_pool().await;
+    let clock = TestClock::new();
+    let quotes = QuoteService::new(pool.clone(), None, "0.01".parse().unwrap())
+        .with_clock(clock.shared());
+
+    let quote = queries::insert_quote(
+        &pool,
+        &Quote {
+            id: format!("quote-{}", Uuid::new_v4()),
+            sell_asset: SELL_ASSET.to_string(),
+            buy_asset: "iso4217:USD".to_string(),
+            sell_amount: "100".parse().unwrap(),
+            buy_amount: "98".parse().unwrap(),
+            rate: "1".parse().unwrap(),
+            total_price: "1.02".parse().unwrap(),
+            fee_total: "2".parse().unwrap(),
+            fee_asset: "iso4217:USD".to_string(),
+            expires_at: clock.now() + chrono::Duration::seconds(60),
+            transaction_id: None,
+            created_at: clock.now(),
+        },
+    )
+    .await
+    .unwrap();
+    let amount = "100".parse().unwrap();
+
+    clock.advance(Duration::from_secs(59));
+    quotes.verify(&quote.id, "USD", Some(ISSUER), &amount).await.unwrap();
+
+    clock.advance(Duration::from_secs(1));
+    match quotes.verify(&quote.id, "USD", Some(ISSUER), &amount).await {
+        Err(AppError::Validation(message)) => assert!(message.starts_with("quote_expired:")),
+        other => panic!("expected quote_expired, got {:?}", other.map(|q| q.id)),
+    }
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_api_token_stops_authenticating_at_expiry() {
+    let pool = common::setup_pool().await;
+    let clock = TestClock::new();
+    let tokens = ApiTokenService::new(pool).with_clock(clock.shared());
+
+    let (_, secret) = tokens
+        .create(
+            CreateTokenRequest {
+                name: "clock-test".to_string(),
+                scope: TokenScope {
+                    routes: vec!["GET /transactions".to_string()],
+                    asset_code: None,
+                    from: None,
+                    to: None,
+                },
+                expires_at: clock.now() + chrono::Duration::hours(1),
+            },
+            "admin",
+        )
+        .await
+        .unwrap();
+    assert!(tokens.authenticate(&secret).await.unwrap().is_some());
+
+    clock.advance(Duration::from_secs(3599));
+    assert!(tokens.authenticate(&secret).await.unwrap().is_some());
+    clock.advance(Duration::from_secs(1));
+    assert!(tokens.authenticate(&secret).await.unwrap().is_none());
+}
+
+/// Wait for database round trips started by a woken task; time itself only
+/// moves through the test clock
+async fn stored(pool: &PgPool, id: Uuid) -> bool {
+    for _ in 0..50 {
+        if queries::get_transaction(pool, id).await.is_ok() {
+            return true;
+        }
+        tokio::task::yield_now().await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    false
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_drainer_runs_on_clock_ticks() {
+    let pool = common::setup_pool().await;
+    let clock = TestClock::new();
+    let ingestion = IngestionService::new(
+        pool.clone(),
+        IngestionConfig {
+            mode: IngestionMode::Async,
+            ..IngestionConfig::default()
+        },
+    )
+    .with_clock(clock.shared());
+    // An hour between drains: without the test clock this test would take that long
+    ingestion.start(Duration::from_secs(3600));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let tx = Transaction::new(
+        format!("G{}", "C".repeat(55)),
+        "10".parse().unwrap(),
+        "USD".to_string(),
+        Some(format!("anchor-clock-{}", Uuid::new_v4())),
+        Some("deposit".to_string()),
+        Some("pending".to_string()),
+    );
+    let id = ingestion.enqueue(&tx, None).await.unwrap();
+    assert!(!stored(&pool, id).await, "drained before the next tick");
+
+    clock.advance(Duration::from_secs(3600));
+    assert!(stored(&pool, id).await, "not drained after the tick");
+}
@@ -0,0 +1,244 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use chrono::Utc;
+use serde_json::{json, Value};
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use std::time::Duration;
+use synapse_core::config::WebhookDispatchConfig;
+use synapse_core::db::models::{Transaction, WebhookDelivery};
+use synapse_core::db::queries;
+use synapse_core::services::webhook_dispatcher::send_delivery;
+use synapse_core::services::WebhookDispatcher;
+use synapse_core::utils::correlation::CORRELATION_HEADER;
+use synapse_core::{create_app, AppState};
+use tower::ServiceExt;
+use uuid::Uuid;
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const ISSUER: &str = "GBBD47IF6LWK7P7MDEVSCWR7DPUWV3NY3DTQEVFL4NAT4AQH3ZLLFLA5";
+const FIXTURE_ID: &str = "82fhs729f63dh0v4";
+
+fn header<'a>(request: &'a wiremock::Request, name: &str) -> Option<&'a str> {
+    request
+        .headers
+        .iter()
+        .find(|(header, _)| header.as_str().eq_ignore_ascii_case(name))
+        .map(|(_, values)| values.last().as_str())
+}
+
+async fn subscriber() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+    server
+}
+
+fn delivery(correlation_id: Option<&str>) -> WebhookDelivery {
+    WebhookDelivery {
+        id: Uuid::new_v4(),
+        subscription_id: Uuid::new_v4(),
+        event_type: "transaction.created".to_string(),
+        payload: json!({"transaction_id": Uuid::new_v4()}),
+        status: "delivering".to_string(),
+        attempts: 0,
+        next_attempt_at: Utc::now(),
+        locked_until: None,
+        last_error: None,
+        created_at: Utc::now(),
+        delivered_at: None,
+        correlation_id: correlation_id.map(str::to_string),
+    }
+}
+
+#[tokio::test]
+async fn test_delivery_carries_correlation_header() {
+    let server = subscriber().await;
+    let http = reqwest::Client::new();
+
+    send_delivery(&http, &server.uri(), &delivery(Some("req-abc")), None, Duration::from_secs(5))
+        .await
+        .unwrap();
+    send_delivery(&http, &server.uri(), &delivery(None), None, Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    let received = server.received_requests().await.unwrap();
+    assert_eq!(header(&received[0], CORRELATION_HEADER), Some("req-abc"));
+    assert_eq!(header(&received[1], CORRELATION_HEADER), None);
+}
+
+async fn setup_pool() -> PgPool {
+    let pool = common::setup_pool().await;
+    sqlx::query(
+        "INSERT INTO assets (asset_code, asset_issuer) VALUES ('USDC', $1) \
+         ON CONFLICT (asset_code, asset_issuer) DO UPDATE SET enabled = true",
+    )
+    .bind(ISSUER)
+    .execute(&pool)
+    .await
+    .unwrap();
+    pool
+}
+
+
+async fn call(
+    state: AppState,
+    request: Request<Body>,
+) -> (StatusCode, Option<String>, Value) {
+    let response = create_app(state).oneshot(request).await.unwrap();
+    let status = response.status();
+    let correlation = response
+        .headers()
+        .get(CORRELATION_HEADER)
+        .map(|v| v.to_str().unwrap().to_string());
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, correlation, serde_json::from_slice(&bytes).unwrap())
+}
+
+/// Run the dispatcher until the subscriber has seen a delivery for `correlation_id`
+async fn delivered_with(pool: &PgPool, server: &MockServer, correlation_id: &str) -> wiremock::Request {
+    let dispatcher = WebhookDispatcher::new(pool.clone(), WebhookDispatchConfig::default());
+    for _ in 0..50 {
+        dispatcher.poll_once().await.unwrap();
+        let received = server.received_requests().await.unwrap();
+        if let Some(request) = received
+            .into_iter()
+            .find(|request| header(request, CORRELATION_HEADER) == Some(correlation_id))
+        {
+            return request;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("no webhook delivered with correlation id {}", correlation_id);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_request_id_follows_callback_into_webhooks_and_timeline() {
+    let pool = setup_pool().await;
+    let server = subscriber().await;
+    queries::insert_webhook_subscription(&pool, &server.uri(), &["transaction.created".to_string()], None, None)
+        .await
+        .unwrap();
+
+    let request_id = format!("req-{}", Uuid::new_v4());
+    let body = json!({
+        "id": format!("anchor-corr-{}", Uuid::new_v4()),
+        "amount_in": "100.50",
+        "stellar_account": "GCAIJAF37NNZGSH6PO442CPK5CEHJNOXAYNHLDJBTQJKF7NIQ6CZ7DQW",
+        "asset_code": "USD",
+        "callback_type": "deposit",
+        "status": "completed"
+    });
+    let (status, correlation, created) = call(
+        common::app_state(pool.clone()),
+        Request::builder()
+            .method("POST")
+            .uri("/callback")
+            .header("content-type", "application/json")
+            .header("x-request-id", &request_id)
+            .body(Body::from(body.to_string()))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(correlation.as_deref(), Some(request_id.as_str()));
+
+    let delivered = delivered_with(&pool, &server, &request_id).await;
+    let payload: Value = serde_json::from_slice(&delivered.body).unwrap();
+    assert_eq!(payload["correlation_id"], request_id.as_str());
+    assert_eq!(payload["transaction_id"], created["transaction_id"]);
+
+    let uri = format!("/transactions/{}/events", created["transaction_id"].as_str().unwrap());
+    let (status, _, timeline) = call(
+        common::app_state(pool),
+        Request::builder().uri(uri).body(Body::empty()).unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(timeline["correlation_id"], request_id.as_str());
+    let sources: Vec<&str> = timeline["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["source"].as_str().unwrap())
+        .collect();
+    assert!(sources.contains(&"audit"));
+    assert!(sources.contains(&"webhook"));
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_sep31_fixture_without_request_id_correlates_by_anchor_id() {
+    let pool = setup_pool().await;
+    let server = subscriber().await;
+    queries::insert_webhook_subscription(&pool, &server.uri(), &["transaction.created".to_string()], None, None)
+        .await
+        .unwrap();
+
+    let sep31_id = format!("sep31-corr-{}", Uuid::new_v4());
+    let path = format!(
+        "{}/tests/fixtures/sep31/01_pending_sender.json",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let body = std::fs::read_to_string(path).unwrap().replace(FIXTURE_ID, &sep31_id);
+    let (status, _, created) = call(
+        common::app_state(pool.clone()),
+        Request::builder()
+            .method("POST")
+            .uri("/callback/sep31")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let stored: Option<String> =
+        sqlx::query_scalar("SELECT correlation_id FROM transactions WHERE id = $1::uuid")
+            .bind(created["transaction_id"].as_str().unwrap())
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(stored.as_deref(), Some(sep31_id.as_str()));
+
+    delivered_with(&pool, &server, &sep31_id).await;
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_events_route_serves_stored_correlation_id() {
+    let pool = setup_pool().await;
+    let correlation_id = format!("corr-{}", Uuid::new_v4());
+    let mut tx = Transaction::new(
+        "GCAIJAF37NNZGSH6PO442CPK5CEHJNOXAYNHLDJBTQJKF7NIQ6CZ7DQW".to_string(),
+        BigDecimal::from(25),
+        "USD".to_string(),
+        None,
+        None,
+        None,
+    );
+    tx.correlation_id = Some(correlation_id.clone());
+    let tx = queries::insert_transaction(&pool, &tx).await.unwrap();
+
+    let (status, _, timeline) = call(
+        common::app_state(pool),
+        Request::builder()
+            .uri(format!("/transactions/{}/events", tx.id))
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(timeline["transaction_id"], tx.id.to_string());
+    assert_eq!(timeline["correlation_id"], correlation_id.as_str());
+    assert!(timeline["events"].is_array());
+}
@@ -0,0 +1,141 @@
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    middleware::from_fn,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use synapse_core::deprecation::{self, Declared, Deprecation, ElementKind};
+use tower::ServiceExt;
+
+static OLD_ROUTE: Deprecation = Deprecation {
+    element: "GET /old",
+    kind: ElementKind::Route,
+    replacement: Some("GET /new"),
+    deprecated: "2026-01-01",
+    sunset: Some("2026-12-31"),
+};
+
+static OLD_FIELD: Deprecation = Deprecation {
+    element: "body.old_name",
+    kind: ElementKind::Field,
+    replacement: Some("body.name"),
+    deprecated: "2025-06-01",
+    sunset: None,
+};
+
+struct OldField;
+
+impl Declared for OldField {
+    const DEPRECATION: &'static Deprecation = &OLD_FIELD;
+}
+
+#[derive(Deserialize)]
+struct Rename {
+    name: Option<String>,
+    #[serde(default, deserialize_with = "deprecation::deserialize::<_, _, OldField>")]
+    old_name: Option<String>,
+}
+
+fn app() -> Router {
+    Router::new()
+        .route(
+            "/old",
+            get(|| async { Json(json!({ "ok": true })) }).layer(deprecation::route(&OLD_ROUTE)),
+        )
+        .route("/new", get(|| async { Json(json!({ "ok": true })) }))
+        .route("/list", get(|| async { Json(json!([1, 2])) }).layer(deprecation::route(&OLD_ROUTE)))
+        .route(
+            "/rename",
+            post(|Json(body): Json<Rename>| async move {
+                Json(json!({ "name": body.name.or(body.old_name) }))
+            }),
+        )
+        .route(deprecation::DEPRECATIONS_PATH, get(deprecation::list_deprecations))
+        .layer(from_fn(deprecation::signal))
+}
+
+async fn send(request: Request<Body>) -> (StatusCode, axum::http::HeaderMap, Value) {
+    let response = app().oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, headers, serde_json::from_slice(&bytes).unwrap())
+}
+
+fn get_request(uri: &str) -> Request<Body> {
+    Request::builder().uri(uri).body(Body::empty()).unwrap()
+}
+
+fn rename_request(body: Value) -> Request<Body> {
+    Request::builder()
+        .method("POST")
+        .uri("/rename")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_deprecated_route_gets_headers_and_body_entry() {
+    let (status, headers, body) = send(get_request("/old")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["deprecation"], "@1767225600");
+    assert_eq!(headers["sunset"], "Thu, 31 Dec 2026 00:00:00 GMT");
+    assert_eq!(
+        headers["link"],
+        "</.well-known/synapse/deprecations>; rel=\"deprecation\""
+    );
+    assert_eq!(body["ok"], true);
+    assert_eq!(body["deprecations"][0]["element"], "GET /old");
+    assert_eq!(body["deprecations"][0]["kind"], "route");
+    assert_eq!(body["deprecations"][0]["replacement"], "GET /new");
+}
+
+#[tokio::test]
+async fn test_other_routes_are_untouched() {
+    let (status, headers, body) = send(get_request("/new")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get("deprecation").is_none());
+    assert!(headers.get("sunset").is_none());
+    assert!(headers.get("link").is_none());
+    assert_eq!(body, json!({ "ok": true }));
+}
+
+#[tokio::test]
+async fn test_non_object_bodies_get_headers_only() {
+    let (_, headers, body) = send(get_request("/list")).await;
+    assert!(headers.get("deprecation").is_some());
+    assert_eq!(body, json!([1, 2]));
+}
+
+#[tokio::test]
+async fn test_deprecated_field_is_signaled_only_when_used() {
+    let (_, headers, body) = send(rename_request(json!({ "old_name": "a" }))).await;
+    assert_eq!(headers["deprecation"], "@1748736000");
+    assert!(headers.get("sunset").is_none());
+    assert_eq!(body["name"], "a");
+    assert_eq!(body["deprecations"][0]["element"], "body.old_name");
+    assert_eq!(body["deprecations"][0]["replacement"], "body.name");
+
+    let (_, headers, body) = send(rename_request(json!({ "name": "a" }))).await;
+    assert!(headers.get("deprecation").is_none());
+    assert!(body.get("deprecations").is_none());
+}
+
+#[tokio::test]
+async fn test_well_known_lists_the_catalog() {
+    let (status, _, body) = send(get_request(deprecation::DEPRECATIONS_PATH)).await;
+    assert_eq!(status, StatusCode::OK);
+    let elements: Vec<&str> = body["deprecations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d["element"].as_str().unwrap())
+        .collect();
+    assert_eq!(elements.len(), deprecation::catalog::ALL.len());
+    assert!(elements.contains(&"POST /callback/transaction"));
+    assert!(elements.contains(&"callback.v1.asset_code"));
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use synapse_core::config::DuplicateCallbackResponse;
+use synapse_core::{create_app, AppState};
+use tower::ServiceExt;
+
+fn app_state(pool: PgPool, mode: DuplicateCallbackResponse) -> AppState {
+    AppState {
+        duplicate_callback_response: mode,
+        ..common::app_state(pool)
+    }
+}
+
+fn payload(anchor_id: &str) -> Value {
+    json!({
+        "id": anchor_id,
+        "amount_in": "100.50",
+        "stellar_account": "GCAIJAF37NNZGSH6PO442CPK5CEHJNOXAYNHLDJBTQJKF7NIQ6CZ7DQW",
+        "asset_code": "USD",
+        "callback_type": "deposit",
+        "status": "completed"
+    })
+}
+
+async fn post_callback(state: AppState, body: &Value) -> (StatusCode, Option<String>, Value) {
+    let response = create_app(state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/callback")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let duplicate = response
+        .headers()
+        .get("x-duplicate")
+        .map(|v| v.to_str().unwrap().to_string());
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, duplicate, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_replayed_callback_echo_mode() {
+    let pool = common::setup_pool().await;
+    let body = payload(&format!("anchor-dup-echo-{}", uuid::Uuid::new_v4()));
+
+    let (status, duplicate, first) =
+        post_callback(app_state(pool.clone(), DuplicateCallbackResponse::Echo), &body).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(duplicate.is_none());
+
+    let (status, duplicate, replay) =
+        post_callback(app_state(pool, DuplicateCallbackResponse::Echo), &body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(duplicate.as_deref(), Some("true"));
+    assert_eq!(replay["transaction_id"], first["transaction_id"]);
+    assert_eq!(replay["status"], first["status"]);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_replayed_callback_ack_mode() {
+    let pool = common::setup_pool().await;
+    let body = payload(&format!("anchor-dup-ack-{}", uuid::Uuid::new_v4()));
+
+    let (status, _, _) =
+        post_callback(app_state(pool.clone(), DuplicateCallbackResponse::Ack), &body).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, duplicate, replay) =
+        post_callback(app_state(pool, DuplicateCallbackResponse::Ack), &body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(duplicate.as_deref(), Some("true"));
+    assert_eq!(replay, json!({ "duplicate": true }));
+}
+
+async fn rows_for_anchor_id(pool: &PgPool, anchor_id: &str) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE anchor_transaction_id = $1")
+        .bind(anchor_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_replayed_callback_stores_one_row() {
+    let pool = common::setup_pool().await;
+    let anchor_id = format!("anchor-dup-seq-{}", uuid::Uuid::new_v4());
+    let body = payload(&anchor_id);
+    let state = app_state(pool.clone(), DuplicateCallbackResponse::Echo);
+
+    let (status, _, first) = post_callback(state.clone(), &body).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, _, replay) = post_callback(state, &body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(replay["transaction_id"], first["transaction_id"]);
+
+    assert_eq!(rows_for_anchor_id(&pool, &anchor_id).await, 1);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_concurrent_replays_share_one_transaction() {
+    let pool = common::setup_pool().await;
+    let anchor_id = format!("anchor-dup-race-{}", uuid::Uuid::new_v4());
+    let body = payload(&anchor_id);
+    let state = app_state(pool.clone(), DuplicateCallbackResponse::Echo);
+
+    // Fired at once, so several can pass the duplicate checks before the
+    // first commits
+    let responses =
+        futures::future::join_all((0..5).map(|_| post_callback(state.clone(), &body))).await;
+
+    let created = responses
+        .iter()
+        .filter(|(status, _, _)| *status == StatusCode::CREATED)
+        .count();
+    assert_eq!(created, 1);
+    for (status, duplicate, _) in &responses {
+        if *status == StatusCode::CREATED {
+            continue;
+        }
+        assert_eq!(*status, StatusCode::OK);
+        assert_eq!(duplicate.as_deref(), Some("true"));
+    }
+    let transaction_id = &responses[0].2["transaction_id"];
+    assert!(responses.iter().all(|(_, _, body)| &body["transaction_id"] == transaction_id));
+
+    assert_eq!(rows_for_anchor_id(&pool, &anchor_id).await, 1);
+}
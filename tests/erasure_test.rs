@@ -0,0 +1,226 @@
+mod common;
+
+use serde_json::Value;
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::time::Duration;
+use synapse_core::db::models::{ErasureJob, Transaction};
+use synapse_core::db::{queries, uow};
+use synapse_core::error::AppError;
+use synapse_core::services::erasure::{
+    self, parse_erasure_policy, ErasureCategory, ErasurePolicy, NOT_ACCOUNT_DATA,
+};
+use synapse_core::services::ErasureService;
+use synapse_core::utils::diff::REDACTED;
+use uuid::Uuid;
+
+/// An account no other test uses
+fn account() -> String {
+    let id = Uuid::new_v4().simple().to_string().to_uppercase();
+    format!("GERASE{:A<50}", id)
+}
+
+/// A transaction with a raw callback and account stats
+async fn seed_account(pool: &PgPool, account: &str) -> Transaction {
+    let tx = Transaction::new(
+        account.to_string(),
+        BigDecimal::from_str("100").unwrap(),
+        "USDC".to_string(),
+        Some(format!("anchor-{}", Uuid::new_v4())),
+        Some("deposit".to_string()),
+        None,
+    );
+    let tx = queries::insert_transaction(pool, &tx).await.unwrap();
+    let body = format!(r#"{{"stellar_account": "{}"}}"#, account);
+    queries::insert_raw_callback(pool, tx.id, "v1", &body).await.unwrap();
+
+    let account = account.to_string();
+    uow::run(pool, |uow| Box::pin(async move {
+        queries::recompute_account_stats(uow.conn(), &account).await
+    }))
+    .await
+    .unwrap();
+    tx
+}
+
+async fn wait_for(service: &ErasureService, id: Uuid) -> ErasureJob {
+    for _ in 0..100 {
+        let job = service.get(id).await.unwrap();
+        if job.status == "completed" || job.status == "failed" {
+            return job;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("erasure {} did not finish", id);
+}
+
+fn rows(job: &ErasureJob, category: ErasureCategory) -> u64 {
+    let report = job.report.as_ref().unwrap().as_array().unwrap();
+    let entry = report
+        .iter()
+        .find(|entry| entry["category"] == category_name(category))
+        .unwrap_or_else(|| panic!("{:?} missing from the report", category));
+    entry["rows"].as_u64().unwrap()
+}
+
+fn category_name(category: ErasureCategory) -> Value {
+    serde_json::to_value(category).unwrap()
+}
+
+async fn raw_callback_count(pool: &PgPool, transaction_id: Uuid) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM raw_callbacks WHERE transaction_id = $1")
+        .bind(transaction_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_dry_run_reports_every_category_and_changes_nothing() {
+    let pool = common::setup_pool().await;
+    let account = account();
+    let tx = seed_account(&pool, &account).await;
+    let service = ErasureService::new(pool.clone(), ErasurePolicy::default());
+
+    let job = service.request(&account, true, true, "test").await.unwrap();
+    assert!(!job.block_callbacks, "dry runs never block callbacks");
+    let job = wait_for(&service, job.id).await;
+    assert_eq!(job.status, "completed");
+
+    // Every registered category is in the report, none silently skipped
+    assert_eq!(job.report.as_ref().unwrap().as_array().unwrap().len(), ErasureCategory::ALL.len());
+    assert_eq!(rows(&job, ErasureCategory::Transactions), 1);
+    assert_eq!(rows(&job, ErasureCategory::RawCallbacks), 1);
+    assert_eq!(rows(&job, ErasureCategory::AccountStats), 1);
+
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().stellar_account, account);
+    assert_eq!(raw_callback_count(&pool, tx.id).await, 1);
+    erasure::ensure_callbacks_allowed(&pool, &account).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_execute_applies_policy_and_leaves_a_certificate() {
+    let pool = common::setup_pool().await;
+    let account = account();
+    let tx = seed_account(&pool, &account).await;
+    let bystander = account();
+    let untouched = seed_account(&pool, &bystander).await;
+    let service = ErasureService::new(pool.clone(), ErasurePolicy::default());
+
+    let job = service.request(&account, false, true, "test").await.unwrap();
+    let job = wait_for(&service, job.id).await;
+    assert_eq!(job.status, "completed");
+    assert_eq!(job.stellar_account, None, "the certificate doesn't keep the address");
+    assert_eq!(job.account_hash, erasure::account_hash(&account));
+    assert_eq!(job.requested_by, "test");
+    assert!(job.completed_at.is_some());
+
+    // Transactions are redacted, raw callbacks and stats deleted
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().stellar_account, REDACTED);
+    assert_eq!(raw_callback_count(&pool, tx.id).await, 0);
+    assert!(queries::get_account_stats(&pool, &account).await.unwrap().is_empty());
+    assert_eq!(rows(&job, ErasureCategory::Transactions), 1);
+
+    // Nothing else was touched
+    assert_eq!(queries::get_transaction(&pool, untouched.id).await.unwrap().stellar_account, bystander);
+    assert_eq!(raw_callback_count(&pool, untouched.id).await, 1);
+
+    // The certificate can be found again by the account
+    let certificates = service.list_for_account(&account).await.unwrap();
+    assert_eq!(certificates.len(), 1);
+    assert_eq!(certificates[0].id, job.id);
+
+    // Later callbacks for the account are refused
+    assert!(matches!(
+        erasure::ensure_callbacks_allowed(&pool, &account).await,
+        Err(AppError::Forbidden(_))
+    ));
+    erasure::ensure_callbacks_allowed(&pool, &bystander).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_run_interrupted_by_a_restart_is_resumed() {
+    let pool = common::setup_pool().await;
+    let account = account();
+    let tx = seed_account(&pool, &account).await;
+    let policy = ErasurePolicy::default();
+
+    // What a stop mid-run leaves behind: the job marked running, nothing erased
+    let interrupted = queries::insert_erasure_job(
+        &pool,
+        &ErasureJob {
+            id: Uuid::new_v4(),
+            account_hash: erasure::account_hash(&account),
+            stellar_account: Some(account.clone()),
+            dry_run: false,
+            block_callbacks: false,
+            status: "running".to_string(),
+            policy: policy.to_json(),
+            report: None,
+            error: None,
+            requested_by: "test".to_string(),
+            created_at: chrono::Utc::now(),
+            started_at: Some(chrono::Utc::now()),
+            completed_at: None,
+        },
+    )
+    .await
+    .unwrap();
+
+    let service = ErasureService::new(pool.clone(), policy);
+    assert!(service.resume().await.unwrap() >= 1);
+    let job = wait_for(&service, interrupted.id).await;
+    assert_eq!(job.status, "completed");
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().stellar_account, REDACTED);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_retained_categories_are_counted_but_kept() {
+    let pool = common::setup_pool().await;
+    let account = account();
+    let tx = seed_account(&pool, &account).await;
+    let policy = parse_erasure_policy("raw_callbacks=retain").unwrap();
+    let service = ErasureService::new(pool.clone(), policy);
+
+    let job = service.request(&account, false, false, "test").await.unwrap();
+    let job = wait_for(&service, job.id).await;
+    assert_eq!(job.policy["raw_callbacks"], "retain");
+    assert_eq!(rows(&job, ErasureCategory::RawCallbacks), 1);
+    assert_eq!(raw_callback_count(&pool, tx.id).await, 1);
+    erasure::ensure_callbacks_allowed(&pool, &account).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_every_table_with_account_columns_is_registered() {
+    let pool = common::setup_pool().await;
+    let tables: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT c.table_name::text
+        FROM information_schema.columns c
+        JOIN information_schema.tables t
+            ON t.table_schema = c.table_schema AND t.table_name = c.table_name
+        WHERE c.table_schema = 'public'
+          AND c.column_name IN ('stellar_account', 'transaction_id')
+          AND t.table_type = 'BASE TABLE'
+          AND c.table_name NOT LIKE 'transactions\_%'
+        "#,
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+
+    for table in tables {
+        assert!(
+            ErasureCategory::ALL.iter().any(|c| c.table() == table)
+                || NOT_ACCOUNT_DATA.iter().any(|(name, _)| *name == table),
+            "{} links to accounts but is not in the erasure registry",
+            table
+        );
+    }
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    middleware::from_fn_with_state,
+    routing::get,
+    Router,
+};
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::time::Duration;
+use synapse_core::handlers::events;
+use synapse_core::middleware::auth::{admin_auth, AdminAuthenticator};
+use synapse_core::{create_app, AppState};
+use tower::ServiceExt;
+
+const ADMIN_KEY: &str = "admin-secret-key";
+
+/// The event stream route as main.rs wires it
+fn app(state: AppState) -> Router {
+    Router::new()
+        .route("/admin/events/stream", get(events::event_stream))
+        .layer(from_fn_with_state(AdminAuthenticator::default(), admin_auth))
+        .with_state(state)
+}
+
+async fn connect(state: AppState, uri: &str, last_event_id: Option<i64>) -> axum::response::Response {
+    let mut request = Request::builder()
+        .uri(uri)
+        .header("authorization", format!("Bearer {}", ADMIN_KEY));
+    if let Some(seq) = last_event_id {
+        request = request.header("last-event-id", seq.to_string());
+    }
+    app(state).oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+}
+
+/// Read the stream until `needle` shows up, or fail after a few seconds
+async fn read_until(
+    body: &mut (impl futures::Stream<Item = Result<bytes::Bytes, axum::Error>> + Unpin),
+    seen: &mut String,
+    needle: &str,
+) {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while !seen.contains(needle) {
+            let chunk = body.next().await.expect("stream ended").unwrap();
+            seen.push_str(&String::from_utf8_lossy(&chunk));
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("{needle:?} not streamed; got {seen:?}"));
+}
+
+async fn post_callback(state: AppState, anchor_id: &str) -> String {
+    let body = json!({
+        "id": anchor_id,
+        "amount_in": "25.00",
+        "stellar_account": "GDQ2AIQSKJR3K2XMUGV6LGWO2ZRYMYFPSG6MLWKPC7OW2VITKON4W6FO",
+        "asset_code": "USD",
+        "callback_type": "deposit",
+        "status": "completed"
+    });
+    let response = create_app(state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/callback")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let created: Value = serde_json::from_slice(&bytes).unwrap();
+    created["transaction_id"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_callback_event_is_streamed() {
+    let pool = common::setup_pool().await;
+    let state = common::app_state(pool);
+    state.events.start();
+    // Let the relay start listening before anything is written
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let response =
+        connect(state.clone(), "/admin/events/stream?event_type=transaction.created", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    let mut body = response.into_body().into_data_stream();
+    let mut seen = String::new();
+    read_until(&mut body, &mut seen, "event: snapshot").await;
+
+    let transaction_id = post_callback(state, &format!("anchor-sse-{}", uuid::Uuid::new_v4())).await;
+    read_until(&mut body, &mut seen, &transaction_id).await;
+    assert!(seen.contains("event: transaction.created"));
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_reconnect_replays_missed_events() {
+    let pool = common::setup_pool().await;
+    let state = common::app_state(pool);
+    let before = state.events.latest_seq().await.unwrap();
+
+    // Written while the client was away
+    let transaction_id =
+        post_callback(state.clone(), &format!("anchor-sse-{}", uuid::Uuid::new_v4())).await;
+
+    let response = connect(state, "/admin/events/stream", Some(before)).await;
+    let mut body = response.into_body().into_data_stream();
+    let mut seen = String::new();
+    read_until(&mut body, &mut seen, &transaction_id).await;
+    assert!(!seen.contains("event: resync"));
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_stream_requires_admin_auth() {
+    let pool = common::setup_pool().await;
+    let response = app(common::app_state(pool))
+        .oneshot(Request::builder().uri("/admin/events/stream").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
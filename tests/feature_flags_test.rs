@@ -1,3 +1,5 @@
+mod common;
+
 use synapse_core::services::FeatureFlagService;
 
 #[cfg(test)]
@@ -11,3 +13,215 @@ mod feature_flag_tests {
         assert!(true, "Feature flag service structure is valid");
     }
 }
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_slow_full_refresh_loses_to_newer_flag_update() {
+    let flag = common::TestFlag::create("generation_test").await;
+    let (pool, name) = (flag.pool.clone(), flag.name.clone());
+
+    let service = FeatureFlagService::new(pool.clone());
+    service.refresh_cache().await.unwrap();
+    assert!(!service.is_enabled(&name).await);
+
+    // The full refresh reads before the update but applies after it
+    let slow_refresh = service.load_snapshot().await.unwrap();
+    let (updated, changes) = service.update(&name, true, "test").await.unwrap();
+    assert!(updated.enabled);
+    assert_eq!(changes.paths().collect::<Vec<_>>(), vec!["enabled"]);
+
+    assert!(!service.apply_snapshot(slow_refresh).await);
+    assert!(service.is_enabled(&name).await);
+
+    // A refresh read after the update agrees with it
+    service.refresh_cache().await.unwrap();
+    assert!(service.is_enabled(&name).await);
+
+    flag.delete().await;
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_incremental_refresh_fetches_only_changed_flags() {
+    use synapse_core::services::feature_flags::RefreshMode;
+
+    let flag = common::TestFlag::create("incremental_test").await;
+    let (pool, name) = (flag.pool.clone(), flag.name.clone());
+
+    let service = FeatureFlagService::new(pool.clone()).with_full_refresh_every(3);
+    let full = service.refresh_cache().await.unwrap();
+    assert_eq!(full.mode, RefreshMode::Full);
+    assert!(full.rows >= 1);
+
+    // Another instance flips the flag; only changed rows come back
+    sqlx::query("UPDATE feature_flags SET enabled = true, updated_at = NOW() WHERE name = $1")
+        .bind(&name)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let incremental = service.refresh_cache().await.unwrap();
+    assert_eq!(incremental.mode, RefreshMode::Incremental);
+    assert!(incremental.rows >= 1 && incremental.rows < full.rows);
+    assert!(service.is_enabled(&name).await);
+
+    // A deletion leaves a tombstone, so the next incremental refresh drops it
+    sqlx::query("DELETE FROM feature_flags WHERE name = $1")
+        .bind(&name)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let deleted = service.refresh_cache().await.unwrap();
+    assert_eq!(deleted.mode, RefreshMode::Incremental);
+    assert!(deleted.applied >= 1);
+    assert!(!service.is_enabled(&name).await);
+
+    let forced = service.refresh_full().await.unwrap();
+    assert_eq!(forced.mode, RefreshMode::Full);
+    assert!(!service.is_enabled(&name).await);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_rollout_survives_refreshes_and_only_grows() {
+    use synapse_core::services::feature_flags::{EvaluationReason, FlagUpdate};
+
+    let flag = common::TestFlag::create("rollout_test").await;
+    let (pool, name) = (flag.pool.clone(), flag.name.clone());
+    let subjects: Vec<String> = (0..500).map(|i| format!("GROLLOUT{:04}", i)).collect();
+    let allowlisted = "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ";
+
+    let service = FeatureFlagService::new(pool.clone()).with_full_refresh_every(2);
+    let update = FlagUpdate {
+        rollout_percentage: Some(10),
+        allowed_accounts: Some(vec![allowlisted.to_string()]),
+        ..Default::default()
+    };
+    let (flag, changes) = service.update_with(&name, &update, "test").await.unwrap();
+    assert_eq!(flag.rollout_percentage, 10);
+    assert_eq!(changes.paths().count(), 2);
+
+    let mut at_ten = Vec::new();
+    for subject in &subjects {
+        at_ten.push(service.is_enabled_for(&name, subject).await);
+    }
+    assert!(!service.is_enabled(&name).await);
+    assert_eq!(
+        service.evaluate(&name, allowlisted).await.reason,
+        EvaluationReason::Allowlisted
+    );
+
+    // A full and an incremental refresh read the same rule back
+    for _ in 0..2 {
+        service.refresh_cache().await.unwrap();
+        for (subject, was) in subjects.iter().zip(&at_ten) {
+            assert_eq!(service.is_enabled_for(&name, subject).await, *was);
+        }
+    }
+
+    let update = FlagUpdate {
+        rollout_percentage: Some(50),
+        ..Default::default()
+    };
+    service.update_with(&name, &update, "test").await.unwrap();
+    let mut on = 0;
+    for (subject, was) in subjects.iter().zip(&at_ten) {
+        let is = service.is_enabled_for(&name, subject).await;
+        assert!(!was || is, "{} turned off going from 10% to 50%", subject);
+        on += is as usize;
+    }
+    assert!(on > at_ten.iter().filter(|was| **was).count());
+    assert!(service.is_enabled_for(&name, allowlisted).await);
+
+    // Out of range percentages are refused by the table as well
+    let update = FlagUpdate {
+        rollout_percentage: Some(101),
+        ..Default::default()
+    };
+    assert!(service.update_with(&name, &update, "test").await.is_err());
+
+    let missing = format!("missing_{}", uuid::Uuid::new_v4().simple());
+    let evaluation = service.evaluate(&missing, allowlisted).await;
+    assert!(!evaluation.enabled);
+    assert_eq!(evaluation.reason, EvaluationReason::UnknownFlag);
+
+    flag.delete().await;
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_targeting_rules_and_audit_trail() {
+    use synapse_core::services::feature_flags::{
+        EvaluationReason, FlagContext, FlagUpdate, TargetingRule, ATTRIBUTE_ASSET_CODE,
+    };
+
+    let flag = common::TestFlag::create("targeting_test").await;
+    let (pool, name) = (flag.pool.clone(), flag.name.clone());
+    let account = "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ";
+
+    let service = FeatureFlagService::new(pool.clone());
+    let update = FlagUpdate {
+        targeting: Some(vec![TargetingRule {
+            attribute: ATTRIBUTE_ASSET_CODE.to_string(),
+            values: vec!["USDC".to_string()],
+        }]),
+        ..Default::default()
+    };
+    let (flag, changes) = service.update_with(&name, &update, "admin:ops").await.unwrap();
+    assert_eq!(flag.targeting.0, update.targeting.clone().unwrap());
+    assert_eq!(changes.paths().count(), 1);
+
+    let usdc = FlagContext::new(account).with_asset_code("USDC");
+    let eurc = FlagContext::new(account).with_asset_code("EURC");
+    // The rule is read back by refreshes too
+    service.refresh_full().await.unwrap();
+    assert_eq!(service.evaluate(&name, &usdc).await.reason, EvaluationReason::Targeted);
+    assert!(!service.is_enabled_for(&name, &eurc).await);
+    assert!(!service.is_enabled_for(&name, account).await);
+
+    service.update(&name, true, "admin:oncall").await.unwrap();
+    // An update that changes nothing isn't recorded
+    service.update(&name, true, "admin:oncall").await.unwrap();
+
+    let history = service.history(&name, 10).await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].actor, "admin:oncall");
+    assert_eq!(history[0].before["enabled"], false);
+    assert_eq!(history[0].after["enabled"], true);
+    assert_eq!(history[1].actor, "admin:ops");
+    assert_eq!(history[1].before["targeting"], serde_json::json!([]));
+    assert_eq!(history[1].after["targeting"][0]["values"], serde_json::json!(["USDC"]));
+    assert_eq!(service.history(&name, 1).await.unwrap().len(), 1);
+
+    flag.delete().await;
+}
+
+#[tokio::test]
+#[ignore] // Requires running Postgres and Redis instances
+async fn test_update_is_pushed_to_other_instances() {
+    let redis_url =
+        std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://localhost:6379".to_string());
+    let flag = common::TestFlag::create("invalidation_test").await;
+    let (pool, name) = (flag.pool.clone(), flag.name.clone());
+
+    // Two instances on a channel of their own, neither refreshing on a schedule
+    let channel = format!("test:flags:{}", uuid::Uuid::new_v4().simple());
+    let instance = || {
+        FeatureFlagService::new(pool.clone())
+            .with_invalidation(redis::Client::open(redis_url.as_str()).unwrap(), channel.as_str())
+    };
+    let (writer, reader) = (instance(), instance());
+    reader.refresh_cache().await.unwrap();
+    reader.start_invalidation_listener();
+    assert!(!reader.is_enabled(&name).await);
+    // Let the listener subscribe before publishing
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    writer.update(&name, true, "test").await.unwrap();
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+    while !reader.is_enabled(&name).await {
+        assert!(tokio::time::Instant::now() < deadline, "update never reached the reader");
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+
+    flag.delete().await;
+}
@@ -1,44 +0,0 @@
-<<<<<<< HEAD
-use axum::{
-    Router,
-    body::Body,
-    http::{Request, StatusCode},
-};
-use tower::ServiceExt;
-
-||||||| 2822865
-use axum::{
-    body::Body,
-    http::{Request, StatusCode},
-    Router,
-};
-use tower::ServiceExt;
-
-=======
->>>>>>> refs/remotes/origin/feature/issue-18-circuit-breaker
-#[cfg(test)]
-mod idempotency_tests {
-    // Note: These tests require a running Redis instance
-    // Run with: docker-compose up -d redis
-
-    #[tokio::test]
-    #[ignore] // Ignore by default since it requires Redis
-    async fn test_idempotency_new_request() {
-        // This test would require setting up the full app with Redis
-        // For now, it serves as a template for integration testing
-
-        // TODO: Implement full integration test with test Redis instance
-    }
-
-    #[tokio::test]
-    #[ignore]
-    async fn test_idempotency_duplicate_request() {
-        // TODO: Test that duplicate requests return cached response
-    }
-
-    #[tokio::test]
-    #[ignore]
-    async fn test_idempotency_processing_lock() {
-        // TODO: Test that concurrent requests return 429
-    }
-}
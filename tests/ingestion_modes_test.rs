@@ -0,0 +1,171 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use synapse_core::config::{IngestionConfig, IngestionMode};
+use synapse_core::db::queries;
+use synapse_core::services::IngestionService;
+use synapse_core::{create_app, AppState};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+/// Inserts for this account are slowed down by a test trigger
+const SLOW_ACCOUNT: &str = "GAPSWFWNPCAWMHM5SIJR66UDCLSZ34OIXLAR6IMF6NLTEV2AF222RYJA";
+const FAST_ACCOUNT: &str = "GDMZCO6NOSVD2J2XSPCAHKJAGFNYN3MMKI674WNSSSBPTQRPXNUAEB46";
+
+fn app_state(pool: PgPool, ingestion: IngestionConfig) -> AppState {
+    AppState {
+        ingestion: IngestionService::new(pool.clone(), ingestion),
+        ..common::app_state(pool)
+    }
+}
+
+fn payload(stellar_account: &str) -> Value {
+    json!({
+        "id": format!("anchor-ingest-{}", Uuid::new_v4()),
+        "amount_in": "100.50",
+        "stellar_account": stellar_account,
+        "asset_code": "USD",
+        "callback_type": "deposit",
+        "status": "completed"
+    })
+}
+
+async fn send(state: &AppState, request: Request<Body>) -> (StatusCode, Option<String>, Value) {
+    let response = create_app(state.clone()).oneshot(request).await.unwrap();
+    let status = response.status();
+    let mode = response
+        .headers()
+        .get("x-ingestion-mode")
+        .map(|v| v.to_str().unwrap().to_string());
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, mode, serde_json::from_slice(&bytes).unwrap())
+}
+
+async fn post_callback(state: &AppState, body: &Value) -> (StatusCode, Option<String>, Value) {
+    let request = Request::builder()
+        .method("POST")
+        .uri("/callback")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    send(state, request).await
+}
+
+async fn get_transaction(state: &AppState, id: &str) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .uri(format!("/transactions/{}", id))
+        .body(Body::empty())
+        .unwrap();
+    let (status, _, body) = send(state, request).await;
+    (status, body)
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_async_provisional_id_is_the_stored_id() {
+    let pool = common::setup_pool().await;
+    let config = IngestionConfig {
+        mode: IngestionMode::Async,
+        ..IngestionConfig::default()
+    };
+    let state = app_state(pool.clone(), config);
+    let body = payload("GCAIJAF37NNZGSH6PO442CPK5CEHJNOXAYNHLDJBTQJKF7NIQ6CZ7DQW");
+
+    let (status, mode, accepted) = post_callback(&state, &body).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(mode.as_deref(), Some("async"));
+    assert_eq!(accepted["status"], "queued");
+    let id = accepted["transaction_id"].as_str().unwrap().to_string();
+
+    // Pollable before it is stored
+    let (status, queued) = get_transaction(&state, &id).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(queued["id"], id.as_str());
+
+    // A replay while queued is a duplicate of the same id
+    let (status, _, replay) = post_callback(&state, &body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(replay["transaction_id"], id.as_str());
+
+    assert!(state.ingestion.drain_once().await.unwrap() >= 1);
+
+    let stored = queries::get_transaction(&pool, id.parse().unwrap()).await.unwrap();
+    assert_eq!(stored.anchor_transaction_id.as_deref(), body["id"].as_str());
+    assert!(queries::get_ingestion_entry(&pool, stored.id).await.unwrap().is_none());
+    let (status, fetched) = get_transaction(&state, &id).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(fetched["id"], id.as_str());
+
+    // A replay after draining is a duplicate of the stored row
+    let (status, _, replay) = post_callback(&state, &body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(replay["transaction_id"], id.as_str());
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_auto_mode_flips_to_async_when_inserts_are_slow() {
+    let pool = common::setup_pool().await;
+    sqlx::query(&format!(
+        r#"
+        CREATE OR REPLACE FUNCTION test_slow_insert() RETURNS trigger AS $$
+        BEGIN
+            IF NEW.stellar_account = '{}' THEN
+                PERFORM pg_sleep(0.5);
+            END IF;
+            RETURN NEW;
+        END;
+        $$ LANGUAGE plpgsql
+        "#,
+        SLOW_ACCOUNT
+    ))
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("DROP TRIGGER IF EXISTS test_slow_insert ON transactions")
+        .execute(&pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "CREATE TRIGGER test_slow_insert AFTER INSERT ON transactions \
+         FOR EACH ROW EXECUTE FUNCTION test_slow_insert()",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // One slow insert after a fast one lifts the smoothed latency past 100ms
+    let config = IngestionConfig {
+        mode: IngestionMode::Auto,
+        latency_threshold: std::time::Duration::from_millis(100),
+        ..IngestionConfig::default()
+    };
+    let state = app_state(pool.clone(), config);
+
+    let (status, mode, _) = post_callback(&state, &payload(FAST_ACCOUNT)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(mode.as_deref(), Some("sync"));
+
+    // The slow insert still completes synchronously, then flips the mode
+    let (status, mode, _) = post_callback(&state, &payload(SLOW_ACCOUNT)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(mode.as_deref(), Some("sync"));
+
+    let (status, mode, accepted) = post_callback(&state, &payload(FAST_ACCOUNT)).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(mode.as_deref(), Some("async"));
+
+    state.ingestion.drain_once().await.unwrap();
+    let id: Uuid = accepted["transaction_id"].as_str().unwrap().parse().unwrap();
+    assert_eq!(queries::get_transaction(&pool, id).await.unwrap().id, id);
+
+    sqlx::query("DROP TRIGGER IF EXISTS test_slow_insert ON transactions")
+        .execute(&pool)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use std::str::FromStr;
+use synapse_core::adapters::PostgresTransactionRepository;
+use synapse_core::db::legacy_statuses;
+use synapse_core::db::models::Transaction;
+use synapse_core::db::queries;
+use synapse_core::domain::TransactionStatus;
+use synapse_core::ports::{RepositoryError, TransactionRepository};
+use uuid::Uuid;
+
+/// A transaction stored with `status` exactly as given
+async fn insert_with_status(pool: &PgPool, status: &str) -> Uuid {
+    let tx = Transaction::new(
+        format!("GLEGACY{}", Uuid::new_v4().simple()),
+        BigDecimal::from_str("10").unwrap(),
+        "USDC".to_string(),
+        None,
+        None,
+        None,
+    );
+    let tx = queries::insert_transaction(pool, &tx).await.unwrap();
+    sqlx::query("UPDATE transactions SET status = $2 WHERE id = $1")
+        .bind(tx.id)
+        .bind(status)
+        .execute(pool)
+        .await
+        .unwrap();
+    tx.id
+}
+
+async fn stored_status(pool: &PgPool, id: Uuid) -> String {
+    sqlx::query_scalar("SELECT status FROM transactions WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_legacy_statuses_are_read_mapped_then_migrated_in_batches() {
+    let pool = common::setup_pool().await;
+    let repository = PostgresTransactionRepository::new();
+    let capitalized = insert_with_status(&pool, "Pending").await;
+    let mixed = insert_with_status(&pool, "PENDING_anchor").await;
+    let unknown_status = format!("hold_{}", &Uuid::new_v4().simple().to_string()[..8]);
+    let unknown = insert_with_status(&pool, &unknown_status).await;
+
+    // Reads map legacy spellings before the migration has run
+    let tx = repository.get_by_id((&pool).into(), mixed).await.unwrap();
+    assert_eq!(tx.status, "pending");
+    assert!(matches!(
+        repository.get_by_id((&pool).into(), unknown).await,
+        Err(RepositoryError::UnknownStatus { id, .. }) if id == unknown
+    ));
+
+    let report = legacy_statuses::scan(&pool).await.unwrap();
+    assert!(report.legacy_rows() >= 2);
+    assert!(report.unknown.contains(&(unknown_status.clone(), 1)));
+
+    // A dry run changes nothing
+    legacy_statuses::migrate(&pool, 1, true, |_, _, _, _| panic!("dry run rewrote rows"))
+        .await
+        .unwrap();
+    assert_eq!(stored_status(&pool, capitalized).await, "Pending");
+
+    // One row per batch, with progress after each
+    let mut progress = Vec::new();
+    legacy_statuses::migrate(&pool, 1, false, |raw, status, done, total| {
+        progress.push((raw.to_string(), status, done, total));
+    })
+    .await
+    .unwrap();
+    let (_, status, done, total) = progress
+        .iter()
+        .rev()
+        .find(|(raw, ..)| raw == "PENDING_anchor")
+        .expect("no progress reported");
+    assert_eq!(*status, TransactionStatus::Pending);
+    assert_eq!(done, total);
+
+    assert_eq!(stored_status(&pool, capitalized).await, "pending");
+    assert_eq!(stored_status(&pool, mixed).await, "pending");
+    // Unknown statuses are left for a person to fix, not coerced
+    assert_eq!(stored_status(&pool, unknown).await, unknown_status);
+
+    let report = legacy_statuses::scan(&pool).await.unwrap();
+    assert_eq!(report.legacy_rows(), 0);
+
+    sqlx::query("DELETE FROM transactions WHERE id = $1")
+        .bind(unknown)
+        .execute(&pool)
+        .await
+        .unwrap();
+}
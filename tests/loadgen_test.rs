@@ -0,0 +1,124 @@
+use synapse_core::handlers::callback_schema::{parse_callback, CallbackSchemaVersion};
+use synapse_core::loadgen::{execute, LoadgenArgs, LoadgenConfig};
+use synapse_core::middleware::anchor_signature::{sign, SIGNATURE_HEADER};
+use synapse_core::stellar::strkey;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn header<'a>(request: &'a wiremock::Request, name: &str) -> Option<&'a str> {
+    request
+        .headers
+        .iter()
+        .find(|(header, _)| header.as_str().eq_ignore_ascii_case(name))
+        .map(|(_, values)| values.last().as_str())
+}
+
+const SEED: [u8; 32] = [7; 32];
+
+fn args(target: String) -> LoadgenArgs {
+    LoadgenArgs {
+        target,
+        rate: 500,
+        concurrency: 4,
+        requests: Some(60),
+        duration_secs: None,
+        seed_file: None,
+        duplicate_pct: 20.0,
+        malformed_pct: 10.0,
+        schema_version: "2".to_string(),
+        signing_seed: Some(strkey::encode_secret_seed(&SEED)),
+        rng_seed: Some(1234),
+        timeout_secs: 5,
+    }
+}
+
+#[tokio::test]
+async fn test_loadgen_sends_signed_payloads_and_reports() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/callback"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&server)
+        .await;
+
+    let config = LoadgenConfig::try_from(args(format!("{}/callback", server.uri()))).unwrap();
+    let report = execute(config).await.unwrap();
+
+    assert_eq!(report.sent, 60);
+    assert_eq!(report.outcomes.get("201"), Some(&60));
+    assert_eq!(report.latencies.len(), 60);
+    assert!(report.percentile(50.0).unwrap() <= report.percentile(99.0).unwrap());
+    assert!(report.throughput() > 0.0);
+
+    let kinds: u64 = report
+        .outcomes_by_kind
+        .values()
+        .flat_map(|outcomes| outcomes.values())
+        .sum();
+    assert_eq!(kinds, 60);
+    assert!(report.outcomes_by_kind.contains_key("valid"));
+
+    let key = ed25519_dalek::SigningKey::from_bytes(&SEED);
+    let received = server.received_requests().await.unwrap();
+    assert_eq!(received.len(), 60);
+    for request in &received {
+        // ed25519 signatures are deterministic, so re-signing must reproduce the header
+        let sig = header(request, SIGNATURE_HEADER).unwrap();
+        let timestamp: i64 = sig
+            .split(',')
+            .find_map(|part| part.trim().strip_prefix("t="))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(sig, sign(&key, timestamp, "127.0.0.1", &request.body));
+        assert_eq!(header(request, "x-callback-schema-version"), Some("2"));
+    }
+
+    let parsed = received
+        .iter()
+        .filter(|request| parse_callback(CallbackSchemaVersion::V2, &request.body).is_ok())
+        .count();
+    assert!(parsed >= 50, "only {} bodies matched the V2 schema", parsed);
+}
+
+#[tokio::test]
+async fn test_loadgen_breaks_down_errors_by_status() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+
+    let mut args = args(format!("{}/callback", server.uri()));
+    args.requests = Some(10);
+    let report = execute(LoadgenConfig::try_from(args).unwrap()).await.unwrap();
+    assert_eq!(report.outcomes.get("503"), Some(&10));
+}
+
+#[tokio::test]
+async fn test_loadgen_counts_transport_errors() {
+    // Nothing listens on this port once the listener is dropped
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let target = format!("http://{}/callback", listener.local_addr().unwrap());
+    drop(listener);
+
+    let mut args = args(target);
+    args.requests = Some(5);
+    let report = execute(LoadgenConfig::try_from(args).unwrap()).await.unwrap();
+    assert_eq!(report.outcomes.get("error:connect"), Some(&5));
+}
+
+#[test]
+fn test_invalid_signing_seeds_are_rejected() {
+    let mut invalid = args("http://localhost".to_string());
+    invalid.signing_seed = Some("GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ".into());
+    assert!(LoadgenConfig::try_from(invalid).is_err());
+}
+
+#[test]
+fn test_invalid_percentages_are_rejected() {
+    let mut invalid = args("http://localhost".to_string());
+    invalid.duplicate_pct = 80.0;
+    invalid.malformed_pct = 30.0;
+    assert!(LoadgenConfig::try_from(invalid).is_err());
+}
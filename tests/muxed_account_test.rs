@@ -0,0 +1,69 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::{json, Value};
+use synapse_core::{create_app, AppState};
+use tower::ServiceExt;
+
+const ACCOUNT: &str = "GA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJVSGZ";
+/// `ACCOUNT` with muxed id 1234
+const MUXED_ACCOUNT: &str =
+    "MA7QYNF7SOWQ3GLR2BGMZEHXAVIRZA4KVWLTJJFC7MGXUA74P7UJUAAAAAAAAAAE2JUG6";
+
+async fn post_callback(state: AppState, stellar_account: &str) -> (StatusCode, Value) {
+    let body = json!({
+        "id": format!("anchor-muxed-{}", uuid::Uuid::new_v4()),
+        "amount_in": "100.50",
+        "stellar_account": stellar_account,
+        "asset_code": "USD",
+        "callback_type": "deposit",
+        "status": "completed"
+    });
+    let response = create_app(state)
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/callback")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_muxed_account_stores_base_account_and_id() {
+    let pool = common::setup_pool().await;
+    let (status, created) = post_callback(common::app_state(pool.clone()), MUXED_ACCOUNT).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let id: uuid::Uuid = created["transaction_id"].as_str().unwrap().parse().unwrap();
+    let (stellar_account, muxed_id): (String, Option<String>) = sqlx::query_as(
+        "SELECT stellar_account, muxed_id::text FROM transactions WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(stellar_account, ACCOUNT);
+    assert_eq!(muxed_id.as_deref(), Some("1234"));
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_account_with_bad_checksum_is_rejected() {
+    let pool = common::setup_pool().await;
+    let flipped = format!("{}Y", &ACCOUNT[..ACCOUNT.len() - 1]);
+    let (status, body) = post_callback(common::app_state(pool), &flipped).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.to_string().contains("checksum does not match"), "{}", body);
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use synapse_core::config::WebhookDispatchConfig;
+use synapse_core::db::queries;
+use synapse_core::services::notifications::GENERIC_MESSAGE;
+use synapse_core::services::WebhookDispatcher;
+use uuid::Uuid;
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// A subscriber for a single, test-specific event type, so other tests'
+/// deliveries never reach it
+async fn subscribe(pool: &PgPool, event_type: &str, locale: &str) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+    queries::insert_webhook_subscription(pool, &server.uri(), &[event_type.to_string()], None, Some(locale))
+        .await
+        .unwrap();
+    server
+}
+
+async fn delivered_payload(pool: &PgPool, server: &MockServer) -> Value {
+    let dispatcher = WebhookDispatcher::new(pool.clone(), WebhookDispatchConfig::default());
+    for _ in 0..50 {
+        dispatcher.poll_once().await.unwrap();
+        if let Some(request) = server.received_requests().await.unwrap().first() {
+            return serde_json::from_slice(&request.body).unwrap();
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("no webhook delivered");
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_transaction_created_gets_localized_display_message() {
+    let pool = common::setup_pool().await;
+    let asset_code = format!("N{}", &Uuid::new_v4().simple().to_string()[..8]).to_uppercase();
+    sqlx::query("INSERT INTO assets (asset_code, metadata) VALUES ($1, '{\"decimals\": 2}')")
+        .bind(&asset_code)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    // A pt-BR subscriber falls back to the pt template
+    let server = subscribe(&pool, "transaction.created", "pt-BR").await;
+    queries::upsert_notification_template(
+        &pool,
+        "transaction.created",
+        "pt",
+        "Seu depósito de { $amount } { $asset_code } está em <processamento>",
+    )
+    .await
+    .unwrap();
+
+    let payload = json!({
+        "transaction_id": Uuid::new_v4(),
+        "status": "pending",
+        "amount": "100.0000000",
+        "asset_code": asset_code,
+        "callback_type": "deposit",
+    });
+    queries::enqueue_webhook_deliveries(&pool, "transaction.created", &payload, None)
+        .await
+        .unwrap();
+
+    let delivered = delivered_payload(&pool, &server).await;
+    assert_eq!(
+        delivered["display_message"],
+        format!("Seu depósito de 100.00 {} está em &lt;processamento&gt;", asset_code)
+    );
+    assert_eq!(delivered["transaction_id"], payload["transaction_id"]);
+
+    queries::delete_notification_template(&pool, "transaction.created", "pt")
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_render_failure_falls_back_to_generic_message() {
+    let pool = common::setup_pool().await;
+    let event_type = format!("test.{}", Uuid::new_v4());
+    let server = subscribe(&pool, &event_type, "en").await;
+    // Not reachable through the admin API, which rejects unknown event types
+    queries::upsert_notification_template(&pool, &event_type, "en", "Hello { $name }")
+        .await
+        .unwrap();
+
+    queries::enqueue_webhook_deliveries(&pool, &event_type, &json!({"name": "x"}), None)
+        .await
+        .unwrap();
+
+    let delivered = delivered_payload(&pool, &server).await;
+    assert_eq!(delivered["display_message"], GENERIC_MESSAGE);
+    assert_eq!(delivered["name"], "x");
+}
@@ -0,0 +1,217 @@
+mod common;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use synapse_core::config::{PartitionInterval, PartitionRetentionConfig, PartitionRetentionMode};
+use std::time::Duration;
+use synapse_core::db::partition::{list_partitions, PartitionManager};
+use synapse_core::utils::clock::TestClock;
+use synapse_core::utils::shutdown::Shutdown;
+use tokio::sync::Mutex;
+
+/// A retention pass would also remove the other tests' backdated partitions,
+/// so the tests take turns
+static PASS: Mutex<()> = Mutex::const_new(());
+
+/// A fresh partition from midnight UTC on `from` to midnight UTC on `to`
+async fn backdated_partition(pool: &PgPool, name: &str, from: &str, to: &str) {
+    sqlx::query(&format!("DROP TABLE IF EXISTS {}", name))
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query(&format!(
+        "CREATE TABLE {} PARTITION OF transactions \
+         FOR VALUES FROM ('{} 00:00+00') TO ('{} 00:00+00')",
+        name, from, to
+    ))
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+async fn table_exists(pool: &PgPool, name: &str) -> bool {
+    sqlx::query_scalar::<_, bool>("SELECT to_regclass($1) IS NOT NULL")
+        .bind(name)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn attached(pool: &PgPool) -> Vec<String> {
+    list_partitions(pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|p| p.name)
+        .collect()
+}
+
+fn manager(pool: &PgPool, mode: PartitionRetentionMode) -> PartitionManager {
+    PartitionManager::new(pool.clone(), 24)
+        .with_retention(PartitionRetentionConfig { months: 12, mode })
+}
+
+fn at(raw: &str) -> DateTime<Utc> {
+    raw.parse().unwrap()
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_detach_mode_keeps_partitions_still_in_the_window() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    backdated_partition(&pool, "transactions_y1990m01", "1990-01-01", "1990-02-01").await;
+    backdated_partition(&pool, "transactions_y1990m02", "1990-02-01", "1990-03-01").await;
+
+    // Cutoff 1990-02-15: January is wholly before it, February is not
+    let report = manager(&pool, PartitionRetentionMode::Detach)
+        .apply_retention(at("1991-02-15T00:00:00Z"))
+        .await
+        .unwrap();
+    assert_eq!(report.removed, vec!["transactions_y1990m01".to_string()]);
+    assert!(report.failed.is_empty());
+
+    let names = attached(&pool).await;
+    assert!(!names.contains(&"transactions_y1990m01".to_string()));
+    assert!(names.contains(&"transactions_y1990m02".to_string()));
+    assert!(names.contains(&"transactions_default".to_string()));
+    // Detached, not dropped
+    assert!(table_exists(&pool, "transactions_y1990m01").await);
+
+    for name in ["transactions_y1990m01", "transactions_y1990m02"] {
+        sqlx::query(&format!("DROP TABLE {}", name)).execute(&pool).await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_drop_mode_removes_the_table() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    backdated_partition(&pool, "transactions_y1980m01", "1980-01-01", "1980-02-01").await;
+    backdated_partition(&pool, "transactions_y1980m02", "1980-02-01", "1980-03-01").await;
+
+    let report = manager(&pool, PartitionRetentionMode::Drop)
+        .apply_retention(at("1981-03-01T00:00:00Z"))
+        .await
+        .unwrap();
+    assert_eq!(
+        report.removed,
+        vec!["transactions_y1980m01".to_string(), "transactions_y1980m02".to_string()]
+    );
+    assert!(!table_exists(&pool, "transactions_y1980m01").await);
+    assert!(!table_exists(&pool, "transactions_y1980m02").await);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_list_partitions_reports_bounds() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    backdated_partition(&pool, "transactions_y1970m01", "1970-01-01", "1970-02-01").await;
+
+    let partitions = list_partitions(&pool).await.unwrap();
+    let default = partitions.iter().find(|p| p.name == "transactions_default").unwrap();
+    assert!(default.is_default);
+    assert_eq!(default.upper_bound, None);
+
+    let old = partitions.iter().find(|p| p.name == "transactions_y1970m01").unwrap();
+    assert!(!old.is_default);
+    assert_eq!(old.lower_bound, Some(at("1970-01-01T00:00:00Z")));
+    assert_eq!(old.upper_bound, Some(at("1970-02-01T00:00:00Z")));
+    assert!(old.size_bytes > 0);
+
+    sqlx::query("DROP TABLE transactions_y1970m01").execute(&pool).await.unwrap();
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_archive_mode_moves_rows_to_the_archive() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    backdated_partition(&pool, "transactions_y1960m01", "1960-01-01", "1960-02-01").await;
+    let id: uuid::Uuid = sqlx::query_scalar(
+        "INSERT INTO transactions (stellar_account, amount, asset_code, created_at) \
+         VALUES ('GARCHIVED', 5, 'USDC', '1960-01-15 00:00+00') RETURNING id",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let report = manager(&pool, PartitionRetentionMode::Archive)
+        .apply_retention(at("1961-03-01T00:00:00Z"))
+        .await
+        .unwrap();
+    assert_eq!(report.removed, vec!["transactions_y1960m01".to_string()]);
+    assert!(!table_exists(&pool, "transactions_y1960m01").await);
+
+    let archived: String =
+        sqlx::query_scalar("SELECT stellar_account FROM transactions_archive WHERE id = $1")
+            .bind(id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(archived, "GARCHIVED");
+
+    sqlx::query("DELETE FROM transactions_archive WHERE id = $1")
+        .bind(id)
+        .execute(&pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_create_partitions_skips_ranges_already_covered() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    let now = at("2090-05-10T00:00:00Z");
+
+    let monthly = manager(&pool, PartitionRetentionMode::Detach);
+    let report = monthly.create_partitions(now).await.unwrap();
+    assert_eq!(
+        report.created,
+        vec!["transactions_y2090m05", "transactions_y2090m06", "transactions_y2090m07"]
+    );
+    assert!(monthly.create_partitions(now).await.unwrap().created.is_empty());
+
+    // Daily partitions inside the monthly ones would overlap them
+    let daily = manager(&pool, PartitionRetentionMode::Detach)
+        .with_partition_interval(PartitionInterval::Daily);
+    let report = daily.create_partitions(now).await.unwrap();
+    assert!(report.created.is_empty() && report.failed.is_empty());
+
+    for name in ["transactions_y2090m05", "transactions_y2090m06", "transactions_y2090m07"] {
+        sqlx::query(&format!("DROP TABLE {}", name)).execute(&pool).await.unwrap();
+    }
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_maintenance_runs_on_clock_ticks() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    let clock = TestClock::at(at("2091-05-10T00:00:00Z"));
+    let shutdown = Shutdown::new();
+    // A century of retention: the pass removes nothing the other tests rely on
+    PartitionManager::new(pool.clone(), 24)
+        .with_retention(PartitionRetentionConfig {
+            months: 1200,
+            mode: PartitionRetentionMode::Detach,
+        })
+        .with_clock(clock.shared())
+        .with_shutdown(shutdown.clone())
+        .start();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!table_exists(&pool, "transactions_y2091m05").await, "ran before the first tick");
+
+    // A day between runs: without the test clock this test would take that long
+    clock.advance(Duration::from_secs(24 * 3600));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(table_exists(&pool, "transactions_y2091m05").await, "did not run on the tick");
+
+    shutdown.cancel();
+    for name in ["transactions_y2091m05", "transactions_y2091m06", "transactions_y2091m07"] {
+        sqlx::query(&format!("DROP TABLE IF EXISTS {}", name)).execute(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+mod common;
+
+use serde_json::{json, Value};
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use std::str::FromStr;
+use synapse_core::db::models::Transaction;
+use synapse_core::db::queries;
+use synapse_core::services::payment_listener::{IncomingPayment, MatchOutcome, PaymentLeg, METADATA_KEY};
+use synapse_core::services::{FeatureFlagService, PaymentListener};
+use synapse_core::stellar::HorizonClient;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+const ANCHOR: &str = "GANCHORAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+const CUSTOMER: &str = "GCUSTOMERAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+const ISSUER: &str = "GISSUERAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+
+fn listener(pool: &PgPool, horizon_url: String) -> PaymentListener {
+    let (tx_broadcast, _) = broadcast::channel(16);
+    PaymentListener::new(
+        pool.clone(),
+        HorizonClient::new(horizon_url),
+        FeatureFlagService::new(pool.clone()),
+        tx_broadcast,
+        ANCHOR.to_string(),
+    )
+}
+
+/// A registry asset no other test uses
+async fn registry_asset(pool: &PgPool) -> String {
+    let code = format!("T{}", &Uuid::new_v4().simple().to_string()[..6]).to_uppercase();
+    queries::insert_asset(pool, &code, Some(ISSUER), &json!({}), true).await.unwrap();
+    code
+}
+
+async fn pending(pool: &PgPool, asset_code: &str, amount: &str) -> Transaction {
+    let mut tx = Transaction::new(
+        CUSTOMER.to_string(),
+        BigDecimal::from_str(amount).unwrap(),
+        asset_code.to_string(),
+        Some(format!("anchor-{}", Uuid::new_v4())),
+        Some("deposit".to_string()),
+        None,
+    );
+    tx.asset_issuer = (asset_code != "XLM").then(|| ISSUER.to_string());
+    queries::insert_transaction(pool, &tx).await.unwrap()
+}
+
+fn page(records: Value) -> String {
+    json!({ "_embedded": { "records": records } }).to_string()
+}
+
+fn operation_id() -> String {
+    Uuid::new_v4().as_u128().to_string()
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_cross_asset_path_payment_matches_on_destination_and_keeps_both_legs() {
+    let pool = common::setup_pool().await;
+    let asset_code = registry_asset(&pool).await;
+    let tx = pending(&pool, &asset_code, "100").await;
+    let memo = tx.anchor_transaction_id.clone().unwrap();
+    let op_id = operation_id();
+
+    let mut server = mockito::Server::new_async().await;
+    let payments = server
+        .mock("GET", mockito::Matcher::Regex(format!("^/accounts/{}/payments", ANCHOR)))
+        .with_body(page(json!([
+            {
+                "id": "1", "paging_token": "1", "type": "create_account",
+                "funder": CUSTOMER, "account": "GNEW", "starting_balance": "5.0000000"
+            },
+            {
+                "id": op_id, "paging_token": "2", "type": "path_payment_strict_receive",
+                "transaction_hash": "abc123", "from": CUSTOMER, "to": ANCHOR,
+                "amount": "100.0000000", "asset_type": "credit_alphanum12",
+                "asset_code": asset_code, "asset_issuer": ISSUER,
+                "source_amount": "92.5000000", "source_asset_type": "native",
+                "transaction": { "memo": memo, "memo_type": "text" }
+            }
+        ])))
+        .create_async()
+        .await;
+
+    sqlx::query("DELETE FROM stream_cursors WHERE name = 'payment_operations'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let listener = listener(&pool, server.url());
+    assert_eq!(listener.poll_once().await.unwrap(), 1);
+    payments.assert_async().await;
+
+    let matched = queries::get_transaction(&pool, tx.id).await.unwrap();
+    assert_eq!(matched.status, "processing");
+
+    let metadata = queries::get_transaction_metadata(&pool, tx.id).await.unwrap().unwrap();
+    let payment = &metadata[METADATA_KEY];
+    assert_eq!(payment["operation_id"], op_id);
+    assert_eq!(payment["operation_type"], "path_payment_strict_receive");
+    assert_eq!(payment["received"]["asset_code"], asset_code);
+    assert_eq!(payment["received"]["amount"], "100.0000000");
+    assert_eq!(payment["sent"]["asset_code"], "XLM");
+    assert_eq!(payment["sent"]["amount"], "92.5000000");
+
+    // A replay after a cursor reset matches nothing twice
+    sqlx::query("DELETE FROM stream_cursors WHERE name = 'payment_operations'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(listener.poll_once().await.unwrap(), 0);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_account_merge_matches_on_merged_balance() {
+    let pool = common::setup_pool().await;
+    let tx = pending(&pool, "XLM", "250.5").await;
+    let memo = tx.anchor_transaction_id.clone().unwrap();
+    let op_id = operation_id();
+
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", mockito::Matcher::Regex(format!("^/accounts/{}/payments", ANCHOR)))
+        .with_body(page(json!([{
+            "id": op_id, "paging_token": "3", "type": "account_merge",
+            "transaction_hash": "def456", "account": CUSTOMER, "into": ANCHOR,
+            "transaction": { "memo": memo, "memo_type": "text" }
+        }])))
+        .create_async()
+        .await;
+    server
+        .mock("GET", mockito::Matcher::Regex(format!("^/operations/{}/effects", op_id)))
+        .with_body(page(json!([
+            {
+                "id": format!("{}-1", op_id), "paging_token": "3-1", "account": CUSTOMER,
+                "type": "account_debited", "created_at": "2026-01-01T00:00:00Z", "amount": "250.5000000"
+            },
+            {
+                "id": format!("{}-2", op_id), "paging_token": "3-2", "account": ANCHOR,
+                "type": "account_credited", "created_at": "2026-01-01T00:00:00Z", "amount": "250.5000000"
+            }
+        ])))
+        .create_async()
+        .await;
+
+    assert_eq!(listener(&pool, server.url()).poll_once().await.unwrap(), 1);
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().status, "processing");
+
+    let metadata = queries::get_transaction_metadata(&pool, tx.id).await.unwrap().unwrap();
+    assert_eq!(metadata[METADATA_KEY]["operation_type"], "account_merge");
+    assert!(metadata[METADATA_KEY].get("sent").is_none());
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_payment_needs_memo_amount_and_registry_issuer() {
+    let pool = common::setup_pool().await;
+    let asset_code = registry_asset(&pool).await;
+    let tx = pending(&pool, &asset_code, "10").await;
+    let listener = listener(&pool, "http://127.0.0.1:9".to_string());
+
+    let payment = |memo: &str, amount: &str, issuer: &str| IncomingPayment {
+        operation_id: operation_id(),
+        operation_type: "payment",
+        transaction_hash: None,
+        from: CUSTOMER.to_string(),
+        memo: Some(memo.to_string()),
+        received: PaymentLeg {
+            asset_code: asset_code.clone(),
+            asset_issuer: Some(issuer.to_string()),
+            amount: amount.to_string(),
+        },
+        sent: None,
+    };
+    let memo = tx.anchor_transaction_id.clone().unwrap();
+
+    for unmatched in [
+        payment("some-other-memo", "10", ISSUER),
+        payment(&memo, "9.99", ISSUER),
+        payment(&memo, "10", "GCOUNTERFEITISSUER"),
+    ] {
+        assert_eq!(listener.handle_payment(&unmatched).await.unwrap(), MatchOutcome::Unmatched);
+    }
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().status, "pending");
+
+    let matching = payment(&memo, "10.0000000", ISSUER);
+    assert_eq!(listener.handle_payment(&matching).await.unwrap(), MatchOutcome::Matched(tx.id));
+    assert_eq!(listener.handle_payment(&matching).await.unwrap(), MatchOutcome::Duplicate);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_page_without_matches_still_advances_cursor() {
+    let pool = common::setup_pool().await;
+    let last_token = operation_id();
+
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", mockito::Matcher::Regex(format!("^/accounts/{}/payments", ANCHOR)))
+        .with_body(page(json!([
+            {
+                "id": operation_id(), "paging_token": operation_id(), "type": "create_account",
+                "funder": CUSTOMER, "account": "GNEW", "starting_balance": "5.0000000"
+            },
+            {
+                "id": operation_id(), "paging_token": last_token, "type": "payment",
+                "transaction_hash": "fed789", "from": CUSTOMER, "to": ANCHOR,
+                "amount": "1.0000000", "asset_type": "native",
+                "transaction": { "memo_type": "none" }
+            }
+        ])))
+        .create_async()
+        .await;
+
+    sqlx::query("DELETE FROM stream_cursors WHERE name = 'payment_operations'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let listener = listener(&pool, server.url());
+    assert_eq!(listener.poll_once().await.unwrap(), 0);
+
+    let cursor = queries::get_stream_cursor(&pool, "payment_operations").await.unwrap();
+    assert_eq!(cursor.as_deref(), Some(last_token.as_str()));
+}
@@ -0,0 +1,228 @@
+mod common;
+
+use chrono::Utc;
+use ed25519_dalek::SigningKey;
+use serde_json::json;
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::time::Duration;
+use synapse_core::config::PaymentProcessorConfig;
+use synapse_core::db::models::Transaction;
+use synapse_core::db::queries;
+use synapse_core::services::payment_processor::{PayoutOutcome, METADATA_KEY};
+use synapse_core::services::PaymentProcessor;
+use synapse_core::stellar::strkey;
+use synapse_core::stellar::{HorizonClient, RetryPolicy};
+use synapse_core::utils::clock::TestClock;
+use tokio::sync::{broadcast, Mutex};
+use uuid::Uuid;
+
+/// A pass claims every pending deposit, including the other tests' ones, so
+/// the tests take turns
+static PASS: Mutex<()> = Mutex::const_new(());
+
+fn signing_key() -> SigningKey {
+    SigningKey::from_bytes(&[3; 32])
+}
+
+fn processor(pool: &PgPool, horizon_url: String, clock: &TestClock) -> PaymentProcessor {
+    let (tx_broadcast, _) = broadcast::channel(16);
+    let horizon_client = HorizonClient::new(horizon_url).with_retry(RetryPolicy {
+        max_retries: 0,
+        base_delay: Duration::ZERO,
+        max_delay: Duration::ZERO,
+    });
+    let config = PaymentProcessorConfig {
+        batch_size: 1_000,
+        ..Default::default()
+    };
+    PaymentProcessor::new(pool.clone(), horizon_client, tx_broadcast, config, signing_key())
+        .with_clock(clock.shared())
+}
+
+/// A pending lumen deposit to an account no other test uses
+async fn pending(pool: &PgPool) -> Transaction {
+    let mut seed = [0; 32];
+    seed[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+    let account = strkey::encode_ed25519(SigningKey::from_bytes(&seed).verifying_key().as_bytes());
+    let tx = Transaction::new(
+        account,
+        BigDecimal::from_str("12.5").unwrap(),
+        "XLM".to_string(),
+        Some(format!("anchor-{}", Uuid::new_v4())),
+        Some("deposit".to_string()),
+        None,
+    );
+    queries::insert_transaction(pool, &tx).await.unwrap()
+}
+
+/// Horizon with a funded source account
+async fn horizon() -> mockito::ServerGuard {
+    let mut server = mockito::Server::new_async().await;
+    let source = strkey::encode_ed25519(signing_key().verifying_key().as_bytes());
+    server
+        .mock("GET", format!("/accounts/{}", source).as_str())
+        .with_header("content-type", "application/json")
+        .with_body(
+            json!({
+                "id": source, "account_id": source, "balances": [],
+                "sequence": "4096", "subentry_count": 0, "home_domain": null
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+    server
+}
+
+fn outcome_for(outcomes: &[(Uuid, PayoutOutcome)], id: Uuid) -> Option<&PayoutOutcome> {
+    outcomes.iter().find(|(tx_id, _)| *tx_id == id).map(|(_, outcome)| outcome)
+}
+
+async fn payout_columns(pool: &PgPool, id: Uuid) -> (Option<String>, Option<String>) {
+    sqlx::query_as("SELECT payout_tx_hash, payout_envelope FROM transactions WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_submitted_payment_completes_the_transaction() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    let tx = pending(&pool).await;
+    let mut server = horizon().await;
+    server
+        .mock("POST", "/transactions")
+        .with_header("content-type", "application/json")
+        .with_body(json!({ "hash": "landed", "ledger": 7, "successful": true }).to_string())
+        .create_async()
+        .await;
+
+    let clock = TestClock::at(Utc::now());
+    let outcomes = processor(&pool, server.url(), &clock).run_once().await.unwrap();
+    assert_eq!(
+        outcome_for(&outcomes, tx.id),
+        Some(&PayoutOutcome::Completed { hash: "landed".to_string() })
+    );
+
+    let stored = queries::get_transaction(&pool, tx.id).await.unwrap();
+    assert_eq!(stored.status, "completed");
+    assert_eq!(payout_columns(&pool, tx.id).await.0.as_deref(), Some("landed"));
+    let metadata = queries::get_transaction_metadata(&pool, tx.id).await.unwrap().unwrap();
+    assert_eq!(metadata[METADATA_KEY]["outcome"], "completed");
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_missing_trustline_parks_the_transaction() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    let tx = pending(&pool).await;
+    let mut server = horizon().await;
+    server
+        .mock("POST", "/transactions")
+        .with_status(400)
+        .with_header("content-type", "application/json")
+        .with_body(
+            json!({ "extras": { "result_codes": {
+                "transaction": "tx_failed", "operations": ["op_no_trust"]
+            } } })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let clock = TestClock::at(Utc::now());
+    let outcomes = processor(&pool, server.url(), &clock).run_once().await.unwrap();
+    assert_eq!(
+        outcome_for(&outcomes, tx.id),
+        Some(&PayoutOutcome::PendingTrustline { codes: vec!["op_no_trust".to_string()] })
+    );
+
+    let stored = queries::get_transaction(&pool, tx.id).await.unwrap();
+    assert_eq!(stored.status, "pending_trustline");
+    assert_eq!(payout_columns(&pool, tx.id).await, (None, None));
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_lost_answer_is_looked_up_instead_of_paying_twice() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    let tx = pending(&pool).await;
+    let mut server = horizon().await;
+    let unavailable = server
+        .mock("POST", "/transactions")
+        .with_status(503)
+        .create_async()
+        .await;
+
+    let clock = TestClock::at(Utc::now());
+    let processor = processor(&pool, server.url(), &clock);
+    let outcomes = processor.run_once().await.unwrap();
+    let Some(PayoutOutcome::Unconfirmed { hash }) = outcome_for(&outcomes, tx.id).cloned() else {
+        panic!("expected an unconfirmed payment: {:?}", outcomes);
+    };
+    let stored = queries::get_transaction(&pool, tx.id).await.unwrap();
+    assert_eq!(stored.status, "submitted");
+    let (stored_hash, envelope) = payout_columns(&pool, tx.id).await;
+    assert_eq!(stored_hash.as_deref(), Some(hash.as_str()));
+    assert!(envelope.is_some());
+
+    // The payment did land; the next pass finds it without submitting again
+    unavailable.remove_async().await;
+    // Sending it again would be answered with another hash
+    server
+        .mock("POST", "/transactions")
+        .with_header("content-type", "application/json")
+        .with_body(json!({ "hash": "paid-twice", "ledger": 8, "successful": true }).to_string())
+        .create_async()
+        .await;
+    server
+        .mock("GET", format!("/transactions/{}", hash).as_str())
+        .with_header("content-type", "application/json")
+        .with_body(json!({ "hash": hash, "ledger": 8, "successful": true }).to_string())
+        .create_async()
+        .await;
+    clock.advance(Duration::from_secs(30));
+
+    let outcomes = processor.run_once().await.unwrap();
+    assert_eq!(
+        outcome_for(&outcomes, tx.id),
+        Some(&PayoutOutcome::Completed { hash: hash.clone() })
+    );
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().status, "completed");
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_sep31_transactions_are_not_claimed() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    let deposit = pending(&pool).await;
+    let sep31 = Transaction::new(
+        deposit.stellar_account.clone(),
+        BigDecimal::from_str("12.5").unwrap(),
+        "XLM".to_string(),
+        Some(format!("anchor-{}", Uuid::new_v4())),
+        Some("sep31".to_string()),
+        None,
+    );
+    let sep31 = queries::insert_transaction(&pool, &sep31).await.unwrap();
+
+    let now = Utc::now();
+    let claimed = queries::claim_transactions_for_payout(
+        &pool,
+        now,
+        now + chrono::Duration::seconds(30),
+        1_000,
+    )
+    .await
+    .unwrap();
+    assert!(claimed.iter().any(|payout| payout.transaction.id == deposit.id));
+    assert!(!claimed.iter().any(|payout| payout.transaction.id == sep31.id));
+}
@@ -0,0 +1,200 @@
+mod common;
+
+use serde_json::json;
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use std::str::FromStr;
+use synapse_core::db::models::Transaction;
+use synapse_core::db::queries;
+use synapse_core::services::payout_listener::{ConfirmOutcome, CURSOR_NAME, METADATA_KEY};
+use synapse_core::services::PayoutListener;
+use synapse_core::stellar::stream::PaymentStream;
+use synapse_core::stellar::{strkey, Operation};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+fn source() -> String {
+    strkey::encode_ed25519(&[3; 32])
+}
+
+fn listener(pool: &PgPool, horizon_url: String) -> PayoutListener {
+    let (tx_broadcast, _) = broadcast::channel(16);
+    PayoutListener::new(pool.clone(), PaymentStream::new(horizon_url, source()), tx_broadcast)
+}
+
+/// A lumen deposit that the payment processor has submitted in a Stellar
+/// transaction with a hash of its own
+async fn submitted(pool: &PgPool) -> (Transaction, String) {
+    let mut key = [0; 32];
+    key[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+    let tx = Transaction::new(
+        strkey::encode_ed25519(&key),
+        BigDecimal::from_str("12.5").unwrap(),
+        "XLM".to_string(),
+        Some(format!("anchor-{}", Uuid::new_v4())),
+        Some("deposit".to_string()),
+        None,
+    );
+    let tx = queries::insert_transaction(pool, &tx).await.unwrap();
+    let hash = Uuid::new_v4().simple().to_string();
+    sqlx::query("UPDATE transactions SET status = 'submitted', payout_tx_hash = $2 WHERE id = $1")
+        .bind(tx.id)
+        .bind(&hash)
+        .execute(pool)
+        .await
+        .unwrap();
+    (tx, hash)
+}
+
+fn payment(tx: &Transaction, hash: &str, amount: &str) -> Operation {
+    let id = Uuid::new_v4().as_u128().to_string();
+    Operation {
+        paging_token: id.clone(),
+        id,
+        operation_type: "payment".to_string(),
+        transaction_hash: Some(hash.to_string()),
+        from: Some(source()),
+        to: Some(tx.stellar_account.clone()),
+        amount: Some(amount.to_string()),
+        asset_type: Some("native".to_string()),
+        ..Default::default()
+    }
+}
+
+async fn review(pool: &PgPool, id: Uuid) -> (bool, Option<String>) {
+    sqlx::query_as("SELECT needs_review, review_reason FROM transactions WHERE id = $1")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_payout_in_ledger_completes_the_transaction_once() {
+    let pool = common::setup_pool().await;
+    let (tx, hash) = submitted(&pool).await;
+    let listener = listener(&pool, "http://localhost:1".to_string());
+    let operation = payment(&tx, &hash, "12.5000000");
+
+    assert_eq!(
+        listener.handle_operation(&operation).await.unwrap(),
+        ConfirmOutcome::Completed(tx.id)
+    );
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().status, "completed");
+    let metadata = queries::get_transaction_metadata(&pool, tx.id).await.unwrap().unwrap();
+    assert_eq!(metadata[METADATA_KEY]["operation_id"], json!(operation.id));
+    let history = queries::get_status_history(&pool, tx.id).await.unwrap();
+    assert_eq!(history.last().unwrap().actor, "payout_listener");
+
+    // The same operation again, e.g. after a reconnect
+    assert_eq!(
+        listener.handle_operation(&operation).await.unwrap(),
+        ConfirmOutcome::Duplicate
+    );
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_mismatched_amount_is_flagged_for_review() {
+    let pool = common::setup_pool().await;
+    let (tx, hash) = submitted(&pool).await;
+    let listener = listener(&pool, "http://localhost:1".to_string());
+
+    let outcome = listener
+        .handle_operation(&payment(&tx, &hash, "1.2500000"))
+        .await
+        .unwrap();
+    assert!(matches!(outcome, ConfirmOutcome::Mismatch { id, .. } if id == tx.id));
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().status, "submitted");
+    let (needs_review, reason) = review(&pool, tx.id).await;
+    assert!(needs_review);
+    assert!(reason.unwrap().contains("1.2500000"));
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_unrelated_payments_are_ignored() {
+    let pool = common::setup_pool().await;
+    let (tx, _) = submitted(&pool).await;
+    let listener = listener(&pool, "http://localhost:1".to_string());
+
+    let unknown = payment(&tx, "not-a-payout", "12.5");
+    assert_eq!(listener.handle_operation(&unknown).await.unwrap(), ConfirmOutcome::Unknown);
+
+    let mut incoming = payment(&tx, "not-a-payout", "12.5");
+    incoming.from = Some(tx.stellar_account.clone());
+    assert_eq!(listener.handle_operation(&incoming).await.unwrap(), ConfirmOutcome::NotPayout);
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().status, "submitted");
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_stream_is_followed_and_cursor_saved() {
+    let pool = common::setup_pool().await;
+    let (tx, hash) = submitted(&pool).await;
+    let operation = payment(&tx, &hash, "12.5");
+    let mut server = mockito::Server::new_async().await;
+    let body = format!(
+        "event: open\ndata: \"hello\"\n\nid: {}\ndata: {}\n\n",
+        operation.paging_token,
+        json!({
+            "id": operation.id, "paging_token": operation.paging_token, "type": "payment",
+            "transaction_hash": hash, "from": source(), "to": tx.stellar_account,
+            "amount": "12.5000000", "asset_type": "native"
+        })
+    );
+    server
+        .mock("GET", mockito::Matcher::Regex(format!("^/accounts/{}/payments", source())))
+        .with_header("content-type", "text/event-stream")
+        .with_body(body)
+        .create_async()
+        .await;
+
+    let handled = listener(&pool, server.url()).follow().await.unwrap();
+    assert_eq!(handled, 1);
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().status, "completed");
+    assert_eq!(
+        queries::get_stream_cursor(&pool, CURSOR_NAME).await.unwrap().as_deref(),
+        Some(operation.paging_token.as_str())
+    );
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_failing_payment_is_flagged_and_cursor_advances() {
+    let pool = common::setup_pool().await;
+    let (tx, hash) = submitted(&pool).await;
+    sqlx::query("UPDATE transactions SET status = 'bogus' WHERE id = $1")
+        .bind(tx.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let operation = payment(&tx, &hash, "12.5");
+    let mut server = mockito::Server::new_async().await;
+    let body = format!(
+        "id: {}\ndata: {}\n\n",
+        operation.paging_token,
+        json!({
+            "id": operation.id, "paging_token": operation.paging_token, "type": "payment",
+            "transaction_hash": hash, "from": source(), "to": tx.stellar_account,
+            "amount": "12.5000000", "asset_type": "native"
+        })
+    );
+    server
+        .mock("GET", mockito::Matcher::Regex(format!("^/accounts/{}/payments", source())))
+        .with_header("content-type", "text/event-stream")
+        .with_body(body)
+        .create_async()
+        .await;
+
+    let handled = listener(&pool, server.url()).follow().await.unwrap();
+    assert_eq!(handled, 1);
+    let (needs_review, reason) = review(&pool, tx.id).await;
+    assert!(needs_review);
+    assert!(reason.unwrap().contains(&operation.id));
+    assert_eq!(
+        queries::get_stream_cursor(&pool, CURSOR_NAME).await.unwrap().as_deref(),
+        Some(operation.paging_token.as_str())
+    );
+}
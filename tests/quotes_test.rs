@@ -0,0 +1,144 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use synapse_core::db::models::Quote;
+use synapse_core::db::queries;
+use synapse_core::services::QuoteService;
+use synapse_core::create_app;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+const ISSUER: &str = "GBBD47IF6LWK7P7MDEVSCWR7DPUWV3NY3DTQEVFL4NAT4AQH3ZLLFLA5";
+const SELL_ASSET: &str = "stellar:USD:GBBD47IF6LWK7P7MDEVSCWR7DPUWV3NY3DTQEVFL4NAT4AQH3ZLLFLA5";
+
+async fn insert_quote(pool: &PgPool, sell_amount: &str, expires_at: chrono::DateTime<Utc>) -> Quote {
+    let quote = Quote {
+        id: format!("quote-{}", Uuid::new_v4()),
+        sell_asset: SELL_ASSET.to_string(),
+        buy_asset: "iso4217:USD".to_string(),
+        sell_amount: sell_amount.parse().unwrap(),
+        buy_amount: "98".parse().unwrap(),
+        rate: "1".parse().unwrap(),
+        total_price: "1.02".parse().unwrap(),
+        fee_total: "2".parse().unwrap(),
+        fee_asset: "iso4217:USD".to_string(),
+        expires_at,
+        transaction_id: None,
+        created_at: Utc::now(),
+    };
+    queries::insert_quote(pool, &quote).await.unwrap()
+}
+
+async fn withdraw(pool: &PgPool, amount: &str, quote_id: &str) -> (StatusCode, Value) {
+    withdraw_issued(pool, amount, quote_id, ISSUER).await
+}
+
+async fn withdraw_issued(
+    pool: &PgPool,
+    amount: &str,
+    quote_id: &str,
+    issuer: &str,
+) -> (StatusCode, Value) {
+    let body = json!({
+        "id": format!("anchor-wd-{}", Uuid::new_v4()),
+        "amount_in": amount,
+        "stellar_account": "GCAIJAF37NNZGSH6PO442CPK5CEHJNOXAYNHLDJBTQJKF7NIQ6CZ7DQW",
+        "asset_code": "USD",
+        "asset_issuer": issuer,
+        "callback_type": "withdrawal",
+        "status": "pending_anchor",
+        "quote_id": quote_id
+    });
+    let response = create_app(common::app_state(pool.clone()))
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/callback")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_withdrawal_links_quote_once() {
+    let pool = common::setup_pool().await;
+    let quote = insert_quote(&pool, "100", Utc::now() + Duration::minutes(10)).await;
+
+    let (status, created) = withdraw(&pool, "100.50", &quote.id).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let transaction_id: Uuid = created["transaction_id"].as_str().unwrap().parse().unwrap();
+
+    let tx = queries::get_transaction(&pool, transaction_id).await.unwrap();
+    assert_eq!(tx.quote_id.as_deref(), Some(quote.id.as_str()));
+    let linked = queries::get_quote(&pool, &quote.id).await.unwrap().unwrap();
+    assert_eq!(linked.transaction_id, Some(transaction_id));
+
+    let (status, body) = withdraw(&pool, "100", &quote.id).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("quote_used"));
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_expired_or_mismatched_quote_is_rejected() {
+    let pool = common::setup_pool().await;
+
+    let expired = insert_quote(&pool, "100", Utc::now() - Duration::seconds(1)).await;
+    let (status, body) = withdraw(&pool, "100", &expired.id).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("quote_expired"));
+
+    let live = insert_quote(&pool, "100", Utc::now() + Duration::minutes(10)).await;
+    let (status, body) = withdraw(&pool, "150", &live.id).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("quote_amount_mismatch"));
+
+    // Same code, another issuer: the quote priced a different asset
+    let other = "GCAIJAF37NNZGSH6PO442CPK5CEHJNOXAYNHLDJBTQJKF7NIQ6CZ7DQW";
+    let (status, body) = withdraw_issued(&pool, "100", &live.id, other).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("quote_asset_mismatch"));
+
+    let (status, body) = withdraw(&pool, "100", "quote-that-does-not-exist").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].as_str().unwrap().contains("quote_not_found"));
+
+    // Nothing was linked by the rejected callbacks
+    let untouched = queries::get_quote(&pool, &live.id).await.unwrap().unwrap();
+    assert_eq!(untouched.transaction_id, None);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_sweeper_purges_only_long_expired_unused_quotes() {
+    let pool = common::setup_pool().await;
+    let stale = insert_quote(&pool, "100", Utc::now() - Duration::hours(48)).await;
+    let recent = insert_quote(&pool, "100", Utc::now() - Duration::hours(1)).await;
+    let used = insert_quote(&pool, "100", Utc::now() + Duration::minutes(10)).await;
+    assert!(queries::link_quote_to_transaction(&pool, &used.id, Uuid::new_v4()).await.unwrap());
+    sqlx::query("UPDATE quotes SET expires_at = NOW() - INTERVAL '48 hours' WHERE id = $1")
+        .bind(&used.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let service = QuoteService::new(pool.clone(), None, "0.01".parse().unwrap());
+    assert!(service.purge_expired(Duration::hours(24)).await.unwrap() >= 1);
+
+    assert!(queries::get_quote(&pool, &stale.id).await.unwrap().is_none());
+    assert!(queries::get_quote(&pool, &recent.id).await.unwrap().is_some());
+    assert!(queries::get_quote(&pool, &used.id).await.unwrap().is_some());
+}
@@ -0,0 +1,241 @@
+mod common;
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::time::Duration;
+use synapse_core::config::ReconciliationConfig;
+use synapse_core::db::models::Transaction;
+use synapse_core::db::queries;
+use synapse_core::services::reconciliation::{ReconcileOutcome, METADATA_KEY};
+use synapse_core::services::ReconciliationWorker;
+use synapse_core::stellar::{HorizonClient, RetryPolicy};
+use synapse_core::utils::clock::TestClock;
+use tokio::sync::{broadcast, Mutex};
+use uuid::Uuid;
+
+const ANCHOR: &str = "GANCHORAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+const ISSUER: &str = "GISSUERAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+
+/// A pass claims every pending deposit, including the other tests' ones, so
+/// the tests take turns
+static PASS: Mutex<()> = Mutex::const_new(());
+
+/// A worker whose clock is `ahead` of now, so fresh transactions count as old
+fn worker(pool: &PgPool, horizon_url: String, ahead: Duration) -> ReconciliationWorker {
+    let (tx_broadcast, _) = broadcast::channel(16);
+    // Pending rows left by other tests are claimed too; their Horizon calls
+    // fail fast instead of backing off
+    let horizon_client = HorizonClient::new(horizon_url).with_retry(RetryPolicy {
+        max_retries: 0,
+        base_delay: Duration::ZERO,
+        max_delay: Duration::ZERO,
+    });
+    let config = ReconciliationConfig {
+        batch_size: 1_000,
+        ..Default::default()
+    };
+    let clock = TestClock::at(Utc::now() + chrono::Duration::from_std(ahead).unwrap());
+    let source = ANCHOR.to_string();
+    ReconciliationWorker::new(pool.clone(), horizon_client, tx_broadcast, config, source)
+        .with_clock(clock.shared())
+}
+
+/// A registry asset no other test uses
+async fn registry_asset(pool: &PgPool) -> String {
+    let code = format!("R{}", &Uuid::new_v4().simple().to_string()[..6]).to_uppercase();
+    queries::insert_asset(pool, &code, Some(ISSUER), &json!({}), true).await.unwrap();
+    code
+}
+
+/// A pending deposit to an account no other test uses
+async fn pending(pool: &PgPool, asset_code: &str, amount: &str) -> Transaction {
+    let account = format!("GRECON{}", Uuid::new_v4().simple()).to_uppercase();
+    let mut tx = Transaction::new(
+        account,
+        BigDecimal::from_str(amount).unwrap(),
+        asset_code.to_string(),
+        Some(format!("anchor-{}", Uuid::new_v4())),
+        Some("deposit".to_string()),
+        None,
+    );
+    tx.asset_issuer = Some(ISSUER.to_string());
+    queries::insert_transaction(pool, &tx).await.unwrap()
+}
+
+fn payment(tx: &Transaction, asset_code: &str, amount: &str, created_at: DateTime<Utc>) -> Value {
+    let id = Uuid::new_v4().as_u128().to_string();
+    json!({
+        "id": id, "paging_token": id, "type": "payment",
+        "created_at": created_at.to_rfc3339(), "transaction_hash": "abc123",
+        "from": ANCHOR, "to": tx.stellar_account, "amount": amount,
+        "asset_type": "credit_alphanum12", "asset_code": asset_code, "asset_issuer": ISSUER
+    })
+}
+
+async fn horizon(tx: &Transaction, records: Value) -> mockito::ServerGuard {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock(
+            "GET",
+            mockito::Matcher::Regex(format!("^/accounts/{}/payments", tx.stellar_account)),
+        )
+        .with_header("content-type", "application/json")
+        .with_body(json!({ "_embedded": { "records": records } }).to_string())
+        .create_async()
+        .await;
+    server
+}
+
+fn outcome_for(outcomes: &[(Uuid, ReconcileOutcome)], id: Uuid) -> Option<&ReconcileOutcome> {
+    outcomes.iter().find(|(tx_id, _)| *tx_id == id).map(|(_, outcome)| outcome)
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_found_payment_completes_the_transaction() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    let asset_code = registry_asset(&pool).await;
+    let tx = pending(&pool, &asset_code, "100").await;
+    let after = tx.created_at + chrono::Duration::seconds(5);
+    let paid = payment(&tx, &asset_code, "100.0000000", after);
+    let operation_id = paid["id"].as_str().unwrap().to_string();
+    // Same amount, but sent before the transaction existed
+    let before = tx.created_at - chrono::Duration::hours(1);
+    let earlier = payment(&tx, &asset_code, "100.0000000", before);
+    let server = horizon(&tx, json!([paid, earlier])).await;
+
+    let worker = worker(&pool, server.url(), Duration::from_secs(120));
+    let outcomes = worker.run_once().await.unwrap();
+    assert_eq!(
+        outcome_for(&outcomes, tx.id),
+        Some(&ReconcileOutcome::Completed {
+            operation_id: operation_id.clone()
+        })
+    );
+
+    let completed = queries::get_transaction(&pool, tx.id).await.unwrap();
+    assert_eq!(completed.status, "completed");
+    let metadata = queries::get_transaction_metadata(&pool, tx.id).await.unwrap().unwrap();
+    assert_eq!(metadata[METADATA_KEY]["outcome"], "completed");
+    assert_eq!(metadata[METADATA_KEY]["payment"]["operation_id"], operation_id);
+
+    // Completed transactions are not claimed again
+    let outcomes = worker.run_once().await.unwrap();
+    assert_eq!(outcome_for(&outcomes, tx.id), None);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_missing_payment_leaves_the_transaction_pending() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    let asset_code = registry_asset(&pool).await;
+    let tx = pending(&pool, &asset_code, "100").await;
+    let server = horizon(&tx, json!([])).await;
+
+    let worker = worker(&pool, server.url(), Duration::from_secs(120));
+    let outcomes = worker.run_once().await.unwrap();
+    assert_eq!(outcome_for(&outcomes, tx.id), Some(&ReconcileOutcome::NotFound));
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().status, "pending");
+
+    // Claimed until the next pass is due
+    let outcomes = worker.run_once().await.unwrap();
+    assert_eq!(outcome_for(&outcomes, tx.id), None);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_partial_amount_does_not_complete() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    let asset_code = registry_asset(&pool).await;
+    let tx = pending(&pool, &asset_code, "100").await;
+    let after = tx.created_at + chrono::Duration::seconds(5);
+    let partial = payment(&tx, &asset_code, "40.0000000", after);
+    let server = horizon(&tx, json!([partial])).await;
+
+    let worker = worker(&pool, server.url(), Duration::from_secs(120));
+    let outcomes = worker.run_once().await.unwrap();
+    assert_eq!(
+        outcome_for(&outcomes, tx.id),
+        Some(&ReconcileOutcome::PartialAmount {
+            received: vec!["40.0000000".to_string()]
+        })
+    );
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().status, "pending");
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_transaction_past_max_age_fails() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    let asset_code = registry_asset(&pool).await;
+    let tx = pending(&pool, &asset_code, "100").await;
+    let server = horizon(&tx, json!([])).await;
+
+    let worker = worker(&pool, server.url(), Duration::from_secs(25 * 60 * 60));
+    let outcomes = worker.run_once().await.unwrap();
+    assert_eq!(outcome_for(&outcomes, tx.id), Some(&ReconcileOutcome::Expired));
+
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().status, "failed");
+    let metadata = queries::get_transaction_metadata(&pool, tx.id).await.unwrap().unwrap();
+    assert_eq!(metadata[METADATA_KEY]["outcome"], "expired");
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_payment_from_another_sender_does_not_complete() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    let asset_code = registry_asset(&pool).await;
+    let tx = pending(&pool, &asset_code, "100").await;
+    let after = tx.created_at + chrono::Duration::seconds(5);
+    let mut stranger = payment(&tx, &asset_code, "100.0000000", after);
+    stranger["from"] = json!("GSTRANGERAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA");
+    let server = horizon(&tx, json!([stranger])).await;
+
+    let worker = worker(&pool, server.url(), Duration::from_secs(120));
+    let outcomes = worker.run_once().await.unwrap();
+    assert_eq!(outcome_for(&outcomes, tx.id), Some(&ReconcileOutcome::NotFound));
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().status, "pending");
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_deposits_claimed_by_the_payment_processor_are_left_alone() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    let asset_code = registry_asset(&pool).await;
+    let tx = pending(&pool, &asset_code, "100").await;
+    sqlx::query("UPDATE transactions SET payout_after = NOW() WHERE id = $1")
+        .bind(tx.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let server = horizon(&tx, json!([])).await;
+
+    // Past the maximum age, but the processor's to finish
+    let worker = worker(&pool, server.url(), Duration::from_secs(25 * 60 * 60));
+    let outcomes = worker.run_once().await.unwrap();
+    assert_eq!(outcome_for(&outcomes, tx.id), None);
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().status, "pending");
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_young_transactions_are_not_claimed() {
+    let _pass = PASS.lock().await;
+    let pool = common::setup_pool().await;
+    let asset_code = registry_asset(&pool).await;
+    let tx = pending(&pool, &asset_code, "100").await;
+    let server = horizon(&tx, json!([])).await;
+
+    let worker = worker(&pool, server.url(), Duration::ZERO);
+    let outcomes = worker.run_once().await.unwrap();
+    assert_eq!(outcome_for(&outcomes, tx.id), None);
+}
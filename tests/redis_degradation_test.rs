@@ -0,0 +1,128 @@
+//! Redis outage behavior per feature.
+//!
+//! These tests stop and restart a real Redis container mid-run:
+//!   docker run -d --name synapse-redis-test -p 6390:6379 redis:7-alpine
+//!   REDIS_TEST_CONTAINER=synapse-redis-test REDIS_TEST_URL=redis://localhost:6390 \
+//!       cargo test --test redis_degradation_test -- --ignored --test-threads=1
+
+use std::collections::HashSet;
+use std::process::Command;
+use std::time::Duration;
+use synapse_core::error::AppError;
+use synapse_core::middleware::idempotency::IdempotencyService;
+use synapse_core::services::redis_health::{
+    DistributedLock, Fallback, LockGuard, RedisDecision, RedisFeature, RedisHealth,
+};
+
+const RESET: Duration = Duration::from_millis(500);
+
+fn redis_url() -> String {
+    std::env::var("REDIS_TEST_URL").unwrap_or_else(|_| "redis://localhost:6390".to_string())
+}
+
+fn docker(action: &str) {
+    let container = std::env::var("REDIS_TEST_CONTAINER")
+        .unwrap_or_else(|_| "synapse-redis-test".to_string());
+    let status = Command::new("docker")
+        .args([action, &container])
+        .status()
+        .expect("docker must be available");
+    assert!(status.success(), "docker {} {} failed", action, container);
+}
+
+async fn wait_for_redis() {
+    let client = redis::Client::open(redis_url()).unwrap();
+    for _ in 0..50 {
+        if let Ok(mut conn) = client.get_multiplexed_async_connection().await {
+            if redis::cmd("PING").query_async::<_, String>(&mut conn).await.is_ok() {
+                return;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Redis did not come back");
+}
+
+fn service(health: RedisHealth) -> IdempotencyService {
+    IdempotencyService::new(&redis_url(), health).unwrap()
+}
+
+fn id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_features_degrade_and_recover_when_redis_stops() {
+    wait_for_redis().await;
+    let health = RedisHealth::new(1, RESET, HashSet::new());
+    let callbacks = service(health.clone());
+
+    // Healthy: replays are answered from Redis
+    let first = id();
+    callbacks.cache_callback(&first, "{}").await;
+    assert_eq!(callbacks.cached_callback(&first).await.unwrap().as_deref(), Some("{}"));
+
+    docker("stop");
+
+    // Callback ingestion keeps working: lookups miss and the callback
+    // handler's database check catches replays
+    assert_eq!(callbacks.cached_callback(&id()).await.unwrap(), None);
+    assert!(!health.status().available);
+    assert_eq!(
+        health.decide(RedisFeature::Idempotency),
+        RedisDecision::Degrade(Fallback::DatabaseDedup)
+    );
+    assert_eq!(callbacks.cached_callback(&first).await.unwrap(), None);
+
+    // Rate limiting fails open and response caching is bypassed
+    assert_eq!(
+        health.decide(RedisFeature::RateLimiting),
+        RedisDecision::Degrade(Fallback::FailOpen)
+    );
+    assert_eq!(
+        health.decide(RedisFeature::ResponseCache),
+        RedisDecision::Degrade(Fallback::Bypass)
+    );
+
+    // Locks fall back to this instance only
+
+    let locks = DistributedLock::new(redis::Client::open(redis_url()).unwrap(), health.clone());
+    let guard = locks.acquire("settlement", Duration::from_secs(5)).await.unwrap();
+    assert!(matches!(guard, Some(LockGuard::Local(_))));
+    drop(guard);
+
+    docker("start");
+    wait_for_redis().await;
+    tokio::time::sleep(RESET).await;
+
+    // After the reset window the next call goes to Redis again and closes the breaker
+    let second = id();
+    callbacks.cache_callback(&second, "{}").await;
+    assert_eq!(callbacks.cached_callback(&second).await.unwrap().as_deref(), Some("{}"));
+    assert!(health.status().available);
+    assert_eq!(health.status().features["idempotency"], "normal");
+    assert_eq!(health.status().features["rate_limiting"], "normal");
+
+    let guard = locks.acquire("settlement", Duration::from_secs(5)).await.unwrap();
+    assert!(matches!(guard, Some(LockGuard::Redis { .. })));
+    guard.unwrap().release().await;
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_required_idempotency_fails_closed_when_redis_stops() {
+    wait_for_redis().await;
+    let required: HashSet<_> = [RedisFeature::Idempotency].into_iter().collect();
+    let health = RedisHealth::new(1, RESET, required);
+
+    docker("stop");
+    let lookup = service(health.clone()).cached_callback(&id()).await;
+    docker("start");
+
+    assert!(matches!(lookup, Err(AppError::Unavailable(_))));
+    assert_eq!(health.status().features["idempotency"], "fail_closed");
+    // Features not marked required still degrade
+    assert_eq!(health.status().features["rate_limiting"], "fail_open");
+    wait_for_redis().await;
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use synapse_core::config::Deployment;
+use synapse_core::db::seed::{self, SeedReport, ACCOUNTS, SANDBOX_ISSUER, TRANSACTIONS};
+use synapse_core::middleware::sandbox::{SANDBOX_BANNER, SANDBOX_HEADER};
+use synapse_core::stellar::sandbox::FakeHorizon;
+use synapse_core::stellar::{HorizonClient, HorizonError};
+use synapse_core::{create_app, AppState};
+use tower::ServiceExt;
+
+fn sandbox_horizon() -> HorizonClient {
+    HorizonClient::sandbox(Arc::new(FakeHorizon::new(Duration::ZERO)))
+}
+
+fn app_state(pool: PgPool, environment: &str) -> AppState {
+    AppState {
+        deployment: Deployment {
+            version: "test".to_string(),
+            environment: environment.to_string(),
+        },
+        ..common::app_state_with_horizon(pool, sandbox_horizon())
+    }
+}
+
+async fn seeded_transactions(pool: &PgPool) -> i64 {
+    let ids: Vec<_> = TRANSACTIONS.iter().map(|seeded| seed::transaction_id(seeded.key)).collect();
+    sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE id = ANY($1)")
+        .bind(&ids)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn get_version(environment: &str) -> (Option<String>, Value) {
+    let pool = common::setup_pool().await;
+    let response = create_app(app_state(pool, environment))
+        .oneshot(Request::builder().uri("/version").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let header = response
+        .headers()
+        .get(SANDBOX_HEADER)
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (header, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_seeding_is_idempotent() {
+    let pool = common::setup_pool().await;
+    seed::run(&pool).await.unwrap();
+
+    // A second run puts assets and flags back and inserts no transaction twice
+    let report = seed::run(&pool).await.unwrap();
+    assert_eq!(
+        report,
+        SeedReport {
+            assets: 2,
+            flags: 5,
+            transactions: 0,
+        }
+    );
+    assert_eq!(seeded_transactions(&pool).await, TRANSACTIONS.len() as i64);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_reset_restores_the_seeded_transactions() {
+    let pool = common::setup_pool().await;
+    seed::run(&pool).await.unwrap();
+    sqlx::query("DELETE FROM transactions WHERE id = $1")
+        .bind(seed::transaction_id("alice-deposit-1"))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let report = seed::reset(&pool).await.unwrap();
+    assert_eq!(report.transactions, TRANSACTIONS.len());
+    assert_eq!(seeded_transactions(&pool).await, TRANSACTIONS.len() as i64);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_seeded_assets_are_trusted_by_sandbox_accounts() {
+    let pool = common::setup_pool().await;
+    seed::run(&pool).await.unwrap();
+    let horizon_client = sandbox_horizon();
+    seed::sync_trustlines(&pool, horizon_client.fake().unwrap()).await.unwrap();
+
+    let account = horizon_client.get_account(ACCOUNTS[0].address).await.unwrap();
+    for code in ["USDC", "EURC"] {
+        assert!(account
+            .balances
+            .iter()
+            .any(|b| b.asset_code.as_deref() == Some(code) && b.asset_issuer.as_deref() == Some(SANDBOX_ISSUER)));
+    }
+}
+
+#[tokio::test]
+async fn test_failure_accounts_through_the_client() {
+    let horizon_client = sandbox_horizon();
+    assert!(matches!(
+        horizon_client.get_account(ACCOUNTS[2].address).await,
+        Err(HorizonError::AccountNotFound(_))
+    ));
+    assert_eq!(horizon_client.get_account(ACCOUNTS[3].address).await.unwrap().balances.len(), 1);
+    assert!(matches!(
+        horizon_client.submit_transaction(ACCOUNTS[4].address, "AAAAenvelope").await,
+        Err(HorizonError::TransactionFailed(code)) if code == "tx_failed"
+    ));
+    assert!(horizon_client.get_account(ACCOUNTS[5].address).await.is_err());
+
+    let submitted = horizon_client.submit_transaction(ACCOUNTS[0].address, "AAAAenvelope").await.unwrap();
+    assert!(submitted.successful);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_version_reports_sandbox_banner() {
+    let (header, body) = get_version("sandbox").await;
+    assert_eq!(header.as_deref(), Some(SANDBOX_BANNER));
+    assert_eq!(body["environment"], "sandbox");
+    assert_eq!(body["sandbox"], true);
+    assert_eq!(body["banner"], SANDBOX_BANNER);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_version_has_no_banner_outside_sandbox() {
+    let (header, body) = get_version("production").await;
+    assert_eq!(header, None);
+    assert_eq!(body["sandbox"], false);
+    assert!(body["banner"].is_null());
+}
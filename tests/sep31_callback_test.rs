@@ -0,0 +1,181 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use serde_json::Value;
+use sqlx::PgPool;
+use synapse_core::domain::TransactionStatus;
+use synapse_core::handlers::sep31::{map_sep31_status, plan_update, Sep31Callback, Sep31Update};
+use synapse_core::create_app;
+use tower::ServiceExt;
+
+/// One transaction's callbacks over a full corridor lifecycle, in order
+const LIFECYCLE: &[&str] = &[
+    "01_pending_sender",
+    "02_pending_stellar",
+    "03_pending_receiver",
+    "04_pending_external",
+    "05_completed",
+];
+
+const ISSUER: &str = "GBBD47IF6LWK7P7MDEVSCWR7DPUWV3NY3DTQEVFL4NAT4AQH3ZLLFLA5";
+const FIXTURE_ID: &str = "82fhs729f63dh0v4";
+
+fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/sep31/{}.json", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("failed to read {}: {}", path, e))
+}
+
+fn parse(name: &str) -> Sep31Callback {
+    serde_json::from_str(&fixture(name)).unwrap()
+}
+
+#[test]
+fn test_lifecycle_fixtures_walk_the_state_machine() {
+    let expected = [
+        TransactionStatus::Pending,
+        TransactionStatus::Pending,
+        TransactionStatus::Processing,
+        TransactionStatus::Processing,
+        TransactionStatus::Completed,
+    ];
+
+    let first = parse(LIFECYCLE[0]).transaction;
+    let mut status = map_sep31_status(&first.status).unwrap();
+    let mut callback_status = first.status;
+    assert_eq!(status, expected[0]);
+
+    for (name, expected) in LIFECYCLE.iter().zip(expected).skip(1) {
+        let incoming = parse(name).transaction;
+        match plan_update(status, Some(&callback_status), &incoming.status) {
+            Sep31Update::Duplicate => panic!("{} treated as a replay", name),
+            Sep31Update::Apply {
+                new_status,
+                review_reason,
+            } => {
+                assert!(review_reason.is_none(), "{} flagged for review", name);
+                status = new_status.unwrap_or(status);
+            }
+        }
+        callback_status = incoming.status;
+        assert_eq!(status, expected, "after {}", name);
+    }
+
+    // Replaying the last callback is a no-op
+    let replay = parse(LIFECYCLE[LIFECYCLE.len() - 1]).transaction;
+    assert_eq!(
+        plan_update(status, Some(&callback_status), &replay.status),
+        Sep31Update::Duplicate
+    );
+}
+
+#[test]
+fn test_unknown_status_fixture_is_flagged() {
+    let incoming = parse("unknown_status").transaction;
+    assert_eq!(map_sep31_status(&incoming.status), None);
+    assert!(matches!(
+        plan_update(TransactionStatus::Pending, Some("pending_stellar"), &incoming.status),
+        Sep31Update::Apply {
+            new_status: None,
+            review_reason: Some(_),
+        }
+    ));
+}
+
+async fn setup_pool() -> PgPool {
+    let pool = common::setup_pool().await;
+    sqlx::query(
+        "INSERT INTO assets (asset_code, asset_issuer) VALUES ('USDC', $1) \
+         ON CONFLICT (asset_code, asset_issuer) DO UPDATE SET enabled = true",
+    )
+    .bind(ISSUER)
+    .execute(&pool)
+    .await
+    .unwrap();
+    pool
+}
+
+
+async fn post_fixture(pool: &PgPool, name: &str, sep31_id: &str) -> (StatusCode, Value) {
+    let body = fixture(name).replace(FIXTURE_ID, sep31_id);
+    let response = create_app(common::app_state(pool.clone()))
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/callback/sep31")
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_full_corridor_lifecycle() {
+    let pool = setup_pool().await;
+    let sep31_id = format!("sep31-{}", uuid::Uuid::new_v4());
+
+    let (status, created) = post_fixture(&pool, LIFECYCLE[0], &sep31_id).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(created["status"], "pending");
+    let transaction_id = created["transaction_id"].clone();
+
+    let expected = ["pending", "processing", "processing", "completed"];
+    for (name, expected) in LIFECYCLE[1..].iter().zip(expected) {
+        let (status, body) = post_fixture(&pool, name, &sep31_id).await;
+        assert_eq!(status, StatusCode::OK, "{}", name);
+        assert_eq!(body["transaction_id"], transaction_id);
+        assert_eq!(body["status"], expected, "after {}", name);
+        assert_eq!(body["needs_review"], false);
+    }
+
+    // Replay of the last callback
+    let (status, body) = post_fixture(&pool, LIFECYCLE[4], &sep31_id).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["transaction_id"], transaction_id);
+
+    let (asset_code, callback_status, needs_review): (String, String, bool) = sqlx::query_as(
+        "SELECT asset_code, callback_status, needs_review FROM transactions \
+         WHERE callback_type = 'sep31' AND anchor_transaction_id = $1",
+    )
+    .bind(&sep31_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(asset_code, "USDC");
+    assert_eq!(callback_status, "completed");
+    assert!(!needs_review);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_unknown_status_is_stored_and_flagged() {
+    let pool = setup_pool().await;
+    let sep31_id = format!("sep31-{}", uuid::Uuid::new_v4());
+
+    post_fixture(&pool, LIFECYCLE[0], &sep31_id).await;
+    let (status, body) = post_fixture(&pool, "unknown_status", &sep31_id).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "pending");
+    assert_eq!(body["callback_status"], "pending_compliance_hold");
+    assert_eq!(body["needs_review"], true);
+
+    let (needs_review, reason): (bool, Option<String>) = sqlx::query_as(
+        "SELECT needs_review, review_reason FROM transactions \
+         WHERE callback_type = 'sep31' AND anchor_transaction_id = $1",
+    )
+    .bind(&sep31_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert!(needs_review);
+    assert!(reason.unwrap().contains("pending_compliance_hold"));
+}
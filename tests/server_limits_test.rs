@@ -0,0 +1,151 @@
+use axum::{routing::get, Router};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use synapse_core::config::ServerLimits;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn start_server(limits: ServerLimits) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/health", get(|| async { "ok" }));
+    tokio::spawn(synapse_core::server::serve(listener, app, limits));
+    addr
+}
+
+/// Read until the server closes the connection; returns everything read
+async fn read_until_closed(stream: &mut TcpStream, within: Duration) -> Option<String> {
+    let mut received = Vec::new();
+    let mut buf = [0u8; 1024];
+    let read = tokio::time::timeout(within, async {
+        loop {
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => received.extend_from_slice(&buf[..n]),
+            }
+        }
+    })
+    .await;
+    read.ok().map(|_| String::from_utf8_lossy(&received).to_string())
+}
+
+const REQUEST: &[u8] = b"GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+#[tokio::test]
+async fn test_silent_connections_are_reaped() {
+    let addr = start_server(ServerLimits {
+        header_read_timeout: Some(Duration::from_millis(500)),
+        idle_timeout: Some(Duration::from_secs(30)),
+        ..Default::default()
+    })
+    .await;
+
+    let mut connections = Vec::new();
+    for _ in 0..5 {
+        connections.push(TcpStream::connect(addr).await.unwrap());
+    }
+
+    let started = Instant::now();
+    for stream in &mut connections {
+        assert!(
+            read_until_closed(stream, Duration::from_secs(3)).await.is_some(),
+            "connection that never sent a request was not closed"
+        );
+    }
+    assert!(started.elapsed() >= Duration::from_millis(400));
+}
+
+#[tokio::test]
+async fn test_idle_keep_alive_connection_is_closed() {
+    let addr = start_server(ServerLimits {
+        idle_timeout: Some(Duration::from_millis(500)),
+        ..Default::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(REQUEST).await.unwrap();
+
+    let received = read_until_closed(&mut stream, Duration::from_secs(3))
+        .await
+        .expect("idle keep-alive connection was not closed");
+    assert!(received.starts_with("HTTP/1.1 200"));
+}
+
+#[tokio::test]
+async fn test_max_requests_per_connection_closes_connection() {
+    let addr = start_server(ServerLimits {
+        max_requests_per_connection: Some(1),
+        ..Default::default()
+    })
+    .await;
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(REQUEST).await.unwrap();
+
+    let received = read_until_closed(&mut stream, Duration::from_secs(3))
+        .await
+        .expect("connection was not closed after its last request");
+    assert!(received.to_ascii_lowercase().contains("connection: close"));
+}
+
+#[tokio::test]
+async fn test_connections_beyond_the_limit_wait_for_a_slot() {
+    let addr = start_server(ServerLimits {
+        header_read_timeout: Some(Duration::from_millis(500)),
+        max_connections: Some(1),
+        ..Default::default()
+    })
+    .await;
+
+    // Holds the only slot until it is reaped
+    let _silent = TcpStream::connect(addr).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let started = Instant::now();
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(REQUEST).await.unwrap();
+    let mut buf = [0u8; 64];
+    let n = tokio::time::timeout(Duration::from_secs(3), stream.read(&mut buf))
+        .await
+        .expect("queued connection was never served")
+        .unwrap();
+    assert!(String::from_utf8_lossy(&buf[..n]).starts_with("HTTP/1.1 200"));
+    assert!(started.elapsed() >= Duration::from_millis(300));
+}
+
+#[tokio::test]
+async fn test_shutdown_closes_kept_alive_connections() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/health", get(|| async { "ok" }));
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let limits = ServerLimits {
+        idle_timeout: Some(Duration::from_secs(60)),
+        ..Default::default()
+    };
+    let server = tokio::spawn(synapse_core::server::serve_with_shutdown(
+        listener,
+        app,
+        limits,
+        async move {
+            let _ = stopped.await;
+        },
+    ));
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(REQUEST).await.unwrap();
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf).await.unwrap();
+    assert!(String::from_utf8_lossy(&buf[..n]).starts_with("HTTP/1.1 200"));
+
+    // Idle and kept alive: only shutdown can close it before the idle timeout
+    let started = Instant::now();
+    stop.send(()).unwrap();
+    assert!(
+        read_until_closed(&mut stream, Duration::from_secs(5)).await.is_some(),
+        "kept-alive connection was not closed on shutdown"
+    );
+    tokio::time::timeout(Duration::from_secs(5), server).await.unwrap().unwrap().unwrap();
+    assert!(started.elapsed() < synapse_core::server::SHUTDOWN_GRACE);
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use synapse_core::db::models::{Transaction, TransactionStatus};
+use synapse_core::db::{queries, uow};
+use synapse_core::error::AppError;
+use synapse_core::services::transaction;
+use uuid::Uuid;
+
+async fn pending(pool: &PgPool) -> Transaction {
+    let tx = Transaction::new(
+        format!("GHISTORY{}", Uuid::new_v4().simple()).to_uppercase(),
+        BigDecimal::from(25),
+        "USDC".to_string(),
+        Some(format!("anchor-{}", Uuid::new_v4())),
+        Some("deposit".to_string()),
+        None,
+    );
+    queries::insert_transaction(pool, &tx).await.unwrap()
+}
+
+async fn move_to(
+    pool: &PgPool,
+    id: Uuid,
+    to: TransactionStatus,
+    reason: Option<&'static str>,
+) -> Result<Transaction, AppError> {
+    uow::run(pool, |uow| Box::pin(async move {
+        transaction::transition(uow.conn(), id, to, reason, "test").await
+    }))
+    .await
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_transitions_are_recorded() {
+    let pool = common::setup_pool().await;
+    let tx = pending(&pool).await;
+
+    let moved = move_to(&pool, tx.id, TransactionStatus::Processing, Some("picked up")).await.unwrap();
+    assert_eq!(moved.status, "processing");
+    move_to(&pool, tx.id, TransactionStatus::Completed, None).await.unwrap();
+    // Staying put records nothing
+    move_to(&pool, tx.id, TransactionStatus::Completed, None).await.unwrap();
+
+    let history = queries::get_status_history(&pool, tx.id).await.unwrap();
+    let moves: Vec<_> = history
+        .iter()
+        .map(|entry| (entry.old_status.as_str(), entry.new_status.as_str(), entry.reason.as_deref()))
+        .collect();
+    assert_eq!(
+        moves,
+        vec![("pending", "processing", Some("picked up")), ("processing", "completed", None)]
+    );
+    assert!(history.iter().all(|entry| entry.actor == "test"));
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_invalid_transition_is_rejected_and_not_recorded() {
+    let pool = common::setup_pool().await;
+    let tx = pending(&pool).await;
+    move_to(&pool, tx.id, TransactionStatus::Failed, Some("rejected")).await.unwrap();
+
+    let error = move_to(&pool, tx.id, TransactionStatus::Completed, None).await.unwrap_err();
+    assert!(matches!(
+        error,
+        AppError::InvalidTransition {
+            from: TransactionStatus::Failed,
+            to: TransactionStatus::Completed,
+            ..
+        }
+    ));
+    assert_eq!(queries::get_transaction(&pool, tx.id).await.unwrap().status, "failed");
+    assert_eq!(queries::get_status_history(&pool, tx.id).await.unwrap().len(), 1);
+
+    // A failed transaction may still be refunded
+    let refunded = move_to(&pool, tx.id, TransactionStatus::Refunded, Some("returned")).await.unwrap();
+    assert_eq!(refunded.status, "refunded");
+    assert_eq!(queries::get_status_history(&pool, tx.id).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_unknown_transaction_is_not_found() {
+    let pool = common::setup_pool().await;
+    let error = move_to(&pool, Uuid::new_v4(), TransactionStatus::Completed, None).await.unwrap_err();
+    assert!(matches!(error, AppError::NotFound(_)));
+}
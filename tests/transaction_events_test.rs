@@ -0,0 +1,123 @@
+mod common;
+
+use chrono::Utc;
+use serde_json::json;
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use synapse_core::db::models::{OutboxEvent, Transaction, TransactionStatus};
+use synapse_core::db::queries::{self, EVENT_TRANSACTION_STATUS_CHANGED};
+use synapse_core::db::uow;
+use synapse_core::error::AppError;
+use synapse_core::services::transaction;
+use uuid::Uuid;
+
+async fn pending(pool: &PgPool) -> Transaction {
+    let tx = Transaction::new(
+        format!("GEVENTS{}", Uuid::new_v4().simple()).to_uppercase(),
+        BigDecimal::from(25),
+        "USDC".to_string(),
+        Some(format!("anchor-{}", Uuid::new_v4())),
+        Some("deposit".to_string()),
+        None,
+    );
+    queries::insert_transaction(pool, &tx).await.unwrap()
+}
+
+async fn status_events(pool: &PgPool, id: Uuid) -> Vec<OutboxEvent> {
+    sqlx::query_as::<_, OutboxEvent>(
+        "SELECT * FROM outbox_events WHERE event_type = $1 AND payload->>'transaction_id' = $2 ORDER BY seq",
+    )
+    .bind(EVENT_TRANSACTION_STATUS_CHANGED)
+    .bind(id.to_string())
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_status_change_publishes_event_with_the_change() {
+    let pool = common::setup_pool().await;
+    let tx = pending(&pool).await;
+
+    let id = tx.id;
+    uow::run(&pool, |uow| Box::pin(async move {
+        transaction::transition(uow.conn(), id, TransactionStatus::Processing, Some("picked up"), "test")
+            .await
+    }))
+    .await
+    .unwrap();
+
+    let events = status_events(&pool, tx.id).await;
+    assert_eq!(events.len(), 1);
+    let payload = &events[0].payload;
+    assert_eq!(payload["status"], "processing");
+    assert_eq!(payload["previous_status"], "pending");
+    assert_eq!(payload["reason"], "picked up");
+    assert_eq!(events[0].asset_code.as_deref(), Some("USDC"));
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_rolled_back_status_change_publishes_nothing() {
+    let pool = common::setup_pool().await;
+    let tx = pending(&pool).await;
+
+    let id = tx.id;
+    let result: Result<(), AppError> = uow::run(&pool, |uow| Box::pin(async move {
+        transaction::transition(uow.conn(), id, TransactionStatus::Processing, None, "test").await?;
+        Err(AppError::Internal("later step failed".to_string()))
+    }))
+    .await;
+    assert!(result.is_err());
+
+    assert!(status_events(&pool, tx.id).await.is_empty());
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_exhausted_delivery_is_dead_lettered_and_can_be_redelivered() {
+    let pool = common::setup_pool().await;
+    let event_type = format!("test.dead_letter.{}", Uuid::new_v4().simple());
+    let subscription = queries::insert_webhook_subscription(
+        &pool,
+        "http://localhost:9/unreachable",
+        &[event_type.clone()],
+        None,
+        None,
+    )
+    .await
+    .unwrap();
+    assert!(subscription.secret.starts_with("whsec_"));
+    queries::enqueue_webhook_deliveries(&pool, &event_type, &json!({"n": 1}), None)
+        .await
+        .unwrap();
+    let delivery = queries::claim_webhook_deliveries(&pool, subscription.id, 10, Utc::now())
+        .await
+        .unwrap()
+        .remove(0);
+
+    // One failed attempt of two leaves it queued; the second dead-letters it
+    assert!(!queries::mark_webhook_delivery_failed(&pool, delivery.id, "HTTP 500", Utc::now(), 2)
+        .await
+        .unwrap());
+    assert!(queries::mark_webhook_delivery_failed(&pool, delivery.id, "HTTP 500", Utc::now(), 2)
+        .await
+        .unwrap());
+
+    let dead = queries::list_dead_letter_webhook_deliveries(&pool, Some(subscription.id), 10)
+        .await
+        .unwrap();
+    assert_eq!(dead.len(), 1);
+    assert_eq!(dead[0].attempts, 2);
+    assert_eq!(dead[0].last_error.as_deref(), Some("HTTP 500"));
+
+    let requeued = queries::redeliver_webhook_delivery(&pool, delivery.id).await.unwrap().unwrap();
+    assert_eq!((requeued.status.as_str(), requeued.attempts), ("pending", 0));
+    // Only dead letters can be requeued
+    assert!(queries::redeliver_webhook_delivery(&pool, delivery.id).await.unwrap().is_none());
+    assert!(queries::list_dead_letter_webhook_deliveries(&pool, Some(subscription.id), 10)
+        .await
+        .unwrap()
+        .is_empty());
+}
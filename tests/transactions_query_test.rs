@@ -0,0 +1,237 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::get,
+    Router,
+};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::str::FromStr;
+use synapse_core::db::models::Transaction;
+use synapse_core::db::queries;
+use synapse_core::handlers::transactions;
+use synapse_core::AppState;
+use tower::ServiceExt;
+
+/// The transaction routes as main.rs wires them, without authentication
+fn app(state: AppState) -> Router {
+    Router::new()
+        .route("/transactions", get(transactions::list_transactions))
+        .route("/transactions/:id", get(transactions::get_transaction))
+        .with_state(state)
+}
+
+async fn get_json(pool: &PgPool, uri: &str) -> (StatusCode, Value) {
+    let response = app(common::app_state(pool.clone()))
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+/// Keeps each test's rows apart from everything else in the database
+fn unique_asset() -> String {
+    format!("Q{}", &uuid::Uuid::new_v4().simple().to_string()[..6]).to_uppercase()
+}
+
+async fn insert(
+    pool: &PgPool,
+    asset_code: &str,
+    account: &str,
+    status: &str,
+    created_at: &str,
+) -> Transaction {
+    let mut tx = Transaction::new(
+        account.to_string(),
+        BigDecimal::from_str("12.30").unwrap(),
+        asset_code.to_string(),
+        None,
+        None,
+        None,
+    );
+    tx.status = status.to_string();
+    tx.created_at = DateTime::parse_from_rfc3339(created_at).unwrap().with_timezone(&Utc);
+    queries::insert_transaction(pool, &tx).await.unwrap()
+}
+
+fn ids(page: &Value) -> Vec<String> {
+    page["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| row["id"].as_str().unwrap().to_string())
+        .collect()
+}
+
+const ACCOUNT_A: &str = "GCAIJAF37NNZGSH6PO442CPK5CEHJNOXAYNHLDJBTQJKF7NIQ6CZ7DQW";
+const ACCOUNT_B: &str = "GDMZCO6NOSVD2J2XSPCAHKJAGFNYN3MMKI674WNSSSBPTQRPXNUAEB46";
+
+/// Seven rows over four days, two of them created at the same instant
+async fn seed(pool: &PgPool, asset: &str) -> Vec<Transaction> {
+    let mut rows = Vec::new();
+    for (account, status, created_at) in [
+        (ACCOUNT_A, "completed", "2026-01-01T08:00:00Z"),
+        (ACCOUNT_B, "pending", "2026-01-01T20:00:00Z"),
+        (ACCOUNT_A, "pending", "2026-01-02T09:30:00Z"),
+        (ACCOUNT_A, "completed", "2026-01-02T09:30:00Z"),
+        (ACCOUNT_B, "failed", "2026-01-03T23:59:59.999999Z"),
+        (ACCOUNT_A, "completed", "2026-01-04T00:00:00Z"),
+        (ACCOUNT_B, "completed", "2026-01-04T12:00:00Z"),
+    ] {
+        rows.push(insert(pool, asset, account, status, created_at).await);
+    }
+    rows
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_cursor_walk_visits_every_row_once() {
+    let pool = common::setup_pool().await;
+    let asset = unique_asset();
+    let rows = seed(&pool, &asset).await;
+
+    let mut seen = Vec::new();
+    let mut pages = 0;
+    let mut uri = format!("/transactions?asset_code={}&limit=2", asset);
+    loop {
+        let (status, page) = get_json(&pool, &uri).await;
+        assert_eq!(status, StatusCode::OK, "{}", page);
+        assert!(page["data"].as_array().unwrap().len() <= 2);
+        seen.extend(ids(&page));
+        pages += 1;
+        if page["meta"]["has_more"] != true {
+            break;
+        }
+        let cursor = page["meta"]["next_cursor"].as_str().unwrap();
+        uri = format!("/transactions?asset_code={}&limit=2&cursor={}", asset, cursor);
+    }
+    assert_eq!(pages, 4);
+
+    let unique: HashSet<&String> = seen.iter().collect();
+    assert_eq!(unique.len(), seen.len(), "a row was visited twice: {:?}", seen);
+
+    // Newest first, ties broken by id
+    let mut expected: Vec<&Transaction> = rows.iter().collect();
+    expected.sort_by(|a, b| (b.created_at, b.id).cmp(&(a.created_at, a.id)));
+    let expected: Vec<String> = expected.iter().map(|tx| tx.id.to_string()).collect();
+    assert_eq!(seen, expected);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_backward_pages_return_newer_rows() {
+    let pool = common::setup_pool().await;
+    let asset = unique_asset();
+    let rows = seed(&pool, &asset).await;
+    let mut newest_first: Vec<&Transaction> = rows.iter().collect();
+    newest_first.sort_by(|a, b| (b.created_at, b.id).cmp(&(a.created_at, a.id)));
+    let expected = |txs: &[&Transaction]| -> Vec<String> {
+        txs.iter().map(|tx| tx.id.to_string()).collect()
+    };
+
+    // Start from the oldest row and walk towards the newest
+    let oldest = newest_first.last().unwrap();
+    let cursor = synapse_core::utils::cursor::encode(oldest.created_at, oldest.id);
+    let uri = format!(
+        "/transactions?asset_code={}&limit=2&direction=backward&cursor={}",
+        asset, cursor
+    );
+    let (status, page) = get_json(&pool, &uri).await;
+    assert_eq!(status, StatusCode::OK, "{}", page);
+    // The two rows after the cursor, still newest first
+    assert_eq!(ids(&page), expected(&newest_first[4..6]));
+    assert_eq!(page["meta"]["has_more"], true);
+
+    let cursor = page["meta"]["next_cursor"].as_str().unwrap();
+    let uri = format!(
+        "/transactions?asset_code={}&limit=2&direction=backward&cursor={}",
+        asset, cursor
+    );
+    let (_, page) = get_json(&pool, &uri).await;
+    assert_eq!(ids(&page), expected(&newest_first[2..4]));
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_filter_combinations() {
+    let pool = common::setup_pool().await;
+    let asset = unique_asset();
+    let rows = seed(&pool, &asset).await;
+    let expect = |indexes: &[usize]| -> HashSet<String> {
+        indexes.iter().map(|i| rows[*i].id.to_string()).collect()
+    };
+    let query = |filters: &str| format!("/transactions?asset_code={}&{}", asset, filters);
+
+    let (_, page) = get_json(&pool, &query("status=completed")).await;
+    assert_eq!(ids(&page).into_iter().collect::<HashSet<_>>(), expect(&[0, 3, 5, 6]));
+
+    let filters = format!("status=completed&stellar_account={}", ACCOUNT_A);
+    let (_, page) = get_json(&pool, &query(&filters)).await;
+    assert_eq!(ids(&page).into_iter().collect::<HashSet<_>>(), expect(&[0, 3, 5]));
+
+    // A bare `to` date includes that whole day
+    let (_, page) = get_json(&pool, &query("from=2026-01-02&to=2026-01-03")).await;
+    assert_eq!(ids(&page).into_iter().collect::<HashSet<_>>(), expect(&[2, 3, 4]));
+
+    // A timestamp `to` is exclusive
+    let (_, page) = get_json(
+        &pool,
+        &query(&format!(
+            "from=2026-01-01T20:00:00Z&to=2026-01-04T00:00:00Z&stellar_account={}",
+            ACCOUNT_B
+        )),
+    )
+    .await;
+    assert_eq!(ids(&page).into_iter().collect::<HashSet<_>>(), expect(&[1, 4]));
+    assert_eq!(page["meta"]["filters"]["stellar_account"], ACCOUNT_B);
+    assert_eq!(page["meta"]["filters"]["asset_code"], asset.as_str());
+    assert_eq!(page["meta"]["filters"]["from"], "2026-01-01T20:00:00Z");
+    assert_eq!(page["meta"]["filters"]["to"], "2026-01-04T00:00:00Z");
+    assert_eq!(page["meta"]["filters"]["limit"], 50);
+    assert_eq!(page["meta"]["has_more"], false);
+
+    // Amounts keep their scale as strings
+    assert_eq!(page["data"][0]["amount"], "12.30");
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_invalid_queries_are_rejected() {
+    let pool = common::setup_pool().await;
+
+    for (uri, field) in [
+        ("/transactions?limit=0", "limit:"),
+        ("/transactions?limit=201", "limit:"),
+        ("/transactions?cursor=not-a-cursor", "cursor:"),
+        ("/transactions?cursor=7a7a", "cursor:"),
+        ("/transactions?status=pending_anchor", "status:"),
+        ("/transactions?from=2026-02-01&to=2026-01-01", "from:"),
+        ("/transactions?direction=sideways", "direction:"),
+    ] {
+        let (status, body) = get_json(&pool, uri).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+        assert!(body["error"].as_str().unwrap().contains(field), "{}: {}", uri, body);
+    }
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_get_transaction_by_id() {
+    let pool = common::setup_pool().await;
+    let tx = insert(&pool, &unique_asset(), ACCOUNT_A, "pending", "2026-01-05T10:00:00Z").await;
+
+    let (status, body) = get_json(&pool, &format!("/transactions/{}", tx.id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["id"], tx.id.to_string());
+    assert_eq!(body["amount"], "12.30");
+
+    let (status, _) = get_json(&pool, &format!("/transactions/{}", uuid::Uuid::new_v4())).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
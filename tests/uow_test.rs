@@ -0,0 +1,104 @@
+mod common;
+
+use sqlx::types::BigDecimal;
+use sqlx::PgPool;
+use synapse_core::db::audit::{AuditLog, ENTITY_TRANSACTION};
+use synapse_core::db::models::Transaction;
+use synapse_core::db::{queries, uow};
+use synapse_core::handlers::webhook::{persist_callback_transaction, RawCallback};
+use uuid::Uuid;
+
+fn new_transaction() -> Transaction {
+    Transaction::new(
+        format!("G{}", "A".repeat(55)),
+        BigDecimal::from(100),
+        "USD".to_string(),
+        Some(format!("anchor-uow-{}", Uuid::new_v4())),
+        Some("deposit".to_string()),
+        None,
+    )
+}
+
+async fn audit_entries(pool: &PgPool, id: Uuid) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM audit_logs WHERE entity_id = $1")
+        .bind(id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_failure_after_first_statement_persists_nothing() {
+    let pool = common::setup_pool().await;
+    let tx = new_transaction();
+    let id = tx.id;
+
+    let result: Result<(), sqlx::Error> = uow::run(&pool, |uow| Box::pin(async move {
+        queries::insert_transaction(uow.conn(), &tx).await?;
+        // Forced failure after the insert
+        sqlx::query("SELECT 1 / 0").execute(uow.conn()).await?;
+        Ok(())
+    }))
+    .await;
+
+    assert!(result.is_err());
+    assert!(matches!(
+        queries::get_transaction(&pool, id).await,
+        Err(sqlx::Error::RowNotFound)
+    ));
+    assert_eq!(audit_entries(&pool, id).await, 0);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_failed_savepoint_keeps_main_insert() {
+    let pool = common::setup_pool().await;
+    let tx = new_transaction();
+    let id = tx.id;
+
+    uow::run(&pool, |uow| Box::pin(async move {
+        let inserted = queries::insert_transaction(uow.conn(), &tx).await?;
+
+        let optional: Result<(), sqlx::Error> = uow
+            .savepoint(|sp| Box::pin(async move {
+                sqlx::query("SELECT 1 / 0").execute(sp.conn()).await?;
+                Ok(())
+            }))
+            .await;
+        assert!(optional.is_err());
+
+        // The unit of work is still usable after the savepoint rolled back
+        AuditLog::log_creation(uow.conn(), inserted.id, ENTITY_TRANSACTION, serde_json::json!({}), "test")
+            .await?;
+        Ok::<_, sqlx::Error>(())
+    }))
+    .await
+    .unwrap();
+
+    assert!(queries::get_transaction(&pool, id).await.is_ok());
+    assert_eq!(audit_entries(&pool, id).await, 1);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_callback_persists_transaction_audit_and_raw_body_together() {
+    let pool = common::setup_pool().await;
+    let tx = new_transaction();
+    let id = tx.id;
+
+    let raw = RawCallback {
+        schema_version: "1",
+        body: r#"{"id":"anchor-1"}"#,
+    };
+    persist_callback_transaction(&pool, tx, Some(raw)).await.unwrap();
+
+    assert!(queries::get_transaction(&pool, id).await.is_ok());
+    assert_eq!(audit_entries(&pool, id).await, 1);
+    let raw_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM raw_callbacks WHERE transaction_id = $1")
+        .bind(id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(raw_count, 1);
+}
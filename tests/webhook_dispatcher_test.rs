@@ -0,0 +1,202 @@
+mod common;
+
+use chrono::Utc;
+use serde_json::json;
+use std::time::{Duration, Instant};
+use synapse_core::config::WebhookDispatchConfig;
+use synapse_core::db::models::WebhookDelivery;
+use synapse_core::db::queries;
+use synapse_core::services::webhook_dispatcher::{
+    send_delivery, sign_delivery, DeliveryLimiter, SIGNATURE_HEADER,
+};
+use synapse_core::services::WebhookDispatcher;
+use synapse_core::utils::shutdown::Shutdown;
+use uuid::Uuid;
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn delivery(subscription_id: Uuid) -> WebhookDelivery {
+    WebhookDelivery {
+        id: Uuid::new_v4(),
+        subscription_id,
+        event_type: "transaction.completed".to_string(),
+        payload: json!({"transaction_id": Uuid::new_v4()}),
+        status: "delivering".to_string(),
+        attempts: 0,
+        next_attempt_at: Utc::now(),
+        locked_until: None,
+        last_error: None,
+        created_at: Utc::now(),
+        delivered_at: None,
+        correlation_id: None,
+    }
+}
+
+fn header<'a>(request: &'a wiremock::Request, name: &str) -> Option<&'a str> {
+    request
+        .headers
+        .iter()
+        .find(|(header, _)| header.as_str().eq_ignore_ascii_case(name))
+        .map(|(_, values)| values.last().as_str())
+}
+
+async fn hanging_server(delay: Duration) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(delay))
+        .mount(&server)
+        .await;
+    server
+}
+
+#[tokio::test]
+async fn test_hanging_subscriber_does_not_affect_other_subscriptions() {
+    let hanging = hanging_server(Duration::from_secs(30)).await;
+    let healthy = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&healthy)
+        .await;
+
+    let limiter = DeliveryLimiter::new(2, 20, 0.5);
+    let http = reqwest::Client::new();
+    let slow_sub = Uuid::new_v4();
+    let fast_sub = Uuid::new_v4();
+
+    // Fill the hanging subscription's in-flight slots
+    for _ in 0..2 {
+        let permit = limiter.try_acquire(slow_sub, None).unwrap();
+        let http = http.clone();
+        let url = hanging.uri();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let _ = send_delivery(&http, &url, &delivery(slow_sub), None, Duration::from_secs(60)).await;
+        });
+    }
+
+    // Saturated: no more of its deliveries can be started
+    assert!(limiter.try_acquire(slow_sub, None).is_none());
+    assert_eq!(limiter.available(slow_sub, None), 0);
+    assert_eq!(limiter.in_flight(slow_sub), 2);
+
+    // The healthy subscription keeps delivering at full speed
+    let started = Instant::now();
+    for _ in 0..5 {
+        let _permit = limiter
+            .try_acquire(fast_sub, None)
+            .expect("healthy subscription must not be saturated");
+        send_delivery(&http, &healthy.uri(), &delivery(fast_sub), None, Duration::from_secs(5))
+            .await
+            .unwrap();
+    }
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(healthy.received_requests().await.unwrap().len(), 5);
+    assert_eq!(limiter.in_flight(slow_sub), 2);
+}
+
+#[tokio::test]
+async fn test_hanging_delivery_times_out_and_frees_its_slot() {
+    let hanging = hanging_server(Duration::from_secs(10)).await;
+    let limiter = DeliveryLimiter::new(1, 20, 0.5);
+    let http = reqwest::Client::new();
+    let sub = Uuid::new_v4();
+
+    let permit = limiter.try_acquire(sub, None).unwrap();
+    let result = send_delivery(&http, &hanging.uri(), &delivery(sub), None, Duration::from_millis(200)).await;
+    assert!(result.is_err());
+    drop(permit);
+
+    assert_eq!(limiter.in_flight(sub), 0);
+    assert!(limiter.try_acquire(sub, None).is_some());
+}
+
+#[tokio::test]
+async fn test_failing_subscriber_trips_failure_rate() {
+    let failing = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&failing)
+        .await;
+
+    let limiter = DeliveryLimiter::new(4, 10, 0.5);
+    let http = reqwest::Client::new();
+    let sub = Uuid::new_v4();
+
+    let mut tripped = false;
+    for _ in 0..5 {
+        let _permit = limiter.try_acquire(sub, None).unwrap();
+        let result = send_delivery(&http, &failing.uri(), &delivery(sub), None, Duration::from_secs(5)).await;
+        tripped = limiter.record_outcome(sub, result.is_ok());
+    }
+    assert!(tripped);
+}
+
+#[tokio::test]
+async fn test_delivery_is_signed_over_the_exact_body() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+    let http = reqwest::Client::new();
+    let delivery = delivery(Uuid::new_v4());
+
+    send_delivery(&http, &server.uri(), &delivery, Some("whsec_test"), Duration::from_secs(5))
+        .await
+        .unwrap();
+    send_delivery(&http, &server.uri(), &delivery, None, Duration::from_secs(5))
+        .await
+        .unwrap();
+
+    let received = server.received_requests().await.unwrap();
+    let signature = header(&received[0], SIGNATURE_HEADER).unwrap();
+    let timestamp: i64 = signature
+        .strip_prefix("t=")
+        .and_then(|rest| rest.split(',').next())
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((Utc::now().timestamp() - timestamp).abs() < 60);
+    assert_eq!(signature, sign_delivery("whsec_test", timestamp, &received[0].body));
+    assert_eq!(header(&received[1], SIGNATURE_HEADER), None);
+}
+
+#[tokio::test]
+#[ignore] // Requires a running Postgres instance
+async fn test_shutdown_releases_a_delivery_mid_send() {
+    let pool = common::setup_pool().await;
+    let hanging = hanging_server(Duration::from_secs(30)).await;
+    let event_type = format!("test.release.{}", Uuid::new_v4());
+    let event_types = [event_type.clone()];
+    let subscription =
+        queries::insert_webhook_subscription(&pool, &hanging.uri(), &event_types, None, None)
+            .await
+            .unwrap();
+    queries::enqueue_webhook_deliveries(&pool, &event_type, &json!({}), None)
+        .await
+        .unwrap();
+
+    let shutdown = Shutdown::new();
+    let dispatcher = WebhookDispatcher::new(pool.clone(), WebhookDispatchConfig::default())
+        .with_shutdown(shutdown.clone());
+    assert!(dispatcher.poll_once().await.unwrap() >= 1);
+    while hanging.received_requests().await.unwrap().is_empty() {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    shutdown.cancel();
+    assert!(shutdown.wait(Duration::from_secs(5)).await);
+
+    let (status, attempts, locked_until): (String, i32, Option<chrono::DateTime<Utc>>) =
+        sqlx::query_as(
+            "SELECT status, attempts, locked_until FROM webhook_deliveries \
+             WHERE subscription_id = $1",
+        )
+        .bind(subscription.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "pending");
+    assert_eq!(attempts, 0);
+    assert_eq!(locked_until, None);
+}